@@ -11,7 +11,7 @@ use std::{
 
 use libfuzzer_sys::fuzz_target;
 use ntp_proto::{
-    test_cookie, v5::BloomFilter, EncryptResult, ExtensionField, ExtensionHeaderVersion,
+    test_cookie, v5::BloomFilter, ClockState, EncryptResult, ExtensionField, ExtensionHeaderVersion,
     FilterAction, FilterList, HandleInnerData, KeySetProvider, NtpClock, NtpDuration,
     NtpLeapIndicator, NtpServerInfo, NtpSnapshot, NtpTimestamp, NtpVersion, ReferenceId, Server,
     ServerConfig, ServerReason, ServerResponse, ServerStatHandler, TimeSnapshot,
@@ -98,6 +98,7 @@ fuzz_target!(|parts: (
             allowlist,
             rate_limiting_cache_size: 0,
             rate_limiting_cutoff: Duration::from_secs(1),
+            nts_rate_limiting_cutoff: None,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V3, NtpVersion::V4, NtpVersion::V5],
         },
@@ -121,6 +122,7 @@ fuzz_target!(|parts: (
                 leap_indicator: NtpLeapIndicator::NoWarning,
                 accumulated_steps: NtpDuration::from_seconds(0.0),
                 accumulated_steps_threshold: None,
+                clock_state: ClockState::Synchronized,
             },
         })),
         keyset,
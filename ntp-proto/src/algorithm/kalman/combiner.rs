@@ -36,33 +36,51 @@ fn vote_leap(selection: &[SourceSnapshot]) -> Option<NtpLeapIndicator> {
     }
 }
 
+fn weighted_estimate(snapshot: &SourceSnapshot, algo_config: &AlgorithmConfig) -> KalmanState {
+    let mut estimate = if algo_config.ignore_server_dispersion {
+        snapshot.state
+    } else {
+        snapshot
+            .state
+            .add_server_dispersion(snapshot.source_uncertainty.to_seconds())
+    };
+
+    // Giving a source more weight is equivalent to trusting its estimate more,
+    // so scale down its uncertainty accordingly.
+    if snapshot.refclock {
+        estimate.uncertainty = (1.0 / algo_config.refclock_weight) * estimate.uncertainty;
+    }
+
+    estimate
+}
+
 pub(super) fn combine(
     selection: &[SourceSnapshot],
     algo_config: &AlgorithmConfig,
 ) -> Option<Combine> {
     selection.first().map(|first| {
-        let mut estimate = first.state;
-        if !algo_config.ignore_server_dispersion {
-            estimate = estimate.add_server_dispersion(first.source_uncertainty.to_seconds());
-        }
+        let mut estimate = weighted_estimate(first, algo_config);
 
-        let mut used_sources = vec![(first.index, estimate.uncertainty.determinant())];
+        let mut used_sources = vec![(
+            first.index,
+            first.prefer,
+            estimate.uncertainty.determinant(),
+        )];
 
         for snapshot in selection.iter().skip(1) {
-            let source_estimate = if algo_config.ignore_server_dispersion {
-                snapshot.state
-            } else {
-                snapshot
-                    .state
-                    .add_server_dispersion(snapshot.source_uncertainty.to_seconds())
-            };
+            let source_estimate = weighted_estimate(snapshot, algo_config);
 
-            used_sources.push((snapshot.index, source_estimate.uncertainty.determinant()));
+            used_sources.push((
+                snapshot.index,
+                snapshot.prefer,
+                source_estimate.uncertainty.determinant(),
+            ));
 
             estimate = estimate.merge(&source_estimate);
         }
 
-        used_sources.sort_by(|a, b| a.1.total_cmp(&b.1));
+        // Preferred sources go first, so that they become the primary source
+        used_sources.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.total_cmp(&b.2)));
 
         Combine {
             estimate,
@@ -108,6 +126,12 @@ mod tests {
             source_delay: NtpDuration::from_seconds(0.01),
             leap_indicator: NtpLeapIndicator::NoWarning,
             last_update: NtpTimestamp::from_fixed_int(0),
+            may_discipline: true,
+            monitor_only: false,
+            warming_up: false,
+            prefer: false,
+            refclock: false,
+            group: None,
         }
     }
 
@@ -220,6 +244,68 @@ mod tests {
         assert_eq!(result.sources, vec![ClockId(1), ClockId(0)]);
     }
 
+    #[test]
+    fn test_prefer() {
+        let mut selected = vec![
+            snapshot_for_state(
+                Vector::new_vector([0.0, 0.0]),
+                Matrix::new([[1e-6, 0.0], [0.0, 1e-12]]),
+                1e-3,
+            ),
+            snapshot_for_state(
+                Vector::new_vector([0.0, 0.0]),
+                Matrix::new([[1e-6, 0.0], [0.0, 1e-12]]),
+                1e-3,
+            ),
+        ];
+        selected[0].index = ClockId(0);
+        selected[1].index = ClockId(1);
+        selected[1].prefer = true;
+
+        let algconfig = AlgorithmConfig::default();
+        let result = combine(&selected, &algconfig).unwrap();
+        assert_eq!(result.sources, vec![ClockId(1), ClockId(0)]);
+
+        selected[0].prefer = true;
+        selected[1].prefer = false;
+        let result = combine(&selected, &algconfig).unwrap();
+        assert_eq!(result.sources, vec![ClockId(0), ClockId(1)]);
+    }
+
+    #[test]
+    fn test_refclock_weight() {
+        let mut selected = vec![
+            snapshot_for_state(
+                Vector::new_vector([0.0, 0.0]),
+                Matrix::new([[1e-6, 0.0], [0.0, 1e-12]]),
+                0.0,
+            ),
+            snapshot_for_state(
+                Vector::new_vector([1e-3, 0.0]),
+                Matrix::new([[1e-6, 0.0], [0.0, 1e-12]]),
+                0.0,
+            ),
+        ];
+        selected[0].refclock = true;
+
+        // Without extra weight, both sources count equally
+        let algconfig = AlgorithmConfig::default();
+        let result = combine(&selected, &algconfig).unwrap();
+        assert!((result.estimate.offset() - 5e-4).abs() < 1e-8);
+
+        let algconfig = AlgorithmConfig {
+            refclock_weight: 9.0,
+            ..Default::default()
+        };
+        let result = combine(&selected, &algconfig).unwrap();
+        assert!((result.estimate.offset() - 1e-4).abs() < 1e-8);
+
+        // The weight applies regardless of the order of the selection
+        selected.reverse();
+        let result = combine(&selected, &algconfig).unwrap();
+        assert!((result.estimate.offset() - 1e-4).abs() < 1e-8);
+    }
+
     fn snapshot_for_leap(leap: NtpLeapIndicator) -> SourceSnapshot {
         SourceSnapshot {
             index: ClockId(0),
@@ -235,6 +321,12 @@ mod tests {
             source_delay: NtpDuration::from_seconds(0.0),
             leap_indicator: leap,
             last_update: NtpTimestamp::from_fixed_int(0),
+            may_discipline: true,
+            monitor_only: false,
+            warming_up: false,
+            prefer: false,
+            refclock: false,
+            group: None,
         }
     }
 
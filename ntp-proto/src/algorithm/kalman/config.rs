@@ -1,8 +1,8 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::time_types::NtpDuration;
 
-#[derive(Debug, Copy, Clone, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct AlgorithmConfig {
     /// Probability bound below which we start moving towards decreasing
@@ -108,6 +108,11 @@ pub struct AlgorithmConfig {
     /// Threshold for detecting external clock meddling
     #[serde(default = "default_meddling_threshold")]
     pub meddling_threshold: NtpDuration,
+
+    /// Weight of reference clocks (sock and pps sources) relative to network
+    /// sources when combining the selected sources. Unit: dimensionless, >0
+    #[serde(default = "default_refclock_weight")]
+    pub refclock_weight: f64,
 }
 
 impl Default for AlgorithmConfig {
@@ -145,6 +150,8 @@ impl Default for AlgorithmConfig {
             ignore_server_dispersion: false,
 
             meddling_threshold: default_meddling_threshold(),
+
+            refclock_weight: default_refclock_weight(),
         }
     }
 }
@@ -240,3 +247,7 @@ fn default_slew_minimum_duration() -> f64 {
 fn default_meddling_threshold() -> NtpDuration {
     NtpDuration::from_seconds(5.)
 }
+
+fn default_refclock_weight() -> f64 {
+    1.0
+}
@@ -7,16 +7,18 @@ use tracing::{debug, error, info, warn};
 use crate::{
     ClockId,
     algorithm::kalman::source::FixedMeasurementNoise,
-    clock::NtpClock,
-    config::{SourceConfig, SynchronizationConfig},
+    clock::{ClockAdjustment, NtpClock},
+    config::{SourceConfig, SourceGroup, SynchronizationConfig},
     packet::NtpLeapIndicator,
-    system::TimeSnapshot,
+    system::{ClockState, TimeSnapshot},
     time_types::{NtpDuration, NtpTimestamp},
 };
 
 use self::{combiner::combine, config::AlgorithmConfig, source::KalmanState};
 
-use super::{InternalStateUpdate, InternalTimeSyncController, ObservableSourceTimedata};
+use super::{
+    ForceSyncOutcome, InternalStateUpdate, InternalTimeSyncController, ObservableSourceTimedata,
+};
 
 mod combiner;
 pub(super) mod config;
@@ -47,6 +49,30 @@ struct SourceSnapshot {
     leap_indicator: NtpLeapIndicator,
 
     last_update: NtpTimestamp,
+
+    // Whether the source is stable enough to be used for steering
+    // the clock. Sources for which this is false still take part in
+    // selection.
+    may_discipline: bool,
+
+    // Whether the source is only monitored. Such sources are excluded
+    // from selection altogether.
+    monitor_only: bool,
+
+    // Whether the source has not yet made enough measurements since it was
+    // added. Like monitor only sources, it is excluded from selection.
+    warming_up: bool,
+
+    // Whether the source is preferred by the operator. Preferred sources
+    // that survive selection become the primary source.
+    prefer: bool,
+
+    // Whether the source is a local reference clock, which is weighted
+    // differently from network sources when combining.
+    refclock: bool,
+
+    // Group the source belongs to, used to check the per-group quorum.
+    group: Option<SourceGroup>,
 }
 
 impl SourceSnapshot {
@@ -81,6 +107,25 @@ enum KalmanControllerMessageInner {
     FreqChange { steer: f64, time: NtpTimestamp },
 }
 
+/// A leap second that is being smeared out by running the clock at a
+/// slightly different frequency until the leap takes effect.
+#[derive(Debug, Clone, Copy)]
+struct LeapSmear {
+    freq: f64,
+    start: NtpTimestamp,
+    end: NtpTimestamp,
+}
+
+impl LeapSmear {
+    /// Amount in seconds by which the smear has made the clock lag behind
+    /// its sources at `time`.
+    fn offset(&self, time: NtpTimestamp) -> f64 {
+        let elapsed = (time - self.start).to_seconds();
+        let duration = (self.end - self.start).to_seconds();
+        self.freq * elapsed.clamp(0.0, duration)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct KalmanSourceMessage {
     inner: SourceSnapshot,
@@ -96,6 +141,26 @@ pub struct KalmanClockController<C: NtpClock> {
     timedata: TimeSnapshot,
     desired_freq: f64,
     in_startup: bool,
+    // Offset corrections are held after exceeding the panic threshold, see
+    // `panic_threshold_recovery`
+    panic_hold: bool,
+    // Time of the last adjustment of the clock, and whether an adjustment is
+    // held back by `min_clock_update_interval`
+    last_clock_update: Option<NtpTimestamp>,
+    clock_update_pending: bool,
+    // Local time at which the current slew or hold on clock updates ends
+    pending_update: Option<NtpTimestamp>,
+    // Leap smear in progress, and whether the requested time update is for
+    // starting or stopping a leap smear rather than for `pending_update`
+    leap_smear: Option<LeapSmear>,
+    leap_smear_timer: bool,
+    // Leap indicator the sources agreed on at the last clock update
+    source_leap_indicator: Option<NtpLeapIndicator>,
+    // Whether the next clock update should correct the offset right away,
+    // as requested through `force_sync`, and whether that correction was
+    // refused for exceeding the panic thresholds
+    force_step: bool,
+    force_step_refused: bool,
 }
 
 impl<C: NtpClock> KalmanClockController<C> {
@@ -112,6 +177,7 @@ impl<C: NtpClock> KalmanClockController<C> {
             return InternalStateUpdate {
                 source_message: None,
                 used_sources: None,
+                outlier_sources: None,
                 time_snapshot: Some(self.timedata),
                 next_update: None,
             };
@@ -133,10 +199,24 @@ impl<C: NtpClock> KalmanClockController<C> {
                     if *usable { state.as_ref() } else { None }
                 },
             )
+            .filter(|snapshot| !snapshot.monitor_only && !snapshot.warming_up)
             .copied()
             .collect();
-        let selection =
-            select::select(&self.synchronization_config, &self.algo_config, &candidates);
+        let agreeing = select::select(&self.synchronization_config, &self.algo_config, &candidates);
+        // Candidates left out of an agreeing set disagree with the other sources
+        let outlier_sources: Vec<_> = if agreeing.is_empty() {
+            vec![]
+        } else {
+            candidates
+                .iter()
+                .filter(|candidate| !agreeing.iter().any(|s| s.index == candidate.index))
+                .map(|candidate| candidate.index)
+                .collect()
+        };
+        let selection: Vec<_> = agreeing
+            .into_iter()
+            .filter(|snapshot| snapshot.may_discipline)
+            .collect();
 
         if let Some(combined) = combine(&selection, &self.algo_config) {
             info!(
@@ -153,28 +233,62 @@ impl<C: NtpClock> KalmanClockController<C> {
                     .expect("Cannot update clock");
             }
 
-            let freq_delta = combined.estimate.frequency() - self.desired_freq;
+            // While smearing a leap second the clock lags behind the sources,
+            // which should not be corrected
+            self.source_leap_indicator = combined.leap_indicator;
+            let (smear, smear_freq) = self
+                .leap_smear
+                .map_or((0.0, 0.0), |smear| (smear.offset(time), smear.freq));
+            let leap_indicator = if self.synchronization_config.leap_smear.is_some() {
+                combined.leap_indicator.map(|leap| match leap {
+                    NtpLeapIndicator::Leap61 | NtpLeapIndicator::Leap59 => {
+                        NtpLeapIndicator::NoWarning
+                    }
+                    other => other,
+                })
+            } else {
+                combined.leap_indicator
+            };
+
+            let freq_delta = combined.estimate.frequency() - self.desired_freq - smear_freq;
             let freq_uncertainty = combined.estimate.frequency_variance().sqrt();
-            let offset_delta = combined.estimate.offset();
+            let offset_delta = combined.estimate.offset() - smear;
             let offset_uncertainty = combined.estimate.offset_variance().sqrt();
-            let next_update = if self.desired_freq == 0.0
-                && offset_delta.abs() > offset_uncertainty * self.algo_config.steer_offset_threshold
-            {
-                // Note: because of threshold effects, offset_delta is likely an extreme estimate
-                // at this point. Hence we only correct it partially in order to avoid
-                // overcorrecting.
-                // The same does not apply to freq_delta, so if we start slewing
-                // it can be fully corrected without qualms.
-                self.steer_offset(
-                    offset_delta
-                        - offset_uncertainty
-                            * self.algo_config.steer_offset_leftover
-                            * offset_delta.signum(),
-                    freq_delta,
-                )
-            } else if freq_delta.abs()
-                > freq_uncertainty * self.algo_config.steer_frequency_threshold
-            {
+            let steer_offset = self.desired_freq == 0.0
+                && (self.force_step
+                    || offset_delta.abs()
+                        > offset_uncertainty * self.algo_config.steer_offset_threshold);
+            let steer_frequency =
+                freq_delta.abs() > freq_uncertainty * self.algo_config.steer_frequency_threshold;
+            // Note: because of threshold effects, offset_delta is likely an extreme estimate
+            // at this point. Hence we only correct it partially in order to avoid
+            // overcorrecting.
+            // The same does not apply to freq_delta, so if we start slewing
+            // it can be fully corrected without qualms.
+            let offset_change = offset_delta
+                - offset_uncertainty
+                    * self.algo_config.steer_offset_leftover
+                    * offset_delta.signum();
+            // Steps are never held back, the offset would only grow further
+            let step = steer_offset && self.is_step(offset_change);
+            let next_update = if !steer_offset && !steer_frequency {
+                self.clock_update_pending = false;
+                InternalStateUpdate::default()
+            } else if let Some(hold) = self.clock_update_hold(time).filter(|_| !step) {
+                debug!("Holding back clock update for {}s", hold.as_secs_f64());
+                self.clock_update_pending = true;
+                InternalStateUpdate {
+                    // While slewing, the end of the slew schedules the update
+                    next_update: (self.desired_freq == 0.0).then_some(hold),
+                    ..InternalStateUpdate::default()
+                }
+            } else if steer_offset {
+                self.last_clock_update = Some(time);
+                self.clock_update_pending = false;
+                self.steer_offset(offset_change, freq_delta)
+            } else {
+                self.last_clock_update = Some(time);
+                self.clock_update_pending = false;
                 // Note: because of threshold effects, freq_delta is likely an extreme estimate
                 // at this point. Hence we only correct it partially in order to avoid
                 // overcorrecting.
@@ -184,8 +298,6 @@ impl<C: NtpClock> KalmanClockController<C> {
                             * self.algo_config.steer_frequency_leftover
                             * freq_delta.signum(),
                 )
-            } else {
-                InternalStateUpdate::default()
             };
 
             self.timedata.root_delay = combined.delay;
@@ -200,36 +312,54 @@ impl<C: NtpClock> KalmanClockController<C> {
                     Some(v.map_or(a, |b| b.max(a)))
                 })
                 .unwrap_or(self.algo_config.initial_wander);
-            self.clock
-                .error_estimate_update(
-                    self.timedata.root_dispersion(time),
-                    self.timedata.root_delay,
-                )
-                .expect("Cannot update clock");
-
-            if let Some(leap) = combined.leap_indicator {
-                self.clock.status_update(leap).expect("Cannot update clock");
+            if let Some(leap) = leap_indicator {
                 self.timedata.leap_indicator = leap;
             }
+            self.clock
+                .adjust(ClockAdjustment {
+                    error_estimate: Some((
+                        self.timedata.root_dispersion(time),
+                        self.timedata.root_delay,
+                    )),
+                    leap_status: leap_indicator,
+                    ..ClockAdjustment::default()
+                })
+                .expect("Cannot update clock");
 
             // After a successful measurement we are out of startup.
             self.in_startup = false;
+            self.force_step = false;
+            self.timedata.clock_state = if self.panic_hold {
+                ClockState::PanicHold
+            } else if self.desired_freq == 0.0 {
+                ClockState::Synchronized
+            } else {
+                ClockState::Steering
+            };
 
             InternalStateUpdate {
                 used_sources: Some(combined.sources),
+                outlier_sources: Some(outlier_sources),
                 time_snapshot: Some(self.timedata),
+                next_update: self.schedule_update(time, next_update.next_update),
                 ..next_update
             }
         } else {
             info!("No consensus on current time");
+            self.force_step = false;
+            self.timedata.clock_state = ClockState::Unsynchronized;
             InternalStateUpdate {
+                outlier_sources: Some(outlier_sources),
                 time_snapshot: Some(self.timedata),
                 ..InternalStateUpdate::default()
             }
         }
     }
 
-    fn check_offset_steer(&mut self, change: f64) {
+    /// Returns whether the step may be applied. Exits when a panic threshold
+    /// is exceeded, unless the offset corrections are put on hold or the step
+    /// was forced, in which case it is refused.
+    fn check_offset_steer(&mut self, change: f64) -> bool {
         let change = NtpDuration::from_seconds(change);
         if self.in_startup {
             if !self
@@ -237,6 +367,9 @@ impl<C: NtpClock> KalmanClockController<C> {
                 .startup_step_panic_threshold
                 .is_within(change)
             {
+                if self.force_step {
+                    return self.refuse_forced_step(change);
+                }
                 error!(
                     "Unusually large clock step suggested, please manually verify system clock and reference clock state and restart if appropriate. If the clock is significantly wrong, you can use `ntp-ctl force-sync` to correct it."
                 );
@@ -246,6 +379,35 @@ impl<C: NtpClock> KalmanClockController<C> {
                 panic!("Threshold exceeded");
             }
         } else {
+            if self.force_step
+                && (!self
+                    .synchronization_config
+                    .single_step_panic_threshold
+                    .is_within(change)
+                    || self
+                        .synchronization_config
+                        .accumulated_step_panic_threshold
+                        .is_some_and(|v| self.timedata.accumulated_steps + change.abs() > v))
+            {
+                return self.refuse_forced_step(change);
+            }
+
+            if let Some(recovery) = self.synchronization_config.panic_threshold_recovery
+                && !self
+                    .synchronization_config
+                    .single_step_panic_threshold
+                    .is_within(change)
+            {
+                error!(
+                    "Unusually large clock step of {}ms suggested, holding offset corrections until the offset is below {}ms. Please manually verify system clock and reference clock state.",
+                    change.to_seconds() * 1e3,
+                    recovery.to_seconds() * 1e3
+                );
+                self.panic_hold = true;
+                self.timedata.clock_state = ClockState::PanicHold;
+                return false;
+            }
+
             self.timedata.accumulated_steps += change.abs();
             if !self
                 .synchronization_config
@@ -265,6 +427,126 @@ impl<C: NtpClock> KalmanClockController<C> {
                 panic!("Threshold exceeded");
             }
         }
+
+        true
+    }
+
+    fn refuse_forced_step(&mut self, change: NtpDuration) -> bool {
+        error!(
+            "Refusing forced clock step of {}ms, as it exceeds the panic threshold. Please manually verify system clock and reference clock state.",
+            change.to_seconds() * 1e3
+        );
+        self.force_step_refused = true;
+        false
+    }
+
+    /// Time left until the clock may be adjusted again at `time`, if the
+    /// minimum interval between clock updates has not yet passed.
+    fn clock_update_hold(&self, time: NtpTimestamp) -> Option<Duration> {
+        let interval = self.synchronization_config.min_clock_update_interval?;
+        let elapsed = (time - self.last_clock_update?).to_seconds();
+        let remaining = interval.as_secs_f64() - elapsed;
+        (remaining > 0.0).then(|| Duration::from_secs_f64(remaining))
+    }
+
+    /// Whether correcting the offset by `change` steps the clock instead of
+    /// slewing it.
+    fn is_step(&self, change: f64) -> bool {
+        let step_threshold = self
+            .synchronization_config
+            .step_threshold
+            .map_or(self.algo_config.step_threshold, NtpDuration::to_seconds);
+        let first_step = self.in_startup && self.synchronization_config.first_measurement_step;
+        first_step || self.force_step || change.abs() > step_threshold
+    }
+
+    /// Pick the earliest of the pending slew or hold and the next change of
+    /// the leap smear as the next time update. Only a single time update can
+    /// be requested, so the other one is requested again once it has passed.
+    fn schedule_update(
+        &mut self,
+        time: NtpTimestamp,
+        requested: Option<Duration>,
+    ) -> Option<Duration> {
+        if let Some(requested) = requested {
+            self.pending_update = Some(time + NtpDuration::from_seconds(requested.as_secs_f64()));
+        }
+        let pending = self
+            .pending_update
+            .map(|at| Duration::from_secs_f64((at - time).to_seconds().max(0.0)));
+
+        match self.next_leap_smear_change(time) {
+            Some(smear) if pending.is_none_or(|pending| smear < pending) => {
+                self.leap_smear_timer = true;
+                Some(smear)
+            }
+            _ if requested.is_some() => {
+                self.leap_smear_timer = false;
+                requested
+            }
+            // The pending update was displaced by the leap smear, request it again
+            _ if self.leap_smear_timer => {
+                self.leap_smear_timer = false;
+                pending
+            }
+            _ => None,
+        }
+    }
+
+    /// Time until the leap smear should next be started or stopped.
+    fn next_leap_smear_change(&self, time: NtpTimestamp) -> Option<Duration> {
+        let window = self.synchronization_config.leap_smear?.as_secs_f64();
+        let pending = leap_direction(self.source_leap_indicator) != 0.0;
+        let seconds = match self.leap_smear {
+            Some(smear) if pending => (smear.end - time).to_seconds(),
+            Some(_) => 0.0,
+            None if pending => seconds_until_midnight(time) - window,
+            None => return None,
+        };
+        Some(Duration::from_secs_f64(seconds.max(0.0)))
+    }
+
+    /// Start smearing a pending leap second once in the window before it, and
+    /// stop when the leap has taken effect or is no longer announced. The
+    /// smear only changes the frequency of the clock, so it never steps.
+    fn update_leap_smear(
+        &mut self,
+        time: NtpTimestamp,
+    ) -> InternalStateUpdate<KalmanControllerMessage> {
+        let Some(window) = self.synchronization_config.leap_smear else {
+            return InternalStateUpdate::default();
+        };
+        let direction = leap_direction(self.source_leap_indicator);
+        match self.leap_smear {
+            Some(smear) if direction == 0.0 || time - smear.end >= NtpDuration::ZERO => {
+                info!("Finished smearing leap second");
+                self.leap_smear = None;
+                // Exactly undo the frequency change of the smear
+                self.steer_frequency(smear.freq / (1.0 - smear.freq))
+            }
+            None if direction != 0.0 && seconds_until_midnight(time) <= window.as_secs_f64() => {
+                // Absorb the full leap second before midnight, even when
+                // starting late in the window
+                let remaining = seconds_until_midnight(time);
+                let previous_freq_offset = self.freq_offset;
+                let update = self.steer_frequency(-direction / remaining);
+                // Frequency steering is limited, so short windows may only
+                // absorb part of the second
+                let freq = 1.0 - (1.0 + self.freq_offset) / (1.0 + previous_freq_offset);
+                info!(
+                    "Smearing leap second over {}s at {}ppm",
+                    remaining,
+                    freq * 1e6
+                );
+                self.leap_smear = Some(LeapSmear {
+                    freq,
+                    start: time,
+                    end: time + NtpDuration::from_seconds(remaining),
+                });
+                update
+            }
+            _ => InternalStateUpdate::default(),
+        }
     }
 
     fn steer_offset(
@@ -272,9 +554,24 @@ impl<C: NtpClock> KalmanClockController<C> {
         change: f64,
         freq_delta: f64,
     ) -> InternalStateUpdate<KalmanControllerMessage> {
-        if change.abs() > self.algo_config.step_threshold {
+        if self.panic_hold {
+            let recovery = self
+                .synchronization_config
+                .panic_threshold_recovery
+                .map_or(0.0, NtpDuration::to_seconds);
+            if change.abs() >= recovery {
+                self.force_step_refused |= self.force_step;
+                return InternalStateUpdate::default();
+            }
+            info!("Offset is back below the panic threshold recovery bound, resuming corrections");
+            self.panic_hold = false;
+        }
+
+        if self.is_step(change) {
             // jump
-            self.check_offset_steer(change);
+            if !self.check_offset_steer(change) {
+                return InternalStateUpdate::default();
+            }
             self.clock
                 .step_clock(NtpDuration::from_seconds(change))
                 .expect("Cannot adjust clock");
@@ -299,10 +596,15 @@ impl<C: NtpClock> KalmanClockController<C> {
             }
         } else {
             // start slew
-            let freq = self
-                .algo_config
-                .slew_maximum_frequency_offset
-                .min(change.abs() / self.algo_config.slew_minimum_duration);
+            let max_freq = self.synchronization_config.max_slew_rate_ppm.map_or(
+                self.algo_config.slew_maximum_frequency_offset,
+                |ppm| {
+                    self.algo_config
+                        .slew_maximum_frequency_offset
+                        .min(ppm * 1e-6)
+                },
+            );
+            let freq = max_freq.min(change.abs() / self.algo_config.slew_minimum_duration);
             let duration = Duration::from_secs_f64(change.abs() / freq);
             debug!(
                 "Slewing by {}ms over {}s",
@@ -365,6 +667,32 @@ impl<C: NtpClock> KalmanClockController<C> {
     }
 }
 
+const SECONDS_PER_DAY: u64 = 86400;
+
+/// Seconds until the next UTC midnight, at which leap seconds take effect.
+///
+/// Timestamps with the top bit clear are taken to be in the second NTP era,
+/// as in RFC 4330. This covers the years 1968 up to 2104.
+fn seconds_until_midnight(time: NtpTimestamp) -> f64 {
+    let fixed = u64::from_be_bytes(time.to_bits());
+    let mut seconds = fixed >> 32;
+    if seconds < 1 << 31 {
+        seconds += 1 << 32;
+    }
+    let fraction = (fixed & 0xFFFF_FFFF) as f64 / 2f64.powi(32);
+    (SECONDS_PER_DAY - seconds % SECONDS_PER_DAY) as f64 - fraction
+}
+
+/// Amount in seconds by which a pending leap second moves the sources away
+/// from the clock, negative for a deleted second and zero without one.
+fn leap_direction(leap_indicator: Option<NtpLeapIndicator>) -> f64 {
+    match leap_indicator {
+        Some(NtpLeapIndicator::Leap61) => 1.0,
+        Some(NtpLeapIndicator::Leap59) => -1.0,
+        _ => 0.0,
+    }
+}
+
 impl<C: NtpClock> InternalTimeSyncController for KalmanClockController<C> {
     type Clock = C;
     type AlgorithmConfig = AlgorithmConfig;
@@ -394,6 +722,15 @@ impl<C: NtpClock> InternalTimeSyncController for KalmanClockController<C> {
                 ..TimeSnapshot::default()
             },
             in_startup: true,
+            panic_hold: false,
+            last_clock_update: None,
+            clock_update_pending: false,
+            pending_update: None,
+            leap_smear: None,
+            leap_smear_timer: false,
+            source_leap_indicator: None,
+            force_step: false,
+            force_step_refused: false,
         })
     }
 
@@ -415,6 +752,7 @@ impl<C: NtpClock> InternalTimeSyncController for KalmanClockController<C> {
             None,
             source_config,
             AveragingBuffer::default(),
+            false,
         )
     }
 
@@ -436,6 +774,7 @@ impl<C: NtpClock> InternalTimeSyncController for KalmanClockController<C> {
                 precision: measurement_noise_estimate,
                 accuracy: measurement_accuracy_estimate,
             },
+            true,
         )
     }
 
@@ -448,9 +787,80 @@ impl<C: NtpClock> InternalTimeSyncController for KalmanClockController<C> {
             state.1 = usable;
         }
     }
-    fn time_update(&mut self) -> InternalStateUpdate<Self::ControllerMessage> {
+    fn time_update(&mut self) -> Result<InternalStateUpdate<Self::ControllerMessage>, C::Error> {
+        let now = self.clock.now()?;
+        if self.leap_smear_timer {
+            let update = self.update_leap_smear(now);
+            return Ok(InternalStateUpdate {
+                time_snapshot: Some(self.timedata),
+                next_update: self.schedule_update(now, None),
+                ..update
+            });
+        }
+
+        self.pending_update = None;
+        if self.desired_freq == 0.0 {
+            // Not slewing, so this is the end of a hold on clock updates
+            if self.clock_update_pending {
+                return Ok(self.update_clock(now));
+            }
+            return Ok(InternalStateUpdate {
+                next_update: self.schedule_update(now, None),
+                ..InternalStateUpdate::default()
+            });
+        }
+
         // End slew
-        self.change_desired_frequency(0.0, 0.0)
+        let update = self.change_desired_frequency(0.0, 0.0);
+        if self.timedata.clock_state == ClockState::Steering {
+            self.timedata.clock_state = ClockState::Synchronized;
+        }
+        let hold = self
+            .clock_update_pending
+            .then(|| self.clock_update_hold(now).unwrap_or_default());
+        Ok(InternalStateUpdate {
+            time_snapshot: Some(self.timedata),
+            next_update: self.schedule_update(now, hold),
+            ..update
+        })
+    }
+
+    fn force_sync(
+        &mut self,
+    ) -> (
+        Vec<InternalStateUpdate<Self::ControllerMessage>>,
+        ForceSyncOutcome,
+    ) {
+        info!("Forced synchronization requested");
+        let mut updates = vec![];
+
+        // End the slew in progress first, the offset that is left is stepped
+        // right after
+        if self.desired_freq != 0.0 {
+            self.pending_update = None;
+            let update = self.change_desired_frequency(0.0, 0.0);
+            if self.timedata.clock_state == ClockState::Steering {
+                self.timedata.clock_state = ClockState::Synchronized;
+            }
+            updates.push(update);
+        }
+
+        let now = self.clock.now().expect("Cannot get current time");
+        self.force_step = true;
+        self.force_step_refused = false;
+        let update = self.update_clock(now);
+        let outcome = if self.force_step_refused {
+            ForceSyncOutcome::Refused
+        } else if update.used_sources.is_some() {
+            ForceSyncOutcome::Corrected
+        } else {
+            ForceSyncOutcome::NoConsensus
+        };
+        self.force_step = false;
+        self.force_step_refused = false;
+        updates.push(update);
+
+        (updates, outcome)
     }
 
     fn source_message(
@@ -524,130 +934,62 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_startup_flag_unsets() {
-        let synchronization_config = SynchronizationConfig {
-            minimum_agreeing_sources: 1,
-            ..SynchronizationConfig::default()
-        };
-        let algo_config = AlgorithmConfig::default();
-        let source_config = SourceConfig::default();
-        let mut algo = KalmanClockController::new(
-            TestClock {
-                has_steered: RefCell::new(false),
-                current_time: NtpTimestamp::from_fixed_int(0),
-            },
-            synchronization_config,
-            algo_config,
-        )
-        .unwrap();
-
-        // ignore startup steer of frequency.
-        *algo.clock.has_steered.borrow_mut() = false;
+    #[derive(Debug, Clone, Default)]
+    struct AdjustRecordingClock {
+        adjustments: RefCell<Vec<ClockAdjustment>>,
+    }
 
-        let mut source = algo.add_source(ClockId(0), source_config);
-        algo.source_update(ClockId(0), true);
+    impl NtpClock for AdjustRecordingClock {
+        type Error = std::io::Error;
 
-        assert!(algo.in_startup);
+        fn now(&self) -> Result<NtpTimestamp, Self::Error> {
+            Ok(NtpTimestamp::from_fixed_int(0))
+        }
 
-        let mut noise = 1e-9;
+        fn set_frequency(&self, _freq: f64) -> Result<NtpTimestamp, Self::Error> {
+            Ok(NtpTimestamp::from_fixed_int(0))
+        }
 
-        while !*algo.clock.has_steered.borrow() {
-            algo.clock.current_time += NtpDuration::from_seconds(1.0);
-            noise += 1e-9;
+        fn get_frequency(&self) -> Result<f64, Self::Error> {
+            Ok(0.0)
+        }
 
-            let message = source.handle_measurement(InternalMeasurement {
-                delay: NtpDuration::from_seconds(0.001 + noise),
-                offset: NtpDuration::from_seconds(1700.0 + noise),
-                localtime: algo.clock.current_time,
+        fn step_clock(&self, _offset: NtpDuration) -> Result<NtpTimestamp, Self::Error> {
+            Ok(NtpTimestamp::from_fixed_int(0))
+        }
 
-                root_delay: NtpDuration::default(),
-                root_dispersion: NtpDuration::default(),
-                leap: NtpLeapIndicator::NoWarning,
-                precision: 0,
-            });
-            if let Some(message) = message {
-                let actions = algo.source_message(ClockId(0), message);
-                if let Some(source_message) = actions.source_message {
-                    source.handle_message(source_message);
-                }
-            }
+        fn disable_ntp_algorithm(&self) -> Result<(), Self::Error> {
+            Ok(())
         }
 
-        assert!(!algo.in_startup);
-        assert_eq!(algo.timedata.leap_indicator, NtpLeapIndicator::NoWarning);
-        assert_ne!(algo.timedata.root_delay, NtpDuration::ZERO);
-        assert_ne!(algo.timedata.root_variance_base, 0.0);
-    }
+        fn error_estimate_update(
+            &self,
+            _est_error: NtpDuration,
+            _maximum_error: NtpDuration,
+        ) -> Result<(), Self::Error> {
+            unreachable!("error estimate should be part of a combined adjustment")
+        }
 
-    #[test]
-    fn slews_dont_accumulate() {
-        let synchronization_config = SynchronizationConfig {
-            minimum_agreeing_sources: 1,
-            single_step_panic_threshold: StepThreshold {
-                forward: None,
-                backward: None,
-            },
-            ..SynchronizationConfig::default()
-        };
-        let algo_config = AlgorithmConfig {
-            step_threshold: 1800.0,
-            ..Default::default()
-        };
-        let mut algo = KalmanClockController::new(
-            TestClock {
-                has_steered: RefCell::new(false),
-                current_time: NtpTimestamp::from_fixed_int(0),
-            },
-            synchronization_config,
-            algo_config,
-        )
-        .unwrap();
+        fn status_update(&self, _leap_status: NtpLeapIndicator) -> Result<(), Self::Error> {
+            unreachable!("leap status should be part of a combined adjustment")
+        }
 
-        algo.in_startup = false;
-        algo.steer_offset(1000.0, 0.0);
-        assert_eq!(algo.timedata.accumulated_steps, NtpDuration::ZERO);
+        fn adjust(&self, adjustment: ClockAdjustment) -> Result<NtpTimestamp, Self::Error> {
+            self.adjustments.borrow_mut().push(adjustment);
+            Ok(NtpTimestamp::from_fixed_int(0))
+        }
     }
 
     #[test]
-    #[should_panic]
-    fn jumps_add_absolutely() {
+    fn test_clock_update_is_combined() {
         let synchronization_config = SynchronizationConfig {
             minimum_agreeing_sources: 1,
-            single_step_panic_threshold: StepThreshold {
-                forward: None,
-                backward: None,
-            },
-            accumulated_step_panic_threshold: Some(NtpDuration::from_seconds(1800.0)),
             ..SynchronizationConfig::default()
         };
-        let algo_config = AlgorithmConfig::default();
-        let mut algo = KalmanClockController::new(
-            TestClock {
-                has_steered: RefCell::new(false),
-                current_time: NtpTimestamp::from_fixed_int(0),
-            },
-            synchronization_config,
-            algo_config,
-        )
-        .unwrap();
-
-        algo.in_startup = false;
-        algo.steer_offset(1000.0, 0.0);
-        algo.steer_offset(-1000.0, 0.0);
-    }
-
-    #[test]
-    fn test_jumps_update_state() {
-        let synchronization_config = SynchronizationConfig::default();
-        let algo_config = AlgorithmConfig::default();
         let mut algo = KalmanClockController::new(
-            TestClock {
-                has_steered: RefCell::new(false),
-                current_time: NtpTimestamp::from_fixed_int(0),
-            },
+            AdjustRecordingClock::default(),
             synchronization_config,
-            algo_config,
+            AlgorithmConfig::default(),
         )
         .unwrap();
 
@@ -668,28 +1010,794 @@ mod tests {
                     source_delay: NtpDuration::ZERO,
                     leap_indicator: NtpLeapIndicator::NoWarning,
                     last_update: NtpTimestamp::from_fixed_int(0),
+                    may_discipline: true,
+                    monitor_only: false,
+                    warming_up: false,
+                    prefer: false,
+                    refclock: false,
+                    group: None,
                 }),
                 true,
             ),
         );
 
-        algo.sources.insert(
-            ClockId(1),
-            (
-                Some(SourceSnapshot {
-                    index: ClockId(1),
-                    state: KalmanState {
-                        state: Vector::new_vector([0.0, 0.0]),
-                        uncertainty: Matrix::new([[1e-18, 0.0], [0.0, 1e-18]]),
-                        time: NtpTimestamp::from_fixed_int(0),
-                    },
-                    wander: 0.0,
-                    delay: 0.0,
-                    period: Some(3.0),
-                    source_uncertainty: NtpDuration::ZERO,
-                    source_delay: NtpDuration::ZERO,
-                    leap_indicator: NtpLeapIndicator::NoWarning,
+        let update = algo.update_clock(NtpTimestamp::from_fixed_int(0));
+        assert_eq!(update.used_sources, Some(vec![ClockId(0)]));
+
+        let adjustments = algo.clock.adjustments.borrow();
+        assert_eq!(adjustments.len(), 1);
+        assert!(adjustments[0].error_estimate.is_some());
+        assert_eq!(
+            adjustments[0].leap_status,
+            Some(NtpLeapIndicator::NoWarning)
+        );
+        assert_eq!(adjustments[0].frequency, None);
+    }
+
+    #[test]
+    fn test_outlier_sources() {
+        let synchronization_config = SynchronizationConfig {
+            minimum_agreeing_sources: 1,
+            ..SynchronizationConfig::default()
+        };
+        let mut algo = KalmanClockController::new(
+            AdjustRecordingClock::default(),
+            synchronization_config,
+            AlgorithmConfig::default(),
+        )
+        .unwrap();
+
+        for (index, offset) in [(0, 0.0), (1, 0.0), (2, 1.0)] {
+            algo.sources.insert(
+                ClockId(index),
+                (
+                    Some(SourceSnapshot {
+                        index: ClockId(index),
+                        state: KalmanState {
+                            state: Vector::new_vector([offset, 0.0]),
+                            uncertainty: Matrix::new([[1e-18, 0.0], [0.0, 1e-18]]),
+                            time: NtpTimestamp::from_fixed_int(0),
+                        },
+                        wander: 0.0,
+                        delay: 0.0,
+                        period: None,
+                        source_uncertainty: NtpDuration::ZERO,
+                        source_delay: NtpDuration::ZERO,
+                        leap_indicator: NtpLeapIndicator::NoWarning,
+                        last_update: NtpTimestamp::from_fixed_int(0),
+                        may_discipline: true,
+                        monitor_only: false,
+                        warming_up: false,
+                        prefer: false,
+                        refclock: false,
+                        group: None,
+                    }),
+                    true,
+                ),
+            );
+        }
+
+        // The source disagreeing with the other two is not used
+        let update = algo.update_clock(NtpTimestamp::from_fixed_int(0));
+        let mut used_sources = update.used_sources.unwrap();
+        used_sources.sort_by_key(|id| id.0);
+        assert_eq!(used_sources, vec![ClockId(0), ClockId(1)]);
+        assert_eq!(update.outlier_sources, Some(vec![ClockId(2)]));
+
+        // Without agreement there are no outliers either
+        algo.sources.remove(&ClockId(1));
+        let update = algo.update_clock(NtpTimestamp::from_fixed_int(0));
+        assert_eq!(update.used_sources, None);
+        assert_eq!(update.outlier_sources, Some(vec![]));
+    }
+
+    #[test]
+    fn test_clock_state_transitions() {
+        let synchronization_config = SynchronizationConfig {
+            minimum_agreeing_sources: 1,
+            ..SynchronizationConfig::default()
+        };
+        let mut algo = KalmanClockController::new(
+            AdjustRecordingClock::default(),
+            synchronization_config,
+            AlgorithmConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(algo.timedata.clock_state, ClockState::Unsynchronized);
+
+        let snapshot = |offset: f64| SourceSnapshot {
+            index: ClockId(0),
+            state: KalmanState {
+                state: Vector::new_vector([offset, 0.0]),
+                uncertainty: Matrix::new([[1e-18, 0.0], [0.0, 1e-18]]),
+                time: NtpTimestamp::from_fixed_int(0),
+            },
+            wander: 0.0,
+            delay: 0.0,
+            period: None,
+            source_uncertainty: NtpDuration::ZERO,
+            source_delay: NtpDuration::ZERO,
+            leap_indicator: NtpLeapIndicator::NoWarning,
+            last_update: NtpTimestamp::from_fixed_int(0),
+            may_discipline: true,
+            monitor_only: false,
+            warming_up: false,
+            prefer: false,
+            refclock: false,
+            group: None,
+        };
+
+        // An offset below the step threshold is slewed away
+        algo.sources
+            .insert(ClockId(0), (Some(snapshot(1e-3)), true));
+        let update = algo.update_clock(NtpTimestamp::from_fixed_int(0));
+        assert_eq!(
+            update.time_snapshot.unwrap().clock_state,
+            ClockState::Steering
+        );
+
+        // Ending the slew reports the clock as synchronized
+        let update = algo.time_update().unwrap();
+        assert_eq!(
+            update.time_snapshot.unwrap().clock_state,
+            ClockState::Synchronized
+        );
+
+        algo.sources.insert(ClockId(0), (Some(snapshot(0.0)), true));
+        let update = algo.update_clock(NtpTimestamp::from_fixed_int(0));
+        assert_eq!(
+            update.time_snapshot.unwrap().clock_state,
+            ClockState::Synchronized
+        );
+
+        // Losing all sources means there is no consensus anymore
+        algo.remove_source(ClockId(0));
+        let update = algo.update_clock(NtpTimestamp::from_fixed_int(0));
+        assert_eq!(
+            update.time_snapshot.unwrap().clock_state,
+            ClockState::Unsynchronized
+        );
+    }
+
+    #[test]
+    fn test_min_clock_update_interval() {
+        let synchronization_config = SynchronizationConfig {
+            minimum_agreeing_sources: 1,
+            min_clock_update_interval: Some(Duration::from_secs(16)),
+            ..SynchronizationConfig::default()
+        };
+        let mut algo = KalmanClockController::new(
+            TestClock {
+                has_steered: RefCell::new(false),
+                current_time: NtpTimestamp::from_fixed_int(0),
+            },
+            synchronization_config,
+            AlgorithmConfig::default(),
+        )
+        .unwrap();
+
+        let at = |seconds: u64| NtpTimestamp::from_fixed_int(seconds << 32);
+        // A source consistently suggesting a frequency correction
+        let snapshot = |time: NtpTimestamp| SourceSnapshot {
+            index: ClockId(0),
+            state: KalmanState {
+                state: Vector::new_vector([0.0, 1e-6]),
+                uncertainty: Matrix::new([[1e-18, 0.0], [0.0, 1e-18]]),
+                time,
+            },
+            wander: 0.0,
+            delay: 0.0,
+            period: None,
+            source_uncertainty: NtpDuration::ZERO,
+            source_delay: NtpDuration::ZERO,
+            leap_indicator: NtpLeapIndicator::NoWarning,
+            last_update: time,
+            may_discipline: true,
+            monitor_only: false,
+            warming_up: false,
+            prefer: false,
+            refclock: false,
+            group: None,
+        };
+
+        for interval in 0..3 {
+            let mut applied = 0;
+            for second in 0..16 {
+                let time = at(16 * interval + second);
+                algo.clock.current_time = time;
+                algo.sources
+                    .insert(ClockId(0), (Some(snapshot(time)), true));
+                let update = algo.update_clock(time);
+                if update.source_message.is_some() {
+                    applied += 1;
+                } else {
+                    // Held updates are scheduled for the next allowed tick
+                    assert!(algo.clock_update_pending);
+                    assert!(update.next_update.is_some());
+                }
+            }
+
+            if interval == 0 {
+                // Only the first measurement is applied directly
+                assert_eq!(applied, 1);
+            } else {
+                // All measurements in the interval were batched into the
+                // update at its start
+                assert_eq!(applied, 0);
+            }
+
+            // At the next tick the held updates are applied at once
+            let tick = at(16 * (interval + 1));
+            algo.clock.current_time = tick;
+            algo.sources
+                .insert(ClockId(0), (Some(snapshot(tick)), true));
+            let update = algo.time_update().unwrap();
+            assert!(update.source_message.is_some());
+            assert!(!algo.clock_update_pending);
+        }
+
+        // Without a held update, a time update leaves the clock alone
+        algo.clock.current_time = at(49);
+        let update = algo.time_update().unwrap();
+        assert!(update.source_message.is_none());
+        assert!(update.next_update.is_none());
+        assert!(!algo.clock_update_pending);
+
+        // Steps are applied right away, even within the interval
+        let time = at(50);
+        algo.clock.current_time = time;
+        let offset = SourceSnapshot {
+            state: KalmanState {
+                state: Vector::new_vector([0.5, 1e-6]),
+                ..snapshot(time).state
+            },
+            ..snapshot(time)
+        };
+        algo.sources.insert(ClockId(0), (Some(offset), true));
+        let update = algo.update_clock(time);
+        assert!(matches!(
+            update.source_message,
+            Some(KalmanControllerMessage {
+                inner: KalmanControllerMessageInner::Step { .. }
+            })
+        ));
+        assert!(!algo.clock_update_pending);
+    }
+
+    #[test]
+    fn test_seconds_until_midnight() {
+        // 2017-01-01T00:00:00Z
+        let midnight = 3_692_217_600;
+        let at = |seconds, nanos| {
+            seconds_until_midnight(NtpTimestamp::from_seconds_nanos_since_ntp_era(
+                seconds, nanos,
+            ))
+        };
+        assert_eq!(at(midnight, 0), 86400.0);
+        assert_eq!(at(midnight - 1, 0), 1.0);
+        assert!((at(midnight - 1, 500_000_000) - 0.5).abs() < 1e-6);
+        assert_eq!(at(midnight - 86399, 0), 86399.0);
+
+        // The start of the second era is 2036-02-07T06:28:16Z
+        assert_eq!(at(0, 0), 86400.0 - 23296.0);
+    }
+
+    #[test]
+    fn test_leap_smear_ramp() {
+        let midnight = 3_692_217_600;
+        let at = |seconds| NtpTimestamp::from_seconds_nanos_since_ntp_era(seconds, 0);
+        let smear = LeapSmear {
+            freq: 1.0 / 3600.0,
+            start: at(midnight - 3600),
+            end: at(midnight),
+        };
+
+        // Nothing happens before the smear
+        assert_eq!(smear.offset(at(midnight - 7200)), 0.0);
+        assert_eq!(smear.offset(at(midnight - 3600)), 0.0);
+
+        // Linear ramp across the window
+        assert!((smear.offset(at(midnight - 2700)) - 0.25).abs() < 1e-9);
+        assert!((smear.offset(at(midnight - 1800)) - 0.5).abs() < 1e-9);
+        assert!((smear.offset(at(midnight - 1)) - (1.0 - 1.0 / 3600.0)).abs() < 1e-9);
+
+        // The full second is absorbed at the leap
+        assert!((smear.offset(at(midnight + 1800)) - 1.0).abs() < 1e-9);
+
+        // Only a pending leap second is smeared
+        assert_eq!(leap_direction(Some(NtpLeapIndicator::Leap61)), 1.0);
+        assert_eq!(leap_direction(Some(NtpLeapIndicator::Leap59)), -1.0);
+        assert_eq!(leap_direction(Some(NtpLeapIndicator::NoWarning)), 0.0);
+        assert_eq!(leap_direction(None), 0.0);
+    }
+
+    fn leap_smear_snapshot(offset: f64, frequency: f64, time: NtpTimestamp) -> SourceSnapshot {
+        SourceSnapshot {
+            index: ClockId(0),
+            state: KalmanState {
+                state: Vector::new_vector([offset, frequency]),
+                uncertainty: Matrix::new([[1e-6, 0.0], [0.0, 1e-18]]),
+                time,
+            },
+            wander: 0.0,
+            delay: 0.0,
+            period: None,
+            source_uncertainty: NtpDuration::ZERO,
+            source_delay: NtpDuration::ZERO,
+            leap_indicator: NtpLeapIndicator::Leap61,
+            last_update: time,
+            may_discipline: true,
+            monitor_only: false,
+            warming_up: false,
+            prefer: false,
+            refclock: false,
+            group: None,
+        }
+    }
+
+    #[test]
+    fn test_leap_smear_long_poll_interval() {
+        let midnight = 3_692_217_600;
+        let at = |seconds| NtpTimestamp::from_seconds_nanos_since_ntp_era(seconds, 0);
+        let synchronization_config = SynchronizationConfig {
+            minimum_agreeing_sources: 1,
+            step_threshold: Some(NtpDuration::from_seconds(0.01)),
+            leap_smear: Some(Duration::from_secs(3600)),
+            ..SynchronizationConfig::default()
+        };
+        let mut algo = KalmanClockController::new(
+            TestClock {
+                has_steered: RefCell::new(false),
+                current_time: at(midnight - 7200),
+            },
+            synchronization_config,
+            AlgorithmConfig::default(),
+        )
+        .unwrap();
+        algo.in_startup = false;
+
+        // A single measurement announcing the leap, well before the window.
+        // The next one only arrives long after the leap.
+        algo.sources.insert(
+            ClockId(0),
+            (
+                Some(leap_smear_snapshot(0.0, 0.0, at(midnight - 7200))),
+                true,
+            ),
+        );
+        let update = algo.update_clock(at(midnight - 7200));
+        assert!(update.source_message.is_none());
+        assert_eq!(update.next_update, Some(Duration::from_secs(3600)));
+
+        // The smear starts on its own timer, adjusting only the frequency
+        algo.clock.current_time = at(midnight - 3600);
+        let update = algo.time_update().unwrap();
+        assert!(matches!(
+            update.source_message,
+            Some(KalmanControllerMessage {
+                inner: KalmanControllerMessageInner::FreqChange { steer, .. }
+            }) if (steer + 1.0 / 3600.0).abs() < 1e-12
+        ));
+        assert_eq!(update.next_update, Some(Duration::from_secs(3600)));
+        assert!((algo.freq_offset + 1.0 / 3600.0).abs() < 1e-12);
+
+        // Half way, the clock lagging half a second behind is not corrected,
+        // even though that is far above the step threshold
+        let halfway = at(midnight - 1800);
+        algo.clock.current_time = halfway;
+        algo.sources.insert(
+            ClockId(0),
+            (Some(leap_smear_snapshot(0.5, 1.0 / 3600.0, halfway)), true),
+        );
+        let update = algo.update_clock(halfway);
+        assert!(update.source_message.is_none());
+        assert_eq!(update.next_update, Some(Duration::from_secs(1800)));
+
+        // At the leap the smear ends, again without stepping
+        algo.clock.current_time = at(midnight);
+        let update = algo.time_update().unwrap();
+        assert!(matches!(
+            update.source_message,
+            Some(KalmanControllerMessage {
+                inner: KalmanControllerMessageInner::FreqChange { .. }
+            })
+        ));
+        assert!(algo.freq_offset.abs() < 1e-12);
+        assert!(algo.leap_smear.is_none());
+    }
+
+    #[test]
+    fn test_leap_smear_hides_leap() {
+        let synchronization_config = SynchronizationConfig {
+            minimum_agreeing_sources: 1,
+            leap_smear: Some(Duration::from_secs(3600)),
+            ..SynchronizationConfig::default()
+        };
+        let mut algo = KalmanClockController::new(
+            AdjustRecordingClock::default(),
+            synchronization_config,
+            AlgorithmConfig::default(),
+        )
+        .unwrap();
+
+        // Half way the window, with the clock already half a second behind
+        let midnight = 3_692_217_600;
+        let time = NtpTimestamp::from_seconds_nanos_since_ntp_era(midnight - 1800, 0);
+        algo.leap_smear = Some(LeapSmear {
+            freq: 1.0 / 3600.0,
+            start: NtpTimestamp::from_seconds_nanos_since_ntp_era(midnight - 3600, 0),
+            end: NtpTimestamp::from_seconds_nanos_since_ntp_era(midnight, 0),
+        });
+        algo.sources.insert(
+            ClockId(0),
+            (Some(leap_smear_snapshot(0.5, 1.0 / 3600.0, time)), true),
+        );
+
+        let update = algo.update_clock(time);
+        assert_eq!(update.used_sources, Some(vec![ClockId(0)]));
+        assert!(update.source_message.is_none());
+        assert_eq!(algo.desired_freq, 0.0);
+        assert_eq!(algo.timedata.leap_indicator, NtpLeapIndicator::NoWarning);
+
+        let adjustments = algo.clock.adjustments.borrow();
+        assert_eq!(
+            adjustments[0].leap_status,
+            Some(NtpLeapIndicator::NoWarning)
+        );
+    }
+
+    #[test]
+    fn test_startup_flag_unsets() {
+        let synchronization_config = SynchronizationConfig {
+            minimum_agreeing_sources: 1,
+            ..SynchronizationConfig::default()
+        };
+        let algo_config = AlgorithmConfig::default();
+        let source_config = SourceConfig::default();
+        let mut algo = KalmanClockController::new(
+            TestClock {
+                has_steered: RefCell::new(false),
+                current_time: NtpTimestamp::from_fixed_int(0),
+            },
+            synchronization_config,
+            algo_config,
+        )
+        .unwrap();
+
+        // ignore startup steer of frequency.
+        *algo.clock.has_steered.borrow_mut() = false;
+
+        let mut source = algo.add_source(ClockId(0), source_config);
+        algo.source_update(ClockId(0), true);
+
+        assert!(algo.in_startup);
+
+        let mut noise = 1e-9;
+
+        while !*algo.clock.has_steered.borrow() {
+            algo.clock.current_time += NtpDuration::from_seconds(1.0);
+            noise += 1e-9;
+
+            let message = source.handle_measurement(InternalMeasurement {
+                delay: NtpDuration::from_seconds(0.001 + noise),
+                offset: NtpDuration::from_seconds(1700.0 + noise),
+                localtime: algo.clock.current_time,
+
+                root_delay: NtpDuration::default(),
+                root_dispersion: NtpDuration::default(),
+                leap: NtpLeapIndicator::NoWarning,
+                precision: 0,
+            });
+            if let Some(message) = message {
+                let actions = algo.source_message(ClockId(0), message);
+                if let Some(source_message) = actions.source_message {
+                    source.handle_message(source_message);
+                }
+            }
+        }
+
+        assert!(!algo.in_startup);
+        assert_eq!(algo.timedata.leap_indicator, NtpLeapIndicator::NoWarning);
+        assert_ne!(algo.timedata.root_delay, NtpDuration::ZERO);
+        assert_ne!(algo.timedata.root_variance_base, 0.0);
+    }
+
+    #[test]
+    fn test_jittery_source_held_back() {
+        let synchronization_config = SynchronizationConfig {
+            minimum_agreeing_sources: 1,
+            ..SynchronizationConfig::default()
+        };
+        let algo_config = AlgorithmConfig::default();
+        let source_config = SourceConfig {
+            max_jitter_for_discipline: Some(NtpDuration::from_seconds(1e-3)),
+            ..SourceConfig::default()
+        };
+        let mut algo = KalmanClockController::new(
+            TestClock {
+                has_steered: RefCell::new(false),
+                current_time: NtpTimestamp::from_fixed_int(0),
+            },
+            synchronization_config,
+            algo_config,
+        )
+        .unwrap();
+
+        // ignore startup steer of frequency.
+        *algo.clock.has_steered.borrow_mut() = false;
+
+        let mut source = algo.add_source(ClockId(0), source_config);
+        algo.source_update(ClockId(0), true);
+
+        for i in 0..32 {
+            algo.clock.current_time += NtpDuration::from_seconds(1.0);
+            let delay = if i % 2 == 0 { 0.001 } else { 0.1 };
+
+            let message = source.handle_measurement(InternalMeasurement {
+                delay: NtpDuration::from_seconds(delay),
+                offset: NtpDuration::from_seconds(1700.0),
+                localtime: algo.clock.current_time,
+
+                root_delay: NtpDuration::default(),
+                root_dispersion: NtpDuration::default(),
+                leap: NtpLeapIndicator::NoWarning,
+                precision: 0,
+            });
+            if let Some(message) = message {
+                assert!(!message.inner.may_discipline);
+                let actions = algo.source_message(ClockId(0), message);
+                assert!(actions.used_sources.is_none());
+            }
+        }
+
+        assert!(algo.in_startup);
+        assert!(!*algo.clock.has_steered.borrow());
+    }
+
+    #[test]
+    fn test_monitor_only_source_not_selected() {
+        let synchronization_config = SynchronizationConfig {
+            minimum_agreeing_sources: 1,
+            ..SynchronizationConfig::default()
+        };
+        let algo_config = AlgorithmConfig::default();
+        let source_config = SourceConfig {
+            monitor_only: true,
+            ..SourceConfig::default()
+        };
+        let mut algo = KalmanClockController::new(
+            TestClock {
+                has_steered: RefCell::new(false),
+                current_time: NtpTimestamp::from_fixed_int(0),
+            },
+            synchronization_config,
+            algo_config,
+        )
+        .unwrap();
+
+        // ignore startup steer of frequency.
+        *algo.clock.has_steered.borrow_mut() = false;
+
+        let mut source = algo.add_source(ClockId(0), source_config);
+        algo.source_update(ClockId(0), true);
+
+        for _ in 0..32 {
+            algo.clock.current_time += NtpDuration::from_seconds(1.0);
+
+            let message = source.handle_measurement(InternalMeasurement {
+                delay: NtpDuration::from_seconds(0.001),
+                offset: NtpDuration::from_seconds(1.0),
+                localtime: algo.clock.current_time,
+
+                root_delay: NtpDuration::default(),
+                root_dispersion: NtpDuration::default(),
+                leap: NtpLeapIndicator::NoWarning,
+                precision: 0,
+            });
+            if let Some(message) = message {
+                assert!(message.inner.monitor_only);
+                let actions = algo.source_message(ClockId(0), message);
+                assert!(actions.used_sources.is_none());
+            }
+        }
+
+        // The source is fully measured, but never used to steer the clock
+        assert!((source.observe().offset.to_seconds() - 1.0).abs() < 1e-3);
+        assert!(algo.in_startup);
+        assert!(!*algo.clock.has_steered.borrow());
+    }
+
+    #[test]
+    fn test_source_not_selected_during_warmup() {
+        let synchronization_config = SynchronizationConfig {
+            minimum_agreeing_sources: 1,
+            ..SynchronizationConfig::default()
+        };
+        let algo_config = AlgorithmConfig::default();
+        let source_config = SourceConfig {
+            warmup_samples: 4,
+            ..SourceConfig::default()
+        };
+        let mut algo = KalmanClockController::new(
+            TestClock {
+                has_steered: RefCell::new(false),
+                current_time: NtpTimestamp::from_fixed_int(0),
+            },
+            synchronization_config,
+            algo_config,
+        )
+        .unwrap();
+
+        // ignore startup steer of frequency.
+        *algo.clock.has_steered.borrow_mut() = false;
+
+        let mut source = algo.add_source(ClockId(0), source_config);
+        algo.source_update(ClockId(0), true);
+
+        let mut samples = 0;
+        let mut first_used = None;
+        for _ in 0..64 {
+            algo.clock.current_time += NtpDuration::from_seconds(1.0);
+
+            let message = source.handle_measurement(InternalMeasurement {
+                delay: NtpDuration::from_seconds(0.001),
+                offset: NtpDuration::from_seconds(1700.0),
+                localtime: algo.clock.current_time,
+
+                root_delay: NtpDuration::default(),
+                root_dispersion: NtpDuration::default(),
+                leap: NtpLeapIndicator::NoWarning,
+                precision: 0,
+            });
+            if let Some(message) = message {
+                samples += 1;
+                assert_eq!(message.inner.warming_up, samples < 4);
+
+                let actions = algo.source_message(ClockId(0), message);
+                if samples < 4 {
+                    assert!(actions.used_sources.is_none());
+                    assert!(!*algo.clock.has_steered.borrow());
+                } else if actions.used_sources.is_some() && first_used.is_none() {
+                    first_used = Some(samples);
+                }
+                if let Some(source_message) = actions.source_message {
+                    source.handle_message(source_message);
+                }
+            }
+
+            if *algo.clock.has_steered.borrow() {
+                break;
+            }
+        }
+
+        // Once warmed up, the source is used as normal
+        assert!(first_used.is_some_and(|samples| samples >= 4));
+        assert!(*algo.clock.has_steered.borrow());
+        assert!(!algo.in_startup);
+    }
+
+    #[test]
+    fn slews_dont_accumulate() {
+        let synchronization_config = SynchronizationConfig {
+            minimum_agreeing_sources: 1,
+            single_step_panic_threshold: StepThreshold {
+                forward: None,
+                backward: None,
+            },
+            ..SynchronizationConfig::default()
+        };
+        let algo_config = AlgorithmConfig {
+            step_threshold: 1800.0,
+            ..Default::default()
+        };
+        let mut algo = KalmanClockController::new(
+            TestClock {
+                has_steered: RefCell::new(false),
+                current_time: NtpTimestamp::from_fixed_int(0),
+            },
+            synchronization_config,
+            algo_config,
+        )
+        .unwrap();
+
+        algo.in_startup = false;
+        algo.steer_offset(1000.0, 0.0);
+        assert_eq!(algo.timedata.accumulated_steps, NtpDuration::ZERO);
+    }
+
+    #[test]
+    #[should_panic]
+    fn jumps_add_absolutely() {
+        let synchronization_config = SynchronizationConfig {
+            minimum_agreeing_sources: 1,
+            single_step_panic_threshold: StepThreshold {
+                forward: None,
+                backward: None,
+            },
+            accumulated_step_panic_threshold: Some(NtpDuration::from_seconds(1800.0)),
+            ..SynchronizationConfig::default()
+        };
+        let algo_config = AlgorithmConfig::default();
+        let mut algo = KalmanClockController::new(
+            TestClock {
+                has_steered: RefCell::new(false),
+                current_time: NtpTimestamp::from_fixed_int(0),
+            },
+            synchronization_config,
+            algo_config,
+        )
+        .unwrap();
+
+        algo.in_startup = false;
+        algo.steer_offset(1000.0, 0.0);
+        algo.steer_offset(-1000.0, 0.0);
+    }
+
+    #[test]
+    fn test_jumps_update_state() {
+        let synchronization_config = SynchronizationConfig::default();
+        let algo_config = AlgorithmConfig::default();
+        let mut algo = KalmanClockController::new(
+            TestClock {
+                has_steered: RefCell::new(false),
+                current_time: NtpTimestamp::from_fixed_int(0),
+            },
+            synchronization_config,
+            algo_config,
+        )
+        .unwrap();
+
+        algo.sources.insert(
+            ClockId(0),
+            (
+                Some(SourceSnapshot {
+                    index: ClockId(0),
+                    state: KalmanState {
+                        state: Vector::new_vector([0.0, 0.0]),
+                        uncertainty: Matrix::new([[1e-18, 0.0], [0.0, 1e-18]]),
+                        time: NtpTimestamp::from_fixed_int(0),
+                    },
+                    wander: 0.0,
+                    delay: 0.0,
+                    period: None,
+                    source_uncertainty: NtpDuration::ZERO,
+                    source_delay: NtpDuration::ZERO,
+                    leap_indicator: NtpLeapIndicator::NoWarning,
+                    last_update: NtpTimestamp::from_fixed_int(0),
+                    may_discipline: true,
+                    monitor_only: false,
+                    warming_up: false,
+                    prefer: false,
+                    refclock: false,
+                    group: None,
+                }),
+                true,
+            ),
+        );
+
+        algo.sources.insert(
+            ClockId(1),
+            (
+                Some(SourceSnapshot {
+                    index: ClockId(1),
+                    state: KalmanState {
+                        state: Vector::new_vector([0.0, 0.0]),
+                        uncertainty: Matrix::new([[1e-18, 0.0], [0.0, 1e-18]]),
+                        time: NtpTimestamp::from_fixed_int(0),
+                    },
+                    wander: 0.0,
+                    delay: 0.0,
+                    period: Some(3.0),
+                    source_uncertainty: NtpDuration::ZERO,
+                    source_delay: NtpDuration::ZERO,
+                    leap_indicator: NtpLeapIndicator::NoWarning,
                     last_update: NtpTimestamp::from_fixed_int(0),
+                    may_discipline: true,
+                    monitor_only: false,
+                    warming_up: false,
+                    prefer: false,
+                    refclock: false,
+                    group: None,
                 }),
                 true,
             ),
@@ -753,6 +1861,12 @@ mod tests {
                     source_delay: NtpDuration::ZERO,
                     leap_indicator: NtpLeapIndicator::NoWarning,
                     last_update: NtpTimestamp::from_fixed_int(0),
+                    may_discipline: true,
+                    monitor_only: false,
+                    warming_up: false,
+                    prefer: false,
+                    refclock: false,
+                    group: None,
                 }),
                 true,
             ),
@@ -822,6 +1936,249 @@ mod tests {
         }
     }
 
+    fn step_region_controller() -> KalmanClockController<TestClock> {
+        let synchronization_config = SynchronizationConfig {
+            step_threshold: Some(NtpDuration::from_seconds(1.0)),
+            single_step_panic_threshold: StepThreshold {
+                forward: Some(NtpDuration::from_seconds(10.0)),
+                backward: Some(NtpDuration::from_seconds(10.0)),
+            },
+            ..SynchronizationConfig::default()
+        };
+        let mut algo = KalmanClockController::new(
+            TestClock {
+                has_steered: RefCell::new(false),
+                current_time: NtpTimestamp::from_fixed_int(0),
+            },
+            synchronization_config,
+            AlgorithmConfig::default(),
+        )
+        .unwrap();
+        algo.in_startup = false;
+        algo
+    }
+
+    #[test]
+    fn test_step_region() {
+        // Below the step threshold we slew, even though this is well above
+        // the default algorithm step threshold
+        let mut algo = step_region_controller();
+        let update = algo.steer_offset(0.5, 0.0);
+        assert!(update.next_update.is_some());
+        assert!(!matches!(
+            update.source_message,
+            Some(KalmanControllerMessage {
+                inner: KalmanControllerMessageInner::Step { .. }
+            })
+        ));
+
+        // Between the step and panic thresholds we step
+        let mut algo = step_region_controller();
+        let update = algo.steer_offset(5.0, 0.0);
+        assert!(update.next_update.is_none());
+        assert!(matches!(
+            update.source_message,
+            Some(KalmanControllerMessage {
+                inner: KalmanControllerMessageInner::Step { steer }
+            }) if steer == 5.0
+        ));
+    }
+
+    fn first_measurement_step_controller() -> KalmanClockController<TestClock> {
+        let synchronization_config = SynchronizationConfig {
+            step_threshold: Some(NtpDuration::from_seconds(10.0)),
+            first_measurement_step: true,
+            startup_step_panic_threshold: StepThreshold {
+                forward: Some(NtpDuration::from_seconds(100.0)),
+                backward: Some(NtpDuration::from_seconds(100.0)),
+            },
+            ..SynchronizationConfig::default()
+        };
+        KalmanClockController::new(
+            TestClock {
+                has_steered: RefCell::new(false),
+                current_time: NtpTimestamp::from_fixed_int(0),
+            },
+            synchronization_config,
+            AlgorithmConfig::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_first_measurement_step() {
+        // The first correction is a step, even below the step threshold
+        let mut algo = first_measurement_step_controller();
+        assert!(algo.in_startup);
+        let update = algo.steer_offset(5.0, 0.0);
+        assert!(update.next_update.is_none());
+        assert!(matches!(
+            update.source_message,
+            Some(KalmanControllerMessage {
+                inner: KalmanControllerMessageInner::Step { steer }
+            }) if steer == 5.0
+        ));
+
+        // Once out of startup we slew again
+        algo.in_startup = false;
+        let update = algo.steer_offset(5.0, 0.0);
+        assert!(update.next_update.is_some());
+        assert!(!matches!(
+            update.source_message,
+            Some(KalmanControllerMessage {
+                inner: KalmanControllerMessageInner::Step { .. }
+            })
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_first_measurement_step_above_panic_threshold() {
+        let mut algo = first_measurement_step_controller();
+        algo.steer_offset(-200.0, 0.0);
+    }
+
+    #[test]
+    fn test_panic_threshold_recovery() {
+        let mut algo = step_region_controller();
+        algo.synchronization_config.panic_threshold_recovery = Some(NtpDuration::from_seconds(5.0));
+        *algo.clock.has_steered.borrow_mut() = false;
+
+        let is_step = |update: &InternalStateUpdate<KalmanControllerMessage>| {
+            matches!(
+                update.source_message,
+                Some(KalmanControllerMessage {
+                    inner: KalmanControllerMessageInner::Step { .. }
+                })
+            )
+        };
+
+        // Exceeding the panic threshold holds corrections instead of exiting
+        let update = algo.steer_offset(10.1, 0.0);
+        assert!(!is_step(&update));
+        assert!(update.next_update.is_none());
+        assert!(algo.panic_hold);
+        assert_eq!(algo.timedata.clock_state, ClockState::PanicHold);
+        assert!(!*algo.clock.has_steered.borrow());
+
+        // An offset hovering around the threshold does not flap between
+        // stepping and holding
+        for offset in [9.9, 10.1, 9.5, -9.9, 10.05, 6.0] {
+            let update = algo.steer_offset(offset, 0.0);
+            assert!(!is_step(&update));
+            assert!(update.next_update.is_none());
+            assert!(algo.panic_hold);
+        }
+        assert!(!*algo.clock.has_steered.borrow());
+        assert_eq!(algo.timedata.accumulated_steps, NtpDuration::ZERO);
+
+        // Only below the recovery bound are corrections resumed
+        let update = algo.steer_offset(4.0, 0.0);
+        assert!(is_step(&update));
+        assert!(!algo.panic_hold);
+        assert!(*algo.clock.has_steered.borrow());
+    }
+
+    #[test]
+    fn test_max_slew_rate() {
+        let slew = |max_slew_rate_ppm: Option<f64>, offset: f64| {
+            let mut algo = step_region_controller();
+            algo.synchronization_config.max_slew_rate_ppm = max_slew_rate_ppm;
+            let update = algo.steer_offset(offset, 0.0);
+            (
+                update.next_update.unwrap().as_secs_f64(),
+                algo.desired_freq * 1e6,
+            )
+        };
+
+        // Without a cap, the maximum slew frequency of the algorithm (200ppm) applies
+        let (duration, freq) = slew(None, 0.5);
+        assert!((duration - 2500.0).abs() < 1e-6);
+        assert!((freq + 200.0).abs() < 1e-6);
+
+        // A cap of 50ppm spreads the same correction over four times as long
+        let (duration, freq) = slew(Some(50.0), 0.5);
+        assert!((duration - 10000.0).abs() < 1e-6);
+        assert!((freq + 50.0).abs() < 1e-6);
+
+        let (duration, freq) = slew(Some(50.0), -0.5);
+        assert!((duration - 10000.0).abs() < 1e-6);
+        assert!((freq - 50.0).abs() < 1e-6);
+
+        // A cap above the maximum of the algorithm has no effect
+        let (duration, _) = slew(Some(1000.0), 0.5);
+        assert!((duration - 2500.0).abs() < 1e-6);
+
+        // Small offsets that stay below the cap are slewed as before
+        let (duration, freq) = slew(Some(50.0), 1e-4);
+        assert!((duration - 8.0).abs() < 1e-6);
+        assert!((freq + 12.5).abs() < 1e-6);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_step_region_above_panic_threshold() {
+        let mut algo = step_region_controller();
+        algo.steer_offset(20.0, 0.0);
+    }
+
+    #[test]
+    fn test_force_sync_steps_below_step_threshold() {
+        let mut algo = step_region_controller();
+        algo.synchronization_config.minimum_agreeing_sources = 1;
+        let time = NtpTimestamp::from_fixed_int(0);
+        algo.sources.insert(
+            ClockId(0),
+            (Some(leap_smear_snapshot(0.5, 0.0, time)), true),
+        );
+
+        // Normally this offset is slewed away
+        let update = algo.clone().update_clock(time);
+        assert!(update.next_update.is_some());
+
+        let (mut updates, outcome) = algo.force_sync();
+        assert_eq!(outcome, ForceSyncOutcome::Corrected);
+        let update = updates.pop().unwrap();
+        assert!(matches!(
+            update.source_message,
+            Some(KalmanControllerMessage {
+                inner: KalmanControllerMessageInner::Step { steer }
+            }) if (steer - 0.5).abs() < 0.01
+        ));
+        assert!(!algo.force_step);
+    }
+
+    #[test]
+    fn test_force_sync_above_panic_threshold() {
+        let mut algo = step_region_controller();
+        algo.synchronization_config.minimum_agreeing_sources = 1;
+        let time = NtpTimestamp::from_fixed_int(0);
+        algo.sources.insert(
+            ClockId(0),
+            (Some(leap_smear_snapshot(20.0, 0.0, time)), true),
+        );
+
+        // The step is refused instead of exiting
+        let (updates, outcome) = algo.force_sync();
+        assert_eq!(outcome, ForceSyncOutcome::Refused);
+        assert!(!updates.iter().any(|update| matches!(
+            update.source_message,
+            Some(KalmanControllerMessage {
+                inner: KalmanControllerMessageInner::Step { .. }
+            })
+        )));
+        assert!(!algo.force_step);
+        assert!(!algo.force_step_refused);
+
+        // A forced step within the thresholds is still accepted afterwards
+        algo.sources.insert(
+            ClockId(0),
+            (Some(leap_smear_snapshot(5.0, 0.0, time)), true),
+        );
+        let (_, outcome) = algo.force_sync();
+        assert_eq!(outcome, ForceSyncOutcome::Corrected);
+    }
+
     #[test]
     #[should_panic]
     fn test_backward_step_panics_before_steer() {
@@ -78,19 +78,41 @@ pub(super) fn select(
     assert_eq!(maxlow, maxhigh);
     let max = maxlow;
 
-    if max >= synchronization_config.minimum_agreeing_sources && max * 4 > bounds.len() {
-        candidates
+    if max * 4 <= bounds.len() {
+        return vec![];
+    }
+
+    let selected: Vec<_> = candidates
+        .iter()
+        .filter(|snapshot| {
+            let radius = snapshot.offset_uncertainty() * algo_config.range_statistical_weight
+                + snapshot.delay * algo_config.range_delay_weight;
+            radius <= algo_config.maximum_source_uncertainty
+                && snapshot.offset() - radius <= maxthigh
+                && snapshot.offset() + radius >= maxtlow
+                && snapshot.leap_indicator.is_synchronized()
+        })
+        .copied()
+        .collect();
+
+    // A surviving reference clock may be enough on its own, periodic sources
+    // can't provide the time by themselves however.
+    let standalone = synchronization_config.standalone_reference_clock
+        && selected
+            .iter()
+            .any(|snapshot| snapshot.refclock && snapshot.period.is_none());
+
+    // Every group in the quorum needs enough agreeing sources of its own,
+    // again only counting sources that can provide the time by themselves.
+    let quorum_met = synchronization_config.quorum.is_met(
+        selected
             .iter()
-            .filter(|snapshot| {
-                let radius = snapshot.offset_uncertainty() * algo_config.range_statistical_weight
-                    + snapshot.delay * algo_config.range_delay_weight;
-                radius <= algo_config.maximum_source_uncertainty
-                    && snapshot.offset() - radius <= maxthigh
-                    && snapshot.offset() + radius >= maxtlow
-                    && snapshot.leap_indicator.is_synchronized()
-            })
-            .copied()
-            .collect()
+            .filter(|snapshot| snapshot.period.is_none())
+            .map(|snapshot| snapshot.group),
+    );
+
+    if (max >= synchronization_config.minimum_agreeing_sources || standalone) && quorum_met {
+        selected
     } else {
         vec![]
     }
@@ -101,6 +123,7 @@ mod tests {
     use crate::{
         ClockId,
         algorithm::kalman::source::KalmanState,
+        config::{Quorum, SourceGroup},
         packet::NtpLeapIndicator,
         time_types::{NtpDuration, NtpTimestamp},
     };
@@ -132,6 +155,12 @@ mod tests {
             source_delay: NtpDuration::from_seconds(0.01),
             leap_indicator: NtpLeapIndicator::NoWarning,
             last_update: NtpTimestamp::from_fixed_int(0),
+            may_discipline: true,
+            monitor_only: false,
+            warming_up: false,
+            prefer: false,
+            refclock: false,
+            group: None,
         }
     }
 
@@ -310,4 +339,92 @@ mod tests {
         let result = select(&sysconfig, &algconfig, &candidates);
         assert_eq!(result.len(), 0);
     }
+
+    #[test]
+    fn test_standalone_reference_clock() {
+        let mut refclock = snapshot_for_range(0.0, 0.01, 0.01, None);
+        refclock.refclock = true;
+        let mut pps = snapshot_for_range(0.0, 0.01, 0.01, Some(1.0));
+        pps.refclock = true;
+        let candidates = vec![refclock, pps];
+        let algconfig = AlgorithmConfig::default();
+
+        let sysconfig = SynchronizationConfig {
+            minimum_agreeing_sources: 3,
+            ..Default::default()
+        };
+        let result = select(&sysconfig, &algconfig, &candidates);
+        assert_eq!(result.len(), 0);
+
+        let sysconfig = SynchronizationConfig {
+            minimum_agreeing_sources: 3,
+            standalone_reference_clock: true,
+            ..Default::default()
+        };
+        let result = select(&sysconfig, &algconfig, &candidates);
+        assert_eq!(result.len(), 2);
+
+        // A periodic reference clock alone is not enough
+        let result = select(&sysconfig, &algconfig, &candidates[1..]);
+        assert_eq!(result.len(), 0);
+
+        // Nor is a network source
+        let candidates = vec![snapshot_for_range(0.0, 0.01, 0.01, None)];
+        let result = select(&sysconfig, &algconfig, &candidates);
+        assert_eq!(result.len(), 0);
+    }
+
+    #[test]
+    fn test_quorum() {
+        let gps = SourceGroup::new("gps").unwrap();
+        let internet = SourceGroup::new("internet").unwrap();
+        let in_group = |group| SourceSnapshot {
+            group: Some(group),
+            ..snapshot_for_range(0.0, 0.01, 0.01, None)
+        };
+        let algconfig = AlgorithmConfig::default();
+        let sysconfig = SynchronizationConfig {
+            minimum_agreeing_sources: 1,
+            quorum: Quorum::new(&[(gps, 2), (internet, 1)]).unwrap(),
+            ..Default::default()
+        };
+
+        let candidates = vec![in_group(gps), in_group(gps), in_group(internet)];
+        let result = select(&sysconfig, &algconfig, &candidates);
+        assert_eq!(result.len(), 3);
+
+        // Too few sources from one group
+        let result = select(&sysconfig, &algconfig, &candidates[1..]);
+        assert_eq!(result.len(), 0);
+
+        // No sources from the other group at all
+        let result = select(&sysconfig, &algconfig, &candidates[..2]);
+        assert_eq!(result.len(), 0);
+
+        // Ungrouped sources don't count towards any group
+        let candidates = vec![
+            in_group(gps),
+            in_group(internet),
+            snapshot_for_range(0.0, 0.01, 0.01, None),
+        ];
+        let result = select(&sysconfig, &algconfig, &candidates);
+        assert_eq!(result.len(), 0);
+
+        // Only agreeing sources count
+        let candidates = vec![
+            in_group(gps),
+            in_group(gps),
+            in_group(internet),
+            SourceSnapshot {
+                group: Some(gps),
+                ..snapshot_for_range(0.5, 0.01, 0.01, None)
+            },
+        ];
+        let sysconfig = SynchronizationConfig {
+            quorum: Quorum::new(&[(gps, 3)]).unwrap(),
+            ..sysconfig
+        };
+        let result = select(&sysconfig, &algconfig, &candidates);
+        assert_eq!(result.len(), 0);
+    }
 }
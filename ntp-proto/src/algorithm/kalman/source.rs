@@ -80,7 +80,7 @@ use crate::{
     algorithm::{
         InternalMeasurement, InternalSourceController, KalmanControllerMessage, KalmanSourceMessage,
     },
-    config::SourceConfig,
+    config::{FilterAlgorithm, SourceConfig},
     time_types::{NtpDuration, NtpTimestamp, PollInterval, PollIntervalLimits},
 };
 
@@ -466,6 +466,118 @@ impl<D: Debug + Copy + Clone, N: MeasurementNoiseEstimator<MeasurementDelay = D>
     }
 }
 
+/// Simpler alternative to the Kalman filter, estimating the offset of the
+/// source as the median offset of its most recent measurements. It does not
+/// track the frequency of the source, nor adapt the poll interval.
+#[derive(Debug, Clone)]
+struct MedianSourceFilter<
+    D: Debug + Copy + Clone,
+    N: MeasurementNoiseEstimator<MeasurementDelay = D> + Clone,
+> {
+    noise_estimator: N,
+    offsets: AveragingBuffer,
+    last_measurement: Option<InternalMeasurement<D>>,
+    poll_interval: PollInterval,
+
+    samples: i32,
+
+    // Total frequency steering of the clock, and the time up to which the
+    // buffered offsets have been corrected for it
+    steered_frequency: f64,
+    steered_until: Option<NtpTimestamp>,
+}
+
+impl<D: Debug + Copy + Clone, N: MeasurementNoiseEstimator<MeasurementDelay = D> + Clone>
+    MedianSourceFilter<D, N>
+{
+    fn median(&self) -> f64 {
+        let mut offsets = self.offsets.data;
+        let offsets = &mut offsets[..self.samples as usize];
+        offsets.sort_by(f64::total_cmp);
+
+        let mid = offsets.len() / 2;
+        if offsets.is_empty() {
+            0.0
+        } else if offsets.len().is_multiple_of(2) {
+            (offsets[mid - 1] + offsets[mid]) / 2.0
+        } else {
+            offsets[mid]
+        }
+    }
+
+    fn correct_period(&mut self, period: Option<f64>) {
+        if self.samples == 0 {
+            return;
+        }
+        if let Some(period) = period {
+            while self.median() > period / 2.0 {
+                for sample in &mut self.offsets.data {
+                    *sample -= period;
+                }
+            }
+
+            while self.median() < -period / 2.0 {
+                for sample in &mut self.offsets.data {
+                    *sample += period;
+                }
+            }
+        }
+    }
+
+    /// Correct the buffered offsets for the frequency steering of the clock
+    /// up to `time`, so that they all estimate the offset at that time
+    fn progress_time(&mut self, time: NtpTimestamp) {
+        let Some(steered_until) = self.steered_until else {
+            return;
+        };
+        let elapsed = (time - steered_until).to_seconds();
+        if elapsed > 0.0 {
+            for sample in &mut self.offsets.data {
+                *sample -= self.steered_frequency * elapsed;
+            }
+            self.steered_until = Some(time);
+        }
+    }
+
+    fn update(&mut self, measurement: InternalMeasurement<D>, period: Option<f64>) {
+        self.progress_time(measurement.localtime);
+
+        let mut offset = measurement.offset.to_seconds();
+        if let Some(period) = period {
+            let median = self.median();
+            while offset - median > period / 2.0 {
+                offset -= period;
+            }
+            while offset - median < -period / 2.0 {
+                offset += period;
+            }
+        }
+
+        self.noise_estimator.update(measurement.delay);
+        self.offsets.update(offset);
+        self.samples = (self.samples + 1).min(self.offsets.data.len() as i32);
+        self.last_measurement = Some(measurement);
+        self.correct_period(period);
+        trace!(samples = self.samples, "Median source update");
+    }
+
+    fn process_offset_steering(&mut self, steer: f64, period: Option<f64>) {
+        for sample in &mut self.offsets.data {
+            *sample -= steer;
+        }
+        self.correct_period(period);
+    }
+
+    fn process_frequency_steering(&mut self, time: NtpTimestamp, steer: f64, period: Option<f64>) {
+        // Slews are frequency steering, which only affect the offsets
+        // measured after them. Bring the earlier ones up to date first.
+        self.progress_time(time);
+        self.steered_until.get_or_insert(time);
+        self.steered_frequency += steer;
+        self.correct_period(period);
+    }
+}
+
 #[derive(Debug, Clone)]
 struct SourceFilter<D: Debug + Copy + Clone, N: MeasurementNoiseEstimator<MeasurementDelay = D>> {
     state: KalmanState,
@@ -695,6 +807,7 @@ enum SourceStateInner<
 > {
     Initial(InitialSourceFilter<D, N>),
     Stable(SourceFilter<D, N>),
+    Median(MedianSourceFilter<D, N>),
 }
 
 #[derive(Debug, Clone)]
@@ -717,6 +830,18 @@ impl<D: Debug + Copy + Clone, N: MeasurementNoiseEstimator<MeasurementDelay = D>
         }))
     }
 
+    pub(super) fn new_median(noise_estimator: N, poll_interval: PollInterval) -> Self {
+        SourceState(SourceStateInner::Median(MedianSourceFilter {
+            noise_estimator,
+            offsets: AveragingBuffer::default(),
+            last_measurement: None,
+            poll_interval,
+            samples: 0,
+            steered_frequency: 0.0,
+            steered_until: None,
+        }))
+    }
+
     // Returns whether the clock may need adjusting.
     pub fn update_self_using_measurement(
         &mut self,
@@ -729,6 +854,7 @@ impl<D: Debug + Copy + Clone, N: MeasurementNoiseEstimator<MeasurementDelay = D>
         let noise_estimator = match self {
             SourceState(SourceStateInner::Initial(filter)) => &filter.noise_estimator,
             SourceState(SourceStateInner::Stable(filter)) => &filter.noise_estimator,
+            SourceState(SourceStateInner::Median(filter)) => &filter.noise_estimator,
         };
         measurement.delay = noise_estimator.preprocess(measurement.delay);
 
@@ -802,6 +928,10 @@ impl<D: Debug + Copy + Clone, N: MeasurementNoiseEstimator<MeasurementDelay = D>
                     filter.update(source_config, algo_config, measurement, period)
                 }
             }
+            SourceStateInner::Median(filter) => {
+                filter.update(measurement, period);
+                true
+            }
         }
     }
 
@@ -817,35 +947,29 @@ impl<D: Debug + Copy + Clone, N: MeasurementNoiseEstimator<MeasurementDelay = D>
                 init_offset,
                 last_measurement: Some(last_measurement),
                 samples,
-            }) if *samples > 0 => {
-                let max_roundtrip = noise_estimator.get_max_roundtrip(samples)?;
-                Some(SourceSnapshot {
+            }) if *samples > 0 => Some(Self::offset_only_snapshot(
+                index,
+                config,
+                period,
+                noise_estimator.get_max_roundtrip(samples)?,
+                last_measurement,
+                init_offset.data[..*samples as usize]
+                    .iter()
+                    .copied()
+                    .sum::<f64>()
+                    / (*samples as f64),
+            )),
+            SourceStateInner::Median(filter) if filter.samples > 0 => {
+                Some(Self::offset_only_snapshot(
                     index,
-                    source_uncertainty: last_measurement.root_dispersion,
-                    source_delay: last_measurement.root_delay,
-                    leap_indicator: last_measurement.leap,
-                    last_update: last_measurement.localtime,
-                    delay: max_roundtrip,
+                    config,
                     period,
-                    state: KalmanState {
-                        state: Vector::new_vector([
-                            init_offset.data[..*samples as usize]
-                                .iter()
-                                .copied()
-                                .sum::<f64>()
-                                / (*samples as f64),
-                            0.0,
-                        ]),
-                        uncertainty: Matrix::new([
-                            [max_roundtrip, 0.0],
-                            [0.0, INITIALIZATION_FREQ_UNCERTAINTY],
-                        ]),
-                        time: last_measurement.localtime,
-                    },
-                    wander: config.initial_wander,
-                })
+                    filter.noise_estimator.get_max_roundtrip(&filter.samples)?,
+                    filter.last_measurement.as_ref()?,
+                    filter.median(),
+                ))
             }
-            SourceStateInner::Initial(_) => None,
+            SourceStateInner::Initial(_) | SourceStateInner::Median(_) => None,
             SourceStateInner::Stable(filter) => Some(SourceSnapshot {
                 index,
                 state: filter.state,
@@ -856,14 +980,68 @@ impl<D: Debug + Copy + Clone, N: MeasurementNoiseEstimator<MeasurementDelay = D>
                 source_delay: filter.last_measurement.root_delay,
                 leap_indicator: filter.last_measurement.leap,
                 last_update: filter.last_iter,
+                may_discipline: true,
+                monitor_only: false,
+                warming_up: false,
+                prefer: false,
+                refclock: false,
+                group: None,
             }),
         }
     }
 
+    /// Snapshot of a source for which only the offset is known, with a
+    /// large uncertainty in its frequency
+    fn offset_only_snapshot(
+        index: ClockId,
+        config: &AlgorithmConfig,
+        period: Option<f64>,
+        max_roundtrip: f64,
+        last_measurement: &InternalMeasurement<D>,
+        offset: f64,
+    ) -> SourceSnapshot {
+        SourceSnapshot {
+            index,
+            source_uncertainty: last_measurement.root_dispersion,
+            source_delay: last_measurement.root_delay,
+            leap_indicator: last_measurement.leap,
+            last_update: last_measurement.localtime,
+            delay: max_roundtrip,
+            period,
+            state: KalmanState {
+                state: Vector::new_vector([offset, 0.0]),
+                uncertainty: Matrix::new([
+                    [max_roundtrip, 0.0],
+                    [0.0, INITIALIZATION_FREQ_UNCERTAINTY],
+                ]),
+                time: last_measurement.localtime,
+            },
+            wander: config.initial_wander,
+            may_discipline: true,
+            monitor_only: false,
+            warming_up: false,
+            prefer: false,
+            refclock: false,
+            group: None,
+        }
+    }
+
+    // Estimate of the standard deviation of the offset measurements
+    // caused by measurement noise.
+    fn jitter(&self) -> f64 {
+        let noise_estimator = match &self.0 {
+            SourceStateInner::Initial(filter) => &filter.noise_estimator,
+            SourceStateInner::Stable(filter) => &filter.noise_estimator,
+            SourceStateInner::Median(filter) => &filter.noise_estimator,
+        };
+        noise_estimator.get_noise_estimate().sqrt()
+    }
+
     pub fn get_desired_poll(&self, limits: &PollIntervalLimits) -> PollInterval {
         match &self.0 {
             SourceStateInner::Initial(_) => limits.min,
             SourceStateInner::Stable(filter) => filter.desired_poll_interval,
+            SourceStateInner::Median(filter) => filter.poll_interval.clamp(limits.min, limits.max),
         }
     }
 
@@ -875,6 +1053,7 @@ impl<D: Debug + Copy + Clone, N: MeasurementNoiseEstimator<MeasurementDelay = D>
         match &mut self.0 {
             SourceStateInner::Initial(filter) => filter.process_offset_steering(steer, period),
             SourceStateInner::Stable(filter) => filter.process_offset_steering(steer, period),
+            SourceStateInner::Median(filter) => filter.process_offset_steering(steer, period),
         }
     }
 
@@ -889,6 +1068,9 @@ impl<D: Debug + Copy + Clone, N: MeasurementNoiseEstimator<MeasurementDelay = D>
             SourceStateInner::Stable(filter) => {
                 filter.process_frequency_steering(time, steer, period);
             }
+            SourceStateInner::Median(filter) => {
+                filter.process_frequency_steering(time, steer, period);
+            }
         }
     }
 }
@@ -903,6 +1085,9 @@ pub struct KalmanSourceController<
     period: Option<f64>,
     algo_config: AlgorithmConfig,
     source_config: SourceConfig,
+    refclock: bool,
+    // Number of measurements used so far, counted up to the warmup
+    samples: u8,
 }
 
 pub type TwoWayKalmanSourceController = KalmanSourceController<NtpDuration, AveragingBuffer>;
@@ -918,13 +1103,22 @@ impl<D: Debug + Copy + Clone, N: MeasurementNoiseEstimator<MeasurementDelay = D>
         period: Option<f64>,
         source_config: SourceConfig,
         noise_estimator: N,
+        refclock: bool,
     ) -> Self {
+        let state = match source_config.filter_algorithm {
+            FilterAlgorithm::Kalman => SourceState::new(noise_estimator),
+            FilterAlgorithm::Median => {
+                SourceState::new_median(noise_estimator, source_config.initial_poll_interval)
+            }
+        };
         KalmanSourceController {
             index,
-            state: SourceState::new(noise_estimator),
+            state,
             period,
             algo_config,
             source_config,
+            refclock,
+            samples: 0,
         }
     }
 }
@@ -959,9 +1153,32 @@ impl<
             measurement,
             self.period,
         ) {
+            let jitter = self.state.jitter();
+            let may_discipline = self
+                .source_config
+                .max_jitter_for_discipline
+                .is_none_or(|max_jitter| jitter <= max_jitter.to_seconds());
+            if !may_discipline {
+                debug!(jitter, "Source too jittery to discipline the clock");
+            }
+            self.samples = self.samples.saturating_add(1);
+            let warming_up = self.samples < self.source_config.warmup_samples;
+            if warming_up {
+                debug!(samples = self.samples, "Source still warming up");
+            }
             self.state
                 .snapshot(self.index, &self.algo_config, self.period)
-                .map(|snapshot| KalmanSourceMessage { inner: snapshot })
+                .map(|snapshot| KalmanSourceMessage {
+                    inner: SourceSnapshot {
+                        may_discipline,
+                        monitor_only: self.source_config.monitor_only,
+                        warming_up,
+                        prefer: self.source_config.prefer,
+                        refclock: self.refclock,
+                        group: self.source_config.group,
+                        ..snapshot
+                    },
+                })
         } else {
             None
         }
@@ -2443,4 +2660,183 @@ mod tests {
         assert_eq!(source.precision_score, 0);
         assert!((source.clock_wander - 1e-8).abs() < 1e-12);
     }
+
+    #[test]
+    fn test_jittery_source_may_not_discipline() {
+        let base = NtpTimestamp::from_fixed_int(0);
+
+        let feed = |max_jitter: f64| {
+            let mut controller = TwoWayKalmanSourceController::new(
+                ClockId(0),
+                AlgorithmConfig::default(),
+                None,
+                SourceConfig {
+                    max_jitter_for_discipline: Some(NtpDuration::from_seconds(max_jitter)),
+                    ..SourceConfig::default()
+                },
+                AveragingBuffer::default(),
+                false,
+            );
+
+            let mut last = None;
+            for i in 0..4 {
+                let delay = if i % 2 == 0 { 1e-3 } else { 50e-3 };
+                last = controller.handle_measurement(InternalMeasurement {
+                    delay: NtpDuration::from_seconds(delay),
+                    offset: NtpDuration::from_seconds(0.0),
+                    localtime: base + NtpDuration::from_seconds(f64::from(i)),
+
+                    root_delay: NtpDuration::default(),
+                    root_dispersion: NtpDuration::default(),
+                    leap: NtpLeapIndicator::NoWarning,
+                    precision: 0,
+                });
+            }
+            last.unwrap().inner
+        };
+
+        // The source is still reported, but held back from disciplining the clock
+        assert!(!feed(1e-3).may_discipline);
+        assert!(feed(1.0).may_discipline);
+    }
+
+    #[test]
+    fn test_filter_algorithm_selection() {
+        let median = SourceConfig {
+            filter_algorithm: FilterAlgorithm::Median,
+            ..SourceConfig::default()
+        };
+
+        let two_way = |source_config| {
+            TwoWayKalmanSourceController::new(
+                ClockId(0),
+                AlgorithmConfig::default(),
+                None,
+                source_config,
+                AveragingBuffer::default(),
+                false,
+            )
+        };
+        let one_way = |source_config| {
+            OneWayKalmanSourceController::new(
+                ClockId(0),
+                AlgorithmConfig::default(),
+                Some(1.0),
+                source_config,
+                FixedMeasurementNoise {
+                    precision: 1e-9,
+                    accuracy: 1e-6,
+                },
+                true,
+            )
+        };
+
+        assert!(matches!(
+            two_way(SourceConfig::default()).state.0,
+            SourceStateInner::Initial(_)
+        ));
+        assert!(matches!(
+            two_way(median).state.0,
+            SourceStateInner::Median(_)
+        ));
+        assert!(matches!(
+            one_way(SourceConfig::default()).state.0,
+            SourceStateInner::Initial(_)
+        ));
+        assert!(matches!(
+            one_way(median).state.0,
+            SourceStateInner::Median(_)
+        ));
+    }
+
+    #[test]
+    fn test_median_filter() {
+        let base = NtpTimestamp::from_fixed_int(0);
+        let mut controller = TwoWayKalmanSourceController::new(
+            ClockId(0),
+            AlgorithmConfig::default(),
+            None,
+            SourceConfig {
+                filter_algorithm: FilterAlgorithm::Median,
+                ..SourceConfig::default()
+            },
+            AveragingBuffer::default(),
+            false,
+        );
+
+        let mut measure = |i: usize| {
+            let offset = if i == 2 { 1.0 } else { 1e-3 * i as f64 };
+            controller
+                .handle_measurement(InternalMeasurement {
+                    delay: NtpDuration::from_seconds(1e-3),
+                    offset: NtpDuration::from_seconds(offset),
+                    localtime: base + NtpDuration::from_seconds(i as f64),
+
+                    root_delay: NtpDuration::default(),
+                    root_dispersion: NtpDuration::default(),
+                    leap: NtpLeapIndicator::NoWarning,
+                    precision: 0,
+                })
+                .unwrap()
+                .inner
+                .state
+                .offset()
+        };
+
+        // Reported from the first measurement on, unaffected by outliers
+        assert!((measure(0) - 0.0).abs() < 1e-9);
+        assert!((measure(1) - 0.5e-3).abs() < 1e-9);
+        assert!((measure(2) - 1e-3).abs() < 1e-9);
+        assert!((measure(3) - 2e-3).abs() < 1e-9);
+
+        // Only the most recent measurements are considered
+        for i in 4..12 {
+            measure(i);
+        }
+        assert!((measure(12) - 8.5e-3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_median_filter_steering() {
+        let base = NtpTimestamp::from_fixed_int(0);
+        let at = |seconds: f64| base + NtpDuration::from_seconds(seconds);
+        let mut state =
+            SourceState::new_median(AveragingBuffer::default(), PollInterval::default());
+
+        let measure = |state: &mut SourceState<_, _>, time: f64, offset: f64| {
+            state.update_self_using_raw_measurement(
+                &SourceConfig::default(),
+                &AlgorithmConfig::default(),
+                InternalMeasurement {
+                    delay: NtpDuration::from_seconds(1e-3),
+                    offset: NtpDuration::from_seconds(offset),
+                    localtime: at(time),
+
+                    root_delay: NtpDuration::default(),
+                    root_dispersion: NtpDuration::default(),
+                    leap: NtpLeapIndicator::NoWarning,
+                    precision: 0,
+                },
+                None,
+            );
+            state
+                .snapshot(ClockId(0), &AlgorithmConfig::default(), None)
+                .unwrap()
+                .state
+                .offset()
+        };
+
+        for i in 0..3 {
+            assert!((measure(&mut state, f64::from(i), 0.1) - 0.1).abs() < 1e-9);
+        }
+
+        // Slewing away half of the offset also corrects the earlier offsets
+        state.process_frequency_steering(at(10.0), 1e-3, None);
+        state.process_frequency_steering(at(60.0), -1e-3, None);
+        assert!((measure(&mut state, 70.0, 0.05) - 0.05).abs() < 1e-9);
+
+        // A step corrects them right away
+        state.process_offset_steering(0.05, None);
+        assert!((measure(&mut state, 80.0, 0.0) - 0.0).abs() < 1e-9);
+    }
 }
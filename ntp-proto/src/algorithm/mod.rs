@@ -6,6 +6,7 @@ use std::{
 };
 
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::error;
 
 use crate::{
     ClockId, NtpLeapIndicator, PollInterval,
@@ -35,6 +36,8 @@ pub struct InternalStateUpdate<ControllerMessage> {
     pub time_snapshot: Option<TimeSnapshot>,
     // Update to the used sources, if any
     pub used_sources: Option<Vec<ClockId>>,
+    // Update to the sources rejected for disagreeing with the others, if any
+    pub outlier_sources: Option<Vec<ClockId>>,
     // Requested timestamp for next non-measurement update
     pub next_update: Option<Duration>,
 }
@@ -48,6 +51,7 @@ impl<ControllerMessage> Default for InternalStateUpdate<ControllerMessage> {
             source_message: None,
             time_snapshot: None,
             used_sources: None,
+            outlier_sources: None,
             next_update: None,
         }
     }
@@ -105,7 +109,29 @@ pub trait InternalTimeSyncController: Sized + Send + 'static {
         message: Self::SourceMessage,
     ) -> InternalStateUpdate<Self::ControllerMessage>;
     /// Non-message driven update (queued via next_update)
-    fn time_update(&mut self) -> InternalStateUpdate<Self::ControllerMessage>;
+    fn time_update(
+        &mut self,
+    ) -> Result<InternalStateUpdate<Self::ControllerMessage>, <Self::Clock as NtpClock>::Error>;
+    /// Correct the clock right away based on the current estimate, stepping
+    /// it even when the offset is below the step threshold. Steps exceeding
+    /// the panic thresholds are refused. The updates are applied in order.
+    fn force_sync(
+        &mut self,
+    ) -> (
+        Vec<InternalStateUpdate<Self::ControllerMessage>>,
+        ForceSyncOutcome,
+    );
+}
+
+/// Result of a forced synchronization
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForceSyncOutcome {
+    /// The clock was corrected with the current estimate
+    Corrected,
+    /// The sources do not agree on the current time, so nothing was done
+    NoConsensus,
+    /// The correction exceeds the panic thresholds and was not applied
+    Refused,
 }
 
 pub trait InternalSourceController: Sized + Send + 'static {
@@ -189,6 +215,13 @@ pub trait TimeSyncController: Sized + Send + Sync + 'static {
     ) -> Self::OneWaySourceController;
     /// Current synchronization state
     fn synchronization_state(&self) -> (TimeSnapshot, Vec<ClockId>);
+    /// Sources left out of the last selection because they disagree with
+    /// the other sources
+    fn outlier_sources(&self) -> Vec<ClockId>;
+    /// Request an immediate correction of the clock, applied by `run`, which
+    /// sends the outcome on `reply`. Steps exceeding the panic thresholds are
+    /// refused.
+    fn force_sync(&self, reply: tokio::sync::oneshot::Sender<ForceSyncOutcome>);
     /// Run the internal watchdog and messaging.
     fn run(&self) -> impl Future<Output = ()> + Send;
 }
@@ -205,6 +238,7 @@ pub struct TimeSyncControllerWrapper<T: InternalTimeSyncController> {
     twoway_sources: Mutex<Vec<Weak<Mutex<T::NtpSourceController>>>>,
     snapshot: Mutex<TimeSnapshot>,
     used_sources: Mutex<Vec<ClockId>>,
+    outlier_sources: Mutex<Vec<ClockId>>,
     has_taken_control: Mutex<bool>,
 }
 
@@ -230,6 +264,7 @@ impl<T: InternalTimeSyncController> TimeSyncController for TimeSyncControllerWra
             twoway_sources: Mutex::new(Vec::new()),
             snapshot: Mutex::new(TimeSnapshot::default()),
             used_sources: Mutex::new(Vec::new()),
+            outlier_sources: Mutex::new(Vec::new()),
             has_taken_control: Mutex::new(false),
         })
     }
@@ -249,6 +284,7 @@ impl<T: InternalTimeSyncController> TimeSyncController for TimeSyncControllerWra
             id,
             inner: Arc::new(Mutex::new(source_controller)),
             last_outgoing_measurement: None,
+            delay_asymmetry: source_config.delay_asymmetry,
             messages_for_system: self.messages_for_system_sender.clone(),
         };
         self.twoway_sources
@@ -292,6 +328,16 @@ impl<T: InternalTimeSyncController> TimeSyncController for TimeSyncControllerWra
         )
     }
 
+    fn outlier_sources(&self) -> Vec<ClockId> {
+        self.outlier_sources.lock().unwrap().clone()
+    }
+
+    fn force_sync(&self, reply: tokio::sync::oneshot::Sender<ForceSyncOutcome>) {
+        self.messages_for_system_sender
+            .send((ClockId::SYSTEM, WrapperMessage::ForceSync(reply)))
+            .ok();
+    }
+
     async fn run(&self) {
         let mut messages_for_system = self.messages_for_system.lock().unwrap().take().unwrap();
         let mut sleeper = std::pin::pin!(SingleshotSleep::new_disabled());
@@ -301,23 +347,7 @@ impl<T: InternalTimeSyncController> TimeSyncController for TimeSyncControllerWra
                     match message {
                         WrapperMessage::SourceMessage(message) => {
                             let update = self.inner.lock().unwrap().source_message(clock_id, message);
-                            if let Some(source_message) = update.source_message {
-                                for source in self.oneway_sources.lock().unwrap().iter().filter_map(Weak::upgrade) {
-                                    source.lock().unwrap().handle_message(source_message.clone());
-                                }
-                                for source in self.twoway_sources.lock().unwrap().iter().filter_map(Weak::upgrade) {
-                                    source.lock().unwrap().handle_message(source_message.clone());
-                                }
-                            }
-                            if let Some(time_snapshot) = update.time_snapshot {
-                                *self.snapshot.lock().unwrap() = time_snapshot;
-                            }
-                            if let Some(used_sources) = update.used_sources {
-                                *self.used_sources.lock().unwrap() = used_sources;
-                            }
-                            if let Some(next_update) = update.next_update {
-                                sleeper.as_mut().reset(tokio::time::Instant::now() + next_update);
-                            }
+                            self.apply_update(update, sleeper.as_mut());
                         },
                         WrapperMessage::UsabilityChange(usable) => {
                             self.inner.lock().unwrap().source_update(clock_id, usable);
@@ -325,26 +355,21 @@ impl<T: InternalTimeSyncController> TimeSyncController for TimeSyncControllerWra
                         WrapperMessage::Dropped => {
                             self.inner.lock().unwrap().remove_source(clock_id);
                         },
+                        WrapperMessage::ForceSync(reply) => {
+                            let (updates, outcome) = self.inner.lock().unwrap().force_sync();
+                            for update in updates {
+                                self.apply_update(update, sleeper.as_mut());
+                            }
+                            reply.send(outcome).ok();
+                        },
                     }
                 },
                 () = sleeper.as_mut() => {
                     let update = self.inner.lock().unwrap().time_update();
-                    if let Some(source_message) = update.source_message {
-                        for source in self.oneway_sources.lock().unwrap().iter().filter_map(Weak::upgrade) {
-                            source.lock().unwrap().handle_message(source_message.clone());
-                        }
-                        for source in self.twoway_sources.lock().unwrap().iter().filter_map(Weak::upgrade) {
-                            source.lock().unwrap().handle_message(source_message.clone());
-                        }
-                    }
-                    if let Some(time_snapshot) = update.time_snapshot {
-                        *self.snapshot.lock().unwrap() = time_snapshot;
-                    }
-                    if let Some(used_sources) = update.used_sources {
-                        *self.used_sources.lock().unwrap() = used_sources;
-                    }
-                    if let Some(next_update) = update.next_update {
-                        sleeper.as_mut().reset(tokio::time::Instant::now() + next_update);
+                    match update {
+                        Ok(update) => self.apply_update(update, sleeper.as_mut()),
+                        // The next measurement updates the clock instead
+                        Err(e) => error!(error = %e, "Could not read the clock for a time update"),
                     }
                 },
             }
@@ -352,6 +377,53 @@ impl<T: InternalTimeSyncController> TimeSyncController for TimeSyncControllerWra
     }
 }
 
+impl<T: InternalTimeSyncController> TimeSyncControllerWrapper<T> {
+    fn apply_update(
+        &self,
+        update: InternalStateUpdate<T::ControllerMessage>,
+        sleeper: Pin<&mut SingleshotSleep>,
+    ) {
+        if let Some(source_message) = update.source_message {
+            for source in self
+                .oneway_sources
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(Weak::upgrade)
+            {
+                source
+                    .lock()
+                    .unwrap()
+                    .handle_message(source_message.clone());
+            }
+            for source in self
+                .twoway_sources
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(Weak::upgrade)
+            {
+                source
+                    .lock()
+                    .unwrap()
+                    .handle_message(source_message.clone());
+            }
+        }
+        if let Some(time_snapshot) = update.time_snapshot {
+            *self.snapshot.lock().unwrap() = time_snapshot;
+        }
+        if let Some(used_sources) = update.used_sources {
+            *self.used_sources.lock().unwrap() = used_sources;
+        }
+        if let Some(outlier_sources) = update.outlier_sources {
+            *self.outlier_sources.lock().unwrap() = outlier_sources;
+        }
+        if let Some(next_update) = update.next_update {
+            sleeper.reset(tokio::time::Instant::now() + next_update);
+        }
+    }
+}
+
 pub trait SourceController: Sized + Send + 'static {
     fn handle_measurement(&mut self, measurement: Measurement);
 
@@ -366,6 +438,7 @@ enum WrapperMessage<SourceMessage> {
     SourceMessage(SourceMessage),
     UsabilityChange(bool),
     Dropped,
+    ForceSync(tokio::sync::oneshot::Sender<ForceSyncOutcome>),
 }
 
 pub struct OneWaySourceControllerWrapper<T: InternalSourceController<MeasurementDelay = ()>> {
@@ -429,6 +502,7 @@ pub struct TwoWaySourceControllerWrapper<
     id: ClockId,
     inner: Arc<Mutex<T>>,
     last_outgoing_measurement: Option<Measurement>,
+    delay_asymmetry: NtpDuration,
     messages_for_system:
         tokio::sync::mpsc::UnboundedSender<(ClockId, WrapperMessage<T::SourceMessage>)>,
 }
@@ -462,9 +536,12 @@ impl<T: InternalSourceController<MeasurementDelay = NtpDuration>> SourceControll
                     .handle_measurement(InternalMeasurement {
                         delay: (measurement.receiver_ts - last_outgoing.sender_ts)
                             - (measurement.sender_ts - last_outgoing.receiver_ts),
+                        // A longer path towards the source makes the source
+                        // appear ahead by half the difference, correct for that.
                         offset: ((last_outgoing.receiver_ts - last_outgoing.sender_ts)
                             + (measurement.sender_ts - measurement.receiver_ts))
-                            / 2,
+                            / 2
+                            - self.delay_asymmetry / 2,
                         localtime: measurement.receiver_ts,
                         root_delay: measurement.root_delay,
                         root_dispersion: measurement.root_dispersion,
@@ -601,6 +678,7 @@ mod tests {
                 last_measurement: None,
             })),
             last_outgoing_measurement: None,
+            delay_asymmetry: NtpDuration::ZERO,
             messages_for_system: tokio::sync::mpsc::unbounded_channel().0,
         };
         measurement_outgoing.sender_ts = NtpTimestamp::from_fixed_int(0);
@@ -637,6 +715,7 @@ mod tests {
             })),
             messages_for_system: tokio::sync::mpsc::unbounded_channel().0,
             last_outgoing_measurement: None,
+            delay_asymmetry: NtpDuration::ZERO,
         };
         measurement_outgoing.sender_ts = NtpTimestamp::from_fixed_int(0);
         measurement_outgoing.receiver_ts = NtpTimestamp::from_fixed_int(2);
@@ -672,6 +751,7 @@ mod tests {
             })),
             messages_for_system: tokio::sync::mpsc::unbounded_channel().0,
             last_outgoing_measurement: None,
+            delay_asymmetry: NtpDuration::ZERO,
         };
         measurement_outgoing.sender_ts = NtpTimestamp::from_fixed_int(0);
         measurement_outgoing.receiver_ts = NtpTimestamp::from_fixed_int(0);
@@ -700,4 +780,57 @@ mod tests {
             NtpDuration::from_fixed_int(-2)
         );
     }
+
+    #[test]
+    fn test_delay_asymmetry() {
+        let measurement_outgoing = Measurement {
+            sender_id: ClockId::SYSTEM,
+            receiver_id: ClockId(1),
+            sender_ts: NtpTimestamp::from_fixed_int(0),
+            receiver_ts: NtpTimestamp::from_fixed_int(3),
+            root_delay: NtpDuration::from_fixed_int(0),
+            root_dispersion: NtpDuration::from_fixed_int(0),
+            leap: NtpLeapIndicator::NoWarning,
+            precision: 0,
+        };
+        let measurement_incoming = Measurement {
+            sender_id: ClockId(1),
+            receiver_id: ClockId::SYSTEM,
+            sender_ts: NtpTimestamp::from_fixed_int(4),
+            receiver_ts: NtpTimestamp::from_fixed_int(5),
+            root_delay: NtpDuration::from_fixed_int(0),
+            root_dispersion: NtpDuration::from_fixed_int(0),
+            leap: NtpLeapIndicator::NoWarning,
+            precision: 0,
+        };
+
+        let offset = |delay_asymmetry| {
+            let mut controller = TwoWaySourceControllerWrapper {
+                id: ClockId(1),
+                inner: Arc::new(Mutex::new(TestInternalSourceController {
+                    last_measurement: None,
+                })),
+                messages_for_system: tokio::sync::mpsc::unbounded_channel().0,
+                last_outgoing_measurement: None,
+                delay_asymmetry,
+            };
+            controller.handle_measurement(measurement_outgoing);
+            controller.handle_measurement(measurement_incoming);
+            let measurement = controller.inner.lock().unwrap().last_measurement.unwrap();
+            // The asymmetry does not change the measured round trip delay
+            assert_eq!(measurement.delay, NtpDuration::from_fixed_int(4));
+            measurement.offset
+        };
+
+        // Requests take 3 and responses 1, which looks like the source is ahead
+        assert_eq!(offset(NtpDuration::ZERO), NtpDuration::from_fixed_int(1));
+        assert_eq!(
+            offset(NtpDuration::from_fixed_int(2)),
+            NtpDuration::from_fixed_int(0)
+        );
+        assert_eq!(
+            offset(NtpDuration::from_fixed_int(-2)),
+            NtpDuration::from_fixed_int(2)
+        );
+    }
 }
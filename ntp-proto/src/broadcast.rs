@@ -0,0 +1,630 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+use tracing::{debug, info};
+
+use crate::{
+    ClockId,
+    algorithm::{Measurement, SourceController},
+    config::SourceConfig,
+    identifiers::ReferenceId,
+    packet::{
+        NoCipher, NtpAssociationMode, NtpLeapIndicator, NtpPacket, RequestIdentifier, Stratum,
+    },
+    source::{NtpSourceSnapshot, ObservableSourceState, ProtocolVersion, Reach},
+    system::NtpSourceInfo,
+    time_types::{NtpDuration, NtpTimestamp, PollInterval},
+};
+
+/// Number of client/server exchanges used to calibrate the delay from the
+/// broadcast server before its broadcasts are used as measurements
+const CALIBRATION_EXCHANGES: usize = 4;
+
+#[derive(Debug)]
+pub enum BroadcastAction {
+    /// Nothing needs to be done
+    Ignore,
+    /// Send a calibration request to the broadcast server, and pass its
+    /// response to [`BroadcastClient::handle_calibration_response`]
+    SendCalibration(Vec<u8>),
+    /// The state of the source changed and should be published again
+    Update,
+}
+
+#[derive(Debug)]
+enum BroadcastState {
+    /// No valid broadcast has been received yet
+    WaitingForBroadcast,
+    /// Exchanging client/server packets to determine the delay from the server
+    Calibrating {
+        request: Option<RequestIdentifier>,
+        delays: Vec<NtpDuration>,
+    },
+    /// The delay is known and broadcasts are used as measurements
+    Listening {
+        /// Estimate of the one way delay from the server to us
+        delay: NtpDuration,
+    },
+}
+
+/// Client side of the NTP broadcast mode (RFC 5905, section 3).
+///
+/// Broadcasts only carry the transmit time of the server, so on the first
+/// valid broadcast the client calibrates the delay from the server through a
+/// few ordinary client/server exchanges. After that, every broadcast becomes a
+/// one way measurement, corrected by half of the smallest calibrated round
+/// trip delay.
+pub struct BroadcastClient<Controller: SourceController> {
+    id: ClockId,
+    server: SocketAddr,
+    source_id: ReferenceId,
+    source_config: SourceConfig,
+    controller: Controller,
+    state: BroadcastState,
+
+    reach: Reach,
+    stratum: u8,
+    reference_id: ReferenceId,
+    broadcast_interval: PollInterval,
+    last_transmit: Option<NtpTimestamp>,
+    last_broadcast_received: NtpTimestamp,
+
+    source_info: Arc<RwLock<NtpSourceInfo>>,
+    source_snapshots: Arc<Mutex<HashMap<ClockId, NtpSourceSnapshot>>>,
+}
+
+impl<Controller: SourceController> BroadcastClient<Controller> {
+    pub(crate) fn new(
+        server: SocketAddr,
+        source_config: SourceConfig,
+        controller: Controller,
+        id: ClockId,
+        source_info: Arc<RwLock<NtpSourceInfo>>,
+        source_snapshots: Arc<Mutex<HashMap<ClockId, NtpSourceSnapshot>>>,
+    ) -> Self {
+        let mut client = BroadcastClient {
+            id,
+            server,
+            source_id: ReferenceId::from_ip(server.ip()),
+            source_config,
+            controller,
+            state: BroadcastState::WaitingForBroadcast,
+
+            reach: Reach::with_window(source_config.reach_window),
+            stratum: Stratum::UNSYNCHRONIZED.to_bits(),
+            reference_id: ReferenceId::NONE,
+            broadcast_interval: source_config.initial_poll_interval,
+            last_transmit: None,
+            last_broadcast_received: NtpTimestamp::default(),
+
+            source_info,
+            source_snapshots,
+        };
+        client.update_snapshot();
+        client
+    }
+
+    /// Handle a packet received on the broadcast socket
+    pub fn handle_broadcast(
+        &mut self,
+        sender: IpAddr,
+        data: &[u8],
+        recv_time: NtpTimestamp,
+    ) -> BroadcastAction {
+        if sender != self.server.ip() {
+            debug!(?sender, "Ignoring broadcast from unexpected sender");
+            return BroadcastAction::Ignore;
+        }
+
+        // Broadcasts are never authenticated, so they can't meet the requirement
+        if self.source_config.require_authenticated {
+            debug!("Ignoring unauthenticated broadcast");
+            return BroadcastAction::Ignore;
+        }
+
+        let packet = match NtpPacket::deserialize(data, &NoCipher) {
+            Ok((packet, _)) => packet,
+            Err(e) => {
+                debug!(error = ?e, "Ignoring broadcast that could not be parsed");
+                return BroadcastAction::Ignore;
+            }
+        };
+
+        if packet.mode() != NtpAssociationMode::Broadcast {
+            debug!(mode = ?packet.mode(), "Ignoring packet that is not a broadcast");
+            return BroadcastAction::Ignore;
+        }
+
+        let stratum = Stratum::from_bits(packet.stratum());
+        if stratum.is_kiss_of_death()
+            || stratum.is_unsynchronized()
+            || packet.leap() == NtpLeapIndicator::Unknown
+        {
+            debug!("Ignoring broadcast from an unsynchronized server");
+            return BroadcastAction::Ignore;
+        }
+
+        // A spoofed or replayed broadcast will not carry a fresh transmit time
+        let transmit = packet.transmit_timestamp();
+        if transmit == NtpTimestamp::default()
+            || self
+                .last_transmit
+                .is_some_and(|last| !last.is_before(transmit))
+        {
+            debug!("Ignoring duplicate or replayed broadcast");
+            return BroadcastAction::Ignore;
+        }
+
+        self.last_transmit = Some(transmit);
+        self.last_broadcast_received = recv_time;
+        self.reach.received_packet();
+        self.stratum = packet.stratum();
+        self.reference_id = packet.reference_id();
+        self.broadcast_interval = packet.poll().clamp(
+            self.source_config.poll_interval_limits.min,
+            self.source_config.poll_interval_limits.max,
+        );
+
+        match self.state {
+            BroadcastState::WaitingForBroadcast => {
+                info!("Received first broadcast, calibrating delay");
+                self.state = BroadcastState::Calibrating {
+                    request: None,
+                    delays: Vec::with_capacity(CALIBRATION_EXCHANGES),
+                };
+                self.update_snapshot();
+                BroadcastAction::SendCalibration(self.calibration_request())
+            }
+            BroadcastState::Calibrating { .. } => {
+                self.update_snapshot();
+                BroadcastAction::Update
+            }
+            BroadcastState::Listening { delay } => {
+                self.update_snapshot();
+                self.controller.handle_measurement(Measurement {
+                    sender_id: self.id,
+                    receiver_id: ClockId::SYSTEM,
+                    sender_ts: transmit + delay,
+                    receiver_ts: recv_time,
+                    root_delay: packet.root_delay() + delay * 2u8,
+                    root_dispersion: packet.root_dispersion(),
+                    leap: packet.leap(),
+                    precision: packet.precision(),
+                });
+                BroadcastAction::Update
+            }
+        }
+    }
+
+    /// Handle the response to a calibration request sent at `send_time`
+    pub fn handle_calibration_response(
+        &mut self,
+        data: &[u8],
+        send_time: NtpTimestamp,
+        recv_time: NtpTimestamp,
+    ) -> BroadcastAction {
+        let BroadcastState::Calibrating { request, delays } = &mut self.state else {
+            debug!("Ignoring calibration response outside of calibration");
+            return BroadcastAction::Ignore;
+        };
+
+        let packet = match NtpPacket::deserialize(data, &NoCipher) {
+            Ok((packet, _)) => packet,
+            Err(e) => {
+                debug!(error = ?e, "Ignoring calibration response that could not be parsed");
+                return BroadcastAction::Ignore;
+            }
+        };
+
+        let Some(identifier) = *request else {
+            debug!("Ignoring calibration response without outstanding request");
+            return BroadcastAction::Ignore;
+        };
+        if packet.mode() != NtpAssociationMode::Server
+            || packet.is_kiss()
+            || !packet.valid_unauthenticated_server_response(identifier)
+        {
+            debug!("Ignoring calibration response that does not answer our request");
+            return BroadcastAction::Ignore;
+        }
+        *request = None;
+
+        let delay =
+            (recv_time - send_time) - (packet.transmit_timestamp() - packet.receive_timestamp());
+        if delay < NtpDuration::ZERO {
+            debug!(?delay, "Ignoring calibration response with negative delay");
+        } else {
+            delays.push(delay);
+        }
+
+        if delays.len() < CALIBRATION_EXCHANGES {
+            return BroadcastAction::SendCalibration(self.calibration_request());
+        }
+
+        let round_trip = delays.iter().copied().min().unwrap_or_default();
+        info!(?round_trip, "Calibrated delay to broadcast server");
+        self.state = BroadcastState::Listening {
+            delay: round_trip / 2u8,
+        };
+        self.update_snapshot();
+        BroadcastAction::Update
+    }
+
+    /// Called every broadcast interval to track whether the server is still
+    /// broadcasting and to retry calibration requests that got lost.
+    pub fn handle_timer(&mut self) -> BroadcastAction {
+        self.reach.poll();
+
+        if !self.reach.is_reachable() && !matches!(self.state, BroadcastState::WaitingForBroadcast)
+        {
+            // The network path may have changed while no broadcasts arrived
+            info!("Broadcast server unreachable, will recalibrate");
+            self.state = BroadcastState::WaitingForBroadcast;
+        }
+        self.update_snapshot();
+
+        match self.state {
+            BroadcastState::Calibrating { .. } => {
+                BroadcastAction::SendCalibration(self.calibration_request())
+            }
+            _ => BroadcastAction::Ignore,
+        }
+    }
+
+    /// Time until [`BroadcastClient::handle_timer`] should be called again
+    pub fn timer_interval(&self) -> Duration {
+        self.broadcast_interval.as_system_duration()
+    }
+
+    pub fn observe(&self, name: String) -> ObservableSourceState {
+        ObservableSourceState {
+            timedata: self.controller.observe(),
+            unanswered_polls: self.reach.unanswered_polls(),
+            poll_interval: self.broadcast_interval,
+            nts_cookies: None,
+            last_poll_sent: NtpTimestamp::default(),
+            last_response_received: self.last_broadcast_received,
+            timescale: None,
+            captured_packets: vec![],
+            last_round_trip_delay: None,
+            last_precision: None,
+            name,
+            address: self.server.to_string(),
+            id: self.id,
+        }
+    }
+
+    fn calibration_request(&mut self) -> Vec<u8> {
+        let (packet, identifier) = NtpPacket::poll_message(self.broadcast_interval);
+        if let BroadcastState::Calibrating { request, .. } = &mut self.state {
+            *request = Some(identifier);
+        }
+        packet
+            .serialize_without_encryption_vec(None)
+            .expect("Internal error: could not serialize packet")
+    }
+
+    fn update_snapshot(&mut self) {
+        let snapshot = NtpSourceSnapshot {
+            source_addr: self.server,
+            source_id: self.source_id,
+            poll_interval: self.broadcast_interval,
+            reach: self.reach,
+            stratum: self.stratum,
+            reference_id: self.reference_id,
+            protocol_version: ProtocolVersion::V4,
+            bloom_filter: None,
+            expected_refid: self.source_config.enforced_refid(),
+        };
+        let usable = matches!(self.state, BroadcastState::Listening { .. }) && {
+            let source_info = self.source_info.read().unwrap();
+            snapshot
+                .accept_synchronization(
+                    source_info.local_stratum,
+                    &source_info.ip_list,
+                    source_info.server_id,
+                )
+                .is_ok()
+        };
+        self.source_snapshots
+            .lock()
+            .unwrap()
+            .insert(self.id, snapshot);
+        self.controller.set_usable(usable);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ObservableSourceTimedata,
+        packet::{NtpHeaderBuilder, v5::server_reference_id::ServerId},
+    };
+
+    use super::*;
+
+    const SERVER: &str = "192.0.2.1:123";
+
+    #[derive(Default)]
+    struct RecordingController {
+        measurements: Vec<Measurement>,
+        usable: bool,
+    }
+
+    impl SourceController for RecordingController {
+        fn handle_measurement(&mut self, measurement: Measurement) {
+            self.measurements.push(measurement);
+        }
+
+        fn set_usable(&mut self, usable: bool) {
+            self.usable = usable;
+        }
+
+        fn desired_poll_interval(&self) -> PollInterval {
+            PollInterval::default()
+        }
+
+        fn observe(&self) -> ObservableSourceTimedata {
+            ObservableSourceTimedata::default()
+        }
+    }
+
+    fn client(source_config: SourceConfig) -> BroadcastClient<RecordingController> {
+        BroadcastClient::new(
+            SERVER.parse().unwrap(),
+            source_config,
+            RecordingController::default(),
+            ClockId::new(),
+            Arc::new(RwLock::new(NtpSourceInfo {
+                ip_list: Arc::new([]),
+                server_id: ServerId::default(),
+                local_stratum: 16,
+            })),
+            Arc::default(),
+        )
+    }
+
+    fn ts(seconds: u32, millis: u32) -> NtpTimestamp {
+        NtpTimestamp::from_seconds_nanos_since_ntp_era(seconds, millis * 1_000_000)
+    }
+
+    fn broadcast(mode: NtpAssociationMode, transmit: NtpTimestamp) -> Vec<u8> {
+        NtpPacket::from_header(
+            NtpHeaderBuilder::new()
+                .mode(mode)
+                .stratum(2)
+                .poll(PollInterval::from_byte(6))
+                .reference_id(ReferenceId::from_int(0xc000_0264))
+                .transmit_timestamp(transmit)
+                .build()
+                .unwrap(),
+        )
+        .serialize_without_encryption_vec(None)
+        .unwrap()
+    }
+
+    fn calibration_response(
+        request: &[u8],
+        receive: NtpTimestamp,
+        transmit: NtpTimestamp,
+    ) -> Vec<u8> {
+        let (request, _) = NtpPacket::deserialize(request, &NoCipher).unwrap();
+        NtpPacket::from_header(
+            NtpHeaderBuilder::new()
+                .mode(NtpAssociationMode::Server)
+                .stratum(2)
+                .reference_id(ReferenceId::from_int(0xc000_0264))
+                .origin_timestamp(request.transmit_timestamp())
+                .receive_timestamp(receive)
+                .transmit_timestamp(transmit)
+                .build()
+                .unwrap(),
+        )
+        .serialize_without_encryption_vec(None)
+        .unwrap()
+    }
+
+    fn server_ip() -> IpAddr {
+        SERVER.parse::<SocketAddr>().unwrap().ip()
+    }
+
+    /// Receive a first broadcast and complete calibration with a round trip
+    /// delay of 20ms
+    fn calibrate(client: &mut BroadcastClient<RecordingController>) {
+        let BroadcastAction::SendCalibration(mut request) = client.handle_broadcast(
+            server_ip(),
+            &broadcast(NtpAssociationMode::Broadcast, ts(1000, 0)),
+            ts(1000, 10),
+        ) else {
+            panic!("Expected calibration to start");
+        };
+
+        for i in 0..CALIBRATION_EXCHANGES as u32 {
+            let response = calibration_response(&request, ts(1001 + i, 10), ts(1001 + i, 11));
+            match client.handle_calibration_response(&response, ts(1001 + i, 0), ts(1001 + i, 21)) {
+                BroadcastAction::SendCalibration(next) => request = next,
+                BroadcastAction::Update => {
+                    assert_eq!(i as usize, CALIBRATION_EXCHANGES - 1);
+                }
+                BroadcastAction::Ignore => panic!("Calibration response was ignored"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_ignores_spoofed_sender() {
+        let mut client = client(SourceConfig::default());
+        let action = client.handle_broadcast(
+            "192.0.2.2".parse().unwrap(),
+            &broadcast(NtpAssociationMode::Broadcast, ts(1000, 0)),
+            ts(1000, 10),
+        );
+        assert!(matches!(action, BroadcastAction::Ignore));
+        assert!(!client.reach.is_reachable());
+    }
+
+    #[test]
+    fn test_ignores_non_broadcast() {
+        let mut client = client(SourceConfig::default());
+        let action = client.handle_broadcast(
+            server_ip(),
+            &broadcast(NtpAssociationMode::Server, ts(1000, 0)),
+            ts(1000, 10),
+        );
+        assert!(matches!(action, BroadcastAction::Ignore));
+
+        let action = client.handle_broadcast(server_ip(), &[0; 12], ts(1000, 10));
+        assert!(matches!(action, BroadcastAction::Ignore));
+    }
+
+    #[test]
+    fn test_ignores_unsynchronized_server() {
+        let mut client = client(SourceConfig::default());
+        let mut packet = NtpPacket::from_header(
+            NtpHeaderBuilder::new()
+                .mode(NtpAssociationMode::Broadcast)
+                .stratum(2)
+                .transmit_timestamp(ts(1000, 0))
+                .build()
+                .unwrap(),
+        );
+        packet.set_leap(NtpLeapIndicator::Unknown);
+        let action = client.handle_broadcast(
+            server_ip(),
+            &packet.serialize_without_encryption_vec(None).unwrap(),
+            ts(1000, 10),
+        );
+        assert!(matches!(action, BroadcastAction::Ignore));
+    }
+
+    #[test]
+    fn test_require_authenticated() {
+        let mut client = client(SourceConfig {
+            require_authenticated: true,
+            ..SourceConfig::default()
+        });
+        let action = client.handle_broadcast(
+            server_ip(),
+            &broadcast(NtpAssociationMode::Broadcast, ts(1000, 0)),
+            ts(1000, 10),
+        );
+        assert!(matches!(action, BroadcastAction::Ignore));
+        assert!(!client.controller.usable);
+    }
+
+    #[test]
+    fn test_calibration() {
+        let mut client = client(SourceConfig::default());
+        assert!(matches!(client.state, BroadcastState::WaitingForBroadcast));
+
+        calibrate(&mut client);
+
+        let BroadcastState::Listening { delay } = client.state else {
+            panic!("Expected calibration to complete");
+        };
+        assert!((delay.to_seconds() - 0.01).abs() < 1e-6);
+        assert!(client.controller.usable);
+        // Calibration exchanges are not measurements by themselves
+        assert!(client.controller.measurements.is_empty());
+    }
+
+    #[test]
+    fn test_calibration_ignores_mismatched_response() {
+        let mut client = client(SourceConfig::default());
+        let BroadcastAction::SendCalibration(request) = client.handle_broadcast(
+            server_ip(),
+            &broadcast(NtpAssociationMode::Broadcast, ts(1000, 0)),
+            ts(1000, 10),
+        ) else {
+            panic!("Expected calibration to start");
+        };
+
+        // Response to a request we never sent
+        let (other, _) = NtpPacket::poll_message(PollInterval::default());
+        let other = other.serialize_without_encryption_vec(None).unwrap();
+        let response = calibration_response(&other, ts(1001, 10), ts(1001, 11));
+        let action = client.handle_calibration_response(&response, ts(1001, 0), ts(1001, 21));
+        assert!(matches!(action, BroadcastAction::Ignore));
+
+        // Response with a negative delay is consumed but not used
+        let response = calibration_response(&request, ts(1001, 10), ts(1001, 50));
+        let action = client.handle_calibration_response(&response, ts(1001, 0), ts(1001, 21));
+        assert!(matches!(action, BroadcastAction::SendCalibration(_)));
+        let BroadcastState::Calibrating { delays, .. } = &client.state else {
+            panic!("Expected to still be calibrating");
+        };
+        assert!(delays.is_empty());
+
+        // A late duplicate of the answered request is not accepted again
+        let action = client.handle_calibration_response(&response, ts(1001, 0), ts(1001, 21));
+        assert!(matches!(action, BroadcastAction::Ignore));
+    }
+
+    #[test]
+    fn test_measurement_after_calibration() {
+        let mut client = client(SourceConfig::default());
+        calibrate(&mut client);
+
+        let action = client.handle_broadcast(
+            server_ip(),
+            &broadcast(NtpAssociationMode::Broadcast, ts(1064, 0)),
+            ts(1064, 30),
+        );
+        assert!(matches!(action, BroadcastAction::Update));
+
+        let [measurement] = client.controller.measurements[..] else {
+            panic!("Expected exactly one measurement");
+        };
+        assert_eq!(measurement.sender_id, client.id);
+        assert_eq!(measurement.receiver_id, ClockId::SYSTEM);
+        // 30ms after the transmit time, of which 10ms is delay
+        let offset = measurement.receiver_ts - measurement.sender_ts;
+        assert!((offset.to_seconds() - 0.02).abs() < 1e-6);
+        assert!((measurement.root_delay.to_seconds() - 0.02).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_ignores_replayed_broadcast() {
+        let mut client = client(SourceConfig::default());
+        calibrate(&mut client);
+
+        let packet = broadcast(NtpAssociationMode::Broadcast, ts(1064, 0));
+        let action = client.handle_broadcast(server_ip(), &packet, ts(1064, 30));
+        assert!(matches!(action, BroadcastAction::Update));
+        let action = client.handle_broadcast(server_ip(), &packet, ts(1065, 30));
+        assert!(matches!(action, BroadcastAction::Ignore));
+
+        // Older broadcasts are ignored as well
+        let packet = broadcast(NtpAssociationMode::Broadcast, ts(1032, 0));
+        let action = client.handle_broadcast(server_ip(), &packet, ts(1066, 30));
+        assert!(matches!(action, BroadcastAction::Ignore));
+
+        assert_eq!(client.controller.measurements.len(), 1);
+    }
+
+    #[test]
+    fn test_recalibrates_when_unreachable() {
+        let mut client = client(SourceConfig::default());
+        calibrate(&mut client);
+
+        for _ in 0..7 {
+            assert!(matches!(client.handle_timer(), BroadcastAction::Ignore));
+        }
+        assert!(client.controller.usable);
+
+        client.handle_timer();
+        assert!(matches!(client.state, BroadcastState::WaitingForBroadcast));
+        assert!(!client.controller.usable);
+
+        let action = client.handle_broadcast(
+            server_ip(),
+            &broadcast(NtpAssociationMode::Broadcast, ts(2000, 0)),
+            ts(2000, 10),
+        );
+        assert!(matches!(action, BroadcastAction::SendCalibration(_)));
+    }
+}
@@ -3,6 +3,17 @@ use crate::{
     time_types::{NtpDuration, NtpTimestamp},
 };
 
+/// A set of adjustments to the clock that should be applied together.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClockAdjustment {
+    /// New frequency of the clock
+    pub frequency: Option<f64>,
+    /// Estimated and maximum error of the clock
+    pub error_estimate: Option<(NtpDuration, NtpDuration)>,
+    /// Leap second and synchronization status
+    pub leap_status: Option<NtpLeapIndicator>,
+}
+
 /// Interface for a clock settable by the ntp implementation.
 /// This needs to be a trait as a single system can have multiple clocks
 /// which need different implementation for steering and/or now.
@@ -37,7 +48,234 @@ pub trait NtpClock: Clone + Send + 'static {
         est_error: NtpDuration,
         max_error: NtpDuration,
     ) -> Result<(), Self::Error>;
+
+    // Read back the estimated and maximum error of the clock, as
+    // last provided through error_estimate_update and since then
+    // increased by the clock itself. Clocks that don't keep track
+    // of these return None.
+    fn get_error_estimate(&self) -> Result<Option<(NtpDuration, NtpDuration)>, Self::Error> {
+        Ok(None)
+    }
+
+    // Set the offset of TAI from UTC in seconds kept by the clock.
+    // Clocks that don't keep such an offset ignore it.
+    fn set_tai_offset(&self, _tai_offset: i32) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    // Get the offset of TAI from UTC in seconds kept by the clock.
+    // Clocks that don't keep such an offset return None.
+    fn get_tai_offset(&self) -> Result<Option<i32>, Self::Error> {
+        Ok(None)
+    }
+
     // Change the indicators for upcoming leap seconds and
     // the clocks synchronization status.
     fn status_update(&self, leap_status: NtpLeapIndicator) -> Result<(), Self::Error>;
+
+    // Apply a set of adjustments at once, returning the time at
+    // which they were applied. Clocks that can combine these into
+    // a single operation (e.g. a single adjtimex call) should
+    // override this, by default the adjustments are applied one
+    // after the other.
+    fn adjust(&self, adjustment: ClockAdjustment) -> Result<NtpTimestamp, Self::Error> {
+        let mut applied = None;
+        if let Some(freq) = adjustment.frequency {
+            applied = Some(self.set_frequency(freq)?);
+        }
+        if let Some((est_error, max_error)) = adjustment.error_estimate {
+            self.error_estimate_update(est_error, max_error)?;
+        }
+        if let Some(leap_status) = adjustment.leap_status {
+            self.status_update(leap_status)?;
+        }
+        match applied {
+            Some(time) => Ok(time),
+            None => self.now(),
+        }
+    }
+}
+
+/// A call made to a [`RecordingClock`]
+#[cfg(any(test, feature = "__internal-test"))]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordedClockCall {
+    StepClock(NtpDuration),
+    SetFrequency(f64),
+    ErrorEstimateUpdate {
+        est_error: NtpDuration,
+        max_error: NtpDuration,
+    },
+}
+
+/// Clock wrapper for tests that records the adjustments made to the wrapped
+/// clock. Clones share their recording, so calls made through a clone handed
+/// to e.g. a clock controller can be inspected afterwards.
+///
+/// Combined adjustments are recorded as their individual calls, regardless
+/// of whether the wrapped clock would apply them at once.
+#[cfg(any(test, feature = "__internal-test"))]
+#[derive(Debug, Clone)]
+pub struct RecordingClock<C: NtpClock> {
+    inner: C,
+    calls: std::sync::Arc<std::sync::Mutex<Vec<RecordedClockCall>>>,
+}
+
+#[cfg(any(test, feature = "__internal-test"))]
+impl<C: NtpClock> RecordingClock<C> {
+    pub fn new(inner: C) -> Self {
+        RecordingClock {
+            inner,
+            calls: Default::default(),
+        }
+    }
+
+    /// All calls recorded so far, in the order they were made
+    pub fn calls(&self) -> Vec<RecordedClockCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Take the calls recorded so far, clearing the recording
+    pub fn take_calls(&self) -> Vec<RecordedClockCall> {
+        std::mem::take(&mut self.calls.lock().unwrap())
+    }
+
+    fn record(&self, call: RecordedClockCall) {
+        self.calls.lock().unwrap().push(call);
+    }
+}
+
+#[cfg(any(test, feature = "__internal-test"))]
+impl<C: NtpClock> NtpClock for RecordingClock<C> {
+    type Error = C::Error;
+
+    fn now(&self) -> Result<NtpTimestamp, Self::Error> {
+        self.inner.now()
+    }
+
+    fn set_frequency(&self, freq: f64) -> Result<NtpTimestamp, Self::Error> {
+        self.record(RecordedClockCall::SetFrequency(freq));
+        self.inner.set_frequency(freq)
+    }
+
+    fn get_frequency(&self) -> Result<f64, Self::Error> {
+        self.inner.get_frequency()
+    }
+
+    fn step_clock(&self, offset: NtpDuration) -> Result<NtpTimestamp, Self::Error> {
+        self.record(RecordedClockCall::StepClock(offset));
+        self.inner.step_clock(offset)
+    }
+
+    fn disable_ntp_algorithm(&self) -> Result<(), Self::Error> {
+        self.inner.disable_ntp_algorithm()
+    }
+
+    fn error_estimate_update(
+        &self,
+        est_error: NtpDuration,
+        max_error: NtpDuration,
+    ) -> Result<(), Self::Error> {
+        self.record(RecordedClockCall::ErrorEstimateUpdate {
+            est_error,
+            max_error,
+        });
+        self.inner.error_estimate_update(est_error, max_error)
+    }
+
+    fn get_error_estimate(&self) -> Result<Option<(NtpDuration, NtpDuration)>, Self::Error> {
+        self.inner.get_error_estimate()
+    }
+
+    fn set_tai_offset(&self, tai_offset: i32) -> Result<(), Self::Error> {
+        self.inner.set_tai_offset(tai_offset)
+    }
+
+    fn get_tai_offset(&self) -> Result<Option<i32>, Self::Error> {
+        self.inner.get_tai_offset()
+    }
+
+    fn status_update(&self, leap_status: NtpLeapIndicator) -> Result<(), Self::Error> {
+        self.inner.status_update(leap_status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone)]
+    struct TestClock;
+
+    impl NtpClock for TestClock {
+        type Error = std::io::Error;
+
+        fn now(&self) -> Result<NtpTimestamp, Self::Error> {
+            Ok(NtpTimestamp::from_fixed_int(0))
+        }
+
+        fn set_frequency(&self, _freq: f64) -> Result<NtpTimestamp, Self::Error> {
+            Ok(NtpTimestamp::from_fixed_int(0))
+        }
+
+        fn get_frequency(&self) -> Result<f64, Self::Error> {
+            Ok(0.0)
+        }
+
+        fn step_clock(&self, _offset: NtpDuration) -> Result<NtpTimestamp, Self::Error> {
+            Ok(NtpTimestamp::from_fixed_int(0))
+        }
+
+        fn disable_ntp_algorithm(&self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn error_estimate_update(
+            &self,
+            _est_error: NtpDuration,
+            _max_error: NtpDuration,
+        ) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn status_update(&self, _leap_status: NtpLeapIndicator) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_recording_clock_records_calls() {
+        let clock = RecordingClock::new(TestClock);
+        // The recording is shared with clones
+        let handle = clock.clone();
+
+        clock.step_clock(NtpDuration::from_seconds(1.5)).unwrap();
+        clock.set_frequency(2e-6).unwrap();
+        clock.status_update(NtpLeapIndicator::NoWarning).unwrap();
+        clock.now().unwrap();
+        clock
+            .adjust(ClockAdjustment {
+                frequency: Some(-1e-6),
+                error_estimate: Some((
+                    NtpDuration::from_seconds(0.25),
+                    NtpDuration::from_seconds(0.5),
+                )),
+                leap_status: None,
+            })
+            .unwrap();
+
+        assert_eq!(
+            handle.take_calls(),
+            vec![
+                RecordedClockCall::StepClock(NtpDuration::from_seconds(1.5)),
+                RecordedClockCall::SetFrequency(2e-6),
+                RecordedClockCall::SetFrequency(-1e-6),
+                RecordedClockCall::ErrorEstimateUpdate {
+                    est_error: NtpDuration::from_seconds(0.25),
+                    max_error: NtpDuration::from_seconds(0.5),
+                },
+            ]
+        );
+        assert!(clock.calls().is_empty());
+    }
 }
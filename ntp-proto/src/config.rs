@@ -1,11 +1,15 @@
-use std::fmt;
+use std::{fmt, time::Duration};
 
 use serde::{
-    Deserialize, Deserializer,
+    Deserialize, Deserializer, Serialize, Serializer,
     de::{self, MapAccess, Unexpected, Visitor},
+    ser::SerializeMap,
 };
 
-use crate::time_types::{NtpDuration, PollInterval, PollIntervalLimits};
+use crate::{
+    identifiers::ReferenceId,
+    time_types::{NtpDuration, PollInterval, PollIntervalLimits},
+};
 
 fn deserialize_option_accumulated_step_panic_threshold<'de, D>(
     deserializer: D,
@@ -21,7 +25,213 @@ where
     })
 }
 
-#[derive(Debug, Default, Copy, Clone)]
+/// Longest leap smear window. Leap seconds take effect at the next UTC
+/// midnight, so a smear can not start earlier than a day before.
+const MAX_LEAP_SMEAR: Duration = Duration::from_secs(86400);
+
+fn deserialize_option_leap_smear<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let seconds: f64 = Deserialize::deserialize(deserializer)?;
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(window) if !window.is_zero() && window <= MAX_LEAP_SMEAR => Ok(Some(window)),
+        _ => Err(de::Error::invalid_value(
+            Unexpected::Float(seconds),
+            &"a positive number of seconds of at most one day",
+        )),
+    }
+}
+
+fn deserialize_option_min_clock_update_interval<'de, D>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let seconds: f64 = Deserialize::deserialize(deserializer)?;
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(interval) if !interval.is_zero() => Ok(Some(interval)),
+        _ => Err(de::Error::invalid_value(
+            Unexpected::Float(seconds),
+            &"a positive number of seconds",
+        )),
+    }
+}
+
+#[allow(clippy::ref_option, reason = "Signature required by serde")]
+fn serialize_option_seconds<S>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match duration {
+        Some(duration) => serializer.serialize_f64(duration.as_secs_f64()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_option_max_slew_rate<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let ppm: f64 = Deserialize::deserialize(deserializer)?;
+    if ppm.is_finite() && ppm > 0.0 {
+        Ok(Some(ppm))
+    } else {
+        Err(de::Error::invalid_value(
+            Unexpected::Float(ppm),
+            &"a positive number of ppm",
+        ))
+    }
+}
+
+/// Largest value that fits in the 6 bits of the DSCP field
+pub const MAX_DSCP: u8 = 63;
+
+/// Deserialize an optional DSCP value, rejecting values that do not fit in the
+/// 6 bits of the DSCP field
+pub fn deserialize_dscp<'de, D>(deserializer: D) -> Result<Option<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let dscp: Option<u8> = Deserialize::deserialize(deserializer)?;
+    match dscp {
+        Some(dscp) if dscp > MAX_DSCP => Err(de::Error::invalid_value(
+            Unexpected::Unsigned(dscp.into()),
+            &"a DSCP value between 0 and 63",
+        )),
+        dscp => Ok(dscp),
+    }
+}
+
+/// Deserialize an optional reference id, given either as an IPv4 address (as
+/// used by stratum 2+ servers) or as a code of at most 4 ASCII characters (as
+/// used by stratum 1 servers, e.g. `GPS`)
+pub fn deserialize_expected_refid<'de, D>(deserializer: D) -> Result<Option<ReferenceId>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(refid): Option<String> = Deserialize::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+
+    parse_refid(&refid).map(Some)
+}
+
+/// Deserialize a list of reference ids, each in the format accepted by
+/// [`deserialize_expected_refid`]
+pub(crate) fn deserialize_refid_list<'de, D>(deserializer: D) -> Result<Vec<ReferenceId>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let refids: Vec<String> = Deserialize::deserialize(deserializer)?;
+    refids.iter().map(|refid| parse_refid(refid)).collect()
+}
+
+/// Serialize an optional reference id in the format accepted by
+/// [`deserialize_expected_refid`]
+#[allow(clippy::ref_option, reason = "Signature required by serde")]
+pub fn serialize_expected_refid<S>(
+    refid: &Option<ReferenceId>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match refid {
+        Some(refid) => serializer.serialize_str(&format_refid(*refid)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Serialize a list of reference ids in the format accepted by
+/// [`deserialize_refid_list`]
+pub(crate) fn serialize_refid_list<S>(
+    refids: &[ReferenceId],
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(refids.iter().map(|refid| format_refid(*refid)))
+}
+
+fn parse_refid<E: de::Error>(refid: &str) -> Result<ReferenceId, E> {
+    if let Ok(addr) = refid.parse::<std::net::Ipv4Addr>() {
+        return Ok(ReferenceId::from_ip(addr.into()));
+    }
+
+    if refid.is_empty() || refid.len() > 4 || !refid.bytes().all(|b| b.is_ascii_graphic()) {
+        return Err(E::invalid_value(
+            Unexpected::Str(refid),
+            &"an IPv4 address or a reference code of at most 4 ASCII characters",
+        ));
+    }
+
+    let mut bytes = [0; 4];
+    bytes[..refid.len()].copy_from_slice(refid.as_bytes());
+    Ok(ReferenceId::from_bytes(bytes))
+}
+
+/// Format a reference id as its ASCII code when it has one, and as an IPv4
+/// address otherwise
+fn format_refid(refid: ReferenceId) -> String {
+    let bytes = refid.to_bytes();
+    let len = bytes
+        .iter()
+        .rposition(|b| *b != 0)
+        .map_or(0, |last| last + 1);
+    let code = &bytes[..len];
+
+    if !code.is_empty() && code.iter().all(u8::is_ascii_graphic) {
+        code.iter().map(|&b| char::from(b)).collect()
+    } else {
+        std::net::Ipv4Addr::from(bytes).to_string()
+    }
+}
+
+/// Largest fraction of the poll interval by which polls may be randomized
+pub const MAX_POLL_JITTER: f64 = 0.5;
+
+pub fn deserialize_poll_jitter<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let poll_jitter: f64 = Deserialize::deserialize(deserializer)?;
+    if (0.0..=MAX_POLL_JITTER).contains(&poll_jitter) {
+        Ok(poll_jitter)
+    } else {
+        Err(de::Error::invalid_value(
+            Unexpected::Float(poll_jitter),
+            &"a fraction between 0.0 and 0.5",
+        ))
+    }
+}
+
+/// Maximum number of polls that are taken into account for reachability
+pub const MAX_REACH_WINDOW: u8 = 8;
+
+/// Deserialize the number of polls that are taken into account for
+/// reachability, between 1 and [`MAX_REACH_WINDOW`]
+pub fn deserialize_reach_window<'de, D>(deserializer: D) -> Result<u8, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let reach_window: u8 = Deserialize::deserialize(deserializer)?;
+    if (1..=MAX_REACH_WINDOW).contains(&reach_window) {
+        Ok(reach_window)
+    } else {
+        Err(de::Error::invalid_value(
+            Unexpected::Unsigned(reach_window.into()),
+            &"a number of polls between 1 and 8",
+        ))
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub struct ReferenceIdConfig {
     id: u32,
 }
@@ -71,7 +281,23 @@ impl<'de> Deserialize<'de> for ReferenceIdConfig {
     }
 }
 
-#[derive(Debug, Default, Copy, Clone)]
+// Serialize to the string type in config, without the padding
+impl Serialize for ReferenceIdConfig {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let chars: String = self
+            .id
+            .to_be_bytes()
+            .iter()
+            .map(|&b| char::from(b))
+            .collect();
+        serializer.serialize_str(chars.trim_end_matches(' '))
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub struct StepThreshold {
     pub forward: Option<NtpDuration>,
     pub backward: Option<NtpDuration>,
@@ -139,6 +365,36 @@ impl<'de> Deserialize<'de> for ThresholdPart {
     }
 }
 
+impl Serialize for ThresholdPart {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0 {
+            Some(duration) => duration.serialize(serializer),
+            None => serializer.serialize_str("inf"),
+        }
+    }
+}
+
+// Thresholds that are the same in both directions are serialized as a single
+// value, others as a map
+impl Serialize for StepThreshold {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if self.forward == self.backward {
+            return ThresholdPart(self.forward).serialize(serializer);
+        }
+
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("forward", &ThresholdPart(self.forward))?;
+        map.serialize_entry("backward", &ThresholdPart(self.backward))?;
+        map.end()
+    }
+}
+
 // We have a custom deserializer for StepThreshold because we
 // want to deserialize it from either a number or map
 impl<'de> Deserialize<'de> for StepThreshold {
@@ -244,7 +500,189 @@ impl<'de> Deserialize<'de> for StepThreshold {
     }
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+/// Longest name of a source group
+pub const MAX_SOURCE_GROUP_LENGTH: usize = 16;
+
+/// Largest number of groups a quorum can place requirements on
+pub const MAX_QUORUM_GROUPS: usize = 8;
+
+/// Name of a group of sources, used to express per-group quorum
+/// requirements. The name is stored inline to keep source configuration
+/// `Copy`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SourceGroup {
+    name: [u8; MAX_SOURCE_GROUP_LENGTH],
+    len: u8,
+}
+
+impl SourceGroup {
+    /// Create a group with the given name, which must be non-empty and at
+    /// most [`MAX_SOURCE_GROUP_LENGTH`] bytes long
+    pub fn new(name: &str) -> Option<Self> {
+        if name.is_empty() || name.len() > MAX_SOURCE_GROUP_LENGTH {
+            return None;
+        }
+
+        let mut group = SourceGroup {
+            name: [0; MAX_SOURCE_GROUP_LENGTH],
+            len: name.len() as u8,
+        };
+        group.name[..name.len()].copy_from_slice(name.as_bytes());
+        Some(group)
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only ever constructed from a valid str
+        std::str::from_utf8(&self.name[..self.len as usize]).unwrap()
+    }
+}
+
+impl fmt::Debug for SourceGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SourceGroup").field(&self.as_str()).finish()
+    }
+}
+
+impl fmt::Display for SourceGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for SourceGroup {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name: String = Deserialize::deserialize(deserializer)?;
+        SourceGroup::new(&name).ok_or_else(|| {
+            de::Error::invalid_value(
+                Unexpected::Str(&name),
+                &"a non-empty group name of at most 16 bytes",
+            )
+        })
+    }
+}
+
+impl Serialize for SourceGroup {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// Minimum number of agreeing sources required from each of a number of
+/// source groups before the clock is steered
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Quorum {
+    groups: [Option<(SourceGroup, usize)>; MAX_QUORUM_GROUPS],
+}
+
+impl Quorum {
+    /// Create a quorum from (group, minimum) pairs, failing when more than
+    /// [`MAX_QUORUM_GROUPS`] groups are given
+    pub fn new(groups: &[(SourceGroup, usize)]) -> Option<Self> {
+        if groups.len() > MAX_QUORUM_GROUPS {
+            return None;
+        }
+
+        let mut quorum = Quorum::default();
+        for (slot, group) in quorum.groups.iter_mut().zip(groups) {
+            *slot = Some(*group);
+        }
+        Some(quorum)
+    }
+
+    /// The groups with their minimum number of agreeing sources
+    pub fn groups(&self) -> impl Iterator<Item = (SourceGroup, usize)> + '_ {
+        self.groups.iter().flatten().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups().next().is_none()
+    }
+
+    /// Whether the groups of a set of agreeing sources meet the minimum of
+    /// every group in the quorum
+    pub fn is_met(&self, agreeing: impl Iterator<Item = Option<SourceGroup>> + Clone) -> bool {
+        self.groups().all(|(group, minimum)| {
+            agreeing.clone().filter(|g| *g == Some(group)).count() >= minimum
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Quorum {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct QuorumVisitor;
+
+        impl<'de> Visitor<'de> for QuorumVisitor {
+            type Value = Quorum;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a table of source groups with their minimum number of sources")
+            }
+
+            fn visit_map<M>(self, mut map: M) -> Result<Quorum, M::Error>
+            where
+                M: MapAccess<'de>,
+            {
+                let mut groups: Vec<(SourceGroup, usize)> = vec![];
+                while let Some((group, minimum)) = map.next_entry::<SourceGroup, usize>()? {
+                    if groups.iter().any(|(other, _)| *other == group) {
+                        return Err(de::Error::custom(format!("duplicate quorum group {group}")));
+                    }
+                    groups.push((group, minimum));
+                }
+
+                Quorum::new(&groups).ok_or_else(|| {
+                    de::Error::invalid_length(groups.len(), &"at most 8 quorum groups")
+                })
+            }
+        }
+
+        deserializer.deserialize_map(QuorumVisitor)
+    }
+}
+
+impl Serialize for Quorum {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(self.groups())
+    }
+}
+
+/// What to do with a source that reports a different reference id than
+/// expected
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RefidMismatchAction {
+    /// Log a warning, but keep using the source
+    Warn,
+    /// Log a warning and stop using the source for synchronization
+    #[default]
+    Demote,
+}
+
+/// Filter with which the measurements of a source are combined into an
+/// estimate of its offset
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterAlgorithm {
+    /// Kalman filter tracking both the offset and frequency of the source
+    #[default]
+    Kalman,
+    /// Median of the offsets of the most recent measurements
+    Median,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct SourceConfig {
     /// Minima and maxima for the poll interval of clients
@@ -254,6 +692,109 @@ pub struct SourceConfig {
     /// Initial poll interval of the system
     #[serde(default = "default_initial_poll_interval")]
     pub initial_poll_interval: PollInterval,
+
+    /// Maximum measurement jitter a source may have and still be used to
+    /// discipline the clock. Sources exceeding it are still observed and take
+    /// part in selection, but are not used to steer the clock.
+    #[serde(default)]
+    pub max_jitter_for_discipline: Option<NtpDuration>,
+
+    /// DSCP value with which outgoing packets to the source are marked
+    #[serde(default, deserialize_with = "deserialize_dscp")]
+    pub dscp: Option<u8>,
+
+    /// Fraction of the poll interval by which each poll is randomly moved
+    /// earlier or later, to spread out polls from clients that started at the
+    /// same time. With 0.0, only a small fixed randomization is applied.
+    #[serde(default, deserialize_with = "deserialize_poll_jitter")]
+    pub poll_jitter: f64,
+
+    /// Ignore longer poll intervals requested by NTPv5 servers and keep
+    /// polling at our own intended interval. Rate limiting kiss codes are
+    /// still respected.
+    #[serde(default)]
+    pub ignore_server_poll: bool,
+
+    /// Reference id the source is expected to report. Sources reporting a
+    /// different reference id are not used for synchronization.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_expected_refid",
+        serialize_with = "serialize_expected_refid"
+    )]
+    pub expected_refid: Option<ReferenceId>,
+
+    /// What to do when the source reports a different reference id than
+    /// `expected_refid`
+    #[serde(default)]
+    pub on_refid_mismatch: RefidMismatchAction,
+
+    /// Only measure the source, never using it for synchronization. Such
+    /// sources are still observed but take no part in selection.
+    #[serde(default)]
+    pub monitor_only: bool,
+
+    /// Known difference between the delay of requests to the source and that
+    /// of responses from the source. Half of it is subtracted from the offset
+    /// of each measurement.
+    #[serde(default)]
+    pub delay_asymmetry: NtpDuration,
+
+    /// Maximum round trip delay of a measurement. Responses with a longer
+    /// round trip are discarded, and count as missed for reachability.
+    #[serde(default)]
+    pub max_round_trip: Option<NtpDuration>,
+
+    /// Lowest stratum the source may advertise. Responses with a lower
+    /// stratum are discarded, and count as missed for reachability.
+    #[serde(default)]
+    pub min_acceptable_stratum: Option<u8>,
+
+    /// Ask NTPv4 servers for interleaved responses, which carry the actual
+    /// transmit timestamp of the previous response instead of an estimate.
+    #[serde(default)]
+    pub interleaved: bool,
+
+    /// Number of most recent polls that are considered when determining
+    /// whether the source is reachable. A source that did not answer any of
+    /// them is unreachable.
+    #[serde(
+        default = "default_reach_window",
+        deserialize_with = "deserialize_reach_window"
+    )]
+    pub reach_window: u8,
+
+    /// Keep the raw bytes of the most recent requests to and responses from
+    /// the source, and include them in the observation output. Intended for
+    /// protocol debugging.
+    #[serde(default)]
+    pub capture_packets: bool,
+
+    /// Filter used to estimate the offset of the source from its measurements
+    #[serde(default)]
+    pub filter_algorithm: FilterAlgorithm,
+
+    /// Number of measurements a newly added source must have made before it
+    /// can be selected. Until then the source is measured and counts as
+    /// reachable, but takes no part in selection. With 0 or 1, a source can be
+    /// selected from its first measurement.
+    #[serde(default)]
+    pub warmup_samples: u8,
+
+    /// Prefer the source over other selected sources when choosing the
+    /// primary source. Only configurable on individual sources.
+    #[serde(skip)]
+    pub prefer: bool,
+
+    /// Group the source belongs to, for the purpose of the quorum
+    /// requirements. Only configurable on individual sources.
+    #[serde(skip)]
+    pub group: Option<SourceGroup>,
+
+    /// Stop using an NTS source for synchronization once it sends a response
+    /// without valid NTS authentication. Only configurable on NTS sources.
+    #[serde(skip)]
+    pub require_authenticated: bool,
 }
 
 impl Default for SourceConfig {
@@ -261,15 +802,45 @@ impl Default for SourceConfig {
         Self {
             poll_interval_limits: PollIntervalLimits::default(),
             initial_poll_interval: default_initial_poll_interval(),
+            max_jitter_for_discipline: None,
+            dscp: None,
+            poll_jitter: 0.0,
+            ignore_server_poll: false,
+            expected_refid: None,
+            on_refid_mismatch: RefidMismatchAction::Demote,
+            monitor_only: false,
+            delay_asymmetry: NtpDuration::ZERO,
+            max_round_trip: None,
+            min_acceptable_stratum: None,
+            interleaved: false,
+            reach_window: default_reach_window(),
+            capture_packets: false,
+            filter_algorithm: FilterAlgorithm::Kalman,
+            warmup_samples: 0,
+            prefer: false,
+            group: None,
+            require_authenticated: false,
         }
     }
 }
 
+impl SourceConfig {
+    /// Reference id a source must report to be used for synchronization
+    pub(crate) fn enforced_refid(&self) -> Option<ReferenceId> {
+        self.expected_refid
+            .filter(|_| self.on_refid_mismatch == RefidMismatchAction::Demote)
+    }
+}
+
+fn default_reach_window() -> u8 {
+    MAX_REACH_WINDOW
+}
+
 fn default_initial_poll_interval() -> PollInterval {
     PollIntervalLimits::default().min
 }
 
-#[derive(Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct SynchronizationConfig {
     /// Minimum number of survivors needed to be able to discipline the system clock.
@@ -283,6 +854,19 @@ pub struct SynchronizationConfig {
     #[serde(default = "default_minimum_agreeing_sources")]
     pub minimum_agreeing_sources: usize,
 
+    /// Trust a single reference clock, such as a GPS receiver providing time
+    /// through a sock source, to discipline the clock on its own. When set, a
+    /// reference clock that survives selection is enough, regardless of
+    /// `minimum_agreeing_sources`.
+    #[serde(default)]
+    pub standalone_reference_clock: bool,
+
+    /// Minimum number of agreeing sources required from specific source
+    /// groups, on top of `minimum_agreeing_sources`. Unlike that minimum,
+    /// these also apply to a standalone reference clock.
+    #[serde(default)]
+    pub quorum: Quorum,
+
     /// The maximum amount the system clock is allowed to change in a single go
     /// before we conclude something is seriously wrong. This is used to limit
     /// the changes to the clock to reasonable amounts, and stop issues with
@@ -307,6 +891,45 @@ pub struct SynchronizationConfig {
     )]
     pub accumulated_step_panic_threshold: Option<NtpDuration>,
 
+    /// Offset from which corrections are applied by stepping the clock
+    /// instead of slewing it. Steps are still subject to the panic
+    /// thresholds. When not set, the step threshold from the algorithm
+    /// configuration is used.
+    #[serde(default)]
+    pub step_threshold: Option<NtpDuration>,
+
+    /// Correct the offset found on the first measurement after startup by
+    /// stepping the clock, regardless of the step threshold. This is still
+    /// subject to the startup panic threshold. Later corrections follow the
+    /// regular rules.
+    #[serde(default)]
+    pub first_measurement_step: bool,
+
+    /// When set, a step exceeding the single step panic threshold after
+    /// startup does not terminate the daemon. Instead, offset corrections are
+    /// held until the suggested correction drops below this bound. Keeping it
+    /// well below the panic threshold prevents alternating between stepping
+    /// and holding when the offset hovers around the threshold.
+    #[serde(default)]
+    pub panic_threshold_recovery: Option<NtpDuration>,
+
+    /// Maximum rate (in ppm) at which offsets are slewed away. Larger
+    /// corrections are spread out over a longer time instead. When not set,
+    /// only the slew limits of the algorithm configuration apply.
+    #[serde(default, deserialize_with = "deserialize_option_max_slew_rate")]
+    pub max_slew_rate_ppm: Option<f64>,
+
+    /// Minimum time between adjustments of the system clock. Adjustments
+    /// suggested sooner are held back, and the combined correction is applied
+    /// once the interval has passed. Steps are never held back. When not set,
+    /// every update of the time estimate may adjust the clock.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_option_min_clock_update_interval",
+        serialize_with = "serialize_option_seconds"
+    )]
+    pub min_clock_update_interval: Option<Duration>,
+
     /// Stratum of the local clock, when not synchronized through ntp. This
     /// can be used in servers to indicate that there are external mechanisms
     /// synchronizing the clock
@@ -356,21 +979,45 @@ pub struct SynchronizationConfig {
     /// Should a warning be emitted on jumps in the clock
     #[serde(default = "default_warn_on_jump")]
     pub warn_on_jump: bool,
+
+    /// Offset of TAI from UTC in seconds to set on the clock at startup
+    #[serde(default)]
+    pub tai_offset: Option<i32>,
+
+    /// Window before a leap second over which it is smeared out, instead of
+    /// inserting or deleting the second at once. While smearing no leap
+    /// second is announced to the kernel or to clients.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_option_leap_smear",
+        serialize_with = "serialize_option_seconds"
+    )]
+    pub leap_smear: Option<Duration>,
 }
 
 impl Default for SynchronizationConfig {
     fn default() -> Self {
         Self {
             minimum_agreeing_sources: default_minimum_agreeing_sources(),
+            standalone_reference_clock: false,
+            quorum: Quorum::default(),
 
             single_step_panic_threshold: default_single_step_panic_threshold(),
             startup_step_panic_threshold: default_startup_step_panic_threshold(),
             accumulated_step_panic_threshold: None,
+            step_threshold: None,
+            first_measurement_step: false,
+            panic_threshold_recovery: None,
+            max_slew_rate_ppm: None,
+            min_clock_update_interval: None,
 
             local_stratum: default_local_stratum(),
             reference_id: default_reference_id(),
 
             warn_on_jump: default_warn_on_jump(),
+
+            tai_offset: None,
+            leap_smear: None,
         }
     }
 }
@@ -8,7 +8,7 @@ use openssl::hash::{MessageDigest, hash};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ReferenceId(u32);
 
 impl ReferenceId {
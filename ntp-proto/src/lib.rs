@@ -35,6 +35,7 @@
 compile_error!("A crypto provider is needed, use '--features rustcrypto' or '--features openssl'");
 
 mod algorithm;
+mod broadcast;
 mod clock;
 mod config;
 mod cookiestash;
@@ -140,15 +141,31 @@ impl std::fmt::Display for ClockId {
     }
 }
 
+impl std::str::FromStr for ClockId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(ClockId)
+    }
+}
+
 mod exports {
     pub use super::algorithm::{
-        AlgorithmConfig, KalmanClockController, KalmanControllerMessage, KalmanSourceController,
-        KalmanSourceMessage, Measurement, ObservableSourceTimedata, OneWaySourceControllerWrapper,
-        SourceController, TimeSyncController, TimeSyncControllerWrapper,
-        TwoWayKalmanSourceController, TwoWaySourceControllerWrapper,
+        AlgorithmConfig, ForceSyncOutcome, KalmanClockController, KalmanControllerMessage,
+        KalmanSourceController, KalmanSourceMessage, Measurement, ObservableSourceTimedata,
+        OneWaySourceControllerWrapper, SourceController, TimeSyncController,
+        TimeSyncControllerWrapper, TwoWayKalmanSourceController, TwoWaySourceControllerWrapper,
+    };
+    pub use super::broadcast::{BroadcastAction, BroadcastClient};
+    pub use super::clock::{ClockAdjustment, NtpClock};
+    #[cfg(feature = "__internal-test")]
+    pub use super::clock::{RecordedClockCall, RecordingClock};
+    pub use super::config::{
+        FilterAlgorithm, MAX_DSCP, MAX_POLL_JITTER, MAX_REACH_WINDOW, Quorum, RefidMismatchAction,
+        SourceConfig, SourceGroup, StepThreshold, SynchronizationConfig, deserialize_dscp,
+        deserialize_expected_refid, deserialize_poll_jitter, deserialize_reach_window,
+        serialize_expected_refid,
     };
-    pub use super::clock::NtpClock;
-    pub use super::config::{SourceConfig, StepThreshold, SynchronizationConfig};
     pub use super::identifiers::ReferenceId;
     #[cfg(feature = "__internal-fuzz")]
     pub use super::ipfilter::fuzz::fuzz_ipfilter;
@@ -159,37 +176,41 @@ mod exports {
     #[cfg(feature = "__internal-fuzz")]
     pub use super::packet::ExtensionField;
     pub use super::packet::{
-        Cipher, CipherProvider, EncryptResult, ExtensionHeaderVersion, NoCipher,
-        NtpAssociationMode, NtpLeapIndicator, NtpPacket, PacketParsingError,
+        Cipher, CipherProvider, EncryptResult, ExtensionFieldList, ExtensionHeaderVersion,
+        NoCipher, NtpAssociationMode, NtpHeader, NtpHeaderBuilder, NtpHeaderBuilderError,
+        NtpLeapIndicator, NtpPacket, PacketParsingError, Stratum, WireExtensionField,
     };
     #[cfg(feature = "__internal-fuzz")]
     pub use super::server::HandleInnerData;
     pub use super::server::{
-        FilterAction, FilterList, IpSubnet, Server, ServerAction, ServerConfig, ServerReason,
-        ServerResponse, ServerStatHandler, SubnetParseError,
+        FilterAction, FilterList, IpSubnet, ReferenceIdFilterList, SERVER_STATUS_REQUEST_TYPE,
+        SERVER_STATUS_RESPONSE_TYPE, Server, ServerAction, ServerConfig, ServerReason,
+        ServerResponse, ServerStatHandler, ServerStatus, SubnetParseError,
     };
     #[cfg(feature = "__internal-test")]
     pub use super::source::source_snapshot;
     pub use super::source::{
-        AcceptSynchronizationError, NtpSource, NtpSourceAction, NtpSourceActionIterator,
-        NtpSourceSnapshot, ObservableSourceState, OneWaySource, ProtocolVersion, Reach,
-        SourceNtsData,
+        AcceptSynchronizationError, CapturedPacket, NtpSource, NtpSourceAction,
+        NtpSourceActionIterator, NtpSourceSnapshot, ObservableSourceState, OneWaySource,
+        PacketDirection, ProtocolVersion, Reach, SourceNtsData,
     };
     pub use super::system::{
-        NtpManager, NtpServerInfo, NtpSnapshot, SourceType, SystemSnapshot, TimeSnapshot,
+        ClockState, NtpManager, NtpServerInfo, NtpSnapshot, SourceType, SystemSnapshot,
+        TimeSnapshot,
     };
 
     #[cfg(feature = "__internal-fuzz")]
     pub use super::time_types::fuzz_duration_from_seconds;
     pub use super::time_types::{
-        FrequencyTolerance, NtpDuration, NtpTimestamp, PollInterval, PollIntervalLimits,
+        FrequencyTolerance, NtpDuration, NtpDurationParseError, NtpTimestamp, PollInterval,
+        PollIntervalLimits,
     };
 
     #[cfg(feature = "__internal-fuzz")]
     pub use super::nts::Request as KeyExchangeRequest;
     pub use super::nts::{
-        KeyExchangeClient, KeyExchangeResult, KeyExchangeServer, NtsClientConfig, NtsError,
-        NtsServerConfig,
+        KeyExchangeClient, KeyExchangeResult, KeyExchangeServer, MAX_COOKIE_SIZE, NtsClientConfig,
+        NtsCookieConfig, NtsError, NtsNtpEndpoint, NtsServerConfig,
     };
     #[cfg(feature = "__internal-fuzz")]
     pub use super::nts::{KeyExchangeResponse, NtsRecord};
@@ -197,6 +218,7 @@ mod exports {
     pub use super::cookiestash::MAX_COOKIES;
 
     pub mod v5 {
+        pub use crate::packet::v5::NtpTimescale;
         pub use crate::packet::v5::server_reference_id::{BloomFilter, ServerId};
     }
 
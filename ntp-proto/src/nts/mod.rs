@@ -1,5 +1,6 @@
 use std::{borrow::Cow, convert::Into, sync::Arc};
 
+use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio_rustls::{TlsAcceptor, TlsConnector};
 
@@ -254,6 +255,7 @@ pub enum NtsError {
     AeadNotSupported(u16),
     IncorrectSizedKey,
     NotPermitted,
+    UnsupportedPrivateKey,
 }
 
 impl From<std::io::Error> for NtsError {
@@ -298,6 +300,9 @@ impl std::fmt::Display for NtsError {
                 write!(f, "Received fix key request with incorrectly sized key(s)")
             }
             NtsError::NotPermitted => write!(f, "Request not permitted without authentication"),
+            NtsError::UnsupportedPrivateKey => f.write_str(
+                "Private key type not supported by the TLS backend, use an RSA, ECDSA (P-256 or P-384) or Ed25519 key",
+            ),
         }
     }
 }
@@ -422,14 +427,57 @@ impl KeyExchangeClient {
     }
 }
 
+/// Largest cookie size a server may pad its cookies to. Clients send a cookie
+/// together with at least one placeholder of the same size in packets of at
+/// most 1024 bytes, next to the header and the other extension fields.
+pub const MAX_COOKIE_SIZE: usize = 400;
+
+/// Parameters of the cookies handed out by the key exchange server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct NtsCookieConfig {
+    /// Number of cookies provided in a single key exchange response
+    #[serde(default = "default_cookie_count")]
+    pub count: usize,
+    /// Minimum size in bytes of each cookie. Shorter cookies are padded with
+    /// zeroes, which is ignored when the cookie is decoded. At most
+    /// [`MAX_COOKIE_SIZE`].
+    #[serde(default)]
+    pub min_size: usize,
+}
+
+fn default_cookie_count() -> usize {
+    DEFAULT_NUMBER_OF_COOKIES
+}
+
+impl Default for NtsCookieConfig {
+    fn default() -> Self {
+        Self {
+            count: DEFAULT_NUMBER_OF_COOKIES,
+            min_size: 0,
+        }
+    }
+}
+
+/// Location of the NTP server that clients are directed to after key exchange.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NtsNtpEndpoint {
+    /// Server name, or `None` if it is the same as the key exchange server
+    pub server: Option<String>,
+    /// Port, or `None` if it is the default NTP port
+    pub port: Option<u16>,
+}
+
 #[derive(Debug)]
 pub struct NtsServerConfig {
     pub certificate_chain: Vec<Certificate>,
     pub private_key: PrivateKey,
     pub accepted_versions: Vec<NtpVersion>,
-    pub server: Option<String>,
-    pub port: Option<u16>,
+    pub v4_endpoint: NtsNtpEndpoint,
+    pub v5_endpoint: NtsNtpEndpoint,
     pub pool_authentication_tokens: Vec<String>,
+    pub v4_cookies: NtsCookieConfig,
+    pub v5_cookies: NtsCookieConfig,
 }
 
 pub struct KeyExchangeServer {
@@ -437,13 +485,24 @@ pub struct KeyExchangeServer {
     protocols: Box<[NextProtocol]>,
     algorithms: Box<[AlgorithmDescription]>,
     pool_authentication_tokens: Box<[String]>,
-    server: Option<String>,
-    port: Option<u16>,
+    v4_endpoint: NtsNtpEndpoint,
+    v5_endpoint: NtsNtpEndpoint,
+    v4_cookies: NtsCookieConfig,
+    v5_cookies: NtsCookieConfig,
 }
 
 impl KeyExchangeServer {
     pub fn new(config: NtsServerConfig) -> Result<Self, NtsError> {
-        let mut server_config = tls_utils::server_config_builder_with_protocol_versions(&[&TLS13])
+        let builder = tls_utils::server_config_builder_with_protocol_versions(&[&TLS13]);
+        // The signature scheme follows from the type of key, check up front
+        // whether the backend can sign with it as rustls reports a generic
+        // error otherwise
+        builder
+            .crypto_provider()
+            .key_provider
+            .load_private_key(config.private_key.clone_key())
+            .map_err(|_| NtsError::UnsupportedPrivateKey)?;
+        let mut server_config = builder
             .with_no_client_auth()
             .with_single_cert(config.certificate_chain, config.private_key)?;
         server_config.alpn_protocols = vec![b"ntske/1".to_vec()];
@@ -470,11 +529,44 @@ impl KeyExchangeServer {
                     .expect("Missing description for AEAD algorithm"),
             ]),
             pool_authentication_tokens: config.pool_authentication_tokens.into(),
-            server: config.server,
-            port: config.port,
+            v4_endpoint: config.v4_endpoint,
+            v5_endpoint: config.v5_endpoint,
+            v4_cookies: config.v4_cookies,
+            v5_cookies: config.v5_cookies,
         })
     }
 
+    fn endpoint(&self, protocol: NextProtocol) -> &NtsNtpEndpoint {
+        match protocol {
+            NextProtocol::DraftNTPv5 => &self.v5_endpoint,
+            NextProtocol::NTPv4 | NextProtocol::Unknown(_) => &self.v4_endpoint,
+        }
+    }
+
+    fn make_cookies(
+        &self,
+        protocol: NextProtocol,
+        keyset: &KeySet,
+        cookie: &DecodedServerCookie,
+    ) -> Vec<Cow<'static, [u8]>> {
+        let config = match protocol {
+            NextProtocol::DraftNTPv5 => self.v5_cookies,
+            NextProtocol::NTPv4 | NextProtocol::Unknown(_) => self.v4_cookies,
+        };
+
+        let mut cookies = Vec::with_capacity(config.count);
+
+        for _ in 0..config.count {
+            let mut encoded = keyset.encode_cookie(cookie);
+            if encoded.len() < config.min_size {
+                encoded.resize(config.min_size, 0);
+            }
+            cookies.push(encoded.into());
+        }
+
+        cookies
+    }
+
     // FIXME: Figure out a way to simplify and/or split this function.
     #[expect(clippy::too_many_lines)]
     pub async fn handle_longterm<T: AsyncRead + AsyncWrite + Unpin, U: AsRef<KeySet>>(
@@ -539,22 +631,16 @@ impl KeyExchangeServer {
                         c2s: c2s_key,
                     };
 
-                    let mut cookies = Vec::with_capacity(DEFAULT_NUMBER_OF_COOKIES);
-
                     let keyset = get_keyset();
-
-                    for _ in 0..DEFAULT_NUMBER_OF_COOKIES {
-                        cookies.push(keyset.as_ref().encode_cookie(&cookie).into());
-                    }
-
+                    let cookies = self.make_cookies(protocol, keyset.as_ref(), &cookie);
                     drop(keyset);
 
                     let response = KeyExchangeResponse {
                         protocol,
                         algorithm,
                         cookies: cookies.into(),
-                        server: self.server.as_deref().map(Into::into),
-                        port: self.port,
+                        server: self.endpoint(protocol).server.as_deref().map(Into::into),
+                        port: self.endpoint(protocol).port,
                         keep_alive,
                     };
 
@@ -709,18 +795,14 @@ impl KeyExchangeServer {
                             c2s: keys.c2s,
                         };
 
-                        let mut cookies = Vec::with_capacity(DEFAULT_NUMBER_OF_COOKIES);
-
-                        for _ in 0..DEFAULT_NUMBER_OF_COOKIES {
-                            cookies.push(keyset.encode_cookie(&cookie).into());
-                        }
+                        let cookies = self.make_cookies(protocol, keyset, &cookie);
 
                         let response = KeyExchangeResponse {
                             protocol,
                             algorithm,
                             cookies: cookies.into(),
-                            server: self.server.as_deref().map(Into::into),
-                            port: self.port,
+                            server: self.endpoint(protocol).server.as_deref().map(Into::into),
+                            port: self.endpoint(protocol).port,
                             keep_alive: false,
                         };
 
@@ -755,11 +837,7 @@ impl KeyExchangeServer {
                     c2s: c2s_key,
                 };
 
-                let mut cookies = Vec::with_capacity(DEFAULT_NUMBER_OF_COOKIES);
-
-                for _ in 0..DEFAULT_NUMBER_OF_COOKIES {
-                    cookies.push(keyset.encode_cookie(&cookie).into());
-                }
+                let cookies = self.make_cookies(protocol, keyset, &cookie);
 
                 let permit = if keep_alive {
                     get_keepalive_permit()
@@ -771,8 +849,8 @@ impl KeyExchangeServer {
                     protocol,
                     algorithm,
                     cookies: cookies.into(),
-                    server: self.server.as_deref().map(Into::into),
-                    port: self.port,
+                    server: self.endpoint(protocol).server.as_deref().map(Into::into),
+                    port: self.endpoint(protocol).port,
                     keep_alive: permit.is_some(),
                 };
 
@@ -911,9 +989,11 @@ mod tests {
                 certificate_chain,
                 private_key,
                 accepted_versions: vec![NtpVersion::V4],
-                server: None,
-                port: None,
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
                 pool_authentication_tokens: vec![],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig::default(),
             })
             .unwrap();
             let keyset = KeySet::new();
@@ -939,6 +1019,113 @@ mod tests {
         assert_eq!(count, 8);
     }
 
+    async fn keyexchange_roundtrip_with_key(certificate_chain: &[u8], private_key: &[u8]) {
+        #[cfg(feature = "openssl")]
+        let _ = rustls_openssl::default_provider().install_default();
+
+        let (client, server) = tokio::io::duplex(2048);
+
+        let client = async move {
+            let certificates = tls_utils::pemfile::certs(
+                &mut include_bytes!("../../test-keys/testca.pem").as_slice(),
+            )
+            .collect::<Result<Arc<_>, _>>()
+            .unwrap();
+            let kex = KeyExchangeClient::new(&NtsClientConfig {
+                certificates,
+                protocol_version: ProtocolVersion::V4,
+            })
+            .unwrap();
+            kex.exchange_keys(client, "localhost".into(), [])
+                .await
+                .unwrap()
+        };
+
+        let server = async move {
+            let certificate_chain = tls_utils::pemfile::certs(&mut &*certificate_chain)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            let private_key = tls_utils::pemfile::private_key(&mut &*private_key).unwrap();
+            let kex = KeyExchangeServer::new(NtsServerConfig {
+                certificate_chain,
+                private_key,
+                accepted_versions: vec![NtpVersion::V4],
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
+                pool_authentication_tokens: vec![],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig::default(),
+            })
+            .unwrap();
+            let keyset = KeySet::new();
+            assert!(
+                kex.handle_connection(server, &keyset, || None::<()>)
+                    .await
+                    .is_ok()
+            );
+        };
+
+        let (kexresult, ()) = tokio::join!(client, server);
+        assert_eq!(kexresult.protocol_version, ProtocolVersion::V4);
+        assert_eq!(kexresult.remote, "localhost");
+    }
+
+    #[tokio::test]
+    async fn test_keyexchange_roundtrip_ecdsa_p256() {
+        keyexchange_roundtrip_with_key(
+            include_bytes!("../../test-keys/end-p256.fullchain.pem"),
+            include_bytes!("../../test-keys/end-p256.key"),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_keyexchange_roundtrip_ecdsa_p384() {
+        keyexchange_roundtrip_with_key(
+            include_bytes!("../../test-keys/end-p384.fullchain.pem"),
+            include_bytes!("../../test-keys/end-p384.key"),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_keyexchange_roundtrip_ed25519() {
+        keyexchange_roundtrip_with_key(
+            include_bytes!("../../test-keys/end-ed25519.fullchain.pem"),
+            include_bytes!("../../test-keys/end-ed25519.key"),
+        )
+        .await;
+    }
+
+    #[test]
+    fn test_keyexchange_unsupported_key() {
+        #[cfg(feature = "openssl")]
+        let _ = rustls_openssl::default_provider().install_default();
+
+        let certificate_chain = tls_utils::pemfile::certs(
+            &mut include_bytes!("../../test-keys/end.fullchain.pem").as_slice(),
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        // openssl genpkey -algorithm X25519 -out x25519_key.pem
+        // X25519 keys can only be used for key agreement, not for signing
+        let private_key = tls_utils::pemfile::private_key(
+            &mut include_bytes!("../../test-keys/x25519_key.pem").as_slice(),
+        )
+        .unwrap();
+        let result = KeyExchangeServer::new(NtsServerConfig {
+            certificate_chain,
+            private_key,
+            accepted_versions: vec![NtpVersion::V4],
+            v4_endpoint: NtsNtpEndpoint::default(),
+            v5_endpoint: NtsNtpEndpoint::default(),
+            pool_authentication_tokens: vec![],
+            v4_cookies: NtsCookieConfig::default(),
+            v5_cookies: NtsCookieConfig::default(),
+        });
+        assert!(matches!(result, Err(NtsError::UnsupportedPrivateKey)));
+    }
+
     #[tokio::test]
     async fn test_keyexchange_roundtrip_v5() {
         #[cfg(feature = "openssl")]
@@ -976,9 +1163,11 @@ mod tests {
                 certificate_chain,
                 private_key,
                 accepted_versions: vec![NtpVersion::V5],
-                server: None,
-                port: None,
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
                 pool_authentication_tokens: vec![],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig::default(),
             })
             .unwrap();
             let keyset = KeySet::new();
@@ -1004,6 +1193,77 @@ mod tests {
         assert_eq!(count, 8);
     }
 
+    #[tokio::test]
+    async fn test_keyexchange_roundtrip_v5_cookie_config() {
+        #[cfg(feature = "openssl")]
+        let _ = rustls_openssl::default_provider().install_default();
+
+        let (client, server) = tokio::io::duplex(2048);
+
+        let client = async move {
+            let certificates = tls_utils::pemfile::certs(
+                &mut include_bytes!("../../test-keys/testca.pem").as_slice(),
+            )
+            .collect::<Result<Arc<_>, _>>()
+            .unwrap();
+            let kex = KeyExchangeClient::new(&NtsClientConfig {
+                certificates,
+                protocol_version: ProtocolVersion::V5,
+            })
+            .unwrap();
+            kex.exchange_keys(client, "localhost".into(), [])
+                .await
+                .unwrap()
+        };
+
+        let server = async move {
+            let certificate_chain = tls_utils::pemfile::certs(
+                &mut include_bytes!("../../test-keys/end.fullchain.pem").as_slice(),
+            )
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+            let private_key = tls_utils::pemfile::private_key(
+                &mut include_bytes!("../../test-keys/end.key").as_slice(),
+            )
+            .unwrap();
+            let kex = KeyExchangeServer::new(NtsServerConfig {
+                certificate_chain,
+                private_key,
+                accepted_versions: vec![NtpVersion::V4, NtpVersion::V5],
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
+                pool_authentication_tokens: vec![],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig {
+                    count: 4,
+                    min_size: 200,
+                },
+            })
+            .unwrap();
+            let keyset = KeySet::new();
+            assert!(
+                kex.handle_connection(server, &keyset, || None::<()>)
+                    .await
+                    .is_ok()
+            );
+            keyset
+        };
+
+        let (mut kexresult, keyset) = tokio::join!(client, server);
+        assert_eq!(kexresult.protocol_version, ProtocolVersion::V5);
+
+        let mut count = 0;
+        while let Some(cookie) = kexresult.nts.get_cookie() {
+            assert_eq!(cookie.len(), 200);
+            let decoded = keyset.decode_cookie(&cookie).unwrap();
+
+            assert_eq!(decoded.c2s.key_bytes(), kexresult.nts.c2s.key_bytes());
+            assert_eq!(decoded.s2c.key_bytes(), kexresult.nts.s2c.key_bytes());
+            count += 1;
+        }
+        assert_eq!(count, 4);
+    }
+
     #[tokio::test]
     async fn test_keyexchange_roundtrip_upgrading() {
         #[cfg(feature = "openssl")]
@@ -1041,9 +1301,11 @@ mod tests {
                 certificate_chain,
                 private_key,
                 accepted_versions: vec![NtpVersion::V4, NtpVersion::V5],
-                server: None,
-                port: None,
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
                 pool_authentication_tokens: vec![],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig::default(),
             })
             .unwrap();
             let keyset = KeySet::new();
@@ -1106,9 +1368,11 @@ mod tests {
                 certificate_chain,
                 private_key,
                 accepted_versions: vec![NtpVersion::V4],
-                server: None,
-                port: None,
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
                 pool_authentication_tokens: vec![],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig::default(),
             })
             .unwrap();
             let keyset = KeySet::new();
@@ -1169,9 +1433,11 @@ mod tests {
                 certificate_chain,
                 private_key,
                 accepted_versions: vec![NtpVersion::V4],
-                server: None,
-                port: None,
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
                 pool_authentication_tokens: vec![],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig::default(),
             })
             .unwrap();
             let keyset = KeySet::new();
@@ -1218,9 +1484,11 @@ mod tests {
                 certificate_chain,
                 private_key,
                 accepted_versions: vec![NtpVersion::V4],
-                server: None,
-                port: None,
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
                 pool_authentication_tokens: vec![],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig::default(),
             })
             .unwrap();
             let mut server = kex.acceptor.accept(server).await.unwrap();
@@ -1312,9 +1580,11 @@ mod tests {
                 certificate_chain,
                 private_key,
                 accepted_versions: vec![NtpVersion::V4],
-                server: None,
-                port: None,
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
                 pool_authentication_tokens: vec!["hi".into()],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig::default(),
             })
             .unwrap();
             let keyset = KeySet::new();
@@ -1435,9 +1705,11 @@ mod tests {
                 certificate_chain,
                 private_key,
                 accepted_versions: vec![NtpVersion::V4],
-                server: None,
-                port: None,
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
                 pool_authentication_tokens: vec!["hi".into()],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig::default(),
             })
             .unwrap();
             let keyset = Arc::new(KeySet::new());
@@ -1543,9 +1815,11 @@ mod tests {
                 certificate_chain,
                 private_key,
                 accepted_versions: vec![NtpVersion::V4],
-                server: None,
-                port: None,
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
                 pool_authentication_tokens: vec!["hi".into()],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig::default(),
             })
             .unwrap();
             let keyset = KeySet::new();
@@ -1639,9 +1913,11 @@ mod tests {
                 certificate_chain,
                 private_key,
                 accepted_versions: vec![NtpVersion::V4],
-                server: None,
-                port: None,
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
                 pool_authentication_tokens: vec!["hi".into()],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig::default(),
             })
             .unwrap();
             let keyset = KeySet::new();
@@ -1723,9 +1999,11 @@ mod tests {
                 certificate_chain,
                 private_key,
                 accepted_versions: vec![NtpVersion::V4],
-                server: None,
-                port: None,
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
                 pool_authentication_tokens: vec!["hi".into()],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig::default(),
             })
             .unwrap();
             let keyset = KeySet::new();
@@ -1803,9 +2081,11 @@ mod tests {
                 certificate_chain,
                 private_key,
                 accepted_versions: vec![NtpVersion::V4],
-                server: None,
-                port: None,
+                v4_endpoint: NtsNtpEndpoint::default(),
+                v5_endpoint: NtsNtpEndpoint::default(),
                 pool_authentication_tokens: vec!["hi".into()],
+                v4_cookies: NtsCookieConfig::default(),
+                v5_cookies: NtsCookieConfig::default(),
             })
             .unwrap();
             let keyset = KeySet::new();
@@ -4,7 +4,14 @@ use super::NtpPacket;
 
 #[derive(Debug)]
 pub enum ParsingError<T> {
+    /// The packet is shorter than its header
+    TooShort,
+    /// The packet uses an NTP version we don't support
     InvalidVersion(u8),
+    /// The mode of the packet can not be used with its version
+    UnknownMode(u8),
+    /// An extension field has a length that is invalid or doesn't fit in the packet
+    BadExtensionLength,
     IncorrectLength,
     MalformedNtsExtensionFields,
     MalformedNonce,
@@ -16,7 +23,10 @@ pub enum ParsingError<T> {
 impl<T> ParsingError<T> {
     pub(super) fn get_decrypt_error<U>(self) -> Result<T, ParsingError<U>> {
         match self {
+            ParsingError::TooShort => Err(ParsingError::TooShort),
             ParsingError::InvalidVersion(v) => Err(ParsingError::InvalidVersion(v)),
+            ParsingError::UnknownMode(m) => Err(ParsingError::UnknownMode(m)),
+            ParsingError::BadExtensionLength => Err(ParsingError::BadExtensionLength),
             ParsingError::IncorrectLength => Err(ParsingError::IncorrectLength),
             ParsingError::MalformedNtsExtensionFields => {
                 Err(ParsingError::MalformedNtsExtensionFields)
@@ -34,7 +44,10 @@ impl<T> ParsingError<T> {
 impl ParsingError<std::convert::Infallible> {
     pub(super) fn generalize<U>(self) -> ParsingError<U> {
         match self {
+            ParsingError::TooShort => ParsingError::TooShort,
             ParsingError::InvalidVersion(v) => ParsingError::InvalidVersion(v),
+            ParsingError::UnknownMode(m) => ParsingError::UnknownMode(m),
+            ParsingError::BadExtensionLength => ParsingError::BadExtensionLength,
             ParsingError::IncorrectLength => ParsingError::IncorrectLength,
             ParsingError::MalformedNtsExtensionFields => ParsingError::MalformedNtsExtensionFields,
             ParsingError::MalformedNonce => ParsingError::MalformedNonce,
@@ -45,12 +58,34 @@ impl ParsingError<std::convert::Infallible> {
     }
 }
 
+impl<T> ParsingError<T> {
+    /// Short, stable name of the kind of error, suitable for categorizing
+    /// parse failures in logs and metrics
+    pub fn category(&self) -> &'static str {
+        match self {
+            Self::TooShort => "too_short",
+            Self::InvalidVersion(_) => "unsupported_version",
+            Self::UnknownMode(_) => "unknown_mode",
+            Self::BadExtensionLength => "bad_extension_length",
+            Self::IncorrectLength => "incorrect_length",
+            Self::MalformedNtsExtensionFields
+            | Self::MalformedNonce
+            | Self::MalformedCookiePlaceholder => "malformed_nts",
+            Self::DecryptError(_) => "decrypt_error",
+            Self::V5(_) => "v5",
+        }
+    }
+}
+
 pub type PacketParsingError<'a> = ParsingError<NtpPacket<'a>>;
 
 impl<T> Display for ParsingError<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Self::TooShort => f.write_str("Packet too short"),
             Self::InvalidVersion(version) => f.write_fmt(format_args!("Invalid version {version}")),
+            Self::UnknownMode(mode) => f.write_fmt(format_args!("Unknown mode {mode}")),
+            Self::BadExtensionLength => f.write_str("Invalid extension field length"),
             Self::IncorrectLength => f.write_str("Incorrect packet length"),
             Self::MalformedNtsExtensionFields => f.write_str("Malformed nts extension fields"),
             Self::MalformedNonce => f.write_str("Malformed nonce (likely invalid length)"),
@@ -759,6 +759,51 @@ impl<'a> arbitrary::Arbitrary<'a> for ExtensionHeaderVersion {
     }
 }
 
+/// An extension field as found on the wire, without interpreting its value
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WireExtensionField<'a> {
+    pub type_id: u16,
+    /// Length of the entire field, including its header and padding
+    pub length: u16,
+    /// Value of the field, including any padding
+    pub body: &'a [u8],
+}
+
+/// The extension fields of a packet in the order they appear on the wire
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ExtensionFieldList<'a> {
+    pub fields: Vec<WireExtensionField<'a>>,
+    /// Whether parsing stopped early on a field with an invalid length
+    pub truncated: bool,
+}
+
+impl<'a> ExtensionFieldList<'a> {
+    pub(super) fn parse(data: &'a [u8], cutoff: usize, version: ExtensionHeaderVersion) -> Self {
+        let mut list = Self::default();
+
+        for field in RawExtensionField::deserialize_sequence(
+            data,
+            cutoff,
+            RawExtensionField::BARE_MINIMUM_SIZE,
+            version,
+        ) {
+            let Ok((offset, field)) = field else {
+                list.truncated = true;
+                break;
+            };
+
+            list.fields.push(WireExtensionField {
+                type_id: field.type_id.to_type_id(),
+                // a successfully parsed field always has a complete header
+                length: u16::from_be_bytes([data[offset + 2], data[offset + 3]]),
+                body: field.message_bytes,
+            });
+        }
+
+        list
+    }
+}
+
 #[derive(Debug)]
 struct RawExtensionField<'a> {
     type_id: ExtensionFieldTypeId,
@@ -790,10 +835,10 @@ impl<'a> RawExtensionField<'a> {
         minimum_size: usize,
         version: ExtensionHeaderVersion,
     ) -> Result<Self, ParsingError<std::convert::Infallible>> {
-        use ParsingError::IncorrectLength;
+        use ParsingError::BadExtensionLength;
 
         let [b0, b1, b2, b3, ..] = data[..] else {
-            return Err(IncorrectLength);
+            return Err(BadExtensionLength);
         };
 
         let type_id = u16::from_be_bytes([b0, b1]);
@@ -803,20 +848,20 @@ impl<'a> RawExtensionField<'a> {
         let field_length = u16::from_be_bytes([b2, b3]) as usize;
 
         if field_length < minimum_size {
-            return Err(IncorrectLength);
+            return Err(BadExtensionLength);
         }
 
         // In NTPv4: padding is up to a multiple of 4 bytes, so a valid field length is divisible by 4
         if version == ExtensionHeaderVersion::V4 && !field_length.is_multiple_of(4) {
-            return Err(IncorrectLength);
+            return Err(BadExtensionLength);
         }
 
         // In NTPv5: There must still be enough room in the packet for data + padding
         data.get(4..next_multiple_of_usize(field_length, 4))
-            .ok_or(IncorrectLength)?;
+            .ok_or(BadExtensionLength)?;
 
         // because the field length includes padding, the message bytes may not exactly match the input
-        let message_bytes = data.get(4..field_length).ok_or(IncorrectLength)?;
+        let message_bytes = data.get(4..field_length).ok_or(BadExtensionLength)?;
 
         Ok(Self {
             type_id: ExtensionFieldTypeId::from_type_id(type_id),
@@ -27,7 +27,9 @@ pub use crypto::{
     EncryptResult, NoCipher,
 };
 pub use error::PacketParsingError;
-pub use extension_fields::{ExtensionField, ExtensionHeaderVersion};
+pub use extension_fields::{
+    ExtensionField, ExtensionFieldList, ExtensionHeaderVersion, WireExtensionField,
+};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NtpLeapIndicator {
@@ -83,10 +85,10 @@ pub enum NtpAssociationMode {
 }
 
 impl NtpAssociationMode {
-    // This function should only ever be called with 3 bit values
-    // (in the least significant position)
-    fn from_bits(bits: u8) -> NtpAssociationMode {
-        match bits {
+    /// Mode from the three mode bits of an NTP header. Higher bits are
+    /// ignored.
+    pub const fn from_bits(bits: u8) -> NtpAssociationMode {
+        match bits & 0b111 {
             0 => NtpAssociationMode::Reserved,
             1 => NtpAssociationMode::SymmetricActive,
             2 => NtpAssociationMode::SymmetricPassive,
@@ -95,13 +97,13 @@ impl NtpAssociationMode {
             5 => NtpAssociationMode::Broadcast,
             6 => NtpAssociationMode::Control,
             7 => NtpAssociationMode::Private,
-            // This function should only ever be called from the packet parser
-            // with just three bits, so this really should be unreachable
+            // Only three bits remain after masking
             _ => unreachable!(),
         }
     }
 
-    fn to_bits(self) -> u8 {
+    /// The three mode bits of an NTP header for this mode
+    pub const fn to_bits(self) -> u8 {
         match self {
             NtpAssociationMode::Reserved => 0,
             NtpAssociationMode::SymmetricActive => 1,
@@ -115,6 +117,39 @@ impl NtpAssociationMode {
     }
 }
 
+/// Stratum of an NTP header: the distance of its sender to a reference clock
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Stratum(u8);
+
+impl Stratum {
+    /// Stratum 0, used in requests and for kiss-o'-death responses
+    pub const UNSPECIFIED: Stratum = Stratum(0);
+    /// Stratum of a server directly synchronized to a reference clock
+    pub const PRIMARY: Stratum = Stratum(1);
+    /// Stratum of a server that is not synchronized
+    pub const UNSYNCHRONIZED: Stratum = Stratum(16);
+
+    pub const fn from_bits(bits: u8) -> Stratum {
+        Stratum(bits)
+    }
+
+    pub const fn to_bits(self) -> u8 {
+        self.0
+    }
+
+    /// Whether a server response with this stratum is a kiss-o'-death
+    /// message, carrying a kiss code instead of time.
+    pub const fn is_kiss_of_death(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether the sender is unsynchronized. Strata above 16 are reserved,
+    /// and treated the same.
+    pub const fn is_unsynchronized(self) -> bool {
+        self.0 >= Self::UNSYNCHRONIZED.0
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NtpPacket<'a> {
     header: NtpHeader,
@@ -133,7 +168,7 @@ pub enum NtpHeader {
 pub struct NtpHeaderV3V4 {
     leap: NtpLeapIndicator,
     mode: NtpAssociationMode,
-    stratum: u8,
+    stratum: Stratum,
     poll: PollInterval,
     precision: i8,
     root_delay: NtpDuration,
@@ -151,6 +186,8 @@ pub struct NtpHeaderV3V4 {
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct RequestIdentifier {
     expected_origin_timestamp: NtpTimestamp,
+    /// Origin timestamp with which a server answers an interleaved request
+    interleaved_origin_timestamp: Option<NtpTimestamp>,
     uid: Option<[u8; 32]>,
 }
 
@@ -162,7 +199,7 @@ impl NtpHeaderV3V4 {
         Self {
             leap: NtpLeapIndicator::NoWarning,
             mode: NtpAssociationMode::Client,
-            stratum: 0,
+            stratum: Stratum::UNSPECIFIED,
             poll: PollInterval::from_byte(0),
             precision: 0,
             root_delay: NtpDuration::default(),
@@ -177,14 +214,14 @@ impl NtpHeaderV3V4 {
 
     fn deserialize(data: &[u8]) -> Result<(Self, usize), ParsingError<std::convert::Infallible>> {
         if data.len() < Self::WIRE_LENGTH {
-            return Err(ParsingError::IncorrectLength);
+            return Err(ParsingError::TooShort);
         }
 
         Ok((
             Self {
                 leap: NtpLeapIndicator::from_bits((data[0] & 0xC0) >> 6),
                 mode: NtpAssociationMode::from_bits(data[0] & 0x07),
-                stratum: data[1],
+                stratum: Stratum::from_bits(data[1]),
                 poll: PollInterval::from_byte(data[2]),
                 precision: data[3] as i8,
                 root_delay: NtpDuration::from_bits_short(data[4..8].try_into().unwrap()),
@@ -201,7 +238,11 @@ impl NtpHeaderV3V4 {
 
     fn serialize(&self, mut w: impl NonBlockingWrite, version: u8) -> std::io::Result<()> {
         w.write_all(&[(self.leap.to_bits() << 6) | (version << 3) | self.mode.to_bits()])?;
-        w.write_all(&[self.stratum, self.poll.as_byte(), self.precision as u8])?;
+        w.write_all(&[
+            self.stratum.to_bits(),
+            self.poll.as_byte(),
+            self.precision as u8,
+        ])?;
         w.write_all(&self.root_delay.to_bits_short())?;
         w.write_all(&self.root_dispersion.to_bits_short())?;
         w.write_all(&self.reference_id.to_bytes())?;
@@ -228,6 +269,7 @@ impl NtpHeaderV3V4 {
             packet,
             RequestIdentifier {
                 expected_origin_timestamp: transmit_timestamp,
+                interleaved_origin_timestamp: None,
                 uid: None,
             },
         )
@@ -241,7 +283,7 @@ impl NtpHeaderV3V4 {
     ) -> Self {
         Self {
             mode: NtpAssociationMode::Server,
-            stratum: server_info.ntp_snapshot.stratum,
+            stratum: Stratum::from_bits(server_info.ntp_snapshot.stratum),
             origin_timestamp: input.transmit_timestamp,
             receive_timestamp: recv_timestamp,
             reference_id: server_info.ntp_snapshot.reference_id,
@@ -259,7 +301,7 @@ impl NtpHeaderV3V4 {
     fn rate_limit_response(packet_from_client: Self) -> Self {
         Self {
             mode: NtpAssociationMode::Server,
-            stratum: 0, // indicates a kiss code
+            stratum: Stratum::UNSPECIFIED, // indicates a kiss code
             reference_id: ReferenceId::KISS_RATE,
             origin_timestamp: packet_from_client.transmit_timestamp,
             ..Self::new()
@@ -269,7 +311,7 @@ impl NtpHeaderV3V4 {
     fn deny_response(packet_from_client: Self) -> Self {
         Self {
             mode: NtpAssociationMode::Server,
-            stratum: 0, // indicates a kiss code
+            stratum: Stratum::UNSPECIFIED, // indicates a kiss code
             reference_id: ReferenceId::KISS_DENY,
             origin_timestamp: packet_from_client.transmit_timestamp,
             ..Self::new()
@@ -279,7 +321,7 @@ impl NtpHeaderV3V4 {
     fn nts_nak_response(packet_from_client: Self) -> Self {
         Self {
             mode: NtpAssociationMode::Server,
-            stratum: 0,
+            stratum: Stratum::UNSPECIFIED, // indicates a kiss code
             reference_id: ReferenceId::KISS_NTSN,
             origin_timestamp: packet_from_client.transmit_timestamp,
             ..Self::new()
@@ -287,6 +329,193 @@ impl NtpHeaderV3V4 {
     }
 }
 
+/// Error returned by [`NtpHeaderBuilder::build`] for invalid field combinations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NtpHeaderBuilderError {
+    /// Only NTP versions 3, 4 and 5 are supported
+    InvalidVersion(u8),
+    /// A stratum 0 (kiss-o'-death) server header needs a kiss code as reference id
+    MissingKissCode,
+    /// NTPv5 only knows client and server modes
+    UnsupportedMode(NtpAssociationMode),
+    /// The field does not exist in the requested NTP version
+    UnsupportedField(&'static str),
+}
+
+impl std::fmt::Display for NtpHeaderBuilderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidVersion(version) => write!(f, "Invalid NTP version {version}"),
+            Self::MissingKissCode => {
+                f.write_str("Stratum 0 server headers require a kiss code as reference id")
+            }
+            Self::UnsupportedMode(mode) => write!(f, "Mode {mode:?} is not supported by NTPv5"),
+            Self::UnsupportedField(field) => write!(f, "Field {field} is not supported by NTPv5"),
+        }
+    }
+}
+
+impl std::error::Error for NtpHeaderBuilderError {}
+
+/// Builder for an [`NtpHeader`], validating the combination of fields on
+/// [`build`](Self::build).
+///
+/// Fields that are not set keep the values of an empty NTPv4 client header.
+#[derive(Debug, Clone, Copy)]
+pub struct NtpHeaderBuilder {
+    version: u8,
+    mode: NtpAssociationMode,
+    stratum: u8,
+    poll: PollInterval,
+    precision: i8,
+    reference_id: Option<ReferenceId>,
+    reference_timestamp: Option<NtpTimestamp>,
+    origin_timestamp: NtpTimestamp,
+    receive_timestamp: NtpTimestamp,
+    transmit_timestamp: NtpTimestamp,
+}
+
+impl Default for NtpHeaderBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NtpHeaderBuilder {
+    pub fn new() -> Self {
+        Self {
+            version: 4,
+            mode: NtpAssociationMode::Client,
+            stratum: 0,
+            poll: PollInterval::from_byte(0),
+            precision: 0,
+            reference_id: None,
+            reference_timestamp: None,
+            origin_timestamp: NtpTimestamp::default(),
+            receive_timestamp: NtpTimestamp::default(),
+            transmit_timestamp: NtpTimestamp::default(),
+        }
+    }
+
+    pub fn version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn mode(mut self, mode: NtpAssociationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn stratum(mut self, stratum: u8) -> Self {
+        self.stratum = stratum;
+        self
+    }
+
+    pub fn poll(mut self, poll: PollInterval) -> Self {
+        self.poll = poll;
+        self
+    }
+
+    pub fn precision(mut self, precision: i8) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Reference id, or the kiss code for stratum 0 headers. Not available in NTPv5.
+    pub fn reference_id(mut self, reference_id: ReferenceId) -> Self {
+        self.reference_id = Some(reference_id);
+        self
+    }
+
+    /// Not available in NTPv5.
+    pub fn reference_timestamp(mut self, timestamp: NtpTimestamp) -> Self {
+        self.reference_timestamp = Some(timestamp);
+        self
+    }
+
+    /// In NTPv5, this is carried in the client cookie.
+    pub fn origin_timestamp(mut self, timestamp: NtpTimestamp) -> Self {
+        self.origin_timestamp = timestamp;
+        self
+    }
+
+    pub fn receive_timestamp(mut self, timestamp: NtpTimestamp) -> Self {
+        self.receive_timestamp = timestamp;
+        self
+    }
+
+    pub fn transmit_timestamp(mut self, timestamp: NtpTimestamp) -> Self {
+        self.transmit_timestamp = timestamp;
+        self
+    }
+
+    pub fn build(self) -> Result<NtpHeader, NtpHeaderBuilderError> {
+        match self.version {
+            3 | 4 => {
+                let reference_id = match self.reference_id {
+                    Some(reference_id) => reference_id,
+                    None if Stratum::from_bits(self.stratum).is_kiss_of_death()
+                        && self.mode == NtpAssociationMode::Server =>
+                    {
+                        return Err(NtpHeaderBuilderError::MissingKissCode);
+                    }
+                    None => ReferenceId::from_int(0),
+                };
+
+                let header = NtpHeaderV3V4 {
+                    mode: self.mode,
+                    stratum: Stratum::from_bits(self.stratum),
+                    poll: self.poll,
+                    precision: self.precision,
+                    reference_id,
+                    reference_timestamp: self.reference_timestamp.unwrap_or_default(),
+                    origin_timestamp: self.origin_timestamp,
+                    receive_timestamp: self.receive_timestamp,
+                    transmit_timestamp: self.transmit_timestamp,
+                    ..NtpHeaderV3V4::new()
+                };
+
+                Ok(if self.version == 3 {
+                    NtpHeader::V3(header)
+                } else {
+                    NtpHeader::V4(header)
+                })
+            }
+            5 => {
+                if self.reference_id.is_some() {
+                    return Err(NtpHeaderBuilderError::UnsupportedField("reference id"));
+                }
+                if self.reference_timestamp.is_some() {
+                    return Err(NtpHeaderBuilderError::UnsupportedField(
+                        "reference timestamp",
+                    ));
+                }
+
+                let mode = match self.mode {
+                    NtpAssociationMode::Client => v5::NtpMode::Request,
+                    NtpAssociationMode::Server => v5::NtpMode::Response,
+                    mode => return Err(NtpHeaderBuilderError::UnsupportedMode(mode)),
+                };
+
+                // NTPv5 signals kiss codes through the poll field, so stratum 0
+                // needs no further fields here
+                Ok(NtpHeader::V5(v5::NtpHeaderV5 {
+                    mode,
+                    stratum: self.stratum,
+                    poll: self.poll,
+                    precision: self.precision,
+                    client_cookie: v5::NtpClientCookie::from_ntp_timestamp(self.origin_timestamp),
+                    receive_timestamp: self.receive_timestamp,
+                    transmit_timestamp: self.transmit_timestamp,
+                    ..v5::NtpHeaderV5::new()
+                }))
+            }
+            version => Err(NtpHeaderBuilderError::InvalidVersion(version)),
+        }
+    }
+}
+
 impl<'a> NtpPacket<'a> {
     pub fn into_owned(self) -> NtpPacket<'static> {
         NtpPacket::<'static> {
@@ -296,6 +525,68 @@ impl<'a> NtpPacket<'a> {
         }
     }
 
+    /// A packet without extension fields or MAC around the given header
+    pub fn from_header(header: NtpHeader) -> NtpPacket<'static> {
+        let efdata = match header {
+            NtpHeader::V3(_) | NtpHeader::V4(_) => ExtensionFieldData::default(),
+            NtpHeader::V5(_) => ExtensionFieldData {
+                authenticated: vec![],
+                encrypted: vec![],
+                untrusted: vec![ExtensionField::DraftIdentification(Cow::Borrowed(
+                    v5::DRAFT_VERSION,
+                ))],
+            },
+        };
+
+        NtpPacket {
+            header,
+            efdata,
+            mac: None,
+        }
+    }
+
+    /// Parse the header and list the extension fields of a packet, without
+    /// decrypting or otherwise interpreting them. Useful for debugging what
+    /// extension fields a server sends. Listing stops at the first field with
+    /// an invalid length, which is reported through
+    /// [`ExtensionFieldList::truncated`].
+    #[expect(clippy::result_large_err)]
+    pub fn inspect_extension_fields(
+        data: &'a [u8],
+    ) -> Result<(NtpHeader, ExtensionFieldList<'a>), PacketParsingError<'a>> {
+        if data.is_empty() {
+            return Err(PacketParsingError::TooShort);
+        }
+
+        let version = (data[0] & 0b0011_1000) >> 3;
+
+        match version {
+            3 => {
+                let (header, _) =
+                    NtpHeaderV3V4::deserialize(data).map_err(ParsingError::generalize)?;
+                Ok((NtpHeader::V3(header), ExtensionFieldList::default()))
+            }
+            4 => {
+                let (header, header_size) =
+                    NtpHeaderV3V4::deserialize(data).map_err(ParsingError::generalize)?;
+                let fields = ExtensionFieldList::parse(
+                    &data[header_size..],
+                    Mac::MAXIMUM_SIZE,
+                    ExtensionHeaderVersion::V4,
+                );
+                Ok((NtpHeader::V4(header), fields))
+            }
+            5 => {
+                let (header, header_size) =
+                    v5::NtpHeaderV5::deserialize(data).map_err(ParsingError::generalize)?;
+                let fields =
+                    ExtensionFieldList::parse(&data[header_size..], 0, ExtensionHeaderVersion::V5);
+                Ok((NtpHeader::V5(header), fields))
+            }
+            _ => Err(PacketParsingError::InvalidVersion(version)),
+        }
+    }
+
     #[expect(clippy::result_large_err)]
     // FIXME: Figure out a way to simplify and/or split this function.
     #[expect(clippy::too_many_lines)]
@@ -304,7 +595,7 @@ impl<'a> NtpPacket<'a> {
         cipher: &(impl CipherProvider + ?Sized),
     ) -> Result<(Self, Option<DecodedServerCookie>), PacketParsingError<'a>> {
         if data.is_empty() {
-            return Err(PacketParsingError::IncorrectLength);
+            return Err(PacketParsingError::TooShort);
         }
 
         let version = (data[0] & 0b0011_1000) >> 3;
@@ -1076,7 +1367,7 @@ impl<'a> NtpPacket<'a> {
 
     pub fn stratum(&self) -> u8 {
         match self.header {
-            NtpHeader::V3(header) | NtpHeader::V4(header) => header.stratum,
+            NtpHeader::V3(header) | NtpHeader::V4(header) => header.stratum.to_bits(),
             NtpHeader::V5(header) => header.stratum,
         }
     }
@@ -1116,6 +1407,14 @@ impl<'a> NtpPacket<'a> {
         }
     }
 
+    /// Reference timestamp of the packet, NTPv5 does not carry one.
+    pub fn reference_timestamp(&self) -> Option<NtpTimestamp> {
+        match self.header {
+            NtpHeader::V3(header) | NtpHeader::V4(header) => Some(header.reference_timestamp),
+            NtpHeader::V5(_header) => None,
+        }
+    }
+
     pub fn reference_id(&self) -> ReferenceId {
         match self.header {
             NtpHeader::V3(header) | NtpHeader::V4(header) => header.reference_id,
@@ -1124,6 +1423,14 @@ impl<'a> NtpPacket<'a> {
         }
     }
 
+    /// Timescale the server reports its time in, only carried by NTPv5.
+    pub fn timescale(&self) -> Option<v5::NtpTimescale> {
+        match self.header {
+            NtpHeader::V3(_) | NtpHeader::V4(_) => None,
+            NtpHeader::V5(header) => Some(header.timescale),
+        }
+    }
+
     fn kiss_code(&self) -> ReferenceId {
         match self.header {
             NtpHeader::V3(header) | NtpHeader::V4(header) => header.reference_id,
@@ -1136,8 +1443,8 @@ impl<'a> NtpPacket<'a> {
 
     pub fn is_kiss(&self) -> bool {
         match self.header {
-            NtpHeader::V3(header) | NtpHeader::V4(header) => header.stratum == 0,
-            NtpHeader::V5(header) => header.stratum == 0,
+            NtpHeader::V3(header) | NtpHeader::V4(header) => header.stratum.is_kiss_of_death(),
+            NtpHeader::V5(header) => Stratum::from_bits(header.stratum).is_kiss_of_death(),
         }
     }
 
@@ -1207,6 +1514,7 @@ impl<'a> NtpPacket<'a> {
         match self.header {
             NtpHeader::V3(header) | NtpHeader::V4(header) => {
                 header.origin_timestamp == identifier.expected_origin_timestamp
+                    || Some(header.origin_timestamp) == identifier.interleaved_origin_timestamp
             }
             NtpHeader::V5(header) => {
                 header.client_cookie
@@ -1215,6 +1523,49 @@ impl<'a> NtpPacket<'a> {
         }
     }
 
+    /// Turn an NTPv4 poll into an interleaved request. The origin timestamp
+    /// carries the receive timestamp of the previous response from the server,
+    /// so that it recognizes the request and sends the actual transmit
+    /// timestamp of that previous response. Servers that don't support
+    /// interleaved mode answer it as a regular request.
+    pub fn make_interleaved(
+        &mut self,
+        identifier: &mut RequestIdentifier,
+        previous_receive_timestamp: NtpTimestamp,
+    ) {
+        if let NtpHeader::V4(header) = &mut self.header {
+            // The server echoes this in the origin timestamp of an interleaved
+            // response, so just like the transmit timestamp it is random.
+            let receive_timestamp = thread_rng().r#gen();
+            header.origin_timestamp = previous_receive_timestamp;
+            header.receive_timestamp = receive_timestamp;
+            identifier.interleaved_origin_timestamp = Some(receive_timestamp);
+        }
+    }
+
+    /// Whether the packet is an interleaved response to the given request, in
+    /// which case its transmit timestamp belongs to the previous response.
+    pub fn is_interleaved_response(&self, identifier: RequestIdentifier) -> bool {
+        match self.header {
+            NtpHeader::V3(header) | NtpHeader::V4(header) => {
+                Some(header.origin_timestamp) == identifier.interleaved_origin_timestamp
+            }
+            NtpHeader::V5(_) => false,
+        }
+    }
+
+    /// Whether the packet answers the given request when ignoring any NTS
+    /// unique identifier, i.e. without checking its authentication.
+    pub fn valid_unauthenticated_server_response(&self, identifier: RequestIdentifier) -> bool {
+        self.valid_server_response(
+            RequestIdentifier {
+                uid: None,
+                ..identifier
+            },
+            false,
+        )
+    }
+
     pub fn untrusted_extension_fields(&self) -> impl Iterator<Item = &ExtensionField<'_>> {
         self.efdata.untrusted.iter()
     }
@@ -1223,6 +1574,38 @@ impl<'a> NtpPacket<'a> {
         self.efdata.authenticated.iter()
     }
 
+    pub fn encrypted_extension_fields(&self) -> impl Iterator<Item = &ExtensionField<'_>> {
+        self.efdata.encrypted.iter()
+    }
+
+    /// Extension fields of the given types that a server should echo back
+    /// in its response to this packet. For NTS packets only authenticated
+    /// fields are considered, as echoed fields end up in the authenticated
+    /// part of the response. NTPv3 packets cannot carry extension fields.
+    pub(crate) fn echoable_extension_fields(
+        &self,
+        type_ids: &[u16],
+    ) -> Vec<ExtensionField<'static>> {
+        if type_ids.is_empty() || matches!(self.header, NtpHeader::V3(_)) {
+            return vec![];
+        }
+
+        let candidates = if self.efdata.authenticated.is_empty() && self.efdata.encrypted.is_empty()
+        {
+            &self.efdata.untrusted
+        } else {
+            &self.efdata.authenticated
+        };
+
+        candidates
+            .iter()
+            .filter(|ef| {
+                matches!(ef, ExtensionField::Unknown { type_id, .. } if type_ids.contains(type_id))
+            })
+            .map(|ef| ef.clone().into_owned())
+            .collect()
+    }
+
     pub fn push_additional(&mut self, ef: ExtensionField<'static>) {
         if !self.efdata.authenticated.is_empty() || !self.efdata.encrypted.is_empty() {
             self.efdata.authenticated.push(ef);
@@ -1230,6 +1613,36 @@ impl<'a> NtpPacket<'a> {
             self.efdata.untrusted.push(ef);
         }
     }
+
+    /// Add a field to the encrypted part of an NTS packet. Only packets that
+    /// are serialized with an NTS cipher carry encrypted fields.
+    pub fn push_encrypted(&mut self, ef: ExtensionField<'static>) {
+        self.efdata.encrypted.push(ef);
+    }
+
+    /// Overwrite the reference timestamp of a response. The NTPv5 upgrade
+    /// timestamp is left in place, as the client relies on it being echoed.
+    pub(crate) fn set_response_reference_timestamp(&mut self, timestamp: NtpTimestamp) {
+        match &mut self.header {
+            NtpHeader::V3(header) | NtpHeader::V4(header) => {
+                if header.reference_timestamp != v5::UPGRADE_TIMESTAMP {
+                    header.reference_timestamp = timestamp;
+                }
+            }
+            NtpHeader::V5(_header) => {}
+        }
+    }
+
+    /// Turn a response into one from a symmetric passive peer. NTPv5 has no
+    /// symmetric modes, so its responses are left as they are.
+    pub(crate) fn set_symmetric_passive(&mut self) {
+        match &mut self.header {
+            NtpHeader::V3(header) | NtpHeader::V4(header) => {
+                header.mode = NtpAssociationMode::SymmetricPassive;
+            }
+            NtpHeader::V5(_header) => {}
+        }
+    }
 }
 
 // Returns whether all uid extension fields found match the given uid, or
@@ -1269,6 +1682,13 @@ impl NtpPacket<'_> {
         }
     }
 
+    pub fn origin_timestamp(&self) -> NtpTimestamp {
+        match self.header {
+            NtpHeader::V3(header) | NtpHeader::V4(header) => header.origin_timestamp,
+            NtpHeader::V5(header) => header.client_cookie.into_ntp_timestamp(),
+        }
+    }
+
     pub fn set_origin_timestamp(&mut self, timestamp: NtpTimestamp) {
         match &mut self.header {
             NtpHeader::V3(header) | NtpHeader::V4(header) => header.origin_timestamp = timestamp,
@@ -1292,6 +1712,22 @@ impl NtpPacket<'_> {
         }
     }
 
+    pub fn set_poll(&mut self, poll: PollInterval) {
+        match &mut self.header {
+            NtpHeader::V3(header) | NtpHeader::V4(header) => header.poll = poll,
+            NtpHeader::V5(header) => header.poll = poll,
+        }
+    }
+
+    /// Set the timescale of an NTPv5 packet. Older versions always use UTC
+    /// and carry no timescale, so their packets are left as they are.
+    pub fn set_timescale(&mut self, timescale: v5::NtpTimescale) {
+        match &mut self.header {
+            NtpHeader::V3(_) | NtpHeader::V4(_) => {}
+            NtpHeader::V5(header) => header.timescale = timescale,
+        }
+    }
+
     pub fn set_precision(&mut self, precision: i8) {
         match &mut self.header {
             NtpHeader::V3(header) | NtpHeader::V4(header) => header.precision = precision,
@@ -1308,7 +1744,9 @@ impl NtpPacket<'_> {
 
     pub fn set_stratum(&mut self, stratum: u8) {
         match &mut self.header {
-            NtpHeader::V3(header) | NtpHeader::V4(header) => header.stratum = stratum,
+            NtpHeader::V3(header) | NtpHeader::V4(header) => {
+                header.stratum = Stratum::from_bits(stratum);
+            }
             NtpHeader::V5(header) => header.stratum = stratum,
         }
     }
@@ -1421,6 +1859,37 @@ mod tests {
             assert_eq!(i, b);
             assert_eq!(a, c);
         }
+
+        assert_eq!(NtpAssociationMode::Client.to_bits(), 3);
+        assert_eq!(NtpAssociationMode::Server.to_bits(), 4);
+        assert_eq!(NtpAssociationMode::Broadcast.to_bits(), 5);
+
+        // Bits outside of the mode field are ignored
+        assert_eq!(
+            NtpAssociationMode::from_bits(0b0010_0011),
+            NtpAssociationMode::Client
+        );
+    }
+
+    #[test]
+    fn stratum_semantics() {
+        for i in 0..=u8::MAX {
+            assert_eq!(Stratum::from_bits(i).to_bits(), i);
+        }
+
+        assert_eq!(Stratum::from_bits(0), Stratum::UNSPECIFIED);
+        assert!(Stratum::UNSPECIFIED.is_kiss_of_death());
+        assert!(!Stratum::UNSPECIFIED.is_unsynchronized());
+
+        assert!(!Stratum::PRIMARY.is_kiss_of_death());
+        assert!(!Stratum::PRIMARY.is_unsynchronized());
+        assert!(!Stratum::from_bits(15).is_unsynchronized());
+
+        assert_eq!(Stratum::from_bits(16), Stratum::UNSYNCHRONIZED);
+        assert!(Stratum::UNSYNCHRONIZED.is_unsynchronized());
+        assert!(!Stratum::UNSYNCHRONIZED.is_kiss_of_death());
+        assert!(Stratum::from_bits(17).is_unsynchronized());
+        assert!(Stratum::from_bits(u8::MAX).is_unsynchronized());
     }
 
     #[test]
@@ -1430,7 +1899,7 @@ mod tests {
             header: NtpHeader::V4(NtpHeaderV3V4 {
                 leap: NtpLeapIndicator::NoWarning,
                 mode: NtpAssociationMode::Client,
-                stratum: 2,
+                stratum: Stratum::from_bits(2),
                 poll: PollInterval::from_byte(6),
                 precision: -24,
                 root_delay: NtpDuration::from_fixed_int(1023 << 16),
@@ -1459,7 +1928,7 @@ mod tests {
             header: NtpHeader::V3(NtpHeaderV3V4 {
                 leap: NtpLeapIndicator::NoWarning,
                 mode: NtpAssociationMode::Client,
-                stratum: 2,
+                stratum: Stratum::from_bits(2),
                 poll: PollInterval::from_byte(6),
                 precision: -24,
                 root_delay: NtpDuration::from_fixed_int(1023 << 16),
@@ -1491,7 +1960,7 @@ mod tests {
             header: NtpHeader::V4(NtpHeaderV3V4 {
                 leap: NtpLeapIndicator::NoWarning,
                 mode: NtpAssociationMode::Server,
-                stratum: 2,
+                stratum: Stratum::from_bits(2),
                 poll: PollInterval::from_byte(6),
                 precision: -23,
                 root_delay: NtpDuration::from_fixed_int(566 << 16),
@@ -1732,6 +2201,47 @@ mod tests {
         assert!(!response.valid_server_response(id, true));
     }
 
+    #[test]
+    fn test_interleaved_response_validation() {
+        let (mut packet, mut id) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+        let previous_receive = NtpTimestamp::from_fixed_int(1234);
+        packet.make_interleaved(&mut id, previous_receive);
+        assert_eq!(packet.origin_timestamp(), previous_receive);
+
+        let clock = TestClock {
+            now: NtpTimestamp::from_fixed_int(2),
+        };
+        let mut response = NtpPacket::timestamp_response(
+            NtpServerInfo::default(),
+            packet.clone(),
+            NtpTimestamp::from_fixed_int(0),
+            &clock,
+        );
+
+        // A server without interleaved mode answers as usual
+        assert!(response.valid_server_response(id, false));
+        assert!(!response.is_interleaved_response(id));
+
+        response.set_origin_timestamp(packet.receive_timestamp());
+        assert!(response.valid_server_response(id, false));
+        assert!(response.is_interleaved_response(id));
+
+        response.set_origin_timestamp(previous_receive);
+        assert!(!response.valid_server_response(id, false));
+        assert!(!response.is_interleaved_response(id));
+
+        // Regular requests never get an interleaved response
+        let (packet, id) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+        let mut response = NtpPacket::timestamp_response(
+            NtpServerInfo::default(),
+            packet,
+            previous_receive,
+            &clock,
+        );
+        response.set_origin_timestamp(NtpTimestamp::default());
+        assert!(!response.is_interleaved_response(id));
+    }
+
     #[test]
     fn v5_upgrade_packet() {
         let (packet, _) = NtpPacket::poll_message_upgrade_request(PollInterval::default());
@@ -2384,10 +2894,47 @@ mod tests {
 
         assert!(matches!(
             NtpPacket::deserialize(&data, &NoCipher),
-            Err(ParsingError::IncorrectLength)
+            Err(ParsingError::BadExtensionLength)
         ));
     }
 
+    #[test]
+    fn parse_error_variants() {
+        let parse = |data: &[u8]| NtpPacket::deserialize(data, &NoCipher).map(|_| ());
+
+        let (packet, _) = NtpPacket::poll_message(PollInterval::default());
+        let v4 = packet.serialize_without_encryption_vec(None).unwrap();
+
+        let err = parse(&[]).unwrap_err();
+        assert!(matches!(err, ParsingError::TooShort));
+        assert_eq!(err.category(), "too_short");
+        assert!(matches!(parse(&v4[..47]), Err(ParsingError::TooShort)));
+
+        let mut unsupported = v4.clone();
+        unsupported[0] = (unsupported[0] & !0b0011_1000) | (6 << 3);
+        let err = parse(&unsupported).unwrap_err();
+        assert!(matches!(err, ParsingError::InvalidVersion(6)));
+        assert_eq!(err.category(), "unsupported_version");
+
+        let (packet, _) = NtpPacket::poll_message_v5(PollInterval::default());
+        let mut v5 = packet.serialize_without_encryption_vec(None).unwrap();
+        v5[0] = (v5[0] & !0b111) | NtpAssociationMode::SymmetricActive.to_bits();
+        let err = parse(&v5).unwrap_err();
+        assert!(matches!(err, ParsingError::UnknownMode(1)));
+        assert_eq!(err.category(), "unknown_mode");
+
+        // An extension field claiming to extend beyond the end of the packet
+        let mut bad_extension = v4.clone();
+        bad_extension.extend([
+            0, 0, // Type = Unknown
+            0, 64, // Length = 64
+        ]);
+        bad_extension.extend([0; 28]);
+        let err = parse(&bad_extension).unwrap_err();
+        assert!(matches!(err, ParsingError::BadExtensionLength));
+        assert_eq!(err.category(), "bad_extension_length");
+    }
+
     #[test]
     fn padding_v5() {
         for i in 10..40 {
@@ -2402,4 +2949,191 @@ mod tests {
             assert!(NtpPacket::deserialize(&data, &NoCipher).is_ok());
         }
     }
+
+    fn poll_with_extension_fields() -> Vec<u8> {
+        let (packet, _) = NtpPacket::poll_message(PollInterval::default());
+        let mut data = packet.serialize_without_encryption_vec(None).unwrap();
+        data.extend([
+            0x01, 0x04, // Type = Unique identifier
+            0, 8, // Length = 8
+            1, 2, 3, 4, // Data
+        ]);
+        data.extend([
+            0xAB, 0xCD, // Type = Unknown
+            0, 28, // Length = 28
+        ]);
+        data.extend([0xEE; 24]);
+        data
+    }
+
+    #[test]
+    fn inspect_extension_fields() {
+        let data = poll_with_extension_fields();
+
+        let (header, list) = NtpPacket::inspect_extension_fields(&data).unwrap();
+        assert!(matches!(header, NtpHeader::V4(_)));
+        assert!(!list.truncated);
+        assert_eq!(
+            list.fields,
+            vec![
+                WireExtensionField {
+                    type_id: 0x104,
+                    length: 8,
+                    body: &[1, 2, 3, 4],
+                },
+                WireExtensionField {
+                    type_id: 0xABCD,
+                    length: 28,
+                    body: &[0xEE; 24],
+                },
+            ]
+        );
+
+        let (packet, _) = NtpPacket::poll_message_v5(PollInterval::default());
+        let data = packet.serialize_without_encryption_vec(None).unwrap();
+        let (header, list) = NtpPacket::inspect_extension_fields(&data).unwrap();
+        assert!(matches!(header, NtpHeader::V5(_)));
+        assert!(!list.truncated);
+        assert!(list.fields.iter().any(|field| field.type_id == 0xF5FF));
+    }
+
+    #[test]
+    fn inspect_extension_fields_overlong_length() {
+        let mut data = poll_with_extension_fields();
+        // Claim the second field extends beyond the end of the packet
+        data[48 + 8 + 2..48 + 8 + 4].copy_from_slice(&200u16.to_be_bytes());
+
+        let (_, list) = NtpPacket::inspect_extension_fields(&data).unwrap();
+        assert!(list.truncated);
+        assert_eq!(list.fields.len(), 1);
+        assert_eq!(list.fields[0].type_id, 0x104);
+    }
+
+    #[test]
+    fn inspect_extension_fields_arbitrary_lengths() {
+        let mut data = poll_with_extension_fields();
+        for length in 0..=u16::MAX {
+            data[48 + 2..48 + 4].copy_from_slice(&length.to_be_bytes());
+            let (_, list) = NtpPacket::inspect_extension_fields(&data).unwrap();
+            if length == 8 {
+                assert!(!list.truncated);
+            }
+            for field in &list.fields {
+                assert!(usize::from(field.length) >= 4 + field.body.len());
+            }
+            if list.truncated {
+                assert!(list.fields.len() <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn inspect_extension_fields_truncated_packet() {
+        let data = poll_with_extension_fields();
+        for end in 0..data.len() {
+            // Must never panic, regardless of where the packet is cut off
+            let Ok((_, list)) = NtpPacket::inspect_extension_fields(&data[..end]) else {
+                assert!(end < 48);
+                continue;
+            };
+            assert!(list.fields.len() <= 2);
+            for field in &list.fields {
+                assert_eq!(usize::from(field.length), 4 + field.body.len());
+            }
+        }
+    }
+
+    #[test]
+    fn set_timescale_older_versions() {
+        // Only NTPv5 carries a timescale, for older versions it is a no-op
+        let mut packet = NtpPacket::test();
+        let before = packet.serialize_without_encryption_vec(None).unwrap();
+        packet.set_timescale(v5::NtpTimescale::Tai);
+        assert_eq!(packet.timescale(), None);
+        assert_eq!(
+            packet.serialize_without_encryption_vec(None).unwrap(),
+            before
+        );
+    }
+
+    #[test]
+    fn header_builder_roundtrip() {
+        for version in 3..=5 {
+            let mut builder = NtpHeaderBuilder::new()
+                .version(version)
+                .mode(NtpAssociationMode::Server)
+                .stratum(2)
+                .poll(PollInterval::from_byte(6))
+                .precision(-20)
+                .origin_timestamp(NtpTimestamp::from_fixed_int(1))
+                .receive_timestamp(NtpTimestamp::from_fixed_int(2))
+                .transmit_timestamp(NtpTimestamp::from_fixed_int(3));
+            if version != 5 {
+                builder = builder
+                    .reference_id(ReferenceId::from_int(0x7f000001))
+                    .reference_timestamp(NtpTimestamp::from_fixed_int(4));
+            }
+            let header = builder.build().unwrap();
+
+            let data = NtpPacket::from_header(header)
+                .serialize_without_encryption_vec(None)
+                .unwrap();
+            let (packet, _) = NtpPacket::deserialize(&data, &NoCipher).unwrap();
+
+            assert_eq!(packet.header(), header);
+            assert_eq!(packet.version().as_u8(), version);
+            assert_eq!(packet.mode(), NtpAssociationMode::Server);
+            assert_eq!(packet.stratum(), 2);
+            assert_eq!(packet.poll(), PollInterval::from_byte(6));
+            assert_eq!(packet.precision(), -20);
+            assert_eq!(packet.receive_timestamp(), NtpTimestamp::from_fixed_int(2));
+            assert_eq!(packet.transmit_timestamp(), NtpTimestamp::from_fixed_int(3));
+            if version != 5 {
+                assert_eq!(packet.reference_id(), ReferenceId::from_int(0x7f000001));
+            }
+        }
+    }
+
+    #[test]
+    fn header_builder_validation() {
+        assert_eq!(
+            NtpHeaderBuilder::new().version(2).build(),
+            Err(NtpHeaderBuilderError::InvalidVersion(2))
+        );
+        assert_eq!(
+            NtpHeaderBuilder::new().version(6).build(),
+            Err(NtpHeaderBuilderError::InvalidVersion(6))
+        );
+        assert_eq!(
+            NtpHeaderBuilder::new()
+                .mode(NtpAssociationMode::Server)
+                .stratum(0)
+                .build(),
+            Err(NtpHeaderBuilderError::MissingKissCode)
+        );
+        assert!(
+            NtpHeaderBuilder::new()
+                .mode(NtpAssociationMode::Server)
+                .stratum(0)
+                .reference_id(ReferenceId::KISS_RATE)
+                .build()
+                .is_ok()
+        );
+        assert_eq!(
+            NtpHeaderBuilder::new()
+                .version(5)
+                .mode(NtpAssociationMode::Broadcast)
+                .build(),
+            Err(NtpHeaderBuilderError::UnsupportedMode(
+                NtpAssociationMode::Broadcast
+            ))
+        );
+        assert_eq!(
+            NtpHeaderBuilder::new()
+                .version(5)
+                .reference_id(ReferenceId::KISS_RATE)
+                .build(),
+            Err(NtpHeaderBuilderError::UnsupportedField("reference id"))
+        );
+    }
 }
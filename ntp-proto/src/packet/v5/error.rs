@@ -4,8 +4,6 @@ use std::fmt::{Display, Formatter};
 #[derive(Debug)]
 pub enum V5Error {
     InvalidDraftIdentification,
-    MalformedTimescale,
-    MalformedMode,
     InvalidFlags,
 }
 
@@ -20,8 +18,6 @@ impl Display for V5Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::InvalidDraftIdentification => f.write_str("Draft Identification invalid"),
-            Self::MalformedTimescale => f.write_str("Malformed timescale"),
-            Self::MalformedMode => f.write_str("Malformed mode"),
             Self::InvalidFlags => f.write_str("Invalid flags specified"),
         }
     }
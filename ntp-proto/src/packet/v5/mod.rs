@@ -4,6 +4,7 @@ use crate::{
     system::NtpServerInfo,
 };
 use rand::random;
+use serde::{Deserialize, Serialize};
 
 mod error;
 pub mod extension_fields;
@@ -29,7 +30,7 @@ impl NtpMode {
         Ok(match bits {
             3 => Self::Request,
             4 => Self::Response,
-            _ => return Err(V5Error::MalformedMode.into_parse_err()),
+            _ => return Err(ParsingError::UnknownMode(bits)),
         })
     }
 
@@ -48,28 +49,35 @@ impl NtpMode {
     }
 }
 
-#[repr(u8)]
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
 pub enum NtpTimescale {
-    Utc = 0,
-    Tai = 1,
-    Ut1 = 2,
-    LeapSmearedUtc = 3,
+    Utc,
+    Tai,
+    Ut1,
+    LeapSmearedUtc,
+    /// A timescale not defined by the draft we implement, kept as its raw code
+    Unknown(u8),
 }
 
 impl NtpTimescale {
-    const fn from_bits(bits: u8) -> Result<Self, ParsingError<std::convert::Infallible>> {
-        Ok(match bits {
+    const fn from_bits(bits: u8) -> Self {
+        match bits {
             0 => Self::Utc,
             1 => Self::Tai,
             2 => Self::Ut1,
             3 => Self::LeapSmearedUtc,
-            _ => return Err(V5Error::MalformedTimescale.into_parse_err()),
-        })
+            _ => Self::Unknown(bits),
+        }
     }
 
     const fn to_bits(self) -> u8 {
-        self as u8
+        match self {
+            Self::Utc => 0,
+            Self::Tai => 1,
+            Self::Ut1 => 2,
+            Self::LeapSmearedUtc => 3,
+            Self::Unknown(bits) => bits,
+        }
     }
 }
 
@@ -162,7 +170,7 @@ pub struct NtpHeaderV5 {
 }
 
 impl NtpHeaderV5 {
-    fn new() -> Self {
+    pub(super) fn new() -> Self {
         Self {
             leap: NtpLeapIndicator::NoWarning,
             mode: NtpMode::Request,
@@ -269,7 +277,7 @@ impl NtpHeaderV5 {
         data: &[u8],
     ) -> Result<(Self, usize), ParsingError<std::convert::Infallible>> {
         if data.len() < Self::WIRE_LENGTH {
-            return Err(ParsingError::IncorrectLength);
+            return Err(ParsingError::TooShort);
         }
 
         let version = (data[0] >> 3) & 0b111;
@@ -286,7 +294,7 @@ impl NtpHeaderV5 {
                 precision: data[3] as i8,
                 root_delay: NtpDuration::from_bits_time32(data[4..8].try_into().unwrap()),
                 root_dispersion: NtpDuration::from_bits_time32(data[8..12].try_into().unwrap()),
-                timescale: NtpTimescale::from_bits(data[12])?,
+                timescale: NtpTimescale::from_bits(data[12]),
                 era: NtpEra(data[13]),
                 flags: NtpFlags::from_bits(data[14..16].try_into().unwrap())?,
                 server_cookie: NtpServerCookie(data[16..24].try_into().unwrap()),
@@ -326,6 +334,7 @@ impl NtpHeaderV5 {
             packet,
             RequestIdentifier {
                 expected_origin_timestamp: client_cookie.into_ntp_timestamp(),
+                interleaved_origin_timestamp: None,
                 uid: None,
             },
         )
@@ -340,9 +349,9 @@ mod tests {
     #[test]
     fn round_trip_timescale() {
         for i in 0..=u8::MAX {
-            if let Ok(ts) = NtpTimescale::from_bits(i) {
-                assert_eq!(ts as u8, i);
-            }
+            let ts = NtpTimescale::from_bits(i);
+            assert_eq!(ts.to_bits(), i);
+            assert_eq!(matches!(ts, NtpTimescale::Unknown(_)), i > 3);
         }
     }
 
@@ -584,7 +593,7 @@ mod tests {
                 stratum: i.wrapping_add(1),
                 poll: PollInterval::from_byte(i.wrapping_add(3)),
                 precision: i.wrapping_add(4) as i8,
-                timescale: NtpTimescale::from_bits(i % 4).unwrap(),
+                timescale: NtpTimescale::from_bits(i % 4),
                 era: NtpEra(i.wrapping_add(6)),
                 flags: NtpFlags {
                     synchronized: true,
@@ -610,13 +619,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_timescale() {
+        let header = NtpHeaderV5 {
+            mode: NtpMode::Response,
+            ..NtpHeaderV5::new()
+        };
+        let mut buffer: [u8; 48] = [0u8; 48];
+        header
+            .serialize(&mut Cursor::new(buffer.as_mut_slice()))
+            .unwrap();
+
+        for (bits, timescale) in [
+            (0, NtpTimescale::Utc),
+            (1, NtpTimescale::Tai),
+            (2, NtpTimescale::Ut1),
+            (3, NtpTimescale::LeapSmearedUtc),
+            (4, NtpTimescale::Unknown(4)),
+            (0xff, NtpTimescale::Unknown(0xff)),
+        ] {
+            buffer[12] = bits;
+            let (parsed, _) = NtpHeaderV5::deserialize(&buffer).unwrap();
+            assert_eq!(parsed.timescale, timescale);
+
+            // Unknown codes must survive being re-encoded
+            let mut reencoded: [u8; 48] = [0u8; 48];
+            parsed
+                .serialize(&mut Cursor::new(reencoded.as_mut_slice()))
+                .unwrap();
+            assert_eq!(reencoded, buffer);
+        }
+    }
+
     #[test]
     fn fail_on_incorrect_length() {
         let data: [u8; 47] = [0u8; 47];
 
         assert!(matches!(
             NtpHeaderV5::deserialize(&data),
-            Err(ParsingError::IncorrectLength)
+            Err(ParsingError::TooShort)
         ));
     }
 
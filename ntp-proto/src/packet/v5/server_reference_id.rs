@@ -2,7 +2,8 @@ use crate::packet::v5::NtpClientCookie;
 use crate::packet::v5::extension_fields::{ReferenceIdRequest, ReferenceIdResponse};
 use rand::distributions::{Distribution, Standard};
 use rand::{Rng, thread_rng};
-use std::fmt::{Debug, Formatter};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use std::fmt::{Debug, Formatter, Write};
 
 #[derive(Copy, Clone, Debug)]
 struct U12(u16);
@@ -137,6 +138,43 @@ impl Default for BloomFilter {
     }
 }
 
+/// Serialized as a hex string, so observers can inspect the filter
+impl Serialize for BloomFilter {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let hex = self.0.iter().fold(String::new(), |mut out, byte| {
+            let _ = write!(out, "{byte:02x}");
+            out
+        });
+        hex.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BloomFilter {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() != 2 * Self::BYTES || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(de::Error::invalid_value(
+                de::Unexpected::Str(&hex),
+                &"a hex string of 1024 characters",
+            ));
+        }
+
+        let mut filter = Self::new();
+        for (byte, digits) in filter.0.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+            let digits = std::str::from_utf8(digits).map_err(de::Error::custom)?;
+            *byte = u8::from_str_radix(digits, 16).map_err(de::Error::custom)?;
+        }
+
+        Ok(filter)
+    }
+}
+
 impl Debug for BloomFilter {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let str: String = self
@@ -397,4 +435,20 @@ mod tests {
             assert_eq!(&target_filter, result_filter);
         }
     }
+
+    #[test]
+    fn serde_roundtrip() {
+        let mut filter = BloomFilter::new();
+        filter.add_id(&ServerId::default());
+        filter.add_id(&ServerId::default());
+
+        let json = serde_json::to_string(&filter).unwrap();
+        assert_eq!(json.len(), 2 * BloomFilter::BYTES + 2);
+        let parsed: BloomFilter = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, filter);
+
+        assert!(serde_json::from_str::<BloomFilter>("\"00ff\"").is_err());
+        let invalid = format!("\"{}\"", "zz".repeat(BloomFilter::BYTES));
+        assert!(serde_json::from_str::<BloomFilter>(&invalid).is_err());
+    }
 }
@@ -1,4 +1,5 @@
 use std::{
+    borrow::Cow,
     collections::hash_map::RandomState,
     fmt::Display,
     io::Cursor,
@@ -7,13 +8,19 @@ use std::{
     time::{Duration, Instant},
 };
 
-use serde::{Deserialize, Deserializer, de};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
 
 use crate::{
-    Cipher, KeySet, NtpClock, NtpPacket, NtpTimestamp, NtpVersion, PacketParsingError,
-    ipfilter::IpFilter, system::NtpServerInfo,
+    Cipher, KeySet, NtpClock, NtpDuration, NtpLeapIndicator, NtpPacket, NtpTimestamp, NtpVersion,
+    PacketParsingError, ReferenceId, ipfilter::IpFilter, packet::ExtensionField,
+    system::NtpServerInfo,
 };
 
+/// Extension field type with which NTS clients ask for the status of a server
+pub const SERVER_STATUS_REQUEST_TYPE: u16 = 0xF510;
+/// Extension field type carrying the status of a server in its response
+pub const SERVER_STATUS_RESPONSE_TYPE: u16 = 0xF511;
+
 pub enum ServerAction<'a> {
     Ignore,
     Respond { message: &'a [u8] },
@@ -48,9 +55,95 @@ pub enum ServerResponse {
 pub trait ServerStatHandler {
     /// Called by the server handle once per packet
     fn register(&mut self, version: u8, nts: bool, reason: ServerReason, response: ServerResponse);
+
+    /// Called in addition to `register` for packets that could not be
+    /// parsed, with the category of the parse error
+    fn register_parse_error(&mut self, _category: &'static str) {}
+
+    /// Statistics to report in response to a status query, if available
+    fn status(&self) -> Option<ServerStatus> {
+        None
+    }
+}
+
+/// Minimal statistics of a server, which NTS authenticated clients can query
+/// through an extension field. There is deliberately no unauthenticated way
+/// to obtain these, unlike the mode 6 and 7 queries of the classic ntpd.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServerStatus {
+    pub received_packets: u64,
+    pub accepted_packets: u64,
+    pub denied_packets: u64,
+    pub ignored_packets: u64,
+    pub rate_limited_packets: u64,
+}
+
+impl ServerStatus {
+    const ENCODED_LENGTH: usize = 5 * 8;
+
+    /// The extension field with which a client asks for the server status.
+    /// It must be sent in the encrypted part of an NTS request. Its body is
+    /// as large as that of the response, so that answering the query never
+    /// makes the response larger than the request.
+    pub fn request() -> ExtensionField<'static> {
+        ExtensionField::Unknown {
+            type_id: SERVER_STATUS_REQUEST_TYPE,
+            data: Cow::Borrowed(&[0; Self::ENCODED_LENGTH]),
+        }
+    }
+
+    pub fn is_request(ef: &ExtensionField<'_>) -> bool {
+        matches!(
+            ef,
+            ExtensionField::Unknown { type_id, data }
+                if *type_id == SERVER_STATUS_REQUEST_TYPE && data.len() >= Self::ENCODED_LENGTH
+        )
+    }
+
+    pub fn to_extension_field(&self) -> ExtensionField<'static> {
+        let data: Vec<u8> = [
+            self.received_packets,
+            self.accepted_packets,
+            self.denied_packets,
+            self.ignored_packets,
+            self.rate_limited_packets,
+        ]
+        .iter()
+        .flat_map(|value| value.to_be_bytes())
+        .collect();
+
+        ExtensionField::Unknown {
+            type_id: SERVER_STATUS_RESPONSE_TYPE,
+            data: Cow::Owned(data),
+        }
+    }
+
+    /// Decode the server status from an extension field of a response. Any
+    /// data beyond the known counters (such as padding) is ignored.
+    pub fn from_extension_field(ef: &ExtensionField<'_>) -> Option<Self> {
+        let ExtensionField::Unknown { type_id, data } = ef else {
+            return None;
+        };
+        if *type_id != SERVER_STATUS_RESPONSE_TYPE || data.len() < Self::ENCODED_LENGTH {
+            return None;
+        }
+
+        let mut counters = data[..Self::ENCODED_LENGTH]
+            .chunks_exact(8)
+            .map(|chunk| u64::from_be_bytes(chunk.try_into().unwrap_or_default()));
+        let mut next = || counters.next().unwrap_or_default();
+
+        Some(ServerStatus {
+            received_packets: next(),
+            accepted_packets: next(),
+            denied_packets: next(),
+            ignored_packets: next(),
+            rate_limited_packets: next(),
+        })
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum FilterAction {
     Ignore,
@@ -66,20 +159,64 @@ impl From<FilterAction> for ServerResponse {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 pub struct FilterList {
     pub filter: Vec<IpSubnet>,
     pub action: FilterAction,
 }
 
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
+pub struct ReferenceIdFilterList {
+    #[serde(
+        deserialize_with = "crate::config::deserialize_refid_list",
+        serialize_with = "crate::config::serialize_refid_list"
+    )]
+    pub filter: Vec<ReferenceId>,
+    pub action: FilterAction,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ServerConfig {
     pub denylist: FilterList,
     pub allowlist: FilterList,
+    /// Stop serving time while the system is synchronized to a source
+    /// with one of these reference ids
+    pub reference_id_denylist: Option<ReferenceIdFilterList>,
     pub rate_limiting_cache_size: usize,
     pub rate_limiting_cutoff: Duration,
+    /// Rate limiting cutoff for NTS authenticated clients, which are kept
+    /// apart from other clients. Without one, NTS clients are never limited.
+    pub nts_rate_limiting_cutoff: Option<Duration>,
+    /// Prefix length with which IPv4 clients are grouped for rate limiting.
+    /// All clients in the same subnet share a single rate limiting entry.
+    pub rate_limiting_prefix_v4: u8,
+    /// Prefix length with which IPv6 clients are grouped for rate limiting
+    pub rate_limiting_prefix_v6: u8,
     pub require_nts: Option<FilterAction>,
     pub accepted_versions: Vec<NtpVersion>,
+    /// Types of (otherwise unknown) extension fields that are echoed back
+    /// to the client in time responses. Other unknown fields are dropped.
+    pub echo_extension_fields: Vec<u16>,
+    /// Answer status queries from NTS authenticated clients
+    pub allow_status_query: bool,
+    /// Cadence (in seconds) on which the reference timestamp in responses is
+    /// refreshed. Served reference timestamps are never older than this.
+    pub reference_timestamp_interval: u32,
+    /// Warn when the synchronization loop has not updated the time snapshot
+    /// for longer than this
+    pub max_reference_age: Option<NtpDuration>,
+    /// Answer requests while our own clock is not synchronized. When false,
+    /// such requests are ignored instead of answered with stratum 16.
+    pub serve_when_unsynchronized: bool,
+    /// Never answer unauthenticated requests with a response larger than the
+    /// request, limiting the amplification the server offers for abuse.
+    /// Responses are padded to the size of the request where the protocol
+    /// allows, and oversized responses are dropped.
+    pub pad_responses: bool,
+    /// Answer NTPv3 and NTPv4 requests in symmetric active mode with a
+    /// symmetric passive response, as if the peer were a client. No peer
+    /// association is formed, so the server never takes time from such peers.
+    pub allow_symmetric_peers: bool,
 }
 
 pub struct Server<C> {
@@ -87,9 +224,11 @@ pub struct Server<C> {
     clock: C,
     denyfilter: IpFilter,
     allowfilter: IpFilter,
-    client_cache: TimestampedCache<IpAddr>,
+    client_cache: TimestampedCache<IpSubnet>,
+    nts_client_cache: TimestampedCache<IpSubnet>,
     server_info: Arc<RwLock<NtpServerInfo>>,
     keyset: Arc<KeySet>,
+    reference_stale: bool,
 }
 
 // Quick estimation of ntp packet message version without doing full parsing
@@ -108,14 +247,17 @@ impl<C> Server<C> {
         let denyfilter = IpFilter::new(&config.denylist.filter);
         let allowfilter = IpFilter::new(&config.allowlist.filter);
         let client_cache = TimestampedCache::new(config.rate_limiting_cache_size);
+        let nts_client_cache = TimestampedCache::new(config.rate_limiting_cache_size);
         Self {
             config,
             clock,
             denyfilter,
             allowfilter,
             client_cache,
+            nts_client_cache,
             server_info,
             keyset,
+            reference_stale: false,
         }
     }
 
@@ -124,6 +266,27 @@ impl<C> Server<C> {
         self.keyset = keyset;
     }
 
+    /// Check whether the time snapshot we serve from has been updated by the
+    /// synchronization loop recently enough, warning once when it goes stale.
+    fn check_reference_age(&mut self, server_info: &NtpServerInfo, now: NtpTimestamp) -> bool {
+        let last_update = server_info.time_snapshot.root_variance_base_time;
+        // A snapshot that was never updated (e.g. without any sources) is not
+        // disciplined at all, so there is nothing to warn about going stale.
+        let stale = self.config.max_reference_age.is_some_and(|max_age| {
+            last_update != NtpTimestamp::default() && now - last_update > max_age
+        });
+
+        if stale && !self.reference_stale {
+            tracing::warn!(
+                age = (now - last_update).to_seconds(),
+                "Time snapshot has not been updated by the synchronization loop for longer than max-reference-age"
+            );
+        }
+        self.reference_stale = stale;
+
+        stale
+    }
+
     fn intended_action(&mut self, client_ip: IpAddr) -> (ServerResponse, ServerReason) {
         if self.denyfilter.is_in(&client_ip) {
             // First apply denylist
@@ -131,18 +294,62 @@ impl<C> Server<C> {
         } else if !self.allowfilter.is_in(&client_ip) {
             // Then allowlist
             (self.config.allowlist.action.into(), ServerReason::Policy)
-        } else if !self.client_cache.is_allowed(
-            client_ip,
-            Instant::now(),
-            self.config.rate_limiting_cutoff,
-        ) {
-            // Then ratelimit
-            (ServerResponse::Ignore, ServerReason::RateLimit)
+        } else if let Some(action) = self.reference_id_action() {
+            // Then the reference id of our own time source
+            (action.into(), ServerReason::Policy)
+        } else if !self.config.serve_when_unsynchronized && !self.is_synchronized() {
+            // Then whether we have time worth serving at all
+            (ServerResponse::Ignore, ServerReason::Policy)
         } else {
-            // Then accept
+            // Then accept, subject to rate limiting once we know whether the
+            // client uses NTS
             (ServerResponse::ProvideTime, ServerReason::Policy)
         }
     }
+
+    /// Whether the client is within its rate limit. NTS authenticated
+    /// clients are tracked separately, so that unauthenticated traffic
+    /// from the same address can't get them limited.
+    fn is_within_rate_limit(&mut self, client_ip: IpAddr, authenticated: bool) -> bool {
+        let prefix = match client_ip {
+            IpAddr::V4(_) => self.config.rate_limiting_prefix_v4,
+            IpAddr::V6(_) => self.config.rate_limiting_prefix_v6,
+        };
+        let client_subnet = IpSubnet::containing(client_ip, prefix);
+
+        if authenticated {
+            self.config.nts_rate_limiting_cutoff.is_none_or(|cutoff| {
+                self.nts_client_cache
+                    .is_allowed(client_subnet, Instant::now(), cutoff)
+            })
+        } else {
+            self.client_cache.is_allowed(
+                client_subnet,
+                Instant::now(),
+                self.config.rate_limiting_cutoff,
+            )
+        }
+    }
+
+    fn is_synchronized(&self) -> bool {
+        !matches!(
+            self.server_info
+                .read()
+                .unwrap()
+                .time_snapshot
+                .leap_indicator,
+            NtpLeapIndicator::Unknown | NtpLeapIndicator::Unsynchronized
+        )
+    }
+
+    fn reference_id_action(&self) -> Option<FilterAction> {
+        let denylist = self.config.reference_id_denylist.as_ref()?;
+        let reference_id = self.server_info.read().unwrap().ntp_snapshot.reference_id;
+        denylist
+            .filter
+            .contains(&reference_id)
+            .then_some(denylist.action)
+    }
 }
 
 pub struct HandleInnerData<'a> {
@@ -185,8 +392,19 @@ impl<C: NtpClock> Server<C> {
         let mut cursor = Cursor::new(buffer);
         match packet.serialize(&mut cursor, &cipher.as_deref(), desired_size) {
             Ok(()) => {
-                stats_handler.register(version.into(), nts, reason, action);
                 let length = cursor.position();
+                if self.config.pad_responses && !nts && length as usize > message.len() {
+                    tracing::debug!("Dropping response that would be larger than the request");
+                    stats_handler.register(
+                        version.into(),
+                        nts,
+                        ServerReason::Policy,
+                        ServerResponse::Ignore,
+                    );
+                    return ServerAction::Ignore;
+                }
+
+                stats_handler.register(version.into(), nts, reason, action);
                 ServerAction::Respond {
                     message: &cursor.into_inner()[..length as _],
                 }
@@ -214,6 +432,7 @@ impl<C: NtpClock> Server<C> {
         stats_handler: &mut impl ServerStatHandler,
     ) -> Result<HandleInnerData<'a>, ServerAction<'static>> {
         let (mut action, mut reason) = self.intended_action(client_ip);
+        let subject_to_rate_limit = action == ServerResponse::ProvideTime;
         if action == ServerResponse::Ignore {
             // Early exit for ignore
             stats_handler.register(fallback_message_version(message), false, reason, action);
@@ -223,7 +442,11 @@ impl<C: NtpClock> Server<C> {
         // Try and parse the message
         let (packet, cookie) = match NtpPacket::deserialize(message, self.keyset.as_ref()) {
             Ok((packet, cookie)) => {
-                if packet.mode() == crate::NtpAssociationMode::Client {
+                let symmetric = packet.mode() == crate::NtpAssociationMode::SymmetricActive
+                    && self.config.allow_symmetric_peers
+                    // NTS only defines client/server exchanges
+                    && cookie.is_none();
+                if packet.mode() == crate::NtpAssociationMode::Client || symmetric {
                     (packet, cookie)
                 } else {
                     stats_handler.register(
@@ -243,7 +466,9 @@ impl<C: NtpClock> Server<C> {
                 }
                 (packet, None)
             }
-            Err(_) => {
+            Err(e) => {
+                tracing::debug!(category = e.category(), "Could not parse request: {}", e);
+                stats_handler.register_parse_error(e.category());
                 stats_handler.register(
                     fallback_message_version(message),
                     false,
@@ -256,6 +481,7 @@ impl<C: NtpClock> Server<C> {
 
         // Generate the appropriate response
         let version = packet.version();
+        let symmetric_request = packet.mode() == crate::NtpAssociationMode::SymmetricActive;
 
         if !self.config.accepted_versions.contains(&version) {
             // handle this packet as if we don't know it
@@ -268,6 +494,16 @@ impl<C: NtpClock> Server<C> {
             return Err(ServerAction::Ignore);
         }
 
+        if subject_to_rate_limit && !self.is_within_rate_limit(client_ip, cookie.is_some()) {
+            stats_handler.register(
+                version.into(),
+                cookie.is_some(),
+                ServerReason::RateLimit,
+                ServerResponse::Ignore,
+            );
+            return Err(ServerAction::Ignore);
+        }
+
         let nts = cookie.is_some() || action == ServerResponse::NTSNak;
 
         // ignore non-NTS packets when configured to require NTS
@@ -285,15 +521,51 @@ impl<C: NtpClock> Server<C> {
             reason = ServerReason::Policy;
         }
 
-        let server_info = *self.server_info.read().unwrap();
+        let mut server_info = *self.server_info.read().unwrap();
+        if action == ServerResponse::ProvideTime {
+            self.check_reference_age(&server_info, recv_timestamp);
+        }
+        if server_info.draining {
+            // Push clients towards other servers before we go away
+            server_info.time_snapshot.leap_indicator = NtpLeapIndicator::Unknown;
+            server_info.ntp_snapshot.stratum = 16;
+        }
+
+        let echoed = if action == ServerResponse::ProvideTime {
+            packet.echoable_extension_fields(&self.config.echo_extension_fields)
+        } else {
+            vec![]
+        };
+
+        // Status queries are only answered over NTS, the status request must
+        // be authenticated (and is answered in the encrypted part).
+        let status = if action == ServerResponse::ProvideTime
+            && self.config.allow_status_query
+            && cookie.is_some()
+            && packet
+                .authenticated_extension_fields()
+                .chain(packet.encrypted_extension_fields())
+                .any(ServerStatus::is_request)
+        {
+            stats_handler.status()
+        } else {
+            if packet
+                .untrusted_extension_fields()
+                .any(ServerStatus::is_request)
+            {
+                tracing::debug!("Refusing unauthenticated status query");
+            }
+            None
+        };
 
-        let (packet, cipher, desired_size) = match action {
+        let (mut packet, cipher, desired_size) = match action {
             ServerResponse::NTSNak => (NtpPacket::nts_nak_response(packet), None, None),
             ServerResponse::Deny => {
                 if let Some(cookie) = cookie {
                     (NtpPacket::nts_deny_response(packet), Some(cookie.s2c), None)
                 } else {
-                    (NtpPacket::deny_response(packet), None, None)
+                    let desired_size = self.config.pad_responses.then_some(message.len());
+                    (NtpPacket::deny_response(packet), None, desired_size)
                 }
             }
             ServerResponse::ProvideTime => {
@@ -326,6 +598,24 @@ impl<C: NtpClock> Server<C> {
             ServerResponse::Ignore => unreachable!(),
         };
 
+        if symmetric_request {
+            packet.set_symmetric_passive();
+        }
+
+        if action == ServerResponse::ProvideTime {
+            packet.set_response_reference_timestamp(
+                recv_timestamp.truncated_to_interval(self.config.reference_timestamp_interval),
+            );
+        }
+
+        for ef in echoed {
+            packet.push_additional(ef);
+        }
+
+        if let Some(status) = status {
+            packet.push_encrypted(status.to_extension_field());
+        }
+
         Ok(HandleInnerData {
             action,
             reason,
@@ -417,6 +707,33 @@ pub struct IpSubnet {
     pub mask: u8,
 }
 
+impl IpSubnet {
+    /// The subnet with the given prefix length that contains `addr`. Prefix
+    /// lengths longer than the address are clamped to the full address.
+    pub fn containing(addr: IpAddr, mask: u8) -> Self {
+        match addr {
+            IpAddr::V4(addr) => {
+                let mask = mask.min(32);
+                let bits =
+                    u32::from(addr) & u32::MAX.checked_shl(32 - u32::from(mask)).unwrap_or(0);
+                IpSubnet {
+                    addr: IpAddr::V4(bits.into()),
+                    mask,
+                }
+            }
+            IpAddr::V6(addr) => {
+                let mask = mask.min(128);
+                let bits =
+                    u128::from(addr) & u128::MAX.checked_shl(128 - u32::from(mask)).unwrap_or(0);
+                IpSubnet {
+                    addr: IpAddr::V6(bits.into()),
+                    mask,
+                }
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SubnetParseError {
     Subnet,
@@ -470,6 +787,21 @@ impl<'de> Deserialize<'de> for IpSubnet {
     }
 }
 
+impl Display for IpSubnet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.mask)
+    }
+}
+
+impl Serialize for IpSubnet {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 #[cfg(test)]
 #[expect(
     clippy::too_many_lines,
@@ -529,6 +861,7 @@ mod tests {
     #[derive(Debug, Default)]
     struct TestStatHandler {
         last_register: Option<(u8, bool, ServerReason, ServerResponse)>,
+        last_parse_error: Option<&'static str>,
     }
 
     impl ServerStatHandler for TestStatHandler {
@@ -542,8 +875,24 @@ mod tests {
             assert!(self.last_register.is_none());
             self.last_register = Some((version, nts, reason, response));
         }
+
+        fn register_parse_error(&mut self, category: &'static str) {
+            self.last_parse_error = Some(category);
+        }
+
+        fn status(&self) -> Option<ServerStatus> {
+            Some(TEST_STATUS)
+        }
     }
 
+    const TEST_STATUS: ServerStatus = ServerStatus {
+        received_packets: 10,
+        accepted_packets: 7,
+        denied_packets: 1,
+        ignored_packets: 2,
+        rate_limited_packets: 0,
+    };
+
     fn serialize_packet_unencrypted(send_packet: &NtpPacket) -> Vec<u8> {
         let mut buf = vec![0; 1024];
         let mut cursor = Cursor::new(buf.as_mut_slice());
@@ -576,9 +925,20 @@ mod tests {
                 action: FilterAction::Ignore,
             },
             rate_limiting_cutoff: Duration::from_secs(1),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -643,9 +1003,20 @@ mod tests {
                 action: FilterAction::Deny,
             },
             rate_limiting_cutoff: Duration::from_secs(1),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -686,9 +1057,20 @@ mod tests {
                 action: FilterAction::Ignore,
             },
             rate_limiting_cutoff: Duration::from_secs(1),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -759,9 +1141,20 @@ mod tests {
                 action: FilterAction::Ignore,
             },
             rate_limiting_cutoff: Duration::from_secs(1),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -784,6 +1177,134 @@ mod tests {
         assert!(matches!(response, ServerAction::Ignore));
     }
 
+    #[test]
+    fn test_server_reference_id_filter() {
+        let config = ServerConfig {
+            denylist: FilterList {
+                filter: vec![],
+                action: FilterAction::Deny,
+            },
+            allowlist: FilterList {
+                filter: vec!["0.0.0.0/0".parse().unwrap()],
+                action: FilterAction::Ignore,
+            },
+            rate_limiting_cutoff: Duration::from_secs(1),
+            nts_rate_limiting_cutoff: None,
+            rate_limiting_cache_size: 0,
+            require_nts: None,
+            accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: Some(ReferenceIdFilterList {
+                filter: vec![
+                    ReferenceId::from_bytes(*b"LOCL"),
+                    ReferenceId::from_ip("192.0.2.1".parse().unwrap()),
+                ],
+                action: FilterAction::Deny,
+            }),
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
+        };
+        let clock = TestClock {
+            cur: NtpTimestamp::from_fixed_int(200),
+        };
+        let server_info = Arc::new(RwLock::new(NtpServerInfo::default()));
+        let mut server = Server::new_internal(
+            config.clone(),
+            clock.clone(),
+            server_info.clone(),
+            KeySetProvider::new(1).get(),
+        );
+        let client_ip = "127.0.0.1".parse().unwrap();
+
+        let mut check = |reference_id: ReferenceId, expected: ServerResponse| {
+            server_info.write().unwrap().ntp_snapshot.reference_id = reference_id;
+            assert_eq!(
+                server.intended_action(client_ip),
+                (expected, ServerReason::Policy)
+            );
+        };
+
+        check(ReferenceId::from_bytes(*b"LOCL"), ServerResponse::Deny);
+        check(
+            ReferenceId::from_ip("192.0.2.1".parse().unwrap()),
+            ServerResponse::Deny,
+        );
+        check(
+            ReferenceId::from_ip("192.0.2.2".parse().unwrap()),
+            ServerResponse::ProvideTime,
+        );
+        check(
+            ReferenceId::from_bytes(*b"GPS\0"),
+            ServerResponse::ProvideTime,
+        );
+        check(ReferenceId::NONE, ServerResponse::ProvideTime);
+
+        // The full response is a DENY kiss code
+        server_info.write().unwrap().ntp_snapshot.reference_id = ReferenceId::from_bytes(*b"LOCL");
+        let (packet, id) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+        let serialized = serialize_packet_unencrypted(&packet);
+        let mut stats = TestStatHandler::default();
+        let mut buf = [0; 48];
+        let response = server.handle(
+            client_ip,
+            NtpTimestamp::from_fixed_int(100),
+            &serialized,
+            &mut buf,
+            &mut stats,
+        );
+        assert_eq!(
+            stats.last_register.take(),
+            Some((4, false, ServerReason::Policy, ServerResponse::Deny))
+        );
+        let data = match response {
+            ServerAction::Ignore => panic!("Server ignored packet"),
+            ServerAction::Respond { message } => message,
+        };
+        let packet = NtpPacket::deserialize(data, &NoCipher).unwrap().0;
+        assert!(packet.valid_server_response(id, false));
+        assert!(packet.is_kiss_deny());
+
+        // With the ignore action, the server stops responding altogether
+        let config = ServerConfig {
+            reference_id_denylist: Some(ReferenceIdFilterList {
+                filter: vec![ReferenceId::from_bytes(*b"LOCL")],
+                action: FilterAction::Ignore,
+            }),
+            ..config
+        };
+        let mut server = Server::new_internal(
+            config,
+            clock,
+            server_info.clone(),
+            KeySetProvider::new(1).get(),
+        );
+        let mut buf = [0; 48];
+        let response = server.handle(
+            client_ip,
+            NtpTimestamp::from_fixed_int(100),
+            &serialized,
+            &mut buf,
+            &mut stats,
+        );
+        assert_eq!(
+            stats.last_register.take(),
+            Some((4, false, ServerReason::Policy, ServerResponse::Ignore))
+        );
+        assert!(matches!(response, ServerAction::Ignore));
+
+        server_info.write().unwrap().ntp_snapshot.reference_id = ReferenceId::NONE;
+        assert_eq!(
+            server.intended_action(client_ip),
+            (ServerResponse::ProvideTime, ServerReason::Policy)
+        );
+    }
+
     #[test]
     fn test_server_rate_limit() {
         let config = ServerConfig {
@@ -796,9 +1317,20 @@ mod tests {
                 action: FilterAction::Ignore,
             },
             rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 32,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -893,9 +1425,20 @@ mod tests {
                 action: FilterAction::Ignore,
             },
             rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
 
         let clock = TestClock {
@@ -961,6 +1504,68 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_server_rate_limit_subnets() {
+        let config = ServerConfig {
+            denylist: FilterList {
+                filter: vec![],
+                action: FilterAction::Deny,
+            },
+            allowlist: FilterList {
+                filter: vec!["0.0.0.0/0".parse().unwrap(), "::/0".parse().unwrap()],
+                action: FilterAction::Ignore,
+            },
+            rate_limiting_cutoff: Duration::from_secs(10),
+            nts_rate_limiting_cutoff: None,
+            rate_limiting_cache_size: 32,
+            require_nts: None,
+            accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 24,
+            rate_limiting_prefix_v6: 64,
+        };
+        let clock = TestClock {
+            cur: NtpTimestamp::from_fixed_int(200),
+        };
+        let mut stats = TestStatHandler::default();
+
+        let mut server =
+            Server::new_internal(config, clock, Arc::default(), KeySetProvider::new(1).get());
+
+        let (packet, _) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+        let serialized = serialize_packet_unencrypted(&packet);
+
+        let mut request_from = |addr: &str| {
+            let mut buf = [0; 48];
+            server.handle(
+                addr.parse().unwrap(),
+                NtpTimestamp::from_fixed_int(100),
+                &serialized,
+                &mut buf,
+                &mut stats,
+            );
+            stats.last_register.take().unwrap().2
+        };
+
+        // Clients in the same subnet share their rate limit
+        assert_eq!(request_from("10.0.0.1"), ServerReason::Policy);
+        assert_eq!(request_from("10.0.0.2"), ServerReason::RateLimit);
+
+        // but don't throttle clients in other subnets
+        assert_eq!(request_from("10.0.1.1"), ServerReason::Policy);
+
+        assert_eq!(request_from("2001:db8::1"), ServerReason::Policy);
+        assert_eq!(request_from("2001:db8::2"), ServerReason::RateLimit);
+        assert_eq!(request_from("2001:db8:0:1::1"), ServerReason::Policy);
+    }
+
     #[test]
     fn test_server_ignores_non_request() {
         let config = ServerConfig {
@@ -973,9 +1578,20 @@ mod tests {
                 action: FilterAction::Ignore,
             },
             rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -1024,9 +1640,20 @@ mod tests {
                 action: FilterAction::Ignore,
             },
             rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -1072,6 +1699,23 @@ mod tests {
             stats.last_register.take(),
             Some((5, false, ServerReason::ParseError, ServerResponse::Ignore))
         );
+        assert!(stats.last_parse_error.take().is_some());
+        assert!(matches!(response, ServerAction::Ignore));
+
+        // Parse errors are registered with their category
+        let serialized = serialize_packet_unencrypted(&packet);
+        let response = server.handle(
+            "127.0.0.1".parse().unwrap(),
+            NtpTimestamp::from_fixed_int(100),
+            &serialized[..47],
+            &mut buf,
+            &mut stats,
+        );
+        assert_eq!(
+            stats.last_register.take(),
+            Some((4, false, ServerReason::ParseError, ServerResponse::Ignore))
+        );
+        assert_eq!(stats.last_parse_error.take(), Some("too_short"));
         assert!(matches!(response, ServerAction::Ignore));
 
         let config = ServerConfig {
@@ -1084,9 +1728,20 @@ mod tests {
                 action: FilterAction::Deny,
             },
             rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -1118,9 +1773,20 @@ mod tests {
                 action: FilterAction::Ignore,
             },
             rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -1152,9 +1818,20 @@ mod tests {
                 action: FilterAction::Ignore,
             },
             rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -1186,9 +1863,20 @@ mod tests {
                 action: FilterAction::Ignore,
             },
             rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -1223,9 +1911,20 @@ mod tests {
                 action: FilterAction::Ignore,
             },
             rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: Some(FilterAction::Ignore),
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -1303,7 +2002,7 @@ mod tests {
     }
 
     #[test]
-    fn test_server_require_nts() {
+    fn test_server_nts_rate_limit() {
         let mut config = ServerConfig {
             denylist: FilterList {
                 filter: vec![],
@@ -1313,33 +2012,145 @@ mod tests {
                 filter: vec!["0.0.0.0/0".parse().unwrap()],
                 action: FilterAction::Ignore,
             },
-            rate_limiting_cutoff: Duration::from_secs(1),
-            rate_limiting_cache_size: 0,
-            require_nts: Some(FilterAction::Ignore),
+            rate_limiting_cutoff: Duration::from_secs(10),
+            nts_rate_limiting_cutoff: None,
+            rate_limiting_cache_size: 32,
+            require_nts: None,
             accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
         };
         let mut stats = TestStatHandler::default();
+        let keyset = KeySetProvider::new(1).get();
+
+        let decodedcookie = DecodedServerCookie {
+            algorithm: AeadAlgorithm::AeadAesSivCmac256,
+            s2c: Box::new(AesSivCmac256::new([0; 32].into())),
+            c2s: Box::new(AesSivCmac256::new([0; 32].into())),
+        };
+        let cookie = keyset.encode_cookie(&decodedcookie);
+        let (packet, _) =
+            NtpPacket::nts_poll_message(&cookie, 0, PollIntervalLimits::default().min);
+        let nts_request = serialize_packet_encrypted(&packet, decodedcookie.c2s.as_ref());
+        let (packet, _) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+        let plain_request = serialize_packet_unencrypted(&packet);
+
+        let mut handle = |server: &mut Server<TestClock>, request: &[u8]| {
+            let mut buf = [0; 1024];
+            server.handle(
+                "127.0.0.1".parse().unwrap(),
+                NtpTimestamp::from_fixed_int(100),
+                request,
+                &mut buf,
+                &mut stats,
+            );
+            stats.last_register.take()
+        };
 
+        // Without an NTS cutoff, NTS clients are never limited, and don't
+        // count against the limit of unauthenticated requests
         let mut server = Server::new_internal(
             config.clone(),
-            clock,
+            clock.clone(),
             Arc::default(),
-            KeySetProvider::new(1).get(),
+            keyset.clone(),
         );
-
-        let (packet, _) = NtpPacket::poll_message(PollIntervalLimits::default().min);
-        let serialized = serialize_packet_unencrypted(&packet);
-
-        let mut buf = [0; 1024];
-        let response = server.handle(
-            "127.0.0.1".parse().unwrap(),
-            NtpTimestamp::from_fixed_int(100),
-            &serialized,
-            &mut buf,
-            &mut stats,
+        for _ in 0..3 {
+            assert_eq!(
+                handle(&mut server, &nts_request),
+                Some((4, true, ServerReason::Policy, ServerResponse::ProvideTime))
+            );
+        }
+        assert_eq!(
+            handle(&mut server, &plain_request),
+            Some((4, false, ServerReason::Policy, ServerResponse::ProvideTime))
+        );
+        assert_eq!(
+            handle(&mut server, &plain_request),
+            Some((4, false, ServerReason::RateLimit, ServerResponse::Ignore))
+        );
+        assert_eq!(
+            handle(&mut server, &nts_request),
+            Some((4, true, ServerReason::Policy, ServerResponse::ProvideTime))
+        );
+
+        // With an NTS cutoff, NTS clients are limited separately
+        config.nts_rate_limiting_cutoff = Some(Duration::from_secs(10));
+        let mut server = Server::new_internal(config, clock, Arc::default(), keyset);
+        assert_eq!(
+            handle(&mut server, &nts_request),
+            Some((4, true, ServerReason::Policy, ServerResponse::ProvideTime))
+        );
+        assert_eq!(
+            handle(&mut server, &nts_request),
+            Some((4, true, ServerReason::RateLimit, ServerResponse::Ignore))
+        );
+        assert_eq!(
+            handle(&mut server, &plain_request),
+            Some((4, false, ServerReason::Policy, ServerResponse::ProvideTime))
+        );
+    }
+
+    #[test]
+    fn test_server_require_nts() {
+        let mut config = ServerConfig {
+            denylist: FilterList {
+                filter: vec![],
+                action: FilterAction::Deny,
+            },
+            allowlist: FilterList {
+                filter: vec!["0.0.0.0/0".parse().unwrap()],
+                action: FilterAction::Ignore,
+            },
+            rate_limiting_cutoff: Duration::from_secs(1),
+            nts_rate_limiting_cutoff: None,
+            rate_limiting_cache_size: 0,
+            require_nts: Some(FilterAction::Ignore),
+            accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
+        };
+        let clock = TestClock {
+            cur: NtpTimestamp::from_fixed_int(200),
+        };
+        let mut stats = TestStatHandler::default();
+
+        let mut server = Server::new_internal(
+            config.clone(),
+            clock,
+            Arc::default(),
+            KeySetProvider::new(1).get(),
+        );
+
+        let (packet, _) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+        let serialized = serialize_packet_unencrypted(&packet);
+
+        let mut buf = [0; 1024];
+        let response = server.handle(
+            "127.0.0.1".parse().unwrap(),
+            NtpTimestamp::from_fixed_int(100),
+            &serialized,
+            &mut buf,
+            &mut stats,
         );
         assert_eq!(
             stats.last_register.take(),
@@ -1405,6 +2216,608 @@ mod tests {
         assert!(packet.is_kiss_deny());
     }
 
+    #[test]
+    fn test_server_echoes_configured_extension_fields() {
+        let config = ServerConfig {
+            denylist: FilterList {
+                filter: vec![],
+                action: FilterAction::Deny,
+            },
+            allowlist: FilterList {
+                filter: vec!["0.0.0.0/0".parse().unwrap()],
+                action: FilterAction::Deny,
+            },
+            rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
+            rate_limiting_cache_size: 0,
+            require_nts: None,
+            accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![0xF001],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
+        };
+        let clock = TestClock {
+            cur: NtpTimestamp::from_fixed_int(200),
+        };
+        let mut stats = TestStatHandler::default();
+
+        let mut server =
+            Server::new_internal(config, clock, Arc::default(), KeySetProvider::new(1).get());
+
+        let (mut packet, id) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+        packet.push_additional(crate::packet::ExtensionField::Unknown {
+            type_id: 0xF001,
+            data: std::borrow::Cow::Borrowed(&[1, 2, 3, 4]),
+        });
+        packet.push_additional(crate::packet::ExtensionField::Unknown {
+            type_id: 0xF002,
+            data: std::borrow::Cow::Borrowed(&[5, 6, 7, 8]),
+        });
+        let serialized = serialize_packet_unencrypted(&packet);
+
+        let mut buf = [0; 1024];
+        let response = server.handle(
+            "127.0.0.1".parse().unwrap(),
+            NtpTimestamp::from_fixed_int(100),
+            &serialized,
+            &mut buf,
+            &mut stats,
+        );
+        let data = match response {
+            ServerAction::Ignore => panic!("Server ignored packet"),
+            ServerAction::Respond { message } => message,
+        };
+        let packet = NtpPacket::deserialize(data, &NoCipher).unwrap().0;
+        assert!(packet.valid_server_response(id, false));
+
+        let echoed: Vec<_> = packet
+            .untrusted_extension_fields()
+            .filter_map(|ef| match ef {
+                crate::packet::ExtensionField::Unknown { type_id, data } => {
+                    Some((*type_id, &**data))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(echoed, vec![(0xF001, &[1u8, 2, 3, 4][..])]);
+    }
+
+    #[test]
+    fn test_server_status_extension_field() {
+        let ef = TEST_STATUS.to_extension_field();
+        let ExtensionField::Unknown { type_id, data } = &ef else {
+            panic!("Unexpected extension field");
+        };
+        assert_eq!(*type_id, SERVER_STATUS_RESPONSE_TYPE);
+        assert_eq!(data.len(), 40);
+        assert_eq!(&data[..8], &10u64.to_be_bytes());
+        assert_eq!(&data[8..16], &7u64.to_be_bytes());
+        assert_eq!(ServerStatus::from_extension_field(&ef), Some(TEST_STATUS));
+
+        // padding after the counters is ignored
+        let mut padded = data.to_vec();
+        padded.extend_from_slice(&[0; 4]);
+        let padded = ExtensionField::Unknown {
+            type_id: SERVER_STATUS_RESPONSE_TYPE,
+            data: Cow::Owned(padded),
+        };
+        assert_eq!(
+            ServerStatus::from_extension_field(&padded),
+            Some(TEST_STATUS)
+        );
+
+        // but truncated data and other types are not a status
+        let truncated = ExtensionField::Unknown {
+            type_id: SERVER_STATUS_RESPONSE_TYPE,
+            data: Cow::Borrowed(&data[..32]),
+        };
+        assert_eq!(ServerStatus::from_extension_field(&truncated), None);
+        assert_eq!(
+            ServerStatus::from_extension_field(&ServerStatus::request()),
+            None
+        );
+
+        assert!(ServerStatus::is_request(&ServerStatus::request()));
+        assert!(!ServerStatus::is_request(&ef));
+        let short_request = ExtensionField::Unknown {
+            type_id: SERVER_STATUS_REQUEST_TYPE,
+            data: Cow::Borrowed(&[]),
+        };
+        assert!(!ServerStatus::is_request(&short_request));
+    }
+
+    fn status_query_server() -> (Server<TestClock>, Arc<KeySet>) {
+        let config = ServerConfig {
+            denylist: FilterList {
+                filter: vec![],
+                action: FilterAction::Deny,
+            },
+            allowlist: FilterList {
+                filter: vec!["0.0.0.0/0".parse().unwrap()],
+                action: FilterAction::Deny,
+            },
+            rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
+            rate_limiting_cache_size: 0,
+            require_nts: None,
+            accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: true,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
+        };
+        let clock = TestClock {
+            cur: NtpTimestamp::from_fixed_int(200),
+        };
+        let keyset = KeySetProvider::new(1).get();
+        let server = Server::new_internal(config, clock, Arc::default(), keyset.clone());
+        (server, keyset)
+    }
+
+    fn response_status(packet: &NtpPacket) -> Option<ServerStatus> {
+        packet
+            .untrusted_extension_fields()
+            .chain(packet.authenticated_extension_fields())
+            .chain(packet.encrypted_extension_fields())
+            .find_map(ServerStatus::from_extension_field)
+    }
+
+    #[test]
+    fn test_server_status_query() {
+        let (mut server, keyset) = status_query_server();
+        let mut stats = TestStatHandler::default();
+
+        let decodedcookie = DecodedServerCookie {
+            algorithm: AeadAlgorithm::AeadAesSivCmac256,
+            s2c: Box::new(AesSivCmac256::new([0; 32].into())),
+            c2s: Box::new(AesSivCmac256::new([0; 32].into())),
+        };
+        let cookie = keyset.encode_cookie(&decodedcookie);
+        let (mut packet, id) =
+            NtpPacket::nts_poll_message(&cookie, 0, PollIntervalLimits::default().min);
+        packet.push_encrypted(ServerStatus::request());
+        let serialized = serialize_packet_encrypted(&packet, decodedcookie.c2s.as_ref());
+
+        let mut buf = [0; 1024];
+        let response = server.handle(
+            "127.0.0.1".parse().unwrap(),
+            NtpTimestamp::from_fixed_int(100),
+            &serialized,
+            &mut buf,
+            &mut stats,
+        );
+        assert_eq!(
+            stats.last_register.take(),
+            Some((4, true, ServerReason::Policy, ServerResponse::ProvideTime))
+        );
+        let data = match response {
+            ServerAction::Ignore => panic!("Server ignored packet"),
+            ServerAction::Respond { message } => message,
+        };
+        // Answering the query does not amplify the traffic
+        assert!(data.len() <= serialized.len());
+
+        let packet = NtpPacket::deserialize(data, decodedcookie.s2c.as_ref())
+            .unwrap()
+            .0;
+        assert!(packet.valid_server_response(id, true));
+        let status = packet
+            .encrypted_extension_fields()
+            .find_map(ServerStatus::from_extension_field);
+        assert_eq!(status, Some(TEST_STATUS));
+    }
+
+    #[test]
+    fn test_server_status_query_refused_without_nts() {
+        let (mut server, _) = status_query_server();
+        let mut stats = TestStatHandler::default();
+
+        let (mut packet, id) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+        packet.push_additional(ServerStatus::request());
+        let serialized = serialize_packet_unencrypted(&packet);
+
+        let mut buf = [0; 1024];
+        let response = server.handle(
+            "127.0.0.1".parse().unwrap(),
+            NtpTimestamp::from_fixed_int(100),
+            &serialized,
+            &mut buf,
+            &mut stats,
+        );
+        // Time is still provided, just without the status
+        assert_eq!(
+            stats.last_register.take(),
+            Some((4, false, ServerReason::Policy, ServerResponse::ProvideTime))
+        );
+        let data = match response {
+            ServerAction::Ignore => panic!("Server ignored packet"),
+            ServerAction::Respond { message } => message,
+        };
+        let packet = NtpPacket::deserialize(data, &NoCipher).unwrap().0;
+        assert!(packet.valid_server_response(id, false));
+        assert_eq!(response_status(&packet), None);
+    }
+
+    #[test]
+    fn test_server_status_query_disabled() {
+        let (mut server, keyset) = status_query_server();
+        server.config.allow_status_query = false;
+        let mut stats = TestStatHandler::default();
+
+        let decodedcookie = DecodedServerCookie {
+            algorithm: AeadAlgorithm::AeadAesSivCmac256,
+            s2c: Box::new(AesSivCmac256::new([0; 32].into())),
+            c2s: Box::new(AesSivCmac256::new([0; 32].into())),
+        };
+        let cookie = keyset.encode_cookie(&decodedcookie);
+        let (mut packet, _) =
+            NtpPacket::nts_poll_message(&cookie, 0, PollIntervalLimits::default().min);
+        packet.push_encrypted(ServerStatus::request());
+        let serialized = serialize_packet_encrypted(&packet, decodedcookie.c2s.as_ref());
+
+        let mut buf = [0; 1024];
+        let response = server.handle(
+            "127.0.0.1".parse().unwrap(),
+            NtpTimestamp::from_fixed_int(100),
+            &serialized,
+            &mut buf,
+            &mut stats,
+        );
+        let data = match response {
+            ServerAction::Ignore => panic!("Server ignored packet"),
+            ServerAction::Respond { message } => message,
+        };
+        let packet = NtpPacket::deserialize(data, decodedcookie.s2c.as_ref())
+            .unwrap()
+            .0;
+        assert_eq!(response_status(&packet), None);
+    }
+
+    fn reference_timestamp_server() -> Server<TestClock> {
+        let config = ServerConfig {
+            denylist: FilterList {
+                filter: vec![],
+                action: FilterAction::Deny,
+            },
+            allowlist: FilterList {
+                filter: vec!["127.0.0.0/24".parse().unwrap()],
+                action: FilterAction::Deny,
+            },
+            rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
+            rate_limiting_cache_size: 0,
+            require_nts: None,
+            accepted_versions: vec![NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 16,
+            max_reference_age: Some(NtpDuration::from_seconds(100.0)),
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
+        };
+        let clock = TestClock {
+            cur: NtpTimestamp::from_fixed_int(200),
+        };
+
+        Server::new_internal(config, clock, Arc::default(), KeySetProvider::new(1).get())
+    }
+
+    #[test]
+    fn test_server_reference_timestamp_interval() {
+        let mut server = reference_timestamp_server();
+        let mut stats = TestStatHandler::default();
+
+        for seconds in [0, 15, 16, 1000, 1023] {
+            let recv_timestamp = NtpTimestamp::from_seconds_nanos_since_ntp_era(seconds, 12345);
+            let (packet, id) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+            let serialized = serialize_packet_unencrypted(&packet);
+
+            let mut buf = [0; 1024];
+            let response = server.handle(
+                "127.0.0.1".parse().unwrap(),
+                recv_timestamp,
+                &serialized,
+                &mut buf,
+                &mut stats,
+            );
+            let data = match response {
+                ServerAction::Ignore => panic!("Server ignored packet"),
+                ServerAction::Respond { message } => message,
+            };
+            let packet = NtpPacket::deserialize(data, &NoCipher).unwrap().0;
+            assert!(packet.valid_server_response(id, false));
+
+            let reference_timestamp = packet.reference_timestamp().unwrap();
+            assert_eq!(
+                reference_timestamp,
+                NtpTimestamp::from_seconds_nanos_since_ntp_era(seconds - seconds % 16, 0)
+            );
+            assert!(!recv_timestamp.is_before(reference_timestamp));
+            assert!(recv_timestamp - reference_timestamp < NtpDuration::from_seconds(16.0));
+        }
+    }
+
+    #[test]
+    fn test_server_serve_when_unsynchronized() {
+        let server_info = Arc::new(RwLock::new(NtpServerInfo::default()));
+        let mut server = reference_timestamp_server();
+        server.server_info = server_info.clone();
+        server.config.serve_when_unsynchronized = false;
+        let mut stats = TestStatHandler::default();
+
+        let mut request = || {
+            let (packet, id) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+            let serialized = serialize_packet_unencrypted(&packet);
+
+            let mut buf = [0; 1024];
+            let response = server.handle(
+                "127.0.0.1".parse().unwrap(),
+                NtpTimestamp::from_fixed_int(100),
+                &serialized,
+                &mut buf,
+                &mut stats,
+            );
+            let (_, _, reason, action) = stats.last_register.take().unwrap();
+            assert_eq!(reason, ServerReason::Policy);
+            match response {
+                ServerAction::Ignore => {
+                    assert_eq!(action, ServerResponse::Ignore);
+                    None
+                }
+                ServerAction::Respond { message } => {
+                    assert_eq!(action, ServerResponse::ProvideTime);
+                    let packet = NtpPacket::deserialize(message, &NoCipher).unwrap().0;
+                    assert!(packet.valid_server_response(id, false));
+                    Some(packet.stratum())
+                }
+            }
+        };
+
+        // Requests are dropped silently while we are not synchronized
+        assert_eq!(request(), None);
+
+        {
+            let mut server_info = server_info.write().unwrap();
+            server_info.time_snapshot.leap_indicator = NtpLeapIndicator::NoWarning;
+            server_info.ntp_snapshot.stratum = 2;
+        }
+        assert_eq!(request(), Some(2));
+
+        server_info.write().unwrap().time_snapshot.leap_indicator =
+            NtpLeapIndicator::Unsynchronized;
+        assert_eq!(request(), None);
+    }
+
+    #[test]
+    fn test_server_draining() {
+        let server_info = Arc::new(RwLock::new(NtpServerInfo::default()));
+        {
+            let mut server_info = server_info.write().unwrap();
+            server_info.time_snapshot.leap_indicator = NtpLeapIndicator::NoWarning;
+            server_info.ntp_snapshot.stratum = 2;
+        }
+        let mut server = reference_timestamp_server();
+        server.server_info = server_info.clone();
+        server.config.serve_when_unsynchronized = false;
+        let mut stats = TestStatHandler::default();
+
+        let mut request = || {
+            let (packet, id) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+            let serialized = serialize_packet_unencrypted(&packet);
+
+            let mut buf = [0; 1024];
+            let response = server.handle(
+                "127.0.0.1".parse().unwrap(),
+                NtpTimestamp::from_fixed_int(100),
+                &serialized,
+                &mut buf,
+                &mut stats,
+            );
+            let ServerAction::Respond { message } = response else {
+                panic!("Server ignored packet");
+            };
+            let packet = NtpPacket::deserialize(message, &NoCipher).unwrap().0;
+            assert!(packet.valid_server_response(id, false));
+            (packet.stratum(), packet.leap())
+        };
+
+        assert_eq!(request(), (2, NtpLeapIndicator::NoWarning));
+
+        // While draining, requests are still answered, but as unsynchronized
+        server_info.write().unwrap().draining = true;
+        assert_eq!(request(), (16, NtpLeapIndicator::Unknown));
+
+        server_info.write().unwrap().draining = false;
+        assert_eq!(request(), (2, NtpLeapIndicator::NoWarning));
+    }
+
+    #[test]
+    fn test_server_pad_responses() {
+        let mut server = reference_timestamp_server();
+        server.config.pad_responses = true;
+        server.config.accepted_versions = vec![NtpVersion::V4, NtpVersion::V5];
+        server.config.echo_extension_fields = vec![0xF001];
+        let mut stats = TestStatHandler::default();
+
+        let (v4, _) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+        let (mut v4_extensions, _) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+        v4_extensions.push_additional(crate::packet::ExtensionField::Unknown {
+            type_id: 0xF001,
+            data: std::borrow::Cow::Borrowed(&[1, 2, 3, 4]),
+        });
+        v4_extensions.push_additional(crate::packet::ExtensionField::Unknown {
+            type_id: 0xF002,
+            data: std::borrow::Cow::Borrowed(&[5, 6, 7, 8]),
+        });
+        let (v5, _) = NtpPacket::poll_message_v5(PollIntervalLimits::default().min);
+
+        // Both time responses (allowed) and deny responses (other clients)
+        for client in ["127.0.0.1", "128.0.0.1"] {
+            for packet in [&v4, &v4_extensions, &v5] {
+                let serialized = serialize_packet_unencrypted(packet);
+
+                let mut buf = [0; 1024];
+                let response = server.handle(
+                    client.parse().unwrap(),
+                    NtpTimestamp::from_fixed_int(100),
+                    &serialized,
+                    &mut buf,
+                    &mut stats,
+                );
+                let data = match response {
+                    ServerAction::Ignore => panic!("Server ignored packet"),
+                    ServerAction::Respond { message } => message,
+                };
+                assert!(data.len() <= serialized.len());
+                if matches!(packet.version(), NtpVersion::V5) {
+                    // NTPv5 responses are padded to the size of the request
+                    assert_eq!(data.len(), serialized.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_server_symmetric_active() {
+        let mut server = reference_timestamp_server();
+        let mut stats = TestStatHandler::default();
+
+        let (mut packet, _) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+        packet.set_mode(crate::NtpAssociationMode::SymmetricActive);
+        let mut serialized = serialize_packet_unencrypted(&packet);
+
+        // Dropped unless symmetric peers are allowed
+        let mut buf = [0; 1024];
+        let response = server.handle(
+            "127.0.0.1".parse().unwrap(),
+            NtpTimestamp::from_fixed_int(100),
+            &serialized,
+            &mut buf,
+            &mut stats,
+        );
+        assert!(matches!(response, ServerAction::Ignore));
+        assert_eq!(
+            stats.last_register.take(),
+            Some((4, false, ServerReason::ParseError, ServerResponse::Ignore))
+        );
+
+        server.config.allow_symmetric_peers = true;
+        let mut buf = [0; 1024];
+        let response = server.handle(
+            "127.0.0.1".parse().unwrap(),
+            NtpTimestamp::from_fixed_int(100),
+            &serialized,
+            &mut buf,
+            &mut stats,
+        );
+        let ServerAction::Respond { message } = response else {
+            panic!("Server ignored packet");
+        };
+        assert_eq!(
+            stats.last_register.take(),
+            Some((4, false, ServerReason::Policy, ServerResponse::ProvideTime))
+        );
+
+        let response = NtpPacket::deserialize(message, &NoCipher).unwrap().0;
+        assert_eq!(response.mode(), crate::NtpAssociationMode::SymmetricPassive);
+        assert_eq!(response.origin_timestamp(), packet.transmit_timestamp());
+        assert_eq!(
+            response.receive_timestamp(),
+            NtpTimestamp::from_fixed_int(100)
+        );
+
+        // Legacy NTPv3 peers are answered in their own version
+        server.config.accepted_versions = vec![NtpVersion::V3, NtpVersion::V4];
+        serialized[0] = (serialized[0] & 0b1100_0111) | (3 << 3);
+        let mut buf = [0; 1024];
+        let response = server.handle(
+            "127.0.0.1".parse().unwrap(),
+            NtpTimestamp::from_fixed_int(100),
+            &serialized,
+            &mut buf,
+            &mut stats,
+        );
+        let ServerAction::Respond { message } = response else {
+            panic!("Server ignored packet");
+        };
+        assert_eq!(
+            stats.last_register.take(),
+            Some((3, false, ServerReason::Policy, ServerResponse::ProvideTime))
+        );
+
+        let response = NtpPacket::deserialize(message, &NoCipher).unwrap().0;
+        assert_eq!(response.version(), NtpVersion::V3);
+        assert_eq!(message[0] & 0b0011_1000, 3 << 3);
+        assert_eq!(response.mode(), crate::NtpAssociationMode::SymmetricPassive);
+        assert_eq!(response.origin_timestamp(), packet.transmit_timestamp());
+
+        // Clients still get regular server responses
+        let (packet, id) = NtpPacket::poll_message(PollIntervalLimits::default().min);
+        let serialized = serialize_packet_unencrypted(&packet);
+        let mut buf = [0; 1024];
+        let response = server.handle(
+            "127.0.0.1".parse().unwrap(),
+            NtpTimestamp::from_fixed_int(100),
+            &serialized,
+            &mut buf,
+            &mut stats,
+        );
+        let ServerAction::Respond { message } = response else {
+            panic!("Server ignored packet");
+        };
+        stats.last_register.take();
+        let response = NtpPacket::deserialize(message, &NoCipher).unwrap().0;
+        assert_eq!(response.mode(), crate::NtpAssociationMode::Server);
+        assert!(response.valid_server_response(id, false));
+    }
+
+    #[test]
+    fn test_server_reference_age() {
+        let mut server = reference_timestamp_server();
+        let mut server_info = NtpServerInfo::default();
+        let now = NtpTimestamp::from_seconds_nanos_since_ntp_era(1000, 0);
+
+        // Never updated by the synchronization loop
+        assert!(!server.check_reference_age(&server_info, now));
+
+        server_info.time_snapshot.root_variance_base_time =
+            NtpTimestamp::from_seconds_nanos_since_ntp_era(950, 0);
+        assert!(!server.check_reference_age(&server_info, now));
+
+        server_info.time_snapshot.root_variance_base_time =
+            NtpTimestamp::from_seconds_nanos_since_ntp_era(850, 0);
+        assert!(server.check_reference_age(&server_info, now));
+        assert!(server.reference_stale);
+
+        // Recovers once the loop updates the snapshot again
+        server_info.time_snapshot.root_variance_base_time = now;
+        assert!(!server.check_reference_age(&server_info, now));
+        assert!(!server.reference_stale);
+
+        // Without a maximum age, the snapshot is never considered stale
+        server.config.max_reference_age = None;
+        server_info.time_snapshot.root_variance_base_time =
+            NtpTimestamp::from_seconds_nanos_since_ntp_era(1, 0);
+        assert!(!server.check_reference_age(&server_info, now));
+    }
+
     #[test]
     fn test_server_v5() {
         let config = ServerConfig {
@@ -1417,9 +2830,20 @@ mod tests {
                 action: FilterAction::Deny,
             },
             rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V5],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -1493,9 +2917,20 @@ mod tests {
                 action: FilterAction::Ignore,
             },
             rate_limiting_cutoff: Duration::from_millis(1000),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V3, NtpVersion::V4],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
         let clock = TestClock {
             cur: NtpTimestamp::from_fixed_int(200),
@@ -1533,9 +2968,20 @@ mod tests {
                 action: FilterAction::Ignore,
             },
             rate_limiting_cutoff: Duration::from_millis(100),
+            nts_rate_limiting_cutoff: None,
             rate_limiting_cache_size: 0,
             require_nts: None,
             accepted_versions: vec![NtpVersion::V5],
+            reference_id_denylist: None,
+            echo_extension_fields: vec![],
+            allow_status_query: false,
+            reference_timestamp_interval: 128,
+            max_reference_age: None,
+            serve_when_unsynchronized: true,
+            pad_responses: false,
+            allow_symmetric_peers: false,
+            rate_limiting_prefix_v4: 32,
+            rate_limiting_prefix_v6: 128,
         };
 
         let clock = TestClock {
@@ -1683,4 +3129,28 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_subnet_containing() {
+        assert_eq!(
+            IpSubnet::containing("192.168.17.42".parse().unwrap(), 24),
+            "192.168.17.0/24".parse().unwrap()
+        );
+        assert_eq!(
+            IpSubnet::containing("192.168.17.42".parse().unwrap(), 0),
+            "0.0.0.0/0".parse().unwrap()
+        );
+        assert_eq!(
+            IpSubnet::containing("192.168.17.42".parse().unwrap(), 40),
+            "192.168.17.42/32".parse().unwrap()
+        );
+        assert_eq!(
+            IpSubnet::containing("2001:db8:1:2:3:4:5:6".parse().unwrap(), 64),
+            "2001:db8:1:2::/64".parse().unwrap()
+        );
+        assert_eq!(
+            IpSubnet::containing("2001:db8:1:2:3:4:5:6".parse().unwrap(), 128),
+            "2001:db8:1:2:3:4:5:6/128".parse().unwrap()
+        );
+    }
 }
@@ -6,21 +6,21 @@ use crate::{
         v5::server_reference_id::{BloomFilter, RemoteBloomFilter},
     },
     system::NtpSourceInfo,
-    v5::ServerId,
+    v5::{NtpTimescale, ServerId},
 };
 use crate::{
     algorithm::{ObservableSourceTimedata, SourceController},
-    config::SourceConfig,
+    config::{MAX_REACH_WINDOW, RefidMismatchAction, SourceConfig},
     cookiestash::CookieStash,
     identifiers::ReferenceId,
     packet::{Cipher, NtpAssociationMode, NtpPacket, RequestIdentifier},
-    time_types::{NtpTimestamp, PollInterval},
+    time_types::{NtpDuration, NtpTimestamp, PollInterval},
 };
 use rand::{Rng, thread_rng};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    fmt::Debug,
+    collections::{HashMap, VecDeque},
+    fmt::{Debug, Write},
     io::Cursor,
     net::{IpAddr, SocketAddr},
     sync::{Arc, Mutex, RwLock},
@@ -30,8 +30,16 @@ use tracing::{debug, trace, warn};
 
 const MAX_STRATUM: u8 = 16;
 const POLL_WINDOW: std::time::Duration = std::time::Duration::from_secs(5);
+/// Largest difference between the round trip seen by the clock and the one
+/// seen by the monotonic clock before we conclude the clock was stepped during
+/// the exchange. This must leave room for the time between the kernel
+/// timestamping a packet and the daemon reading the monotonic clock.
+const STEP_DETECTION_THRESHOLD: std::time::Duration = std::time::Duration::from_millis(100);
 const STARTUP_TRIES_THRESHOLD: usize = 3;
 const AFTER_UPGRADE_TRIES_THRESHOLD: u32 = 2;
+/// Number of responses without a RATE kiss code after which the remote minimum
+/// poll interval is lowered again by one step.
+const RATE_RECOVERY_RESPONSES: u32 = 4;
 
 pub struct SourceNtsData {
     pub(crate) cookies: CookieStash,
@@ -70,6 +78,11 @@ pub struct NtpSource<Controller: SourceController> {
     // The poll interval desired by the remove server.
     // Must be increased when the server sends the RATE kiss code.
     remote_min_poll_interval: PollInterval,
+    // No polls are sent before this time after the server sent the RATE kiss code.
+    rate_cooldown_until: Option<tokio::time::Instant>,
+    // Responses received since the remote minimum poll interval was last changed
+    // because of rate limiting, as long as it is raised above our own minimum.
+    rate_recovery_responses: Option<u32>,
 
     // Identifier of the last request sent to the server. This is correlated
     // with any received response from the server to guard against replay
@@ -80,8 +93,36 @@ pub struct NtpSource<Controller: SourceController> {
     // interaction
     have_deny_rstr_response: bool,
 
+    // Whether the source sent a response without valid NTS authentication
+    // while it is required to authenticate all of them.
+    authentication_failed: bool,
+
+    // Whether the source was disabled by the operator, excluding it from
+    // synchronization while it keeps being polled.
+    disabled: bool,
+
+    // Local time at which we last sent a poll, and at which we last
+    // received a valid response.
+    last_poll_sent: NtpTimestamp,
+    last_response_received: NtpTimestamp,
+    // Monotonic time at which the outstanding poll was sent, used to detect
+    // steps of the clock during the exchange.
+    last_poll_sent_instant: Option<tokio::time::Instant>,
+
+    // Timestamps of the last exchange, completed by the transmit timestamp
+    // in an interleaved response. Only kept when interleaved mode is enabled.
+    previous_exchange: Option<PreviousExchange>,
+    // Whether the server answered our last interleaved request in kind.
+    server_interleaved: bool,
+
     stratum: u8,
     reference_id: ReferenceId,
+    timescale: Option<NtpTimescale>,
+
+    // Round trip delay and advertised precision of the last accepted
+    // measurement, kept for observability.
+    last_round_trip_delay: Option<NtpDuration>,
+    last_precision: Option<i8>,
 
     source_addr: SocketAddr,
     source_id: ReferenceId,
@@ -104,6 +145,44 @@ pub struct NtpSource<Controller: SourceController> {
     source_info: Arc<RwLock<NtpSourceInfo>>,
 
     source_snapshots: Arc<Mutex<HashMap<ClockId, NtpSourceSnapshot>>>,
+
+    // Most recent raw packets exchanged with the source, only kept when
+    // packet capture is enabled.
+    captured_packets: VecDeque<CapturedPacket>,
+}
+
+/// Number of raw packets kept per source when packet capture is enabled
+const PACKET_CAPTURE_LENGTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PacketDirection {
+    Request,
+    Response,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedPacket {
+    pub direction: PacketDirection,
+    /// Raw bytes of the packet, hex encoded
+    pub data: String,
+}
+
+impl CapturedPacket {
+    fn new(direction: PacketDirection, data: &[u8]) -> Self {
+        let data = data.iter().fold(String::new(), |mut out, byte| {
+            let _ = write!(out, "{byte:02x}");
+            out
+        });
+        CapturedPacket { direction, data }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PreviousExchange {
+    send_time: NtpTimestamp,
+    server_receive_time: NtpTimestamp,
+    recv_time: NtpTimestamp,
 }
 
 pub struct OneWaySource<Controller: SourceController> {
@@ -126,6 +205,12 @@ impl<Controller: SourceController> OneWaySource<Controller> {
             unanswered_polls: 0,
             poll_interval: crate::time_types::PollInterval::from_byte(0),
             nts_cookies: None,
+            last_poll_sent: NtpTimestamp::default(),
+            last_response_received: NtpTimestamp::default(),
+            timescale: None,
+            captured_packets: vec![],
+            last_round_trip_delay: None,
+            last_precision: None,
             name,
             address,
             id,
@@ -135,13 +220,17 @@ impl<Controller: SourceController> OneWaySource<Controller> {
 
 /// Used to determine whether the server is reachable and the data are fresh
 ///
-/// This value is represented as an 8-bit shift register. The register is shifted left
-/// by one bit when a packet is sent and the rightmost bit is set to zero.
-/// As valid packets arrive, the rightmost bit is set to one.
+/// This value is represented as a shift register of up to 8 bits, the window. The
+/// register is shifted left by one bit when a packet is sent and the rightmost bit
+/// is set to zero. As valid packets arrive, the rightmost bit is set to one.
 /// If the register contains any nonzero bits, the server is considered reachable;
 /// otherwise, it is unreachable.
 #[derive(Clone, Copy, Serialize, Deserialize)]
-pub struct Reach(u8);
+pub struct Reach {
+    register: u8,
+    #[serde(deserialize_with = "crate::config::deserialize_reach_window")]
+    window: u8,
+}
 
 impl std::fmt::Debug for Reach {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -149,8 +238,8 @@ impl std::fmt::Debug for Reach {
             write!(
                 f,
                 "Reach(0b{:07b} ({} polls until unreachable))",
-                self.0,
-                7 - self.0.trailing_zeros()
+                self.register,
+                u32::from(self.window) - 1 - self.register.trailing_zeros()
             )
         } else {
             write!(f, "Reach(unreachable)",)
@@ -160,27 +249,36 @@ impl std::fmt::Debug for Reach {
 
 impl Reach {
     pub fn never() -> Self {
-        Reach(0)
+        Reach::with_window(MAX_REACH_WINDOW)
+    }
+
+    /// A register that considers only the last `window` polls
+    pub fn with_window(window: u8) -> Self {
+        Reach {
+            register: 0,
+            window: window.clamp(1, MAX_REACH_WINDOW),
+        }
     }
 
     pub fn is_reachable(&self) -> bool {
-        self.0 != 0
+        self.register != 0
     }
 
     /// We have just received a packet, so the source is definitely reachable
     pub(crate) fn received_packet(&mut self) {
-        self.0 |= 1;
+        self.register |= 1;
     }
 
     /// A packet received some number of poll intervals ago is decreasingly relevant for
-    /// determining that a source is still reachable. We discount the packets received so far.
-    fn poll(&mut self) {
-        self.0 <<= 1;
+    /// determining that a source is still reachable. We discount the packets received so far,
+    /// forgetting those that fall outside of the window.
+    pub(crate) fn poll(&mut self) {
+        self.register = (self.register << 1) & (u8::MAX >> (MAX_REACH_WINDOW - self.window));
     }
 
-    /// Number of polls since the last message we received
+    /// Number of polls since the last message we received, at most the window
     pub fn unanswered_polls(&self) -> u32 {
-        self.0.trailing_zeros()
+        self.register.trailing_zeros().min(self.window.into())
     }
 }
 
@@ -206,6 +304,8 @@ pub struct NtpSourceSnapshot {
     pub protocol_version: ProtocolVersion,
 
     pub bloom_filter: Option<BloomFilter>,
+
+    pub expected_refid: Option<ReferenceId>,
 }
 
 impl NtpSourceSnapshot {
@@ -246,6 +346,21 @@ impl NtpSourceSnapshot {
             _ => {}
         }
 
+        // Reject sources that claim a different reference than configured,
+        // e.g. a source that advertises GPS without being an actual GPS clock.
+        // Until the first response there is no reference id to check yet.
+        if let Some(expected_refid) = self.expected_refid
+            && self.reach.is_reachable()
+            && self.reference_id != expected_refid
+        {
+            debug!(
+                reference_id = ?self.reference_id,
+                ?expected_refid,
+                "Source rejected because of an unexpected reference id"
+            );
+            return Err(AcceptSynchronizationError::ReferenceId);
+        }
+
         // An unreachable error occurs if the server is unreachable.
         if !self.reach.is_reachable() {
             debug!("Source is unreachable");
@@ -265,6 +380,7 @@ impl NtpSourceSnapshot {
             poll_interval: source.last_poll_interval,
             protocol_version: source.protocol_version,
             bloom_filter: source.bloom_filter.full_filter().copied(),
+            expected_refid: source.source_config.enforced_refid(),
         }
     }
 }
@@ -286,6 +402,7 @@ pub fn source_snapshot() -> NtpSourceSnapshot {
         poll_interval: crate::time_types::PollIntervalLimits::default().min,
         protocol_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
         bloom_filter: None,
+        expected_refid: None,
     }
 }
 
@@ -296,6 +413,7 @@ pub enum AcceptSynchronizationError {
     Loop,
     Distance,
     Stratum,
+    ReferenceId,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -376,6 +494,24 @@ pub struct ObservableSourceState {
     pub unanswered_polls: u32,
     pub poll_interval: PollInterval,
     pub nts_cookies: Option<usize>,
+    #[serde(default)]
+    pub last_poll_sent: NtpTimestamp,
+    #[serde(default)]
+    pub last_response_received: NtpTimestamp,
+    /// Timescale reported by the source, only known for NTPv5 sources
+    #[serde(default)]
+    pub timescale: Option<NtpTimescale>,
+    /// Most recent raw packets exchanged with the source, oldest first. Only
+    /// filled when packet capture is enabled for the source.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub captured_packets: Vec<CapturedPacket>,
+    /// Round trip delay of the last accepted measurement
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_round_trip_delay: Option<NtpDuration>,
+    /// Precision advertised by the source in the last accepted measurement,
+    /// as a log2 of seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_precision: Option<i8>,
     pub name: String,
     pub address: String,
     pub id: ClockId,
@@ -402,17 +538,32 @@ impl<Controller: SourceController> NtpSource<Controller> {
 
                 last_poll_interval: source_config.poll_interval_limits.min,
                 remote_min_poll_interval: source_config.poll_interval_limits.min,
+                rate_cooldown_until: None,
+                rate_recovery_responses: None,
 
                 have_deny_rstr_response: false,
+                authentication_failed: false,
+                disabled: false,
+
+                last_poll_sent: NtpTimestamp::default(),
+                last_response_received: NtpTimestamp::default(),
+                last_poll_sent_instant: None,
+
+                previous_exchange: None,
+                server_interleaved: false,
 
                 current_request_identifier: None,
                 source_id: ReferenceId::from_ip(source_addr.ip()),
                 source_addr,
-                reach: Reach::never(),
+                reach: Reach::with_window(source_config.reach_window),
                 tries: 0,
 
                 stratum: 16,
                 reference_id: ReferenceId::NONE,
+                timescale: None,
+
+                last_round_trip_delay: None,
+                last_precision: None,
 
                 source_config,
                 controller,
@@ -428,6 +579,8 @@ impl<Controller: SourceController> NtpSource<Controller> {
                 source_info,
 
                 source_snapshots,
+
+                captured_packets: VecDeque::new(),
             },
             actions!(NtpSourceAction::SetTimer(Duration::from_secs(0))),
         )
@@ -439,12 +592,57 @@ impl<Controller: SourceController> NtpSource<Controller> {
             unanswered_polls: self.reach.unanswered_polls(),
             poll_interval: self.last_poll_interval,
             nts_cookies: self.nts.as_ref().map(|nts| nts.cookies.len()),
+            last_poll_sent: self.last_poll_sent,
+            last_response_received: self.last_response_received,
+            timescale: self.timescale,
+            captured_packets: self.captured_packets.iter().cloned().collect(),
+            last_round_trip_delay: self.last_round_trip_delay,
+            last_precision: self.last_precision,
             name,
             address: self.source_addr.to_string(),
             id,
         }
     }
 
+    fn capture_packet(&mut self, direction: PacketDirection, data: &[u8]) {
+        if !self.source_config.capture_packets {
+            return;
+        }
+
+        if self.captured_packets.len() >= PACKET_CAPTURE_LENGTH {
+            self.captured_packets.pop_front();
+        }
+        self.captured_packets
+            .push_back(CapturedPacket::new(direction, data));
+    }
+
+    /// Register the local time at which the last poll message was actually sent.
+    pub fn handle_poll_sent(&mut self, send_time: NtpTimestamp) {
+        self.last_poll_sent = send_time;
+        self.last_poll_sent_instant = Some(tokio::time::Instant::now());
+    }
+
+    /// Whether an extra poll outside of the regular schedule can be sent at
+    /// `now` without exceeding the poll rate allowed by the remote.
+    pub fn may_poll_early(&self, now: NtpTimestamp) -> bool {
+        self.last_poll_sent == NtpTimestamp::default()
+            || now - self.last_poll_sent >= self.remote_min_poll_interval.as_duration()
+    }
+
+    /// Exclude the source from synchronization, or allow it to be used again.
+    /// A disabled source is still polled, so it can be enabled without delay.
+    pub fn set_disabled(&mut self, disabled: bool) {
+        self.disabled = disabled;
+        if disabled {
+            self.controller.set_usable(false);
+        }
+    }
+
+    fn set_usable(&mut self, usable: bool) {
+        self.controller
+            .set_usable(usable && !self.authentication_failed && !self.disabled);
+    }
+
     pub fn current_poll_interval(&self) -> PollInterval {
         self.controller
             .desired_poll_interval()
@@ -452,6 +650,15 @@ impl<Controller: SourceController> NtpSource<Controller> {
     }
 
     pub fn handle_timer(&mut self) -> NtpSourceActionIterator {
+        if let Some(cooldown_until) = self.rate_cooldown_until {
+            let now = tokio::time::Instant::now();
+            if cooldown_until > now {
+                // The server asked us to back off, so skip this poll
+                return actions!(NtpSourceAction::SetTimer(cooldown_until - now));
+            }
+            self.rate_cooldown_until = None;
+        }
+
         if !self.reach.is_reachable() && self.tries >= STARTUP_TRIES_THRESHOLD {
             return if self.have_deny_rstr_response {
                 // There were kiss of death responses, so we should probably demobilize instead
@@ -473,9 +680,13 @@ impl<Controller: SourceController> NtpSource<Controller> {
         self.tries = self.tries.saturating_add(1);
 
         let poll_interval = self.current_poll_interval();
-        let (mut packet, identifier) = match &mut self.nts {
+        let (mut packet, mut identifier) = match &mut self.nts {
             Some(nts) => {
                 let Some(cookie) = nts.cookies.get() else {
+                    // Every request asks for new cookies, so running out
+                    // means none of the recent requests got a response
+                    // refilling the stash.
+                    warn!("NTS source ran out of cookies, resetting to get new ones");
                     return actions!(NtpSourceAction::Reset);
                 };
                 // Do ensure we don't exceed the buffer size
@@ -513,8 +724,15 @@ impl<Controller: SourceController> NtpSource<Controller> {
                 }
             },
         };
+        if self.source_config.interleaved
+            && matches!(self.protocol_version, ProtocolVersion::V4)
+            && let Some(previous) = self.previous_exchange
+        {
+            packet.make_interleaved(&mut identifier, previous.server_receive_time);
+        }
         self.current_request_identifier =
             Some((identifier, tokio::time::Instant::now() + POLL_WINDOW));
+        self.last_poll_sent_instant = None;
 
         if let NtpHeader::V5(header) = packet.header() {
             let req_ef = self.bloom_filter.next_request(header.client_cookie);
@@ -536,7 +754,8 @@ impl<Controller: SourceController> NtpSource<Controller> {
             )
             .expect("Internal error: could not serialize packet");
         let used = cursor.position();
-        let result = &cursor.into_inner()[..used as usize];
+        let result = cursor.into_inner()[..used as usize].to_vec();
+        self.capture_packet(PacketDirection::Request, &result);
 
         let usable = {
             let source_info = self.source_info.read().unwrap();
@@ -552,16 +771,25 @@ impl<Controller: SourceController> NtpSource<Controller> {
             .lock()
             .unwrap()
             .insert(self.id, snapshot);
-        self.controller.set_usable(usable);
+        self.set_usable(usable);
+
+        // randomize the poll interval a little to make it harder to predict poll requests,
+        // or by the configured amount to spread out polls from many clients
+        let poll_jitter = self.source_config.poll_jitter;
+        let poll_factor = if poll_jitter > 0.0 {
+            thread_rng().gen_range((1.0 - poll_jitter)..=(1.0 + poll_jitter))
+        } else {
+            thread_rng().gen_range(1.01..=1.05)
+        };
+        // Jitter may not make us poll faster than the remote allows
+        let timer = poll_interval
+            .as_system_duration()
+            .mul_f64(poll_factor)
+            .max(self.remote_min_poll_interval.as_system_duration());
 
         actions!(
-            NtpSourceAction::Send(result.into()),
-            // randomize the poll interval a little to make it harder to predict poll requests
-            NtpSourceAction::SetTimer(
-                poll_interval
-                    .as_system_duration()
-                    .mul_f64(thread_rng().gen_range(1.01..=1.05))
-            )
+            NtpSourceAction::Send(result),
+            NtpSourceAction::SetTimer(timer)
         )
     }
 
@@ -570,7 +798,10 @@ impl<Controller: SourceController> NtpSource<Controller> {
         message: &[u8],
         send_time: NtpTimestamp,
         recv_time: NtpTimestamp,
+        recv_instant: tokio::time::Instant,
     ) -> NtpSourceActionIterator {
+        self.capture_packet(PacketDirection::Response, message);
+
         let message =
             match NtpPacket::deserialize(message, &self.nts.as_ref().map(|nts| nts.s2c.as_ref())) {
                 Ok((packet, _)) => packet,
@@ -621,6 +852,17 @@ impl<Controller: SourceController> NtpSource<Controller> {
         }
 
         if !message.valid_server_response(request_identifier, self.nts.is_some()) {
+            if self.nts.is_some()
+                && self.source_config.require_authenticated
+                && message.valid_unauthenticated_server_response(request_identifier)
+            {
+                // A response to our request that fails authentication, stop
+                // trusting the source altogether.
+                warn!("Received unauthenticated response from NTS source, no longer using it");
+                self.authentication_failed = true;
+                self.controller.set_usable(false);
+                return actions!();
+            }
             // Packets should be a response to a previous request from us,
             // if not just ignore. Note that this might also happen when
             // we reset between sending the request and receiving the response.
@@ -636,6 +878,12 @@ impl<Controller: SourceController> NtpSource<Controller> {
                     .inc(self.source_config.poll_interval_limits),
                 self.last_poll_interval,
             );
+            self.rate_cooldown_until = Some(
+                tokio::time::Instant::now() + self.remote_min_poll_interval.as_system_duration(),
+            );
+            self.rate_recovery_responses = (self.remote_min_poll_interval
+                > self.source_config.poll_interval_limits.min)
+                .then_some(0);
             warn!(?self.remote_min_poll_interval, "Source requested rate limit");
             actions!()
         } else if message.is_kiss_rstr() || message.is_kiss_deny() {
@@ -669,40 +917,154 @@ impl<Controller: SourceController> NtpSource<Controller> {
             warn!("Received packet with invalid mode");
             actions!()
         } else {
-            self.process_message(&message, send_time, recv_time)
+            let interleaved = message.is_interleaved_response(request_identifier);
+            self.process_message(&message, interleaved, send_time, recv_time, recv_instant)
         }
     }
 
     fn process_message(
         &mut self,
         message: &NtpPacket,
+        interleaved: bool,
         send_time: NtpTimestamp,
         recv_time: NtpTimestamp,
+        recv_instant: tokio::time::Instant,
     ) -> NtpSourceActionIterator {
         trace!("Packet accepted for processing");
+
+        // Process new cookies. The response is authentic at this point, so its
+        // cookies are kept even if the measurement itself is discarded below.
+        if let Some(nts) = self.nts.as_mut() {
+            for cookie in message.new_cookies() {
+                nts.cookies.store(cookie);
+            }
+        }
+
+        // If the clock was stepped between sending the poll and receiving the
+        // response, the timestamps of this exchange are inconsistent.
+        if let Some(sent_at) = self.last_poll_sent_instant {
+            let clock_round_trip = (recv_time - send_time).to_seconds();
+            let monotonic_round_trip = (recv_instant - sent_at).as_secs_f64();
+            if (clock_round_trip - monotonic_round_trip).abs()
+                > STEP_DETECTION_THRESHOLD.as_secs_f64()
+            {
+                warn!(
+                    clock_round_trip,
+                    monotonic_round_trip,
+                    "Clock was stepped during exchange with source, discarding response"
+                );
+                self.current_request_identifier = None;
+                return actions!();
+            }
+        }
+
+        let (mut measurement_outgoing, mut measurement_incoming) =
+            measurements_from_packet(message, self.id, send_time, recv_time);
+        if interleaved && let Some(previous) = self.previous_exchange {
+            // The transmit timestamp of an interleaved response belongs to the
+            // previous response, so the measurement is of the previous exchange
+            measurement_outgoing.sender_ts = previous.send_time;
+            measurement_outgoing.receiver_ts = previous.server_receive_time;
+            measurement_incoming.receiver_ts = previous.recv_time;
+        }
+
+        if let Some(min_stratum) = self.source_config.min_acceptable_stratum
+            && message.stratum() < min_stratum
+        {
+            debug!(
+                stratum = message.stratum(),
+                min_stratum,
+                "Discarding response with a stratum below the minimum acceptable stratum"
+            );
+            // Counts as a missed poll, like responses with an excessive
+            // round trip delay
+            self.current_request_identifier = None;
+            return actions!();
+        }
+
+        let round_trip = (measurement_incoming.receiver_ts - measurement_outgoing.sender_ts)
+            - (measurement_incoming.sender_ts - measurement_outgoing.receiver_ts);
+        if let Some(max_round_trip) = self.source_config.max_round_trip
+            && round_trip > max_round_trip
+        {
+            debug!(
+                %round_trip,
+                %max_round_trip,
+                "Discarding response with excessive round trip delay"
+            );
+            // Not counted as a response for reachability, but we also
+            // don't want to accept duplicates of it.
+            self.current_request_identifier = None;
+            return actions!();
+        }
+
         // For reachability, mark that we have had a response
         self.reach.received_packet();
 
         // Clear received deny/rstr kod
         self.have_deny_rstr_response = false;
 
+        self.last_response_received = recv_time;
+
         // we received this packet, and don't want to accept future ones with this next_expected_origin
         self.current_request_identifier = None;
 
         // Update stratum and reference id
+        let previous_reference_id = self.reference_id;
         self.stratum = message.stratum();
         self.reference_id = message.reference_id();
+        self.timescale = message.timescale();
+
+        // Only alert when the reference id changes, not on every response
+        if let Some(expected_refid) = self.source_config.expected_refid
+            && self.reference_id != expected_refid
+            && self.reference_id != previous_reference_id
+        {
+            match self.source_config.on_refid_mismatch {
+                RefidMismatchAction::Warn => warn!(
+                    reference_id = ?self.reference_id,
+                    ?expected_refid,
+                    "Source reported an unexpected reference id"
+                ),
+                RefidMismatchAction::Demote => warn!(
+                    reference_id = ?self.reference_id,
+                    ?expected_refid,
+                    "Source reported an unexpected reference id, excluding it from synchronization"
+                ),
+            }
+        }
+
+        if let Some(responses) = self.rate_recovery_responses {
+            // The server no longer rate limits us, slowly return to our own poll interval
+            if responses + 1 < RATE_RECOVERY_RESPONSES {
+                self.rate_recovery_responses = Some(responses + 1);
+            } else {
+                let limits = self.source_config.poll_interval_limits;
+                self.remote_min_poll_interval = self.remote_min_poll_interval.dec(limits);
+                debug!(?self.remote_min_poll_interval, "Lowering rate limited poll interval");
+                self.rate_recovery_responses =
+                    (self.remote_min_poll_interval > limits.min).then_some(0);
+            }
+        }
 
         if let NtpHeader::V5(header) = message.header() {
             // Handle new requested poll interval
             let requested_poll = message.poll();
             if requested_poll > self.remote_min_poll_interval {
-                debug!(
-                    ?requested_poll,
-                    ?self.remote_min_poll_interval,
-                    "Adapting to longer poll interval requested by server"
-                );
-                self.remote_min_poll_interval = requested_poll;
+                if self.source_config.ignore_server_poll {
+                    debug!(
+                        ?requested_poll,
+                        intended_poll = ?self.last_poll_interval,
+                        "Ignoring longer poll interval requested by server"
+                    );
+                } else {
+                    debug!(
+                        ?requested_poll,
+                        ?self.remote_min_poll_interval,
+                        "Adapting to longer poll interval requested by server"
+                    );
+                    self.remote_min_poll_interval = requested_poll;
+                }
             }
 
             // Update our bloom filter (we need separate branches due to types
@@ -730,6 +1092,14 @@ impl<Controller: SourceController> NtpSource<Controller> {
                     warn!(?err, "Invalid ReferenceIdResponse from source, ignoring...");
                 }
             }
+        } else if message.poll() != self.last_poll_interval {
+            // Before NTPv5 the poll field only echoes our request, so we never
+            // adapt to it and keep using our own intended poll interval
+            debug!(
+                response_poll = ?message.poll(),
+                intended_poll = ?self.last_poll_interval,
+                "Server echoed a different poll interval than requested, ignoring"
+            );
         }
 
         let snapshot = NtpSourceSnapshot::from_source(self);
@@ -747,18 +1117,32 @@ impl<Controller: SourceController> NtpSource<Controller> {
             .lock()
             .unwrap()
             .insert(self.id, snapshot);
-        self.controller.set_usable(usable);
+        self.set_usable(usable);
 
-        let (measurement_outgoing, measurement_incoming) =
-            measurements_from_packet(message, self.id, send_time, recv_time);
-        self.controller.handle_measurement(measurement_outgoing);
-        self.controller.handle_measurement(measurement_incoming);
+        // The first interleaved response measures an exchange that was
+        // already used as a regular response, so it is not used again
+        let measured_before = interleaved && !self.server_interleaved;
 
-        // Process new cookies
-        if let Some(nts) = self.nts.as_mut() {
-            for cookie in message.new_cookies() {
-                nts.cookies.store(cookie);
+        if self.source_config.interleaved {
+            if interleaved && !self.server_interleaved {
+                debug!("Server answered in interleaved mode");
+            } else if !interleaved && self.server_interleaved {
+                debug!("Server no longer answers in interleaved mode");
             }
+            self.server_interleaved = interleaved;
+            self.previous_exchange = Some(PreviousExchange {
+                send_time,
+                server_receive_time: message.receive_timestamp(),
+                recv_time,
+            });
+        }
+
+        self.last_round_trip_delay = Some(round_trip);
+        self.last_precision = Some(message.precision());
+
+        if !measured_before {
+            self.controller.handle_measurement(measurement_outgoing);
+            self.controller.handle_measurement(measurement_incoming);
         }
 
         actions!()
@@ -773,10 +1157,21 @@ impl<Controller: SourceController> NtpSource<Controller> {
 
             last_poll_interval: PollInterval::default(),
             remote_min_poll_interval: PollInterval::default(),
+            rate_cooldown_until: None,
+            rate_recovery_responses: None,
 
             current_request_identifier: None,
 
             have_deny_rstr_response: false,
+            authentication_failed: false,
+            disabled: false,
+
+            last_poll_sent: NtpTimestamp::default(),
+            last_response_received: NtpTimestamp::default(),
+            last_poll_sent_instant: None,
+
+            previous_exchange: None,
+            server_interleaved: false,
 
             source_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
             source_id: ReferenceId::from_int(0),
@@ -785,6 +1180,10 @@ impl<Controller: SourceController> NtpSource<Controller> {
 
             stratum: 0,
             reference_id: ReferenceId::from_int(0),
+            timescale: None,
+
+            last_round_trip_delay: None,
+            last_precision: None,
 
             source_config: SourceConfig::default(),
             controller,
@@ -800,6 +1199,8 @@ impl<Controller: SourceController> NtpSource<Controller> {
             source_info: Arc::default(),
 
             source_snapshots: Arc::default(),
+
+            captured_packets: VecDeque::new(),
         }
     }
 }
@@ -944,6 +1345,86 @@ mod test {
         assert!(reach.is_reachable());
     }
 
+    #[test]
+    fn reachability_window() {
+        let mut reach = Reach::with_window(3);
+        assert!(!reach.is_reachable());
+        assert_eq!(reach.unanswered_polls(), 3);
+
+        reach.received_packet();
+        assert!(reach.is_reachable());
+        assert_eq!(reach.unanswered_polls(), 0);
+
+        // with a window of 3, two missed polls still leave the source reachable
+        reach.poll();
+        reach.poll();
+        assert!(reach.is_reachable());
+        assert_eq!(reach.unanswered_polls(), 2);
+
+        // but the third miss marks it unreachable
+        reach.poll();
+        assert!(!reach.is_reachable());
+        assert_eq!(reach.unanswered_polls(), 3);
+
+        // and it stays that way, without the count growing beyond the window
+        reach.poll();
+        assert!(!reach.is_reachable());
+        assert_eq!(reach.unanswered_polls(), 3);
+
+        reach.received_packet();
+        assert!(reach.is_reachable());
+
+        // an answered poll within the window keeps the source reachable
+        reach.poll();
+        reach.poll();
+        reach.received_packet();
+        reach.poll();
+        reach.poll();
+        assert!(reach.is_reachable());
+        assert_eq!(reach.unanswered_polls(), 2);
+    }
+
+    #[test]
+    fn reachability_window_deserialize() {
+        let reach: Reach = serde_json::from_str(r#"{"register":1,"window":3}"#).unwrap();
+        assert_eq!(reach.window, 3);
+        assert!(reach.is_reachable());
+
+        // The window is validated, a zero window would make poll overflow
+        assert!(serde_json::from_str::<Reach>(r#"{"register":1,"window":0}"#).is_err());
+        assert!(serde_json::from_str::<Reach>(r#"{"register":1,"window":9}"#).is_err());
+    }
+
+    #[test]
+    fn reachability_window_from_config() {
+        let (mut source, _) = NtpSource::new(
+            "127.0.0.1:123".parse().unwrap(),
+            SourceConfig {
+                reach_window: 3,
+                ..SourceConfig::default()
+            },
+            ProtocolVersion::V4,
+            NoopController,
+            None,
+            ClockId::new(),
+            Arc::default(),
+            Arc::default(),
+        );
+        source.reach.received_packet();
+
+        for _ in 0..2 {
+            source.reach.poll();
+        }
+        assert!(source.reach.is_reachable());
+
+        source.reach.poll();
+        assert!(!source.reach.is_reachable());
+        assert_eq!(
+            source.observe("test".into(), ClockId(1)).unanswered_polls,
+            3
+        );
+    }
+
     #[test]
     fn test_accept_synchronization() {
         use AcceptSynchronizationError::*;
@@ -975,6 +1456,120 @@ mod test {
         assert_eq!(accept!(), Err(Stratum));
     }
 
+    fn respond_with_refid(
+        source: &mut NtpSource<NoopController>,
+        reference_id: ReferenceId,
+    ) -> Result<(), AcceptSynchronizationError> {
+        let mut outgoingbuf = None;
+        for action in source.handle_timer() {
+            if let NtpSourceAction::Send(buf) = action {
+                outgoingbuf = Some(buf);
+            }
+        }
+        let outgoing = NtpPacket::deserialize(&outgoingbuf.unwrap(), &NoCipher)
+            .unwrap()
+            .0;
+
+        let mut packet = NtpPacket::test();
+        packet.set_stratum(1);
+        packet.set_mode(NtpAssociationMode::Server);
+        packet.set_reference_id(reference_id);
+        packet.set_origin_timestamp(outgoing.transmit_timestamp());
+        packet.set_receive_timestamp(NtpTimestamp::from_fixed_int(100));
+        packet.set_transmit_timestamp(NtpTimestamp::from_fixed_int(200));
+
+        for action in source.handle_incoming(
+            &packet.serialize_without_encryption_vec(None).unwrap(),
+            NtpTimestamp::from_fixed_int(0),
+            NtpTimestamp::from_fixed_int(400),
+            tokio::time::Instant::now(),
+        ) {
+            assert!(!matches!(
+                action,
+                NtpSourceAction::Reset | NtpSourceAction::Demobilize
+            ));
+        }
+
+        NtpSourceSnapshot::from_source(source).accept_synchronization(
+            16,
+            &["127.0.0.1".parse().unwrap()],
+            ServerId::default(),
+        )
+    }
+
+    fn expected_refid_source(action: RefidMismatchAction) -> NtpSource<NoopController> {
+        let mut source = NtpSource::test_ntp_source(NoopController);
+        source.protocol_version = ProtocolVersion::V4;
+        source.source_config.expected_refid = Some(ReferenceId::from_bytes(*b"GPS\0"));
+        source.source_config.on_refid_mismatch = action;
+        source.source_id = ReferenceId::from_ip("127.0.1.1".parse().unwrap());
+        source
+    }
+
+    #[test]
+    fn test_expected_refid() {
+        let mut source = expected_refid_source(RefidMismatchAction::Demote);
+
+        assert_eq!(
+            respond_with_refid(&mut source, ReferenceId::from_bytes(*b"PPS\0")),
+            Err(AcceptSynchronizationError::ReferenceId)
+        );
+        assert_eq!(
+            respond_with_refid(&mut source, ReferenceId::from_bytes(*b"GPS\0")),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_expected_refid_warn_only() {
+        let mut source = expected_refid_source(RefidMismatchAction::Warn);
+
+        // The mismatch is only reported, the source remains usable
+        assert_eq!(
+            respond_with_refid(&mut source, ReferenceId::from_bytes(*b"PPS\0")),
+            Ok(())
+        );
+        assert_eq!(source.reference_id, ReferenceId::from_bytes(*b"PPS\0"));
+        assert_eq!(
+            respond_with_refid(&mut source, ReferenceId::from_bytes(*b"GPS\0")),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_capture_packets() {
+        let mut source = NtpSource::test_ntp_source(NoopController);
+        source.protocol_version = ProtocolVersion::V4;
+        let _ = respond_with_refid(&mut source, ReferenceId::NONE);
+        assert!(source.captured_packets.is_empty());
+        assert!(
+            source
+                .observe("test".into(), ClockId(1))
+                .captured_packets
+                .is_empty()
+        );
+
+        source.source_config.capture_packets = true;
+        let _ = respond_with_refid(&mut source, ReferenceId::NONE);
+        let captured = source.observe("test".into(), ClockId(1)).captured_packets;
+        assert_eq!(captured.len(), 2);
+        assert_eq!(captured[0].direction, PacketDirection::Request);
+        assert_eq!(captured[1].direction, PacketDirection::Response);
+        // An NTPv4 packet without extensions is 48 bytes
+        assert_eq!(captured[0].data.len(), 96);
+        assert!(captured[1].data.starts_with("24"));
+
+        // The capture is bounded, keeping the most recent packets
+        for _ in 0..PACKET_CAPTURE_LENGTH {
+            let _ = respond_with_refid(&mut source, ReferenceId::NONE);
+        }
+        assert_eq!(source.captured_packets.len(), PACKET_CAPTURE_LENGTH);
+        assert_eq!(
+            source.captured_packets.front().unwrap().direction,
+            PacketDirection::Request
+        );
+    }
+
     #[test]
     fn test_poll_interval() {
         struct PollIntervalController(PollInterval);
@@ -1031,6 +1626,68 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_cookie_exhaustion_warns() {
+        #[derive(Clone, Default)]
+        struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for LogBuffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut source = NtpSource::test_ntp_source(NoopController);
+        source.protocol_version = ProtocolVersion::V4;
+        let mut ntsdata = SourceNtsData {
+            cookies: CookieStash::default(),
+            c2s: Box::new(AesSivCmac256::new([0; 32].into())),
+            s2c: Box::new(AesSivCmac256::new([0; 32].into())),
+        };
+        ntsdata.cookies.store(vec![0; 32]);
+        source.nts = Some(Box::new(ntsdata));
+        assert_eq!(
+            source.observe("test".into(), ClockId(1)).nts_cookies,
+            Some(1)
+        );
+
+        let logs = LogBuffer::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer({
+                let logs = logs.clone();
+                move || logs.clone()
+            })
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            // The last cookie is used for a request that asks for new ones
+            let actions: Vec<_> = source.handle_timer().collect();
+            assert!(
+                actions
+                    .iter()
+                    .any(|action| matches!(action, NtpSourceAction::Send(_)))
+            );
+            assert_eq!(
+                source.observe("test".into(), ClockId(1)).nts_cookies,
+                Some(0)
+            );
+            assert!(logs.0.lock().unwrap().is_empty());
+
+            // Without a response, there is nothing left to poll with
+            let actions: Vec<_> = source.handle_timer().collect();
+            assert!(matches!(actions[..], [NtpSourceAction::Reset]));
+        });
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("WARN"));
+        assert!(logs.contains("ran out of cookies"));
+    }
+
     #[test]
     fn test_handle_incoming() {
         let mut source = NtpSource::test_ntp_source(NoopController);
@@ -1059,6 +1716,7 @@ mod test {
             &packet.serialize_without_encryption_vec(None).unwrap(),
             NtpTimestamp::from_fixed_int(0),
             NtpTimestamp::from_fixed_int(400),
+            tokio::time::Instant::now(),
         );
         for action in actions {
             assert!(!matches!(
@@ -1073,40 +1731,627 @@ mod test {
             &packet.serialize_without_encryption_vec(None).unwrap(),
             NtpTimestamp::from_fixed_int(0),
             NtpTimestamp::from_fixed_int(500),
+            tokio::time::Instant::now(),
         );
         assert!(actions.next().is_none());
     }
 
     #[test]
-    fn test_startup_unreachable() {
+    fn test_observe_last_packet_timestamps() {
         let mut source = NtpSource::test_ntp_source(NoopController);
-        let actions = source.handle_timer();
-        for action in actions {
-            assert!(!matches!(
-                action,
-                NtpSourceAction::Reset | NtpSourceAction::Demobilize
-            ));
-        }
-        let actions = source.handle_timer();
-        for action in actions {
-            assert!(!matches!(
-                action,
-                NtpSourceAction::Reset | NtpSourceAction::Demobilize
-            ));
-        }
-        let actions = source.handle_timer();
-        for action in actions {
-            assert!(!matches!(
-                action,
-                NtpSourceAction::Reset | NtpSourceAction::Demobilize
-            ));
-        }
-        let mut actions = source.handle_timer();
-        assert!(matches!(actions.next(), Some(NtpSourceAction::Reset)));
-    }
 
-    #[test]
-    fn test_running_unreachable() {
+        let observed = source.observe("test".into(), ClockId(1));
+        assert_eq!(observed.last_poll_sent, NtpTimestamp::default());
+        assert_eq!(observed.last_response_received, NtpTimestamp::default());
+
+        let mut previous = observed;
+        for cycle in 1..=2 {
+            let mut outgoingbuf = None;
+            for action in source.handle_timer() {
+                if let NtpSourceAction::Send(buf) = action {
+                    outgoingbuf = Some(buf);
+                }
+            }
+            let outgoing = NtpPacket::deserialize(&outgoingbuf.unwrap(), &NoCipher)
+                .unwrap()
+                .0;
+
+            let send_time = NtpTimestamp::from_fixed_int(1000 * cycle);
+            let recv_time = NtpTimestamp::from_fixed_int(1000 * cycle + 400);
+            source.handle_poll_sent(send_time);
+
+            let mut packet = NtpPacket::test();
+            packet.set_stratum(1);
+            packet.set_mode(NtpAssociationMode::Server);
+            packet.set_origin_timestamp(outgoing.transmit_timestamp());
+            packet.set_receive_timestamp(NtpTimestamp::from_fixed_int(1000 * cycle + 100));
+            packet.set_transmit_timestamp(NtpTimestamp::from_fixed_int(1000 * cycle + 200));
+            let _ = source.handle_incoming(
+                &packet.serialize_without_encryption_vec(None).unwrap(),
+                send_time,
+                recv_time,
+                tokio::time::Instant::now(),
+            );
+
+            let observed = source.observe("test".into(), ClockId(1));
+            assert_eq!(observed.last_poll_sent, send_time);
+            assert_eq!(observed.last_response_received, recv_time);
+            assert!(observed.last_poll_sent > previous.last_poll_sent);
+            assert!(observed.last_response_received > previous.last_response_received);
+            previous = observed;
+        }
+    }
+
+    #[test]
+    fn test_observe_last_measurement() {
+        let mut source = NtpSource::test_ntp_source(NoopController);
+
+        let observed = source.observe("test".into(), ClockId(1));
+        assert_eq!(observed.last_round_trip_delay, None);
+        assert_eq!(observed.last_precision, None);
+
+        let mut outgoingbuf = None;
+        for action in source.handle_timer() {
+            if let NtpSourceAction::Send(buf) = action {
+                outgoingbuf = Some(buf);
+            }
+        }
+        let outgoing = NtpPacket::deserialize(&outgoingbuf.unwrap(), &NoCipher)
+            .unwrap()
+            .0;
+
+        let send_time = NtpTimestamp::from_fixed_int(1000);
+        let recv_time = NtpTimestamp::from_fixed_int(1400);
+        source.handle_poll_sent(send_time);
+
+        let mut packet = NtpPacket::test();
+        packet.set_stratum(1);
+        packet.set_mode(NtpAssociationMode::Server);
+        packet.set_precision(-20);
+        packet.set_origin_timestamp(outgoing.transmit_timestamp());
+        packet.set_receive_timestamp(NtpTimestamp::from_fixed_int(1100));
+        packet.set_transmit_timestamp(NtpTimestamp::from_fixed_int(1200));
+        let _ = source.handle_incoming(
+            &packet.serialize_without_encryption_vec(None).unwrap(),
+            send_time,
+            recv_time,
+            tokio::time::Instant::now(),
+        );
+
+        let observed = source.observe("test".into(), ClockId(1));
+        assert_eq!(
+            observed.last_round_trip_delay,
+            Some(NtpDuration::from_fixed_int(300))
+        );
+        assert_eq!(observed.last_precision, Some(-20));
+    }
+
+    #[derive(Default)]
+    struct RecordingController(Vec<Measurement>);
+    impl SourceController for RecordingController {
+        fn handle_measurement(&mut self, measurement: Measurement) {
+            self.0.push(measurement);
+        }
+
+        fn set_usable(&mut self, _: bool) {
+            // no action
+        }
+
+        fn desired_poll_interval(&self) -> PollInterval {
+            PollInterval::default()
+        }
+
+        fn observe(&self) -> crate::ObservableSourceTimedata {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn test_max_round_trip() {
+        let mut source = NtpSource::test_ntp_source(RecordingController::default());
+        source.source_config.max_round_trip = Some(NtpDuration::from_fixed_int(1000));
+
+        for cycle in 1..=8 {
+            let mut outgoingbuf = None;
+            for action in source.handle_timer() {
+                if let NtpSourceAction::Send(buf) = action {
+                    outgoingbuf = Some(buf);
+                }
+            }
+            let outgoing = NtpPacket::deserialize(&outgoingbuf.unwrap(), &NoCipher)
+                .unwrap()
+                .0;
+
+            // Every other response takes far too long to arrive, and would
+            // show a large offset if it was used
+            let good = cycle % 2 == 1;
+            let base = 1_000_000 * cycle;
+            let send_time = NtpTimestamp::from_fixed_int(base);
+            let recv_time =
+                NtpTimestamp::from_fixed_int(if good { base + 400 } else { base + 100_000 });
+
+            let mut packet = NtpPacket::test();
+            packet.set_stratum(1);
+            packet.set_mode(NtpAssociationMode::Server);
+            packet.set_origin_timestamp(outgoing.transmit_timestamp());
+            packet.set_receive_timestamp(NtpTimestamp::from_fixed_int(base + 100));
+            packet.set_transmit_timestamp(NtpTimestamp::from_fixed_int(base + 200));
+            let _ = source.handle_incoming(
+                &packet.serialize_without_encryption_vec(None).unwrap(),
+                send_time,
+                recv_time,
+                tokio::time::Instant::now(),
+            );
+
+            // Discarded responses still count as a missed poll
+            assert_eq!(source.reach.unanswered_polls(), u32::from(!good));
+        }
+
+        let measurements = &source.controller.0;
+        assert_eq!(measurements.len(), 8);
+        for pair in measurements.chunks(2) {
+            let [outgoing, incoming] = pair else {
+                unreachable!()
+            };
+            let offset = ((outgoing.receiver_ts - outgoing.sender_ts)
+                + (incoming.sender_ts - incoming.receiver_ts))
+                / 2;
+            assert_eq!(offset, NtpDuration::from_fixed_int(-50));
+        }
+    }
+
+    /// Turn `packet` into an NTS protected response to the request in
+    /// `outgoingbuf`, carrying a single new cookie.
+    fn serialize_nts_response(outgoingbuf: &[u8], mut packet: NtpPacket<'static>) -> Vec<u8> {
+        let c2s = AesSivCmac256::new([0; 32].into());
+        let outgoing = NtpPacket::deserialize(outgoingbuf, &c2s).unwrap().0;
+        packet.set_origin_timestamp(outgoing.transmit_timestamp());
+        packet.push_encrypted(ExtensionField::NtsCookie(vec![0; 32].into()));
+        for ef in outgoing.authenticated_extension_fields() {
+            if matches!(ef, ExtensionField::UniqueIdentifier(_)) {
+                packet.push_additional(ef.clone().into_owned());
+            }
+        }
+
+        let s2c = AesSivCmac256::new([0; 32].into());
+        let mut buffer = vec![0u8; 1024];
+        let mut cursor = std::io::Cursor::new(buffer.as_mut_slice());
+        packet.serialize(&mut cursor, &s2c, None).unwrap();
+        let length = cursor.position() as usize;
+        buffer.truncate(length);
+        buffer
+    }
+
+    fn test_nts_source() -> NtpSource<RecordingController> {
+        let mut source = NtpSource::test_ntp_source(RecordingController::default());
+        source.protocol_version = ProtocolVersion::V4;
+        let mut ntsdata = SourceNtsData {
+            cookies: CookieStash::default(),
+            c2s: Box::new(AesSivCmac256::new([0; 32].into())),
+            s2c: Box::new(AesSivCmac256::new([0; 32].into())),
+        };
+        ntsdata.cookies.store(vec![0; 32]);
+        source.nts = Some(Box::new(ntsdata));
+        source
+    }
+
+    #[test]
+    fn test_max_round_trip_keeps_cookies() {
+        let mut source = test_nts_source();
+        source.source_config.max_round_trip = Some(NtpDuration::from_fixed_int(1000));
+
+        let mut outgoingbuf = None;
+        for action in source.handle_timer() {
+            if let NtpSourceAction::Send(buf) = action {
+                outgoingbuf = Some(buf);
+            }
+        }
+        assert_eq!(source.nts.as_ref().unwrap().cookies.len(), 0);
+
+        // An authentic response that takes far too long to arrive
+        let mut packet = NtpPacket::test();
+        packet.set_stratum(1);
+        packet.set_mode(NtpAssociationMode::Server);
+        packet.set_receive_timestamp(NtpTimestamp::from_fixed_int(100));
+        packet.set_transmit_timestamp(NtpTimestamp::from_fixed_int(200));
+        let _ = source.handle_incoming(
+            &serialize_nts_response(&outgoingbuf.unwrap(), packet),
+            NtpTimestamp::from_fixed_int(0),
+            NtpTimestamp::from_fixed_int(100_000),
+            tokio::time::Instant::now(),
+        );
+
+        // The measurement is discarded, but the new cookie is kept
+        assert!(source.controller.0.is_empty());
+        assert_eq!(source.nts.as_ref().unwrap().cookies.len(), 1);
+    }
+
+    #[test]
+    fn test_min_acceptable_stratum() {
+        let mut source = NtpSource::test_ntp_source(RecordingController::default());
+        source.source_config.min_acceptable_stratum = Some(2);
+
+        for (cycle, stratum) in (1..).zip([1, 2, 3, 1, 15]) {
+            let mut outgoingbuf = None;
+            for action in source.handle_timer() {
+                if let NtpSourceAction::Send(buf) = action {
+                    outgoingbuf = Some(buf);
+                }
+            }
+            let outgoing = NtpPacket::deserialize(&outgoingbuf.unwrap(), &NoCipher)
+                .unwrap()
+                .0;
+
+            let base = 1_000_000 * cycle;
+            let mut packet = NtpPacket::test();
+            packet.set_stratum(stratum);
+            packet.set_mode(NtpAssociationMode::Server);
+            packet.set_origin_timestamp(outgoing.transmit_timestamp());
+            packet.set_receive_timestamp(NtpTimestamp::from_fixed_int(base + 100));
+            packet.set_transmit_timestamp(NtpTimestamp::from_fixed_int(base + 200));
+            let _ = source.handle_incoming(
+                &packet.serialize_without_encryption_vec(None).unwrap(),
+                NtpTimestamp::from_fixed_int(base),
+                NtpTimestamp::from_fixed_int(base + 400),
+                tokio::time::Instant::now(),
+            );
+
+            let accepted = stratum >= 2;
+            assert_eq!(source.reach.unanswered_polls(), u32::from(!accepted));
+            if accepted {
+                assert_eq!(source.stratum, stratum);
+            }
+        }
+
+        // Only the three responses at or above the floor were measured
+        assert_eq!(source.controller.0.len(), 6);
+    }
+
+    #[test]
+    fn test_min_acceptable_stratum_keeps_cookies() {
+        let mut source = test_nts_source();
+        source.source_config.min_acceptable_stratum = Some(2);
+
+        let mut outgoingbuf = None;
+        for action in source.handle_timer() {
+            if let NtpSourceAction::Send(buf) = action {
+                outgoingbuf = Some(buf);
+            }
+        }
+        assert_eq!(source.nts.as_ref().unwrap().cookies.len(), 0);
+
+        // An authentic response from a source below the stratum floor
+        let mut packet = NtpPacket::test();
+        packet.set_stratum(1);
+        packet.set_mode(NtpAssociationMode::Server);
+        packet.set_receive_timestamp(NtpTimestamp::from_fixed_int(100));
+        packet.set_transmit_timestamp(NtpTimestamp::from_fixed_int(200));
+        let _ = source.handle_incoming(
+            &serialize_nts_response(&outgoingbuf.unwrap(), packet),
+            NtpTimestamp::from_fixed_int(0),
+            NtpTimestamp::from_fixed_int(400),
+            tokio::time::Instant::now(),
+        );
+
+        // The measurement is discarded, but the new cookie is kept
+        assert!(source.controller.0.is_empty());
+        assert_eq!(source.nts.as_ref().unwrap().cookies.len(), 1);
+    }
+
+    #[test]
+    fn test_step_during_exchange() {
+        let mut source = NtpSource::test_ntp_source(RecordingController::default());
+
+        for stepped in [false, true] {
+            let mut outgoingbuf = None;
+            for action in source.handle_timer() {
+                if let NtpSourceAction::Send(buf) = action {
+                    outgoingbuf = Some(buf);
+                }
+            }
+            let outgoing = NtpPacket::deserialize(&outgoingbuf.unwrap(), &NoCipher)
+                .unwrap()
+                .0;
+
+            let send_time = NtpTimestamp::from_seconds_nanos_since_ntp_era(1000, 0);
+            source.handle_poll_sent(send_time);
+
+            // A step of the clock shows up as a round trip far longer than
+            // the time that actually passed
+            let recv_time = if stepped {
+                send_time + NtpDuration::from_seconds(10.0)
+            } else {
+                send_time + NtpDuration::from_seconds(0.001)
+            };
+
+            let mut packet = NtpPacket::test();
+            packet.set_stratum(1);
+            packet.set_mode(NtpAssociationMode::Server);
+            packet.set_origin_timestamp(outgoing.transmit_timestamp());
+            packet.set_receive_timestamp(send_time);
+            packet.set_transmit_timestamp(send_time);
+            let mut actions = source.handle_incoming(
+                &packet.serialize_without_encryption_vec(None).unwrap(),
+                send_time,
+                recv_time,
+                tokio::time::Instant::now(),
+            );
+            assert!(actions.next().is_none());
+
+            // Only the regular exchange produced measurements
+            assert_eq!(source.controller.0.len(), 2);
+            assert_eq!(source.reach.unanswered_polls(), u32::from(stepped));
+        }
+    }
+
+    #[test]
+    fn test_slow_exchange_is_not_a_step() {
+        let mut source = NtpSource::test_ntp_source(RecordingController::default());
+
+        let mut outgoingbuf = None;
+        for action in source.handle_timer() {
+            if let NtpSourceAction::Send(buf) = action {
+                outgoingbuf = Some(buf);
+            }
+        }
+        let outgoing = NtpPacket::deserialize(&outgoingbuf.unwrap(), &NoCipher)
+            .unwrap()
+            .0;
+
+        let send_time = NtpTimestamp::from_seconds_nanos_since_ntp_era(1000, 0);
+        source.handle_poll_sent(send_time);
+        let sent_at = source.last_poll_sent_instant.unwrap();
+
+        // The round trip is measured up to the moment the response was
+        // received, not up to the moment it is processed
+        let mut packet = NtpPacket::test();
+        packet.set_stratum(1);
+        packet.set_mode(NtpAssociationMode::Server);
+        packet.set_origin_timestamp(outgoing.transmit_timestamp());
+        packet.set_receive_timestamp(send_time);
+        packet.set_transmit_timestamp(send_time);
+        let _ = source.handle_incoming(
+            &packet.serialize_without_encryption_vec(None).unwrap(),
+            send_time,
+            send_time + NtpDuration::from_seconds(3.0),
+            sent_at + Duration::from_secs(3),
+        );
+
+        assert_eq!(source.controller.0.len(), 2);
+        assert_eq!(source.reach.unanswered_polls(), 0);
+    }
+
+    #[test]
+    fn test_step_during_exchange_keeps_cookies() {
+        let mut source = test_nts_source();
+
+        let mut outgoingbuf = None;
+        for action in source.handle_timer() {
+            if let NtpSourceAction::Send(buf) = action {
+                outgoingbuf = Some(buf);
+            }
+        }
+        assert_eq!(source.nts.as_ref().unwrap().cookies.len(), 0);
+
+        let send_time = NtpTimestamp::from_seconds_nanos_since_ntp_era(1000, 0);
+        source.handle_poll_sent(send_time);
+
+        let mut packet = NtpPacket::test();
+        packet.set_stratum(1);
+        packet.set_mode(NtpAssociationMode::Server);
+        packet.set_receive_timestamp(send_time);
+        packet.set_transmit_timestamp(send_time);
+        let _ = source.handle_incoming(
+            &serialize_nts_response(&outgoingbuf.unwrap(), packet),
+            send_time,
+            send_time + NtpDuration::from_seconds(10.0),
+            tokio::time::Instant::now(),
+        );
+
+        // The measurement is discarded, but the new cookie is kept
+        assert!(source.controller.0.is_empty());
+        assert_eq!(source.nts.as_ref().unwrap().cookies.len(), 1);
+    }
+
+    #[test]
+    fn test_interleaved_mode() {
+        let mut source = NtpSource::test_ntp_source(RecordingController::default());
+        source.protocol_version = ProtocolVersion::V4;
+        source.source_config.interleaved = true;
+
+        fn poll(source: &mut NtpSource<RecordingController>) -> NtpPacket<'static> {
+            let mut outgoingbuf = None;
+            for action in source.handle_timer() {
+                if let NtpSourceAction::Send(buf) = action {
+                    outgoingbuf = Some(buf);
+                }
+            }
+            NtpPacket::deserialize(&outgoingbuf.unwrap(), &NoCipher)
+                .unwrap()
+                .0
+                .into_owned()
+        }
+        let ts = NtpTimestamp::from_fixed_int;
+
+        // Without a previous exchange, the first request is a regular one
+        let outgoing = poll(&mut source);
+        assert_eq!(outgoing.origin_timestamp(), NtpTimestamp::default());
+
+        let mut packet = NtpPacket::test();
+        packet.set_stratum(1);
+        packet.set_mode(NtpAssociationMode::Server);
+        packet.set_origin_timestamp(outgoing.transmit_timestamp());
+        packet.set_receive_timestamp(ts(1100));
+        // Estimate of the transmit time, the actual time turns out to be 1250
+        packet.set_transmit_timestamp(ts(1200));
+        let _ = source.handle_incoming(
+            &packet.serialize_without_encryption_vec(None).unwrap(),
+            ts(1000),
+            ts(1400),
+            tokio::time::Instant::now(),
+        );
+
+        // The next request refers to the previous response by its receive
+        // timestamp, and carries a fresh cookie in its receive timestamp
+        let outgoing = poll(&mut source);
+        assert_eq!(outgoing.origin_timestamp(), ts(1100));
+        assert_ne!(outgoing.receive_timestamp(), NtpTimestamp::default());
+
+        // The server recognizes the request and sends the actual transmit
+        // timestamp of its previous response
+        let mut packet = NtpPacket::test();
+        packet.set_stratum(1);
+        packet.set_mode(NtpAssociationMode::Server);
+        packet.set_origin_timestamp(outgoing.receive_timestamp());
+        packet.set_receive_timestamp(ts(2100));
+        packet.set_transmit_timestamp(ts(1250));
+        let _ = source.handle_incoming(
+            &packet.serialize_without_encryption_vec(None).unwrap(),
+            ts(2000),
+            ts(2400),
+            tokio::time::Instant::now(),
+        );
+        assert!(source.server_interleaved);
+
+        // The first exchange is measured once, with the estimated transmit
+        // time, as it was already used when the interleaved response came in
+        let measurements = &source.controller.0;
+        assert_eq!(measurements.len(), 2);
+        assert_eq!(measurements[0].sender_ts, ts(1000));
+        assert_eq!(measurements[0].receiver_ts, ts(1100));
+        assert_eq!(measurements[1].sender_ts, ts(1200));
+        assert_eq!(measurements[1].receiver_ts, ts(1400));
+
+        // Further interleaved responses measure the previous exchange with
+        // its actual transmit time
+        let outgoing = poll(&mut source);
+        assert_eq!(outgoing.origin_timestamp(), ts(2100));
+
+        let mut packet = NtpPacket::test();
+        packet.set_stratum(1);
+        packet.set_mode(NtpAssociationMode::Server);
+        packet.set_origin_timestamp(outgoing.receive_timestamp());
+        packet.set_receive_timestamp(ts(3100));
+        packet.set_transmit_timestamp(ts(2250));
+        let _ = source.handle_incoming(
+            &packet.serialize_without_encryption_vec(None).unwrap(),
+            ts(3000),
+            ts(3400),
+            tokio::time::Instant::now(),
+        );
+        assert!(source.server_interleaved);
+
+        let measurements = &source.controller.0;
+        assert_eq!(measurements.len(), 4);
+        assert_eq!(measurements[2].sender_ts, ts(2000));
+        assert_eq!(measurements[2].receiver_ts, ts(2100));
+        assert_eq!(measurements[3].sender_ts, ts(2250));
+        assert_eq!(measurements[3].receiver_ts, ts(2400));
+
+        // A regular response to an interleaved request is still used as is
+        let outgoing = poll(&mut source);
+        assert_eq!(outgoing.origin_timestamp(), ts(3100));
+
+        let mut packet = NtpPacket::test();
+        packet.set_stratum(1);
+        packet.set_mode(NtpAssociationMode::Server);
+        packet.set_origin_timestamp(outgoing.transmit_timestamp());
+        packet.set_receive_timestamp(ts(4100));
+        packet.set_transmit_timestamp(ts(4200));
+        let _ = source.handle_incoming(
+            &packet.serialize_without_encryption_vec(None).unwrap(),
+            ts(4000),
+            ts(4400),
+            tokio::time::Instant::now(),
+        );
+        assert!(!source.server_interleaved);
+
+        let measurements = &source.controller.0;
+        assert_eq!(measurements.len(), 6);
+        assert_eq!(measurements[4].sender_ts, ts(4000));
+        assert_eq!(measurements[5].sender_ts, ts(4200));
+        assert_eq!(measurements[5].receiver_ts, ts(4400));
+    }
+
+    #[test]
+    fn test_may_poll_early() {
+        let mut source = NtpSource::test_ntp_source(NoopController);
+        let base = NtpTimestamp::from_seconds_nanos_since_ntp_era(1000, 0);
+
+        // Never polled, so an early poll is always fine
+        assert!(source.may_poll_early(base));
+
+        source.handle_poll_sent(base);
+        assert!(!source.may_poll_early(base));
+        assert!(!source.may_poll_early(base + NtpDuration::from_seconds(8.0)));
+        assert!(source.may_poll_early(base + NtpDuration::from_seconds(16.0)));
+    }
+
+    #[test]
+    fn test_poll_jitter() {
+        let mut source = NtpSource::test_ntp_source(NoopController);
+        source.source_config.poll_jitter = 0.25;
+        let interval = source.current_poll_interval().as_system_duration();
+
+        for _ in 0..100 {
+            source.tries = 0;
+            for action in source.handle_timer() {
+                if let NtpSourceAction::SetTimer(timer) = action {
+                    assert!(timer >= interval.mul_f64(0.75));
+                    assert!(timer <= interval.mul_f64(1.25));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_poll_jitter_respects_remote_min_poll_interval() {
+        let mut source = NtpSource::test_ntp_source(NoopController);
+        source.source_config.poll_jitter = 0.25;
+        source.remote_min_poll_interval = source.current_poll_interval();
+        let interval = source.remote_min_poll_interval.as_system_duration();
+
+        for _ in 0..100 {
+            source.tries = 0;
+            for action in source.handle_timer() {
+                if let NtpSourceAction::SetTimer(timer) = action {
+                    assert!(timer >= interval);
+                    assert!(timer <= interval.mul_f64(1.25));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_startup_unreachable() {
+        let mut source = NtpSource::test_ntp_source(NoopController);
+        let actions = source.handle_timer();
+        for action in actions {
+            assert!(!matches!(
+                action,
+                NtpSourceAction::Reset | NtpSourceAction::Demobilize
+            ));
+        }
+        let actions = source.handle_timer();
+        for action in actions {
+            assert!(!matches!(
+                action,
+                NtpSourceAction::Reset | NtpSourceAction::Demobilize
+            ));
+        }
+        let actions = source.handle_timer();
+        for action in actions {
+            assert!(!matches!(
+                action,
+                NtpSourceAction::Reset | NtpSourceAction::Demobilize
+            ));
+        }
+        let mut actions = source.handle_timer();
+        assert!(matches!(actions.next(), Some(NtpSourceAction::Reset)));
+    }
+
+    #[test]
+    fn test_running_unreachable() {
         let mut source = NtpSource::test_ntp_source(NoopController);
 
         let actions = source.handle_timer();
@@ -1132,6 +2377,7 @@ mod test {
             &packet.serialize_without_encryption_vec(None).unwrap(),
             NtpTimestamp::from_fixed_int(0),
             NtpTimestamp::from_fixed_int(400),
+            tokio::time::Instant::now(),
         );
         for action in actions {
             assert!(!matches!(
@@ -1230,6 +2476,7 @@ mod test {
             &packet.serialize_without_encryption_vec(None).unwrap(),
             NtpTimestamp::from_fixed_int(0),
             NtpTimestamp::from_fixed_int(500),
+            tokio::time::Instant::now(),
         );
         assert!(actions.next().is_none());
 
@@ -1238,6 +2485,7 @@ mod test {
             &packet.serialize_without_encryption_vec(None).unwrap(),
             NtpTimestamp::from_fixed_int(0),
             NtpTimestamp::from_fixed_int(500),
+            tokio::time::Instant::now(),
         );
         assert!(actions.next().is_none());
     }
@@ -1253,6 +2501,7 @@ mod test {
             &packet.serialize_without_encryption_vec(None).unwrap(),
             NtpTimestamp::from_fixed_int(0),
             NtpTimestamp::from_fixed_int(100),
+            tokio::time::Instant::now(),
         );
         assert!(!source.have_deny_rstr_response);
         assert!(actions.next().is_none());
@@ -1278,6 +2527,7 @@ mod test {
             &packet.serialize_without_encryption_vec(None).unwrap(),
             NtpTimestamp::from_fixed_int(0),
             NtpTimestamp::from_fixed_int(100),
+            tokio::time::Instant::now(),
         );
         assert!(source.have_deny_rstr_response);
         source.have_deny_rstr_response = false;
@@ -1290,6 +2540,7 @@ mod test {
             &packet.serialize_without_encryption_vec(None).unwrap(),
             NtpTimestamp::from_fixed_int(0),
             NtpTimestamp::from_fixed_int(100),
+            tokio::time::Instant::now(),
         );
         assert!(!source.have_deny_rstr_response);
         assert!(actions.next().is_none());
@@ -1315,6 +2566,7 @@ mod test {
             &packet.serialize_without_encryption_vec(None).unwrap(),
             NtpTimestamp::from_fixed_int(0),
             NtpTimestamp::from_fixed_int(100),
+            tokio::time::Instant::now(),
         );
         assert!(source.have_deny_rstr_response);
         source.have_deny_rstr_response = false;
@@ -1328,6 +2580,7 @@ mod test {
             &packet.serialize_without_encryption_vec(None).unwrap(),
             NtpTimestamp::from_fixed_int(0),
             NtpTimestamp::from_fixed_int(100),
+            tokio::time::Instant::now(),
         );
         assert!(actions.next().is_none());
         assert_eq!(source.remote_min_poll_interval, old_remote_interval);
@@ -1354,6 +2607,7 @@ mod test {
             &packet.serialize_without_encryption_vec(None).unwrap(),
             NtpTimestamp::from_fixed_int(0),
             NtpTimestamp::from_fixed_int(100),
+            tokio::time::Instant::now(),
         );
         assert!(actions.next().is_none());
         assert!(source.remote_min_poll_interval >= old_remote_interval);
@@ -1401,8 +2655,12 @@ mod test {
             // Kill the reference timestamp
             response[16] = 0;
 
-            let actions =
-                source.handle_incoming(&response, NtpTimestamp::default(), NtpTimestamp::default());
+            let actions = source.handle_incoming(
+                &response,
+                NtpTimestamp::default(),
+                NtpTimestamp::default(),
+                tokio::time::Instant::now(),
+            );
             for action in actions {
                 assert!(!matches!(
                     action,
@@ -1466,8 +2724,12 @@ mod test {
             .serialize_without_encryption_vec(Some(poll_len))
             .unwrap();
 
-        let actions =
-            source.handle_incoming(&response, NtpTimestamp::default(), NtpTimestamp::default());
+        let actions = source.handle_incoming(
+            &response,
+            NtpTimestamp::default(),
+            NtpTimestamp::default(),
+            tokio::time::Instant::now(),
+        );
         for action in actions {
             assert!(!matches!(
                 action,
@@ -1506,8 +2768,12 @@ mod test {
             .serialize_without_encryption_vec(Some(poll_len))
             .unwrap();
 
-        let actions =
-            source.handle_incoming(&response, NtpTimestamp::default(), NtpTimestamp::default());
+        let actions = source.handle_incoming(
+            &response,
+            NtpTimestamp::default(),
+            NtpTimestamp::default(),
+            tokio::time::Instant::now(),
+        );
         for action in actions {
             assert!(!matches!(
                 action,
@@ -1519,6 +2785,321 @@ mod test {
         assert!(matches!(source.protocol_version, ProtocolVersion::V5));
     }
 
+    fn poll_transmit_timestamp(source: &mut NtpSource<impl SourceController>) -> NtpTimestamp {
+        let mut outgoingbuf = None;
+        for action in source.handle_timer() {
+            if let NtpSourceAction::Send(buf) = action {
+                outgoingbuf = Some(buf);
+            }
+        }
+        NtpPacket::deserialize(&outgoingbuf.unwrap(), &NoCipher)
+            .unwrap()
+            .0
+            .transmit_timestamp()
+    }
+
+    #[test]
+    fn test_rate_kiss_backoff_and_recovery() {
+        let mut source = NtpSource::test_ntp_source(NoopController);
+        source.protocol_version = ProtocolVersion::V4;
+        let limits = source.source_config.poll_interval_limits;
+        let initial_poll = source.current_poll_interval();
+
+        let mut packet = NtpPacket::test();
+        packet.set_reference_id(ReferenceId::KISS_RATE);
+        packet.set_origin_timestamp(poll_transmit_timestamp(&mut source));
+        packet.set_mode(NtpAssociationMode::Server);
+        let mut actions = source.handle_incoming(
+            &packet.serialize_without_encryption_vec(None).unwrap(),
+            NtpTimestamp::from_fixed_int(0),
+            NtpTimestamp::from_fixed_int(100),
+            tokio::time::Instant::now(),
+        );
+        assert!(actions.next().is_none());
+        assert!(source.current_poll_interval() > initial_poll);
+        assert!(source.current_poll_interval() <= limits.max);
+
+        // Polling is suppressed during the cooldown
+        let actions: Vec<_> = source.handle_timer().collect();
+        assert!(matches!(actions.as_slice(), [NtpSourceAction::SetTimer(_)]));
+
+        // Once the cooldown has passed, the server stops rate limiting us
+        source.rate_cooldown_until = Some(tokio::time::Instant::now());
+        for _ in 0..RATE_RECOVERY_RESPONSES {
+            assert!(source.current_poll_interval() > initial_poll);
+
+            let mut packet = NtpPacket::test();
+            packet.set_stratum(1);
+            packet.set_mode(NtpAssociationMode::Server);
+            packet.set_origin_timestamp(poll_transmit_timestamp(&mut source));
+            packet.set_receive_timestamp(NtpTimestamp::from_fixed_int(100));
+            packet.set_transmit_timestamp(NtpTimestamp::from_fixed_int(200));
+            for action in source.handle_incoming(
+                &packet.serialize_without_encryption_vec(None).unwrap(),
+                NtpTimestamp::from_fixed_int(0),
+                NtpTimestamp::from_fixed_int(400),
+                tokio::time::Instant::now(),
+            ) {
+                assert!(!matches!(
+                    action,
+                    NtpSourceAction::Reset | NtpSourceAction::Demobilize
+                ));
+            }
+        }
+
+        assert_eq!(source.current_poll_interval(), initial_poll);
+        assert!(source.rate_recovery_responses.is_none());
+    }
+
+    // Records the last usability reported by the source
+    struct UsableController(Option<bool>);
+    impl SourceController for UsableController {
+        fn handle_measurement(&mut self, _: Measurement) {
+            // no action
+        }
+
+        fn set_usable(&mut self, usable: bool) {
+            self.0 = Some(usable);
+        }
+
+        fn desired_poll_interval(&self) -> PollInterval {
+            PollInterval::default()
+        }
+
+        fn observe(&self) -> crate::ObservableSourceTimedata {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn test_disabled_source() {
+        let mut source = NtpSource::test_ntp_source(UsableController(None));
+        source.protocol_version = ProtocolVersion::V4;
+        source.source_info.write().unwrap().local_stratum = 16;
+
+        let mut packet = NtpPacket::test();
+        packet.set_stratum(1);
+        packet.set_mode(NtpAssociationMode::Server);
+        packet.set_receive_timestamp(NtpTimestamp::from_fixed_int(100));
+        packet.set_transmit_timestamp(NtpTimestamp::from_fixed_int(200));
+        let mut respond = |source: &mut NtpSource<UsableController>| {
+            packet.set_origin_timestamp(poll_transmit_timestamp(source));
+            for _ in source.handle_incoming(
+                &packet.serialize_without_encryption_vec(None).unwrap(),
+                NtpTimestamp::from_fixed_int(0),
+                NtpTimestamp::from_fixed_int(400),
+                tokio::time::Instant::now(),
+            ) {}
+        };
+
+        respond(&mut source);
+        assert_eq!(source.controller.0, Some(true));
+
+        source.set_disabled(true);
+        assert_eq!(source.controller.0, Some(false));
+        respond(&mut source);
+        assert_eq!(source.controller.0, Some(false));
+
+        source.set_disabled(false);
+        respond(&mut source);
+        assert_eq!(source.controller.0, Some(true));
+    }
+
+    #[test]
+    fn test_require_authenticated() {
+        for require_authenticated in [false, true] {
+            let mut source = NtpSource::test_ntp_source(UsableController(None));
+            source.protocol_version = ProtocolVersion::V4;
+            source.source_config.require_authenticated = require_authenticated;
+            let mut ntsdata = SourceNtsData {
+                cookies: CookieStash::default(),
+                c2s: Box::new(AesSivCmac256::new([0; 32].into())),
+                s2c: Box::new(AesSivCmac256::new([0; 32].into())),
+            };
+            ntsdata.cookies.store(vec![0; 32]);
+            source.nts = Some(Box::new(ntsdata));
+
+            let mut outgoingbuf = None;
+            for action in source.handle_timer() {
+                if let NtpSourceAction::Send(buf) = action {
+                    outgoingbuf = Some(buf);
+                }
+            }
+            let outgoingbuf = outgoingbuf.unwrap();
+            let c2s = AesSivCmac256::new([0; 32].into());
+            let outgoing = NtpPacket::deserialize(&outgoingbuf, &c2s).unwrap().0;
+            source.controller.0 = None;
+
+            // A reply to our request, but without any NTS authentication
+            let mut packet = NtpPacket::test();
+            packet.set_stratum(1);
+            packet.set_mode(NtpAssociationMode::Server);
+            packet.set_origin_timestamp(outgoing.transmit_timestamp());
+            packet.set_receive_timestamp(NtpTimestamp::from_fixed_int(100));
+            packet.set_transmit_timestamp(NtpTimestamp::from_fixed_int(200));
+            let mut actions = source.handle_incoming(
+                &packet.serialize_without_encryption_vec(None).unwrap(),
+                NtpTimestamp::from_fixed_int(0),
+                NtpTimestamp::from_fixed_int(400),
+                tokio::time::Instant::now(),
+            );
+            assert!(actions.next().is_none());
+
+            if require_authenticated {
+                assert_eq!(source.controller.0, Some(false));
+                assert!(source.authentication_failed);
+            } else {
+                assert_eq!(source.controller.0, None);
+                assert!(!source.authentication_failed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mismatched_response_poll() {
+        let mut source = NtpSource::test_ntp_source(NoopController);
+        source.protocol_version = ProtocolVersion::V4;
+
+        let mut outgoingbuf = None;
+        for action in source.handle_timer() {
+            if let NtpSourceAction::Send(buf) = action {
+                outgoingbuf = Some(buf);
+            }
+        }
+        let outgoing = NtpPacket::deserialize(&outgoingbuf.unwrap(), &NoCipher)
+            .unwrap()
+            .0;
+        let intended_poll = source.current_poll_interval();
+        let remote_min_poll_interval = source.remote_min_poll_interval;
+
+        let mut packet = NtpPacket::test();
+        packet.set_stratum(1);
+        packet.set_mode(NtpAssociationMode::Server);
+        packet.set_origin_timestamp(outgoing.transmit_timestamp());
+        packet.set_receive_timestamp(NtpTimestamp::from_fixed_int(100));
+        packet.set_transmit_timestamp(NtpTimestamp::from_fixed_int(200));
+        packet.set_poll(PollIntervalLimits::default().max);
+
+        let actions = source.handle_incoming(
+            &packet.serialize_without_encryption_vec(None).unwrap(),
+            NtpTimestamp::from_fixed_int(0),
+            NtpTimestamp::from_fixed_int(400),
+            tokio::time::Instant::now(),
+        );
+        for action in actions {
+            assert!(!matches!(
+                action,
+                NtpSourceAction::Reset | NtpSourceAction::Demobilize
+            ));
+        }
+
+        // The response was accepted, but the echoed poll was not used
+        assert!(source.current_request_identifier.is_none());
+        assert_eq!(source.remote_min_poll_interval, remote_min_poll_interval);
+        assert_eq!(source.current_poll_interval(), intended_poll);
+    }
+
+    #[test]
+    fn test_ignore_server_poll_v5() {
+        for ignore_server_poll in [false, true] {
+            let mut source = NtpSource::test_ntp_source(NoopController);
+            source.protocol_version = ProtocolVersion::V5;
+            source.source_config.ignore_server_poll = ignore_server_poll;
+            let clock = TestClock {};
+
+            let mut outgoingbuf = None;
+            for action in source.handle_timer() {
+                if let NtpSourceAction::Send(buf) = action {
+                    outgoingbuf = Some(buf);
+                }
+            }
+            let poll = outgoingbuf.unwrap();
+            let poll_len = poll.len();
+            let (poll, _) = NtpPacket::deserialize(&poll, &NoCipher).unwrap();
+            let intended_poll = source.current_poll_interval();
+
+            let mut response = NtpPacket::timestamp_response(
+                NtpServerInfo::default(),
+                poll,
+                NtpTimestamp::default(),
+                &clock,
+            );
+            response.set_poll(PollIntervalLimits::default().max);
+            let response = response
+                .serialize_without_encryption_vec(Some(poll_len))
+                .unwrap();
+
+            let actions = source.handle_incoming(
+                &response,
+                NtpTimestamp::default(),
+                NtpTimestamp::default(),
+                tokio::time::Instant::now(),
+            );
+            for action in actions {
+                assert!(!matches!(
+                    action,
+                    NtpSourceAction::Demobilize | NtpSourceAction::Reset
+                ));
+            }
+
+            assert!(source.current_request_identifier.is_none());
+            if ignore_server_poll {
+                assert_eq!(source.current_poll_interval(), intended_poll);
+            } else {
+                assert_eq!(
+                    source.current_poll_interval(),
+                    PollIntervalLimits::default().max
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_observe_timescale_v5() {
+        for timescale in [
+            NtpTimescale::Utc,
+            NtpTimescale::Tai,
+            NtpTimescale::Ut1,
+            NtpTimescale::LeapSmearedUtc,
+            NtpTimescale::Unknown(42),
+        ] {
+            let mut source = NtpSource::test_ntp_source(NoopController);
+            source.protocol_version = ProtocolVersion::V5;
+            let clock = TestClock {};
+            assert_eq!(source.observe("test".into(), ClockId(1)).timescale, None);
+
+            let mut outgoingbuf = None;
+            for action in source.handle_timer() {
+                if let NtpSourceAction::Send(buf) = action {
+                    outgoingbuf = Some(buf);
+                }
+            }
+            let poll = outgoingbuf.unwrap();
+            let poll_len = poll.len();
+            let (poll, _) = NtpPacket::deserialize(&poll, &NoCipher).unwrap();
+
+            let mut response = NtpPacket::timestamp_response(
+                NtpServerInfo::default(),
+                poll,
+                NtpTimestamp::default(),
+                &clock,
+            );
+            response.set_timescale(timescale);
+            let response = response
+                .serialize_without_encryption_vec(Some(poll_len))
+                .unwrap();
+            let _ = source.handle_incoming(
+                &response,
+                NtpTimestamp::default(),
+                NtpTimestamp::default(),
+                tokio::time::Instant::now(),
+            );
+
+            let observed = source.observe("test".into(), ClockId(1));
+            assert_eq!(observed.timescale, Some(timescale));
+        }
+    }
+
     #[test]
     fn upgrade_state_machine_does_fallback_after_upgrade() {
         let mut source = NtpSource::test_ntp_source(NoopController);
@@ -1557,8 +3138,12 @@ mod test {
             .serialize_without_encryption_vec(Some(poll_len))
             .unwrap();
 
-        let actions =
-            source.handle_incoming(&response, NtpTimestamp::default(), NtpTimestamp::default());
+        let actions = source.handle_incoming(
+            &response,
+            NtpTimestamp::default(),
+            NtpTimestamp::default(),
+            tokio::time::Instant::now(),
+        );
         for action in actions {
             assert!(!matches!(
                 action,
@@ -1649,6 +3234,7 @@ mod test {
                 &resp_bytes,
                 NtpTimestamp::default(),
                 NtpTimestamp::default(),
+                tokio::time::Instant::now(),
             );
             for action in actions {
                 assert!(!matches!(
@@ -10,6 +10,7 @@ use crate::{
     ClockId, KeySet, NtpSourceSnapshot, NtpTimestamp, Server, ServerConfig, SourceController,
 };
 use crate::{
+    broadcast::BroadcastClient,
     config::{SourceConfig, SynchronizationConfig},
     identifiers::ReferenceId,
     packet::NtpLeapIndicator,
@@ -17,6 +18,22 @@ use crate::{
     time_types::NtpDuration,
 };
 
+/// State of the clock controller with respect to the time sources
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClockState {
+    /// There is no agreement yet on the current time
+    #[default]
+    Unsynchronized,
+    /// The clock is being slewed towards the time of the sources
+    Steering,
+    /// The clock is within the uncertainty of the sources
+    Synchronized,
+    /// Offset corrections are held after a step exceeded the panic threshold,
+    /// until the offset is back below the recovery bound
+    PanicHold,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub struct TimeSnapshot {
     /// Precision of the local clock
@@ -39,6 +56,9 @@ pub struct TimeSnapshot {
     pub accumulated_steps: NtpDuration,
     /// Crossing this amount of stepping will cause a Panic
     pub accumulated_steps_threshold: Option<NtpDuration>,
+    /// Current state of the clock controller
+    #[serde(default)]
+    pub clock_state: ClockState,
 }
 
 impl TimeSnapshot {
@@ -68,6 +88,7 @@ impl Default for TimeSnapshot {
             leap_indicator: NtpLeapIndicator::Unknown,
             accumulated_steps: NtpDuration::ZERO,
             accumulated_steps_threshold: None,
+            clock_state: ClockState::Unsynchronized,
         }
     }
 }
@@ -80,20 +101,59 @@ pub struct SystemSnapshot {
     /// NTP specific data
     #[serde(flatten)]
     pub ntp_snapshot: NtpSnapshot,
+    /// Estimated error of the clock, as read back from the clock
+    #[serde(default)]
+    pub clock_estimated_error: Option<NtpDuration>,
+    /// Maximum error of the clock, as read back from the clock
+    #[serde(default)]
+    pub clock_maximum_error: Option<NtpDuration>,
+    /// Offset of TAI from UTC in seconds, as read back from the clock
+    #[serde(default)]
+    pub tai_offset: Option<i32>,
+    /// Counter that is incremented whenever the time or NTP data changes
+    #[serde(default)]
+    pub generation: u64,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+impl SystemSnapshot {
+    /// Replace the contents of this snapshot with `new`, incrementing the
+    /// generation only when the time or NTP data actually changed. The
+    /// clock error readbacks drift continuously and are not considered a
+    /// substantive change. Returns whether the generation was incremented.
+    pub fn update(&mut self, new: SystemSnapshot) -> bool {
+        let changed = self.time_snapshot != new.time_snapshot
+            || self.ntp_snapshot != new.ntp_snapshot
+            || self.tai_offset != new.tai_offset;
+
+        let generation = self.generation;
+        *self = new;
+        self.generation = if changed {
+            generation.wrapping_add(1)
+        } else {
+            generation
+        };
+
+        changed
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct NtpSnapshot {
     /// Log of the precision of the local clock
     pub stratum: u8,
     /// Reference ID of current primary time source
     pub reference_id: ReferenceId,
     /// Bloom filter that contains all currently used time sources
-    #[serde(skip)]
+    #[serde(default)]
     pub bloom_filter: BloomFilter,
 }
 
 impl NtpSnapshot {
+    /// Number of bits set in the NTPv5 loop detection bloom filter
+    pub fn bloom_filter_population(&self) -> u16 {
+        self.bloom_filter.count_ones()
+    }
+
     pub fn from_used_sources(
         local_stratum: u8,
         server_id: ServerId,
@@ -115,12 +175,22 @@ impl NtpSnapshot {
 
         let mut bloom_filter = BloomFilter::new();
         for source in used_sources {
-            if let SourceSnapshot::Ntp(source) = source {
-                if let Some(bf) = &source.bloom_filter {
-                    bloom_filter.add(bf);
-                } else if let ProtocolVersion::V5 = source.protocol_version {
-                    tracing::warn!("Using NTPv5 source without a bloom filter!");
+            match source {
+                SourceSnapshot::Ntp(source) => {
+                    if let Some(bf) = &source.bloom_filter {
+                        bloom_filter.add(bf);
+                    } else if let ProtocolVersion::V5 = source.protocol_version {
+                        tracing::warn!("Using NTPv5 source without a bloom filter!");
+                    }
                 }
+                // When synchronized to a local reference clock, the pulse
+                // determines the precise time, the reference clock only the
+                // seconds.
+                SourceSnapshot::External {
+                    source_id: ReferenceId::PPS,
+                    ..
+                } if stratum == 1 => reference_id = ReferenceId::PPS,
+                SourceSnapshot::External { .. } => {}
             }
         }
         bloom_filter.add_id(&server_id);
@@ -155,6 +225,10 @@ pub enum SourceType {
 pub struct NtpServerInfo {
     pub time_snapshot: TimeSnapshot,
     pub ntp_snapshot: NtpSnapshot,
+    /// Whether the servers are draining ahead of a shutdown, in which case
+    /// they advertise themselves as unsynchronized so that clients move to
+    /// other servers.
+    pub draining: bool,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -184,6 +258,7 @@ impl NtpManager {
         let mut server_info = NtpServerInfo {
             time_snapshot: TimeSnapshot::default(),
             ntp_snapshot: NtpSnapshot::default(),
+            draining: false,
         };
         if synchronization_config.local_stratum == 1 {
             // We are a stratum 1 server so mark our selves synchronized.
@@ -227,6 +302,23 @@ impl NtpManager {
         )
     }
 
+    pub fn new_broadcast_client<Controller: SourceController>(
+        &self,
+        server: SocketAddr,
+        source_config: SourceConfig,
+        controller: Controller,
+        id: ClockId,
+    ) -> BroadcastClient<Controller> {
+        BroadcastClient::new(
+            server,
+            source_config,
+            controller,
+            id,
+            self.source_info.clone(),
+            self.source_snapshots.clone(),
+        )
+    }
+
     pub fn update_ip_list(&self, ip_list: Arc<[IpAddr]>) {
         self.source_info.write().unwrap().ip_list = ip_list;
     }
@@ -277,6 +369,12 @@ impl NtpManager {
     pub fn update_time_snapshot(&self, time_snapshot: TimeSnapshot) {
         self.server_info.write().unwrap().time_snapshot = time_snapshot;
     }
+
+    /// Start or stop draining the servers. While draining, servers keep
+    /// answering requests, but advertise themselves as unsynchronized.
+    pub fn set_draining(&self, draining: bool) {
+        self.server_info.write().unwrap().draining = draining;
+    }
 }
 
 #[cfg(test)]
@@ -311,6 +409,7 @@ mod tests {
                     reference_id: ReferenceId::NONE,
                     protocol_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
                     bloom_filter: None,
+                    expected_refid: None,
                 }),
                 SourceSnapshot::Ntp(NtpSourceSnapshot {
                     source_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
@@ -321,6 +420,7 @@ mod tests {
                     reference_id: ReferenceId::NONE,
                     protocol_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
                     bloom_filter: None,
+                    expected_refid: None,
                 }),
             ]
             .into_iter(),
@@ -329,4 +429,118 @@ mod tests {
         assert_eq!(ntps.stratum, 3);
         assert_eq!(ntps.reference_id, ReferenceId::KISS_DENY);
     }
+
+    #[test]
+    fn test_bloom_filter_population() {
+        let own_id = ServerId::default();
+        let ntps = NtpSnapshot::from_used_sources(16, own_id, std::iter::empty());
+        assert_eq!(ntps.bloom_filter_population(), 10);
+        assert!(ntps.bloom_filter.contains_id(&own_id));
+
+        let upstream_id = ServerId::default();
+        let mut upstream_filter = BloomFilter::new();
+        upstream_filter.add_id(&upstream_id);
+
+        let ntps = NtpSnapshot::from_used_sources(
+            16,
+            own_id,
+            vec![SourceSnapshot::Ntp(NtpSourceSnapshot {
+                source_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+                source_id: ReferenceId::KISS_DENY,
+                poll_interval: PollIntervalLimits::default().max,
+                reach: Reach::never(),
+                stratum: 2,
+                reference_id: ReferenceId::NONE,
+                protocol_version: ProtocolVersion::V5,
+                bloom_filter: Some(upstream_filter),
+                expected_refid: None,
+            })]
+            .into_iter(),
+        );
+
+        let mut expected = upstream_filter;
+        expected.add_id(&own_id);
+        assert_eq!(ntps.bloom_filter_population(), expected.count_ones());
+        assert!(ntps.bloom_filter_population() > 10);
+        assert!(ntps.bloom_filter.contains_id(&own_id));
+        assert!(ntps.bloom_filter.contains_id(&upstream_id));
+
+        // The filter is reported to observers
+        let snapshot = SystemSnapshot {
+            ntp_snapshot: ntps,
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let parsed: SystemSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.ntp_snapshot.bloom_filter, ntps.bloom_filter);
+        assert_eq!(
+            parsed.ntp_snapshot.bloom_filter_population(),
+            ntps.bloom_filter_population()
+        );
+    }
+
+    #[test]
+    fn test_reference_clock_with_pps() {
+        let manager = NtpManager::new(SynchronizationConfig::default(), Arc::new([]));
+
+        let ntps = manager.update_used_sources([(ClockId(1), SourceType::Sock)].into_iter());
+        assert_eq!(ntps.stratum, 1);
+        assert_eq!(ntps.reference_id, ReferenceId::SOCK);
+
+        let ntps = manager.update_used_sources(
+            [
+                (ClockId(1), SourceType::Sock),
+                (ClockId(2), SourceType::Pps),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(ntps.stratum, 1);
+        assert_eq!(ntps.reference_id, ReferenceId::PPS);
+        assert_eq!(manager.observe().reference_id, ReferenceId::PPS);
+    }
+
+    #[test]
+    fn test_snapshot_generation() {
+        let mut snapshot = SystemSnapshot::default();
+        assert_eq!(snapshot.generation, 0);
+
+        // Re-publishing identical data is not a change
+        assert!(!snapshot.update(SystemSnapshot::default()));
+        assert_eq!(snapshot.generation, 0);
+
+        // Neither is drift in the clock error readbacks
+        assert!(!snapshot.update(SystemSnapshot {
+            clock_estimated_error: Some(NtpDuration::from_seconds(0.001)),
+            clock_maximum_error: Some(NtpDuration::from_seconds(0.002)),
+            ..Default::default()
+        }));
+        assert_eq!(snapshot.generation, 0);
+        assert_eq!(
+            snapshot.clock_estimated_error,
+            Some(NtpDuration::from_seconds(0.001))
+        );
+
+        // A new time snapshot is
+        let time_snapshot = TimeSnapshot {
+            leap_indicator: NtpLeapIndicator::NoWarning,
+            ..Default::default()
+        };
+        assert!(snapshot.update(SystemSnapshot {
+            time_snapshot,
+            ..Default::default()
+        }));
+        assert_eq!(snapshot.generation, 1);
+
+        // As is a change in the NTP data
+        assert!(snapshot.update(SystemSnapshot {
+            time_snapshot,
+            ntp_snapshot: NtpSnapshot {
+                stratum: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        }));
+        assert_eq!(snapshot.generation, 2);
+        assert_eq!(snapshot.ntp_snapshot.stratum, 2);
+    }
 }
@@ -49,6 +49,42 @@ impl NtpTimestamp {
         NtpTimestamp::from_bits(timestamp.to_be_bytes())
     }
 
+    /// Add a duration, or `None` if the result would cross an ntp era
+    /// boundary. Use this instead of `+`, which silently wraps into the next
+    /// era, when the result is meant to stay within the era of `self`.
+    pub fn checked_add_duration(self, rhs: NtpDuration) -> Option<NtpTimestamp> {
+        self.timestamp
+            .checked_add_signed(rhs.duration)
+            .map(|timestamp| NtpTimestamp { timestamp })
+    }
+
+    /// Subtract a duration, or `None` if the result would cross an ntp era
+    /// boundary. See [`NtpTimestamp::checked_add_duration`].
+    pub fn checked_sub_duration(self, rhs: NtpDuration) -> Option<NtpTimestamp> {
+        let timestamp = if rhs.duration >= 0 {
+            self.timestamp.checked_sub(rhs.duration.unsigned_abs())
+        } else {
+            self.timestamp.checked_add(rhs.duration.unsigned_abs())
+        };
+        timestamp.map(|timestamp| NtpTimestamp { timestamp })
+    }
+
+    /// Duration from `other` to `self`, interpreting both timestamps in the
+    /// eras that bring them closest together.
+    ///
+    /// Timestamps do not carry an era number, so the result is only correct
+    /// when the timestamps are less than 2^31 seconds (about 68 years) apart.
+    /// Beyond that window the nearest interpretation puts one of them in a
+    /// different era than it actually is in, and the sign of the result flips.
+    /// This is the same as `self - other`.
+    pub fn difference(self, other: NtpTimestamp) -> NtpDuration {
+        // A wrapping subtraction reinterpreted as a signed integer gives the
+        // difference with the smallest norm over all choices of era
+        NtpDuration {
+            duration: self.timestamp.wrapping_sub(other.timestamp) as i64,
+        }
+    }
+
     pub fn is_before(self, other: NtpTimestamp) -> bool {
         // Around an era change, self can be near the maximum value
         // for NtpTimestamp and other near the minimum, and that must
@@ -69,6 +105,15 @@ impl NtpTimestamp {
         self
     }
 
+    /// Create an NTP timestamp where the seconds part is rounded down to a
+    /// multiple of `interval` seconds and the fractional part is set to zero
+    pub fn truncated_to_interval(mut self, interval: u32) -> NtpTimestamp {
+        let seconds = (self.timestamp >> 32) as u32;
+        let seconds = seconds - seconds % interval.max(1);
+        self.timestamp = u64::from(seconds) << 32;
+        self
+    }
+
     #[cfg(test)]
     pub(crate) const fn from_fixed_int(timestamp: u64) -> NtpTimestamp {
         NtpTimestamp { timestamp }
@@ -114,14 +159,7 @@ impl Sub for NtpTimestamp {
     type Output = NtpDuration;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        // In order to properly deal with ntp era changes, timestamps
-        // need to roll over. Doing a wrapping subtract to a signed
-        // integer type always gives us the result as if the eras of
-        // the timestamps were chosen to minimize the norm of the
-        // difference, which is the desired behaviour
-        NtpDuration {
-            duration: self.timestamp.wrapping_sub(rhs.timestamp) as i64,
-        }
+        self.difference(rhs)
     }
 }
 
@@ -164,6 +202,101 @@ impl std::fmt::Debug for NtpDuration {
     }
 }
 
+/// Units accepted when parsing a duration, with their length in seconds
+const DURATION_UNITS: [(&str, f64); 8] = [
+    ("ns", 1e-9),
+    ("us", 1e-6),
+    ("µs", 1e-6),
+    ("ms", 1e-3),
+    ("s", 1.0),
+    ("min", 60.0),
+    ("h", 3600.0),
+    ("", 1.0),
+];
+
+/// Displays the duration in the largest unit in which it is at least 1, with
+/// at most three decimals, e.g. `1.5ms` or `-2.3s`. Nanoseconds are shown
+/// without decimals, as that is below the resolution of the duration.
+impl std::fmt::Display for NtpDuration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.duration == 0 {
+            return write!(f, "0s");
+        }
+
+        let seconds = self.to_seconds();
+        let (unit, scale) = match seconds.abs() {
+            s if s < 1e-6 => ("ns", 1e-9),
+            s if s < 1e-3 => ("us", 1e-6),
+            s if s < 1.0 => ("ms", 1e-3),
+            s if s < 60.0 => ("s", 1.0),
+            s if s < 3600.0 => ("min", 60.0),
+            _ => ("h", 3600.0),
+        };
+
+        let decimals = if unit == "ns" { 0 } else { 3 };
+        let mut value = format!("{:.*}", decimals, seconds / scale);
+        if value.contains('.') {
+            let trimmed = value.trim_end_matches('0').trim_end_matches('.').len();
+            value.truncate(trimmed);
+        }
+        // Avoid printing rounding residue as "-0"
+        if value == "-0" {
+            value = String::from("0");
+        }
+        write!(f, "{value}{unit}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NtpDurationParseError {
+    Number,
+    Unit,
+    NotFinite,
+}
+
+impl std::error::Error for NtpDurationParseError {}
+
+impl std::fmt::Display for NtpDurationParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Number => write!(f, "Invalid number in duration"),
+            Self::Unit => write!(
+                f,
+                "Unknown unit in duration, expected one of ns, us, ms, s, min or h"
+            ),
+            Self::NotFinite => write!(f, "Duration must be a finite number"),
+        }
+    }
+}
+
+/// Parses a number with an optional unit (`ns`, `us`, `ms`, `s`, `min` or
+/// `h`), without a unit the number is interpreted as seconds
+impl std::str::FromStr for NtpDuration {
+    type Err = NtpDurationParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let number = s.trim_end_matches(|c: char| c.is_alphabetic());
+        let unit = &s[number.len()..];
+
+        let (_, scale) = DURATION_UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .ok_or(NtpDurationParseError::Unit)?;
+        let value: f64 = number
+            .trim_end()
+            .parse()
+            .map_err(|_| NtpDurationParseError::Number)?;
+
+        let seconds = value * scale;
+        if !seconds.is_finite() {
+            return Err(NtpDurationParseError::NotFinite);
+        }
+
+        Ok(NtpDuration::from_seconds(seconds))
+    }
+}
+
 impl NtpDuration {
     pub const ZERO: Self = Self { duration: 0 };
     pub const MAX: Self = Self { duration: i64::MAX };
@@ -304,6 +437,36 @@ impl NtpDuration {
         NtpDuration::from_bits(timestamp.to_be_bytes())
     }
 
+    /// Convert a [`Duration`] without going through floating point, rounding
+    /// to the nearest representable value. Durations that are too long to
+    /// be represented saturate to [`NtpDuration::MAX`].
+    pub fn from_std(duration: Duration) -> Self {
+        let Ok(seconds) = i32::try_from(duration.as_secs()) else {
+            return Self::MAX;
+        };
+        // NTP uses 1/2^32 sec as its unit of fractional time, round to nearest
+        let fraction = ((u64::from(duration.subsec_nanos()) << 32) + 500_000_000) / 1_000_000_000;
+
+        Self {
+            duration: (i64::from(seconds) << 32) + fraction as i64,
+        }
+    }
+
+    /// Convert to a [`Duration`] without going through floating point,
+    /// rounding to the nearest nanosecond. Returns `None` for negative
+    /// durations.
+    pub fn to_std(self) -> Option<Duration> {
+        if self.duration < 0 {
+            return None;
+        }
+
+        let seconds = (self.duration >> 32) as u64;
+        let nanos = (((self.duration & 0xFFFF_FFFF) as u64 * 1_000_000_000) + (1 << 31)) >> 32;
+
+        // Duration::new carries nanos rounded up to a full second
+        Some(Duration::new(seconds, nanos as u32))
+    }
+
     #[cfg(test)]
     pub(crate) const fn from_fixed_int(duration: i64) -> NtpDuration {
         NtpDuration { duration }
@@ -325,16 +488,40 @@ impl<'de> Deserialize<'de> for NtpDuration {
     where
         D: serde::Deserializer<'de>,
     {
-        let seconds: f64 = Deserialize::deserialize(deserializer)?;
+        struct DurationVisitor;
+
+        impl serde::de::Visitor<'_> for DurationVisitor {
+            type Value = NtpDuration;
 
-        if seconds.is_nan() || seconds.is_infinite() {
-            return Err(serde::de::Error::invalid_value(
-                Unexpected::Float(seconds),
-                &"a valid number",
-            ));
+            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("a number of seconds or a duration with unit")
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, seconds: f64) -> Result<Self::Value, E> {
+                if seconds.is_nan() || seconds.is_infinite() {
+                    return Err(E::invalid_value(
+                        Unexpected::Float(seconds),
+                        &"a valid number",
+                    ));
+                }
+
+                Ok(NtpDuration::from_seconds(seconds))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, seconds: i64) -> Result<Self::Value, E> {
+                self.visit_f64(seconds as f64)
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, seconds: u64) -> Result<Self::Value, E> {
+                self.visit_f64(seconds as f64)
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                value.parse().map_err(E::custom)
+            }
         }
 
-        Ok(NtpDuration::from_seconds(seconds))
+        deserializer.deserialize_any(DurationVisitor)
     }
 }
 
@@ -532,7 +719,7 @@ impl PollInterval {
 
     #[must_use]
     pub fn inc(self, limits: PollIntervalLimits) -> Self {
-        Self(self.0 + 1).min(limits.max)
+        Self(self.0.saturating_add(1)).min(limits.max)
     }
 
     #[must_use]
@@ -542,7 +729,7 @@ impl PollInterval {
 
     #[must_use]
     pub fn dec(self, limits: PollIntervalLimits) -> Self {
-        Self(self.0 - 1).max(limits.min)
+        Self(self.0.saturating_sub(1)).max(limits.min)
     }
 
     pub const fn as_log(self) -> i8 {
@@ -651,6 +838,82 @@ mod tests {
         assert_eq!(a, NtpTimestamp::from_fixed_int(1));
     }
 
+    #[test]
+    fn test_timestamp_checked_duration_math() {
+        let a = NtpTimestamp::from_fixed_int(5);
+        let b = NtpDuration::from_fixed_int(2);
+        assert_eq!(
+            a.checked_add_duration(b),
+            Some(NtpTimestamp::from_fixed_int(7))
+        );
+        assert_eq!(
+            a.checked_sub_duration(b),
+            Some(NtpTimestamp::from_fixed_int(3))
+        );
+        assert_eq!(
+            a.checked_add_duration(-b),
+            Some(NtpTimestamp::from_fixed_int(3))
+        );
+        assert_eq!(
+            a.checked_sub_duration(-b),
+            Some(NtpTimestamp::from_fixed_int(7))
+        );
+
+        // Crossing the boundary from era 0 into era 1
+        let end_of_era = NtpTimestamp::from_fixed_int(u64::MAX - 1);
+        assert_eq!(
+            end_of_era.checked_add_duration(NtpDuration::from_fixed_int(1)),
+            Some(NtpTimestamp::from_fixed_int(u64::MAX))
+        );
+        assert_eq!(end_of_era.checked_add_duration(b), None);
+        assert_eq!(end_of_era.checked_sub_duration(-b), None);
+
+        // Crossing the boundary back from era 1 into era 0
+        let start_of_era = NtpTimestamp::from_fixed_int(1);
+        assert_eq!(start_of_era.checked_sub_duration(b), None);
+        assert_eq!(start_of_era.checked_add_duration(-b), None);
+
+        // The most negative duration cannot be negated, but can be subtracted
+        let d = NtpDuration::from_fixed_int(i64::MIN);
+        assert_eq!(
+            NtpTimestamp::from_fixed_int(0).checked_sub_duration(d),
+            Some(NtpTimestamp::from_fixed_int(1 << 63))
+        );
+        assert_eq!(
+            NtpTimestamp::from_fixed_int(1 << 63).checked_add_duration(d),
+            Some(NtpTimestamp::from_fixed_int(0))
+        );
+    }
+
+    #[test]
+    fn test_timestamp_difference_across_era() {
+        let year = 365 * 24 * 3600;
+        let end_of_era_0 = NtpTimestamp::from_seconds_nanos_since_ntp_era(u32::MAX, 0);
+        let start_of_era_1 = NtpTimestamp::from_seconds_nanos_since_ntp_era(1, 0);
+        assert_eq!(
+            start_of_era_1.difference(end_of_era_0),
+            NtpDuration::from_seconds(2.0)
+        );
+        assert_eq!(
+            end_of_era_0.difference(start_of_era_1),
+            NtpDuration::from_seconds(-2.0)
+        );
+
+        // Timestamps a decade apart on either side of the boundary
+        let late_era_0 = NtpTimestamp::from_seconds_nanos_since_ntp_era(u32::MAX - 5 * year, 0);
+        let early_era_1 = NtpTimestamp::from_seconds_nanos_since_ntp_era(5 * year, 0);
+        assert_eq!(
+            early_era_1.difference(late_era_0),
+            NtpDuration::from_seconds(f64::from(10 * year + 1))
+        );
+        assert_eq!(early_era_1.difference(late_era_0), early_era_1 - late_era_0);
+        assert!(late_era_0.is_before(early_era_1));
+
+        // Beyond 68 years the nearest interpretation is in the other direction
+        let far_era_1 = NtpTimestamp::from_seconds_nanos_since_ntp_era(70 * year, 0);
+        assert!(far_era_1.difference(late_era_0) < NtpDuration::ZERO);
+    }
+
     #[test]
     fn test_timestamp_from_seconds_nanos() {
         assert_eq!(
@@ -671,6 +934,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_timestamp_truncate_to_interval() {
+        let ts = NtpTimestamp::from_seconds_nanos_since_ntp_era(1000, 500_000_000);
+        assert_eq!(
+            ts.truncated_to_interval(300),
+            NtpTimestamp::from_seconds_nanos_since_ntp_era(900, 0),
+        );
+        assert_eq!(
+            ts.truncated_to_interval(1),
+            NtpTimestamp::from_seconds_nanos_since_ntp_era(1000, 0),
+        );
+        // An interval of zero is treated as one second
+        assert_eq!(ts.truncated_to_interval(0), ts.truncated_to_interval(1));
+        // Powers of two match truncating the lower bits
+        assert_eq!(ts.truncated_to_interval(128), ts.truncated_second_bits(7));
+    }
+
     #[test]
     fn test_timestamp_duration_math() {
         let mut a = NtpTimestamp::from_fixed_int(5);
@@ -752,6 +1032,71 @@ mod tests {
         assert_eq_epsilon!(NtpDuration::from_seconds(2.0).to_seconds(), 2.0, 1e-9);
     }
 
+    #[test]
+    fn duration_display() {
+        let display = |seconds: f64| NtpDuration::from_seconds(seconds).to_string();
+        assert_eq!(display(0.0), "0s");
+        assert_eq!(display(12e-9), "12ns");
+        assert_eq!(display(3.25e-6), "3.25us");
+        assert_eq!(display(0.0015), "1.5ms");
+        assert_eq!(display(-0.0015), "-1.5ms");
+        assert_eq!(display(2.3), "2.3s");
+        assert_eq!(display(90.0), "1.5min");
+        assert_eq!(display(7200.0), "2h");
+    }
+
+    #[test]
+    fn duration_display_from_str_roundtrip() {
+        for text in [
+            "5ns", "250ns", "1.5us", "999us", "1.5ms", "-20ms", "2.3s", "59s", "1.5min", "30min",
+            "2h", "-12.5h",
+        ] {
+            let duration: NtpDuration = text.parse().unwrap();
+            assert_eq!(duration.to_string(), text);
+        }
+
+        for seconds in [
+            3e-9, 4.2e-8, 7e-6, 0.0123, 0.5, 42.0, 600.0, 3600.0, 86400.0,
+        ] {
+            for seconds in [seconds, -seconds] {
+                let duration = NtpDuration::from_seconds(seconds);
+                let parsed: NtpDuration = duration.to_string().parse().unwrap();
+                assert_eq_epsilon!(parsed.to_seconds(), seconds, seconds.abs() * 1e-3 + 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn duration_from_str() {
+        let parse = |s: &str| s.parse::<NtpDuration>();
+        assert_eq!(parse("1.5"), Ok(NtpDuration::from_seconds(1.5)));
+        assert_eq!(parse(" 1.5 ms "), Ok(NtpDuration::from_seconds(0.0015)));
+        assert_eq!(parse("10µs"), Ok(NtpDuration::from_seconds(10e-6)));
+        assert_eq!(parse("1e3ms"), Ok(NtpDuration::from_seconds(1.0)));
+
+        assert_eq!(parse("1.5 days"), Err(NtpDurationParseError::Unit));
+        assert_eq!(parse("ms"), Err(NtpDurationParseError::Number));
+        assert_eq!(parse(""), Err(NtpDurationParseError::Number));
+        assert!(parse("inf").is_err());
+        assert!(parse("-inf").is_err());
+        assert!(parse("NaN").is_err());
+        assert_eq!(parse("1e400"), Err(NtpDurationParseError::NotFinite));
+        assert_eq!(parse("1e305h"), Err(NtpDurationParseError::NotFinite));
+    }
+
+    #[test]
+    fn duration_deserialize() {
+        let parse = |s: &str| serde_json::from_str::<NtpDuration>(s);
+        assert_eq!(parse("1.5").unwrap(), NtpDuration::from_seconds(1.5));
+        assert_eq!(parse("2").unwrap(), NtpDuration::from_seconds(2.0));
+        assert_eq!(
+            parse("\"1.5ms\"").unwrap(),
+            NtpDuration::from_seconds(0.0015)
+        );
+        assert!(parse("\"inf\"").is_err());
+        assert!(parse("\"1 fortnight\"").is_err());
+    }
+
     #[test]
     fn duration_from_exponent() {
         assert_eq_epsilon!(NtpDuration::from_exponent(0).to_seconds(), 1.0, 1e-9);
@@ -795,6 +1140,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn duration_std_sub_nanosecond_rounding() {
+        // 1/2^32 seconds is about 0.23ns, 3/2^32 seconds about 0.70ns
+        assert_eq!(
+            NtpDuration::from_fixed_int(1).to_std(),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            NtpDuration::from_fixed_int(3).to_std(),
+            Some(Duration::from_nanos(1))
+        );
+        // just below a full second rounds up and carries into the seconds
+        assert_eq!(
+            NtpDuration::from_fixed_int(0xFFFF_FFFF).to_std(),
+            Some(Duration::from_secs(1))
+        );
+
+        assert_eq!(
+            NtpDuration::from_std(Duration::from_nanos(1)),
+            NtpDuration::from_fixed_int(4)
+        );
+
+        // the fixed point representation is finer than a nanosecond, so
+        // every nanosecond value survives a roundtrip
+        for nanos in [0, 1, 2, 499_999_999, 500_000_000, 999_999_999] {
+            let duration = Duration::new(7, nanos);
+            assert_eq!(NtpDuration::from_std(duration).to_std(), Some(duration));
+        }
+    }
+
+    #[test]
+    fn duration_std_precision_near_seconds_boundary() {
+        let duration = Duration::new(i32::MAX as u64, 1);
+
+        assert_eq!(NtpDuration::from_std(duration).to_std(), Some(duration));
+
+        // going through f64 loses the nanosecond at this magnitude
+        assert_ne!(
+            NtpDuration::from_seconds(duration.as_secs_f64()).to_std(),
+            Some(duration)
+        );
+    }
+
+    #[test]
+    fn duration_std_saturates_and_rejects_negative() {
+        assert_eq!(
+            NtpDuration::from_std(Duration::from_secs(1 << 40)),
+            NtpDuration::MAX
+        );
+        assert_eq!(NtpDuration::from_seconds(-1.0).to_std(), None);
+    }
+
     #[test]
     fn poll_interval_clamps() {
         let mut interval = PollInterval::default();
@@ -813,6 +1210,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn poll_interval_saturates() {
+        let limits = PollIntervalLimits {
+            min: PollInterval(i8::MIN),
+            max: PollInterval(i8::MAX),
+        };
+        assert_eq!(PollInterval(i8::MAX).inc(limits), PollInterval(i8::MAX));
+        assert_eq!(PollInterval(i8::MIN).dec(limits), PollInterval(i8::MIN));
+    }
+
     #[test]
     fn poll_interval_to_duration() {
         assert_eq!(
@@ -6,22 +6,32 @@
 # Because this script generate keys without passwords set, they should only be used in a development setting.
 
 if [ -z "$1" ]; then
-	echo "usage: gen-cert.sh name-of-server [ca-name] [filename]"
+	echo "usage: gen-cert.sh name-of-server [ca-name] [filename] [key-type]"
 	echo
 	echo "This will generate a name-of-server.key, name-of-server.pem and name-of-server.chain.pem file"
 	echo "containing the private key, public certificate, and full certificate chain (respectively)"
 	echo
 	echo "The second argument denotes the name of the CA be used (found in the files ca-name.key and ca-name.pem)"
 	echo "If this is omitted, the name 'testca' will be used."
+	echo
+	echo "The fourth argument denotes the type of key to generate: rsa, p256, p384 or ed25519"
+	echo "If this is omitted, an RSA key will be generated."
 	exit
 fi
 
 NAME="${1:-ntpd-rs.test}"
 CA="${2:-testca}"
 FILENAME="${3:-$NAME}"
+KEYTYPE="${4:-rsa}"
 
 # generate a key
-openssl genrsa -out "$FILENAME".key 2048
+case "$KEYTYPE" in
+	rsa) openssl genrsa -out "$FILENAME".key 2048 ;;
+	p256) openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 -out "$FILENAME".key ;;
+	p384) openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-384 -out "$FILENAME".key ;;
+	ed25519) openssl genpkey -algorithm ED25519 -out "$FILENAME".key ;;
+	*) echo "unknown key type: $KEYTYPE"; exit 1 ;;
+esac
 
 # generate a certificate signing request
 openssl req -batch -new -key "$FILENAME".key -out "$FILENAME".csr
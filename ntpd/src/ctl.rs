@@ -4,9 +4,15 @@ use std::{
 };
 
 use crate::{
-    daemon::{Config, ObservableState, config::CliArg, tracing::LogLevel},
+    daemon::{
+        Config, ObservableState,
+        config::{CliArg, PermissionCheck},
+        control::{ControlRequest, ControlResponse},
+        tracing::LogLevel,
+    },
     force_sync,
 };
+use ntp_proto::ClockId;
 use tokio::runtime::Builder;
 use tracing_subscriber::util::SubscriberInitExt;
 
@@ -14,6 +20,7 @@ const USAGE_MSG: &str = "\
 usage: ntp-ctl validate [-c PATH]
        ntp-ctl status [-f FORMAT] [-c PATH]
        ntp-ctl force-sync [-c PATH]
+       ntp-ctl poll SOURCE_ID [-c PATH]
        ntp-ctl -h | ntp-ctl -v";
 
 const DESCRIPTOR: &str = "ntp-ctl - ntp-daemon monitoring";
@@ -43,6 +50,7 @@ pub enum NtpCtlAction {
     Validate,
     Status,
     ForceSync,
+    Poll,
 }
 
 #[derive(Debug, Default)]
@@ -54,6 +62,7 @@ pub(crate) struct NtpCtlOptions {
     validate: bool,
     status: bool,
     force_sync: bool,
+    poll: Option<ClockId>,
     action: NtpCtlAction,
 }
 
@@ -103,6 +112,18 @@ impl NtpCtlOptions {
                     }
                 },
                 CliArg::Rest(rest) => {
+                    let mut rest = rest.into_iter();
+                    if rest
+                        .as_slice()
+                        .first()
+                        .is_some_and(|command| command == "poll")
+                    {
+                        let source = rest.nth(1).ok_or("poll requires a source id")?;
+                        let source = source
+                            .parse()
+                            .map_err(|_| format!("invalid source id provided: {source}"))?;
+                        options.poll = Some(source);
+                    }
                     if rest.len() > 1 {
                         eprintln!("Warning: Too many commands provided.");
                     }
@@ -144,6 +165,8 @@ impl NtpCtlOptions {
             self.action = NtpCtlAction::Status;
         } else if self.force_sync {
             self.action = NtpCtlAction::ForceSync;
+        } else if self.poll.is_some() {
+            self.action = NtpCtlAction::Poll;
         } else {
             self.action = NtpCtlAction::Help;
         }
@@ -152,10 +175,8 @@ impl NtpCtlOptions {
 
 fn validate(config: Option<&Path>) -> ExitCode {
     // Late completion not needed, so ignore result.
-    crate::daemon::tracing::tracing_init(LogLevel::Info, None, true)
-        .0
-        .init();
-    match Config::from_args(config.as_ref(), vec![], vec![]) {
+    crate::daemon::tracing::stdout_tracing_init(LogLevel::Info, true).init();
+    match Config::from_args(config.as_ref(), vec![], vec![], PermissionCheck::Warn) {
         Ok(config) => {
             if config.check() {
                 eprintln!("Config looks good");
@@ -190,8 +211,41 @@ pub fn main() -> std::io::Result<ExitCode> {
         }
         NtpCtlAction::Validate => Ok(validate(options.config.as_deref())),
         NtpCtlAction::ForceSync => Ok(force_sync::force_sync(options.config.as_deref())),
+        NtpCtlAction::Poll => {
+            let config = Config::from_args(
+                options.config.as_ref(),
+                vec![],
+                vec![],
+                PermissionCheck::Warn,
+            );
+
+            if let Err(ref e) = config {
+                println!("Warning: Unable to load configuration file: {e}");
+            }
+
+            let config = config.unwrap_or_default();
+
+            let control = config
+                .observability
+                .control_path
+                .unwrap_or_else(|| PathBuf::from("/var/run/ntpd-rs/control"));
+
+            let Some(source) = options.poll else {
+                unreachable!("poll action requires a source id");
+            };
+
+            Builder::new_current_thread()
+                .enable_all()
+                .build()?
+                .block_on(poll_source(control, source))
+        }
         NtpCtlAction::Status => {
-            let config = Config::from_args(options.config.as_ref(), vec![], vec![]);
+            let config = Config::from_args(
+                options.config.as_ref(),
+                vec![],
+                vec![],
+                PermissionCheck::Warn,
+            );
 
             if let Err(ref e) = config {
                 println!("Warning: Unable to load configuration file: {e}");
@@ -259,6 +313,58 @@ async fn print_state(print: Format, observe_socket: PathBuf) -> Result<ExitCode,
     Ok(ExitCode::SUCCESS)
 }
 
+async fn poll_source(control_socket: PathBuf, source: ClockId) -> Result<ExitCode, std::io::Error> {
+    let mut stream = match tokio::net::UnixStream::connect(&control_socket).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            eprintln!("Could not open socket at {}: {e}", control_socket.display(),);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+
+    crate::daemon::sockets::write_json(&mut stream, &ControlRequest::Poll(source)).await?;
+
+    let mut msg = Vec::with_capacity(64);
+    let response =
+        match crate::daemon::sockets::read_json::<ControlResponse>(&mut stream, &mut msg).await {
+            Ok(response) => response,
+            Err(e) => {
+                eprintln!("Failed to read response from control socket: {e}");
+
+                return Ok(ExitCode::FAILURE);
+            }
+        };
+
+    match response {
+        ControlResponse::PollSent => {
+            println!("Poll sent to source {source}");
+            Ok(ExitCode::SUCCESS)
+        }
+        ControlResponse::RateLimited => {
+            eprintln!("Source {source} was polled too recently, try again later");
+            Ok(ExitCode::FAILURE)
+        }
+        ControlResponse::PollFailed => {
+            eprintln!("Poll to source {source} could not be sent");
+            Ok(ExitCode::FAILURE)
+        }
+        ControlResponse::UnknownSource => {
+            eprintln!("No NTP source with id {source}");
+            Ok(ExitCode::FAILURE)
+        }
+        ControlResponse::SourceDisabled
+        | ControlResponse::SourceEnabled
+        | ControlResponse::RequestFailed
+        | ControlResponse::SyncRequested
+        | ControlResponse::SyncRefused
+        | ControlResponse::NoConsensus
+        | ControlResponse::Draining => {
+            eprintln!("Unexpected response from control socket: {response:?}");
+            Ok(ExitCode::FAILURE)
+        }
+    }
+}
+
 fn print_state_plain(output: &ObservableState) {
     println!("Synchronization status:");
     println!(
@@ -404,8 +510,12 @@ mod tests {
         let value = ObservableState {
             program: ProgramData::default(),
             system: SystemSnapshot::default(),
+            bloom_filter_population: 0,
             sources: vec![],
             servers: vec![],
+            nts_ke_servers: vec![],
+            clock_history: vec![],
+            source_histograms: vec![],
         };
         let result = write_socket_helper(Format::Plain, value).await?;
 
@@ -422,8 +532,12 @@ mod tests {
         let value = ObservableState {
             program: ProgramData::default(),
             system: SystemSnapshot::default(),
+            bloom_filter_population: 0,
             sources: vec![],
             servers: vec![],
+            nts_ke_servers: vec![],
+            clock_history: vec![],
+            source_histograms: vec![],
         };
         let result = write_socket_helper(Format::Prometheus, value).await?;
 
@@ -474,4 +588,20 @@ mod tests {
         let err = NtpCtlOptions::try_parse_from(arguments).unwrap_err();
         assert_eq!(err, "invalid format option provided: yaml");
     }
+
+    #[test]
+    fn cli_poll() {
+        let arguments = &[BINARY, "poll", "12"];
+        let options = NtpCtlOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(options.action, NtpCtlAction::Poll);
+        assert_eq!(options.poll, Some("12".parse().unwrap()));
+
+        let arguments = &[BINARY, "poll"];
+        let err = NtpCtlOptions::try_parse_from(arguments).unwrap_err();
+        assert_eq!(err, "poll requires a source id");
+
+        let arguments = &[BINARY, "poll", "example.com"];
+        let err = NtpCtlOptions::try_parse_from(arguments).unwrap_err();
+        assert_eq!(err, "invalid source id provided: example.com");
+    }
 }
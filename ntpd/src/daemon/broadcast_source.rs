@@ -0,0 +1,246 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use ntp_proto::{
+    BroadcastAction, BroadcastClient, ClockId, NtpClock, NtpTimestamp, SourceController,
+};
+use timestamped_socket::socket::{Connected, Open, RecvResult, Socket, connect_address, open_ip};
+use tokio::time::Instant;
+use tracing::{Instrument, Span, debug, error, instrument, warn};
+
+use super::{
+    config::TimestampMode,
+    exitcode,
+    ntp_source::{AcceptResult, MsgForSystem, SourceChannels, accept_packet},
+    util::convert_net_timestamp,
+};
+
+pub(crate) struct BroadcastSourceTask<C: 'static + NtpClock + Send, Controller: SourceController> {
+    index: ClockId,
+    clock: C,
+    timestamp_mode: TimestampMode,
+    server: SocketAddr,
+    socket: Socket<SocketAddr, Open>,
+    calibration_socket: Option<Socket<SocketAddr, Connected>>,
+    channels: SourceChannels,
+    client: BroadcastClient<Controller>,
+
+    /// Timestamp of the last calibration request that we sent
+    last_send_timestamp: Option<NtpTimestamp>,
+}
+
+fn open_broadcast_socket(
+    listen: SocketAddr,
+    group: Option<IpAddr>,
+    timestamp_mode: TimestampMode,
+) -> std::io::Result<Socket<SocketAddr, Open>> {
+    let socket = open_ip(listen, timestamp_mode.as_general_mode(), false)?;
+    if let Some(group) = group {
+        let sock_ref = socket2::SockRef::from(&socket);
+        match group {
+            IpAddr::V4(group) => sock_ref.join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED)?,
+            IpAddr::V6(group) => sock_ref.join_multicast_v6(&group, 0)?,
+        }
+    }
+    Ok(socket)
+}
+
+impl<C, Controller: SourceController> BroadcastSourceTask<C, Controller>
+where
+    C: 'static + NtpClock + Send + Sync,
+{
+    async fn run(&mut self) {
+        let timer = tokio::time::sleep(self.client.timer_interval());
+        tokio::pin!(timer);
+
+        loop {
+            enum SelectResult {
+                Timer,
+                Broadcast(Result<RecvResult<SocketAddr>, std::io::Error>),
+                Calibration(Result<RecvResult<SocketAddr>, std::io::Error>),
+            }
+
+            let mut buf = [0_u8; 1024];
+            let mut calibration_buf = [0_u8; 1024];
+
+            let calibration_socket = &mut self.calibration_socket;
+            let selected: SelectResult = tokio::select! {
+                () = &mut timer => {
+                    SelectResult::Timer
+                },
+                result = self.socket.recv(&mut buf) => {
+                    SelectResult::Broadcast(result)
+                },
+                result = async { if let Some(socket) = calibration_socket { socket.recv(&mut calibration_buf).await } else { std::future::pending().await }} => {
+                    SelectResult::Calibration(result)
+                },
+            };
+
+            let action = match selected {
+                SelectResult::Timer => {
+                    timer
+                        .as_mut()
+                        .reset(Instant::now() + self.client.timer_interval());
+                    self.client.handle_timer()
+                }
+                SelectResult::Broadcast(result) => {
+                    let sender = result.as_ref().ok().map(|result| result.remote_addr);
+                    match accept_packet(result, &buf, &self.clock) {
+                        AcceptResult::Accept(packet, recv_timestamp) => {
+                            let Some(sender) = sender else {
+                                continue;
+                            };
+                            let action =
+                                self.client
+                                    .handle_broadcast(sender.ip(), packet, recv_timestamp);
+                            if matches!(action, BroadcastAction::Update) {
+                                let observed = self.client.observe(self.server.ip().to_string());
+                                self.channels
+                                    .source_histograms
+                                    .write()
+                                    .expect("Unexpected poisoned mutex")
+                                    .record_source(&observed);
+                            }
+                            action
+                        }
+                        AcceptResult::NetworkGone => {
+                            self.network_issue().await;
+                            return;
+                        }
+                        AcceptResult::Ignore => continue,
+                    }
+                }
+                SelectResult::Calibration(result) => {
+                    match accept_packet(result, &calibration_buf, &self.clock) {
+                        AcceptResult::Accept(packet, recv_timestamp) => {
+                            let Some(send_timestamp) = self.last_send_timestamp else {
+                                debug!("we received a message without having sent one; discarding");
+                                continue;
+                            };
+                            self.client.handle_calibration_response(
+                                packet,
+                                send_timestamp,
+                                recv_timestamp,
+                            )
+                        }
+                        AcceptResult::NetworkGone => {
+                            self.network_issue().await;
+                            return;
+                        }
+                        AcceptResult::Ignore => continue,
+                    }
+                }
+            };
+
+            match action {
+                BroadcastAction::Ignore | BroadcastAction::Update => {}
+                BroadcastAction::SendCalibration(packet) => self.send_calibration(&packet).await,
+            }
+
+            self.channels
+                .source_snapshots
+                .write()
+                .expect("Unexpected poisoned mutex")
+                .insert(
+                    self.index,
+                    self.client.observe(self.server.ip().to_string()),
+                );
+        }
+    }
+
+    async fn send_calibration(&mut self, packet: &[u8]) {
+        if self.calibration_socket.is_none() {
+            match connect_address(self.server, self.timestamp_mode.as_general_mode()) {
+                Ok(socket) => self.calibration_socket = Some(socket),
+                Err(error) => {
+                    // The request is repeated on the next timer
+                    warn!(?error, "Could not open calibration socket");
+                    return;
+                }
+            }
+        }
+
+        match self.clock.now() {
+            Err(e) => {
+                error!(error = ?e, "There was an error retrieving the current time");
+                std::process::exit(exitcode::NOPERM);
+            }
+            Ok(ts) => {
+                self.last_send_timestamp = Some(ts);
+            }
+        }
+
+        let Some(socket) = self.calibration_socket.as_mut() else {
+            return;
+        };
+        match socket.send(packet).await {
+            Err(error) => {
+                warn!(?error, "calibration request could not be sent");
+            }
+            Ok(opt_send_timestamp) => {
+                // update the last_send_timestamp with the one given by the kernel, if available
+                self.last_send_timestamp = opt_send_timestamp
+                    .selected_timestamp()
+                    .map(convert_net_timestamp)
+                    .or(self.last_send_timestamp);
+            }
+        }
+    }
+
+    async fn network_issue(&mut self) {
+        self.channels
+            .msg_for_system_sender
+            .send(MsgForSystem::NetworkIssue(self.index))
+            .await
+            .ok();
+        self.channels
+            .source_snapshots
+            .write()
+            .expect("Unexpected poisoned mutex")
+            .remove(&self.index);
+    }
+
+    #[expect(clippy::too_many_arguments)]
+    #[instrument(level = tracing::Level::ERROR, name = "Broadcast Source", skip(timestamp_mode, clock, channels, client))]
+    pub fn spawn(
+        index: ClockId,
+        server: SocketAddr,
+        listen: SocketAddr,
+        group: Option<IpAddr>,
+        clock: C,
+        timestamp_mode: TimestampMode,
+        channels: SourceChannels,
+        client: BroadcastClient<Controller>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(
+            (async move {
+                let socket = match open_broadcast_socket(listen, group, timestamp_mode) {
+                    Ok(socket) => socket,
+                    Err(error) => {
+                        warn!(?error, "Could not open broadcast socket");
+                        channels
+                            .msg_for_system_sender
+                            .send(MsgForSystem::NetworkIssue(index))
+                            .await
+                            .ok();
+                        return;
+                    }
+                };
+
+                let mut process = BroadcastSourceTask {
+                    index,
+                    clock,
+                    timestamp_mode,
+                    server,
+                    socket,
+                    calibration_socket: None,
+                    channels,
+                    client,
+                    last_send_timestamp: None,
+                };
+
+                process.run().await;
+            })
+            .instrument(Span::current()),
+        )
+    }
+}
@@ -62,6 +62,20 @@ impl NtpClock for NtpClockWrapper {
         )
     }
 
+    // FIXME: The kernel keeps the estimated and maximum error set through
+    // error_estimate_update, and increases the maximum error over time. These
+    // should be read back here with adjtimex, but clock-steering has no way to
+    // read them and this crate cannot do so itself without unsafe code. Until
+    // then the default, which reports the error as unknown, is used.
+
+    fn set_tai_offset(&self, tai_offset: i32) -> Result<(), Self::Error> {
+        self.0.set_tai(tai_offset)
+    }
+
+    fn get_tai_offset(&self) -> Result<Option<i32>, Self::Error> {
+        self.0.get_tai().map(Some)
+    }
+
     fn status_update(&self, leap_status: ntp_proto::NtpLeapIndicator) -> Result<(), Self::Error> {
         self.0.set_leap_seconds(match leap_status {
             ntp_proto::NtpLeapIndicator::NoWarning => clock_steering::LeapIndicator::NoWarning,
@@ -3,10 +3,11 @@ mod server;
 
 use clock_steering::unix::UnixClock;
 use ntp_proto::{
-    AlgorithmConfig, NtpVersion, ProtocolVersion, SourceConfig, SynchronizationConfig,
+    AlgorithmConfig, NtpVersion, PollInterval, PollIntervalLimits, ProtocolVersion, SourceConfig,
+    SourceGroup, SynchronizationConfig,
 };
 pub use ntp_source::*;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 pub use server::*;
 use std::io;
 use std::{
@@ -16,14 +17,25 @@ use std::{
     os::unix::fs::PermissionsExt,
     path::{Path, PathBuf},
     str::FromStr,
+    time::Duration,
 };
 use timestamped_socket::interface::InterfaceName;
 use tracing::{info, warn};
 
-use super::{clock::NtpClockWrapper, tracing::LogLevel};
+use super::{
+    clock::NtpClockWrapper,
+    peerstats::PeerStats,
+    system::{DEFAULT_HISTOGRAM_BUCKETS, HistogramBuckets, SourceHistograms},
+    tracing::{LogLevel, LogTarget},
+};
 
 const USAGE_MSG: &str = "\
-usage: ntp-daemon [-c PATH] [-l LOG_LEVEL]
+usage: ntp-daemon [-c PATH] [-l LOG_LEVEL] [--min-poll LOG2] [--max-poll LOG2]
+                  [--no-config-perm-check | --strict-config-perm-check]
+       ntp-daemon -t [-c PATH] [--min-poll LOG2] [--max-poll LOG2]
+                  [--no-config-perm-check | --strict-config-perm-check]
+       ntp-daemon --print-config [-c PATH] [--min-poll LOG2] [--max-poll LOG2]
+                  [--no-config-perm-check | --strict-config-perm-check]
        ntp-daemon -h
        ntp-daemon -v";
 
@@ -32,6 +44,13 @@ const DESCRIPTOR: &str = "ntp-daemon - synchronize system time";
 const HELP_MSG: &str = "Options:
   -c, --config=PATH             change the config .toml file
   -l, --log-level=LOG_LEVEL     change the log level
+      --min-poll=LOG2           override the minimum poll interval of all sources
+      --max-poll=LOG2           override the maximum poll interval of all sources
+      --no-config-perm-check    do not warn about config file permissions
+      --strict-config-perm-check
+                                refuse config files with unsafe permissions
+  -t, --test                    validate the config file and exit
+      --print-config            print the resulting configuration and exit
   -h, --help                    display this help text
   -v, --version                 display version information";
 
@@ -45,8 +64,18 @@ pub(crate) struct NtpDaemonOptions {
     pub config: Option<PathBuf>,
     /// Level for messages to display in logs
     pub log_level: Option<LogLevel>,
+    /// Skip the check for unrestricted permissions on the configuration file
+    pub no_config_perm_check: bool,
+    /// Treat unrestricted or unreadable configuration files as fatal errors
+    pub strict_config_perm_check: bool,
+    /// Minimum poll interval overriding the configuration of all sources
+    pub min_poll: Option<PollInterval>,
+    /// Maximum poll interval overriding the configuration of all sources
+    pub max_poll: Option<PollInterval>,
     help: bool,
     version: bool,
+    validate: bool,
+    print_config: bool,
     pub action: NtpDaemonAction,
 }
 
@@ -139,11 +168,14 @@ pub enum NtpDaemonAction {
     #[default]
     Help,
     Version,
+    Validate,
+    PrintConfig,
     Run,
 }
 
 impl NtpDaemonOptions {
-    const TAKES_ARGUMENT: &'static [&'static str] = &["--config", "--log-level"];
+    const TAKES_ARGUMENT: &'static [&'static str] =
+        &["--config", "--log-level", "--min-poll", "--max-poll"];
     const TAKES_ARGUMENT_SHORT: &'static [char] = &['c', 'l'];
 
     /// parse an iterator over command line arguments
@@ -170,6 +202,18 @@ impl NtpDaemonOptions {
                     "-v" | "--version" => {
                         options.version = true;
                     }
+                    "-t" | "--test" => {
+                        options.validate = true;
+                    }
+                    "--print-config" => {
+                        options.print_config = true;
+                    }
+                    "--no-config-perm-check" => {
+                        options.no_config_perm_check = true;
+                    }
+                    "--strict-config-perm-check" => {
+                        options.strict_config_perm_check = true;
+                    }
                     option => {
                         Err(format!("invalid option provided: {option}"))?;
                     }
@@ -182,6 +226,12 @@ impl NtpDaemonOptions {
                         Ok(level) => options.log_level = Some(level),
                         Err(_) => return Err("invalid log level".into()),
                     },
+                    "--min-poll" => {
+                        options.min_poll = Some(parse_poll_interval(&value)?);
+                    }
+                    "--max-poll" => {
+                        options.max_poll = Some(parse_poll_interval(&value)?);
+                    }
                     option => {
                         Err(format!("invalid option provided: {option}"))?;
                     }
@@ -191,30 +241,72 @@ impl NtpDaemonOptions {
         }
 
         options.resolve_action();
-        // nothing to validate at the moment
+
+        if let (Some(min_poll), Some(max_poll)) = (options.min_poll, options.max_poll)
+            && min_poll > max_poll
+        {
+            return Err("--min-poll must not be larger than --max-poll".into());
+        }
+
+        if options.no_config_perm_check && options.strict_config_perm_check {
+            return Err(
+                "--no-config-perm-check and --strict-config-perm-check are mutually exclusive"
+                    .into(),
+            );
+        }
 
         Ok(options)
     }
 
+    /// How the permissions of the configuration file should be checked
+    pub fn permission_check(&self) -> PermissionCheck {
+        if self.no_config_perm_check {
+            PermissionCheck::Skip
+        } else if self.strict_config_perm_check {
+            PermissionCheck::Strict
+        } else {
+            PermissionCheck::Warn
+        }
+    }
+
     /// from the arguments resolve which action should be performed
     fn resolve_action(&mut self) {
         if self.help {
             self.action = NtpDaemonAction::Help;
         } else if self.version {
             self.action = NtpDaemonAction::Version;
+        } else if self.validate {
+            self.action = NtpDaemonAction::Validate;
+        } else if self.print_config {
+            self.action = NtpDaemonAction::PrintConfig;
         } else {
             self.action = NtpDaemonAction::Run;
         }
     }
 }
 
-fn deserialize_ntp_clock<'de, D>(deserializer: D) -> Result<NtpClockWrapper, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let data: Option<PathBuf> = Deserialize::deserialize(deserializer)?;
+/// Range of poll intervals accepted on the command line, as a log2 number of
+/// seconds. The upper bound is the maximum poll interval of RFC 5905.
+const POLL_INTERVAL_OVERRIDE_RANGE: std::ops::RangeInclusive<i8> = 0..=17;
+
+/// Poll intervals are given as a log2 number of seconds, as in the config file
+fn parse_poll_interval(value: &str) -> Result<PollInterval, String> {
+    let log: i8 = value
+        .parse()
+        .ok()
+        .filter(|log| POLL_INTERVAL_OVERRIDE_RANGE.contains(log))
+        .ok_or_else(|| {
+            format!(
+                "invalid poll interval: '{value}', expected a number from {} to {}",
+                POLL_INTERVAL_OVERRIDE_RANGE.start(),
+                POLL_INTERVAL_OVERRIDE_RANGE.end()
+            )
+        })?;
+    Ok(PollInterval::from_byte(log as u8))
+}
 
-    if let Some(path) = data {
+fn open_ntp_clock<E: serde::de::Error>(path: Option<&Path>) -> Result<NtpClockWrapper, E> {
+    if let Some(path) = path {
         tracing::info!("using custom clock {path:?}");
 
         #[cfg(not(target_os = "linux"))]
@@ -222,7 +314,7 @@ where
 
         #[cfg(target_os = "linux")]
         Ok(NtpClockWrapper::new(
-            UnixClock::open(path).map_err(|e| serde::de::Error::custom(e.to_string()))?,
+            UnixClock::open(path).map_err(|e| E::custom(e.to_string()))?,
         ))
     } else {
         tracing::debug!("using REALTIME clock");
@@ -245,11 +337,25 @@ where
     Ok(opt_interface_name)
 }
 
+#[allow(clippy::ref_option, reason = "Signature required by serde")]
+fn serialize_interface<S>(
+    interface: &Option<InterfaceName>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match interface {
+        Some(interface) => serializer.collect_str(interface),
+        None => serializer.serialize_none(),
+    }
+}
+
 /// Timestamping mode. This is a hint!
 ///
 /// Your OS or hardware might not actually support some timestamping modes.
 /// Unsupported timestamping modes are ignored.
-#[derive(Default, Debug, Clone, Copy, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "kebab-case")]
 pub enum TimestampMode {
     #[cfg_attr(not(any(target_os = "linux", target_os = "freebsd")), default)]
@@ -291,7 +397,7 @@ impl TimestampMode {
 }
 
 #[cfg(target_os = "linux")]
-#[derive(Deserialize, Debug, Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
 pub struct CsptpConfig {
     #[serde(default)]
     pub identity: statime_wire::ClockIdentity,
@@ -334,22 +440,53 @@ fn csptp_config_default_true() -> bool {
     true
 }
 
-#[derive(Deserialize, Debug, Copy, Clone, Default)]
-#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
 pub struct ClockConfig {
-    #[serde(deserialize_with = "deserialize_ntp_clock", default)]
+    #[serde(skip)]
     pub clock: NtpClockWrapper,
-    #[serde(deserialize_with = "deserialize_interface", default)]
+    /// Device the clock was opened from, `None` for the realtime clock
+    #[serde(rename = "clock")]
+    pub clock_path: Option<PathBuf>,
+    #[serde(serialize_with = "serialize_interface")]
     pub interface: Option<InterfaceName>,
     pub timestamp_mode: TimestampMode,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+impl<'de> Deserialize<'de> for ClockConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "kebab-case", deny_unknown_fields)]
+        struct ClockConfigRaw {
+            #[serde(default)]
+            clock: Option<PathBuf>,
+            #[serde(deserialize_with = "deserialize_interface", default)]
+            interface: Option<InterfaceName>,
+            timestamp_mode: TimestampMode,
+        }
+
+        let raw = ClockConfigRaw::deserialize(deserializer)?;
+
+        Ok(ClockConfig {
+            clock: open_ntp_clock(raw.clock.as_deref())?,
+            clock_path: raw.clock,
+            interface: raw.interface,
+            timestamp_mode: raw.timestamp_mode,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct ObservabilityConfig {
     #[serde(default)]
     pub log_level: Option<LogLevel>,
     #[serde(default)]
+    pub log_target: LogTarget,
+    #[serde(default)]
     pub log_path: Option<PathBuf>,
     #[serde(default)]
     pub log_path_metrics_exporter: Option<PathBuf>,
@@ -359,33 +496,261 @@ pub struct ObservabilityConfig {
     pub observation_path: Option<PathBuf>,
     #[serde(default = "default_observation_permissions")]
     pub observation_permissions: u32,
+    #[serde(default)]
+    pub observation_snapshot_interval_ms: u64,
+    #[serde(default)]
+    pub observation_stream_path: Option<PathBuf>,
+    #[serde(default)]
+    pub observation_cbor_path: Option<PathBuf>,
+    #[serde(default)]
+    pub observation_hmac_key_path: Option<PathBuf>,
+    #[serde(default = "default_observation_stream_interval_ms")]
+    pub observation_stream_interval_ms: u64,
+    #[serde(default = "default_observation_max_connections")]
+    pub observation_max_connections: usize,
+    #[serde(
+        default = "default_clock_history_length",
+        deserialize_with = "deserialize_clock_history_length"
+    )]
+    pub clock_history_length: usize,
+    #[serde(
+        default = "default_histogram_buckets",
+        deserialize_with = "deserialize_histogram_buckets"
+    )]
+    pub offset_histogram_buckets: Vec<f64>,
+    #[serde(
+        default = "default_histogram_buckets",
+        deserialize_with = "deserialize_histogram_buckets"
+    )]
+    pub jitter_histogram_buckets: Vec<f64>,
+    #[serde(default)]
+    pub peerstats_path: Option<PathBuf>,
+    #[serde(default)]
+    pub peerstats_max_size: Option<u64>,
+    #[serde(default)]
+    pub peerstats_rotate_daily: bool,
+    #[serde(default)]
+    pub control_path: Option<PathBuf>,
+    #[serde(default = "default_control_permissions")]
+    pub control_permissions: u32,
     #[serde(default = "default_metrics_exporter_listen")]
-    pub metrics_exporter_listen: SocketAddr,
+    pub metrics_exporter_listen: MetricsExporterListen,
+    #[serde(default = "default_metrics_exporter_permissions")]
+    pub metrics_exporter_permissions: u32,
+}
+
+/// Where the metrics exporter listens for scrape requests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetricsExporterListen {
+    /// A TCP socket address, given as a string
+    Tcp(SocketAddr),
+    /// A Unix domain socket, given as `{ unix = "/path/to/socket" }`
+    Unix(PathBuf),
+}
+
+impl Display for MetricsExporterListen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetricsExporterListen::Tcp(addr) => write!(f, "{addr}"),
+            MetricsExporterListen::Unix(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl Serialize for MetricsExporterListen {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[derive(Serialize)]
+        struct UnixListen<'a> {
+            unix: &'a Path,
+        }
+
+        match self {
+            MetricsExporterListen::Tcp(addr) => serializer.collect_str(addr),
+            MetricsExporterListen::Unix(path) => UnixListen { unix: path }.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MetricsExporterListen {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "kebab-case", deny_unknown_fields)]
+        struct UnixListen {
+            unix: PathBuf,
+        }
+
+        struct MetricsExporterListenVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for MetricsExporterListenVisitor {
+            type Value = MetricsExporterListen;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a socket address or a table with a unix socket path")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                v.parse()
+                    .map(MetricsExporterListen::Tcp)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Str(v), &self))
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<Self::Value, A::Error> {
+                let listen =
+                    UnixListen::deserialize(serde::de::value::MapAccessDeserializer::new(map))?;
+                Ok(MetricsExporterListen::Unix(listen.unix))
+            }
+        }
+
+        deserializer.deserialize_any(MetricsExporterListenVisitor)
+    }
 }
 
 impl Default for ObservabilityConfig {
     fn default() -> Self {
         Self {
             log_level: None,
+            log_target: LogTarget::Stdout,
             log_path: None,
             log_path_metrics_exporter: None,
             ansi_colors: None,
             observation_path: None,
             observation_permissions: default_observation_permissions(),
+            observation_snapshot_interval_ms: 0,
+            observation_stream_path: None,
+            observation_cbor_path: None,
+            observation_hmac_key_path: None,
+            observation_stream_interval_ms: default_observation_stream_interval_ms(),
+            observation_max_connections: default_observation_max_connections(),
+            clock_history_length: default_clock_history_length(),
+            offset_histogram_buckets: default_histogram_buckets(),
+            jitter_histogram_buckets: default_histogram_buckets(),
+            peerstats_path: None,
+            peerstats_max_size: None,
+            peerstats_rotate_daily: false,
+            control_path: None,
+            control_permissions: default_control_permissions(),
             metrics_exporter_listen: default_metrics_exporter_listen(),
+            metrics_exporter_permissions: default_metrics_exporter_permissions(),
+        }
+    }
+}
+
+impl ObservabilityConfig {
+    pub fn histogram_buckets(&self) -> HistogramBuckets {
+        HistogramBuckets {
+            offset: self.offset_histogram_buckets.clone(),
+            jitter: self.jitter_histogram_buckets.clone(),
         }
     }
+
+    pub fn source_histograms(&self) -> SourceHistograms {
+        let peerstats = self
+            .peerstats_path
+            .clone()
+            .map(|path| PeerStats::new(path, self.peerstats_max_size, self.peerstats_rotate_daily));
+        SourceHistograms::new(self.histogram_buckets()).with_peerstats(peerstats)
+    }
 }
 
 const fn default_observation_permissions() -> u32 {
     0o666
 }
 
-fn default_metrics_exporter_listen() -> SocketAddr {
-    "127.0.0.1:9975".parse().unwrap()
+const fn default_control_permissions() -> u32 {
+    0o600
+}
+
+const fn default_observation_stream_interval_ms() -> u64 {
+    1000
+}
+
+const fn default_observation_max_connections() -> usize {
+    8
+}
+
+const fn default_clock_history_length() -> usize {
+    64
+}
+
+/// The clock history is allocated up front, and sent in full with every
+/// observation
+const MAX_CLOCK_HISTORY_LENGTH: usize = 4096;
+
+fn deserialize_clock_history_length<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let length: usize = Deserialize::deserialize(deserializer)?;
+    if length > MAX_CLOCK_HISTORY_LENGTH {
+        return Err(serde::de::Error::invalid_value(
+            serde::de::Unexpected::Unsigned(length as u64),
+            &"a clock history length of at most 4096",
+        ));
+    }
+    Ok(length)
+}
+
+fn default_histogram_buckets() -> Vec<f64> {
+    DEFAULT_HISTOGRAM_BUCKETS.to_vec()
+}
+
+fn deserialize_histogram_buckets<'de, D>(deserializer: D) -> Result<Vec<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let buckets: Vec<f64> = Deserialize::deserialize(deserializer)?;
+    if buckets.is_empty()
+        || buckets
+            .iter()
+            .any(|bound| !bound.is_finite() || *bound < 0.0)
+        || buckets.windows(2).any(|pair| pair[0] >= pair[1])
+    {
+        return Err(serde::de::Error::invalid_value(
+            serde::de::Unexpected::Seq,
+            &"a non-empty, strictly increasing list of non-negative bucket bounds",
+        ));
+    }
+    Ok(buckets)
+}
+
+// Above this many concurrent observation connections we warn the user, as
+// this likely indicates a misconfiguration.
+const MAX_SANE_OBSERVATION_CONNECTIONS: usize = 1024;
+
+fn default_metrics_exporter_listen() -> MetricsExporterListen {
+    MetricsExporterListen::Tcp("127.0.0.1:9975".parse().unwrap())
+}
+
+const fn default_metrics_exporter_permissions() -> u32 {
+    0o666
+}
+
+/// Group a source was configured to be part of, if any
+fn source_group(source: &NtpSourceConfig) -> Option<SourceGroup> {
+    match source {
+        NtpSourceConfig::Standard(config) => config.second.group,
+        NtpSourceConfig::Nts(config) => config.second.group,
+        NtpSourceConfig::Pool(config) => config.second.group,
+        NtpSourceConfig::NtsPool(config) => config.second.group,
+        NtpSourceConfig::Broadcast(config) => config.second.group,
+        NtpSourceConfig::Sock(config) => config.group,
+        #[cfg(feature = "pps")]
+        NtpSourceConfig::Pps(_) => None,
+        #[cfg(target_os = "linux")]
+        NtpSourceConfig::Csptp(_) => None,
+    }
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct DaemonSynchronizationConfig {
     #[serde(flatten)]
@@ -395,12 +760,131 @@ pub struct DaemonSynchronizationConfig {
     pub algorithm: AlgorithmConfig,
 }
 
-#[derive(Deserialize, Debug, Default)]
+// Upper bound on the time between startup retries, however large the backoff
+// has grown.
+const MAX_STARTUP_RETRY_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Retry schedule for sources that cannot be resolved or connected to when
+/// the daemon starts.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct StartupSourceRetryConfig {
+    /// Time between the first and second attempt
+    #[serde(default = "default_startup_retry_interval_ms")]
+    pub interval_ms: u64,
+    /// Factor by which the time between attempts grows after each attempt
+    #[serde(
+        default = "default_startup_retry_backoff_factor",
+        deserialize_with = "deserialize_backoff_factor"
+    )]
+    pub backoff_factor: f64,
+    /// Number of attempts after which the source is given up on
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for StartupSourceRetryConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: default_startup_retry_interval_ms(),
+            backoff_factor: default_startup_retry_backoff_factor(),
+            max_attempts: None,
+        }
+    }
+}
+
+impl StartupSourceRetryConfig {
+    /// Time to wait after the given number of failed attempts before trying
+    /// again.
+    pub fn retry_delay(&self, failed_attempts: u32) -> Duration {
+        let exponent = i32::try_from(failed_attempts.saturating_sub(1)).unwrap_or(i32::MAX);
+        let delay = Duration::from_millis(self.interval_ms).as_secs_f64()
+            * self.backoff_factor.powi(exponent);
+        Duration::try_from_secs_f64(delay)
+            .unwrap_or(MAX_STARTUP_RETRY_INTERVAL)
+            .min(MAX_STARTUP_RETRY_INTERVAL)
+    }
+}
+
+const fn default_startup_retry_interval_ms() -> u64 {
+    1000
+}
+
+const fn default_startup_retry_backoff_factor() -> f64 {
+    1.0
+}
+
+fn deserialize_backoff_factor<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let factor: f64 = Deserialize::deserialize(deserializer)?;
+    if !(1.0..=16.0).contains(&factor) {
+        return Err(serde::de::Error::invalid_value(
+            serde::de::Unexpected::Float(factor),
+            &"a backoff factor between 1.0 and 16.0",
+        ));
+    }
+    Ok(factor)
+}
+
+/// Settings for resolving the host names of sources.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct DnsConfig {
+    /// Time after which a single lookup is given up on
+    #[serde(default = "default_dns_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Minimum time in seconds that resolved addresses are cached
+    #[serde(default)]
+    pub min_ttl: u64,
+    /// Maximum time in seconds that resolved addresses are cached
+    #[serde(default = "default_dns_max_ttl")]
+    pub max_ttl: u64,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            timeout_ms: default_dns_timeout_ms(),
+            min_ttl: 0,
+            max_ttl: default_dns_max_ttl(),
+        }
+    }
+}
+
+impl DnsConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+
+    /// Time to cache a resolution with the given TTL. The system resolver does
+    /// not report TTLs, so results without one are cached for the minimum.
+    pub fn cache_duration(&self, ttl: Option<Duration>) -> Duration {
+        let min = Duration::from_secs(self.min_ttl);
+        let max = Duration::from_secs(self.max_ttl).max(min);
+        ttl.unwrap_or(min).clamp(min, max)
+    }
+}
+
+const fn default_dns_timeout_ms() -> u64 {
+    5000
+}
+
+const fn default_dns_max_ttl() -> u64 {
+    3600
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct Config {
     #[serde(rename = "source", default)]
     pub sources: Vec<NtpSourceConfig>,
-    #[serde(rename = "server", default)]
+    #[serde(
+        rename = "server",
+        default,
+        deserialize_with = "server::deserialize_servers"
+    )]
     pub servers: Vec<ServerConfig>,
     #[cfg(target_os = "linux")]
     #[serde(rename = "csptp-server", default)]
@@ -412,6 +896,10 @@ pub struct Config {
     #[serde(default)]
     pub source_defaults: SourceConfig,
     #[serde(default)]
+    pub startup_source_retry: StartupSourceRetryConfig,
+    #[serde(default)]
+    pub dns: DnsConfig,
+    #[serde(default)]
     pub observability: ObservabilityConfig,
     #[serde(default)]
     pub keyset: KeysetConfig,
@@ -423,33 +911,138 @@ pub struct Config {
     pub csptp: CsptpConfig,
 }
 
+/// How strictly the permissions of configuration files are checked
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionCheck {
+    /// Don't check the permissions at all
+    Skip,
+    /// Warn when others can write to the configuration file
+    #[default]
+    Warn,
+    /// Refuse configuration files that others can write to or that cannot be
+    /// read
+    Strict,
+}
+
 impl Config {
-    fn from_file(file: impl AsRef<Path>) -> Result<Config, ConfigError> {
+    /// Warn when others can write to the configuration file. Returns whether
+    /// the warning was emitted.
+    fn warn_unrestricted_permissions(file: impl AsRef<Path>) -> Result<bool, ConfigError> {
+        if Config::has_unrestricted_permissions(&file)? {
+            warn!("Unrestricted config file permissions: Others can write.");
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    fn has_unrestricted_permissions(file: impl AsRef<Path>) -> Result<bool, ConfigError> {
         let meta = std::fs::metadata(&file)?;
         let perm = meta.permissions();
 
-        if perm.mode() as libc::mode_t & libc::S_IWOTH != 0 {
-            warn!("Unrestricted config file permissions: Others can write.");
+        Ok(perm.mode() as libc::mode_t & libc::S_IWOTH != 0)
+    }
+
+    fn check_permissions(
+        file: impl AsRef<Path>,
+        permission_check: PermissionCheck,
+    ) -> Result<(), ConfigError> {
+        match permission_check {
+            PermissionCheck::Skip => {}
+            PermissionCheck::Warn => {
+                Config::warn_unrestricted_permissions(&file)?;
+            }
+            PermissionCheck::Strict => {
+                if Config::has_unrestricted_permissions(&file)? {
+                    return Err(ConfigError::UnrestrictedPermissions(
+                        file.as_ref().to_path_buf(),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn from_file(
+        file: impl AsRef<Path>,
+        permission_check: PermissionCheck,
+    ) -> Result<Config, ConfigError> {
+        Config::from_file_with_drop_ins(file, None, permission_check)
+    }
+
+    /// Read the configuration file, followed by the drop-in files in
+    /// `drop_in_dir`, if given.
+    fn from_file_with_drop_ins(
+        file: impl AsRef<Path>,
+        drop_in_dir: Option<&Path>,
+        permission_check: PermissionCheck,
+    ) -> Result<Config, ConfigError> {
+        Config::check_permissions(&file, permission_check)?;
+
+        let contents = std::fs::read_to_string(&file)?;
+
+        let drop_ins = match drop_in_dir {
+            Some(dir) => Config::drop_in_files(dir)?,
+            None => vec![],
+        };
+        if drop_ins.is_empty() {
+            return Ok(toml::de::from_str(&contents)?);
+        }
+
+        let mut merged: toml::value::Table = toml::de::from_str(&contents)?;
+        for path in drop_ins {
+            info!(?path, "using config drop-in file");
+            Config::check_permissions(&path, permission_check)?;
+
+            let contents = std::fs::read_to_string(&path)?;
+            merge_config_tables(&mut merged, toml::de::from_str(&contents)?);
+        }
+
+        Ok(toml::Value::Table(merged).try_into()?)
+    }
+
+    /// The `*.toml` files in the given drop-in directory, in lexical order.
+    fn drop_in_files(dir: &Path) -> Result<Vec<PathBuf>, ConfigError> {
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let mut files = vec![];
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "toml") && path.is_file() {
+                files.push(path);
+            }
         }
+        files.sort();
 
-        let contents = std::fs::read_to_string(file)?;
-        Ok(toml::de::from_str(&contents)?)
+        Ok(files)
     }
 
-    fn from_first_file(file: Option<impl AsRef<Path>>) -> Result<Config, ConfigError> {
-        // if an explicit file is given, always use that one
+    pub fn from_first_file(
+        file: Option<impl AsRef<Path>>,
+        permission_check: PermissionCheck,
+    ) -> Result<Config, ConfigError> {
+        // if an explicit file is given, always use that one, without the
+        // drop-ins meant for the global file
         if let Some(f) = file {
             let path: &Path = f.as_ref();
             info!(?path, "using config file");
-            return Config::from_file(f);
+            return Config::from_file(f, permission_check);
         }
 
         // for the global file we also ignore it when there are permission errors
         let global_path = Path::new("/etc/ntpd-rs/ntp.toml");
         if global_path.exists() {
             info!("using config file at default location `{:?}`", global_path);
-            match Config::from_file(global_path) {
-                Err(ConfigError::Io(e)) if e.kind() == ErrorKind::PermissionDenied => {
+            let drop_in_dir = Path::new("/etc/ntpd-rs/conf.d");
+            match Config::from_file_with_drop_ins(global_path, Some(drop_in_dir), permission_check)
+            {
+                Err(ConfigError::Io(e))
+                    if e.kind() == ErrorKind::PermissionDenied
+                        && permission_check != PermissionCheck::Strict =>
+                {
                     warn!("permission denied on global config file! using default config ...");
                 }
                 other => {
@@ -465,8 +1058,9 @@ impl Config {
         file: Option<&impl AsRef<Path>>,
         sources: Vec<NtpSourceConfig>,
         servers: Vec<ServerConfig>,
+        permission_check: PermissionCheck,
     ) -> Result<Config, ConfigError> {
-        let mut config = Config::from_first_file(file.as_ref())?;
+        let mut config = Config::from_first_file(file.as_ref(), permission_check)?;
 
         if !sources.is_empty() {
             if !config.sources.is_empty() {
@@ -485,15 +1079,144 @@ impl Config {
         Ok(config)
     }
 
-    /// Count potential number of sources in configuration
+    /// Override the poll interval limits of all sources, both the defaults and
+    /// those configured on individual sources. Fails when this leaves the
+    /// defaults or any source with a minimum above its maximum, in which case
+    /// the configuration is left unchanged.
+    pub fn override_poll_interval_limits(
+        &mut self,
+        min: Option<PollInterval>,
+        max: Option<PollInterval>,
+    ) -> Result<(), ConfigError> {
+        if min.is_none() && max.is_none() {
+            return Ok(());
+        }
+
+        let defaults = PollIntervalLimits {
+            min: min.unwrap_or(self.source_defaults.poll_interval_limits.min),
+            max: max.unwrap_or(self.source_defaults.poll_interval_limits.max),
+        };
+        // Only one of the limits may be overridden, so the result has to be
+        // checked against the limits it is merged with
+        let merged =
+            std::iter::once(defaults).chain(self.sources.iter_mut().filter_map(|source| {
+                let limits = source.poll_interval_limits_mut()?;
+                Some(PollIntervalLimits {
+                    min: min.or(limits.min).unwrap_or(defaults.min),
+                    max: max.or(limits.max).unwrap_or(defaults.max),
+                })
+            }));
+        for limits in merged {
+            if limits.min > limits.max {
+                return Err(ConfigError::PollIntervalLimits(limits));
+            }
+        }
+
+        self.source_defaults.poll_interval_limits = defaults;
+
+        for source in &mut self.sources {
+            let Some(limits) = source.poll_interval_limits_mut() else {
+                continue;
+            };
+            limits.min = min.or(limits.min);
+            limits.max = max.or(limits.max);
+        }
+
+        Ok(())
+    }
+
+    /// The configuration as TOML, with all defaults filled in. Keys and
+    /// certificates are given by the path they were loaded from, not by their
+    /// contents.
+    pub fn to_toml(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+
+    /// Sections that differ between this and a reloaded configuration, but
+    /// that only take effect after restarting the daemon.
+    pub fn restart_required_changes(&self, reloaded: &Config) -> Vec<&'static str> {
+        let mut changes = vec![];
+
+        #[cfg(target_os = "linux")]
+        if self.csptp_servers != reloaded.csptp_servers {
+            changes.push("csptp-server");
+        }
+
+        if self.nts_ke != reloaded.nts_ke {
+            changes.push("nts-ke-server");
+        }
+
+        if self.synchronization != reloaded.synchronization {
+            changes.push("synchronization");
+        }
+
+        if self.source_defaults != reloaded.source_defaults {
+            changes.push("source-defaults");
+        }
+
+        if self.startup_source_retry != reloaded.startup_source_retry {
+            changes.push("startup-source-retry");
+        }
+
+        if self.dns != reloaded.dns {
+            changes.push("dns");
+        }
+
+        if self.observability != reloaded.observability {
+            changes.push("observability");
+        }
+
+        if self.keyset != reloaded.keyset {
+            changes.push("keyset");
+        }
+
+        #[cfg(feature = "hardware-timestamping")]
+        if self.clock.interface != reloaded.clock.interface
+            || self.clock.timestamp_mode != reloaded.clock.timestamp_mode
+        {
+            changes.push("clock");
+        }
+
+        #[cfg(target_os = "linux")]
+        if self.csptp != reloaded.csptp {
+            changes.push("csptp");
+        }
+
+        changes
+    }
+
+    /// Count potential number of sources in configuration. Monitor-only
+    /// sources are never selected, so they don't count.
     fn count_sources(&self) -> usize {
+        self.count_sources_where(|_| true)
+    }
+
+    /// Count potential number of sources in the given group
+    fn count_group_sources(&self, group: SourceGroup) -> usize {
+        self.count_sources_where(|source| source_group(source) == Some(group))
+    }
+
+    fn count_sources_where(&self, filter: impl Fn(&NtpSourceConfig) -> bool) -> usize {
+        let monitor_only = |config: &PartialSourceConfig| {
+            config
+                .monitor_only
+                .unwrap_or(self.source_defaults.monitor_only)
+        };
+
         let mut count = 0;
-        for source in &self.sources {
+        for source in self.sources.iter().filter(|source| filter(source)) {
             match source {
+                NtpSourceConfig::Standard(config) if monitor_only(&config.second) => {}
+                NtpSourceConfig::Nts(config) if monitor_only(&config.second) => {}
+                NtpSourceConfig::Pool(config) if monitor_only(&config.second) => {}
+                NtpSourceConfig::NtsPool(config) if monitor_only(&config.second) => {}
+                NtpSourceConfig::Broadcast(config) if monitor_only(&config.second) => {}
                 NtpSourceConfig::Standard(_) => count += 1,
                 NtpSourceConfig::Nts(_) => count += 1,
                 NtpSourceConfig::Pool(config) => count += config.first.count,
                 NtpSourceConfig::NtsPool(config) => count += config.first.count,
+                NtpSourceConfig::Broadcast(_) => count += 1,
+                NtpSourceConfig::Sock(_) if self.source_defaults.monitor_only => {}
                 NtpSourceConfig::Sock(_) => count += 1,
                 #[cfg(feature = "pps")]
                 NtpSourceConfig::Pps(_) => {} // PPS sources don't count
@@ -504,6 +1227,17 @@ impl Config {
         count
     }
 
+    /// Whether a reference clock is configured that can provide the time on
+    /// its own. PPS sources only provide the start of each second, so don't
+    /// qualify.
+    fn has_reference_clock(&self) -> bool {
+        !self.source_defaults.monitor_only
+            && self
+                .sources
+                .iter()
+                .any(|source| matches!(source, NtpSourceConfig::Sock(_)))
+    }
+
     /// Check that the config is reasonable. This function may panic if the
     /// configuration is egregious, although it doesn't do so currently.
     pub fn check(&self) -> bool {
@@ -517,22 +1251,46 @@ impl Config {
             info!("No sources configured. Daemon will not change system time.");
         }
 
-        if !self.sources.is_empty()
-            && self.count_sources()
-                < self
-                    .synchronization
-                    .synchronization_base
-                    .minimum_agreeing_sources
-        {
+        if self.dns.min_ttl > self.dns.max_ttl {
             warn!(
-                "Fewer sources configured than are required to agree on the current time. Daemon will not change system time."
+                "The dns min-ttl exceeds the max-ttl, resolved addresses are cached for min-ttl."
             );
             ok = false;
         }
 
-        if self.sources.iter().any(|config| match config {
-            NtpSourceConfig::Sock(_) => false,
-            #[cfg(feature = "pps")]
+        if self.dns.timeout_ms == 0 {
+            warn!("The dns timeout-ms is 0, every lookup will time out immediately.");
+            ok = false;
+        }
+
+        let standalone_reference_clock = self
+            .synchronization
+            .synchronization_base
+            .standalone_reference_clock;
+        if standalone_reference_clock && !self.has_reference_clock() {
+            warn!(
+                "A standalone reference clock is allowed, but no sock source is configured to provide one."
+            );
+            ok = false;
+        }
+
+        if !self.sources.is_empty()
+            && !(standalone_reference_clock && self.has_reference_clock())
+            && self.count_sources()
+                < self
+                    .synchronization
+                    .synchronization_base
+                    .minimum_agreeing_sources
+        {
+            warn!(
+                "Fewer sources configured than are required to agree on the current time. Daemon will not change system time."
+            );
+            ok = false;
+        }
+
+        if self.sources.iter().any(|config| match config {
+            NtpSourceConfig::Broadcast(_) | NtpSourceConfig::Sock(_) => false,
+            #[cfg(feature = "pps")]
             NtpSourceConfig::Pps(_) => false,
             #[cfg(target_os = "linux")]
             NtpSourceConfig::Csptp(_) => false,
@@ -555,34 +1313,149 @@ impl Config {
             ok = false;
         }
 
-        // Check that the NTS configuration is consistent with the NTP configuration
-        for ke_server in self
-            .nts_ke
-            .iter()
-            .filter(|ke_server| ke_server.ntp_server.is_none())
+        for source in &self.sources {
+            let NtpSourceConfig::Pool(config) = source else {
+                continue;
+            };
+            let family = config.first.address_family.unwrap_or_default();
+            for addr in config
+                .first
+                .addresses
+                .iter()
+                .filter(|addr| !family.matches(addr))
+            {
+                warn!(
+                    "Pool {} lists {addr}, which is not an {family} address and will not be used.",
+                    *config.first.addr
+                );
+                ok = false;
+            }
+        }
+
+        for (group, minimum) in self.synchronization.synchronization_base.quorum.groups() {
+            let count = self.count_group_sources(group);
+            if count == 0 {
+                warn!(
+                    "The quorum requires sources from group {group}, but no source is configured in that group."
+                );
+                ok = false;
+            } else if count < minimum {
+                warn!(
+                    "The quorum requires {minimum} sources from group {group}, but fewer are configured in that group. Daemon will not change system time."
+                );
+                ok = false;
+            }
+        }
+
+        let synchronization = &self.synchronization.synchronization_base;
+        if let Some(step_threshold) = synchronization.step_threshold
+            && !(synchronization
+                .single_step_panic_threshold
+                .is_within(step_threshold)
+                && synchronization
+                    .single_step_panic_threshold
+                    .is_within(-step_threshold))
+        {
+            warn!(
+                "The step-threshold is not smaller than the single-step-panic-threshold, any step will cause the daemon to panic."
+            );
+            ok = false;
+        }
+
+        if let Some(recovery) = synchronization.panic_threshold_recovery
+            && !(synchronization
+                .single_step_panic_threshold
+                .is_within(recovery)
+                && synchronization
+                    .single_step_panic_threshold
+                    .is_within(-recovery))
+        {
+            warn!(
+                "The panic-threshold-recovery is not smaller than the single-step-panic-threshold, corrections may alternate between stepping and holding."
+            );
+            ok = false;
+        }
+
+        let refclock_weight = self.synchronization.algorithm.refclock_weight;
+        if !(refclock_weight.is_finite() && refclock_weight > 0.0) {
+            warn!(
+                "The refclock-weight must be a positive number, reference clocks may not be used correctly."
+            );
+            ok = false;
+        }
+
+        if self.observability.observation_max_connections == 0 {
+            warn!(
+                "The observation socket must allow at least 1 connection, a limit of 1 will be used instead."
+            );
+            ok = false;
+        } else if self.observability.observation_max_connections > MAX_SANE_OBSERVATION_CONNECTIONS
         {
+            warn!(
+                "Allowing {} concurrent observation connections is unusually large and may exhaust system resources.",
+                self.observability.observation_max_connections
+            );
+            ok = false;
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        if self.servers.iter().any(|server| server.reuse_port) {
+            warn!("Sharing a server address with reuse-port is only supported on Linux.");
+            ok = false;
+        }
+
+        for (index, server) in self.servers.iter().enumerate() {
+            let mut shared = self.servers[..index]
+                .iter()
+                .filter(|other| {
+                    other.listen == server.listen && other.interface == server.interface
+                })
+                .peekable();
+            if shared.peek().is_some()
+                && !(server.reuse_port && shared.all(|other| other.reuse_port))
+            {
+                warn!(
+                    "Multiple servers listen on {}, but not all of them set reuse-port. Binding all but the first will fail.",
+                    server.listen
+                );
+                ok = false;
+            }
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        if self.servers.iter().any(|server| server.interface.is_some()) {
+            warn!(
+                "Binding a server to an interface is only supported on Linux, the interface will be ignored."
+            );
+            ok = false;
+        }
+
+        // Check that the NTS configuration is consistent with the NTP configuration
+        for ke_server in &self.nts_ke {
             if ke_server.accept_ntp_versions.contains(&NtpVersion::V4)
+                && ke_server.ntpv4_endpoint.server.is_none()
                 && !self.servers.iter().any(|server| {
-                    server.listen.port() == ke_server.ntp_port.unwrap_or(123)
+                    server.listen.port() == ke_server.ntpv4_endpoint.port.unwrap_or(123)
                         && server.accept_ntp_versions.contains(&NtpVersion::V4)
                 })
             {
                 warn!(
-                    "Configured NTS for NTPv4 on port {}, but have no server listening on that port for NTPv4 traffic. If this is for an external ntp server, consider configuring a value for `ntp-server`.",
-                    ke_server.ntp_port.unwrap_or(123)
+                    "Configured NTS for NTPv4 on port {}, but have no server listening on that port for NTPv4 traffic. If this is for an external ntp server, consider configuring a value for `ntp-server` or `ntpv4-server`.",
+                    ke_server.ntpv4_endpoint.port.unwrap_or(123)
                 );
                 ok = false;
             }
 
             if ke_server.accept_ntp_versions.contains(&NtpVersion::V5)
+                && ke_server.ntpv5_endpoint.server.is_none()
                 && !self.servers.iter().any(|server| {
-                    server.listen.port() == ke_server.ntp_port.unwrap_or(123)
+                    server.listen.port() == ke_server.ntpv5_endpoint.port.unwrap_or(123)
                         && server.accept_ntp_versions.contains(&NtpVersion::V5)
                 })
             {
                 warn!(
-                    "Configured NTS for NTPv5 on port {}, but have no server listening on that port for NTPv5 traffic. If this is for an external ntp server, consider configuring a value for `ntp-server`.",
-                    ke_server.ntp_port.unwrap_or(123)
+                    "Configured NTS for NTPv5 on port {}, but have no server listening on that port for NTPv5 traffic. If this is for an external ntp server, consider configuring a value for `ntp-server` or `ntpv5-server`.",
+                    ke_server.ntpv5_endpoint.port.unwrap_or(123)
                 );
                 ok = false;
             }
@@ -590,12 +1463,115 @@ impl Config {
 
         ok
     }
+
+    /// Check whether the daemon has the privileges needed to bind the configured
+    /// server ports. Like [`Config::check`], this only warns about problems.
+    pub fn check_privileges(&self) -> bool {
+        if may_bind_privileged_ports() != Some(false) {
+            return true;
+        }
+
+        let mut ok = true;
+        for listen in self.privileged_listen_addrs(unprivileged_port_start()) {
+            warn!(
+                "The server on {listen} listens on privileged port {}, but the daemon lacks the CAP_NET_BIND_SERVICE capability. Binding the server will likely fail, consider running as root or granting the capability.",
+                listen.port()
+            );
+            ok = false;
+        }
+
+        ok
+    }
+
+    /// Listen addresses of servers that use a port below `unprivileged_port_start`
+    fn privileged_listen_addrs(
+        &self,
+        unprivileged_port_start: u16,
+    ) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.servers
+            .iter()
+            .map(|server| server.listen)
+            .filter(move |listen| listen.port() != 0 && listen.port() < unprivileged_port_start)
+    }
+}
+
+// Bit of CAP_NET_BIND_SERVICE in the capability sets of a process
+const CAP_NET_BIND_SERVICE: u32 = 10;
+
+/// Whether the current process may bind ports below the unprivileged port
+/// range, or `None` if this could not be determined.
+fn may_bind_privileged_ports() -> Option<bool> {
+    if cfg!(target_os = "linux") {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        has_net_bind_service(&status)
+    } else {
+        None
+    }
+}
+
+/// Parse the effective capabilities from the contents of `/proc/self/status`
+fn has_net_bind_service(status: &str) -> Option<bool> {
+    let capabilities = status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))?
+        .trim();
+    let capabilities = u64::from_str_radix(capabilities, 16).ok()?;
+    Some(capabilities & (1 << CAP_NET_BIND_SERVICE) != 0)
+}
+
+/// First port that can be bound without privileges
+fn unprivileged_port_start() -> u16 {
+    std::fs::read_to_string("/proc/sys/net/ipv4/ip_unprivileged_port_start")
+        .ok()
+        .and_then(|start| start.trim().parse().ok())
+        .unwrap_or(1024)
+}
+
+// Sections that can be repeated. Drop-in files append to these instead of
+// replacing them.
+const REPEATABLE_SECTIONS: &[&str] = &["source", "server", "csptp-server", "nts-ke-server"];
+
+fn merge_config_tables(base: &mut toml::value::Table, drop_in: toml::value::Table) {
+    for (key, value) in drop_in {
+        let merged = match (base.remove(&key), value) {
+            (Some(toml::Value::Array(mut existing)), toml::Value::Array(additional))
+                if REPEATABLE_SECTIONS.contains(&key.as_str()) =>
+            {
+                existing.extend(additional);
+                toml::Value::Array(existing)
+            }
+            (Some(toml::Value::Table(mut existing)), toml::Value::Table(overrides)) => {
+                override_table(&mut existing, overrides);
+                toml::Value::Table(existing)
+            }
+            (_, value) => value,
+        };
+        base.insert(key, merged);
+    }
+}
+
+fn override_table(base: &mut toml::value::Table, overrides: toml::value::Table) {
+    for (key, value) in overrides {
+        match value {
+            toml::Value::Table(nested) => match base.get_mut(&key) {
+                Some(toml::Value::Table(existing)) => override_table(existing, nested),
+                _ => {
+                    base.insert(key, toml::Value::Table(nested));
+                }
+            },
+            value => {
+                base.insert(key, value);
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum ConfigError {
     Io(io::Error),
     Toml(toml::de::Error),
+    UnrestrictedPermissions(PathBuf),
+    PollIntervalLimits(PollIntervalLimits),
 }
 
 impl std::error::Error for ConfigError {}
@@ -605,6 +1581,15 @@ impl Display for ConfigError {
         match self {
             Self::Io(e) => write!(f, "io error while reading config: {e}"),
             Self::Toml(e) => write!(f, "config toml parsing error: {e}"),
+            Self::UnrestrictedPermissions(path) => {
+                write!(f, "others can write to config file {}", path.display())
+            }
+            Self::PollIntervalLimits(limits) => write!(
+                f,
+                "poll interval override results in a minimum poll interval ({}) above the maximum ({})",
+                limits.min.as_log(),
+                limits.max.as_log()
+            ),
         }
     }
 }
@@ -642,6 +1627,8 @@ mod tests {
                 first: StandardSource {
                     address: NormalizedAddress::new_from_parts("example.com", 123).into(),
                     ntp_version: ProtocolVersion::V4,
+                    prefer: false,
+                    address_family: None,
                 },
                 second: PartialSourceConfig::default()
             })]
@@ -659,6 +1646,8 @@ mod tests {
                 first: StandardSource {
                     address: NormalizedAddress::new_from_parts("example.com", 123).into(),
                     ntp_version: ProtocolVersion::V4,
+                    prefer: false,
+                    address_family: None,
                 },
                 second: PartialSourceConfig::default()
             })]
@@ -674,6 +1663,8 @@ mod tests {
                 first: StandardSource {
                     address: NormalizedAddress::new_from_parts("example.com", 123).into(),
                     ntp_version: ProtocolVersion::V4,
+                    prefer: false,
+                    address_family: None,
                 },
                 second: PartialSourceConfig::default()
             })]
@@ -705,6 +1696,8 @@ mod tests {
                 first: StandardSource {
                     address: NormalizedAddress::new_from_parts("example.com", 123).into(),
                     ntp_version: ProtocolVersion::V4,
+                    prefer: false,
+                    address_family: None,
                 },
                 second: PartialSourceConfig::default()
             })]
@@ -734,10 +1727,19 @@ mod tests {
             [source-defaults]
             poll-interval-limits = { min = 5, max = 9 }
             initial-poll-interval = 5
+            max-jitter-for-discipline = 0.01
             [observability]
             log-level = "info"
             observation-path = "/foo/bar/observe"
             observation-permissions = 0o567
+            observation-stream-path = "/foo/bar/observe-stream"
+            observation-stream-interval-ms = 5000
+            observation-cbor-path = "/foo/bar/observe-cbor"
+            observation-max-connections = 32
+            offset-histogram-buckets = [0.001, 0.01, 0.1]
+            peerstats-path = "/foo/bar/peerstats"
+            peerstats-max-size = 1000000
+            control-path = "/foo/bar/control"
             "#,
         )
         .unwrap();
@@ -748,6 +1750,35 @@ mod tests {
             Some(PathBuf::from("/foo/bar/observe"))
         );
         assert_eq!(config.observability.observation_permissions, 0o567);
+        assert_eq!(
+            config.observability.observation_stream_path,
+            Some(PathBuf::from("/foo/bar/observe-stream"))
+        );
+        assert_eq!(config.observability.observation_stream_interval_ms, 5000);
+        assert_eq!(
+            config.observability.observation_cbor_path,
+            Some(PathBuf::from("/foo/bar/observe-cbor"))
+        );
+        assert_eq!(config.observability.observation_max_connections, 32);
+        assert_eq!(
+            config.observability.offset_histogram_buckets,
+            vec![0.001, 0.01, 0.1]
+        );
+        assert_eq!(
+            config.observability.jitter_histogram_buckets,
+            DEFAULT_HISTOGRAM_BUCKETS
+        );
+        assert_eq!(
+            config.observability.peerstats_path,
+            Some(PathBuf::from("/foo/bar/peerstats"))
+        );
+        assert_eq!(config.observability.peerstats_max_size, Some(1_000_000));
+        assert!(!config.observability.peerstats_rotate_daily);
+        assert_eq!(
+            config.observability.control_path,
+            Some(PathBuf::from("/foo/bar/control"))
+        );
+        assert_eq!(config.observability.control_permissions, 0o600);
 
         assert_eq!(
             config.sources,
@@ -755,6 +1786,8 @@ mod tests {
                 first: StandardSource {
                     address: NormalizedAddress::new_from_parts("example.com", 123).into(),
                     ntp_version: ProtocolVersion::V4,
+                    prefer: false,
+                    address_family: None,
                 },
                 second: PartialSourceConfig::default()
             })]
@@ -765,6 +1798,10 @@ mod tests {
         assert_eq!(poll_interval_limits.max.as_log(), 9);
 
         assert_eq!(config.source_defaults.initial_poll_interval.as_log(), 5);
+        assert_eq!(
+            config.source_defaults.max_jitter_for_discipline,
+            Some(NtpDuration::from_seconds(0.01))
+        );
 
         let config: Config = toml::from_str(
             "[[source]]\nmode = \"server\"\naddress = \"example.com\"\nntp-version = \"auto\"",
@@ -776,6 +1813,8 @@ mod tests {
                 first: StandardSource {
                     address: NormalizedAddress::new_from_parts("example.com", 123).into(),
                     ntp_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
+                    prefer: false,
+                    address_family: None,
                 },
                 second: PartialSourceConfig::default()
             })]
@@ -826,123 +1865,1112 @@ mod tests {
     }
 
     #[test]
-    fn toml_sources_invalid() {
-        let config: Result<Config, _> = toml::from_str(
+    fn cli_poll_override() {
+        let arguments = &["/usr/bin/ntp-daemon"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert!(parsed.min_poll.is_none());
+        assert!(parsed.max_poll.is_none());
+
+        let arguments = &["/usr/bin/ntp-daemon", "--min-poll", "2", "--max-poll=5"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.min_poll.unwrap().as_log(), 2);
+        assert_eq!(parsed.max_poll.unwrap().as_log(), 5);
+        assert_eq!(parsed.action, NtpDaemonAction::Run);
+
+        let arguments = &["/usr/bin/ntp-daemon", "--min-poll", "4", "--max-poll", "4"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.min_poll, parsed.max_poll);
+
+        let arguments = &["/usr/bin/ntp-daemon", "--min-poll", "6", "--max-poll", "3"];
+        assert!(NtpDaemonOptions::try_parse_from(arguments).is_err());
+
+        let arguments = &["/usr/bin/ntp-daemon", "--min-poll", "16s"];
+        assert!(NtpDaemonOptions::try_parse_from(arguments).is_err());
+
+        // Only poll intervals in the valid range are accepted
+        let arguments = &["/usr/bin/ntp-daemon", "--min-poll", "0", "--max-poll", "17"];
+        assert!(NtpDaemonOptions::try_parse_from(arguments).is_ok());
+        let arguments = &["/usr/bin/ntp-daemon", "--min-poll", "-1"];
+        assert!(NtpDaemonOptions::try_parse_from(arguments).is_err());
+        let arguments = &["/usr/bin/ntp-daemon", "--max-poll", "18"];
+        assert!(NtpDaemonOptions::try_parse_from(arguments).is_err());
+        let arguments = &["/usr/bin/ntp-daemon", "--max-poll", "200"];
+        assert!(NtpDaemonOptions::try_parse_from(arguments).is_err());
+
+        let arguments = &["/usr/bin/ntp-daemon", "--max-poll"];
+        assert!(NtpDaemonOptions::try_parse_from(arguments).is_err());
+    }
+
+    #[test]
+    fn override_poll_interval_limits() {
+        let mut config: Config = toml::from_str(
             r#"
             [[source]]
             mode = "server"
-            address = ":invalid:ipv6:123"
+            address = "lan.example.com"
+
+            [[source]]
+            mode = "server"
+            address = "wan.example.com"
+            poll-interval-limits = { min = 6, max = 12 }
+
+            [source-defaults]
+            poll-interval-limits = { min = 3, max = 6 }
             "#,
+        )
+        .unwrap();
+
+        // Overriding only the minimum would put it above the maximum of the
+        // defaults and of the second source
+        let original_defaults = config.source_defaults;
+        let original_sources = config.sources.clone();
+        let arguments = &["/usr/bin/ntp-daemon", "--min-poll", "8"];
+        let options = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert!(matches!(
+            config.override_poll_interval_limits(options.min_poll, options.max_poll),
+            Err(ConfigError::PollIntervalLimits(_))
+        ));
+        assert_eq!(config.source_defaults, original_defaults);
+        assert_eq!(config.sources, original_sources);
+
+        let arguments = &["/usr/bin/ntp-daemon", "--max-poll", "5"];
+        let options = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert!(
+            config
+                .override_poll_interval_limits(options.min_poll, options.max_poll)
+                .is_err()
         );
 
-        assert!(config.is_err());
+        let arguments = &["/usr/bin/ntp-daemon", "--min-poll", "1", "--max-poll", "2"];
+        let options = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        config
+            .override_poll_interval_limits(options.min_poll, options.max_poll)
+            .unwrap();
+
+        for source in &config.sources {
+            let NtpSourceConfig::Standard(source) = source else {
+                panic!("Unexpected source type");
+            };
+            let effective = source.second.clone().with_defaults(config.source_defaults);
+            assert_eq!(effective.poll_interval_limits.min.as_log(), 1);
+            assert_eq!(effective.poll_interval_limits.max.as_log(), 2);
+        }
     }
 
     #[test]
-    fn toml_allow_no_sources() {
-        let config: Result<Config, _> = toml::from_str(
-            r#"
-            [[server]]
-            listen = "[::]:123"
-            "#,
-        );
-
-        assert!(config.is_ok());
-        assert!(config.unwrap().check());
+    fn cli_no_config_perm_check() {
+        let arguments = &["/usr/bin/ntp-daemon"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert!(!parsed.no_config_perm_check);
+
+        let arguments = &["/usr/bin/ntp-daemon", "--no-config-perm-check"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert!(parsed.no_config_perm_check);
+        assert_eq!(parsed.action, NtpDaemonAction::Run);
+        assert_eq!(parsed.permission_check(), PermissionCheck::Skip);
     }
 
     #[test]
-    fn system_config_accumulated_threshold() {
-        let config: Result<SynchronizationConfig, _> = toml::from_str(
-            r#"
-            accumulated-step-panic-threshold = 0
-            "#,
-        );
+    fn cli_strict_config_perm_check() {
+        let arguments = &["/usr/bin/ntp-daemon"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.permission_check(), PermissionCheck::Warn);
+
+        let arguments = &["/usr/bin/ntp-daemon", "--strict-config-perm-check"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.permission_check(), PermissionCheck::Strict);
+
+        let arguments = &[
+            "/usr/bin/ntp-daemon",
+            "--strict-config-perm-check",
+            "--no-config-perm-check",
+        ];
+        assert!(NtpDaemonOptions::try_parse_from(arguments).is_err());
+    }
 
-        let config = config.unwrap();
-        assert!(config.accumulated_step_panic_threshold.is_none());
+    #[test]
+    fn cli_validate() {
+        let arguments = &["/usr/bin/ntp-daemon", "--test"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.action, NtpDaemonAction::Validate);
+
+        let arguments = &["/usr/bin/ntp-daemon", "-t", "-c", "other.toml"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.config, Some("other.toml".into()));
+        assert_eq!(parsed.action, NtpDaemonAction::Validate);
+
+        let arguments = &["/usr/bin/ntp-daemon", "-tc", "other.toml"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.config, Some("other.toml".into()));
+        assert_eq!(parsed.action, NtpDaemonAction::Validate);
+    }
 
-        let config: Result<SynchronizationConfig, _> = toml::from_str(
-            r#"
-            accumulated-step-panic-threshold = 1000
-            "#,
-        );
+    #[test]
+    fn cli_validate_precedence() {
+        let arguments = &["/usr/bin/ntp-daemon", "--test", "--help"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.action, NtpDaemonAction::Help);
+
+        let arguments = &["/usr/bin/ntp-daemon", "-h", "-t"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.action, NtpDaemonAction::Help);
+
+        let arguments = &["/usr/bin/ntp-daemon", "-t", "--version"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.action, NtpDaemonAction::Version);
+    }
 
-        let config = config.unwrap();
-        assert_eq!(
-            config.accumulated_step_panic_threshold,
-            Some(NtpDuration::from_seconds(1000.0))
-        );
+    #[test]
+    fn cli_print_config() {
+        let arguments = &["/usr/bin/ntp-daemon", "--print-config"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.action, NtpDaemonAction::PrintConfig);
+
+        let arguments = &[
+            "/usr/bin/ntp-daemon",
+            "--print-config",
+            "-c",
+            "other.toml",
+            "--min-poll",
+            "4",
+        ];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.config, Some("other.toml".into()));
+        assert_eq!(parsed.min_poll.unwrap().as_log(), 4);
+        assert_eq!(parsed.action, NtpDaemonAction::PrintConfig);
+
+        let arguments = &["/usr/bin/ntp-daemon", "--print-config", "-t"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.action, NtpDaemonAction::Validate);
+
+        let arguments = &["/usr/bin/ntp-daemon", "--print-config", "--help"];
+        let parsed = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+        assert_eq!(parsed.action, NtpDaemonAction::Help);
     }
 
     #[test]
-    fn system_config_startup_panic_threshold() {
-        let config: Result<SynchronizationConfig, _> = toml::from_str(
-            r#"
-            startup-step-panic-threshold = { forward = 10, backward = 20 }
-            "#,
+    fn print_config_round_trip() {
+        let certificate_authority = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/certificates/nos-nl.pem"
         );
 
-        let config = config.unwrap();
-        assert_eq!(
-            config.startup_step_panic_threshold.forward,
-            Some(NtpDuration::from_seconds(10.0))
-        );
-        assert_eq!(
-            config.startup_step_panic_threshold.backward,
-            Some(NtpDuration::from_seconds(20.0))
-        );
-    }
+        let dir = drop_in_test_dir();
+        std::fs::write(
+            dir.join("conf.d/10-extra.toml"),
+            format!(
+                r#"
+                [[source]]
+                mode = "nts"
+                address = "example.net"
+                certificate-authority = "{certificate_authority}"
+
+                [[source]]
+                mode = "pool"
+                address = "pool.example.com"
+                count = 2
+                ignore = ["127.0.0.1"]
+
+                [[server]]
+                listen = "[::]:123"
+
+                [[nts-ke-server]]
+                listen = "[::]:4460"
+                certificate-chain-path = "/etc/ntpd-rs/chain.pem"
+                private-key-path = "/etc/ntpd-rs/key.pem"
+                accepted-pool-authentication-tokens = ["pool-secret-1", "pool-secret-2"]
+
+                [synchronization]
+                single-step-panic-threshold = {{ forward = 10, backward = "inf" }}
+
+                [source-defaults]
+                poll-interval-limits = {{ min = 5, max = 8 }}
+
+                [observability]
+                log-level = "warn"
+                "#
+            ),
+        )
+        .unwrap();
 
-    #[test]
-    fn duration_not_nan() {
-        #[derive(Debug, Deserialize)]
-        struct Helper {
-            #[expect(unused)]
-            duration: NtpDuration,
+        let config = from_drop_in_test_dir(&dir, PermissionCheck::Skip).unwrap();
+        let printed = config.to_toml().unwrap();
+
+        // Files are shown by their path, never by their contents
+        assert!(printed.contains(certificate_authority));
+        assert!(printed.contains("/etc/ntpd-rs/key.pem"));
+        assert!(!printed.contains("BEGIN CERTIFICATE"));
+
+        // Pool authentication tokens are secret, only their number is shown
+        assert!(!printed.contains("pool-secret"));
+        assert_eq!(printed.matches(server::REDACTED_TOKEN).count(), 2);
+
+        let reparsed: Config = toml::from_str(&printed).unwrap();
+        assert_eq!(reparsed.sources, config.sources);
+        assert_eq!(reparsed.servers, config.servers);
+        let mut redacted_nts_ke = config.nts_ke.clone();
+        for nts_ke in &mut redacted_nts_ke {
+            nts_ke.accepted_pool_authentication_tokens =
+                vec![server::REDACTED_TOKEN.to_string(); 2];
         }
+        assert_eq!(reparsed.nts_ke, redacted_nts_ke);
+        assert_eq!(reparsed.synchronization, config.synchronization);
+        assert_eq!(reparsed.source_defaults, config.source_defaults);
+        assert_eq!(reparsed.dns, config.dns);
+        assert_eq!(reparsed.keyset, config.keyset);
+        assert_eq!(reparsed.to_toml().unwrap(), printed);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 
-        let result: Result<Helper, _> = toml::from_str(
-            r#"
-            duration = nan
-            "#,
-        );
-
-        let error = result.unwrap_err();
-        assert!(error.to_string().contains("expected a valid number"));
+    #[test]
+    fn config_permission_check_can_be_disabled() {
+        let path = std::env::temp_dir().join(format!(
+            "ntp-test-config-{}.toml",
+            crate::test::alloc_port()
+        ));
+        std::fs::write(&path, "[[server]]\nlisten = \"[::]:123\"\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+        assert!(Config::warn_unrestricted_permissions(&path).unwrap());
+
+        let captured_logs = |permission_check| {
+            let buffer = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let subscriber = tracing_subscriber::fmt()
+                .with_writer(buffer.clone())
+                .with_ansi(false)
+                .finish();
+            let config = tracing::subscriber::with_default(subscriber, || {
+                Config::from_file(&path, permission_check).unwrap()
+            });
+            // The file is read either way
+            assert_eq!(config.servers.len(), 1);
+            String::from_utf8(buffer.lock().unwrap().clone()).unwrap()
+        };
+
+        let warning = "Unrestricted config file permissions";
+        assert!(captured_logs(PermissionCheck::Warn).contains(warning));
+        assert!(!captured_logs(PermissionCheck::Skip).contains(warning));
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert!(!Config::warn_unrestricted_permissions(&path).unwrap());
+
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn step_threshold_not_nan() {
-        #[derive(Debug, Deserialize)]
-        struct Helper {
-            #[expect(unused)]
-            threshold: StepThreshold,
-        }
+    fn config_permission_check_lenient() {
+        let path = std::env::temp_dir().join(format!(
+            "ntp-test-config-{}.toml",
+            crate::test::alloc_port()
+        ));
+        std::fs::write(&path, "[[server]]\nlisten = \"[::]:123\"\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+        // By default unrestricted permissions only result in a warning
+        let config = Config::from_file(&path, PermissionCheck::Warn).unwrap();
+        assert_eq!(config.servers.len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
 
-        let result: Result<Helper, _> = toml::from_str(
-            r#"
-            threshold = nan
-            "#,
-        );
+    #[test]
+    fn config_permission_check_strict() {
+        let path = std::env::temp_dir().join(format!(
+            "ntp-test-config-{}.toml",
+            crate::test::alloc_port()
+        ));
+        std::fs::write(&path, "[[server]]\nlisten = \"[::]:123\"\n").unwrap();
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666)).unwrap();
+        assert!(matches!(
+            Config::from_file(&path, PermissionCheck::Strict),
+            Err(ConfigError::UnrestrictedPermissions(p)) if p == path
+        ));
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let config = Config::from_file(&path, PermissionCheck::Strict).unwrap();
+        assert_eq!(config.servers.len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
 
-        let error = result.unwrap_err();
-        assert!(error.to_string().contains("expected a positive number"));
+    #[test]
+    fn config_permission_check_strict_world_writable() {
+        let path = std::env::temp_dir().join(format!(
+            "ntp-test-config-{}.toml",
+            crate::test::alloc_port()
+        ));
+        std::fs::write(&path, "[[server]]\nlisten = \"[::]:123\"\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+        let load = |arguments: &[&str]| {
+            let options = NtpDaemonOptions::try_parse_from(arguments).unwrap();
+            Config::from_args(
+                options.config.as_ref(),
+                vec![],
+                vec![],
+                options.permission_check(),
+            )
+        };
+        let path_arg = path.to_str().unwrap();
+
+        // With the strict check the daemon refuses the world-writable file
+        // given on the command line, instead of falling back to the defaults
+        let error = load(&[
+            "/usr/bin/ntp-daemon",
+            "-c",
+            path_arg,
+            "--strict-config-perm-check",
+        ])
+        .unwrap_err();
+        assert!(matches!(&error, ConfigError::UnrestrictedPermissions(p) if *p == path));
+        assert!(error.to_string().contains(path_arg));
+
+        let config = load(&["/usr/bin/ntp-daemon", "-c", path_arg]).unwrap();
+        assert_eq!(config.servers.len(), 1);
+
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn deny_unknown_fields() {
-        let config: Result<SynchronizationConfig, _> = toml::from_str(
-            r#"
-            unknown-field = 42
-            "#,
-        );
+    fn config_permission_check_strict_drop_in() {
+        let dir = drop_in_test_dir();
+        let drop_in = dir.join("conf.d/10-extra.toml");
+        std::fs::write(&drop_in, "[observability]\nlog-level = \"info\"\n").unwrap();
+        std::fs::set_permissions(&drop_in, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+        assert!(from_drop_in_test_dir(&dir, PermissionCheck::Warn).is_ok());
+        assert!(matches!(
+            from_drop_in_test_dir(&dir, PermissionCheck::Strict),
+            Err(ConfigError::UnrestrictedPermissions(p)) if p == drop_in
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 
-        let error = config.unwrap_err();
-        assert!(error.to_string().contains("unknown field"));
+    fn from_drop_in_test_dir(
+        dir: &Path,
+        permission_check: PermissionCheck,
+    ) -> Result<Config, ConfigError> {
+        Config::from_file_with_drop_ins(
+            dir.join("ntp.toml"),
+            Some(&dir.join("conf.d")),
+            permission_check,
+        )
     }
 
-    #[test]
+    fn drop_in_test_dir() -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("ntp-test-confd-{}", crate::test::alloc_port()));
+        std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+        std::fs::write(
+            dir.join("ntp.toml"),
+            r#"
+            [[source]]
+            mode = "server"
+            address = "example.com"
+
+            [synchronization]
+            minimum-agreeing-sources = 2
+            local-stratum = 5
+            "#,
+        )
+        .unwrap();
+        dir
+    }
+
+    #[test]
+    fn config_drop_in_empty_directory() {
+        let dir = drop_in_test_dir();
+
+        let config = from_drop_in_test_dir(&dir, PermissionCheck::Skip).unwrap();
+        assert_eq!(config.sources.len(), 1);
+        assert_eq!(
+            config
+                .synchronization
+                .synchronization_base
+                .minimum_agreeing_sources,
+            2
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn config_drop_in_adds_source() {
+        let dir = drop_in_test_dir();
+        std::fs::write(
+            dir.join("conf.d/10-extra.toml"),
+            "[[source]]\nmode = \"server\"\naddress = \"example.net\"\n",
+        )
+        .unwrap();
+        // files without the toml extension are ignored
+        std::fs::write(dir.join("conf.d/20-ignored.bak"), "this is not toml").unwrap();
+
+        let config = from_drop_in_test_dir(&dir, PermissionCheck::Skip).unwrap();
+        let addresses: Vec<_> = config
+            .sources
+            .iter()
+            .map(|source| match source {
+                NtpSourceConfig::Standard(config) => config.first.address.0.to_string(),
+                _ => panic!("unexpected source type"),
+            })
+            .collect();
+        assert_eq!(addresses, vec!["example.com:123", "example.net:123"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn config_drop_in_not_used_with_explicit_file() {
+        let dir = drop_in_test_dir();
+        std::fs::write(
+            dir.join("conf.d/10-extra.toml"),
+            "[[source]]\nmode = \"server\"\naddress = \"example.net\"\n",
+        )
+        .unwrap();
+
+        // Drop-ins only belong to the global configuration file
+        let config =
+            Config::from_first_file(Some(dir.join("ntp.toml")), PermissionCheck::Skip).unwrap();
+        assert_eq!(config.sources.len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn config_drop_in_overrides_synchronization() {
+        let dir = drop_in_test_dir();
+        std::fs::write(
+            dir.join("conf.d/10-first.toml"),
+            "[synchronization]\nminimum-agreeing-sources = 3\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("conf.d/20-second.toml"),
+            "[synchronization]\nminimum-agreeing-sources = 1\n",
+        )
+        .unwrap();
+
+        let config = from_drop_in_test_dir(&dir, PermissionCheck::Skip).unwrap();
+        // later drop-ins take precedence
+        assert_eq!(
+            config
+                .synchronization
+                .synchronization_base
+                .minimum_agreeing_sources,
+            1
+        );
+        // keys not set in a drop-in are kept from the base file
+        assert_eq!(config.synchronization.synchronization_base.local_stratum, 5);
+        assert_eq!(config.sources.len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn toml_sources_invalid() {
+        let config: Result<Config, _> = toml::from_str(
+            r#"
+            [[source]]
+            mode = "server"
+            address = ":invalid:ipv6:123"
+            "#,
+        );
+
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn toml_allow_no_sources() {
+        let config: Result<Config, _> = toml::from_str(
+            r#"
+            [[server]]
+            listen = "[::]:123"
+            "#,
+        );
+
+        assert!(config.is_ok());
+        assert!(config.unwrap().check());
+    }
+
+    #[test]
+    fn toml_monitor_only_sources_not_counted() {
+        let config: Config = toml::from_str(
+            r#"
+            [[source]]
+            mode = "server"
+            address = "example.com"
+
+            [[source]]
+            mode = "server"
+            address = "example.net"
+            monitor-only = true
+
+            [synchronization]
+            minimum-agreeing-sources = 2
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.count_sources(), 1);
+        assert!(!config.check());
+
+        let config: Config = toml::from_str(
+            r#"
+            [[source]]
+            mode = "pool"
+            address = "pool.example.com"
+            count = 4
+            monitor-only = false
+
+            [[source]]
+            mode = "server"
+            address = "example.net"
+
+            [source-defaults]
+            monitor-only = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.count_sources(), 4);
+    }
+
+    #[test]
+    fn toml_source_group_quorum() {
+        let quorum = r#"
+            [synchronization]
+            minimum-agreeing-sources = 1
+            quorum = { gps = 2, internet = 1 }
+            "#;
+        let gps = r#"
+            [[source]]
+            mode = "sock"
+            path = "/run/gps0.sock"
+            precision = 0.1
+            group = "gps"
+
+            [[source]]
+            mode = "sock"
+            path = "/run/gps1.sock"
+            precision = 0.1
+            group = "gps"
+            "#;
+        let internet = r#"
+            [[source]]
+            mode = "pool"
+            address = "pool.example.com"
+            group = "internet"
+            "#;
+
+        let config: Config = toml::from_str(&format!("{gps}{internet}{quorum}")).unwrap();
+        let sync = config.synchronization.synchronization_base;
+        let groups: Vec<_> = sync
+            .quorum
+            .groups()
+            .map(|(group, minimum)| (group.to_string(), minimum))
+            .collect();
+        assert_eq!(groups, vec![("gps".into(), 2), ("internet".into(), 1)]);
+        assert_eq!(
+            config.count_group_sources(SourceGroup::new("gps").unwrap()),
+            2
+        );
+        assert_eq!(
+            config.count_group_sources(SourceGroup::new("internet").unwrap()),
+            4
+        );
+        assert!(config.check());
+
+        // Either group missing makes the quorum unreachable
+        let config: Config = toml::from_str(&format!("{gps}{quorum}")).unwrap();
+        assert!(!config.check());
+        let config: Config = toml::from_str(&format!("{internet}{quorum}")).unwrap();
+        assert!(!config.check());
+
+        // As do too few sources in a group
+        let config: Config = toml::from_str(&format!(
+            "{internet}{quorum}
+            [[source]]
+            mode = \"sock\"
+            path = \"/run/gps0.sock\"
+            precision = 0.1
+            group = \"gps\"
+            "
+        ))
+        .unwrap();
+        assert!(!config.check());
+
+        // Group names are bounded in length
+        let config: Result<Config, _> = toml::from_str(&format!(
+            "{gps}{internet}
+            [synchronization]
+            quorum = {{ a-very-long-group-name = 1 }}
+            "
+        ));
+        assert!(config.is_err());
+    }
+
+    #[cfg(feature = "pps")]
+    #[test]
+    fn toml_standalone_reference_clock() {
+        let reference_clock = r#"
+            [[source]]
+            mode = "sock"
+            path = "/run/chrony.ttyAMA0.sock"
+            precision = 0.1
+
+            [[source]]
+            mode = "pps"
+            path = "/dev/pps0"
+            precision = 1e-7
+            "#;
+
+        let config: Config = toml::from_str(reference_clock).unwrap();
+        assert_eq!(config.count_sources(), 1);
+        assert!(!config.check());
+
+        let config: Config = toml::from_str(&format!(
+            "{reference_clock}
+            [synchronization]
+            standalone-reference-clock = true
+            "
+        ))
+        .unwrap();
+        assert!(config.check());
+
+        // Without a sock source, there is nothing to provide the time
+        let config: Config = toml::from_str(
+            r#"
+            [[source]]
+            mode = "pps"
+            path = "/dev/pps0"
+            precision = 1e-7
+
+            [synchronization]
+            standalone-reference-clock = true
+            "#,
+        )
+        .unwrap();
+        assert!(!config.check());
+    }
+
+    #[test]
+    fn toml_pool_static_addresses_family() {
+        let config: Config = toml::from_str(
+            r#"
+            [[source]]
+            mode = "pool"
+            address = "pool.example.com"
+            address-family = "ipv4"
+            addresses = ["192.0.2.1:123", "192.0.2.2:123"]
+            "#,
+        )
+        .unwrap();
+        assert!(config.check());
+
+        let config: Config = toml::from_str(
+            r#"
+            [[source]]
+            mode = "pool"
+            address = "pool.example.com"
+            address-family = "ipv4"
+            addresses = ["192.0.2.1:123", "[2001:db8::1]:123"]
+            "#,
+        )
+        .unwrap();
+        assert!(!config.check());
+
+        let config: Config = toml::from_str(
+            r#"
+            [[source]]
+            mode = "pool"
+            address = "pool.example.com"
+            addresses = ["192.0.2.1:123", "[2001:db8::1]:123"]
+            "#,
+        )
+        .unwrap();
+        assert!(config.check());
+    }
+
+    #[test]
+    fn toml_server_interface_listen_address() {
+        let config: Config = toml::from_str(
+            r#"
+            [[server]]
+            listen = "[::]:123"
+            interface = "enp0s31f6"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.servers.len(), 1);
+
+        let config: Result<Config, _> = toml::from_str(
+            r#"
+            [[server]]
+            listen = "192.0.2.1:123"
+            interface = "enp0s31f6"
+            "#,
+        );
+        let error = config.unwrap_err();
+        assert!(error.to_string().contains("0.0.0.0:123"));
+    }
+
+    #[test]
+    fn toml_shared_server_address() {
+        let config: Config = toml::from_str(
+            r#"
+            [[server]]
+            listen = "0.0.0.0:123"
+
+            [[server]]
+            listen = "0.0.0.0:123"
+            reuse-port = true
+            "#,
+        )
+        .unwrap();
+        assert!(!config.check());
+
+        let config: Config = toml::from_str(
+            r#"
+            [[server]]
+            listen = "0.0.0.0:123"
+            reuse-port = true
+
+            [[server]]
+            listen = "0.0.0.0:123"
+            reuse-port = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.check(), cfg!(target_os = "linux"));
+    }
+
+    #[test]
+    fn toml_panic_threshold_recovery() {
+        let config = |recovery: f64| -> Config {
+            toml::from_str(&format!(
+                "
+                [synchronization]
+                single-step-panic-threshold = 10
+                panic-threshold-recovery = {recovery:?}
+                "
+            ))
+            .unwrap()
+        };
+
+        assert!(config(5.0).check());
+        // The recovery bound has to be below the threshold it recovers from
+        assert!(!config(10.0).check());
+        assert!(!config(20.0).check());
+    }
+
+    #[test]
+    fn toml_per_source_poll_interval() {
+        let config: Config = toml::from_str(
+            r#"
+            [[source]]
+            mode = "server"
+            address = "lan.example.com"
+
+            [[source]]
+            mode = "server"
+            address = "wan.example.com"
+            poll-interval-limits = { min = 6, max = 12 }
+            initial-poll-interval = 7
+
+            [source-defaults]
+            poll-interval-limits = { min = 3, max = 6 }
+            initial-poll-interval = 4
+            "#,
+        )
+        .unwrap();
+
+        let effective: Vec<_> = config
+            .sources
+            .iter()
+            .map(|source| {
+                let NtpSourceConfig::Standard(source) = source else {
+                    panic!("Unexpected source type");
+                };
+                source.second.clone().with_defaults(config.source_defaults)
+            })
+            .collect();
+
+        assert_eq!(effective[0].poll_interval_limits.min.as_log(), 3);
+        assert_eq!(effective[0].poll_interval_limits.max.as_log(), 6);
+        assert_eq!(effective[0].initial_poll_interval.as_log(), 4);
+
+        assert_eq!(effective[1].poll_interval_limits.min.as_log(), 6);
+        assert_eq!(effective[1].poll_interval_limits.max.as_log(), 12);
+        assert_eq!(effective[1].initial_poll_interval.as_log(), 7);
+    }
+
+    #[test]
+    fn toml_restart_required_changes() {
+        let config: Config = toml::from_str(
+            r#"
+            [[source]]
+            mode = "server"
+            address = "example.com"
+
+            [synchronization]
+            minimum-agreeing-sources = 2
+            "#,
+        )
+        .unwrap();
+
+        // Sources can be changed without a restart
+        let reloaded: Config = toml::from_str(
+            r#"
+            [[source]]
+            mode = "server"
+            address = "example.net"
+
+            [synchronization]
+            minimum-agreeing-sources = 2
+            "#,
+        )
+        .unwrap();
+        assert!(config.restart_required_changes(&reloaded).is_empty());
+
+        let reloaded: Config = toml::from_str(
+            r#"
+            [[source]]
+            mode = "server"
+            address = "example.com"
+
+            [synchronization]
+            minimum-agreeing-sources = 3
+
+            [source-defaults]
+            poll-interval-limits = { min = 5, max = 9 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.restart_required_changes(&reloaded),
+            vec!["synchronization", "source-defaults"]
+        );
+
+        let reloaded: Config = toml::from_str(
+            r#"
+            [[source]]
+            mode = "server"
+            address = "example.com"
+
+            [[nts-ke-server]]
+            listen = "0.0.0.0:4460"
+            certificate-chain-path = "/foo/bar/baz.pem"
+            private-key-path = "spam.der"
+
+            [synchronization]
+            minimum-agreeing-sources = 2
+
+            [observability]
+            log-level = "debug"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.restart_required_changes(&reloaded),
+            vec!["nts-ke-server", "observability"]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn toml_restart_required_changes_csptp() {
+        let config: Config = toml::from_str("").unwrap();
+        let reloaded: Config = toml::from_str(
+            r#"
+            [csptp]
+            priority_1 = 64
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.restart_required_changes(&reloaded), vec!["csptp"]);
+    }
+
+    #[test]
+    fn privileged_server_ports() {
+        let config: Config = toml::from_str(
+            r#"
+            [[server]]
+            listen = "[::]:123"
+            [[server]]
+            listen = "0.0.0.0:1123"
+            [[server]]
+            listen = "127.0.0.1:0"
+            "#,
+        )
+        .unwrap();
+
+        let privileged: Vec<_> = config.privileged_listen_addrs(1024).collect();
+        assert_eq!(privileged, vec!["[::]:123".parse().unwrap()]);
+
+        // Lowering the unprivileged port range makes every port usable
+        assert_eq!(config.privileged_listen_addrs(0).count(), 0);
+        assert_eq!(config.privileged_listen_addrs(2000).count(), 2);
+    }
+
+    #[test]
+    fn net_bind_service_capability() {
+        let status = "Name:\tntp-daemon\nCapInh:\t0000000000000000\nCapEff:\t0000000000000400\n";
+        assert_eq!(has_net_bind_service(status), Some(true));
+
+        let status = "Name:\tntp-daemon\nCapEff:\t0000000000000000\n";
+        assert_eq!(has_net_bind_service(status), Some(false));
+
+        let status = "Name:\tntp-daemon\nCapEff:\t000001ffffffffff\n";
+        assert_eq!(has_net_bind_service(status), Some(true));
+
+        assert_eq!(has_net_bind_service("Name:\tntp-daemon\n"), None);
+    }
+
+    #[test]
+    fn system_config_accumulated_threshold() {
+        let config: Result<SynchronizationConfig, _> = toml::from_str(
+            r#"
+            accumulated-step-panic-threshold = 0
+            "#,
+        );
+
+        let config = config.unwrap();
+        assert!(config.accumulated_step_panic_threshold.is_none());
+
+        let config: Result<SynchronizationConfig, _> = toml::from_str(
+            r#"
+            accumulated-step-panic-threshold = 1000
+            "#,
+        );
+
+        let config = config.unwrap();
+        assert_eq!(
+            config.accumulated_step_panic_threshold,
+            Some(NtpDuration::from_seconds(1000.0))
+        );
+    }
+
+    #[test]
+    fn system_config_startup_panic_threshold() {
+        let config: Result<SynchronizationConfig, _> = toml::from_str(
+            r#"
+            startup-step-panic-threshold = { forward = 10, backward = 20 }
+            "#,
+        );
+
+        let config = config.unwrap();
+        assert_eq!(
+            config.startup_step_panic_threshold.forward,
+            Some(NtpDuration::from_seconds(10.0))
+        );
+        assert_eq!(
+            config.startup_step_panic_threshold.backward,
+            Some(NtpDuration::from_seconds(20.0))
+        );
+    }
+
+    #[test]
+    fn system_config_leap_smear() {
+        let config: SynchronizationConfig = toml::from_str("").unwrap();
+        assert_eq!(config.leap_smear, None);
+
+        let config: SynchronizationConfig = toml::from_str("leap-smear = 86400").unwrap();
+        assert_eq!(config.leap_smear, Some(Duration::from_secs(86400)));
+
+        let config: SynchronizationConfig = toml::from_str("leap-smear = 0.5").unwrap();
+        assert_eq!(config.leap_smear, Some(Duration::from_millis(500)));
+
+        for invalid in ["0", "-3600", "86401", "inf"] {
+            let config: Result<SynchronizationConfig, _> =
+                toml::from_str(&format!("leap-smear = {invalid}"));
+            assert!(config.is_err(), "{invalid} should be rejected");
+        }
+    }
+
+    #[test]
+    fn system_config_min_clock_update_interval() {
+        let config: SynchronizationConfig = toml::from_str("").unwrap();
+        assert_eq!(config.min_clock_update_interval, None);
+
+        let config: SynchronizationConfig =
+            toml::from_str("min-clock-update-interval = 64").unwrap();
+        assert_eq!(
+            config.min_clock_update_interval,
+            Some(Duration::from_secs(64))
+        );
+
+        for invalid in ["0", "-1", "inf"] {
+            let config: Result<SynchronizationConfig, _> =
+                toml::from_str(&format!("min-clock-update-interval = {invalid}"));
+            assert!(config.is_err(), "{invalid} should be rejected");
+        }
+    }
+
+    #[test]
+    fn system_config_max_slew_rate() {
+        let config: SynchronizationConfig = toml::from_str("").unwrap();
+        assert_eq!(config.max_slew_rate_ppm, None);
+
+        let config: SynchronizationConfig = toml::from_str("max-slew-rate-ppm = 50").unwrap();
+        assert_eq!(config.max_slew_rate_ppm, Some(50.0));
+
+        for invalid in ["0", "-10", "inf", "nan"] {
+            let config: Result<SynchronizationConfig, _> =
+                toml::from_str(&format!("max-slew-rate-ppm = {invalid}"));
+            assert!(config.is_err(), "{invalid} should be rejected");
+        }
+    }
+
+    #[test]
+    fn duration_not_nan() {
+        #[derive(Debug, Deserialize)]
+        struct Helper {
+            #[expect(unused)]
+            duration: NtpDuration,
+        }
+
+        let result: Result<Helper, _> = toml::from_str(
+            r#"
+            duration = nan
+            "#,
+        );
+
+        let error = result.unwrap_err();
+        assert!(error.to_string().contains("expected a valid number"));
+    }
+
+    #[test]
+    fn step_threshold_not_nan() {
+        #[derive(Debug, Deserialize)]
+        struct Helper {
+            #[expect(unused)]
+            threshold: StepThreshold,
+        }
+
+        let result: Result<Helper, _> = toml::from_str(
+            r#"
+            threshold = nan
+            "#,
+        );
+
+        let error = result.unwrap_err();
+        assert!(error.to_string().contains("expected a positive number"));
+    }
+
+    #[test]
+    fn deny_unknown_fields() {
+        let config: Result<SynchronizationConfig, _> = toml::from_str(
+            r#"
+            unknown-field = 42
+            "#,
+        );
+
+        let error = config.unwrap_err();
+        assert!(error.to_string().contains("unknown field"));
+    }
+
+    #[test]
     fn clock_config() {
         let config: Result<ClockConfig, _> = toml::from_str(
             r#"
@@ -982,4 +3010,163 @@ mod tests {
         assert_eq!(config.synchronization_base.minimum_agreeing_sources, 2);
         assert_eq!(config.algorithm.initial_wander, 1e-7);
     }
+
+    #[test]
+    fn startup_source_retry_schedule() {
+        // By default sources are retried every second, indefinitely
+        let retry = StartupSourceRetryConfig::default();
+        assert_eq!(retry.max_attempts, None);
+        for attempts in [1, 2, 10, u32::MAX] {
+            assert_eq!(retry.retry_delay(attempts), Duration::from_secs(1));
+        }
+
+        let config: Config = toml::from_str(
+            r#"
+            [startup-source-retry]
+            interval-ms = 500
+            backoff-factor = 2.0
+            max-attempts = 8
+            "#,
+        )
+        .unwrap();
+        let retry = config.startup_source_retry;
+        assert_eq!(retry.max_attempts, Some(8));
+        assert_eq!(retry.retry_delay(1), Duration::from_millis(500));
+        assert_eq!(retry.retry_delay(2), Duration::from_secs(1));
+        assert_eq!(retry.retry_delay(3), Duration::from_secs(2));
+        assert_eq!(retry.retry_delay(4), Duration::from_secs(4));
+
+        // The backoff is bounded, also when it would overflow
+        assert_eq!(retry.retry_delay(20), MAX_STARTUP_RETRY_INTERVAL);
+        assert_eq!(retry.retry_delay(u32::MAX), MAX_STARTUP_RETRY_INTERVAL);
+
+        for factor in ["0.5", "100.0", "nan"] {
+            let config: Result<StartupSourceRetryConfig, _> =
+                toml::from_str(&format!("backoff-factor = {factor}"));
+            assert!(config.is_err(), "{factor}");
+        }
+    }
+
+    #[test]
+    fn dns_cache_duration() {
+        let config: Config = toml::from_str(
+            r#"
+            [dns]
+            timeout-ms = 2000
+            min-ttl = 60
+            max-ttl = 600
+            "#,
+        )
+        .unwrap();
+        assert!(config.check());
+        let dns = config.dns;
+        assert_eq!(dns.timeout(), Duration::from_secs(2));
+        assert_eq!(dns.cache_duration(None), Duration::from_secs(60));
+        assert_eq!(
+            dns.cache_duration(Some(Duration::from_secs(10))),
+            Duration::from_secs(60)
+        );
+        assert_eq!(
+            dns.cache_duration(Some(Duration::from_secs(300))),
+            Duration::from_secs(300)
+        );
+        assert_eq!(
+            dns.cache_duration(Some(Duration::from_secs(86400))),
+            Duration::from_secs(600)
+        );
+
+        // By default, results of the system resolver are not cached
+        assert_eq!(DnsConfig::default().cache_duration(None), Duration::ZERO);
+
+        // The minimum wins when the bounds are inconsistent
+        let dns = DnsConfig {
+            timeout_ms: 5000,
+            min_ttl: 60,
+            max_ttl: 30,
+        };
+        assert_eq!(
+            dns.cache_duration(Some(Duration::from_secs(300))),
+            Duration::from_secs(60)
+        );
+
+        // Lookups need time to complete
+        let config: Config = toml::from_str("[dns]\ntimeout-ms = 0").unwrap();
+        assert!(!config.check());
+    }
+
+    #[test]
+    fn metrics_exporter_listen_config() {
+        let config: ObservabilityConfig = toml::from_str("").unwrap();
+        assert_eq!(
+            config.metrics_exporter_listen,
+            MetricsExporterListen::Tcp("127.0.0.1:9975".parse().unwrap())
+        );
+        assert_eq!(config.metrics_exporter_permissions, 0o666);
+
+        let config: ObservabilityConfig =
+            toml::from_str(r#"metrics-exporter-listen = "[::1]:9000""#).unwrap();
+        assert_eq!(
+            config.metrics_exporter_listen,
+            MetricsExporterListen::Tcp("[::1]:9000".parse().unwrap())
+        );
+
+        let config: ObservabilityConfig = toml::from_str(
+            r#"
+            metrics-exporter-listen = { unix = "/run/ntpd-rs/metrics.sock" }
+            metrics-exporter-permissions = 0o660
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.metrics_exporter_listen,
+            MetricsExporterListen::Unix(PathBuf::from("/run/ntpd-rs/metrics.sock"))
+        );
+        assert_eq!(config.metrics_exporter_permissions, 0o660);
+
+        let config: Result<ObservabilityConfig, _> =
+            toml::from_str(r#"metrics-exporter-listen = "/run/ntpd-rs/metrics.sock""#);
+        assert!(config.is_err());
+
+        let config: Result<ObservabilityConfig, _> =
+            toml::from_str(r#"metrics-exporter-listen = { tcp = "127.0.0.1:9975" }"#);
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn histogram_buckets_config() {
+        let config: ObservabilityConfig =
+            toml::from_str("jitter-histogram-buckets = [0.0, 1e-6, 1e-3]").unwrap();
+        assert_eq!(config.jitter_histogram_buckets, vec![0.0, 1e-6, 1e-3]);
+        assert_eq!(config.offset_histogram_buckets, DEFAULT_HISTOGRAM_BUCKETS);
+
+        for buckets in [
+            "[]",
+            "[0.1, 0.01]",
+            "[0.1, 0.1]",
+            "[-0.1, 0.1]",
+            "[0.1, inf]",
+        ] {
+            let config: Result<ObservabilityConfig, _> =
+                toml::from_str(&format!("offset-histogram-buckets = {buckets}"));
+            assert!(config.is_err(), "{buckets} should be rejected");
+        }
+    }
+
+    #[test]
+    fn clock_history_length_config() {
+        let config: ObservabilityConfig = toml::from_str("clock-history-length = 0").unwrap();
+        assert_eq!(config.clock_history_length, 0);
+
+        let config: ObservabilityConfig = toml::from_str(&format!(
+            "clock-history-length = {MAX_CLOCK_HISTORY_LENGTH}"
+        ))
+        .unwrap();
+        assert_eq!(config.clock_history_length, MAX_CLOCK_HISTORY_LENGTH);
+
+        let config: Result<ObservabilityConfig, _> = toml::from_str(&format!(
+            "clock-history-length = {}",
+            MAX_CLOCK_HISTORY_LENGTH + 1
+        ));
+        assert!(config.is_err());
+    }
 }
@@ -5,17 +5,24 @@ use std::time::Duration;
 use std::{
     fmt,
     net::{IpAddr, SocketAddr},
+    num::NonZeroU64,
     ops::Deref,
     path::PathBuf,
+    str::FromStr,
     sync::Arc,
 };
 
-use ntp_proto::{PollInterval, PollIntervalLimits, SourceConfig};
+use ntp_proto::{
+    FilterAlgorithm, NtpDuration, PollInterval, PollIntervalLimits, ReferenceId,
+    RefidMismatchAction, SourceConfig, SourceGroup, deserialize_dscp, deserialize_expected_refid,
+    deserialize_poll_jitter, deserialize_reach_window, serialize_expected_refid,
+};
 use ntp_proto::{ProtocolVersion, tls_utils::Certificate};
 use serde::{
-    Deserialize, Deserializer,
+    Deserialize, Deserializer, Serialize, Serializer,
     de::{self, Visitor},
 };
+use timestamped_socket::interface::InterfaceName;
 
 use super::super::keyexchange::certificates_from_file;
 
@@ -64,65 +71,155 @@ where
     deserializer.deserialize_any(ProtocolVersionVisitor)
 }
 
+#[allow(
+    clippy::trivially_copy_pass_by_ref,
+    reason = "Signature required by serde"
+)]
+fn serialize_ntp_version<S>(version: &ProtocolVersion, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match version {
+        ProtocolVersion::V4 => serializer.serialize_u8(4),
+        ProtocolVersion::V5 => serializer.serialize_u8(5),
+        ProtocolVersion::V4UpgradingToV5 { .. } | ProtocolVersion::UpgradedToV5 => {
+            serializer.serialize_str("auto")
+        }
+    }
+}
+
 fn default_ntp_version() -> ProtocolVersion {
     ProtocolVersion::V4
 }
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+/// Address family to which the resolved addresses of a source are limited
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+    #[default]
+    Any,
+}
+
+impl AddressFamily {
+    pub fn matches(self, addr: &SocketAddr) -> bool {
+        match self {
+            AddressFamily::Ipv4 => addr.is_ipv4(),
+            AddressFamily::Ipv6 => addr.is_ipv6(),
+            AddressFamily::Any => true,
+        }
+    }
+}
+
+impl fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressFamily::Ipv4 => f.write_str("IPv4"),
+            AddressFamily::Ipv6 => f.write_str("IPv6"),
+            AddressFamily::Any => f.write_str("any"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct StandardSource {
     pub address: NtpAddress,
     #[serde(
         default = "default_ntp_version",
-        deserialize_with = "deserialize_ntp_version"
+        deserialize_with = "deserialize_ntp_version",
+        serialize_with = "serialize_ntp_version"
     )]
     pub ntp_version: ProtocolVersion,
+    #[serde(default)]
+    pub prefer: bool,
+    /// Only connect to resolved addresses of this family
+    #[serde(default)]
+    pub address_family: Option<AddressFamily>,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct NtsSourceConfig {
     pub address: NtsKeAddress,
     #[serde(default)]
     pub enable_srv_resolution: bool,
-    #[serde(
-        deserialize_with = "deserialize_certificate_authorities",
-        default = "default_certificate_authorities",
-        rename = "certificate-authority"
-    )]
-    pub certificate_authorities: Arc<[Certificate]>,
+    #[serde(default, rename = "certificate-authority")]
+    pub certificate_authorities: CertificateAuthorities,
     #[serde(
         default = "default_ntp_version",
-        deserialize_with = "deserialize_ntp_version"
+        deserialize_with = "deserialize_ntp_version",
+        serialize_with = "serialize_ntp_version"
     )]
     pub ntp_version: ProtocolVersion,
+    #[serde(default)]
+    pub prefer: bool,
+    #[serde(default)]
+    pub require_authenticated: bool,
+    /// Time to wait before retrying after a first failed key exchange. The
+    /// time doubles with each further failure.
+    #[serde(default = "default_ke_retry_interval_ms")]
+    pub ke_retry_interval_ms: u64,
+    /// Upper bound on the time between key exchange retries
+    #[serde(default = "default_ke_retry_max_interval_ms")]
+    pub ke_retry_max_interval_ms: u64,
+    /// Only connect to NTP servers of this family
+    #[serde(default)]
+    pub address_family: Option<AddressFamily>,
 }
 
-fn deserialize_certificate_authorities<'de, D>(
-    deserializer: D,
-) -> Result<Arc<[Certificate]>, D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let certificate_path: PathBuf = PathBuf::deserialize(deserializer)?;
-    match certificates_from_file(&certificate_path) {
-        Ok(certificates) => Ok(Arc::from(certificates)),
-        Err(io_error) => {
-            let msg = format!(
-                "error while parsing certificate file {}: {:?}",
-                certificate_path.display(),
-                io_error
-            );
-            Err(de::Error::custom(msg))
+const fn default_ke_retry_interval_ms() -> u64 {
+    1000
+}
+
+const fn default_ke_retry_max_interval_ms() -> u64 {
+    5 * 60 * 1000
+}
+
+/// Additional certificate authorities trusted for NTS key exchange, together
+/// with the file they were loaded from
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CertificateAuthorities {
+    pub path: Option<PathBuf>,
+    pub certificates: Arc<[Certificate]>,
+}
+
+impl<'de> Deserialize<'de> for CertificateAuthorities {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let certificate_path: PathBuf = PathBuf::deserialize(deserializer)?;
+        match certificates_from_file(&certificate_path) {
+            Ok(certificates) => Ok(CertificateAuthorities {
+                path: Some(certificate_path),
+                certificates: Arc::from(certificates),
+            }),
+            Err(io_error) => {
+                let msg = format!(
+                    "error while parsing certificate file {}: {:?}",
+                    certificate_path.display(),
+                    io_error
+                );
+                Err(de::Error::custom(msg))
+            }
         }
     }
 }
 
-fn default_certificate_authorities() -> Arc<[Certificate]> {
-    Arc::from([])
+// The certificates themselves are not serialized, only the file they were
+// loaded from
+impl Serialize for CertificateAuthorities {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.path.serialize(serializer)
+    }
 }
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct PoolSourceConfig {
     #[serde(rename = "address")]
@@ -133,42 +230,113 @@ pub struct PoolSourceConfig {
     pub ignore: Vec<IpAddr>,
     #[serde(
         default = "default_ntp_version",
-        deserialize_with = "deserialize_ntp_version"
+        deserialize_with = "deserialize_ntp_version",
+        serialize_with = "serialize_ntp_version"
     )]
     pub ntp_version: ProtocolVersion,
+    /// Seconds between re-resolutions of the pool address. Members that
+    /// vanished from DNS and are unreachable are replaced on each refresh.
+    #[serde(default)]
+    pub refresh_interval: Option<NonZeroU64>,
+    /// Only use resolved addresses of this family
+    #[serde(default)]
+    pub address_family: Option<AddressFamily>,
+    /// Static list of pool members. When non-empty, members are taken from
+    /// this list and the pool address is never resolved.
+    #[serde(default)]
+    pub addresses: Vec<SocketAddr>,
 }
 
 fn max_sources_default() -> usize {
     4
 }
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct BroadcastSourceConfig {
+    /// Address of the server whose broadcasts are used. Packets from any
+    /// other sender are ignored.
+    pub address: IpAddr,
+    /// Address on which broadcasts are received, by default port 123 on all
+    /// interfaces of the address family of the server.
+    #[serde(default)]
+    pub listen: Option<SocketAddr>,
+    /// Multicast group to join for receiving the broadcasts
+    #[serde(default, deserialize_with = "deserialize_multicast_group")]
+    pub group: Option<IpAddr>,
+    /// Expected standard deviation of the measurements in seconds
+    #[serde(default = "default_broadcast_precision")]
+    pub precision: f64,
+    /// Expected systematic error of the measurements in seconds
+    #[serde(default)]
+    pub accuracy: f64,
+}
+
+impl BroadcastSourceConfig {
+    pub fn listen_addr(&self) -> SocketAddr {
+        self.listen.unwrap_or_else(|| {
+            let unspecified = match self.address {
+                IpAddr::V4(_) => IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
+                IpAddr::V6(_) => IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED),
+            };
+            SocketAddr::new(unspecified, 123)
+        })
+    }
+}
+
+fn default_broadcast_precision() -> f64 {
+    1e-3
+}
+
+fn deserialize_multicast_group<'de, D>(deserializer: D) -> Result<Option<IpAddr>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let group = IpAddr::deserialize(deserializer)?;
+    if !group.is_multicast() {
+        return Err(de::Error::invalid_value(
+            de::Unexpected::Str(&group.to_string()),
+            &"a multicast address",
+        ));
+    }
+    Ok(Some(group))
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct NtsPoolSourceConfig {
     #[serde(rename = "address")]
     pub addr: NtsKeAddress,
     #[serde(default)]
     pub enable_srv_resolution: bool,
-    #[serde(
-        deserialize_with = "deserialize_certificate_authorities",
-        default = "default_certificate_authorities",
-        rename = "certificate-authority"
-    )]
-    pub certificate_authorities: Arc<[Certificate]>,
+    #[serde(default, rename = "certificate-authority")]
+    pub certificate_authorities: CertificateAuthorities,
     #[serde(default = "max_sources_default")]
     pub count: usize,
     #[serde(
         default = "default_ntp_version",
-        deserialize_with = "deserialize_ntp_version"
+        deserialize_with = "deserialize_ntp_version",
+        serialize_with = "serialize_ntp_version"
     )]
     pub ntp_version: ProtocolVersion,
+    /// Time to wait before retrying after a first failed key exchange. The
+    /// time doubles with each further failure.
+    #[serde(default = "default_ke_retry_interval_ms")]
+    pub ke_retry_interval_ms: u64,
+    /// Upper bound on the time between key exchange retries
+    #[serde(default = "default_ke_retry_max_interval_ms")]
+    pub ke_retry_max_interval_ms: u64,
+    /// Only connect to NTP servers of this family
+    #[serde(default)]
+    pub address_family: Option<AddressFamily>,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Serialize, Debug, PartialEq, Clone)]
 pub struct SockSourceConfig {
     pub path: PathBuf,
     pub precision: f64,
     pub accuracy: f64,
+    pub group: Option<SourceGroup>,
 }
 
 impl<'de> Deserialize<'de> for SockSourceConfig {
@@ -183,6 +351,7 @@ impl<'de> Deserialize<'de> for SockSourceConfig {
             Precision,
             Accuracy,
             MeasurementNoiseEstimate,
+            Group,
         }
 
         struct SockSourceConfigVisitor;
@@ -201,6 +370,7 @@ impl<'de> Deserialize<'de> for SockSourceConfig {
                 let mut path = None;
                 let mut precision = None;
                 let mut accuracy = None;
+                let mut group = None;
                 while let Some(key) = map.next_key()? {
                     match key {
                         Field::Path => {
@@ -209,6 +379,12 @@ impl<'de> Deserialize<'de> for SockSourceConfig {
                             }
                             path = Some(map.next_value()?);
                         }
+                        Field::Group => {
+                            if group.is_some() {
+                                return Err(de::Error::duplicate_field("group"));
+                            }
+                            group = Some(map.next_value()?);
+                        }
                         Field::MeasurementNoiseEstimate => {
                             tracing::warn!(
                                 "The measurement_noise_estimate field is deprecated. Please switch to using the precision field"
@@ -265,6 +441,7 @@ impl<'de> Deserialize<'de> for SockSourceConfig {
                     path,
                     precision,
                     accuracy,
+                    group,
                 })
             }
         }
@@ -274,19 +451,34 @@ impl<'de> Deserialize<'de> for SockSourceConfig {
             "precision",
             "accuracy",
             "measurement_noise_estimate",
+            "group",
         ];
         deserializer.deserialize_struct("SockSourceConfig", FIELDS, SockSourceConfigVisitor)
     }
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
 #[serde(deny_unknown_fields)]
 pub struct PartialPollIntervalLimits {
     pub min: Option<PollInterval>,
     pub max: Option<PollInterval>,
 }
 
-#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+fn deserialize_option_poll_jitter<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_poll_jitter(deserializer).map(Some)
+}
+
+fn deserialize_option_reach_window<'de, D>(deserializer: D) -> Result<Option<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_reach_window(deserializer).map(Some)
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct PartialSourceConfig {
     /// Minima and maxima for the poll interval of clients
@@ -295,6 +487,62 @@ pub struct PartialSourceConfig {
 
     /// Initial poll interval of the system
     pub initial_poll_interval: Option<PollInterval>,
+
+    /// Maximum jitter for the source to be used in disciplining the clock
+    pub max_jitter_for_discipline: Option<NtpDuration>,
+
+    /// DSCP value with which outgoing packets to the source are marked
+    #[serde(default, deserialize_with = "deserialize_dscp")]
+    pub dscp: Option<u8>,
+
+    /// Fraction of the poll interval by which polls are randomized
+    #[serde(default, deserialize_with = "deserialize_option_poll_jitter")]
+    pub poll_jitter: Option<f64>,
+
+    /// Ignore longer poll intervals requested by NTPv5 servers
+    pub ignore_server_poll: Option<bool>,
+
+    /// Reference id the source is expected to report
+    #[serde(
+        default,
+        deserialize_with = "deserialize_expected_refid",
+        serialize_with = "serialize_expected_refid"
+    )]
+    pub expected_refid: Option<ReferenceId>,
+
+    /// Action taken when the source reports a different reference id
+    pub on_refid_mismatch: Option<RefidMismatchAction>,
+
+    /// Only monitor the source, never using it for synchronization
+    pub monitor_only: Option<bool>,
+
+    /// Difference between the delay of requests and responses
+    pub delay_asymmetry: Option<NtpDuration>,
+
+    /// Maximum round trip delay of a measurement
+    pub max_round_trip: Option<NtpDuration>,
+
+    /// Lowest stratum the source may advertise
+    pub min_acceptable_stratum: Option<u8>,
+
+    /// Request interleaved responses from NTPv4 servers
+    pub interleaved: Option<bool>,
+
+    /// Number of recent polls considered for reachability
+    #[serde(default, deserialize_with = "deserialize_option_reach_window")]
+    pub reach_window: Option<u8>,
+
+    /// Keep the most recent raw packets exchanged with the source
+    pub capture_packets: Option<bool>,
+
+    /// Filter used to estimate the offset of the source
+    pub filter_algorithm: Option<FilterAlgorithm>,
+
+    /// Measurements needed before the source can be selected
+    pub warmup_samples: Option<u8>,
+
+    /// Group the source belongs to, for the synchronization quorum
+    pub group: Option<SourceGroup>,
 }
 
 impl PartialSourceConfig {
@@ -313,11 +561,35 @@ impl PartialSourceConfig {
             initial_poll_interval: self
                 .initial_poll_interval
                 .unwrap_or(defaults.initial_poll_interval),
+            max_jitter_for_discipline: self
+                .max_jitter_for_discipline
+                .or(defaults.max_jitter_for_discipline),
+            dscp: self.dscp.or(defaults.dscp),
+            poll_jitter: self.poll_jitter.unwrap_or(defaults.poll_jitter),
+            ignore_server_poll: self
+                .ignore_server_poll
+                .unwrap_or(defaults.ignore_server_poll),
+            expected_refid: self.expected_refid.or(defaults.expected_refid),
+            on_refid_mismatch: self.on_refid_mismatch.unwrap_or(defaults.on_refid_mismatch),
+            monitor_only: self.monitor_only.unwrap_or(defaults.monitor_only),
+            delay_asymmetry: self.delay_asymmetry.unwrap_or(defaults.delay_asymmetry),
+            max_round_trip: self.max_round_trip.or(defaults.max_round_trip),
+            min_acceptable_stratum: self
+                .min_acceptable_stratum
+                .or(defaults.min_acceptable_stratum),
+            interleaved: self.interleaved.unwrap_or(defaults.interleaved),
+            reach_window: self.reach_window.unwrap_or(defaults.reach_window),
+            capture_packets: self.capture_packets.unwrap_or(defaults.capture_packets),
+            filter_algorithm: self.filter_algorithm.unwrap_or(defaults.filter_algorithm),
+            warmup_samples: self.warmup_samples.unwrap_or(defaults.warmup_samples),
+            prefer: defaults.prefer,
+            group: self.group.or(defaults.group),
+            require_authenticated: defaults.require_authenticated,
         }
     }
 }
 
-#[derive(Deserialize, Debug, PartialEq, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
 #[serde(deny_unknown_fields)]
 pub struct FlattenedPair<T, U> {
     #[serde(flatten)]
@@ -327,14 +599,24 @@ pub struct FlattenedPair<T, U> {
 }
 
 #[cfg(target_os = "linux")]
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Serialize, Debug, PartialEq, Clone)]
 pub struct CsptpSourceConfig {
     pub address: String,
     pub domain: u8,
+    #[serde(serialize_with = "serialize_seconds")]
     pub poll_interval: Duration,
+    #[serde(serialize_with = "serialize_seconds")]
     pub response_interval: Duration,
 }
 
+#[cfg(target_os = "linux")]
+fn serialize_seconds<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
 #[cfg(target_os = "linux")]
 impl<'de> Deserialize<'de> for CsptpSourceConfig {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -442,7 +724,7 @@ impl<'de> Deserialize<'de> for CsptpSourceConfig {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Serialize, Debug, PartialEq, Clone)]
 pub struct PpsSourceConfig {
     pub path: PathBuf,
     pub precision: f64,
@@ -571,7 +853,7 @@ impl<'de> Deserialize<'de> for PpsSourceConfig {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 #[serde(tag = "mode")]
 pub enum NtpSourceConfig {
     #[serde(rename = "server")]
@@ -582,6 +864,8 @@ pub enum NtpSourceConfig {
     Pool(FlattenedPair<PoolSourceConfig, PartialSourceConfig>),
     #[serde(rename = "nts-pool")]
     NtsPool(FlattenedPair<NtsPoolSourceConfig, PartialSourceConfig>),
+    #[serde(rename = "broadcast")]
+    Broadcast(FlattenedPair<BroadcastSourceConfig, PartialSourceConfig>),
     #[serde(rename = "sock")]
     Sock(SockSourceConfig),
     #[cfg(feature = "pps")]
@@ -600,6 +884,10 @@ pub struct NormalizedAddress {
     pub(crate) server_name: String,
     pub(crate) port: u16,
 
+    /// Scope (interface index) of a link-local IPv6 address, e.g. `fe80::1%eth0`
+    #[serde(skip)]
+    pub(crate) scope_id: Option<u32>,
+
     /// Used to inject socket address into the DNS lookup result
     #[cfg(test)]
     #[serde(skip)]
@@ -610,7 +898,9 @@ impl Eq for NormalizedAddress {}
 
 impl PartialEq for NormalizedAddress {
     fn eq(&self, other: &Self) -> bool {
-        self.server_name == other.server_name && self.port == other.port
+        self.server_name == other.server_name
+            && self.port == other.port
+            && self.scope_id == other.scope_id
     }
 }
 
@@ -676,6 +966,24 @@ impl<'de> Deserialize<'de> for NtsKeAddress {
     }
 }
 
+impl Serialize for NtpAddress {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl Serialize for NtsKeAddress {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(&self.0)
+    }
+}
+
 impl From<NormalizedAddress> for NtpAddress {
     fn from(addr: NormalizedAddress) -> Self {
         Self(addr)
@@ -710,11 +1018,13 @@ impl NormalizedAddress {
 
     /// Specifically, this adds the `:123` port if no port is specified
     pub(crate) fn from_string_ntp(address: String) -> std::io::Result<Self> {
-        let (server_name, port) = Self::from_string_help(address, Self::NTP_DEFAULT_PORT)?;
+        let (server_name, port, scope_id) =
+            Self::from_string_help(address, Self::NTP_DEFAULT_PORT)?;
 
         Ok(Self {
             server_name,
             port,
+            scope_id,
 
             #[cfg(test)]
             hardcoded_dns_resolve: None,
@@ -723,27 +1033,34 @@ impl NormalizedAddress {
 
     /// Specifically, this adds the `:4460` port if no port is specified
     fn from_string_nts_ke(address: String) -> std::io::Result<Self> {
-        let (server_name, port) = Self::from_string_help(address, Self::NTS_KE_DEFAULT_PORT)?;
+        let (server_name, port, scope_id) =
+            Self::from_string_help(address, Self::NTS_KE_DEFAULT_PORT)?;
 
         Ok(Self {
             server_name,
             port,
+            scope_id,
 
             #[cfg(test)]
             hardcoded_dns_resolve: None,
         })
     }
 
-    fn from_string_help(address: String, default_port: u16) -> std::io::Result<(String, u16)> {
+    fn from_string_help(
+        address: String,
+        default_port: u16,
+    ) -> std::io::Result<(String, u16, Option<u32>)> {
         if address.split(':').count() > 2 {
-            // IPv6, try to parse it as such
+            // IPv6, strip the scope as the parser only understands numeric
+            // scopes, then try to parse it as such
+            let (address, scope_id) = Self::split_scope(&address)?;
             match address.parse::<SocketAddr>() {
-                Ok(socket_addr) => Ok((socket_addr.ip().to_string(), socket_addr.port())),
+                Ok(socket_addr) => Ok((socket_addr.ip().to_string(), socket_addr.port(), scope_id)),
                 Err(e) => {
                     // Could be because of no port, add one and see
                     let address_with_port = format!("[{address}]:{default_port}");
                     if let Ok(socket_addr) = address_with_port.parse::<SocketAddr>() {
-                        Ok((socket_addr.ip().to_string(), socket_addr.port()))
+                        Ok((socket_addr.ip().to_string(), socket_addr.port(), scope_id))
                     } else {
                         Err(std::io::Error::other(e))
                     }
@@ -754,13 +1071,48 @@ impl NormalizedAddress {
             // check whether the host is valid, but at least check that
             // the port is.
             match port.parse::<u16>() {
-                Ok(port) => Ok((server_name.to_string(), port)),
+                Ok(port) => Ok((server_name.to_string(), port, None)),
                 Err(e) => Err(std::io::Error::other(e)),
             }
         } else {
             // Not ipv6 and no port. As we cant reasonably check host
             // so just append a port
-            Ok((address, default_port))
+            Ok((address, default_port, None))
+        }
+    }
+
+    /// Split the `%scope` part off an IPv6 address (with or without brackets
+    /// and port), resolving interface names to their index
+    fn split_scope(address: &str) -> std::io::Result<(String, Option<u32>)> {
+        let Some((start, rest)) = address.split_once('%') else {
+            return Ok((address.to_string(), None));
+        };
+        let (scope, end) = rest.split_at(rest.find(']').unwrap_or(rest.len()));
+
+        let scope_id = match scope.parse::<u32>() {
+            Ok(scope_id) => scope_id,
+            Err(_) => InterfaceName::from_str(scope)
+                .ok()
+                .and_then(|interface| interface.get_index())
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("unknown interface '{scope}' in address '{address}'"),
+                    )
+                })?,
+        };
+
+        Ok((format!("{start}{end}"), Some(scope_id)))
+    }
+
+    /// Set our scope on link-local IPv6 addresses resulting from a lookup
+    pub(crate) fn with_scope_id(&self, addr: SocketAddr) -> SocketAddr {
+        match (addr, self.scope_id) {
+            (SocketAddr::V6(mut addr), Some(scope_id)) => {
+                addr.set_scope_id(scope_id);
+                SocketAddr::V6(addr)
+            }
+            _ => addr,
         }
     }
 
@@ -768,6 +1120,7 @@ impl NormalizedAddress {
         Self {
             server_name: server_name.to_string(),
             port,
+            scope_id: None,
 
             #[cfg(test)]
             hardcoded_dns_resolve: None,
@@ -783,6 +1136,7 @@ impl NormalizedAddress {
         Self {
             server_name: server_name.to_string(),
             port,
+            scope_id: None,
             hardcoded_dns_resolve: Some(HardcodedDnsResolve::from(hardcoded_dns_resolve)),
         }
     }
@@ -806,20 +1160,50 @@ impl NormalizedAddress {
             }
         }
 
-        #[cfg(test)]
-        if let Some(hardcoded_dns_resolve) = &self.hardcoded_dns_resolve {
-            return Ok(Either::Hardcoded(hardcoded_dns_resolve.lookup_host()));
+        let addresses = 'lookup: {
+            #[cfg(test)]
+            if let Some(hardcoded_dns_resolve) = &self.hardcoded_dns_resolve {
+                break 'lookup Either::Hardcoded(hardcoded_dns_resolve.lookup_host());
+            }
+
+            Either::Lookup(
+                crate::daemon::dns::lookup_host(self.server_name.as_str(), self.port)
+                    .await?
+                    .into_iter(),
+            )
+        };
+
+        Ok(addresses.map(|addr| self.with_scope_id(addr)))
+    }
+
+    /// Resolve the address, keeping only addresses of the given family. A
+    /// name without any addresses of that family is a resolution failure.
+    pub async fn lookup_host_in_family(
+        &self,
+        family: AddressFamily,
+    ) -> std::io::Result<Vec<SocketAddr>> {
+        let addresses: Vec<_> = self
+            .lookup_host()
+            .await?
+            .filter(|addr| family.matches(addr))
+            .collect();
+
+        if addresses.is_empty() && family != AddressFamily::Any {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{} has no {family} addresses", self.server_name),
+            ));
         }
 
-        tokio::net::lookup_host((self.server_name.as_str(), self.port))
-            .await
-            .map(Either::Lookup)
+        Ok(addresses)
     }
 }
 
 impl std::fmt::Display for NormalizedAddress {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.server_name.contains(':') {
+        if let Some(scope_id) = self.scope_id {
+            write!(f, "[{}%{}]:{}", self.server_name, scope_id, self.port)
+        } else if self.server_name.contains(':') {
             write!(f, "[{}]:{}", self.server_name, self.port)
         } else {
             write!(f, "{}:{}", self.server_name, self.port)
@@ -834,10 +1218,31 @@ impl TryFrom<&str> for StandardSource {
         Ok(Self {
             address: NormalizedAddress::from_string_ntp(value.to_string())?.into(),
             ntp_version: default_ntp_version(),
+            prefer: false,
+            address_family: None,
         })
     }
 }
 
+impl NtpSourceConfig {
+    /// Poll interval limits configured on this source, for the kinds of
+    /// sources that are polled
+    pub(crate) fn poll_interval_limits_mut(&mut self) -> Option<&mut PartialPollIntervalLimits> {
+        match self {
+            NtpSourceConfig::Standard(config) => Some(&mut config.second.poll_interval_limits),
+            NtpSourceConfig::Nts(config) => Some(&mut config.second.poll_interval_limits),
+            NtpSourceConfig::Pool(config) => Some(&mut config.second.poll_interval_limits),
+            NtpSourceConfig::NtsPool(config) => Some(&mut config.second.poll_interval_limits),
+            NtpSourceConfig::Broadcast(config) => Some(&mut config.second.poll_interval_limits),
+            NtpSourceConfig::Sock(_) => None,
+            #[cfg(feature = "pps")]
+            NtpSourceConfig::Pps(_) => None,
+            #[cfg(target_os = "linux")]
+            NtpSourceConfig::Csptp(_) => None,
+        }
+    }
+}
+
 impl<'a> TryFrom<&'a str> for NtpSourceConfig {
     type Error = std::io::Error;
 
@@ -870,6 +1275,7 @@ mod tests {
             NtpSourceConfig::Nts(c) => c.first.address.to_string(),
             NtpSourceConfig::Pool(c) => c.first.addr.to_string(),
             NtpSourceConfig::NtsPool(c) => c.first.addr.to_string(),
+            NtpSourceConfig::Broadcast(c) => c.first.address.to_string(),
             NtpSourceConfig::Sock(_c) => String::new(),
             #[cfg(feature = "pps")]
             NtpSourceConfig::Pps(_c) => String::new(),
@@ -902,6 +1308,20 @@ mod tests {
         assert_eq!(source_addr(&test.source), "example.com:5678");
         assert!(matches!(test.source, NtpSourceConfig::Standard(_)));
 
+        let test: TestConfig = toml::from_str(
+            r#"
+            [source]
+            mode = "server"
+            address = "example.com"
+            address-family = "ipv4"
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::Standard(config) = test.source else {
+            panic!("Expected a server source");
+        };
+        assert_eq!(config.first.address_family, Some(AddressFamily::Ipv4));
+
         let test: TestConfig = toml::from_str(
             r#"
             [source]
@@ -940,8 +1360,116 @@ mod tests {
         assert_eq!(source_addr(&test.source), "example.com:123");
         if let NtpSourceConfig::Pool(config) = test.source {
             assert_eq!(config.first.count, 42);
+            assert_eq!(config.first.refresh_interval, None);
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [source]
+            address = "example.com"
+            mode = "pool"
+            refresh-interval = 3600
+            "#,
+        )
+        .unwrap();
+        if let NtpSourceConfig::Pool(config) = test.source {
+            assert_eq!(config.first.refresh_interval, NonZeroU64::new(3600));
+        }
+
+        let test: Result<TestConfig, _> = toml::from_str(
+            r#"
+            [source]
+            address = "example.com"
+            mode = "pool"
+            refresh-interval = 0
+            "#,
+        );
+        assert!(test.is_err());
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [source]
+            address = "example.com"
+            mode = "pool"
+            address-family = "ipv6"
+            "#,
+        )
+        .unwrap();
+        if let NtpSourceConfig::Pool(config) = test.source {
+            assert_eq!(config.first.address_family, Some(AddressFamily::Ipv6));
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [source]
+            address = "example.com"
+            mode = "pool"
+            addresses = ["192.0.2.1:123", "[2001:db8::1]:123"]
+            "#,
+        )
+        .unwrap();
+        if let NtpSourceConfig::Pool(config) = test.source {
+            assert_eq!(
+                config.first.addresses,
+                vec![
+                    "192.0.2.1:123".parse::<SocketAddr>().unwrap(),
+                    "[2001:db8::1]:123".parse().unwrap()
+                ]
+            );
+        }
+
+        let test: Result<TestConfig, _> = toml::from_str(
+            r#"
+            [source]
+            address = "example.com"
+            mode = "server"
+            address-family = "ipx"
+            "#,
+        );
+        assert!(test.is_err());
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [source]
+            address = "192.0.2.1"
+            mode = "broadcast"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(source_addr(&test.source), "192.0.2.1");
+        if let NtpSourceConfig::Broadcast(config) = test.source {
+            assert_eq!(config.first.listen_addr(), "0.0.0.0:123".parse().unwrap());
+            assert_eq!(config.first.group, None);
+        } else {
+            panic!("Expected a broadcast source");
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [source]
+            address = "2001:db8::1"
+            mode = "broadcast"
+            group = "ff05::101"
+            "#,
+        )
+        .unwrap();
+        if let NtpSourceConfig::Broadcast(config) = test.source {
+            assert_eq!(config.first.listen_addr(), "[::]:123".parse().unwrap());
+            assert_eq!(config.first.group, Some("ff05::101".parse().unwrap()));
+        } else {
+            panic!("Expected a broadcast source");
         }
 
+        let test: Result<TestConfig, _> = toml::from_str(
+            r#"
+            [source]
+            address = "192.0.2.1"
+            mode = "broadcast"
+            group = "192.0.2.255"
+            "#,
+        );
+        assert!(test.is_err());
+
         let test: TestConfig = toml::from_str(
             r#"
             [source]
@@ -953,6 +1481,34 @@ mod tests {
         assert!(matches!(test.source, NtpSourceConfig::Nts(_)));
         assert_eq!(source_addr(&test.source), "example.com:4460");
 
+        let test: TestConfig = toml::from_str(
+            r#"
+            [source]
+            address = "example.com"
+            mode = "nts"
+            address-family = "ipv4"
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::Nts(config) = test.source else {
+            panic!("Expected an nts source");
+        };
+        assert_eq!(config.first.address_family, Some(AddressFamily::Ipv4));
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [source]
+            address = "example.com"
+            mode = "nts-pool"
+            address-family = "ipv6"
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::NtsPool(config) = test.source else {
+            panic!("Expected an nts-pool source");
+        };
+        assert_eq!(config.first.address_family, Some(AddressFamily::Ipv6));
+
         {
             let test: TestConfig = toml::from_str(
                 r#"
@@ -1083,16 +1639,426 @@ mod tests {
     }
 
     #[test]
-    fn test_sock_config_parsing() {
-        let TestConfig {
-            source: NtpSourceConfig::Sock(test),
-        } = toml::from_str(
+    fn test_source_dscp_parsing() {
+        let test: TestConfig = toml::from_str(
             r#"
                 [source]
-                mode = "sock"
-                path = "/test/path"
-                measurement_noise_estimate = 0.0625
-            "#,
+                mode = "server"
+                address = "example.com"
+                dscp = 46
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::Standard(source) = test.source else {
+            panic!("Unexpected source type");
+        };
+        assert_eq!(source.second.dscp, Some(46));
+        assert_eq!(
+            source.second.with_defaults(SourceConfig::default()).dscp,
+            Some(46)
+        );
+
+        let test: Result<TestConfig, _> = toml::from_str(
+            r#"
+                [source]
+                mode = "server"
+                address = "example.com"
+                dscp = 64
+            "#,
+        );
+        assert!(test.is_err());
+    }
+
+    #[test]
+    fn test_source_poll_jitter_parsing() {
+        let test: TestConfig = toml::from_str(
+            r#"
+                [source]
+                mode = "server"
+                address = "example.com"
+                poll-jitter = 0.2
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::Standard(source) = test.source else {
+            panic!("Unexpected source type");
+        };
+        assert_eq!(source.second.poll_jitter, Some(0.2));
+        assert_eq!(
+            source
+                .second
+                .with_defaults(SourceConfig::default())
+                .poll_jitter,
+            0.2
+        );
+
+        for invalid in ["-0.1", "0.6"] {
+            let test: Result<TestConfig, _> = toml::from_str(&format!(
+                r#"
+                    [source]
+                    mode = "server"
+                    address = "example.com"
+                    poll-jitter = {invalid}
+                "#,
+            ));
+            assert!(test.is_err());
+        }
+    }
+
+    #[test]
+    fn test_source_warmup_samples_parsing() {
+        let test: TestConfig = toml::from_str(
+            r#"
+                [source]
+                mode = "server"
+                address = "example.com"
+                warmup-samples = 4
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::Standard(source) = test.source else {
+            panic!("Unexpected source type");
+        };
+        assert_eq!(source.second.warmup_samples, Some(4));
+
+        let defaults = SourceConfig {
+            warmup_samples: 2,
+            ..SourceConfig::default()
+        };
+        assert_eq!(source.second.with_defaults(defaults).warmup_samples, 4);
+        assert_eq!(
+            PartialSourceConfig::default()
+                .with_defaults(defaults)
+                .warmup_samples,
+            2
+        );
+    }
+
+    #[test]
+    fn test_source_reach_window_parsing() {
+        let test: TestConfig = toml::from_str(
+            r#"
+                [source]
+                mode = "server"
+                address = "example.com"
+                reach-window = 3
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::Standard(source) = test.source else {
+            panic!("Unexpected source type");
+        };
+        assert_eq!(source.second.reach_window, Some(3));
+        assert_eq!(
+            source
+                .second
+                .with_defaults(SourceConfig::default())
+                .reach_window,
+            3
+        );
+        assert_eq!(SourceConfig::default().reach_window, 8);
+
+        for invalid in ["0", "9"] {
+            let test: Result<TestConfig, _> = toml::from_str(&format!(
+                r#"
+                    [source]
+                    mode = "server"
+                    address = "example.com"
+                    reach-window = {invalid}
+                "#,
+            ));
+            assert!(test.is_err());
+        }
+    }
+
+    #[test]
+    fn test_source_min_acceptable_stratum_parsing() {
+        let test: TestConfig = toml::from_str(
+            r#"
+                [source]
+                mode = "server"
+                address = "example.com"
+                min-acceptable-stratum = 2
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::Standard(source) = test.source else {
+            panic!("Unexpected source type");
+        };
+        assert_eq!(source.second.min_acceptable_stratum, Some(2));
+        assert_eq!(
+            source
+                .second
+                .with_defaults(SourceConfig::default())
+                .min_acceptable_stratum,
+            Some(2)
+        );
+        assert_eq!(SourceConfig::default().min_acceptable_stratum, None);
+    }
+
+    #[test]
+    fn test_source_capture_packets_parsing() {
+        let test: TestConfig = toml::from_str(
+            r#"
+                [source]
+                mode = "server"
+                address = "example.com"
+                capture-packets = true
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::Standard(source) = test.source else {
+            panic!("Unexpected source type");
+        };
+        assert_eq!(source.second.capture_packets, Some(true));
+        assert!(
+            source
+                .second
+                .with_defaults(SourceConfig::default())
+                .capture_packets
+        );
+        assert!(!SourceConfig::default().capture_packets);
+    }
+
+    #[test]
+    fn test_source_filter_algorithm_parsing() {
+        let test: TestConfig = toml::from_str(
+            r#"
+                [source]
+                mode = "server"
+                address = "example.com"
+                filter-algorithm = "median"
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::Standard(source) = test.source else {
+            panic!("Unexpected source type");
+        };
+        assert_eq!(
+            source.second.filter_algorithm,
+            Some(FilterAlgorithm::Median)
+        );
+        assert_eq!(
+            source
+                .second
+                .with_defaults(SourceConfig::default())
+                .filter_algorithm,
+            FilterAlgorithm::Median
+        );
+        assert_eq!(
+            SourceConfig::default().filter_algorithm,
+            FilterAlgorithm::Kalman
+        );
+
+        let test = toml::from_str::<TestConfig>(
+            r#"
+                [source]
+                mode = "server"
+                address = "example.com"
+                filter-algorithm = "average"
+            "#,
+        );
+        assert!(test.is_err());
+    }
+
+    #[test]
+    fn test_source_ignore_server_poll_parsing() {
+        let test: TestConfig = toml::from_str(
+            r#"
+                [source]
+                mode = "server"
+                address = "example.com"
+                ignore-server-poll = true
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::Standard(source) = test.source else {
+            panic!("Unexpected source type");
+        };
+        assert!(
+            source
+                .second
+                .with_defaults(SourceConfig::default())
+                .ignore_server_poll
+        );
+    }
+
+    #[test]
+    fn test_source_expected_refid_parsing() {
+        let parse = |refid: &str| {
+            let test: TestConfig = toml::from_str(&format!(
+                r#"
+                    [source]
+                    mode = "server"
+                    address = "example.com"
+                    expected-refid = "{refid}"
+                "#,
+            ))?;
+            let NtpSourceConfig::Standard(source) = test.source else {
+                panic!("Unexpected source type");
+            };
+            Ok::<_, toml::de::Error>(source.second.expected_refid)
+        };
+
+        assert_eq!(
+            parse("GPS").unwrap(),
+            Some(ReferenceId::from_ip("71.80.83.0".parse().unwrap()))
+        );
+        assert_eq!(
+            parse("192.0.2.1").unwrap(),
+            Some(ReferenceId::from_ip("192.0.2.1".parse().unwrap()))
+        );
+        assert!(parse("").is_err());
+        assert!(parse("TOOLONG").is_err());
+    }
+
+    #[test]
+    fn test_source_on_refid_mismatch_parsing() {
+        let parse = |action: &str| {
+            let test: TestConfig = toml::from_str(&format!(
+                r#"
+                    [source]
+                    mode = "server"
+                    address = "example.com"
+                    expected-refid = "GPS"
+                    {action}
+                "#,
+            ))?;
+            let NtpSourceConfig::Standard(source) = test.source else {
+                panic!("Unexpected source type");
+            };
+            Ok::<_, toml::de::Error>(source.second.on_refid_mismatch)
+        };
+
+        assert_eq!(parse("").unwrap(), None);
+        assert_eq!(
+            parse(r#"on-refid-mismatch = "warn""#).unwrap(),
+            Some(RefidMismatchAction::Warn)
+        );
+        assert_eq!(
+            parse(r#"on-refid-mismatch = "demote""#).unwrap(),
+            Some(RefidMismatchAction::Demote)
+        );
+        assert!(parse(r#"on-refid-mismatch = "ignore""#).is_err());
+
+        let partial = PartialSourceConfig {
+            on_refid_mismatch: Some(RefidMismatchAction::Warn),
+            ..Default::default()
+        };
+        assert_eq!(
+            partial
+                .with_defaults(SourceConfig::default())
+                .on_refid_mismatch,
+            RefidMismatchAction::Warn
+        );
+    }
+
+    #[test]
+    fn test_source_prefer_parsing() {
+        let test: TestConfig = toml::from_str(
+            r#"
+                [source]
+                mode = "server"
+                address = "example.com"
+                prefer = true
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::Standard(source) = test.source else {
+            panic!("Unexpected source type");
+        };
+        assert!(source.first.prefer);
+
+        // Preference only makes sense for individual sources
+        let test: Result<TestConfig, _> = toml::from_str(
+            r#"
+                [source]
+                mode = "pool"
+                address = "example.com"
+                prefer = true
+            "#,
+        );
+        assert!(test.is_err());
+    }
+
+    #[test]
+    fn test_source_require_authenticated_parsing() {
+        let test: TestConfig = toml::from_str(
+            r#"
+                [source]
+                mode = "nts"
+                address = "example.com"
+                require-authenticated = true
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::Nts(source) = test.source else {
+            panic!("Unexpected source type");
+        };
+        assert!(source.first.require_authenticated);
+
+        // Without NTS there is no authentication to require
+        let test: Result<TestConfig, _> = toml::from_str(
+            r#"
+                [source]
+                mode = "server"
+                address = "example.com"
+                require-authenticated = true
+            "#,
+        );
+        assert!(test.is_err());
+
+        // Broadcasts are never authenticated, so such a source could never
+        // be used
+        let test: Result<TestConfig, _> = toml::from_str(
+            r#"
+                [source]
+                mode = "broadcast"
+                address = "192.0.2.1"
+                require-authenticated = true
+            "#,
+        );
+        assert!(test.is_err());
+    }
+
+    #[test]
+    fn test_source_monitor_only_parsing() {
+        let test: TestConfig = toml::from_str(
+            r#"
+                [source]
+                mode = "server"
+                address = "example.com"
+                monitor-only = true
+            "#,
+        )
+        .unwrap();
+        let NtpSourceConfig::Standard(source) = test.source else {
+            panic!("Unexpected source type");
+        };
+        assert!(
+            source
+                .second
+                .with_defaults(SourceConfig::default())
+                .monitor_only
+        );
+        assert!(
+            !PartialSourceConfig::default()
+                .with_defaults(SourceConfig::default())
+                .monitor_only
+        );
+    }
+
+    #[test]
+    fn test_sock_config_parsing() {
+        let TestConfig {
+            source: NtpSourceConfig::Sock(test),
+        } = toml::from_str(
+            r#"
+                [source]
+                mode = "sock"
+                path = "/test/path"
+                measurement_noise_estimate = 0.0625
+            "#,
         )
         .unwrap()
         else {
@@ -1352,5 +2318,77 @@ mod tests {
         assert_eq!(addr.to_string(), "127.0.0.1:123");
         let addr = NormalizedAddress::from_string_ntp("1234567890.example.com".into()).unwrap();
         assert_eq!(addr.to_string(), "1234567890.example.com:123");
+        assert_eq!(addr.scope_id, None);
+    }
+
+    #[test]
+    fn test_normalize_addr_scope() {
+        let addr = NormalizedAddress::from_string_ntp("fe80::1%2".into()).unwrap();
+        assert_eq!(addr.server_name, "fe80::1");
+        assert_eq!(addr.scope_id, Some(2));
+        assert_eq!(addr.to_string(), "[fe80::1%2]:123");
+        let addr = NormalizedAddress::from_string_ntp("[fe80::1%2]:456".into()).unwrap();
+        assert_eq!(addr.scope_id, Some(2));
+        assert_eq!(addr.to_string(), "[fe80::1%2]:456");
+
+        #[cfg(target_os = "linux")]
+        {
+            let addr = NormalizedAddress::from_string_ntp("fe80::1%lo".into()).unwrap();
+            assert_eq!(addr.server_name, "fe80::1");
+            assert_eq!(addr.port, 123);
+            assert!(addr.scope_id.is_some());
+        }
+
+        assert!(NormalizedAddress::from_string_ntp("fe80::1%doesnotexist0".into()).is_err());
+        assert!(NormalizedAddress::from_string_ntp("[fe80::1%doesnotexist0]:123".into()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_lookup_host_in_family() {
+        let addr = NormalizedAddress::with_hardcoded_dns(
+            "example.com",
+            123,
+            vec![
+                "192.0.2.1:123".parse().unwrap(),
+                "[2001:db8::1]:123".parse().unwrap(),
+            ],
+        );
+
+        let resolved = addr
+            .lookup_host_in_family(AddressFamily::Ipv4)
+            .await
+            .unwrap();
+        assert_eq!(resolved, vec!["192.0.2.1:123".parse().unwrap()]);
+        let resolved = addr
+            .lookup_host_in_family(AddressFamily::Ipv6)
+            .await
+            .unwrap();
+        assert_eq!(resolved, vec!["[2001:db8::1]:123".parse().unwrap()]);
+        let resolved = addr
+            .lookup_host_in_family(AddressFamily::Any)
+            .await
+            .unwrap();
+        assert_eq!(resolved.len(), 2);
+
+        let addr = NormalizedAddress::with_hardcoded_dns(
+            "example.com",
+            123,
+            vec!["192.0.2.1:123".parse().unwrap()],
+        );
+        let err = addr
+            .lookup_host_in_family(AddressFamily::Ipv6)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_lookup_keeps_scope() {
+        let addr = NormalizedAddress::from_string_ntp("[fe80::1%2]:123".into()).unwrap();
+        let SocketAddr::V6(resolved) = addr.lookup_host().await.unwrap().next().unwrap() else {
+            panic!("Expected an IPv6 address");
+        };
+        assert_eq!(resolved.scope_id(), 2);
+        assert_eq!(resolved.port(), 123);
     }
 }
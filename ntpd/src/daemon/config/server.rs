@@ -1,14 +1,20 @@
 use std::{
     net::{AddrParseError, SocketAddr},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
     time::Duration,
 };
 
-use ntp_proto::{FilterAction, FilterList, NtpVersion};
-use serde::{Deserialize, Deserializer};
+use ntp_proto::{
+    FilterAction, FilterList, MAX_COOKIE_SIZE, MAX_COOKIES, NtpDuration, NtpVersion,
+    NtsCookieConfig, NtsNtpEndpoint, ReferenceIdFilterList, deserialize_dscp,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use timestamped_socket::interface::InterfaceName;
+
+use super::{deserialize_interface, serialize_interface};
 
-#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct KeysetConfig {
     /// Number of old keys to keep around
@@ -45,7 +51,7 @@ fn default_stale_key_count() -> usize {
 // We need to implement better binding for CSPTP servers, both to addresses and
 // specific interfaces, once we are further with the algorithm stuff.
 #[cfg(target_os = "linux")]
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum Interface {
     #[default]
@@ -53,13 +59,13 @@ pub enum Interface {
 }
 
 #[cfg(target_os = "linux")]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct CsptpServerConfig {
     pub interface: Interface,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct ServerConfig {
     pub listen: SocketAddr,
@@ -68,20 +74,130 @@ pub struct ServerConfig {
     #[serde(default = "default_allowlist")]
     pub allowlist: FilterList,
     #[serde(default)]
+    pub reference_id_denylist: Option<ReferenceIdFilterList>,
+    #[serde(default)]
     pub rate_limiting_cache_size: usize,
     #[serde(
         default,
         rename = "rate-limiting-cutoff-ms",
-        deserialize_with = "deserialize_rate_limiting_cutoff"
+        deserialize_with = "deserialize_rate_limiting_cutoff",
+        serialize_with = "serialize_rate_limiting_cutoff"
     )]
     pub rate_limiting_cutoff: Duration,
+    #[serde(
+        default,
+        rename = "nts-rate-limiting-cutoff-ms",
+        deserialize_with = "deserialize_nts_rate_limiting_cutoff",
+        serialize_with = "serialize_nts_rate_limiting_cutoff"
+    )]
+    pub nts_rate_limiting_cutoff: Option<Duration>,
+    /// Prefix length with which IPv4 clients share a rate limit
+    #[serde(
+        default = "default_rate_limiting_prefix_v4",
+        deserialize_with = "deserialize_rate_limiting_prefix_v4"
+    )]
+    pub rate_limiting_prefix_v4: u8,
+    /// Prefix length with which IPv6 clients share a rate limit
+    #[serde(
+        default = "default_rate_limiting_prefix_v6",
+        deserialize_with = "deserialize_rate_limiting_prefix_v6"
+    )]
+    pub rate_limiting_prefix_v6: u8,
     #[serde(default, deserialize_with = "deserialize_require_nts")]
     pub require_nts: Option<FilterAction>,
     #[serde(
         default = "default_accepted_ntp_versions",
-        deserialize_with = "deserialize_accepted_ntp_versions"
+        deserialize_with = "deserialize_accepted_ntp_versions",
+        serialize_with = "serialize_accepted_ntp_versions"
     )]
     pub accept_ntp_versions: Vec<NtpVersion>,
+    /// Bind the server socket to this interface (Linux only)
+    #[serde(
+        deserialize_with = "deserialize_interface",
+        serialize_with = "serialize_interface",
+        default
+    )]
+    pub interface: Option<InterfaceName>,
+    #[serde(default)]
+    pub echo_extension_fields: Vec<u16>,
+    /// DSCP value with which outgoing packets are marked
+    #[serde(default, deserialize_with = "deserialize_dscp")]
+    pub dscp: Option<u8>,
+    /// Size of the socket receive buffer (`SO_RCVBUF`) in bytes
+    #[serde(default)]
+    pub recv_buffer_size: Option<usize>,
+    /// Share the listen address with other sockets using SO_REUSEPORT (Linux only)
+    #[serde(default)]
+    pub reuse_port: bool,
+    /// Send responses from the address the request was sent to
+    #[serde(default = "default_respect_request_destination")]
+    pub respect_request_destination: bool,
+    /// Answer status queries from NTS authenticated clients
+    #[serde(default)]
+    pub allow_status_query: bool,
+    /// Cadence (in seconds) on which the served reference timestamp is refreshed
+    #[serde(
+        default = "default_reference_timestamp_interval",
+        deserialize_with = "deserialize_reference_timestamp_interval"
+    )]
+    pub reference_timestamp_interval: u32,
+    /// Warn when the clock has not been updated for longer than this
+    #[serde(default)]
+    pub max_reference_age: Option<NtpDuration>,
+    /// Answer requests while the local clock is not yet synchronized
+    #[serde(default = "default_serve_when_unsynchronized")]
+    pub serve_when_unsynchronized: bool,
+    /// Never answer unauthenticated requests with a larger response
+    #[serde(default)]
+    pub pad_responses: bool,
+    /// Answer symmetric active requests from legacy peers
+    #[serde(default)]
+    pub allow_symmetric_peers: bool,
+}
+
+/// A server bound to an interface listens on all addresses of that interface,
+/// so any specific address given in `listen` would silently be ignored.
+pub(super) fn deserialize_servers<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<ServerConfig>, D::Error> {
+    let servers = Vec::<ServerConfig>::deserialize(deserializer)?;
+    for server in &servers {
+        if let Some(interface) = server.interface
+            && !server.listen.ip().is_unspecified()
+        {
+            return Err(serde::de::Error::custom(format!(
+                "server bound to interface {interface} listens on all of its addresses, use an unspecified listen address (e.g. 0.0.0.0:{}) instead of {}",
+                server.listen.port(),
+                server.listen,
+            )));
+        }
+    }
+    Ok(servers)
+}
+
+fn default_serve_when_unsynchronized() -> bool {
+    true
+}
+
+fn default_reference_timestamp_interval() -> u32 {
+    128
+}
+
+fn deserialize_reference_timestamp_interval<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<u32, D::Error> {
+    let interval = u32::deserialize(deserializer)?;
+    if interval == 0 {
+        return Err(serde::de::Error::invalid_value(
+            serde::de::Unexpected::Unsigned(0),
+            &"a positive number of seconds",
+        ));
+    }
+    Ok(interval)
+}
+
+fn default_respect_request_destination() -> bool {
+    true
 }
 
 fn default_accepted_ntp_versions() -> Vec<NtpVersion> {
@@ -105,6 +221,13 @@ fn deserialize_accepted_ntp_versions<'de, D: Deserializer<'de>>(
         .collect::<Result<Vec<NtpVersion>, D::Error>>()
 }
 
+fn serialize_accepted_ntp_versions<S: Serializer>(
+    versions: &[NtpVersion],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(versions.iter().copied().map(NtpVersion::as_u8))
+}
+
 fn deserialize_require_nts<'de, D: Deserializer<'de>>(
     deserializer: D,
 ) -> Result<Option<FilterAction>, D::Error> {
@@ -162,7 +285,126 @@ fn default_allowlist() -> FilterList {
 fn deserialize_rate_limiting_cutoff<'de, D: Deserializer<'de>>(
     deserializer: D,
 ) -> Result<Duration, D::Error> {
-    Ok(Duration::from_millis(u64::deserialize(deserializer)?))
+    struct CutoffVisitor;
+    impl serde::de::Visitor<'_> for CutoffVisitor {
+        type Value = Duration;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str(
+                "a number of milliseconds, or a string with a `ms`, `s`, `m` or `h` suffix",
+            )
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(Duration::from_millis(v))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            let v = u64::try_from(v).map_err(|_| {
+                serde::de::Error::invalid_value(serde::de::Unexpected::Signed(v), &self)
+            })?;
+            self.visit_u64(v)
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            let invalid = || {
+                serde::de::Error::custom(format!(
+                    "invalid duration `{value}`, expected a number followed by one of the suffixes `ms`, `s`, `m` or `h`"
+                ))
+            };
+
+            // Check `ms` before `s`, as the latter is a suffix of the former
+            let (number, unit_millis) = if let Some(number) = value.strip_suffix("ms") {
+                (number, 1)
+            } else if let Some(number) = value.strip_suffix('s') {
+                (number, 1000)
+            } else if let Some(number) = value.strip_suffix('m') {
+                (number, 60 * 1000)
+            } else if let Some(number) = value.strip_suffix('h') {
+                (number, 60 * 60 * 1000)
+            } else {
+                return Err(invalid());
+            };
+
+            let number: u64 = number.trim().parse().map_err(|_| invalid())?;
+
+            number
+                .checked_mul(unit_millis)
+                .map(Duration::from_millis)
+                .ok_or_else(|| serde::de::Error::custom(format!("duration `{value}` is too large")))
+        }
+    }
+
+    deserializer.deserialize_any(CutoffVisitor)
+}
+
+// Cutoffs are always written as a number of milliseconds
+fn serialize_rate_limiting_cutoff<S: Serializer>(
+    cutoff: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(u64::try_from(cutoff.as_millis()).unwrap_or(u64::MAX))
+}
+
+fn default_rate_limiting_prefix_v4() -> u8 {
+    32
+}
+
+fn default_rate_limiting_prefix_v6() -> u8 {
+    128
+}
+
+fn deserialize_rate_limiting_prefix<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    max: u8,
+    expected: &'static str,
+) -> Result<u8, D::Error> {
+    let prefix = u8::deserialize(deserializer)?;
+    if prefix > max {
+        return Err(serde::de::Error::invalid_value(
+            serde::de::Unexpected::Unsigned(prefix.into()),
+            &expected,
+        ));
+    }
+    Ok(prefix)
+}
+
+fn deserialize_rate_limiting_prefix_v4<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<u8, D::Error> {
+    deserialize_rate_limiting_prefix(deserializer, 32, "a prefix length of at most 32")
+}
+
+fn deserialize_rate_limiting_prefix_v6<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<u8, D::Error> {
+    deserialize_rate_limiting_prefix(deserializer, 128, "a prefix length of at most 128")
+}
+
+fn deserialize_nts_rate_limiting_cutoff<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    deserialize_rate_limiting_cutoff(deserializer).map(Some)
+}
+
+#[allow(clippy::ref_option, reason = "Signature required by serde")]
+fn serialize_nts_rate_limiting_cutoff<S: Serializer>(
+    cutoff: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match cutoff {
+        Some(cutoff) => serialize_rate_limiting_cutoff(cutoff, serializer),
+        None => serializer.serialize_none(),
+    }
 }
 
 impl TryFrom<&str> for ServerConfig {
@@ -173,10 +415,26 @@ impl TryFrom<&str> for ServerConfig {
             listen: SocketAddr::from_str(value)?,
             denylist: default_denylist(),
             allowlist: default_allowlist(),
+            reference_id_denylist: None,
             rate_limiting_cache_size: 0,
             rate_limiting_cutoff: Duration::default(),
+            nts_rate_limiting_cutoff: None,
+            rate_limiting_prefix_v4: default_rate_limiting_prefix_v4(),
+            rate_limiting_prefix_v6: default_rate_limiting_prefix_v6(),
             require_nts: None,
             accept_ntp_versions: default_accepted_ntp_versions(),
+            interface: None,
+            echo_extension_fields: vec![],
+            dscp: None,
+            recv_buffer_size: None,
+            reuse_port: false,
+            respect_request_destination: default_respect_request_destination(),
+            allow_status_query: false,
+            reference_timestamp_interval: default_reference_timestamp_interval(),
+            max_reference_age: None,
+            serve_when_unsynchronized: default_serve_when_unsynchronized(),
+            pad_responses: false,
+            allow_symmetric_peers: false,
         })
     }
 }
@@ -188,10 +446,26 @@ impl From<SocketAddr> for ServerConfig {
             listen,
             denylist: default_denylist(),
             allowlist: default_allowlist(),
+            reference_id_denylist: None,
             rate_limiting_cache_size: 0,
             rate_limiting_cutoff: Duration::default(),
+            nts_rate_limiting_cutoff: None,
+            rate_limiting_prefix_v4: default_rate_limiting_prefix_v4(),
+            rate_limiting_prefix_v6: default_rate_limiting_prefix_v6(),
             require_nts: None,
             accept_ntp_versions: default_accepted_ntp_versions(),
+            interface: None,
+            echo_extension_fields: vec![],
+            dscp: None,
+            recv_buffer_size: None,
+            reuse_port: false,
+            respect_request_destination: default_respect_request_destination(),
+            allow_status_query: false,
+            reference_timestamp_interval: default_reference_timestamp_interval(),
+            max_reference_age: None,
+            serve_when_unsynchronized: default_serve_when_unsynchronized(),
+            pad_responses: false,
+            allow_symmetric_peers: false,
         }
     }
 }
@@ -201,10 +475,21 @@ impl From<ServerConfig> for ntp_proto::ServerConfig {
         ntp_proto::ServerConfig {
             denylist: value.denylist,
             allowlist: value.allowlist,
+            reference_id_denylist: value.reference_id_denylist,
             rate_limiting_cache_size: value.rate_limiting_cache_size,
             rate_limiting_cutoff: value.rate_limiting_cutoff,
+            nts_rate_limiting_cutoff: value.nts_rate_limiting_cutoff,
+            rate_limiting_prefix_v4: value.rate_limiting_prefix_v4,
+            rate_limiting_prefix_v6: value.rate_limiting_prefix_v6,
             require_nts: value.require_nts,
             accepted_versions: value.accept_ntp_versions,
+            echo_extension_fields: value.echo_extension_fields,
+            allow_status_query: value.allow_status_query,
+            reference_timestamp_interval: value.reference_timestamp_interval,
+            max_reference_age: value.max_reference_age,
+            serve_when_unsynchronized: value.serve_when_unsynchronized,
+            pad_responses: value.pad_responses,
+            allow_symmetric_peers: value.allow_symmetric_peers,
         }
     }
 }
@@ -218,9 +503,11 @@ pub struct NtsKeConfig {
     pub concurrent_connections: usize,
     pub longlived_connections: usize,
     pub listen: SocketAddr,
-    pub ntp_port: Option<u16>,
-    pub ntp_server: Option<String>,
+    pub ntpv4_endpoint: NtsNtpEndpoint,
+    pub ntpv5_endpoint: NtsNtpEndpoint,
     pub accept_ntp_versions: Vec<NtpVersion>,
+    pub ntpv4_cookies: NtsCookieConfig,
+    pub ntpv5_cookies: NtsCookieConfig,
 }
 
 impl<'de> Deserialize<'de> for NtsKeConfig {
@@ -244,14 +531,36 @@ impl<'de> Deserialize<'de> for NtsKeConfig {
             listen: SocketAddr,
             ntp_port: Option<u16>,
             ntp_server: Option<String>,
+            ntpv4_port: Option<u16>,
+            ntpv4_server: Option<String>,
+            ntpv5_port: Option<u16>,
+            ntpv5_server: Option<String>,
             #[serde(
                 default = "default_accept_ntp_versions",
                 deserialize_with = "deserialize_accepted_ntp_versions_for_nts"
             )]
             accept_ntp_versions: Vec<NtpVersion>,
+            #[serde(default)]
+            ntpv4_cookies: NtsCookieConfig,
+            #[serde(default)]
+            ntpv5_cookies: NtsCookieConfig,
         }
 
         let raw = NtsKeConfigRaw::deserialize(deserializer)?;
+
+        for cookies in [&raw.ntpv4_cookies, &raw.ntpv5_cookies] {
+            if cookies.count == 0 || cookies.count > MAX_COOKIES {
+                return Err(serde::de::Error::custom(format!(
+                    "cookie count must be between 1 and {MAX_COOKIES}"
+                )));
+            }
+            if cookies.min_size > MAX_COOKIE_SIZE {
+                return Err(serde::de::Error::custom(format!(
+                    "cookie min-size must be at most {MAX_COOKIE_SIZE} bytes"
+                )));
+            }
+        }
+
         Ok(NtsKeConfig {
             certificate_chain_path: raw.certificate_chain_path,
             private_key_path: raw.private_key_path,
@@ -262,13 +571,80 @@ impl<'de> Deserialize<'de> for NtsKeConfig {
                 .longlived_connections
                 .unwrap_or(raw.concurrent_connections / 10),
             listen: raw.listen,
-            ntp_port: raw.ntp_port,
-            ntp_server: raw.ntp_server,
+            // The version specific settings take precedence over the shared ones
+            ntpv4_endpoint: NtsNtpEndpoint {
+                server: raw.ntpv4_server.or_else(|| raw.ntp_server.clone()),
+                port: raw.ntpv4_port.or(raw.ntp_port),
+            },
+            ntpv5_endpoint: NtsNtpEndpoint {
+                server: raw.ntpv5_server.or(raw.ntp_server),
+                port: raw.ntpv5_port.or(raw.ntp_port),
+            },
             accept_ntp_versions: raw.accept_ntp_versions,
+            ntpv4_cookies: raw.ntpv4_cookies,
+            ntpv5_cookies: raw.ntpv5_cookies,
         })
     }
 }
 
+// Normalizes the endpoints to the version specific settings, as these take
+// precedence over the shared ones when read back. The pool authentication
+// tokens are secrets, so only their number is shown.
+impl Serialize for NtsKeConfig {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[derive(Serialize)]
+        #[serde(rename_all = "kebab-case")]
+        struct NtsKeConfigRaw<'a> {
+            certificate_chain_path: &'a Path,
+            private_key_path: &'a Path,
+            #[serde(serialize_with = "serialize_redacted_tokens")]
+            accepted_pool_authentication_tokens: &'a [String],
+            key_exchange_timeout_ms: u64,
+            concurrent_connections: usize,
+            longlived_connections: usize,
+            listen: SocketAddr,
+            ntpv4_port: Option<u16>,
+            ntpv4_server: Option<&'a str>,
+            ntpv5_port: Option<u16>,
+            ntpv5_server: Option<&'a str>,
+            #[serde(serialize_with = "serialize_accepted_ntp_versions")]
+            accept_ntp_versions: &'a [NtpVersion],
+            ntpv4_cookies: NtsCookieConfig,
+            ntpv5_cookies: NtsCookieConfig,
+        }
+
+        NtsKeConfigRaw {
+            certificate_chain_path: &self.certificate_chain_path,
+            private_key_path: &self.private_key_path,
+            accepted_pool_authentication_tokens: &self.accepted_pool_authentication_tokens,
+            key_exchange_timeout_ms: self.key_exchange_timeout_ms,
+            concurrent_connections: self.concurrent_connections,
+            longlived_connections: self.longlived_connections,
+            listen: self.listen,
+            ntpv4_port: self.ntpv4_endpoint.port,
+            ntpv4_server: self.ntpv4_endpoint.server.as_deref(),
+            ntpv5_port: self.ntpv5_endpoint.port,
+            ntpv5_server: self.ntpv5_endpoint.server.as_deref(),
+            accept_ntp_versions: &self.accept_ntp_versions,
+            ntpv4_cookies: self.ntpv4_cookies,
+            ntpv5_cookies: self.ntpv5_cookies,
+        }
+        .serialize(serializer)
+    }
+}
+
+pub(crate) const REDACTED_TOKEN: &str = "<redacted>";
+
+fn serialize_redacted_tokens<S>(tokens: &&[String], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_seq(tokens.iter().map(|_| REDACTED_TOKEN))
+}
+
 fn default_accept_ntp_versions() -> Vec<NtpVersion> {
     vec![NtpVersion::V4]
 }
@@ -325,6 +701,7 @@ mod tests {
             ntp_proto::FilterAction::Ignore
         );
         assert_eq!(test.server.denylist.action, ntp_proto::FilterAction::Deny);
+        assert_eq!(test.server.interface, None);
 
         let test: TestConfig = toml::from_str(
             r#"
@@ -346,6 +723,51 @@ mod tests {
             vec![NtpVersion::V3, NtpVersion::V4]
         );
 
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "127.0.0.1:123"
+            rate-limiting-cutoff-ms = "500ms"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(test.server.rate_limiting_cutoff, Duration::from_millis(500));
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "127.0.0.1:123"
+            rate-limiting-cutoff-ms = "2m"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(test.server.rate_limiting_cutoff, Duration::from_secs(120));
+        assert_eq!(test.server.nts_rate_limiting_cutoff, None);
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "127.0.0.1:123"
+            rate-limiting-cutoff-ms = "2m"
+            nts-rate-limiting-cutoff-ms = "1s"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            test.server.nts_rate_limiting_cutoff,
+            Some(Duration::from_secs(1))
+        );
+
+        let test = toml::from_str::<TestConfig>(
+            r#"
+            [server]
+            listen = "127.0.0.1:123"
+            rate-limiting-cutoff-ms = "2x"
+            "#,
+        );
+        let error = test.unwrap_err();
+        assert!(error.to_string().contains("`ms`, `s`, `m` or `h`"));
+
         let test: TestConfig = toml::from_str(
             r#"
             [server]
@@ -382,6 +804,42 @@ mod tests {
         );
         assert!(test.is_err());
 
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "127.0.0.1:123"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(test.server.reference_id_denylist, None);
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "127.0.0.1:123"
+
+            [server.reference-id-denylist]
+            filter = ["LOCL", "192.0.2.1"]
+            action = "ignore"
+            "#,
+        )
+        .unwrap();
+        let denylist = test.server.reference_id_denylist.unwrap();
+        assert_eq!(denylist.filter.len(), 2);
+        assert_eq!(denylist.action, ntp_proto::FilterAction::Ignore);
+
+        let test = toml::from_str::<TestConfig>(
+            r#"
+            [server]
+            listen = "127.0.0.1:123"
+
+            [server.reference-id-denylist]
+            filter = ["TOOLONG"]
+            action = "deny"
+            "#,
+        );
+        assert!(test.is_err());
+
         let test = toml::from_str::<TestConfig>(
             r#"
             [server]
@@ -403,6 +861,331 @@ mod tests {
             "#,
         );
         assert!(test.is_err());
+
+        let test = toml::from_str::<TestConfig>(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            interface = "enp0s31f6"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            test.server.interface,
+            Some(InterfaceName::from_str("enp0s31f6").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_deserialize_server_dscp() {
+        #[derive(Deserialize, Debug)]
+        struct TestConfig {
+            server: ServerConfig,
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(test.server.dscp, None);
+
+        for dscp in [0, 46, 63] {
+            let test: TestConfig = toml::from_str(&format!(
+                r#"
+                [server]
+                listen = "0.0.0.0:123"
+                dscp = {dscp}
+                "#
+            ))
+            .unwrap();
+            assert_eq!(test.server.dscp, Some(dscp));
+        }
+
+        for dscp in [-1, 64, 256] {
+            let test = toml::from_str::<TestConfig>(&format!(
+                r#"
+                [server]
+                listen = "0.0.0.0:123"
+                dscp = {dscp}
+                "#
+            ));
+            assert!(test.is_err());
+        }
+
+        let error = toml::from_str::<TestConfig>(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            dscp = 64
+            "#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("between 0 and 63"));
+    }
+
+    #[test]
+    fn test_deserialize_server_reuse_port() {
+        #[derive(Deserialize, Debug)]
+        struct TestConfig {
+            server: ServerConfig,
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            "#,
+        )
+        .unwrap();
+        assert!(!test.server.reuse_port);
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            reuse-port = true
+            "#,
+        )
+        .unwrap();
+        assert!(test.server.reuse_port);
+        assert_eq!(
+            test.server,
+            ServerConfig {
+                reuse_port: true,
+                ..ServerConfig::try_from("0.0.0.0:123").unwrap()
+            }
+        );
+    }
+
+    #[test]
+    fn test_deserialize_server_respect_request_destination() {
+        #[derive(Deserialize, Debug)]
+        struct TestConfig {
+            server: ServerConfig,
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            "#,
+        )
+        .unwrap();
+        assert!(test.server.respect_request_destination);
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            respect-request-destination = false
+            "#,
+        )
+        .unwrap();
+        assert!(!test.server.respect_request_destination);
+    }
+
+    #[test]
+    fn test_deserialize_server_serve_when_unsynchronized() {
+        #[derive(Deserialize, Debug)]
+        struct TestConfig {
+            server: ServerConfig,
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            "#,
+        )
+        .unwrap();
+        assert!(test.server.serve_when_unsynchronized);
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            serve-when-unsynchronized = false
+            "#,
+        )
+        .unwrap();
+        assert!(!test.server.serve_when_unsynchronized);
+    }
+
+    #[test]
+    fn test_deserialize_server_pad_responses() {
+        #[derive(Deserialize, Debug)]
+        struct TestConfig {
+            server: ServerConfig,
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            "#,
+        )
+        .unwrap();
+        assert!(!test.server.pad_responses);
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            pad-responses = true
+            "#,
+        )
+        .unwrap();
+        assert!(test.server.pad_responses);
+        assert!(ntp_proto::ServerConfig::from(test.server).pad_responses);
+    }
+
+    #[test]
+    fn test_deserialize_server_allow_symmetric_peers() {
+        #[derive(Deserialize, Debug)]
+        struct TestConfig {
+            server: ServerConfig,
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            "#,
+        )
+        .unwrap();
+        assert!(!test.server.allow_symmetric_peers);
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            allow-symmetric-peers = true
+            "#,
+        )
+        .unwrap();
+        assert!(test.server.allow_symmetric_peers);
+        assert!(ntp_proto::ServerConfig::from(test.server).allow_symmetric_peers);
+    }
+
+    #[test]
+    fn test_deserialize_server_recv_buffer_size() {
+        #[derive(Deserialize, Debug)]
+        struct TestConfig {
+            server: ServerConfig,
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(test.server.recv_buffer_size, None);
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            recv-buffer-size = 1048576
+            "#,
+        )
+        .unwrap();
+        assert_eq!(test.server.recv_buffer_size, Some(1_048_576));
+    }
+
+    #[test]
+    fn test_deserialize_server_rate_limiting_prefix() {
+        #[derive(Deserialize, Debug)]
+        struct TestConfig {
+            server: ServerConfig,
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(test.server.rate_limiting_prefix_v4, 32);
+        assert_eq!(test.server.rate_limiting_prefix_v6, 128);
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            rate-limiting-prefix-v4 = 24
+            rate-limiting-prefix-v6 = 64
+            "#,
+        )
+        .unwrap();
+        let config = ntp_proto::ServerConfig::from(test.server);
+        assert_eq!(config.rate_limiting_prefix_v4, 24);
+        assert_eq!(config.rate_limiting_prefix_v6, 64);
+
+        let test = toml::from_str::<TestConfig>(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            rate-limiting-prefix-v4 = 33
+            "#,
+        );
+        assert!(test.is_err());
+
+        let test = toml::from_str::<TestConfig>(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            rate-limiting-prefix-v6 = 129
+            "#,
+        );
+        assert!(test.is_err());
+    }
+
+    #[test]
+    fn test_deserialize_server_reference_timestamp() {
+        #[derive(Deserialize, Debug)]
+        struct TestConfig {
+            server: ServerConfig,
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(test.server.reference_timestamp_interval, 128);
+        assert_eq!(test.server.max_reference_age, None);
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            reference-timestamp-interval = 16
+            max-reference-age = 3600
+            "#,
+        )
+        .unwrap();
+        assert_eq!(test.server.reference_timestamp_interval, 16);
+        assert_eq!(
+            test.server.max_reference_age,
+            Some(NtpDuration::from_seconds(3600.0))
+        );
+
+        let test: Result<TestConfig, _> = toml::from_str(
+            r#"
+            [server]
+            listen = "0.0.0.0:123"
+            reference-timestamp-interval = 0
+            "#,
+        );
+        assert!(test.is_err());
     }
 
     #[test]
@@ -452,6 +1235,134 @@ mod tests {
         );
         assert_eq!(test.nts_ke_server.key_exchange_timeout_ms, 1000,);
         assert_eq!(test.nts_ke_server.listen, "0.0.0.0:4460".parse().unwrap(),);
+        assert_eq!(test.nts_ke_server.ntpv4_cookies, NtsCookieConfig::default());
+        assert_eq!(test.nts_ke_server.ntpv5_cookies, NtsCookieConfig::default());
+        assert_eq!(test.nts_ke_server.ntpv4_endpoint, NtsNtpEndpoint::default());
+        assert_eq!(test.nts_ke_server.ntpv5_endpoint, NtsNtpEndpoint::default());
+    }
+
+    #[test]
+    fn test_deserialize_nts_ke_endpoints() {
+        #[derive(Deserialize, Debug)]
+        #[serde(rename_all = "kebab-case", deny_unknown_fields)]
+        struct TestConfig {
+            nts_ke_server: NtsKeConfig,
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [nts-ke-server]
+            listen = "0.0.0.0:4460"
+            certificate-chain-path = "/foo/bar/baz.pem"
+            private-key-path = "spam.der"
+            ntp-server = "time.example.com"
+            ntp-port = 1123
+            ntpv5-port = 5123
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            test.nts_ke_server.ntpv4_endpoint,
+            NtsNtpEndpoint {
+                server: Some("time.example.com".into()),
+                port: Some(1123),
+            }
+        );
+        assert_eq!(
+            test.nts_ke_server.ntpv5_endpoint,
+            NtsNtpEndpoint {
+                server: Some("time.example.com".into()),
+                port: Some(5123),
+            }
+        );
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [nts-ke-server]
+            listen = "0.0.0.0:4460"
+            certificate-chain-path = "/foo/bar/baz.pem"
+            private-key-path = "spam.der"
+            ntpv4-server = "v4.example.com"
+            ntpv5-server = "v5.example.com"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            test.nts_ke_server.ntpv4_endpoint.server.as_deref(),
+            Some("v4.example.com")
+        );
+        assert_eq!(test.nts_ke_server.ntpv4_endpoint.port, None);
+        assert_eq!(
+            test.nts_ke_server.ntpv5_endpoint.server.as_deref(),
+            Some("v5.example.com")
+        );
+    }
+
+    #[test]
+    fn test_deserialize_nts_ke_cookies() {
+        #[derive(Deserialize, Debug)]
+        #[serde(rename_all = "kebab-case", deny_unknown_fields)]
+        struct TestConfig {
+            nts_ke_server: NtsKeConfig,
+        }
+
+        let test: TestConfig = toml::from_str(
+            r#"
+            [nts-ke-server]
+            listen = "0.0.0.0:4460"
+            certificate-chain-path = "/foo/bar/baz.pem"
+            private-key-path = "spam.der"
+            accept-ntp-versions = [4, 5]
+            ntpv5-cookies = { count = 4, min-size = 200 }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(test.nts_ke_server.ntpv4_cookies, NtsCookieConfig::default());
+        assert_eq!(
+            test.nts_ke_server.ntpv5_cookies,
+            NtsCookieConfig {
+                count: 4,
+                min_size: 200
+            }
+        );
+
+        let test = toml::from_str::<TestConfig>(
+            r#"
+            [nts-ke-server]
+            listen = "0.0.0.0:4460"
+            certificate-chain-path = "/foo/bar/baz.pem"
+            private-key-path = "spam.der"
+            ntpv4-cookies = { count = 0 }
+            "#,
+        );
+        assert!(test.is_err());
+
+        // Padding may not make cookies too large for clients to send back
+        let test = toml::from_str::<TestConfig>(&format!(
+            r#"
+            [nts-ke-server]
+            listen = "0.0.0.0:4460"
+            certificate-chain-path = "/foo/bar/baz.pem"
+            private-key-path = "spam.der"
+            ntpv5-cookies = {{ min-size = {MAX_COOKIE_SIZE} }}
+            "#,
+        ));
+        assert!(test.is_ok());
+
+        let test = toml::from_str::<TestConfig>(&format!(
+            r#"
+            [nts-ke-server]
+            listen = "0.0.0.0:4460"
+            certificate-chain-path = "/foo/bar/baz.pem"
+            private-key-path = "spam.der"
+            ntpv5-cookies = {{ min-size = {} }}
+            "#,
+            MAX_COOKIE_SIZE + 1
+        ));
+        assert!(test.is_err());
     }
 
     #[test]
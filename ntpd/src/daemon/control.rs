@@ -0,0 +1,507 @@
+use std::collections::{HashMap, HashSet};
+use std::os::unix::fs::PermissionsExt;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use ntp_proto::{ClockId, ForceSyncOutcome};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{mpsc, oneshot, watch},
+    task::JoinHandle,
+};
+use tracing::{Instrument, Span, debug, error, info, instrument, trace, warn};
+
+use super::sockets::{create_unix_socket_with_permissions, read_json, write_json};
+
+/// Command from the control socket for a single source task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceCommand {
+    /// Send a poll outside of the regular schedule
+    Poll,
+    /// Stop using the source for synchronization
+    Disable,
+    /// Use the source for synchronization again
+    Enable,
+}
+
+/// Request from the control socket for a source task, carrying the channel on
+/// which the source task reports the outcome.
+#[derive(Debug)]
+pub struct SourceRequest {
+    pub command: SourceCommand,
+    pub reply: oneshot::Sender<ControlResponse>,
+}
+
+/// Channels to the source tasks that accept control requests, by source.
+pub type SourceRequestSenders = Arc<RwLock<HashMap<ClockId, mpsc::Sender<SourceRequest>>>>;
+
+/// Sources that were disabled through the control socket. The system keeps
+/// these disabled when their spawner recreates them.
+pub type DisabledSources = Arc<RwLock<HashSet<ClockId>>>;
+
+/// Channel on which the system is asked to correct the clock right away,
+/// carrying the channel on which the outcome is reported.
+pub type ForceSyncSender = mpsc::Sender<oneshot::Sender<ForceSyncOutcome>>;
+
+/// Period for which the servers are drained before the daemon shuts down,
+/// or `None` while the daemon is not draining.
+pub type DrainSender = watch::Sender<Option<Duration>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ControlRequest {
+    /// Poll the given source immediately
+    Poll(ClockId),
+    /// Stop using the given source for synchronization until it is enabled
+    /// again or the daemon restarts. The source is still polled.
+    DisableSource(ClockId),
+    /// Use a previously disabled source for synchronization again
+    EnableSource(ClockId),
+    /// Correct the clock right away using the current estimate, stepping it
+    /// when needed. Steps exceeding the panic thresholds are refused.
+    ForceSync,
+    /// Advertise the servers as unsynchronized for the given number of
+    /// seconds so clients move elsewhere, then shut the daemon down
+    Drain(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ControlResponse {
+    /// The poll message was sent to the source
+    PollSent,
+    /// The poll could not be sent without exceeding the allowed poll rate
+    RateLimited,
+    /// The source stopped before the poll message could be sent
+    PollFailed,
+    /// There is no NTP source with the requested id
+    UnknownSource,
+    /// The source is no longer used for synchronization
+    SourceDisabled,
+    /// The source may be used for synchronization again
+    SourceEnabled,
+    /// The source task did not handle the request
+    RequestFailed,
+    /// The clock was corrected with the current estimate
+    SyncRequested,
+    /// The correction exceeds the panic thresholds and was not applied
+    SyncRefused,
+    /// The sources do not agree on the current time, so the clock was not
+    /// corrected
+    NoConsensus,
+    /// The servers are draining and the daemon shuts down afterwards
+    Draining,
+}
+
+#[instrument(level = tracing::Level::ERROR, skip_all, name = "Control", fields(path = debug(config.control_path.clone())))]
+pub fn spawn(
+    config: &super::config::ObservabilityConfig,
+    source_requests: SourceRequestSenders,
+    disabled_sources: DisabledSources,
+    force_sync: ForceSyncSender,
+    drain: DrainSender,
+) -> JoinHandle<std::io::Result<()>> {
+    let config = config.clone();
+    tokio::spawn(
+        (async move {
+            let result =
+                control(config, source_requests, disabled_sources, force_sync, drain).await;
+            if let Err(ref e) = result {
+                warn!("Abnormal termination of the control socket: {e}");
+                warn!("The control socket will not be available");
+            }
+            result
+        })
+        .instrument(Span::current()),
+    )
+}
+
+async fn control(
+    config: super::config::ObservabilityConfig,
+    source_requests: SourceRequestSenders,
+    disabled_sources: DisabledSources,
+    force_sync: ForceSyncSender,
+    drain: DrainSender,
+) -> std::io::Result<()> {
+    let timeout = std::time::Duration::from_secs(5);
+
+    let Some(path) = config.control_path else {
+        return Ok(());
+    };
+
+    let permissions: std::fs::Permissions = PermissionsExt::from_mode(config.control_permissions);
+    let listener = create_unix_socket_with_permissions(&path, permissions)?;
+
+    loop {
+        let (mut stream, _addr) = match listener.accept().await {
+            Ok(a) => a,
+            Err(e) => {
+                error!("Could not accept connection due to unexpected problem: {e}");
+                return Err(e);
+            }
+        };
+
+        // Connections are handled one at a time, so control requests cannot be
+        // used to flood the daemon with work.
+        match tokio::time::timeout(
+            timeout,
+            handle_connection(
+                &mut stream,
+                &source_requests,
+                &disabled_sources,
+                &force_sync,
+                &drain,
+            ),
+        )
+        .await
+        {
+            Err(_) => debug!("Handling control request timed out"),
+            Ok(Err(err)) => warn!("error handling control connection: {err}"),
+            Ok(Ok(())) => trace!("Handled control request"),
+        }
+    }
+}
+
+async fn handle_connection(
+    stream: &mut (impl tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin),
+    source_requests: &SourceRequestSenders,
+    disabled_sources: &DisabledSources,
+    force_sync: &ForceSyncSender,
+    drain: &DrainSender,
+) -> std::io::Result<()> {
+    let mut msg = Vec::with_capacity(64);
+    let request = read_json::<ControlRequest>(stream, &mut msg).await?;
+    let response = handle_request(
+        request,
+        source_requests,
+        disabled_sources,
+        force_sync,
+        drain,
+    )
+    .await;
+    write_json(stream, &response).await
+}
+
+async fn handle_request(
+    request: ControlRequest,
+    source_requests: &SourceRequestSenders,
+    disabled_sources: &DisabledSources,
+    force_sync: &ForceSyncSender,
+    drain: &DrainSender,
+) -> ControlResponse {
+    let (id, command) = match request {
+        ControlRequest::Poll(id) => (id, SourceCommand::Poll),
+        ControlRequest::DisableSource(id) => (id, SourceCommand::Disable),
+        ControlRequest::EnableSource(id) => (id, SourceCommand::Enable),
+        ControlRequest::ForceSync => {
+            // Only a single forced synchronization can be pending at any time
+            let (reply, reply_rx) = oneshot::channel();
+            match force_sync.try_send(reply) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => return ControlResponse::RateLimited,
+                Err(mpsc::error::TrySendError::Closed(_)) => {
+                    return ControlResponse::RequestFailed;
+                }
+            }
+
+            return match reply_rx.await {
+                Ok(ForceSyncOutcome::Corrected) => ControlResponse::SyncRequested,
+                Ok(ForceSyncOutcome::Refused) => ControlResponse::SyncRefused,
+                Ok(ForceSyncOutcome::NoConsensus) => ControlResponse::NoConsensus,
+                Err(_) => ControlResponse::RequestFailed,
+            };
+        }
+        ControlRequest::Drain(seconds) => {
+            // Draining can't be extended or shortened once started
+            drain.send_if_modified(|period| {
+                if period.is_some() {
+                    return false;
+                }
+                info!(seconds, "Draining servers before shutting down");
+                *period = Some(Duration::from_secs(seconds));
+                true
+            });
+            return ControlResponse::Draining;
+        }
+    };
+
+    let sender = source_requests
+        .read()
+        .expect("Unexpected poisoned mutex")
+        .get(&id)
+        .cloned();
+    let Some(sender) = sender else {
+        return ControlResponse::UnknownSource;
+    };
+
+    // Only a single request can be pending for a source at any time
+    let (reply, reply_rx) = oneshot::channel();
+    if sender.try_send(SourceRequest { command, reply }).is_err() {
+        return ControlResponse::RateLimited;
+    }
+
+    let response = reply_rx.await.unwrap_or(match command {
+        SourceCommand::Poll => ControlResponse::PollFailed,
+        SourceCommand::Disable | SourceCommand::Enable => ControlResponse::RequestFailed,
+    });
+
+    let mut disabled_sources = disabled_sources.write().expect("Unexpected poisoned mutex");
+    match response {
+        ControlResponse::SourceDisabled => {
+            disabled_sources.insert(id);
+        }
+        ControlResponse::SourceEnabled => {
+            disabled_sources.remove(&id);
+        }
+        _ => {}
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_poll_request_routing() {
+        let poll_requests = SourceRequestSenders::default();
+        let id = ClockId::new();
+
+        assert_eq!(
+            handle_request(
+                ControlRequest::Poll(id),
+                &poll_requests,
+                &DisabledSources::default(),
+                &mpsc::channel(1).0,
+                &DrainSender::new(None)
+            )
+            .await,
+            ControlResponse::UnknownSource
+        );
+
+        let (tx, mut rx) = mpsc::channel::<SourceRequest>(1);
+        poll_requests.write().unwrap().insert(id, tx);
+
+        let source = tokio::spawn(async move {
+            let request = rx.recv().await.unwrap();
+            assert_eq!(request.command, SourceCommand::Poll);
+            request.reply.send(ControlResponse::PollSent).ok();
+            // Dropping a request without answering means the poll failed
+            drop(rx.recv().await.unwrap());
+        });
+
+        assert_eq!(
+            handle_request(
+                ControlRequest::Poll(id),
+                &poll_requests,
+                &DisabledSources::default(),
+                &mpsc::channel(1).0,
+                &DrainSender::new(None)
+            )
+            .await,
+            ControlResponse::PollSent
+        );
+        assert_eq!(
+            handle_request(
+                ControlRequest::Poll(id),
+                &poll_requests,
+                &DisabledSources::default(),
+                &mpsc::channel(1).0,
+                &DrainSender::new(None)
+            )
+            .await,
+            ControlResponse::PollFailed
+        );
+
+        source.await.unwrap();
+    }
+
+    #[test]
+    fn test_parse_control_request() {
+        let id: ClockId = "12".parse().unwrap();
+
+        let request: ControlRequest = serde_json::from_str(r#"{"poll":12}"#).unwrap();
+        assert_eq!(request, ControlRequest::Poll(id));
+
+        let request: ControlRequest = serde_json::from_str(r#"{"disable-source":12}"#).unwrap();
+        assert_eq!(request, ControlRequest::DisableSource(id));
+
+        let request: ControlRequest = serde_json::from_str(r#"{"enable-source":12}"#).unwrap();
+        assert_eq!(request, ControlRequest::EnableSource(id));
+
+        let request: ControlRequest = serde_json::from_str(r#"{"drain":300}"#).unwrap();
+        assert_eq!(request, ControlRequest::Drain(300));
+
+        let request: ControlRequest = serde_json::from_str(r#""force-sync""#).unwrap();
+        assert_eq!(request, ControlRequest::ForceSync);
+
+        assert!(serde_json::from_str::<ControlRequest>(r#"{"step":12}"#).is_err());
+        assert!(serde_json::from_str::<ControlRequest>(r#"{"disable-source":"x"}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_disable_source_round_trip() {
+        let source_requests = SourceRequestSenders::default();
+        let disabled_sources = DisabledSources::default();
+        let id = ClockId::new();
+
+        let (tx, mut rx) = mpsc::channel::<SourceRequest>(1);
+        source_requests.write().unwrap().insert(id, tx);
+
+        let source = tokio::spawn(async move {
+            let mut disabled = false;
+            for _ in 0..2 {
+                let request = rx.recv().await.unwrap();
+                let response = match request.command {
+                    SourceCommand::Disable => {
+                        disabled = true;
+                        ControlResponse::SourceDisabled
+                    }
+                    SourceCommand::Enable => {
+                        disabled = false;
+                        ControlResponse::SourceEnabled
+                    }
+                    SourceCommand::Poll => unreachable!("no poll was requested"),
+                };
+                request.reply.send(response).ok();
+            }
+            disabled
+        });
+
+        for (request, expected, disabled) in [
+            (
+                ControlRequest::DisableSource(id),
+                ControlResponse::SourceDisabled,
+                true,
+            ),
+            (
+                ControlRequest::EnableSource(id),
+                ControlResponse::SourceEnabled,
+                false,
+            ),
+        ] {
+            let (mut client, mut server) = tokio::io::duplex(512);
+            write_json(&mut client, &request).await.unwrap();
+            handle_connection(
+                &mut server,
+                &source_requests,
+                &disabled_sources,
+                &mpsc::channel(1).0,
+                &DrainSender::new(None),
+            )
+            .await
+            .unwrap();
+
+            let mut msg = Vec::new();
+            let response: ControlResponse = read_json(&mut client, &mut msg).await.unwrap();
+            assert_eq!(response, expected);
+            // The system keeps the source disabled when it is recreated
+            assert_eq!(disabled_sources.read().unwrap().contains(&id), disabled);
+        }
+
+        assert!(!source.await.unwrap());
+
+        let (mut client, mut server) = tokio::io::duplex(512);
+        write_json(&mut client, &ControlRequest::DisableSource(ClockId::new()))
+            .await
+            .unwrap();
+        handle_connection(
+            &mut server,
+            &source_requests,
+            &disabled_sources,
+            &mpsc::channel(1).0,
+            &DrainSender::new(None),
+        )
+        .await
+        .unwrap();
+        let mut msg = Vec::new();
+        let response: ControlResponse = read_json(&mut client, &mut msg).await.unwrap();
+        assert_eq!(response, ControlResponse::UnknownSource);
+        assert!(disabled_sources.read().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_drain_request() {
+        let source_requests = SourceRequestSenders::default();
+        let drain = DrainSender::new(None);
+        let mut draining = drain.subscribe();
+        assert_eq!(*draining.borrow_and_update(), None);
+
+        assert_eq!(
+            handle_request(
+                ControlRequest::Drain(60),
+                &source_requests,
+                &DisabledSources::default(),
+                &mpsc::channel(1).0,
+                &drain
+            )
+            .await,
+            ControlResponse::Draining
+        );
+        assert!(draining.has_changed().unwrap());
+        assert_eq!(*draining.borrow_and_update(), Some(Duration::from_secs(60)));
+
+        // A second request doesn't restart the drain period
+        assert_eq!(
+            handle_request(
+                ControlRequest::Drain(600),
+                &source_requests,
+                &DisabledSources::default(),
+                &mpsc::channel(1).0,
+                &drain
+            )
+            .await,
+            ControlResponse::Draining
+        );
+        assert!(!draining.has_changed().unwrap());
+        assert_eq!(*draining.borrow(), Some(Duration::from_secs(60)));
+    }
+
+    #[tokio::test]
+    async fn test_force_sync_request() {
+        let source_requests = SourceRequestSenders::default();
+        let disabled_sources = DisabledSources::default();
+        let drain = DrainSender::new(None);
+        let (force_sync, mut force_sync_rx) = mpsc::channel::<oneshot::Sender<ForceSyncOutcome>>(1);
+
+        let request = || {
+            handle_request(
+                ControlRequest::ForceSync,
+                &source_requests,
+                &disabled_sources,
+                &force_sync,
+                &drain,
+            )
+        };
+
+        // A request while another one is pending is refused
+        force_sync.try_send(oneshot::channel().0).unwrap();
+        assert_eq!(request().await, ControlResponse::RateLimited);
+        drop(force_sync_rx.recv().await);
+
+        let system = tokio::spawn(async move {
+            for outcome in [
+                Some(ForceSyncOutcome::Corrected),
+                Some(ForceSyncOutcome::Refused),
+                Some(ForceSyncOutcome::NoConsensus),
+                None,
+            ] {
+                let reply = force_sync_rx.recv().await.unwrap();
+                if let Some(outcome) = outcome {
+                    reply.send(outcome).unwrap();
+                }
+            }
+        });
+
+        assert_eq!(request().await, ControlResponse::SyncRequested);
+        assert_eq!(request().await, ControlResponse::SyncRefused);
+        assert_eq!(request().await, ControlResponse::NoConsensus);
+        // The system dropped the request without handling it
+        assert_eq!(request().await, ControlResponse::RequestFailed);
+
+        // The system stopped
+        system.await.unwrap();
+        assert_eq!(request().await, ControlResponse::RequestFailed);
+    }
+}
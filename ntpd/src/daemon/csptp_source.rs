@@ -69,7 +69,7 @@ impl CsptpSourceTask {
         controller: Controller,
         manager: &'static CsptpManager<RwLock<InternalState>>,
         network: NetworkManager<A>,
-    ) {
+    ) -> tokio::task::JoinHandle<()> {
         tokio::spawn(async move {
             let interface = network.open_general();
             let mut source = CsptpSource::new(
@@ -96,6 +96,6 @@ impl CsptpSourceTask {
                     rand::thread_rng,
                 )
                 .await;
-        });
+        })
     }
 }
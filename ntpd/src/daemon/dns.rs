@@ -1,19 +1,182 @@
-use std::net::SocketAddr;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 #[cfg(feature = "srv")]
 use hickory_resolver::{
     TokioResolver,
-    net::NetError,
     proto::rr::{IntoName, Name},
 };
-use tokio::net::lookup_host;
 
-use crate::daemon::config::NormalizedAddress;
+use crate::daemon::config::{DnsConfig, NormalizedAddress};
 
 // We keep the resolver globally to avoid reloading its configuration constantly.
 #[cfg(feature = "srv")]
 static RESOLVER: std::sync::OnceLock<TokioResolver> = std::sync::OnceLock::new();
 
+// Address lookups don't require DNSSEC, so they use a separate resolver. When
+// it can't be set up, the system resolver is used instead.
+#[cfg(feature = "srv")]
+static ADDRESS_RESOLVER: std::sync::OnceLock<Option<TokioResolver>> = std::sync::OnceLock::new();
+
+/// Lookup settings and cache of a daemon, shared by all its spawners.
+#[derive(Debug, Default)]
+pub(crate) struct DnsState {
+    config: DnsConfig,
+    cache: Mutex<DnsCache>,
+}
+
+impl DnsState {
+    pub(crate) fn new(config: DnsConfig) -> Arc<Self> {
+        Arc::new(DnsState {
+            config,
+            cache: Mutex::default(),
+        })
+    }
+}
+
+tokio::task_local! {
+    static DNS_STATE: Arc<DnsState>;
+}
+
+/// Run `task` with all its lookups using the given settings and cache.
+/// Outside of such a task, lookups use the default settings without cache.
+pub(crate) fn scope<F: Future>(state: Arc<DnsState>, task: F) -> impl Future<Output = F::Output> {
+    DNS_STATE.scope(state, task)
+}
+
+fn current_state() -> Arc<DnsState> {
+    DNS_STATE.try_with(Arc::clone).unwrap_or_default()
+}
+
+#[derive(Debug, Default)]
+struct DnsCache {
+    entries: HashMap<(String, u16), (Vec<SocketAddr>, Instant)>,
+}
+
+impl DnsCache {
+    fn get(&mut self, name: &str, port: u16, now: Instant) -> Option<Vec<SocketAddr>> {
+        self.entries.retain(|_, (_, expires)| *expires > now);
+        self.entries
+            .get(&(name.to_owned(), port))
+            .map(|(addresses, _)| addresses.clone())
+    }
+
+    fn insert(
+        &mut self,
+        name: &str,
+        port: u16,
+        addresses: &[SocketAddr],
+        valid_for: Duration,
+        now: Instant,
+    ) {
+        if addresses.is_empty() || valid_for.is_zero() {
+            return;
+        }
+        self.entries.insert(
+            (name.to_owned(), port),
+            (addresses.to_vec(), now + valid_for),
+        );
+    }
+}
+
+async fn with_timeout<T>(
+    timeout: Duration,
+    lookup: impl Future<Output = std::io::Result<T>>,
+) -> std::io::Result<T> {
+    tokio::time::timeout(timeout, lookup)
+        .await
+        .unwrap_or_else(|_| {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("lookup did not complete within {timeout:?}"),
+            ))
+        })
+}
+
+/// Resolve a host name, giving up after the configured timeout. Results are
+/// cached for the TTL of their records, within the configured bounds.
+pub(crate) async fn lookup_host(name: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+    let state = current_state();
+
+    if let Some(addresses) =
+        state
+            .cache
+            .lock()
+            .expect("Unexpected poisoned mutex")
+            .get(name, port, Instant::now())
+    {
+        return Ok(addresses);
+    }
+
+    let (addresses, ttl) = with_timeout(state.config.timeout(), resolve_host(name, port)).await?;
+
+    state
+        .cache
+        .lock()
+        .expect("Unexpected poisoned mutex")
+        .insert(
+            name,
+            port,
+            &addresses,
+            state.config.cache_duration(ttl),
+            Instant::now(),
+        );
+
+    Ok(addresses)
+}
+
+/// Resolve a host name with the system resolver, which does not report the
+/// TTL of the records.
+async fn resolve_host_system(
+    name: &str,
+    port: u16,
+) -> std::io::Result<(Vec<SocketAddr>, Option<Duration>)> {
+    Ok((tokio::net::lookup_host((name, port)).await?.collect(), None))
+}
+
+#[cfg(not(feature = "srv"))]
+async fn resolve_host(
+    name: &str,
+    port: u16,
+) -> std::io::Result<(Vec<SocketAddr>, Option<Duration>)> {
+    resolve_host_system(name, port).await
+}
+
+#[cfg(feature = "srv")]
+async fn resolve_host(
+    name: &str,
+    port: u16,
+) -> std::io::Result<(Vec<SocketAddr>, Option<Duration>)> {
+    let resolver = ADDRESS_RESOLVER.get_or_init(|| {
+        match TokioResolver::builder_tokio().and_then(|builder| builder.build()) {
+            Ok(resolver) => Some(resolver),
+            Err(e) => {
+                tracing::warn!("Could not build resolver, using the system resolver: {e}.");
+                None
+            }
+        }
+    });
+    let Some(resolver) = resolver else {
+        return resolve_host_system(name, port).await;
+    };
+
+    let lookup = resolver
+        .lookup_ip(name)
+        .await
+        .map_err(std::io::Error::other)?;
+    let ttl = lookup
+        .valid_until()
+        .saturating_duration_since(Instant::now());
+    Ok((
+        lookup.iter().map(|ip| SocketAddr::new(ip, port)).collect(),
+        Some(ttl),
+    ))
+}
+
 pub(crate) struct KeResolutionResult {
     pub(crate) addr: SocketAddr,
     pub(crate) srv_record_name: Option<String>,
@@ -23,10 +186,11 @@ pub(crate) struct KeResolutionResult {
 pub(crate) async fn resolve_ke(
     addr: &NormalizedAddress,
 ) -> Result<impl Iterator<Item = KeResolutionResult>, std::io::Error> {
-    let lookup_result = lookup_host((addr.server_name.as_str(), addr.port))
+    let lookup_result = lookup_host(addr.server_name.as_str(), addr.port)
         .await?
-        .map(|addr| KeResolutionResult {
-            addr,
+        .into_iter()
+        .map(|resolved| KeResolutionResult {
+            addr: addr.with_scope_id(resolved),
             srv_record_name: None,
         });
 
@@ -57,11 +221,17 @@ pub(crate) async fn resolve_ke(
     }
 
     // First try looking up SRV records
-    if let Ok(srv_names) = resolve_srv(format!("_ntske._tcp.{}", addr.server_name)).await {
+    let timeout = current_state().config.timeout();
+    if let Ok(srv_names) = with_timeout(
+        timeout,
+        resolve_srv(format!("_ntske._tcp.{}", addr.server_name)),
+    )
+    .await
+    {
         let mut result = vec![];
         for name in srv_names.into_iter().map(|v| v.to_ascii()) {
-            if let Ok(lookup) = lookup_host((name.as_str(), 4460)).await {
-                result.extend(lookup.map(|addr| KeResolutionResult {
+            if let Ok(lookup) = lookup_host(name.as_str(), 4460).await {
+                result.extend(lookup.into_iter().map(|addr| KeResolutionResult {
                     addr,
                     srv_record_name: Some(name.clone()),
                 }));
@@ -73,10 +243,11 @@ pub(crate) async fn resolve_ke(
     }
 
     // Otherwise do a direct name lookup
-    let lookup_result = lookup_host((addr.server_name.as_str(), addr.port))
+    let lookup_result = lookup_host(addr.server_name.as_str(), addr.port)
         .await?
-        .map(|addr| KeResolutionResult {
-            addr,
+        .into_iter()
+        .map(|resolved| KeResolutionResult {
+            addr: addr.with_scope_id(resolved),
             srv_record_name: None,
         });
 
@@ -84,7 +255,7 @@ pub(crate) async fn resolve_ke(
 }
 
 #[cfg(feature = "srv")]
-async fn resolve_srv<N: IntoName>(name: N) -> Result<Vec<Name>, NetError> {
+async fn resolve_srv<N: IntoName>(name: N) -> std::io::Result<Vec<Name>> {
     use crate::daemon::exitcode;
     use rand::Rng;
     use std::process::exit;
@@ -110,7 +281,10 @@ async fn resolve_srv<N: IntoName>(name: N) -> Result<Vec<Name>, NetError> {
         }
     });
 
-    let lookup_result = resolver.srv_lookup(name).await?;
+    let lookup_result = resolver
+        .srv_lookup(name)
+        .await
+        .map_err(std::io::Error::other)?;
 
     // Unfortunately, hickory doesn't order the results for us apropriately, so we need
     // to do this ourselves. See also https://github.com/hickory-dns/hickory-dns/issues/3440
@@ -160,3 +334,56 @@ async fn resolve_srv<N: IntoName>(name: N) -> Result<Vec<Name>, NetError> {
 
     Ok(items.into_iter().map(|v| &v.1.target).cloned().collect())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_expiry() {
+        let mut cache = DnsCache::default();
+        let now = Instant::now();
+        let addresses: Vec<SocketAddr> = vec!["192.0.2.1:123".parse().unwrap()];
+
+        cache.insert("example.com", 123, &addresses, Duration::from_secs(60), now);
+        assert_eq!(cache.get("example.com", 123, now), Some(addresses.clone()));
+        assert_eq!(cache.get("example.com", 4460, now), None);
+        assert_eq!(
+            cache.get("example.com", 123, now + Duration::from_secs(59)),
+            Some(addresses.clone())
+        );
+        assert_eq!(
+            cache.get("example.com", 123, now + Duration::from_secs(60)),
+            None
+        );
+
+        // Without a cache duration, or without addresses, nothing is cached
+        cache.insert("example.com", 123, &addresses, Duration::ZERO, now);
+        assert_eq!(cache.get("example.com", 123, now), None);
+        cache.insert("example.com", 123, &[], Duration::from_secs(60), now);
+        assert_eq!(cache.get("example.com", 123, now), None);
+    }
+
+    #[tokio::test]
+    async fn test_state_scoped_to_task() {
+        let state = DnsState::new(DnsConfig {
+            timeout_ms: 100,
+            ..DnsConfig::default()
+        });
+        let scoped = scope(state.clone(), async { current_state() }).await;
+        assert!(Arc::ptr_eq(&scoped, &state));
+
+        // Lookups outside of the task don't share its settings or cache
+        assert_eq!(current_state().config, DnsConfig::default());
+    }
+
+    #[tokio::test]
+    async fn test_lookup_timeout() {
+        let result: std::io::Result<()> =
+            with_timeout(Duration::from_millis(10), std::future::pending()).await;
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::TimedOut);
+
+        let result = with_timeout(Duration::from_secs(10), async { Ok(5) }).await;
+        assert_eq!(result.unwrap(), 5);
+    }
+}
@@ -7,20 +7,28 @@ use std::{
 use libc::{ECONNABORTED, EMFILE, ENFILE, ENOBUFS, ENOMEM};
 use ntp_proto::{KeyExchangeServer, KeySet};
 use ntp_proto::{NtsServerConfig, tls_utils::Certificate};
-use tokio::{net::TcpListener, task::JoinHandle};
+use serde::{Deserialize, Serialize};
+use tokio::{net::TcpListener, sync::TryAcquireError, task::JoinHandle};
 use tracing::{Instrument, Span, debug, error, instrument};
 
 use super::config::NtsKeConfig;
 use super::exitcode;
+use super::server::Counter;
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct NtsKeServerStats {
+    pub rejected_connections: Counter,
+}
 
 #[instrument(level = tracing::Level::ERROR, name = "Nts Server", skip_all, fields(address = debug(nts_ke_config.listen)))]
 pub fn spawn(
     nts_ke_config: NtsKeConfig,
     keyset: tokio::sync::watch::Receiver<Arc<KeySet>>,
+    stats: NtsKeServerStats,
 ) -> JoinHandle<std::io::Result<()>> {
     tokio::spawn(
         (async move {
-            let result = run_nts_ke(nts_ke_config, keyset).await;
+            let result = run_nts_ke(nts_ke_config, keyset, stats).await;
 
             match result {
                 Ok(v) => Ok(v),
@@ -41,6 +49,7 @@ fn io_error(msg: &str) -> std::io::Error {
 async fn run_nts_ke(
     nts_ke_config: NtsKeConfig,
     keyset: tokio::sync::watch::Receiver<Arc<KeySet>>,
+    stats: NtsKeServerStats,
 ) -> std::io::Result<()> {
     let certificate_chain_file = std::fs::File::open(&nts_ke_config.certificate_chain_path)
         .map_err(|e| {
@@ -70,19 +79,22 @@ async fn run_nts_ke(
         certificate_chain,
         private_key,
         accepted_versions: nts_ke_config.accept_ntp_versions.clone(),
-        server: nts_ke_config.ntp_server.clone(),
-        port: nts_ke_config.ntp_port,
+        v4_endpoint: nts_ke_config.ntpv4_endpoint.clone(),
+        v5_endpoint: nts_ke_config.ntpv5_endpoint.clone(),
         pool_authentication_tokens: nts_ke_config.accepted_pool_authentication_tokens.clone(),
+        v4_cookies: nts_ke_config.ntpv4_cookies,
+        v5_cookies: nts_ke_config.ntpv5_cookies,
     })
     .map_err(std::io::Error::other)?;
 
-    run_key_exchange_server(keyset, key_exchange_server, nts_ke_config).await
+    run_key_exchange_server(keyset, key_exchange_server, nts_ke_config, stats).await
 }
 
 async fn run_key_exchange_server(
     keyset: tokio::sync::watch::Receiver<Arc<KeySet>>,
     key_exchange_server: KeyExchangeServer,
     ke_config: NtsKeConfig,
+    stats: NtsKeServerStats,
 ) -> std::io::Result<()> {
     let timeout = std::time::Duration::from_millis(ke_config.key_exchange_timeout_ms);
     let key_exchange_server = Arc::new(key_exchange_server);
@@ -110,14 +122,6 @@ async fn run_key_exchange_server(
         ));
 
         loop {
-            let permit = match connectionpermits.clone().acquire_owned().await {
-                Ok(permit) => permit,
-                Err(e) => {
-                    error!("Could not get ticket for new connection: {}", e);
-                    tokio::time::sleep(timeout).await;
-                    break;
-                }
-            };
             let (stream, source_addr) = match listener.accept().await {
                 Ok(a) => a,
                 Err(e) if matches!(e.raw_os_error(), Some(ECONNABORTED)) => {
@@ -138,6 +142,25 @@ async fn run_key_exchange_server(
                     break;
                 }
             };
+            // Connections beyond the limit are closed right away instead of
+            // queueing, so clients can fall back to another server quickly.
+            let permit = match connectionpermits.clone().try_acquire_owned() {
+                Ok(permit) => permit,
+                Err(TryAcquireError::NoPermits) => {
+                    debug!(
+                        ?source_addr,
+                        "Rejecting NTS-KE connection, too many in flight"
+                    );
+                    stats.rejected_connections.inc();
+                    drop(stream);
+                    continue;
+                }
+                Err(e @ TryAcquireError::Closed) => {
+                    error!("Could not get ticket for new connection: {}", e);
+                    tokio::time::sleep(timeout).await;
+                    break;
+                }
+            };
             let keyset = keyset.borrow().clone();
             let key_exchange_server = key_exchange_server.clone();
             let longlivedpermits = longlivedpermits.clone();
@@ -191,7 +214,9 @@ mod tests {
     use std::{io::Cursor, net::SocketAddr, path::PathBuf};
 
     use ntp_proto::KeySetProvider;
-    use ntp_proto::{KeyExchangeClient, NtpVersion, NtsClientConfig};
+    use ntp_proto::{
+        KeyExchangeClient, NtpVersion, NtsClientConfig, NtsCookieConfig, NtsNtpEndpoint,
+    };
     use tokio::io::{AsyncReadExt, AsyncWriteExt};
     use tokio::net::TcpStream;
 
@@ -256,12 +281,15 @@ mod tests {
             concurrent_connections: 1,
             longlived_connections: 0,
             listen: SocketAddr::new("0.0.0.0".parse().unwrap(), port),
-            ntp_port: None,
-            ntp_server: None,
+            ntpv4_endpoint: NtsNtpEndpoint::default(),
+            ntpv5_endpoint: NtsNtpEndpoint::default(),
             accept_ntp_versions: vec![NtpVersion::V4],
+            ntpv4_cookies: NtsCookieConfig::default(),
+            ntpv5_cookies: NtsCookieConfig::default(),
         };
 
-        let _join_handle = spawn(nts_ke_config, keyset);
+        let stats = NtsKeServerStats::default();
+        let _join_handle = spawn(nts_ke_config, keyset, stats.clone());
 
         // give the server some time to make the port available
         tokio::time::sleep(std::time::Duration::from_millis(20)).await;
@@ -280,8 +308,9 @@ mod tests {
 
         let ca = include_bytes!("../../test-keys/testca.pem");
 
-        assert!(
-            tokio::time::timeout(std::time::Duration::from_millis(750), async move {
+        // Connections over the limit are actively rejected instead of queued
+        for rejected in 1..=2 {
+            let result = tokio::time::timeout(std::time::Duration::from_millis(750), async move {
                 let kex = KeyExchangeClient::new(&NtsClientConfig {
                     certificates: certificates_from_bufread(BufReader::new(Cursor::new(ca)))
                         .unwrap()
@@ -292,15 +321,19 @@ mod tests {
                 let io = TcpStream::connect(("localhost", port)).await.unwrap();
                 kex.exchange_keys(io, "localhost".into(), []).await
             })
-            .await
-            .is_err()
-        );
+            .await;
+            assert!(matches!(result, Ok(Err(_))));
+            assert_eq!(stats.rejected_connections.get(), rejected);
+        }
 
         blocker.shutdown().await.unwrap();
         let mut buf = vec![];
         let _ = blocker.read_to_end(&mut buf).await; // explicitly ignore error as this might be EPIPE
         drop(blocker);
 
+        // give the server time to release the connection permit
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
         let result = tokio::time::timeout(
             std::time::Duration::from_millis(750), // large timeout is needed to ensure test succeeds consistently on MacOS M2 E-cores
             async move {
@@ -321,6 +354,7 @@ mod tests {
 
         assert_eq!(result.remote, "localhost");
         assert_eq!(result.port, 123);
+        assert_eq!(stats.rejected_connections.get(), 2);
     }
 
     #[tokio::test]
@@ -342,12 +376,20 @@ mod tests {
             concurrent_connections: 512,
             longlived_connections: 5,
             listen: SocketAddr::new("0.0.0.0".parse().unwrap(), port),
-            ntp_port: Some(568),
-            ntp_server: Some("jantje".into()),
-            accept_ntp_versions: vec![NtpVersion::V4],
+            ntpv4_endpoint: NtsNtpEndpoint {
+                server: Some("jantje".into()),
+                port: Some(568),
+            },
+            ntpv5_endpoint: NtsNtpEndpoint {
+                server: Some("pietje".into()),
+                port: Some(5123),
+            },
+            accept_ntp_versions: vec![NtpVersion::V4, NtpVersion::V5],
+            ntpv4_cookies: NtsCookieConfig::default(),
+            ntpv5_cookies: NtsCookieConfig::default(),
         };
 
-        let _join_handle = spawn(nts_ke_config, keyset);
+        let _join_handle = spawn(nts_ke_config, keyset, NtsKeServerStats::default());
 
         // give the server some time to make the port available
         tokio::time::sleep(std::time::Duration::from_millis(20)).await;
@@ -369,5 +411,22 @@ mod tests {
 
         assert_eq!(result.remote, "jantje");
         assert_eq!(result.port, 568);
+
+        let result = async move {
+            let kex = KeyExchangeClient::new(&NtsClientConfig {
+                certificates: certificates_from_bufread(BufReader::new(Cursor::new(ca)))
+                    .unwrap()
+                    .into(),
+                protocol_version: ntp_proto::ProtocolVersion::V5,
+            })
+            .unwrap();
+            let io = TcpStream::connect(("localhost", port)).await.unwrap();
+            kex.exchange_keys(io, "localhost".into(), []).await
+        }
+        .await
+        .unwrap();
+
+        assert_eq!(result.remote, "pietje");
+        assert_eq!(result.port, 5123);
     }
 }
@@ -14,7 +14,10 @@ pub fn spawn() -> std::io::Result<watch::Receiver<Arc<[IpAddr]>>> {
 
     tokio::spawn(async move {
         loop {
-            change_listener.wait_for_change().await;
+            tokio::select! {
+                _ = change_listener.wait_for_change() => {}
+                () = writer.closed() => break,
+            }
             match interfaces() {
                 Ok(interfaces) => {
                     let _ = writer.send(
@@ -1,5 +1,7 @@
+mod broadcast_source;
 mod clock;
 pub mod config;
+pub mod control;
 #[cfg(target_os = "linux")]
 mod csptp_server;
 #[cfg(target_os = "linux")]
@@ -10,8 +12,10 @@ mod local_ip_provider;
 mod ntp_source;
 pub mod nts_key_provider;
 pub mod observer;
+mod peerstats;
 #[cfg(feature = "pps")]
 mod pps_source;
+mod reload;
 mod server;
 mod sock_source;
 pub mod sockets;
@@ -30,7 +34,7 @@ pub use system::spawn;
 use tokio::runtime::Builder;
 use tracing_subscriber::util::SubscriberInitExt;
 
-use config::NtpDaemonOptions;
+use config::{NtpDaemonOptions, PermissionCheck};
 
 use crate::daemon::tracing::LogReloadTaskStarter;
 use crate::notify::notify_ready;
@@ -54,6 +58,8 @@ pub fn main() -> Result<(), Box<dyn Error>> {
         config::NtpDaemonAction::Version => {
             eprintln!("ntp-daemon {VERSION}");
         }
+        config::NtpDaemonAction::Validate => validate(&options),
+        config::NtpDaemonAction::PrintConfig => print_config(&options),
         config::NtpDaemonAction::Run => run(&options)?,
     }
 
@@ -73,21 +79,23 @@ pub(crate) enum Application {
 pub(crate) fn initialize_logging_parse_config(
     initial_log_level: Option<LogLevel>,
     config_path: Option<&Path>,
+    permission_check: PermissionCheck,
     app: Application,
-) -> (Config, Option<LogReloadTaskStarter>) {
+) -> std::io::Result<(Config, Option<LogReloadTaskStarter>)> {
     let mut log_level = initial_log_level.unwrap_or_default();
 
-    let (config_tracing, _) = crate::daemon::tracing::tracing_init(log_level, None, true);
+    let config_tracing = crate::daemon::tracing::stdout_tracing_init(log_level, true);
     let (config, tracing_inst, task_starter) =
         ::tracing::subscriber::with_default(config_tracing, || {
-            let config = match Config::from_args(config_path.as_ref(), vec![], vec![]) {
-                Ok(c) => c,
-                Err(e) => {
-                    // print to stderr because tracing is not yet setup
-                    eprintln!("There was an error loading the config: {e}");
-                    std::process::exit(exitcode::CONFIG);
-                }
-            };
+            let config =
+                match Config::from_args(config_path.as_ref(), vec![], vec![], permission_check) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        // print to stderr because tracing is not yet setup
+                        eprintln!("There was an error loading the config: {e}");
+                        std::process::exit(exitcode::CONFIG);
+                    }
+                };
 
             if let Some(config_log_level) = config.observability.log_level
                 && initial_log_level.is_none()
@@ -109,21 +117,91 @@ pub(crate) fn initialize_logging_parse_config(
                 .unwrap_or_else(|| log_path.is_none() && std::io::stdout().is_terminal());
 
             // set a default global subscriber from now on
-            let (tracing_inst, task_starter) =
-                self::tracing::tracing_init(log_level, log_path, ansi_colors);
-            (config, tracing_inst, task_starter)
-        });
+            let (tracing_inst, task_starter) = self::tracing::tracing_init(
+                log_level,
+                config.observability.log_target,
+                log_path,
+                ansi_colors,
+            )?;
+            Ok::<_, std::io::Error>((config, tracing_inst, task_starter))
+        })?;
+    tracing_inst.init();
+
+    Ok((config, task_starter))
+}
+
+// Checks the configuration without starting the daemon, so no sockets are opened
+// and the clock is left alone.
+fn validate(options: &NtpDaemonOptions) {
+    let tracing_inst = self::tracing::stdout_tracing_init(
+        options.log_level.unwrap_or_default(),
+        std::io::stdout().is_terminal(),
+    );
     tracing_inst.init();
 
-    (config, task_starter)
+    let mut config = match Config::from_args(
+        options.config.as_ref(),
+        vec![],
+        vec![],
+        options.permission_check(),
+    ) {
+        Ok(c) => c,
+        Err(e) => {
+            ::tracing::error!("There was an error loading the config: {e}");
+            std::process::exit(exitcode::CONFIG);
+        }
+    };
+    if let Err(e) = config.override_poll_interval_limits(options.min_poll, options.max_poll) {
+        ::tracing::error!("{e}");
+        std::process::exit(exitcode::CONFIG);
+    }
+
+    if !config.check() {
+        ::tracing::error!("The configuration has problems, see the warnings above");
+        std::process::exit(exitcode::CONFIG);
+    }
+
+    info!("The configuration is valid");
+}
+
+// Prints the configuration the daemon would run with, after merging the
+// drop-in files and applying the command line overrides. Nothing is logged, as
+// the log output would end up in between the configuration.
+fn print_config(options: &NtpDaemonOptions) {
+    let mut config = match Config::from_args(
+        options.config.as_ref(),
+        vec![],
+        vec![],
+        options.permission_check(),
+    ) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("There was an error loading the config: {e}");
+            std::process::exit(exitcode::CONFIG);
+        }
+    };
+    if let Err(e) = config.override_poll_interval_limits(options.min_poll, options.max_poll) {
+        eprintln!("{e}");
+        std::process::exit(exitcode::CONFIG);
+    }
+
+    match config.to_toml() {
+        Ok(toml) => print!("{toml}"),
+        Err(e) => {
+            eprintln!("Could not print the config: {e}");
+            std::process::exit(exitcode::SOFTWARE);
+        }
+    }
 }
 
 fn run(options: &NtpDaemonOptions) -> Result<(), Box<dyn Error>> {
-    let (config, task_starter) = initialize_logging_parse_config(
+    let (mut config, task_starter) = initialize_logging_parse_config(
         options.log_level,
         options.config.as_deref(),
+        options.permission_check(),
         Application::Deamon,
-    );
+    )?;
+    config.override_poll_interval_limits(options.min_poll, options.max_poll)?;
 
     let runtime = if config.servers.is_empty() && config.nts_ke.is_empty() {
         Builder::new_current_thread().enable_all().build()?
@@ -141,53 +219,150 @@ fn run(options: &NtpDaemonOptions) -> Result<(), Box<dyn Error>> {
             info!("Log level override from command line arguments is active");
         }
 
-        // Warn/error if the config is unreasonable. We do this after finishing
-        // tracing setup to ensure logging is fully configured.
-        config.check();
-
-        // we always generate the keyset (even if NTS is not used)
-        let keyset = nts_key_provider::spawn(config.keyset).await;
-
-        #[cfg(feature = "hardware-timestamping")]
-        let clock_config = config.clock;
-
-        #[cfg(not(feature = "hardware-timestamping"))]
-        let clock_config = config::ClockConfig::default();
-
-        ::tracing::debug!("Configuration loaded, spawning daemon jobs");
-        let clock = clock_config.clock;
-        let (main_loop_handle, channels) =
-            spawn::<TimeSyncControllerWrapper<KalmanClockController<_>>>(
-                config.synchronization.synchronization_base,
-                config.synchronization.algorithm,
-                config.source_defaults,
-                clock_config,
-                &config.sources,
-                &config.servers,
-                #[cfg(target_os = "linux")]
-                &config.csptp_servers,
-                keyset.clone(),
-                #[cfg(target_os = "linux")]
-                config.csptp,
-            )
-            .await?;
-
-        for nts_ke_config in config.nts_ke {
-            let _join_handle = keyexchange::spawn(nts_ke_config, keyset.clone());
+        if options.min_poll.is_some() || options.max_poll.is_some() {
+            info!("Poll interval override from command line arguments is active");
         }
 
+        Ok(run_until(config, Some(options), std::future::pending()).await?)
+    })
+}
+
+/// Run the daemon with the given configuration until `shutdown` completes.
+///
+/// This starts the configured sources, servers, observer and control socket
+/// and steers the clock, just like the `ntp-daemon` binary. When `shutdown`
+/// completes, or the servers have finished draining on request of the control
+/// socket, all of these are stopped again and `Ok(())` is returned. Must be
+/// called from within a tokio runtime.
+pub async fn run_daemon(config: Config, shutdown: impl Future<Output = ()>) -> std::io::Result<()> {
+    run_until(config, None, shutdown).await
+}
+
+/// Run the daemon until `shutdown` completes. When started from the command
+/// line, `options` is used to reload the configuration on a hangup signal.
+async fn run_until(
+    config: Config,
+    options: Option<&NtpDaemonOptions>,
+    shutdown: impl Future<Output = ()>,
+) -> std::io::Result<()> {
+    // Warn/error if the config is unreasonable. We do this after finishing
+    // tracing setup to ensure logging is fully configured.
+    config.check();
+    config.check_privileges();
+
+    // we always generate the keyset (even if NTS is not used)
+    let keyset = nts_key_provider::spawn(config.keyset.clone()).await;
+
+    #[cfg(feature = "hardware-timestamping")]
+    let clock_config = config.clock.clone();
+
+    #[cfg(not(feature = "hardware-timestamping"))]
+    let clock_config = config::ClockConfig::default();
+
+    ::tracing::debug!("Configuration loaded, spawning daemon jobs");
+    let clock = clock_config.clock;
+    let (mut main_loop_handle, channels) =
+        spawn::<TimeSyncControllerWrapper<KalmanClockController<_>>>(
+            config.synchronization.synchronization_base,
+            config.synchronization.algorithm,
+            config.source_defaults,
+            config.startup_source_retry,
+            config.dns,
+            clock_config,
+            config.observability.clock_history_length,
+            config.observability.source_histograms(),
+            &config.sources,
+            &config.servers,
+            #[cfg(target_os = "linux")]
+            &config.csptp_servers,
+            keyset.clone(),
+            #[cfg(target_os = "linux")]
+            config.csptp,
+        )
+        .await?;
+
+    let mut tasks = vec![];
+
+    let mut nts_ke_servers = vec![];
+    for nts_ke_config in &config.nts_ke {
+        let stats = keyexchange::NtsKeServerStats::default();
+        nts_ke_servers.push(observer::ObservableNtsKeServerState {
+            address: nts_ke_config.listen,
+            stats: stats.clone(),
+        });
+        tasks.push(keyexchange::spawn(nts_ke_config.clone(), keyset.clone(), stats).abort_handle());
+    }
+
+    tasks.push(
         observer::spawn(
             &config.observability,
             channels.source_snapshots,
             channels.server_data_receiver,
             channels.system_snapshot_receiver,
+            channels.clock_history,
+            channels.source_histograms,
+            nts_ke_servers.into(),
             clock,
+        )
+        .abort_handle(),
+    );
+
+    let mut draining = channels.drain.subscribe();
+    tasks.push(
+        control::spawn(
+            &config.observability,
+            channels.source_requests,
+            channels.disabled_sources,
+            channels.force_sync,
+            channels.drain,
+        )
+        .abort_handle(),
+    );
+
+    if let Some(options) = options {
+        tasks.push(
+            reload::spawn(
+                options.config.clone(),
+                options.permission_check(),
+                (options.min_poll, options.max_poll),
+                config,
+                channels.config_reload,
+            )
+            .abort_handle(),
         );
 
         let _ = notify_ready().await;
+    }
 
-        Ok(main_loop_handle.await??)
-    })
+    let result = tokio::select! {
+        result = &mut main_loop_handle => match result {
+            Ok(result) => result,
+            Err(e) => Err(e.into()),
+        },
+        () = shutdown => Ok(()),
+        () = drained(&mut draining) => Ok(()),
+    };
+
+    // Stopping the main loop also stops the spawners, sources and servers it
+    // manages
+    main_loop_handle.abort();
+    for task in tasks {
+        task.abort();
+    }
+
+    result
+}
+
+/// Completes once the servers have been draining for the period requested
+/// over the control socket.
+async fn drained(draining: &mut tokio::sync::watch::Receiver<Option<std::time::Duration>>) {
+    let period = match draining.wait_for(Option::is_some).await {
+        Ok(period) => period.unwrap_or_default(),
+        // Without a control socket, draining can never be requested
+        Err(_) => std::future::pending().await,
+    };
+    tokio::time::sleep(period).await;
+    info!("Servers drained, shutting down");
 }
 
 pub(crate) mod exitcode {
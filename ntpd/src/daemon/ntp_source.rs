@@ -12,11 +12,17 @@ use timestamped_socket::{
     interface::InterfaceName,
     socket::{Connected, RecvResult, Socket, connect_address},
 };
-use tracing::{Instrument, Span, debug, error, instrument, warn};
+use tracing::{Instrument, Span, debug, error, info, instrument, warn};
 
 use tokio::time::{Instant, Sleep};
 
-use super::{config::TimestampMode, exitcode, util::convert_net_timestamp};
+use super::{
+    config::TimestampMode,
+    control::{ControlResponse, SourceCommand, SourceRequest},
+    exitcode,
+    system::SourceHistograms,
+    util::{convert_net_timestamp, is_transient_io_error, set_dscp},
+};
 
 /// Trait needed to allow injecting of futures other than `tokio::time::Sleep` for testing
 pub trait Wait: Future<Output = ()> {
@@ -43,6 +49,7 @@ pub enum MsgForSystem {
 pub struct SourceChannels {
     pub msg_for_system_sender: tokio::sync::mpsc::Sender<MsgForSystem>,
     pub source_snapshots: Arc<std::sync::RwLock<HashMap<ClockId, ObservableSourceState>>>,
+    pub source_histograms: Arc<std::sync::RwLock<SourceHistograms>>,
 }
 
 pub(crate) struct SourceTask<C: 'static + NtpClock + Send, Controller: SourceController, T: Wait> {
@@ -51,10 +58,12 @@ pub(crate) struct SourceTask<C: 'static + NtpClock + Send, Controller: SourceCon
     clock: C,
     interface: Option<InterfaceName>,
     timestamp_mode: TimestampMode,
+    dscp: Option<u8>,
     name: String,
     source_addr: SocketAddr,
     socket: Option<Socket<SocketAddr, Connected>>,
     channels: SourceChannels,
+    source_requests: tokio::sync::mpsc::Receiver<SourceRequest>,
 
     source: NtpSource<Controller>,
 
@@ -93,7 +102,14 @@ where
         };
 
         self.socket = match socket_res {
-            Ok(socket) => Some(socket),
+            Ok(socket) => {
+                if let Some(dscp) = self.dscp
+                    && let Err(error) = set_dscp(&socket, self.source_addr, dscp)
+                {
+                    warn!(?error, dscp, "Could not set DSCP on socket");
+                }
+                Some(socket)
+            }
             Err(error) => {
                 warn!(?error, "Could not open socket");
                 return SocketResult::Abort;
@@ -109,22 +125,34 @@ where
         loop {
             enum SelectResult {
                 Timer,
-                Recv(Result<RecvResult<SocketAddr>, std::io::Error>),
+                SourceRequest(SourceRequest),
+                Recv(
+                    Result<RecvResult<SocketAddr>, std::io::Error>,
+                    tokio::time::Instant,
+                ),
             }
 
             let mut buf = [0_u8; 1024];
+            // Outstanding request for an immediate poll, answered once the poll is sent
+            let mut poll_reply = None;
 
+            let socket = &mut self.socket;
             let selected: SelectResult = tokio::select! {
                 () = &mut poll_wait => {
                     SelectResult::Timer
                 },
-                result = async { if let Some(ref mut socket) = self.socket { socket.recv(&mut buf).await } else { std::future::pending().await }} => {
-                    SelectResult::Recv(result)
+                Some(request) = self.source_requests.recv() => {
+                    SelectResult::SourceRequest(request)
+                },
+                result = async { if let Some(socket) = socket { socket.recv(&mut buf).await } else { std::future::pending().await }} => {
+                    // Read the monotonic clock as close as possible to the
+                    // receive timestamp, to detect steps of the clock
+                    SelectResult::Recv(result, tokio::time::Instant::now())
                 },
             };
 
             let actions = match selected {
-                SelectResult::Recv(result) => {
+                SelectResult::Recv(result, recv_instant) => {
                     tracing::debug!("accept packet");
                     match accept_packet(result, &buf, &self.clock) {
                         AcceptResult::Accept(packet, recv_timestamp) => {
@@ -132,17 +160,23 @@ where
                                 debug!("we received a message without having sent one; discarding");
                                 continue;
                             };
-                            let actions =
-                                self.source
-                                    .handle_incoming(packet, send_timestamp, recv_timestamp);
+                            let actions = self.source.handle_incoming(
+                                packet,
+                                send_timestamp,
+                                recv_timestamp,
+                                recv_instant,
+                            );
+                            let observed = self.source.observe(self.name.clone(), self.index);
+                            self.channels
+                                .source_histograms
+                                .write()
+                                .expect("Unexpected poisoned mutex")
+                                .record_source(&observed);
                             self.channels
                                 .source_snapshots
                                 .write()
                                 .expect("Unexpected poisoned mutex")
-                                .insert(
-                                    self.index,
-                                    self.source.observe(self.name.clone(), self.index),
-                                );
+                                .insert(self.index, observed);
                             actions
                         }
                         AcceptResult::NetworkGone => {
@@ -161,6 +195,42 @@ where
                         AcceptResult::Ignore => NtpSourceActionIterator::default(),
                     }
                 }
+                SelectResult::SourceRequest(SourceRequest {
+                    command: SourceCommand::Poll,
+                    reply,
+                }) => {
+                    let may_poll = self
+                        .clock
+                        .now()
+                        .is_ok_and(|now| self.source.may_poll_early(now));
+                    if !may_poll {
+                        debug!("ignoring poll request due to rate limit");
+                        reply.send(ControlResponse::RateLimited).ok();
+                        continue;
+                    }
+
+                    tracing::debug!("poll requested");
+                    poll_reply = Some(reply);
+                    self.source.handle_timer()
+                }
+                SelectResult::SourceRequest(SourceRequest {
+                    command: SourceCommand::Disable,
+                    reply,
+                }) => {
+                    info!("Source disabled through the control socket");
+                    self.source.set_disabled(true);
+                    reply.send(ControlResponse::SourceDisabled).ok();
+                    continue;
+                }
+                SelectResult::SourceRequest(SourceRequest {
+                    command: SourceCommand::Enable,
+                    reply,
+                }) => {
+                    info!("Source enabled through the control socket");
+                    self.source.set_disabled(false);
+                    reply.send(ControlResponse::SourceEnabled).ok();
+                    continue;
+                }
                 SelectResult::Timer => {
                     tracing::debug!("wait completed");
                     let actions = self.source.handle_timer();
@@ -236,6 +306,22 @@ where
                                     .selected_timestamp()
                                     .map(convert_net_timestamp)
                                     .or(self.last_send_timestamp);
+
+                                if let Some(reply) = poll_reply.take() {
+                                    reply.send(ControlResponse::PollSent).ok();
+                                }
+
+                                if let Some(send_timestamp) = self.last_send_timestamp {
+                                    self.source.handle_poll_sent(send_timestamp);
+                                    self.channels
+                                        .source_snapshots
+                                        .write()
+                                        .expect("Unexpected poisoned mutex")
+                                        .insert(
+                                            self.index,
+                                            self.source.observe(self.name.clone(), self.index),
+                                        );
+                                }
                             }
                         }
                     }
@@ -282,7 +368,7 @@ where
     C: 'static + NtpClock + Send + Sync,
 {
     #[expect(clippy::too_many_arguments)]
-    #[instrument(level = tracing::Level::ERROR, name = "Ntp Source", skip(timestamp_mode, clock, channels, source, initial_actions))]
+    #[instrument(level = tracing::Level::ERROR, name = "Ntp Source", skip(timestamp_mode, clock, channels, source, initial_actions, source_requests))]
     pub fn spawn(
         index: ClockId,
         name: String,
@@ -290,9 +376,11 @@ where
         interface: Option<InterfaceName>,
         clock: C,
         timestamp_mode: TimestampMode,
+        dscp: Option<u8>,
         channels: SourceChannels,
         source: NtpSource<Controller>,
         initial_actions: NtpSourceActionIterator,
+        source_requests: tokio::sync::mpsc::Receiver<SourceRequest>,
     ) -> tokio::task::JoinHandle<()> {
         tokio::spawn(
             (async move {
@@ -322,8 +410,10 @@ where
                     name,
                     clock,
                     channels,
+                    source_requests,
                     interface,
                     timestamp_mode,
+                    dscp,
                     source_addr,
                     socket: None,
                     source,
@@ -338,13 +428,13 @@ where
 }
 
 #[derive(Debug)]
-enum AcceptResult<'a> {
+pub(super) enum AcceptResult<'a> {
     Accept(&'a [u8], NtpTimestamp),
     Ignore,
     NetworkGone,
 }
 
-fn accept_packet<'a, C: NtpClock>(
+pub(super) fn accept_packet<'a, C: NtpClock>(
     result: Result<RecvResult<SocketAddr>, std::io::Error>,
     buf: &'a [u8],
     clock: &C,
@@ -380,6 +470,11 @@ fn accept_packet<'a, C: NtpClock>(
                 AcceptResult::Accept(&buf[0..size], recv_timestamp)
             }
         }
+        Err(receive_error) if is_transient_io_error(&receive_error) => {
+            // Nothing is wrong with the socket, just try again
+            debug!(?receive_error, "transient error receiving packet, retrying");
+            AcceptResult::Ignore
+        }
         Err(receive_error) => {
             warn!(?receive_error, "could not receive packet");
 
@@ -487,6 +582,35 @@ mod tests {
     #[derive(Debug, Clone, Default)]
     struct TestClock {}
 
+    #[test]
+    fn test_accept_packet_errors() {
+        let buf = [0; 48];
+        let clock = TestClock {};
+
+        // Transient errors and errors not caused by the network keep the source running
+        for error in [
+            std::io::Error::from(std::io::ErrorKind::Interrupted),
+            std::io::Error::from(std::io::ErrorKind::WouldBlock),
+            std::io::Error::from_raw_os_error(libc::EINTR),
+            std::io::Error::from_raw_os_error(libc::EAGAIN),
+            std::io::Error::from_raw_os_error(libc::ECONNREFUSED),
+        ] {
+            assert!(matches!(
+                accept_packet(Err(error), &buf, &clock),
+                AcceptResult::Ignore
+            ));
+        }
+
+        assert!(matches!(
+            accept_packet(
+                Err(std::io::Error::from_raw_os_error(libc::ENETDOWN)),
+                &buf,
+                &clock
+            ),
+            AcceptResult::NetworkGone
+        ));
+    }
+
     impl NtpClock for TestClock {
         type Error = std::time::SystemTimeError;
 
@@ -540,6 +664,7 @@ mod tests {
         SourceTask<TestClock, TwoWaySourceControllerWrapper<TwoWayKalmanSourceController>, T>,
         Socket<SocketAddr, Open>,
         mpsc::Receiver<MsgForSystem>,
+        mpsc::Sender<SourceRequest>,
     ) {
         let port_base = alloc_port();
         let test_socket = open_ip(
@@ -550,6 +675,7 @@ mod tests {
         .unwrap();
 
         let (msg_for_system_sender, msg_for_system_receiver) = mpsc::channel(1);
+        let (source_request_sender, source_requests) = mpsc::channel(1);
 
         let index = ClockId::new();
         let controller = TimeSyncControllerWrapper::<KalmanClockController<_>>::new(
@@ -577,22 +703,30 @@ mod tests {
             channels: SourceChannels {
                 msg_for_system_sender,
                 source_snapshots: Arc::new(RwLock::new(HashMap::new())),
+                source_histograms: Arc::new(RwLock::new(SourceHistograms::default())),
             },
+            source_requests,
             source_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, port_base)),
             interface: None,
             timestamp_mode: TimestampMode::KernelRecv,
+            dscp: None,
             socket: None,
             source,
             last_send_timestamp: None,
         };
 
-        (process, test_socket, msg_for_system_receiver)
+        (
+            process,
+            test_socket,
+            msg_for_system_receiver,
+            source_request_sender,
+        )
     }
 
     #[tokio::test]
     async fn test_poll_sends_state_update_and_packet() {
         // Note: Ports must be unique among tests to deal with parallelism
-        let (mut process, socket, _) = test_startup();
+        let (mut process, socket, _, _) = test_startup();
 
         let (poll_wait, poll_send) = TestWait::new();
 
@@ -610,6 +744,82 @@ mod tests {
         handle.abort();
     }
 
+    #[tokio::test]
+    async fn test_poll_request_sends_extra_poll() {
+        // Note: Ports must be unique among tests to deal with parallelism
+        let (mut process, socket, _, source_requests) = test_startup();
+
+        let (poll_wait, _poll_send) = TestWait::new();
+
+        let handle = tokio::spawn(async move {
+            tokio::pin!(poll_wait);
+            process.run(poll_wait).await;
+        });
+
+        // The poll timer never fires, so the packet is due to the request
+        let (reply, reply_rx) = tokio::sync::oneshot::channel();
+        let command = SourceCommand::Poll;
+        source_requests
+            .send(SourceRequest { command, reply })
+            .await
+            .unwrap();
+
+        let mut buf = [0; 48];
+        let network = socket.recv(&mut buf).await.unwrap();
+        assert_eq!(network.bytes_read, 48);
+        assert_eq!(reply_rx.await.unwrap(), ControlResponse::PollSent);
+
+        // Polling again right away would exceed the minimum poll interval
+        let (reply, reply_rx) = tokio::sync::oneshot::channel();
+        let command = SourceCommand::Poll;
+        source_requests
+            .send(SourceRequest { command, reply })
+            .await
+            .unwrap();
+        assert_eq!(reply_rx.await.unwrap(), ControlResponse::RateLimited);
+
+        tokio::select! {
+            () = tokio::time::sleep(Duration::from_millis(10)) => {/*expected */},
+            _ = socket.recv(&mut buf) => { unreachable!("should not receive anything") }
+        }
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_disable_request() {
+        // Note: Ports must be unique among tests to deal with parallelism
+        let (mut process, socket, _, source_requests) = test_startup();
+
+        let (poll_wait, _poll_send) = TestWait::new();
+
+        let handle = tokio::spawn(async move {
+            tokio::pin!(poll_wait);
+            process.run(poll_wait).await;
+        });
+
+        for (command, expected) in [
+            (SourceCommand::Disable, ControlResponse::SourceDisabled),
+            (SourceCommand::Enable, ControlResponse::SourceEnabled),
+        ] {
+            let (reply, reply_rx) = tokio::sync::oneshot::channel();
+            source_requests
+                .send(SourceRequest { command, reply })
+                .await
+                .unwrap();
+            assert_eq!(reply_rx.await.unwrap(), expected);
+        }
+
+        // Changing whether the source is used does not trigger a poll
+        let mut buf = [0; 48];
+        tokio::select! {
+            () = tokio::time::sleep(Duration::from_millis(10)) => {/*expected */},
+            _ = socket.recv(&mut buf) => { unreachable!("should not receive anything") }
+        }
+
+        handle.abort();
+    }
+
     fn serialize_packet_unencrypted(send_packet: &NtpPacket) -> [u8; 48] {
         let mut buf = [0; 48];
         let mut cursor = Cursor::new(buf.as_mut_slice());
@@ -623,7 +833,7 @@ mod tests {
     #[tokio::test]
     async fn test_timeroundtrip() {
         // Note: Ports must be unique among tests to deal with parallelism
-        let (mut process, mut socket, mut msg_recv) = test_startup();
+        let (mut process, mut socket, mut msg_recv, _) = test_startup();
 
         let server_info = NtpServerInfo {
             time_snapshot: TimeSnapshot {
@@ -672,7 +882,7 @@ mod tests {
     #[tokio::test]
     async fn test_deny_stops_poll() {
         // Note: Ports must be unique among tests to deal with parallelism
-        let (mut process, mut socket, mut msg_recv) = test_startup();
+        let (mut process, mut socket, mut msg_recv, _) = test_startup();
 
         let (poll_wait, poll_send) = TestWait::new();
 
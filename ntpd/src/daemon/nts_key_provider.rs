@@ -1,12 +1,13 @@
 use std::{
     fs::{File, OpenOptions},
     os::unix::prelude::{OpenOptionsExt, PermissionsExt},
+    path::Path,
     sync::Arc,
 };
 
 use ntp_proto::{KeySet, KeySetProvider};
 use tokio::sync::watch;
-use tracing::{Span, instrument, warn};
+use tracing::{Instrument, Span, instrument, warn};
 
 use super::config::KeysetConfig;
 
@@ -58,37 +59,54 @@ pub async fn spawn(config: KeysetConfig) -> watch::Receiver<Arc<KeySet>> {
         ),
     };
     let (tx, rx) = watch::channel(provider.get());
-    let span = Span::current();
-    tokio::task::spawn_blocking(move || {
-        let _enter = span.enter();
-        loop {
-            // First save, then sleep. Ensures new sets created at boot are also saved.
-            if let Some(path) = &config.key_storage_path
-                && let Err(e) = (|| -> std::io::Result<()> {
-                    let mut output = OpenOptions::new()
-                        .create(true)
-                        .truncate(true)
-                        .write(true)
-                        .mode(0o600)
-                        .open(path)?;
-                    provider.store(&mut output)
-                })()
-            {
-                if e.kind() == std::io::ErrorKind::NotFound
-                    || e.kind() == std::io::ErrorKind::PermissionDenied
-                {
-                    warn!(error = ?e, "Could not store nts server keys, parent directory does not exist or has insufficient permissions");
-                } else {
-                    warn!(error = ?e, "Could not store nts server keys");
+    tokio::spawn(
+        (async move {
+            loop {
+                // First save, then sleep. Ensures new sets created at boot are also saved.
+                if let Some(path) = config.key_storage_path.clone() {
+                    provider = match tokio::task::spawn_blocking(move || {
+                        store(&provider, &path);
+                        provider
+                    })
+                    .await
+                    {
+                        Ok(provider) => provider,
+                        Err(_) => break,
+                    };
                 }
+                if tx.send(provider.get()).is_err() {
+                    break;
+                }
+                // Stop rotating once nobody uses the keyset anymore
+                tokio::select! {
+                    () = tokio::time::sleep(next_interval) => {}
+                    () = tx.closed() => break,
+                }
+                next_interval = std::time::Duration::from_secs(config.key_rotation_interval as _);
+                provider.rotate();
             }
-            if tx.send(provider.get()).is_err() {
-                break;
-            }
-            std::thread::sleep(next_interval);
-            next_interval = std::time::Duration::from_secs(config.key_rotation_interval as _);
-            provider.rotate();
-        }
-    });
+        })
+        .instrument(Span::current()),
+    );
     rx
 }
+
+fn store(provider: &KeySetProvider, path: &Path) {
+    if let Err(e) = (|| -> std::io::Result<()> {
+        let mut output = OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .mode(0o600)
+            .open(path)?;
+        provider.store(&mut output)
+    })() {
+        if e.kind() == std::io::ErrorKind::NotFound
+            || e.kind() == std::io::ErrorKind::PermissionDenied
+        {
+            warn!(error = ?e, "Could not store nts server keys, parent directory does not exist or has insufficient permissions");
+        } else {
+            warn!(error = ?e, "Could not store nts server keys");
+        }
+    }
+}
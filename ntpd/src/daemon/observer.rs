@@ -1,6 +1,10 @@
+use super::keyexchange::NtsKeServerStats;
 use super::server::ServerStats;
 use super::sockets::create_unix_socket_with_permissions;
-use super::system::ServerData;
+use super::system::{ClockHistory, ClockSample, ServerData};
+pub use super::system::{
+    Histogram, HistogramBuckets, ObservableSourceHistograms, SourceHistograms,
+};
 use libc::{ECONNABORTED, EMFILE, ENFILE, ENOBUFS, ENOMEM};
 use ntp_proto::{ClockId, NtpClock, NtpTimestamp, ObservableSourceState, SystemSnapshot};
 use std::collections::HashMap;
@@ -8,7 +12,7 @@ use std::convert::Into;
 use std::os::unix::fs::PermissionsExt;
 use std::sync::Arc;
 use std::{net::SocketAddr, time::Instant};
-use tokio::task::JoinHandle;
+use tokio::task::{JoinHandle, JoinSet};
 use tracing::{Instrument, Span, debug, error, instrument, trace, warn};
 
 use serde::{Deserialize, Serialize};
@@ -17,8 +21,17 @@ use serde::{Deserialize, Serialize};
 pub struct ObservableState {
     pub program: ProgramData,
     pub system: SystemSnapshot,
+    /// Number of bits set in the NTPv5 loop detection bloom filter
+    #[serde(default)]
+    pub bloom_filter_population: u16,
     pub sources: Vec<ObservableSourceState>,
     pub servers: Vec<ObservableServerState>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nts_ke_servers: Vec<ObservableNtsKeServerState>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clock_history: Vec<ClockSample>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub source_histograms: Vec<ObservableSourceHistograms>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,6 +41,32 @@ pub struct ProgramData {
     pub build_commit_date: String,
     pub uptime_seconds: f64,
     pub now: NtpTimestamp,
+    /// Cargo features the daemon was built with
+    #[serde(default)]
+    pub enabled_features: Vec<String>,
+}
+
+/// Optional cargo features of the daemon, and whether they were enabled at
+/// compile time
+const FEATURES: &[(&str, bool)] = &[
+    ("aws-lc", cfg!(feature = "aws-lc")),
+    ("rustcrypto", cfg!(feature = "rustcrypto")),
+    ("openssl", cfg!(feature = "openssl")),
+    ("openssl-vendored", cfg!(feature = "openssl-vendored")),
+    ("pps", cfg!(feature = "pps")),
+    ("srv", cfg!(feature = "srv")),
+    (
+        "hardware-timestamping",
+        cfg!(feature = "hardware-timestamping"),
+    ),
+];
+
+fn enabled_features() -> Vec<String> {
+    FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| (*name).to_owned())
+        .collect()
 }
 
 impl ProgramData {
@@ -48,6 +87,7 @@ impl Default for ProgramData {
             build_commit_date: env!("NTPD_RS_GIT_DATE").to_owned(),
             uptime_seconds: 0.0,
             now: NtpTimestamp::default(),
+            enabled_features: enabled_features(),
         }
     }
 }
@@ -58,6 +98,12 @@ pub struct ObservableServerState {
     pub stats: ServerStats,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObservableNtsKeServerState {
+    pub address: SocketAddr,
+    pub stats: NtsKeServerStats,
+}
+
 impl From<&ServerData> for ObservableServerState {
     fn from(data: &ServerData) -> Self {
         ObservableServerState {
@@ -73,13 +119,25 @@ pub fn spawn<C: 'static + NtpClock + Send>(
     sources_reader: Arc<std::sync::RwLock<HashMap<ClockId, ObservableSourceState>>>,
     server_reader: tokio::sync::watch::Receiver<Vec<ServerData>>,
     system_reader: tokio::sync::watch::Receiver<SystemSnapshot>,
+    clock_history: Arc<std::sync::RwLock<ClockHistory>>,
+    source_histograms: Arc<std::sync::RwLock<SourceHistograms>>,
+    nts_ke_servers: Arc<[ObservableNtsKeServerState]>,
     clock: C,
 ) -> JoinHandle<std::io::Result<()>> {
     let config = config.clone();
     tokio::spawn(
         (async move {
-            let result =
-                observer(config, sources_reader, server_reader, system_reader, clock).await;
+            let result = observer(
+                config,
+                sources_reader,
+                server_reader,
+                system_reader,
+                clock_history,
+                source_histograms,
+                nts_ke_servers,
+                clock,
+            )
+            .await;
             if let Err(ref e) = result {
                 warn!("Abnormal termination of the state observer: {e}");
                 warn!("The state observer will not be available");
@@ -95,23 +153,163 @@ async fn observer<C: 'static + NtpClock + Send>(
     sources_reader: Arc<std::sync::RwLock<HashMap<ClockId, ObservableSourceState>>>,
     server_reader: tokio::sync::watch::Receiver<Vec<ServerData>>,
     system_reader: tokio::sync::watch::Receiver<SystemSnapshot>,
+    clock_history: Arc<std::sync::RwLock<ClockHistory>>,
+    source_histograms: Arc<std::sync::RwLock<SourceHistograms>>,
+    nts_ke_servers: Arc<[ObservableNtsKeServerState]>,
     clock: C,
 ) -> std::io::Result<()> {
     let start_time = Instant::now();
     let timeout = std::time::Duration::from_millis(500);
 
-    let Some(path) = config.observation_path else {
-        return Ok(());
-    };
-
     // this binary needs to run as root to be able to adjust the system clock.
     // by default, the socket inherits root permissions, but the client should not need
     // elevated permissions to read from the socket. So we explicitly set the permissions
     let permissions: std::fs::Permissions =
         PermissionsExt::from_mode(config.observation_permissions);
 
+    let hmac_key = config
+        .observation_hmac_key_path
+        .as_deref()
+        .map(read_hmac_key)
+        .transpose()?;
+
+    // The observers of the other sockets are stopped together with this one
+    let mut tasks = JoinSet::new();
+
+    if let Some(stream_path) = &config.observation_stream_path {
+        let stream_listener =
+            create_unix_socket_with_permissions(stream_path, permissions.clone())?;
+        // A zero interval would make the writer spin, so use a minimum of 1ms
+        let interval =
+            std::time::Duration::from_millis(config.observation_stream_interval_ms.max(1));
+        tasks.spawn(
+            stream_observer(
+                stream_listener,
+                interval,
+                hmac_key.clone(),
+                config.observation_max_connections,
+                timeout,
+                start_time,
+                sources_reader.clone(),
+                server_reader.clone(),
+                system_reader.clone(),
+                clock_history.clone(),
+                source_histograms.clone(),
+                nts_ke_servers.clone(),
+                clock.clone(),
+            )
+            .instrument(Span::current()),
+        );
+    }
+
+    // Taking a snapshot requires read locks on the source and system state, so
+    // under heavy polling we serve a cached snapshot to bound contention with
+    // the update path.
+    let snapshot_interval =
+        std::time::Duration::from_millis(config.observation_snapshot_interval_ms);
+
+    if let Some(cbor_path) = &config.observation_cbor_path {
+        let cbor_listener = create_unix_socket_with_permissions(cbor_path, permissions.clone())?;
+        let cbor_observer = snapshot_observer(
+            cbor_listener,
+            ObservationFormat::Cbor,
+            hmac_key.clone(),
+            config.observation_max_connections,
+            snapshot_interval,
+            timeout,
+            start_time,
+            sources_reader.clone(),
+            server_reader.clone(),
+            system_reader.clone(),
+            clock_history.clone(),
+            source_histograms.clone(),
+            nts_ke_servers.clone(),
+            clock.clone(),
+        );
+        tasks.spawn(
+            (async move {
+                if let Err(e) = cbor_observer.await {
+                    warn!("Abnormal termination of the CBOR state observer: {e}");
+                }
+            })
+            .instrument(Span::current()),
+        );
+    }
+
+    let Some(path) = config.observation_path else {
+        while tasks.join_next().await.is_some() {}
+        return Ok(());
+    };
+
     let observe_listener = create_unix_socket_with_permissions(&path, permissions)?;
-    let observe_permits = Arc::new(tokio::sync::Semaphore::new(8));
+    snapshot_observer(
+        observe_listener,
+        ObservationFormat::Json,
+        hmac_key,
+        config.observation_max_connections,
+        snapshot_interval,
+        timeout,
+        start_time,
+        sources_reader,
+        server_reader,
+        system_reader,
+        clock_history,
+        source_histograms,
+        nts_ke_servers,
+        clock,
+    )
+    .await
+}
+
+fn read_hmac_key(path: &std::path::Path) -> std::io::Result<Arc<[u8]>> {
+    // Anyone who can read or replace the key can forge signed output
+    let mode = std::fs::metadata(path)?.permissions().mode() as libc::mode_t;
+    if mode & (libc::S_IROTH | libc::S_IWOTH) != 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!(
+                "observation HMAC key file {} is accessible by others",
+                path.display()
+            ),
+        ));
+    }
+
+    let key = std::fs::read(path)?;
+    if key.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "observation HMAC key file is empty",
+        ));
+    }
+    Ok(key.into())
+}
+
+/// Encoding of the snapshots sent over an observation socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ObservationFormat {
+    Json,
+    Cbor,
+}
+
+#[expect(clippy::too_many_arguments)]
+async fn snapshot_observer<C: 'static + NtpClock + Send>(
+    listener: tokio::net::UnixListener,
+    format: ObservationFormat,
+    hmac_key: Option<Arc<[u8]>>,
+    max_connections: usize,
+    snapshot_interval: std::time::Duration,
+    timeout: std::time::Duration,
+    start_time: Instant,
+    sources_reader: Arc<std::sync::RwLock<HashMap<ClockId, ObservableSourceState>>>,
+    server_reader: tokio::sync::watch::Receiver<Vec<ServerData>>,
+    system_reader: tokio::sync::watch::Receiver<SystemSnapshot>,
+    clock_history: Arc<std::sync::RwLock<ClockHistory>>,
+    source_histograms: Arc<std::sync::RwLock<SourceHistograms>>,
+    nts_ke_servers: Arc<[ObservableNtsKeServerState]>,
+    clock: C,
+) -> std::io::Result<()> {
+    let observe_permits = Arc::new(tokio::sync::Semaphore::new(max_connections.max(1)));
+    let mut cached_snapshot: Option<(Instant, Arc<ObservableState>)> = None;
 
     loop {
         let permit = observe_permits
@@ -119,7 +317,7 @@ async fn observer<C: 'static + NtpClock + Send>(
             .acquire_owned()
             .await
             .expect("Semaphore for observability was unexpectedly closed");
-        let (mut stream, _addr) = match observe_listener.accept().await {
+        let (mut stream, _addr) = match listener.accept().await {
             Ok(a) => a,
             Err(e) if matches!(e.raw_os_error(), Some(ECONNABORTED)) => {
                 debug!("Unexpectedly closed unix socket: {e}");
@@ -137,21 +335,32 @@ async fn observer<C: 'static + NtpClock + Send>(
                 return Err(e);
             }
         };
-        let sources_reader = sources_reader.clone();
-        let server_reader = server_reader.clone();
-        let system_reader = system_reader.clone();
 
-        let now = clock.now().expect("Unable to get current time");
+        let observe = match &cached_snapshot {
+            Some((taken, snapshot)) if taken.elapsed() < snapshot_interval => {
+                trace!("Serving cached observability snapshot");
+                snapshot.clone()
+            }
+            _ => {
+                let now = clock.now().expect("Unable to get current time");
+                let snapshot = Arc::new(take_snapshot(
+                    start_time,
+                    &sources_reader,
+                    &server_reader,
+                    &system_reader,
+                    &clock_history,
+                    &source_histograms,
+                    &nts_ke_servers,
+                    now,
+                ));
+                cached_snapshot = Some((Instant::now(), snapshot.clone()));
+                snapshot
+            }
+        };
+
+        let hmac_key = hmac_key.clone();
         let fut = async move {
-            handle_connection(
-                &mut stream,
-                start_time,
-                &sources_reader,
-                server_reader,
-                system_reader,
-                now,
-            )
-            .await
+            handle_connection(&mut stream, &observe, format, hmac_key.as_deref()).await
         };
 
         tokio::spawn(async move {
@@ -165,27 +374,161 @@ async fn observer<C: 'static + NtpClock + Send>(
     }
 }
 
-async fn handle_connection(
-    stream: &mut (impl tokio::io::AsyncWrite + Unpin),
+#[expect(clippy::too_many_arguments)]
+async fn stream_observer<C: 'static + NtpClock + Send>(
+    listener: tokio::net::UnixListener,
+    interval: std::time::Duration,
+    hmac_key: Option<Arc<[u8]>>,
+    max_connections: usize,
+    timeout: std::time::Duration,
     start_time: Instant,
-    sources_reader: &std::sync::RwLock<HashMap<ClockId, ObservableSourceState>>,
+    sources_reader: Arc<std::sync::RwLock<HashMap<ClockId, ObservableSourceState>>>,
     server_reader: tokio::sync::watch::Receiver<Vec<ServerData>>,
     system_reader: tokio::sync::watch::Receiver<SystemSnapshot>,
+    clock_history: Arc<std::sync::RwLock<ClockHistory>>,
+    source_histograms: Arc<std::sync::RwLock<SourceHistograms>>,
+    nts_ke_servers: Arc<[ObservableNtsKeServerState]>,
+    clock: C,
+) {
+    // Streaming connections are long-lived, so they get their own permits to
+    // avoid starving the regular observation socket.
+    let stream_permits = Arc::new(tokio::sync::Semaphore::new(max_connections.max(1)));
+    // Connections are closed when the observer stops
+    let mut connections = JoinSet::new();
+
+    loop {
+        while connections.try_join_next().is_some() {}
+
+        let permit = stream_permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Semaphore for observability was unexpectedly closed");
+        let (mut stream, _addr) = match listener.accept().await {
+            Ok(a) => a,
+            Err(e) if matches!(e.raw_os_error(), Some(ECONNABORTED)) => {
+                debug!("Unexpectedly closed unix socket: {e}");
+                continue;
+            }
+            Err(e) if matches!(e.raw_os_error(), Some(ENFILE | EMFILE | ENOMEM | ENOBUFS)) => {
+                error!(
+                    "Not enough resources available to accept incoming observability stream socket: {e}"
+                );
+                tokio::time::sleep(timeout).await;
+                continue;
+            }
+            Err(e) => {
+                error!("Could not accept stream connection due to unexpected problem: {e}");
+                warn!("The state observer stream will not be available");
+                return;
+            }
+        };
+        let sources_reader = sources_reader.clone();
+        let server_reader = server_reader.clone();
+        let system_reader = system_reader.clone();
+        let clock_history = clock_history.clone();
+        let source_histograms = source_histograms.clone();
+        let nts_ke_servers = nts_ke_servers.clone();
+        let clock = clock.clone();
+        let hmac_key = hmac_key.clone();
+
+        connections.spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                ticker.tick().await;
+
+                let now = clock.now().expect("Unable to get current time");
+                let observe = take_snapshot(
+                    start_time,
+                    &sources_reader,
+                    &server_reader,
+                    &system_reader,
+                    &clock_history,
+                    &source_histograms,
+                    &nts_ke_servers,
+                    now,
+                );
+
+                // A slow reader may not hold up the writer for longer than the
+                // regular observation timeout.
+                let write = async {
+                    match hmac_key.as_deref() {
+                        Some(key) => {
+                            super::sockets::write_json_line_signed(&mut stream, &observe, key).await
+                        }
+                        None => super::sockets::write_json_line(&mut stream, &observe).await,
+                    }
+                };
+                match tokio::time::timeout(timeout, write).await {
+                    Err(_) => {
+                        debug!("Streaming observability records timed out");
+                        break;
+                    }
+                    Ok(Err(err)) => {
+                        trace!("Observability stream closed: {err}");
+                        break;
+                    }
+                    Ok(Ok(())) => {}
+                }
+            }
+
+            drop(permit);
+        });
+    }
+}
+
+fn take_snapshot(
+    start_time: Instant,
+    sources_reader: &std::sync::RwLock<HashMap<ClockId, ObservableSourceState>>,
+    server_reader: &tokio::sync::watch::Receiver<Vec<ServerData>>,
+    system_reader: &tokio::sync::watch::Receiver<SystemSnapshot>,
+    clock_history: &std::sync::RwLock<ClockHistory>,
+    source_histograms: &std::sync::RwLock<SourceHistograms>,
+    nts_ke_servers: &[ObservableNtsKeServerState],
     now: NtpTimestamp,
-) -> std::io::Result<()> {
-    let observe = ObservableState {
+) -> ObservableState {
+    let system = *system_reader.borrow();
+    ObservableState {
         program: ProgramData::with_dynamics(start_time.elapsed().as_secs_f64(), now),
+        bloom_filter_population: system.ntp_snapshot.bloom_filter_population(),
         sources: sources_reader
             .read()
             .expect("Unexpected poisoned mutex")
             .values()
             .cloned()
             .collect(),
-        system: *system_reader.borrow(),
+        system,
         servers: server_reader.borrow().iter().map(Into::into).collect(),
-    };
+        nts_ke_servers: nts_ke_servers.to_vec(),
+        clock_history: clock_history
+            .read()
+            .expect("Unexpected poisoned mutex")
+            .to_vec(),
+        source_histograms: source_histograms
+            .read()
+            .expect("Unexpected poisoned mutex")
+            .to_vec(),
+    }
+}
 
-    super::sockets::write_json(stream, &observe).await?;
+async fn handle_connection(
+    stream: &mut (impl tokio::io::AsyncWrite + Unpin),
+    observe: &ObservableState,
+    format: ObservationFormat,
+    hmac_key: Option<&[u8]>,
+) -> std::io::Result<()> {
+    match (format, hmac_key) {
+        (ObservationFormat::Json, Some(key)) => {
+            super::sockets::write_json_signed(stream, observe, key).await?;
+        }
+        (ObservationFormat::Json, None) => super::sockets::write_json(stream, observe).await?,
+        (ObservationFormat::Cbor, Some(key)) => {
+            super::sockets::write_cbor_signed(stream, observe, key).await?;
+        }
+        (ObservationFormat::Cbor, None) => super::sockets::write_cbor(stream, observe).await?,
+    }
 
     Ok(())
 }
@@ -196,15 +539,63 @@ mod tests {
 
     use ntp_proto::v5::BloomFilter;
     use ntp_proto::{
-        NtpDuration, NtpLeapIndicator, NtpSnapshot, NtpTimestamp, ObservableSourceTimedata,
-        PollIntervalLimits, Reach, ReferenceId, TimeSnapshot,
+        ClockState, NtpDuration, NtpLeapIndicator, NtpSnapshot, NtpTimestamp,
+        ObservableSourceTimedata, PollIntervalLimits, Reach, ReferenceId, TimeSnapshot,
+    };
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt},
+        net::UnixStream,
     };
-    use tokio::{io::AsyncReadExt, net::UnixStream};
 
     use crate::test::alloc_port;
 
     use super::*;
 
+    #[test]
+    fn test_program_data_features() {
+        // Every feature of the crate is listed, so none is left out of the
+        // output when it is enabled
+        let manifest = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"));
+        let manifest: toml::Table = toml::from_str(&manifest.unwrap()).unwrap();
+        let mut features: Vec<_> = manifest["features"]
+            .as_table()
+            .unwrap()
+            .keys()
+            .filter(|name| *name != "default")
+            .map(String::as_str)
+            .collect();
+        features.sort_unstable();
+        let mut listed: Vec<_> = FEATURES.iter().map(|(name, _)| *name).collect();
+        listed.sort_unstable();
+        assert_eq!(features, listed);
+
+        let program = ProgramData::default();
+
+        // The default feature set
+        #[cfg(all(
+            feature = "aws-lc",
+            feature = "rustcrypto",
+            feature = "pps",
+            feature = "srv"
+        ))]
+        for name in ["aws-lc", "rustcrypto", "pps", "srv"] {
+            assert!(program.enabled_features.iter().any(|f| f == name));
+        }
+
+        let json = serde_json::to_value(&program).unwrap();
+        assert_eq!(
+            json["enabled_features"],
+            serde_json::to_value(&program.enabled_features).unwrap()
+        );
+
+        // Output of older daemons does not list the features
+        let old: ProgramData = serde_json::from_str(
+            r#"{"version":"1.5.0","build_commit":"-","build_commit_date":"-","uptime_seconds":1.0,"now":{"timestamp":0}}"#,
+        )
+        .unwrap();
+        assert!(old.enabled_features.is_empty());
+    }
+
     #[derive(Debug, Clone, Copy)]
     struct TestClock;
 
@@ -264,6 +655,12 @@ mod tests {
                 unanswered_polls: Reach::never().unanswered_polls(),
                 poll_interval: PollIntervalLimits::default().min,
                 nts_cookies: None,
+                last_poll_sent: NtpTimestamp::default(),
+                last_response_received: NtpTimestamp::default(),
+                timescale: None,
+                captured_packets: vec![],
+                last_round_trip_delay: None,
+                last_precision: None,
                 name: "127.0.0.3:123".into(),
                 address: "127.0.0.3:123".into(),
                 id,
@@ -291,7 +688,12 @@ mod tests {
                 leap_indicator: NtpLeapIndicator::Leap59,
                 accumulated_steps: NtpDuration::ZERO,
                 accumulated_steps_threshold: None,
+                clock_state: ClockState::Synchronized,
             },
+            clock_estimated_error: None,
+            clock_maximum_error: None,
+            tai_offset: None,
+            generation: 0,
         });
 
         let handle = tokio::spawn(async move {
@@ -300,6 +702,9 @@ mod tests {
                 source_snapshots,
                 servers_reader,
                 system_reader,
+                Arc::default(),
+                Arc::default(),
+                Arc::default(),
                 TestClock,
             )
             .await
@@ -320,6 +725,249 @@ mod tests {
         handle.abort();
     }
 
+    #[tokio::test]
+    async fn test_observation_cbor() {
+        // be careful with copying: tests run concurrently and should use a unique socket name!
+        let path = std::env::temp_dir().join(format!("ntp-test-stream-{}", alloc_port()));
+        let cbor_path = std::env::temp_dir().join(format!("ntp-test-stream-{}", alloc_port()));
+        let config = super::super::config::ObservabilityConfig {
+            log_level: None,
+            observation_path: Some(path.clone()),
+            observation_cbor_path: Some(cbor_path.clone()),
+            observation_permissions: 0o700,
+            ..Default::default()
+        };
+
+        let mut source_snapshots = HashMap::new();
+        let id = ClockId::new();
+        source_snapshots.insert(
+            id,
+            ObservableSourceState {
+                timedata: ObservableSourceTimedata::default(),
+                unanswered_polls: Reach::never().unanswered_polls(),
+                poll_interval: PollIntervalLimits::default().min,
+                nts_cookies: Some(3),
+                last_poll_sent: NtpTimestamp::default(),
+                last_response_received: NtpTimestamp::default(),
+                timescale: None,
+                captured_packets: vec![],
+                last_round_trip_delay: None,
+                last_precision: None,
+                name: "127.0.0.3:123".into(),
+                address: "127.0.0.3:123".into(),
+                id,
+            },
+        );
+
+        let source_snapshots = Arc::new(std::sync::RwLock::new(source_snapshots));
+
+        let (_, servers_reader) = tokio::sync::watch::channel(vec![]);
+
+        let (_, system_reader) = tokio::sync::watch::channel(SystemSnapshot {
+            ntp_snapshot: NtpSnapshot {
+                stratum: 1,
+                reference_id: ReferenceId::NONE,
+                bloom_filter: BloomFilter::new(),
+            },
+            time_snapshot: TimeSnapshot {
+                precision: NtpDuration::from_seconds(1e-3),
+                root_delay: NtpDuration::ZERO,
+                root_variance_base_time: NtpTimestamp::default(),
+                root_variance_base: 0.0,
+                root_variance_linear: 0.0,
+                root_variance_quadratic: 0.0,
+                root_variance_cubic: 0.0,
+                leap_indicator: NtpLeapIndicator::Leap59,
+                accumulated_steps: NtpDuration::ZERO,
+                accumulated_steps_threshold: None,
+                clock_state: ClockState::Synchronized,
+            },
+            clock_estimated_error: None,
+            clock_maximum_error: None,
+            tai_offset: None,
+            generation: 0,
+        });
+
+        let handle = tokio::spawn(async move {
+            observer(
+                config,
+                source_snapshots,
+                servers_reader,
+                system_reader,
+                Arc::default(),
+                Arc::default(),
+                Arc::default(),
+                TestClock,
+            )
+            .await
+            .unwrap();
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let mut reader = UnixStream::connect(&cbor_path).await.unwrap();
+        let mut buf = vec![];
+        let result: ObservableState = crate::daemon::sockets::read_cbor(&mut reader, &mut buf)
+            .await
+            .unwrap();
+
+        assert_eq!(result.sources.len(), 1);
+        assert_eq!(result.sources[0].id, id);
+        assert_eq!(result.sources[0].nts_cookies, Some(3));
+        assert_eq!(result.system.ntp_snapshot.stratum, 1);
+        assert_eq!(
+            result.system.time_snapshot.leap_indicator,
+            NtpLeapIndicator::Leap59
+        );
+
+        // The regular socket keeps serving JSON
+        let mut reader = UnixStream::connect(path).await.unwrap();
+        let result: ObservableState = crate::daemon::sockets::read_json(&mut reader, &mut buf)
+            .await
+            .unwrap();
+        assert_eq!(result.sources.len(), 1);
+
+        // The CBOR socket stops together with the observer
+        handle.abort();
+        assert!(handle.await.unwrap_err().is_cancelled());
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(UnixStream::connect(&cbor_path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_observation_hmac() {
+        // be careful with copying: tests run concurrently and should use a unique socket name!
+        let path = std::env::temp_dir().join(format!("ntp-test-stream-{}", alloc_port()));
+        let cbor_path = std::env::temp_dir().join(format!("ntp-test-stream-{}", alloc_port()));
+        let stream_path = std::env::temp_dir().join(format!("ntp-test-stream-{}", alloc_port()));
+        let key_path = std::env::temp_dir().join(format!("ntp-test-hmac-key-{}", alloc_port()));
+        let key = b"observation test key";
+        std::fs::write(&key_path, key).unwrap();
+        std::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        let config = super::super::config::ObservabilityConfig {
+            log_level: None,
+            observation_path: Some(path.clone()),
+            observation_cbor_path: Some(cbor_path.clone()),
+            observation_stream_path: Some(stream_path.clone()),
+            observation_permissions: 0o700,
+            observation_hmac_key_path: Some(key_path.clone()),
+            ..Default::default()
+        };
+
+        let (_, servers_reader) = tokio::sync::watch::channel(vec![]);
+        let (_, system_reader) = tokio::sync::watch::channel(SystemSnapshot::default());
+
+        let handle = tokio::spawn(async move {
+            observer(
+                config,
+                Arc::default(),
+                servers_reader,
+                system_reader,
+                Arc::default(),
+                Arc::default(),
+                Arc::default(),
+                TestClock,
+            )
+            .await
+            .unwrap();
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let mut reader = UnixStream::connect(&path).await.unwrap();
+        let json_len = reader.read_u64().await.unwrap();
+        let mut json = vec![0; json_len as usize];
+        reader.read_exact(&mut json).await.unwrap();
+        let tag_len = reader.read_u64().await.unwrap();
+        let mut tag = vec![0; tag_len as usize];
+        reader.read_exact(&mut tag).await.unwrap();
+
+        let result: ObservableState = serde_json::from_slice(&json).unwrap();
+        assert!(result.sources.is_empty());
+
+        let verify = |key: &[u8], message: &[u8], tag: &[u8]| {
+            use hmac::Mac;
+            let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(key).unwrap();
+            mac.update(message);
+            mac.verify_slice(tag).is_ok()
+        };
+        assert!(verify(key, &json, &tag));
+
+        let mut mutated = json.clone();
+        mutated[0] ^= 1;
+        assert!(!verify(key, &mutated, &tag));
+        assert!(!verify(b"some other key", &json, &tag));
+
+        // The CBOR socket uses the same framing for its signature
+        let mut reader = UnixStream::connect(&cbor_path).await.unwrap();
+        let cbor_len = reader.read_u64().await.unwrap();
+        let mut cbor = vec![0; cbor_len as usize];
+        reader.read_exact(&mut cbor).await.unwrap();
+        let tag_len = reader.read_u64().await.unwrap();
+        let mut tag = vec![0; tag_len as usize];
+        reader.read_exact(&mut tag).await.unwrap();
+
+        let result: ObservableState = ciborium::from_reader(cbor.as_slice()).unwrap();
+        assert!(result.sources.is_empty());
+        assert!(verify(key, &cbor, &tag));
+
+        // On the stream, every line is a single JSON object holding both the
+        // observation and its tag
+        let stream = UnixStream::connect(&stream_path).await.unwrap();
+        let mut lines = tokio::io::BufReader::new(stream).lines();
+        let line = lines.next_line().await.unwrap().unwrap();
+
+        #[derive(Deserialize)]
+        struct Signed {
+            data: ObservableState,
+            hmac: String,
+        }
+        let signed: Signed = serde_json::from_str(&line).unwrap();
+        assert!(signed.data.sources.is_empty());
+        let tag: Vec<u8> = (0..signed.hmac.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&signed.hmac[i..i + 2], 16).unwrap())
+            .collect();
+
+        // The tag covers the data exactly as it was serialized in the line
+        let data = line
+            .strip_prefix(r#"{"data":"#)
+            .and_then(|rest| rest.strip_suffix(&format!(r#","hmac":"{}"}}"#, signed.hmac)))
+            .unwrap();
+        assert!(verify(key, data.as_bytes(), &tag));
+        assert!(!verify(key, line.as_bytes(), &tag));
+
+        // Clients without the key just read the JSON and ignore the trailer
+        let mut reader = UnixStream::connect(&path).await.unwrap();
+        let mut buf = vec![];
+        let result: ObservableState = crate::daemon::sockets::read_json(&mut reader, &mut buf)
+            .await
+            .unwrap();
+        assert!(result.sources.is_empty());
+
+        handle.abort();
+        std::fs::remove_file(key_path).unwrap();
+    }
+
+    #[test]
+    fn test_observation_hmac_key_permissions() {
+        let key_path = std::env::temp_dir().join(format!("ntp-test-hmac-key-{}", alloc_port()));
+        std::fs::write(&key_path, b"observation test key").unwrap();
+
+        std::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert!(read_hmac_key(&key_path).is_ok());
+
+        std::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let err = read_hmac_key(&key_path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+
+        std::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o602)).unwrap();
+        assert!(read_hmac_key(&key_path).is_err());
+
+        std::fs::remove_file(key_path).unwrap();
+    }
+
     #[tokio::test]
     async fn test_block_during_read() {
         // be careful with copying: tests run concurrently and should use a unique socket name!
@@ -340,6 +988,12 @@ mod tests {
                 unanswered_polls: Reach::never().unanswered_polls(),
                 poll_interval: PollIntervalLimits::default().min,
                 nts_cookies: None,
+                last_poll_sent: NtpTimestamp::default(),
+                last_response_received: NtpTimestamp::default(),
+                timescale: None,
+                captured_packets: vec![],
+                last_round_trip_delay: None,
+                last_precision: None,
                 name: "127.0.0.3:123".into(),
                 address: "127.0.0.3:123".into(),
                 id,
@@ -368,7 +1022,12 @@ mod tests {
                 leap_indicator: NtpLeapIndicator::Leap59,
                 accumulated_steps: NtpDuration::ZERO,
                 accumulated_steps_threshold: None,
+                clock_state: ClockState::Synchronized,
             },
+            clock_estimated_error: None,
+            clock_maximum_error: None,
+            tai_offset: None,
+            generation: 0,
         });
 
         let handle = tokio::spawn(async move {
@@ -377,6 +1036,9 @@ mod tests {
                 source_snapshots,
                 servers_reader,
                 system_reader,
+                Arc::default(),
+                Arc::default(),
+                Arc::default(),
                 TestClock,
             )
             .await
@@ -404,4 +1066,243 @@ mod tests {
 
         handle.abort();
     }
+
+    #[tokio::test]
+    async fn test_cached_snapshot_within_interval() {
+        // be careful with copying: tests run concurrently and should use a unique socket name!
+        let path = std::env::temp_dir().join(format!("ntp-test-stream-{}", alloc_port()));
+        let config = super::super::config::ObservabilityConfig {
+            log_level: None,
+            observation_path: Some(path.clone()),
+            observation_permissions: 0o700,
+            observation_snapshot_interval_ms: 3_600_000,
+            ..Default::default()
+        };
+
+        let mut source_snapshots = HashMap::new();
+        let id = ClockId::new();
+        source_snapshots.insert(
+            id,
+            ObservableSourceState {
+                timedata: ObservableSourceTimedata::default(),
+                unanswered_polls: Reach::never().unanswered_polls(),
+                poll_interval: PollIntervalLimits::default().min,
+                nts_cookies: None,
+                last_poll_sent: NtpTimestamp::default(),
+                last_response_received: NtpTimestamp::default(),
+                timescale: None,
+                captured_packets: vec![],
+                last_round_trip_delay: None,
+                last_precision: None,
+                name: "127.0.0.3:123".into(),
+                address: "127.0.0.3:123".into(),
+                id,
+            },
+        );
+
+        let source_snapshots = Arc::new(std::sync::RwLock::new(source_snapshots));
+        let source_snapshots_clone = source_snapshots.clone();
+
+        let (_, servers_reader) = tokio::sync::watch::channel(vec![]);
+
+        let (_, system_reader) = tokio::sync::watch::channel(SystemSnapshot {
+            ntp_snapshot: NtpSnapshot {
+                stratum: 1,
+                reference_id: ReferenceId::NONE,
+                bloom_filter: BloomFilter::new(),
+            },
+            time_snapshot: TimeSnapshot {
+                precision: NtpDuration::from_seconds(1e-3),
+                root_delay: NtpDuration::ZERO,
+                root_variance_base_time: NtpTimestamp::default(),
+                root_variance_base: 0.0,
+                root_variance_linear: 0.0,
+                root_variance_quadratic: 0.0,
+                root_variance_cubic: 0.0,
+                leap_indicator: NtpLeapIndicator::Leap59,
+                accumulated_steps: NtpDuration::ZERO,
+                accumulated_steps_threshold: None,
+                clock_state: ClockState::Synchronized,
+            },
+            clock_estimated_error: None,
+            clock_maximum_error: None,
+            tai_offset: None,
+            generation: 0,
+        });
+
+        let handle = tokio::spawn(async move {
+            observer(
+                config,
+                source_snapshots,
+                servers_reader,
+                system_reader,
+                Arc::default(),
+                Arc::default(),
+                Arc::default(),
+                TestClock,
+            )
+            .await
+            .unwrap();
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let mut reader = UnixStream::connect(&path).await.unwrap();
+        let mut buf = vec![];
+        let result: ObservableState = crate::daemon::sockets::read_json(&mut reader, &mut buf)
+            .await
+            .unwrap();
+        assert_eq!(result.sources.len(), 1);
+
+        // Add a source, which should not be visible until the interval expires
+        let id = ClockId::new();
+        source_snapshots_clone
+            .write()
+            .expect("Unexpected poisoned mutex")
+            .insert(
+                id,
+                ObservableSourceState {
+                    timedata: ObservableSourceTimedata::default(),
+                    unanswered_polls: Reach::never().unanswered_polls(),
+                    poll_interval: PollIntervalLimits::default().min,
+                    nts_cookies: None,
+                    last_poll_sent: NtpTimestamp::default(),
+                    last_response_received: NtpTimestamp::default(),
+                    timescale: None,
+                    captured_packets: vec![],
+                    last_round_trip_delay: None,
+                    last_precision: None,
+                    name: "127.0.0.4:123".into(),
+                    address: "127.0.0.4:123".into(),
+                    id,
+                },
+            );
+
+        let mut reader = UnixStream::connect(&path).await.unwrap();
+        let result: ObservableState = crate::daemon::sockets::read_json(&mut reader, &mut buf)
+            .await
+            .unwrap();
+        assert_eq!(result.sources.len(), 1);
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_observation_stream() {
+        // be careful with copying: tests run concurrently and should use a unique socket name!
+        let stream_path = std::env::temp_dir().join(format!("ntp-test-stream-{}", alloc_port()));
+        let config = super::super::config::ObservabilityConfig {
+            log_level: None,
+            observation_permissions: 0o700,
+            observation_stream_path: Some(stream_path.clone()),
+            observation_stream_interval_ms: 10,
+            ..Default::default()
+        };
+
+        let mut source_snapshots = HashMap::new();
+        let id = ClockId::new();
+        source_snapshots.insert(
+            id,
+            ObservableSourceState {
+                timedata: ObservableSourceTimedata::default(),
+                unanswered_polls: Reach::never().unanswered_polls(),
+                poll_interval: PollIntervalLimits::default().min,
+                nts_cookies: None,
+                last_poll_sent: NtpTimestamp::default(),
+                last_response_received: NtpTimestamp::default(),
+                timescale: None,
+                captured_packets: vec![],
+                last_round_trip_delay: None,
+                last_precision: None,
+                name: "127.0.0.3:123".into(),
+                address: "127.0.0.3:123".into(),
+                id,
+            },
+        );
+
+        let source_snapshots = Arc::new(std::sync::RwLock::new(source_snapshots));
+
+        let (_, servers_reader) = tokio::sync::watch::channel(vec![]);
+
+        let (system_writer, system_reader) = tokio::sync::watch::channel(SystemSnapshot {
+            ntp_snapshot: NtpSnapshot {
+                stratum: 1,
+                reference_id: ReferenceId::NONE,
+                bloom_filter: BloomFilter::new(),
+            },
+            time_snapshot: TimeSnapshot {
+                precision: NtpDuration::from_seconds(1e-3),
+                root_delay: NtpDuration::ZERO,
+                root_variance_base_time: NtpTimestamp::default(),
+                root_variance_base: 0.0,
+                root_variance_linear: 0.0,
+                root_variance_quadratic: 0.0,
+                root_variance_cubic: 0.0,
+                leap_indicator: NtpLeapIndicator::Leap59,
+                accumulated_steps: NtpDuration::ZERO,
+                accumulated_steps_threshold: None,
+                clock_state: ClockState::Synchronized,
+            },
+            clock_estimated_error: None,
+            clock_maximum_error: None,
+            tai_offset: None,
+            generation: 0,
+        });
+
+        let handle = tokio::spawn(async move {
+            observer(
+                config,
+                source_snapshots,
+                servers_reader,
+                system_reader,
+                Arc::default(),
+                Arc::default(),
+                Arc::default(),
+                TestClock,
+            )
+            .await
+            .unwrap();
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let reader = UnixStream::connect(&stream_path).await.unwrap();
+        let mut lines = tokio::io::BufReader::new(reader).lines();
+
+        // Multiple snapshots are received over a single connection, also
+        // while the system state is unchanged
+        let mut last_uptime = 0.0;
+        for _ in 0..3 {
+            let line = lines.next_line().await.unwrap().unwrap();
+            let result: ObservableState = serde_json::from_str(&line).unwrap();
+            assert_eq!(result.sources.len(), 1);
+            assert_eq!(result.system.generation, 0);
+            assert!(result.program.uptime_seconds > last_uptime);
+            last_uptime = result.program.uptime_seconds;
+        }
+
+        // Changes to the system state show up in the next snapshot
+        system_writer.send_modify(|snapshot| snapshot.generation += 1);
+        let generation = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                let line = lines.next_line().await.unwrap().unwrap();
+                let result: ObservableState = serde_json::from_str(&line).unwrap();
+                if result.system.generation != 0 {
+                    return result.system.generation;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(generation, 1);
+
+        // Stopping the observer closes the streaming connections
+        handle.abort();
+        assert!(handle.await.unwrap_err().is_cancelled());
+        let closed = tokio::time::timeout(Duration::from_secs(1), async {
+            while lines.next_line().await.unwrap().is_some() {}
+        })
+        .await;
+        assert!(closed.is_ok());
+    }
 }
@@ -0,0 +1,396 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::mpsc::{Receiver, SyncSender, TrySendError, sync_channel},
+    time::{Duration, SystemTime},
+};
+
+use ntp_proto::ObservableSourceTimedata;
+use tracing::warn;
+
+const SECONDS_PER_DAY: u64 = 86400;
+
+const HEADER: &str = "timestamp,name,address,offset,delay,dispersion\n";
+
+// Number of measurements that can be waiting for the writer thread before new
+// ones are dropped
+const QUEUE_SIZE: usize = 1024;
+
+/// Writes one CSV line per accepted source measurement, in the spirit of the
+/// peerstats files of the classic ntpd
+#[derive(Debug)]
+pub struct PeerStats {
+    path: PathBuf,
+    max_size: Option<u64>,
+    rotate_daily: bool,
+    file: Option<File>,
+    size: u64,
+    day: u64,
+    failing: bool,
+}
+
+impl PeerStats {
+    pub fn new(path: PathBuf, max_size: Option<u64>, rotate_daily: bool) -> Self {
+        PeerStats {
+            path,
+            max_size,
+            rotate_daily,
+            file: None,
+            size: 0,
+            day: 0,
+            failing: false,
+        }
+    }
+
+    pub fn record(
+        &mut self,
+        now: SystemTime,
+        name: &str,
+        address: &str,
+        timedata: &ObservableSourceTimedata,
+    ) {
+        let line = format_record(now, name, address, timedata);
+        match self.write(now, line.as_bytes()) {
+            Ok(()) => self.failing = false,
+            Err(e) => {
+                // Don't flood the log with the same problem for every measurement
+                if !self.failing {
+                    warn!(path = ?self.path, "Could not write source statistics: {e}");
+                }
+                self.failing = true;
+                self.file = None;
+            }
+        }
+    }
+
+    fn needs_rotation(&self, day: u64, len: u64) -> bool {
+        // An empty file is never worth rotating
+        if self.size == 0 {
+            return false;
+        }
+        let too_large = self
+            .max_size
+            .is_some_and(|max_size| self.size + len > max_size);
+        let new_day = self.rotate_daily && day != self.day;
+        too_large || new_day
+    }
+
+    fn write(&mut self, now: SystemTime, line: &[u8]) -> std::io::Result<()> {
+        let day = unix_time(now).as_secs() / SECONDS_PER_DAY;
+
+        if self.file.is_none() {
+            self.open()?;
+        }
+
+        if self.needs_rotation(day, line.len() as u64) {
+            self.rotate(now)?;
+            self.open()?;
+        }
+        self.day = day;
+
+        let file = self.file.as_mut().expect("file was opened above");
+        if self.size == 0 {
+            file.write_all(HEADER.as_bytes())?;
+            self.size += HEADER.len() as u64;
+        }
+        file.write_all(line)?;
+        self.size += line.len() as u64;
+
+        Ok(())
+    }
+
+    fn open(&mut self) -> std::io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let metadata = file.metadata()?;
+        self.size = metadata.len();
+        // A file left behind by an earlier run belongs to the day it was last written
+        if let Ok(modified) = metadata.modified() {
+            self.day = unix_time(modified).as_secs() / SECONDS_PER_DAY;
+        }
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn rotate(&mut self, now: SystemTime) -> std::io::Result<()> {
+        self.file = None;
+        let mut base = self.path.clone().into_os_string();
+        base.push(format!(".{}", unix_time(now).as_secs()));
+
+        // Several rotations within the same second get a sequence number, so
+        // they don't overwrite each other
+        let mut rotated = PathBuf::from(&base);
+        let mut sequence = 0;
+        while rotated.try_exists()? {
+            sequence += 1;
+            let mut name = base.clone();
+            name.push(format!(".{sequence}"));
+            rotated = PathBuf::from(name);
+        }
+
+        std::fs::rename(&self.path, rotated)
+    }
+
+    /// Move the writer to a thread of its own, so that writing and rotating
+    /// the file never blocks the caller
+    pub fn spawn(mut self) -> PeerStatsWriter {
+        let (sender, receiver): (_, Receiver<Record>) = sync_channel(QUEUE_SIZE);
+        std::thread::Builder::new()
+            .name("ntpd-peerstats".into())
+            .spawn(move || {
+                // Ends once the sending side is dropped
+                for record in receiver {
+                    self.record(record.now, &record.name, &record.address, &record.timedata);
+                }
+            })
+            .expect("Could not spawn the source statistics thread");
+
+        PeerStatsWriter {
+            sender,
+            dropping: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Record {
+    now: SystemTime,
+    name: String,
+    address: String,
+    timedata: ObservableSourceTimedata,
+}
+
+/// Handle to a [`PeerStats`] running on its own thread
+#[derive(Debug)]
+pub struct PeerStatsWriter {
+    sender: SyncSender<Record>,
+    dropping: bool,
+}
+
+impl PeerStatsWriter {
+    /// Queue a measurement for writing. Measurements are dropped when the
+    /// writer can't keep up.
+    pub fn record(
+        &mut self,
+        now: SystemTime,
+        name: &str,
+        address: &str,
+        timedata: &ObservableSourceTimedata,
+    ) {
+        let record = Record {
+            now,
+            name: name.to_owned(),
+            address: address.to_owned(),
+            timedata: timedata.clone(),
+        };
+        match self.sender.try_send(record) {
+            Ok(()) => self.dropping = false,
+            Err(TrySendError::Full(_)) => {
+                if !self.dropping {
+                    warn!("Source statistics writer can't keep up, dropping measurements");
+                }
+                self.dropping = true;
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                if !self.dropping {
+                    warn!("Source statistics writer stopped, dropping measurements");
+                }
+                self.dropping = true;
+            }
+        }
+    }
+}
+
+fn unix_time(time: SystemTime) -> Duration {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+fn format_record(
+    now: SystemTime,
+    name: &str,
+    address: &str,
+    timedata: &ObservableSourceTimedata,
+) -> String {
+    format!(
+        "{:.6},{},{},{:.9},{:.9},{:.9}\n",
+        unix_time(now).as_secs_f64(),
+        csv_field(name),
+        csv_field(address),
+        timedata.offset.to_seconds(),
+        timedata.delay.to_seconds(),
+        timedata.uncertainty.to_seconds(),
+    )
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ntp_proto::NtpDuration;
+
+    use crate::test::alloc_port;
+
+    use super::*;
+
+    fn timedata() -> ObservableSourceTimedata {
+        ObservableSourceTimedata {
+            offset: NtpDuration::from_seconds(-0.0015),
+            delay: NtpDuration::from_seconds(0.012),
+            uncertainty: NtpDuration::from_seconds(0.0002),
+            ..Default::default()
+        }
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    fn rotated_files(path: &std::path::Path) -> Vec<PathBuf> {
+        let prefix = format!("{}.", path.file_name().unwrap().to_str().unwrap());
+        let mut files: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|p| {
+                p.file_name()
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .starts_with(&prefix)
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_format_record() {
+        let line = format_record(
+            at(1_700_000_000) + Duration::from_millis(250),
+            "ntp.example.com",
+            "192.0.2.1:123",
+            &timedata(),
+        );
+        let fields: Vec<_> = line.trim_end().split(',').collect();
+        assert_eq!(fields[0], "1700000000.250000");
+        assert_eq!(fields[1], "ntp.example.com");
+        assert_eq!(fields[2], "192.0.2.1:123");
+        assert!((fields[3].parse::<f64>().unwrap() + 0.0015).abs() < 1e-9);
+        assert!((fields[4].parse::<f64>().unwrap() - 0.012).abs() < 1e-9);
+        assert!((fields[5].parse::<f64>().unwrap() - 0.0002).abs() < 1e-9);
+        assert!(line.ends_with('\n'));
+
+        assert_eq!(csv_field("/run/gps,0.sock"), "\"/run/gps,0.sock\"");
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+
+    #[test]
+    fn test_rotate_on_size() {
+        let path = std::env::temp_dir().join(format!("ntp-test-peerstats-{}", alloc_port()));
+        let mut peerstats = PeerStats::new(path.clone(), Some(200), false);
+
+        // The header and two records fit, the third record starts a new file
+        for i in 0..3 {
+            peerstats.record(at(1_700_000_000 + i), "a", "192.0.2.1:123", &timedata());
+        }
+
+        let rotated = rotated_files(&path);
+        assert_eq!(rotated.len(), 1);
+        let old = std::fs::read_to_string(&rotated[0]).unwrap();
+        assert!(old.starts_with(HEADER));
+        assert_eq!(old.lines().count(), 3);
+        let current = std::fs::read_to_string(&path).unwrap();
+        assert!(current.starts_with(HEADER));
+        assert_eq!(current.lines().count(), 2);
+
+        std::fs::remove_file(&rotated[0]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rotate_twice_within_a_second() {
+        let path = std::env::temp_dir().join(format!("ntp-test-peerstats-{}", alloc_port()));
+        let mut peerstats = PeerStats::new(path.clone(), Some(200), false);
+
+        // Two records fit in a file, so this rotates twice at the same time
+        for _ in 0..5 {
+            peerstats.record(at(1_700_000_000), "a", "192.0.2.1:123", &timedata());
+        }
+
+        let rotated = rotated_files(&path);
+        assert_eq!(rotated.len(), 2);
+        for file in &rotated {
+            assert_eq!(std::fs::read_to_string(file).unwrap().lines().count(), 3);
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        for file in rotated {
+            std::fs::remove_file(file).unwrap();
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_writer_thread() {
+        let path = std::env::temp_dir().join(format!("ntp-test-peerstats-{}", alloc_port()));
+        let mut writer = PeerStats::new(path.clone(), None, false).spawn();
+
+        writer.record(at(1_700_000_000), "a", "192.0.2.1:123", &timedata());
+        writer.record(at(1_700_000_001), "b", "192.0.2.2:123", &timedata());
+
+        let mut lines = 0;
+        for _ in 0..100 {
+            lines = std::fs::read_to_string(&path)
+                .map(|contents| contents.lines().count())
+                .unwrap_or(0);
+            if lines == 3 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(lines, 3);
+
+        drop(writer);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rotate_daily() {
+        let path = std::env::temp_dir().join(format!("ntp-test-peerstats-{}", alloc_port()));
+        let mut peerstats = PeerStats::new(path.clone(), None, true);
+
+        let day = 19_000 * SECONDS_PER_DAY;
+        peerstats.record(at(day + 10), "a", "192.0.2.1:123", &timedata());
+        peerstats.record(at(day + 20), "a", "192.0.2.1:123", &timedata());
+        assert!(rotated_files(&path).is_empty());
+
+        peerstats.record(
+            at(day + SECONDS_PER_DAY + 10),
+            "a",
+            "192.0.2.1:123",
+            &timedata(),
+        );
+        let rotated = rotated_files(&path);
+        assert_eq!(rotated.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&rotated[0])
+                .unwrap()
+                .lines()
+                .count(),
+            3
+        );
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+
+        std::fs::remove_file(&rotated[0]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -70,18 +70,21 @@ impl<Controller: SourceController> PpsSourceTask<Controller> {
 
                         self.source.handle_measurement(measurement);
 
+                        let observed = self.source.observe(
+                            "PPS device".to_string(),
+                            self.path.display().to_string(),
+                            self.index,
+                        );
+                        self.channels
+                            .source_histograms
+                            .write()
+                            .expect("Unexpected poisoned mutex")
+                            .record_source(&observed);
                         self.channels
                             .source_snapshots
                             .write()
                             .expect("Unexpected poisoned mutex")
-                            .insert(
-                                self.index,
-                                self.source.observe(
-                                    "PPS device".to_string(),
-                                    self.path.display().to_string(),
-                                    self.index,
-                                ),
-                            );
+                            .insert(self.index, observed);
                     }
                     None => {
                         warn!("Did not receive any new PPS data");
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use ntp_proto::PollInterval;
+use tokio::{
+    signal::unix::{SignalKind, signal},
+    sync::mpsc,
+    task::JoinHandle,
+};
+use tracing::{error, info, warn};
+
+use super::{
+    config::{Config, PermissionCheck},
+    system::ConfigReload,
+};
+
+/// Reload the sources and servers from the configuration file whenever the
+/// daemon receives a hangup signal.
+pub fn spawn(
+    config_path: Option<PathBuf>,
+    permission_check: PermissionCheck,
+    poll_override: (Option<PollInterval>, Option<PollInterval>),
+    config: Config,
+    config_reload: mpsc::Sender<ConfigReload>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let Ok(mut hangup) = signal(SignalKind::hangup()) else {
+            error!("Could not listen for hangup signal, configuration reloading is unavailable.");
+            return;
+        };
+
+        while hangup.recv().await.is_some() {
+            info!("Received hangup signal, reloading configuration");
+
+            let mut reloaded = match Config::from_first_file(config_path.as_ref(), permission_check)
+            {
+                Ok(reloaded) => reloaded,
+                Err(e) => {
+                    warn!("Could not reload configuration, keeping the current one: {e}");
+                    continue;
+                }
+            };
+            // Command line overrides stay in effect for the lifetime of the daemon
+            if let Err(e) = reloaded.override_poll_interval_limits(poll_override.0, poll_override.1)
+            {
+                warn!("Could not reload configuration, keeping the current one: {e}");
+                continue;
+            }
+            reloaded.check();
+
+            // Compared against the startup configuration, as these sections
+            // are never applied by a reload.
+            for section in config.restart_required_changes(&reloaded) {
+                warn!("Changes to the `{section}` section require a restart to take effect");
+            }
+
+            let reload = ConfigReload {
+                sources: reloaded.sources,
+                servers: reloaded.servers,
+            };
+            if config_reload.send(reload).await.is_err() {
+                break;
+            }
+        }
+    })
+}
@@ -1,4 +1,5 @@
 use std::{
+    net::SocketAddr,
     sync::{
         Arc,
         atomic::{AtomicU64, Ordering},
@@ -6,13 +7,20 @@ use std::{
     time::Duration,
 };
 
-use ntp_proto::{KeySet, NtpClock, Server, ServerReason, ServerResponse, ServerStatHandler};
+use ntp_proto::{
+    KeySet, NtpClock, Server, ServerReason, ServerResponse, ServerStatHandler, ServerStatus,
+};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use timestamped_socket::socket::{RecvResult, open_ip};
+#[cfg(target_os = "linux")]
+use timestamped_socket::socket::open_interface_udp;
+use timestamped_socket::socket::{GeneralTimestampMode, Open, RecvResult, Socket, open_ip};
 use tokio::task::JoinHandle;
-use tracing::{Instrument, Span, debug, instrument, warn};
+use tracing::{Instrument, Span, debug, info, instrument, warn};
 
-use super::{config::ServerConfig, util::convert_net_timestamp};
+use super::{
+    config::ServerConfig,
+    util::{convert_net_timestamp, is_transient_io_error, set_dscp, set_recv_buffer_size},
+};
 
 // Maximum size of udp packet we handle
 const MAX_PACKET_SIZE: usize = 1024;
@@ -30,6 +38,38 @@ pub struct ServerStats {
     pub nts_denied_packets: Counter,
     pub nts_rate_limited_packets: Counter,
     pub nts_nak_packets: Counter,
+    #[serde(default)]
+    pub parse_errors: ParseErrorStats,
+}
+
+/// Number of packets that could not be parsed, per category of parse error
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct ParseErrorStats {
+    pub too_short: Counter,
+    pub unsupported_version: Counter,
+    pub unknown_mode: Counter,
+    pub bad_extension_length: Counter,
+    pub incorrect_length: Counter,
+    pub malformed_nts: Counter,
+    pub decrypt_error: Counter,
+    pub v5: Counter,
+}
+
+impl ParseErrorStats {
+    /// The counters together with the category they count, as given by
+    /// `ParsingError::category`
+    pub fn by_category(&self) -> [(&'static str, &Counter); 8] {
+        [
+            ("too_short", &self.too_short),
+            ("unsupported_version", &self.unsupported_version),
+            ("unknown_mode", &self.unknown_mode),
+            ("bad_extension_length", &self.bad_extension_length),
+            ("incorrect_length", &self.incorrect_length),
+            ("malformed_nts", &self.malformed_nts),
+            ("decrypt_error", &self.decrypt_error),
+            ("v5", &self.v5),
+        ]
+    }
 }
 
 impl ServerStatHandler for ServerStats {
@@ -62,6 +102,27 @@ impl ServerStatHandler for ServerStats {
             }
         }
     }
+
+    fn register_parse_error(&mut self, category: &'static str) {
+        if let Some((_, counter)) = self
+            .parse_errors
+            .by_category()
+            .into_iter()
+            .find(|(name, _)| *name == category)
+        {
+            counter.inc();
+        }
+    }
+
+    fn status(&self) -> Option<ServerStatus> {
+        Some(ServerStatus {
+            received_packets: self.received_packets.get(),
+            accepted_packets: self.accepted_packets.get(),
+            denied_packets: self.denied_packets.get(),
+            ignored_packets: self.ignored_packets.get(),
+            rate_limited_packets: self.rate_limited_packets.get(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -70,7 +131,7 @@ pub struct Counter {
 }
 
 impl Counter {
-    fn inc(&self) {
+    pub(crate) fn inc(&self) {
         self.value.fetch_add(1, Ordering::Relaxed);
     }
 
@@ -131,7 +192,31 @@ impl<C: 'static + NtpClock + Send> ServerTask<C> {
         )
     }
 
+    fn open_socket(config: &ServerConfig) -> std::io::Result<Socket<SocketAddr, Open>> {
+        match config.interface {
+            #[cfg(target_os = "linux")]
+            Some(interface) => open_interface_udp(
+                interface,
+                config.listen.port(),
+                timestamped_socket::socket::InterfaceTimestampMode::SoftwareRecv,
+                None,
+            ),
+            // With reuse-port, SO_REUSEPORT is set before binding, allowing
+            // other sockets to bind to the same address
+            _ => open_ip(
+                config.listen,
+                GeneralTimestampMode::SoftwareRecv,
+                config.reuse_port,
+            ),
+        }
+    }
+
     async fn serve(&mut self) {
+        #[cfg(target_os = "linux")]
+        if self.config.reuse_port && self.config.interface.is_some() {
+            warn!("reuse-port has no effect on a server bound to an interface");
+        }
+
         let mut cur_socket = None;
         loop {
             // open socket if it is not already open
@@ -139,14 +224,28 @@ impl<C: 'static + NtpClock + Send> ServerTask<C> {
                 socket
             } else {
                 let new_socket = loop {
-                    let socket_res = open_ip(
-                        self.config.listen,
-                        timestamped_socket::socket::GeneralTimestampMode::SoftwareRecv,
-                        false,
-                    );
-
-                    match socket_res {
-                        Ok(socket) => break socket,
+                    match Self::open_socket(&self.config) {
+                        Ok(socket) => {
+                            if let Some(dscp) = self.config.dscp
+                                && let Err(error) = set_dscp(&socket, self.config.listen, dscp)
+                            {
+                                warn!(?error, dscp, "Could not set DSCP on server socket");
+                            }
+                            if let Some(requested) = self.config.recv_buffer_size {
+                                match set_recv_buffer_size(&socket, requested) {
+                                    Ok(granted) => info!(
+                                        requested,
+                                        granted, "Set receive buffer size of server socket"
+                                    ),
+                                    Err(error) => warn!(
+                                        ?error,
+                                        requested,
+                                        "Could not set receive buffer size on server socket"
+                                    ),
+                                }
+                            }
+                            break socket;
+                        }
                         Err(error) => {
                             warn!(?error, ?self.config.listen, "Could not open server socket");
                             tokio::time::sleep(self.network_wait_period).await;
@@ -182,9 +281,19 @@ impl<C: 'static + NtpClock + Send> ServerTask<C> {
                             ) {
                                 ntp_proto::ServerAction::Ignore => { /* explicitly do nothing */ }
                                 ntp_proto::ServerAction::Respond { message } => {
-                                    if let Err(send_err) =
-                                        socket.send_from_to(message, local_addr, source_addr).await
-                                    {
+                                    // Replying from the destination address of the request
+                                    // (as reported through IP_PKTINFO/IPV6_PKTINFO) keeps
+                                    // multihomed servers from answering with the wrong
+                                    // source address.
+                                    let send_err = if self.config.respect_request_destination {
+                                        socket
+                                            .send_from_to(message, local_addr, source_addr)
+                                            .await
+                                            .err()
+                                    } else {
+                                        socket.send_to(message, source_addr).await.err()
+                                    };
+                                    if let Some(send_err) = send_err {
                                         self.stats.response_send_errors.inc();
                                         debug!(error=?send_err, "Could not send response packet");
                                     }
@@ -200,6 +309,10 @@ impl<C: 'static + NtpClock + Send> ServerTask<C> {
                                 ServerResponse::Ignore,
                             );
                         }
+                        Err(receive_error) if is_transient_io_error(&receive_error) => {
+                            // Nothing is wrong with the socket, just try again
+                            debug!(?receive_error, "transient error receiving packet, retrying");
+                        }
                         Err(receive_error) => {
                             warn!(?receive_error, "could not receive packet");
 
@@ -227,13 +340,12 @@ impl<C: 'static + NtpClock + Send> ServerTask<C> {
 
 #[cfg(test)]
 mod tests {
-    use std::{convert::Infallible, io::Cursor, net::SocketAddr};
+    use std::{convert::Infallible, io::Cursor};
 
     use ntp_proto::{
         KeySetProvider, NoCipher, NtpDuration, NtpLeapIndicator, NtpPacket, NtpTimestamp,
         PollIntervalLimits,
     };
-    use timestamped_socket::socket::GeneralTimestampMode;
 
     use crate::test::alloc_port;
 
@@ -290,11 +402,10 @@ mod tests {
         buf
     }
 
-    #[tokio::test]
-    async fn test_server_serves() {
-        let port = alloc_port();
-        let config = ServerConfig::from(SocketAddr::new("127.0.0.1".parse().unwrap(), port));
-
+    async fn request_response(
+        config: ServerConfig,
+        server_addr: SocketAddr,
+    ) -> Option<NtpPacket<'static>> {
         let clock = TestClock {
             time: NtpTimestamp::from_seconds_nanos_since_ntp_era(0, 1000),
         };
@@ -309,6 +420,7 @@ mod tests {
             keyset.borrow().clone(),
         );
 
+        let listen = config.listen;
         let join = ServerTask::spawn(
             server,
             config,
@@ -317,29 +429,90 @@ mod tests {
             Duration::from_secs(0),
         );
 
+        // The server task opens its socket once it gets to run, after which
+        // the listen address can no longer be bound
+        while std::net::UdpSocket::bind(listen).is_ok() {
+            tokio::task::yield_now().await;
+        }
+
         let socket = open_ip(
             SocketAddr::new("127.0.0.1".parse().unwrap(), alloc_port()),
             GeneralTimestampMode::SoftwareRecv,
             false,
         )
         .unwrap();
-        let mut socket = socket
-            .connect(SocketAddr::new("127.0.0.1".parse().unwrap(), port))
-            .unwrap();
+        // A connected socket only accepts packets coming from the address it
+        // is connected to.
+        let mut socket = socket.connect(server_addr).unwrap();
         let (packet, id) = NtpPacket::poll_message(PollIntervalLimits::default().min);
 
         let serialized = serialize_packet_unencrypted(&packet);
         socket.send(&serialized).await.unwrap();
 
         let mut buf = [0; 48];
-        tokio::time::timeout(Duration::from_millis(10), socket.recv(&mut buf))
-            .await
-            .unwrap()
-            .unwrap();
-        let packet = NtpPacket::deserialize(&buf, &NoCipher).unwrap().0;
+        let result = tokio::time::timeout(Duration::from_millis(10), socket.recv(&mut buf)).await;
+        join.abort();
+
+        result.ok().map(|recv| {
+            recv.unwrap();
+            let packet = NtpPacket::deserialize(&buf, &NoCipher)
+                .unwrap()
+                .0
+                .into_owned();
+            assert!(packet.valid_server_response(id, false));
+            packet
+        })
+    }
+
+    // Any address in 127.0.0.0/8 is local on Linux, but the kernel would pick
+    // 127.0.0.1 as the source address for replies that don't specify one.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_server_responds_from_request_destination() {
+        let port = alloc_port();
+        let config = ServerConfig::from(SocketAddr::new("0.0.0.0".parse().unwrap(), port));
+        assert!(config.respect_request_destination);
+        let server_addr = SocketAddr::new("127.0.0.2".parse().unwrap(), port);
+
+        let packet = request_response(config.clone(), server_addr).await;
+        assert!(packet.is_some_and(|packet| packet.stratum() != 0));
+
+        let port = alloc_port();
+        let config = ServerConfig {
+            listen: SocketAddr::new("0.0.0.0".parse().unwrap(), port),
+            respect_request_destination: false,
+            ..config
+        };
+        let server_addr = SocketAddr::new("127.0.0.2".parse().unwrap(), port);
+        assert!(request_response(config, server_addr).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_server_serves() {
+        let port = alloc_port();
+        let config = ServerConfig::from(SocketAddr::new("127.0.0.1".parse().unwrap(), port));
+        let server_addr = config.listen;
+
+        let packet = request_response(config, server_addr).await.unwrap();
         assert_ne!(packet.stratum(), 0);
-        assert!(packet.valid_server_response(id, false));
+    }
 
-        join.abort();
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_server_reuse_port() {
+        let listen = SocketAddr::new("127.0.0.1".parse().unwrap(), alloc_port());
+        let config = ServerConfig {
+            reuse_port: true,
+            ..ServerConfig::from(listen)
+        };
+
+        let first = ServerTask::<TestClock>::open_socket(&config).unwrap();
+        let second = ServerTask::<TestClock>::open_socket(&config).unwrap();
+        drop((first, second));
+
+        // Without the option, the address can only be bound once
+        let config = ServerConfig::from(SocketAddr::new(listen.ip(), alloc_port()));
+        let _first = ServerTask::<TestClock>::open_socket(&config).unwrap();
+        assert!(ServerTask::<TestClock>::open_socket(&config).is_err());
     }
 }
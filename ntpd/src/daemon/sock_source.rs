@@ -149,18 +149,21 @@ where
 
                         self.source.handle_measurement(measurement);
 
+                        let observed = self.source.observe(
+                            "GPSd socket".to_string(),
+                            self.path.display().to_string(),
+                            self.index,
+                        );
+                        self.channels
+                            .source_histograms
+                            .write()
+                            .expect("Unexpected poisoned mutex")
+                            .record_source(&observed);
                         self.channels
                             .source_snapshots
                             .write()
                             .expect("Unexpected poisoned mutex")
-                            .insert(
-                                self.index,
-                                self.source.observe(
-                                    "GPSd socket".to_string(),
-                                    self.path.display().to_string(),
-                                    self.index,
-                                ),
-                            );
+                            .insert(self.index, observed);
                     }
                     Err(e) => {
                         error!("Error deserializing sample: {}", e);
@@ -217,6 +220,7 @@ mod tests {
         daemon::{
             ntp_source::SourceChannels,
             sock_source::{SOCK_MAGIC, SampleError, SockSourceTask, create_socket},
+            system::SourceHistograms,
             util::EPOCH_OFFSET,
         },
         test::alloc_port,
@@ -295,6 +299,7 @@ mod tests {
             SourceChannels {
                 msg_for_system_sender,
                 source_snapshots: Arc::new(RwLock::new(HashMap::new())),
+                source_histograms: Arc::new(RwLock::new(SourceHistograms::default())),
             },
             OneWaySource::new(controller.add_one_way_source(
                 index,
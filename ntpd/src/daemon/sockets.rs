@@ -1,6 +1,8 @@
 use std::fs::Permissions;
 use std::path::Path;
 
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 const MAX_JSON_MESSAGE_SIZE: u64 = 1 << 20; // 1 MiB
@@ -14,6 +16,107 @@ where
     stream.write_all(&bytes).await
 }
 
+/// Write a length-prefixed JSON message, followed by a second length-prefixed
+/// frame containing an HMAC-SHA256 over the serialized JSON
+pub async fn write_json_signed<T>(
+    stream: &mut (impl AsyncWrite + Unpin),
+    value: &T,
+    key: &[u8],
+) -> std::io::Result<()>
+where
+    T: serde::Serialize,
+{
+    let bytes = serde_json::to_vec(value).unwrap();
+    let tag = hmac_sha256(key, &bytes);
+    stream.write_u64(bytes.len() as u64).await?;
+    stream.write_all(&bytes).await?;
+    stream.write_u64(tag.len() as u64).await?;
+    stream.write_all(&tag).await
+}
+
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+pub async fn write_json_line<T>(
+    stream: &mut (impl AsyncWrite + Unpin),
+    value: &T,
+) -> std::io::Result<()>
+where
+    T: serde::Serialize,
+{
+    let mut bytes = serde_json::to_vec(value).unwrap();
+    bytes.push(b'\n');
+    stream.write_all(&bytes).await
+}
+
+/// Write a line containing the JSON object `{"data":<value>,"hmac":"<tag>"}`,
+/// where the tag is the hex encoded HMAC-SHA256 over the serialized value
+/// exactly as it appears in the line
+pub async fn write_json_line_signed<T>(
+    stream: &mut (impl AsyncWrite + Unpin),
+    value: &T,
+    key: &[u8],
+) -> std::io::Result<()>
+where
+    T: serde::Serialize,
+{
+    let data = serde_json::to_vec(value).unwrap();
+    let tag = hmac_sha256(key, &data);
+    let mut bytes = b"{\"data\":".to_vec();
+    bytes.extend_from_slice(&data);
+    bytes.extend_from_slice(b",\"hmac\":\"");
+    for byte in tag {
+        bytes.extend_from_slice(format!("{byte:02x}").as_bytes());
+    }
+    bytes.extend_from_slice(b"\"}\n");
+    stream.write_all(&bytes).await
+}
+
+/// Write a length-prefixed CBOR message, framed the same as [`write_json`]
+pub async fn write_cbor<T>(stream: &mut (impl AsyncWrite + Unpin), value: &T) -> std::io::Result<()>
+where
+    T: serde::Serialize,
+{
+    let mut bytes = Vec::new();
+    ciborium::into_writer(value, &mut bytes).map_err(std::io::Error::other)?;
+    stream.write_u64(bytes.len() as u64).await?;
+    stream.write_all(&bytes).await
+}
+
+/// Write a length-prefixed CBOR message, followed by a second length-prefixed
+/// frame containing an HMAC-SHA256 over the serialized CBOR
+pub async fn write_cbor_signed<T>(
+    stream: &mut (impl AsyncWrite + Unpin),
+    value: &T,
+    key: &[u8],
+) -> std::io::Result<()>
+where
+    T: serde::Serialize,
+{
+    let mut bytes = Vec::new();
+    ciborium::into_writer(value, &mut bytes).map_err(std::io::Error::other)?;
+    let tag = hmac_sha256(key, &bytes);
+    stream.write_u64(bytes.len() as u64).await?;
+    stream.write_all(&bytes).await?;
+    stream.write_u64(tag.len() as u64).await?;
+    stream.write_all(&tag).await
+}
+
+pub async fn read_cbor<T>(
+    stream: &mut (impl AsyncRead + Unpin),
+    buffer: &mut Vec<u8>,
+) -> std::io::Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    read_message(stream, buffer).await?;
+    ciborium::from_reader(buffer.as_slice())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+}
+
 pub async fn read_json<'a, T>(
     stream: &mut (impl AsyncRead + Unpin),
     buffer: &'a mut Vec<u8>,
@@ -21,6 +124,15 @@ pub async fn read_json<'a, T>(
 where
     T: serde::Deserialize<'a>,
 {
+    read_message(stream, buffer).await?;
+    serde_json::from_slice(buffer)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+}
+
+async fn read_message(
+    stream: &mut (impl AsyncRead + Unpin),
+    buffer: &mut Vec<u8>,
+) -> std::io::Result<()> {
     buffer.clear();
     let msg_size = stream.read_u64().await?;
     if msg_size > MAX_JSON_MESSAGE_SIZE {
@@ -37,8 +149,7 @@ where
     })?;
     buffer.resize(msg_size, 0);
     stream.read_exact(buffer).await?;
-    serde_json::from_slice(buffer)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+    Ok(())
 }
 
 fn other_error<T>(msg: String) -> std::io::Result<T> {
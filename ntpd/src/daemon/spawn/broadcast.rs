@@ -0,0 +1,131 @@
+use std::net::SocketAddr;
+
+use ntp_proto::SourceConfig;
+use tokio::sync::mpsc;
+
+use crate::daemon::config::BroadcastSourceConfig;
+
+use super::{
+    BroadcastSourceCreateParameters, ClockId, SourceCreateParameters, SourceRemovalReason,
+    SourceRemovedEvent, SpawnAction, SpawnEvent, Spawner, SpawnerId, standard::StandardSpawnError,
+};
+
+pub struct BroadcastSpawner {
+    config: BroadcastSourceConfig,
+    source_config: SourceConfig,
+    id: SpawnerId,
+    has_spawned: bool,
+}
+
+impl BroadcastSpawner {
+    pub fn new(config: BroadcastSourceConfig, source_config: SourceConfig) -> BroadcastSpawner {
+        BroadcastSpawner {
+            config,
+            source_config,
+            id: SpawnerId::new(),
+            has_spawned: false,
+        }
+    }
+}
+
+impl Spawner for BroadcastSpawner {
+    type Error = StandardSpawnError;
+
+    async fn try_spawn(
+        &mut self,
+        action_tx: &mpsc::Sender<SpawnEvent>,
+    ) -> Result<(), StandardSpawnError> {
+        action_tx
+            .send(SpawnEvent::new(
+                self.id,
+                SpawnAction::Create(SourceCreateParameters::Broadcast(
+                    BroadcastSourceCreateParameters {
+                        id: ClockId::new(),
+                        // Calibration exchanges go to the regular NTP port of the server
+                        addr: SocketAddr::new(self.config.address, 123),
+                        listen: self.config.listen_addr(),
+                        group: self.config.group,
+                        config: self.source_config,
+                        precision: self.config.precision.powi(2),
+                        accuracy: self.config.accuracy,
+                    },
+                )),
+            ))
+            .await?;
+        self.has_spawned = true;
+        Ok(())
+    }
+
+    fn is_complete(&self) -> bool {
+        self.has_spawned
+    }
+
+    async fn handle_source_removed(
+        &mut self,
+        removed_source: SourceRemovedEvent,
+    ) -> Result<(), StandardSpawnError> {
+        if removed_source.reason != SourceRemovalReason::Demobilized {
+            self.has_spawned = false;
+        }
+        Ok(())
+    }
+
+    fn get_id(&self) -> SpawnerId {
+        self.id
+    }
+
+    fn get_addr_description(&self) -> String {
+        self.config.address.to_string()
+    }
+
+    fn get_description(&self) -> &'static str {
+        "broadcast"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ntp_proto::SourceConfig;
+    use tokio::sync::mpsc;
+
+    use crate::daemon::{
+        config::BroadcastSourceConfig,
+        spawn::{SourceCreateParameters, SpawnAction, Spawner, broadcast::BroadcastSpawner},
+        system::MESSAGE_BUFFER_SIZE,
+    };
+
+    #[tokio::test]
+    async fn creates_a_source() {
+        let mut spawner = BroadcastSpawner::new(
+            BroadcastSourceConfig {
+                address: "192.0.2.1".parse().unwrap(),
+                listen: None,
+                group: Some("224.0.1.1".parse().unwrap()),
+                precision: 1e-3,
+                accuracy: 0.0,
+            },
+            SourceConfig::default(),
+        );
+        let spawner_id = spawner.get_id();
+        let (action_tx, mut action_rx) = mpsc::channel(MESSAGE_BUFFER_SIZE);
+
+        assert!(!spawner.is_complete());
+        spawner.try_spawn(&action_tx).await.unwrap();
+        let res = action_rx.try_recv().unwrap();
+        assert_eq!(res.id, spawner_id);
+
+        let SpawnAction::Create(create_params) = res.action else {
+            panic!("did not receive create action!");
+        };
+        assert_eq!(create_params.get_addr(), "192.0.2.1:123");
+
+        let SourceCreateParameters::Broadcast(params) = create_params else {
+            panic!("did not receive broadcast source create parameters!");
+        };
+        assert_eq!(params.listen, "0.0.0.0:123".parse().unwrap());
+        assert_eq!(params.group, Some("224.0.1.1".parse().unwrap()));
+
+        // Should be complete after spawning
+        assert!(spawner.is_complete());
+    }
+}
@@ -3,7 +3,7 @@ use std::net::IpAddr;
 
 use tokio::sync::mpsc;
 
-use crate::daemon::config::{CsptpSourceConfig, NormalizedAddress, NtpAddress};
+use crate::daemon::config::{AddressFamily, CsptpSourceConfig, NormalizedAddress, NtpAddress};
 use crate::daemon::spawn::{
     CsptpSourceCreateParameters, SourceCreateParameters, resolve_single_ntp_server,
 };
@@ -54,10 +54,10 @@ impl CsptpSpawner {
         if let (false, Some(addr)) = (force_resolve, self.resolved) {
             Some(addr)
         } else {
-            let address = resolve_single_ntp_server(NtpAddress(NormalizedAddress::new_from_parts(
-                &self.config.address,
-                319,
-            )))
+            let address = resolve_single_ntp_server(
+                NtpAddress(NormalizedAddress::new_from_parts(&self.config.address, 319)),
+                AddressFamily::Any,
+            )
             .await?;
             self.resolved = Some(address.ip());
             self.resolved
@@ -157,7 +157,9 @@ mod tests {
         spawner.try_spawn(&action_tx).await.unwrap();
         let res = action_rx.try_recv().unwrap();
         assert_eq!(res.id, spawner_id);
-        let SpawnAction::Create(create_params) = &res.action;
+        let SpawnAction::Create(create_params) = &res.action else {
+            panic!("did not receive create action!");
+        };
         assert_eq!(create_params.get_addr(), "::1");
         let params = get_csptp_create_params(res).unwrap();
         assert_eq!(params.addr.to_string(), "::1");
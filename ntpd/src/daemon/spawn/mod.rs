@@ -1,18 +1,27 @@
-use std::{future::Future, net::SocketAddr, path::PathBuf, sync::atomic::AtomicU64};
+use std::{
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::atomic::AtomicU64,
+};
 
 use ntp_proto::{ClockId, ProtocolVersion, SourceConfig, SourceNtsData};
 use tokio::{
     sync::mpsc,
     time::{Instant, timeout},
 };
-use tracing::warn;
+use tracing::{error, warn};
 
 #[cfg(target_os = "linux")]
 use crate::daemon::config::CsptpSourceConfig;
-use crate::daemon::config::NtpAddress;
+use crate::daemon::config::{AddressFamily, NtpAddress};
 
-use super::{config::NormalizedAddress, system::NETWORK_WAIT_PERIOD};
+use super::{
+    config::{NormalizedAddress, StartupSourceRetryConfig},
+    system::NETWORK_WAIT_PERIOD,
+};
 
+pub mod broadcast;
 #[cfg(target_os = "linux")]
 pub mod csptp;
 pub mod nts;
@@ -89,11 +98,12 @@ pub enum SourceRemovalReason {
 }
 
 /// The kind of action that the spawner requests to the system.
-/// Currently a spawner can only create sources
 #[derive(Debug)]
 pub enum SpawnAction {
     Create(SourceCreateParameters),
-    // Remove(()),
+    /// Remove the source, but only if it is currently unreachable. A removed
+    /// source is reported back to the spawner like any other removal.
+    RemoveIfUnreachable(ClockId),
 }
 
 impl SpawnAction {
@@ -119,6 +129,7 @@ impl SpawnAction {
 #[derive(Debug)]
 pub enum SourceCreateParameters {
     Ntp(NtpSourceCreateParameters),
+    Broadcast(BroadcastSourceCreateParameters),
     Sock(SockSourceCreateParameters),
     #[cfg(feature = "pps")]
     Pps(PpsSourceCreateParameters),
@@ -130,6 +141,7 @@ impl SourceCreateParameters {
     pub fn get_id(&self) -> ClockId {
         match self {
             Self::Ntp(params) => params.id,
+            Self::Broadcast(params) => params.id,
             Self::Sock(params) => params.id,
             #[cfg(feature = "pps")]
             Self::Pps(params) => params.id,
@@ -141,6 +153,7 @@ impl SourceCreateParameters {
     pub fn get_addr(&self) -> String {
         match self {
             Self::Ntp(params) => params.addr.to_string(),
+            Self::Broadcast(params) => params.addr.to_string(),
             Self::Sock(params) => params.path.display().to_string(),
             #[cfg(feature = "pps")]
             Self::Pps(params) => params.path.display().to_string(),
@@ -168,6 +181,17 @@ pub struct CsptpSourceCreateParameters {
     pub config: CsptpSourceConfig,
 }
 
+#[derive(Debug)]
+pub struct BroadcastSourceCreateParameters {
+    pub id: ClockId,
+    pub addr: SocketAddr,
+    pub listen: SocketAddr,
+    pub group: Option<IpAddr>,
+    pub config: SourceConfig,
+    pub precision: f64,
+    pub accuracy: f64,
+}
+
 #[derive(Debug)]
 pub struct SockSourceCreateParameters {
     pub id: ClockId,
@@ -233,6 +257,30 @@ pub trait Spawner {
         async { Ok(()) }
     }
 
+    /// Minimum time to wait before [`Spawner::try_spawn`] is called again, for
+    /// spawners that back off after failures. Retries are never made sooner
+    /// than the regular schedule of the spawner task.
+    fn retry_delay(&self) -> Option<std::time::Duration> {
+        None
+    }
+
+    /// Interval at which [`Spawner::refresh`] should be called, if at all
+    fn refresh_interval(&self) -> Option<std::time::Duration> {
+        None
+    }
+
+    /// Periodically re-evaluate the sources created by this spawner.
+    ///
+    /// This is called every [`Spawner::refresh_interval`], and can be used to
+    /// request removal of sources that should be replaced. Any replacements
+    /// should be created in try_spawn once the removal is reported back.
+    fn refresh(
+        &mut self,
+        _action_tx: &mpsc::Sender<SpawnEvent>,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send {
+        async { Ok(()) }
+    }
+
     /// Get the id of the spawner
     fn get_id(&self) -> SpawnerId;
 
@@ -247,30 +295,79 @@ pub async fn spawner_task<S: Spawner + Send + 'static>(
     mut spawner: S,
     action_tx: mpsc::Sender<SpawnEvent>,
     mut system_notify: mpsc::Receiver<SystemEvent>,
+    startup_retry: StartupSourceRetryConfig,
 ) -> Result<(), S::Error> {
     let mut has_ticket = true;
     let mut last_ticket_time = Instant::now();
+    let mut wait_period = NETWORK_WAIT_PERIOD;
+    // Additional delay requested by the spawner after its last attempt
+    let mut retry_delay = std::time::Duration::ZERO;
+    // Attempts made until the spawner first got all its sources, after
+    // which the startup retry schedule no longer applies.
+    let mut startup_attempts = Some(0);
+    let mut failed = false;
+    let mut last_refresh_time = Instant::now();
 
     loop {
-        if last_ticket_time.elapsed() >= NETWORK_WAIT_PERIOD {
+        if last_ticket_time.elapsed() >= wait_period.max(retry_delay) {
             has_ticket = true;
         }
 
-        if has_ticket && !spawner.is_complete() {
+        if !failed
+            && let Some(interval) = spawner.refresh_interval()
+            && last_refresh_time.elapsed() >= interval
+        {
+            spawner.refresh(&action_tx).await?;
+            last_refresh_time = Instant::now();
+        }
+
+        if has_ticket && !failed && !spawner.is_complete() {
             spawner.try_spawn(&action_tx).await?;
             has_ticket = false;
             last_ticket_time = Instant::now();
+            retry_delay = spawner.retry_delay().unwrap_or_default();
+
+            if let Some(attempts) = startup_attempts.as_mut() {
+                *attempts += 1;
+                if spawner.is_complete() {
+                    startup_attempts = None;
+                    wait_period = NETWORK_WAIT_PERIOD;
+                } else if startup_retry
+                    .max_attempts
+                    .is_some_and(|max| *attempts >= max)
+                {
+                    error!(
+                        attempts,
+                        "Giving up on {} source {}, it will not be used",
+                        spawner.get_description(),
+                        spawner.get_addr_description()
+                    );
+                    failed = true;
+                } else {
+                    wait_period = startup_retry.retry_delay(*attempts);
+                }
+            }
         }
 
-        let event = if has_ticket {
-            system_notify.recv().await
+        let mut next_wakeup = if has_ticket || failed {
+            None
         } else {
-            timeout(
-                NETWORK_WAIT_PERIOD.saturating_sub(last_ticket_time.elapsed()),
-                system_notify.recv(),
+            Some(
+                wait_period
+                    .max(retry_delay)
+                    .saturating_sub(last_ticket_time.elapsed()),
             )
-            .await
-            .unwrap_or(Some(SystemEvent::Idle))
+        };
+        if !failed && let Some(interval) = spawner.refresh_interval() {
+            let until_refresh = interval.saturating_sub(last_refresh_time.elapsed());
+            next_wakeup = Some(next_wakeup.map_or(until_refresh, |w| w.min(until_refresh)));
+        }
+
+        let event = match next_wakeup {
+            None => system_notify.recv().await,
+            Some(wakeup) => timeout(wakeup, system_notify.recv())
+                .await
+                .unwrap_or(Some(SystemEvent::Idle)),
         };
 
         let Some(event) = event else {
@@ -291,8 +388,11 @@ pub async fn spawner_task<S: Spawner + Send + 'static>(
     Ok(())
 }
 
-pub(super) async fn resolve_single_ntp_server(address: NtpAddress) -> Option<SocketAddr> {
-    match address.lookup_host().await {
+pub(super) async fn resolve_single_ntp_server(
+    address: NtpAddress,
+    family: AddressFamily,
+) -> Option<SocketAddr> {
+    match address.lookup_host_in_family(family).await {
         Ok(addresses) => {
             let mut last_error = None;
             for addr in addresses {
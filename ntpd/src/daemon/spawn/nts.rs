@@ -1,10 +1,12 @@
 use std::fmt::Display;
 use std::ops::Deref;
+use std::time::Duration;
 
 use ntp_proto::{KeyExchangeClient, NtsClientConfig, NtsError, SourceConfig};
+use rand::Rng;
 use tokio::net::TcpStream;
 use tokio::sync::mpsc;
-use tracing::warn;
+use tracing::{info, warn};
 
 use crate::daemon::config::{NormalizedAddress, NtpAddress};
 use crate::daemon::dns::resolve_ke;
@@ -20,6 +22,30 @@ pub struct NtsSpawner {
     source_config: SourceConfig,
     id: SpawnerId,
     has_spawned: bool,
+    /// Number of key exchanges that failed since the last successful one
+    failed_exchanges: u32,
+    retry_delay: Option<Duration>,
+}
+
+/// Fraction by which the time until the next key exchange retry is randomly
+/// lengthened or shortened
+const KE_RETRY_JITTER: f64 = 0.25;
+
+/// Time to wait before retrying after the given number of failed key
+/// exchanges. The jitter, between -1.0 and 1.0, spreads out the retries of
+/// clients that failed at the same time.
+pub(super) fn ke_retry_delay(
+    interval_ms: u64,
+    max_interval_ms: u64,
+    failed_exchanges: u32,
+    jitter: f64,
+) -> Duration {
+    let max = Duration::from_millis(max_interval_ms);
+    let exponent = i32::try_from(failed_exchanges.saturating_sub(1)).unwrap_or(i32::MAX);
+    let delay = Duration::from_millis(interval_ms).as_secs_f64()
+        * 2f64.powi(exponent)
+        * (1.0 + KE_RETRY_JITTER * jitter.clamp(-1.0, 1.0));
+    Duration::try_from_secs_f64(delay).unwrap_or(max).min(max)
 }
 
 #[derive(Debug)]
@@ -49,7 +75,7 @@ impl NtsSpawner {
         source_config: SourceConfig,
     ) -> Result<NtsSpawner, NtsError> {
         let key_exchange_client = KeyExchangeClient::new(&NtsClientConfig {
-            certificates: config.certificate_authorities.clone(),
+            certificates: config.certificate_authorities.certificates.clone(),
             protocol_version: config.ntp_version,
         })?;
 
@@ -59,9 +85,34 @@ impl NtsSpawner {
             source_config,
             id: SpawnerId::new(),
             has_spawned: false,
+            failed_exchanges: 0,
+            retry_delay: None,
         })
     }
 
+    fn record_failed_exchange(&mut self) {
+        self.failed_exchanges = self.failed_exchanges.saturating_add(1);
+        let jitter = rand::thread_rng().gen_range(-1.0..=1.0);
+        let delay = ke_retry_delay(
+            self.config.ke_retry_interval_ms,
+            self.config.ke_retry_max_interval_ms,
+            self.failed_exchanges,
+            jitter,
+        );
+        info!(
+            failed_exchanges = self.failed_exchanges,
+            "Retrying key exchange with {} in {:.1}s",
+            *self.config.address,
+            delay.as_secs_f64()
+        );
+        self.retry_delay = Some(delay);
+    }
+
+    fn record_successful_exchange(&mut self) {
+        self.failed_exchanges = 0;
+        self.retry_delay = None;
+    }
+
     // We do resolution and connecting at the same time to deal with problems with either
     // ipv4 or ipv6.
     async fn resolve_and_connect(&mut self) -> Option<(TcpStream, String)> {
@@ -125,6 +176,7 @@ impl Spawner for NtsSpawner {
         action_tx: &mpsc::Sender<SpawnEvent>,
     ) -> Result<(), NtsSpawnError> {
         let Some((io, name)) = self.resolve_and_connect().await else {
+            self.record_failed_exchange();
             return Ok(());
         };
 
@@ -135,9 +187,14 @@ impl Spawner for NtsSpawner {
         .await
         {
             Ok(Ok(ke)) => {
-                if let Some(address) = resolve_single_ntp_server(NtpAddress(
-                    NormalizedAddress::new_from_parts(ke.remote.as_str(), ke.port),
-                ))
+                self.record_successful_exchange();
+                if let Some(address) = resolve_single_ntp_server(
+                    NtpAddress(NormalizedAddress::new_from_parts(
+                        ke.remote.as_str(),
+                        ke.port,
+                    )),
+                    self.config.address_family.unwrap_or_default(),
+                )
                 .await
                 {
                     action_tx
@@ -158,9 +215,11 @@ impl Spawner for NtsSpawner {
             }
             Ok(Err(e)) => {
                 warn!(error = ?e, "error while attempting key exchange");
+                self.record_failed_exchange();
             }
             Err(_) => {
                 warn!("timeout while attempting key exchange");
+                self.record_failed_exchange();
             }
         }
 
@@ -171,6 +230,10 @@ impl Spawner for NtsSpawner {
         self.has_spawned
     }
 
+    fn retry_delay(&self) -> Option<Duration> {
+        self.retry_delay
+    }
+
     async fn handle_source_removed(
         &mut self,
         _removed_source: SourceRemovedEvent,
@@ -194,16 +257,86 @@ impl Spawner for NtsSpawner {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
-
     use ntp_proto::SourceConfig;
     use tokio::{io::AsyncReadExt, net::TcpListener};
 
     use crate::daemon::{
-        config::{NormalizedAddress, NtsKeAddress, NtsSourceConfig},
-        spawn::{Spawner, nts::NtsSpawner},
+        config::{CertificateAuthorities, NormalizedAddress, NtsKeAddress, NtsSourceConfig},
+        spawn::{
+            Spawner,
+            nts::{NtsSpawner, ke_retry_delay},
+        },
     };
 
+    fn test_config(port: u16) -> NtsSourceConfig {
+        NtsSourceConfig {
+            address: NtsKeAddress(NormalizedAddress::new_from_parts("localhost", port)),
+            enable_srv_resolution: false,
+            certificate_authorities: CertificateAuthorities::default(),
+            ntp_version: ntp_proto::ProtocolVersion::V4,
+            prefer: false,
+            require_authenticated: false,
+            ke_retry_interval_ms: 1000,
+            ke_retry_max_interval_ms: 60_000,
+            address_family: None,
+        }
+    }
+
+    #[test]
+    fn ke_retry_schedule() {
+        let config = test_config(4460);
+        let delay = |failed_exchanges, jitter| {
+            ke_retry_delay(
+                config.ke_retry_interval_ms,
+                config.ke_retry_max_interval_ms,
+                failed_exchanges,
+                jitter,
+            )
+            .as_secs_f64()
+        };
+
+        for (failed_exchanges, expected) in [(1, 1.0), (2, 2.0), (3, 4.0), (6, 32.0)] {
+            assert!((delay(failed_exchanges, 0.0) - expected).abs() < 1e-9);
+            assert!((delay(failed_exchanges, -1.0) - 0.75 * expected).abs() < 1e-9);
+            assert!((delay(failed_exchanges, 1.0) - 1.25 * expected).abs() < 1e-9);
+        }
+
+        // The delay is capped, also with jitter and when it would overflow
+        assert!((delay(7, 0.0) - 60.0).abs() < 1e-9);
+        assert!((delay(7, 1.0) - 60.0).abs() < 1e-9);
+        assert!((delay(u32::MAX, 0.0) - 60.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn ke_retry_backoff_and_reset() {
+        // Nothing listens on the port once the listener is dropped
+        let listener = TcpListener::bind("[::]:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let mut spawner = NtsSpawner::new(test_config(port), SourceConfig::default()).unwrap();
+        let (sender, _receiver) = tokio::sync::mpsc::channel(1);
+        assert_eq!(spawner.retry_delay(), None);
+
+        for (failed_exchanges, expected) in [(1, 1.0), (2, 2.0), (3, 4.0)] {
+            assert!(spawner.try_spawn(&sender).await.is_ok());
+            assert!(!spawner.is_complete());
+            assert_eq!(spawner.failed_exchanges, failed_exchanges);
+
+            let delay = spawner.retry_delay().unwrap().as_secs_f64();
+            assert!((0.75 * expected..=1.25 * expected).contains(&delay));
+        }
+
+        // A successful key exchange resets the backoff
+        spawner.record_successful_exchange();
+        assert_eq!(spawner.failed_exchanges, 0);
+        assert_eq!(spawner.retry_delay(), None);
+
+        assert!(spawner.try_spawn(&sender).await.is_ok());
+        let delay = spawner.retry_delay().unwrap().as_secs_f64();
+        assert!((0.75..=1.25).contains(&delay));
+    }
+
     #[tokio::test]
     async fn direct_name_resolution() {
         #[cfg(feature = "openssl")]
@@ -221,8 +354,13 @@ mod tests {
             NtsSourceConfig {
                 address: NtsKeAddress(NormalizedAddress::new_from_parts("localhost", addr.port())),
                 enable_srv_resolution: false,
-                certificate_authorities: Arc::default(),
+                certificate_authorities: CertificateAuthorities::default(),
                 ntp_version: ntp_proto::ProtocolVersion::V4,
+                prefer: false,
+                require_authenticated: false,
+                ke_retry_interval_ms: 1000,
+                ke_retry_max_interval_ms: 300_000,
+                address_family: None,
             },
             SourceConfig::default(),
         )
@@ -254,8 +392,13 @@ mod tests {
             NtsSourceConfig {
                 address: NtsKeAddress(NormalizedAddress::new_from_parts("localhost", addr.port())),
                 enable_srv_resolution: true,
-                certificate_authorities: Arc::default(),
+                certificate_authorities: CertificateAuthorities::default(),
                 ntp_version: ntp_proto::ProtocolVersion::V4,
+                prefer: false,
+                require_authenticated: false,
+                ke_retry_interval_ms: 1000,
+                ke_retry_max_interval_ms: 300_000,
+                address_family: None,
             },
             SourceConfig::default(),
         )
@@ -2,10 +2,12 @@ use std::borrow::Cow;
 use std::collections::VecDeque;
 use std::fmt::Display;
 use std::ops::Deref;
+use std::time::Duration;
 
+use rand::Rng;
 use tokio::net::TcpStream;
 use tokio::sync::mpsc;
-use tracing::warn;
+use tracing::{info, warn};
 
 use ntp_proto::{KeyExchangeClient, NtsClientConfig, NtsError, SourceConfig};
 
@@ -15,6 +17,7 @@ use crate::daemon::spawn::resolve_single_ntp_server;
 
 use super::super::config::NtsPoolSourceConfig;
 
+use super::nts::ke_retry_delay;
 use super::{ClockId, SourceRemovedEvent, SpawnAction, SpawnEvent, Spawner, SpawnerId};
 
 struct PoolSource {
@@ -29,6 +32,10 @@ pub struct NtsPoolSpawner {
     id: SpawnerId,
     current_sources: Vec<PoolSource>,
     known_resolutions: VecDeque<KeResolutionResult>,
+    /// Number of attempts to add sources that failed since the last one
+    /// that did not
+    failed_exchanges: u32,
+    retry_delay: Option<Duration>,
 }
 
 #[derive(Debug)]
@@ -58,7 +65,7 @@ impl NtsPoolSpawner {
         source_config: SourceConfig,
     ) -> Result<NtsPoolSpawner, NtsError> {
         let key_exchange_client = KeyExchangeClient::new(&NtsClientConfig {
-            certificates: config.certificate_authorities.clone(),
+            certificates: config.certificate_authorities.certificates.clone(),
             protocol_version: config.ntp_version,
         })?;
 
@@ -69,9 +76,34 @@ impl NtsPoolSpawner {
             id: SpawnerId::new(),
             current_sources: vec![],
             known_resolutions: VecDeque::new(),
+            failed_exchanges: 0,
+            retry_delay: None,
         })
     }
 
+    fn record_failed_exchange(&mut self) {
+        self.failed_exchanges = self.failed_exchanges.saturating_add(1);
+        let jitter = rand::thread_rng().gen_range(-1.0..=1.0);
+        let delay = ke_retry_delay(
+            self.config.ke_retry_interval_ms,
+            self.config.ke_retry_max_interval_ms,
+            self.failed_exchanges,
+            jitter,
+        );
+        info!(
+            failed_exchanges = self.failed_exchanges,
+            "Retrying key exchange with pool {} in {:.1}s",
+            *self.config.addr,
+            delay.as_secs_f64()
+        );
+        self.retry_delay = Some(delay);
+    }
+
+    fn record_successful_exchange(&mut self) {
+        self.failed_exchanges = 0;
+        self.retry_delay = None;
+    }
+
     fn contains_source(&self, domain: &str) -> bool {
         self.current_sources
             .iter()
@@ -158,9 +190,11 @@ impl Spawner for NtsPoolSpawner {
         &mut self,
         action_tx: &mpsc::Sender<SpawnEvent>,
     ) -> Result<(), NtsPoolSpawnError> {
+        let mut failed = false;
         for _ in 0..self.config.count.saturating_sub(self.current_sources.len()) {
             let Some((io, name, remote_name)) = self.lookup().await else {
-                return Ok(());
+                failed = true;
+                break;
             };
 
             match tokio::time::timeout(
@@ -178,9 +212,13 @@ impl Spawner for NtsPoolSpawner {
                 Ok(Ok(ke))
                     if !self.contains_source(remote_name.as_deref().unwrap_or(&ke.remote)) =>
                 {
-                    if let Some(address) = resolve_single_ntp_server(NtpAddress(
-                        NormalizedAddress::new_from_parts(ke.remote.as_str(), ke.port),
-                    ))
+                    if let Some(address) = resolve_single_ntp_server(
+                        NtpAddress(NormalizedAddress::new_from_parts(
+                            ke.remote.as_str(),
+                            ke.port,
+                        )),
+                        self.config.address_family.unwrap_or_default(),
+                    )
                     .await
                     {
                         let id = ClockId::new();
@@ -208,14 +246,22 @@ impl Spawner for NtsPoolSpawner {
                 }
                 Ok(Err(e)) => {
                     warn!(error = ?e, "error while attempting key exchange");
+                    failed = true;
                     break;
                 }
                 Err(_) => {
                     warn!("timeout while attempting key exchange");
+                    failed = true;
                 }
             }
         }
 
+        if failed {
+            self.record_failed_exchange();
+        } else {
+            self.record_successful_exchange();
+        }
+
         Ok(())
     }
 
@@ -223,6 +269,10 @@ impl Spawner for NtsPoolSpawner {
         self.current_sources.len() >= self.config.count
     }
 
+    fn retry_delay(&self) -> Option<Duration> {
+        self.retry_delay
+    }
+
     async fn handle_source_removed(
         &mut self,
         removed_source: SourceRemovedEvent,
@@ -243,3 +293,57 @@ impl Spawner for NtsPoolSpawner {
         "nts-pool"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ntp_proto::SourceConfig;
+    use tokio::net::TcpListener;
+
+    use crate::daemon::{
+        config::{CertificateAuthorities, NormalizedAddress, NtsKeAddress, NtsPoolSourceConfig},
+        spawn::{Spawner, nts_pool::NtsPoolSpawner},
+    };
+
+    #[tokio::test]
+    async fn ke_retry_backoff_and_reset() {
+        // Nothing listens on the port once the listener is dropped
+        let listener = TcpListener::bind("[::]:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let mut spawner = NtsPoolSpawner::new(
+            NtsPoolSourceConfig {
+                addr: NtsKeAddress(NormalizedAddress::new_from_parts("localhost", port)),
+                enable_srv_resolution: false,
+                certificate_authorities: CertificateAuthorities::default(),
+                count: 2,
+                ntp_version: ntp_proto::ProtocolVersion::V4,
+                ke_retry_interval_ms: 1000,
+                ke_retry_max_interval_ms: 60_000,
+                address_family: None,
+            },
+            SourceConfig::default(),
+        )
+        .unwrap();
+        let (sender, _receiver) = tokio::sync::mpsc::channel(1);
+        assert_eq!(spawner.retry_delay(), None);
+
+        for (failed_exchanges, expected) in [(1, 1.0), (2, 2.0), (3, 4.0)] {
+            assert!(spawner.try_spawn(&sender).await.is_ok());
+            assert!(!spawner.is_complete());
+            assert_eq!(spawner.failed_exchanges, failed_exchanges);
+
+            let delay = spawner.retry_delay().unwrap().as_secs_f64();
+            assert!((0.75 * expected..=1.25 * expected).contains(&delay));
+        }
+
+        // Adding sources without failures resets the backoff
+        spawner.record_successful_exchange();
+        assert_eq!(spawner.failed_exchanges, 0);
+        assert_eq!(spawner.retry_delay(), None);
+
+        assert!(spawner.try_spawn(&sender).await.is_ok());
+        let delay = spawner.retry_delay().unwrap().as_secs_f64();
+        assert!((0.75..=1.25).contains(&delay));
+    }
+}
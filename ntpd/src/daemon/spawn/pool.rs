@@ -1,4 +1,5 @@
 use std::fmt::Display;
+use std::time::Duration;
 use std::{net::SocketAddr, ops::Deref};
 
 use ntp_proto::SourceConfig;
@@ -20,6 +21,8 @@ pub struct PoolSpawner {
     id: SpawnerId,
     current_sources: Vec<PoolSource>,
     known_ips: Vec<SocketAddr>,
+    /// Position in the static member list of the next member to try
+    next_static_member: usize,
 }
 
 #[derive(Debug)]
@@ -41,8 +44,55 @@ impl PoolSpawner {
             id: SpawnerId::new(),
             current_sources: vec![],
             known_ips: vec![],
+            next_static_member: 0,
         }
     }
+
+    fn has_static_members(&self) -> bool {
+        !self.config.addresses.is_empty()
+    }
+
+    /// Take the next usable address from the static member list, continuing
+    /// after the last member handed out so replacements rotate through the
+    /// list.
+    fn take_static_member(&mut self) -> Option<SocketAddr> {
+        let addresses = &self.config.addresses;
+        let family = self.config.address_family.unwrap_or_default();
+        for _ in 0..addresses.len() {
+            let addr = addresses[self.next_static_member];
+            self.next_static_member = (self.next_static_member + 1) % addresses.len();
+            if !self.current_sources.iter().any(|p| p.addr == addr)
+                && !self.config.ignore.iter().any(|ign| *ign == addr.ip())
+                && family.matches(&addr)
+            {
+                return Some(addr);
+            }
+        }
+
+        None
+    }
+
+    /// Update the spawner with a fresh resolution of the pool address.
+    ///
+    /// The addresses available for new sources are replaced by the resolved
+    /// ones, and the ids of current sources whose address is no longer part
+    /// of the resolution are returned.
+    fn update_members(&mut self, resolved: Vec<SocketAddr>) -> Vec<ClockId> {
+        let vanished = self
+            .current_sources
+            .iter()
+            .filter(|p| !resolved.contains(&p.addr))
+            .map(|p| p.id)
+            .collect();
+
+        self.known_ips = resolved;
+        self.known_ips.retain(|ip| {
+            !self.current_sources.iter().any(|p| p.addr == *ip)
+                && !self.config.ignore.iter().any(|ign| *ign == ip.ip())
+        });
+
+        vanished
+    }
 }
 
 impl Spawner for PoolSpawner {
@@ -57,11 +107,18 @@ impl Spawner for PoolSpawner {
             return Ok(());
         }
 
-        if self.known_ips.len() < self.config.count - self.current_sources.len() {
-            match self.config.addr.lookup_host().await {
-                Ok(addresses) => {
+        if !self.has_static_members()
+            && self.known_ips.len() < self.config.count - self.current_sources.len()
+        {
+            match self
+                .config
+                .addr
+                .lookup_host_in_family(self.config.address_family.unwrap_or_default())
+                .await
+            {
+                Ok(mut addresses) => {
                     // add the addresses looked up to our list of known ips
-                    self.known_ips.append(&mut addresses.collect());
+                    self.known_ips.append(&mut addresses);
                     // remove known ips that we are already connected to or that we want to ignore
                     self.known_ips.retain(|ip| {
                         !self.current_sources.iter().any(|p| p.addr == *ip)
@@ -77,7 +134,13 @@ impl Spawner for PoolSpawner {
 
         // Try and add sources to our pool
         while self.current_sources.len() < self.config.count {
-            if let Some(addr) = self.known_ips.pop() {
+            let next = if self.has_static_members() {
+                self.take_static_member()
+            } else {
+                self.known_ips.pop()
+            };
+
+            if let Some(addr) = next {
                 let id = ClockId::new();
                 self.current_sources.push(PoolSource { id, addr });
                 let action = SpawnAction::create_ntp(
@@ -106,6 +169,49 @@ impl Spawner for PoolSpawner {
         self.current_sources.len() >= self.config.count
     }
 
+    fn refresh_interval(&self) -> Option<Duration> {
+        self.config
+            .refresh_interval
+            .map(|secs| Duration::from_secs(secs.get()))
+    }
+
+    async fn refresh(
+        &mut self,
+        action_tx: &mpsc::Sender<SpawnEvent>,
+    ) -> Result<(), PoolSpawnError> {
+        // A static member list has nothing to refresh
+        if self.has_static_members() {
+            return Ok(());
+        }
+
+        let resolved = match self
+            .config
+            .addr
+            .lookup_host_in_family(self.config.address_family.unwrap_or_default())
+            .await
+        {
+            Ok(addresses) => addresses,
+            Err(e) => {
+                warn!(error = ?e, "error while refreshing pool address, keeping current sources");
+                return Ok(());
+            }
+        };
+
+        // Only ask for removal, the system keeps members that are still reachable
+        for id in self.update_members(resolved) {
+            tracing::debug!(?id, "pool source no longer in DNS");
+            action_tx
+                .send(SpawnEvent::new(
+                    self.id,
+                    SpawnAction::RemoveIfUnreachable(id),
+                ))
+                .await
+                .expect("Channel was no longer connected");
+        }
+
+        Ok(())
+    }
+
     async fn handle_source_removed(
         &mut self,
         removed_source: SourceRemovedEvent,
@@ -129,15 +235,18 @@ impl Spawner for PoolSpawner {
 
 #[cfg(test)]
 mod tests {
-    use ntp_proto::ProtocolVersion;
+    use std::{net::SocketAddr, num::NonZeroU64, time::Duration};
+
+    use ntp_proto::{ClockId, ProtocolVersion};
 
     use ntp_proto::SourceConfig;
     use tokio::sync::mpsc::{self, error::TryRecvError};
 
     use crate::daemon::{
-        config::{NormalizedAddress, PoolSourceConfig},
+        config::{AddressFamily, NormalizedAddress, PoolSourceConfig},
         spawn::{
-            SourceRemovalReason, SourceRemovedEvent, Spawner, pool::PoolSpawner,
+            SourceRemovalReason, SourceRemovedEvent, SpawnAction, Spawner,
+            pool::{PoolSource, PoolSpawner},
             tests::get_ntp_create_params,
         },
         system::MESSAGE_BUFFER_SIZE,
@@ -155,6 +264,9 @@ mod tests {
                 count: 2,
                 ignore: vec![],
                 ntp_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
+                refresh_interval: None,
+                address_family: None,
+                addresses: vec![],
             },
             SourceConfig::default(),
         );
@@ -202,6 +314,9 @@ mod tests {
                 count: 2,
                 ignore: vec![],
                 ntp_version: ProtocolVersion::V5,
+                refresh_interval: None,
+                address_family: None,
+                addresses: vec![],
             },
             SourceConfig::default(),
         );
@@ -243,6 +358,9 @@ mod tests {
                 count: 2,
                 ignore: vec![],
                 ntp_version: ProtocolVersion::V4,
+                refresh_interval: None,
+                address_family: None,
+                addresses: vec![],
             },
             SourceConfig::default(),
         );
@@ -285,6 +403,9 @@ mod tests {
                 count: 2,
                 ignore: ignores.clone(),
                 ntp_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
+                refresh_interval: None,
+                address_family: None,
+                addresses: vec![],
             },
             SourceConfig::default(),
         );
@@ -326,6 +447,9 @@ mod tests {
                 count: 2,
                 ignore: vec![],
                 ntp_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
+                refresh_interval: None,
+                address_family: None,
+                addresses: vec![],
             },
             SourceConfig::default(),
         );
@@ -371,6 +495,9 @@ mod tests {
                 count: 2,
                 ignore: vec![],
                 ntp_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
+                refresh_interval: None,
+                address_family: None,
+                addresses: vec![],
             },
             SourceConfig::default(),
         );
@@ -381,4 +508,205 @@ mod tests {
         assert_eq!(res, TryRecvError::Empty);
         assert!(!pool.is_complete());
     }
+
+    fn pool_with_addresses(addresses: &[SocketAddr], count: usize) -> PoolSpawner {
+        PoolSpawner::new(
+            PoolSourceConfig {
+                addr: NormalizedAddress::with_hardcoded_dns("example.com", 123, addresses.to_vec())
+                    .into(),
+                count,
+                ignore: vec!["127.0.0.9".parse().unwrap()],
+                ntp_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
+                refresh_interval: NonZeroU64::new(3600),
+                address_family: None,
+                addresses: vec![],
+            },
+            SourceConfig::default(),
+        )
+    }
+
+    #[test]
+    fn update_members_diffs_resolution() {
+        let addr = |s: &str| -> SocketAddr { s.parse().unwrap() };
+        let mut pool = pool_with_addresses(&[], 2);
+        let (id1, id2) = (ClockId::new(), ClockId::new());
+        pool.current_sources = vec![
+            PoolSource {
+                id: id1,
+                addr: addr("127.0.0.1:123"),
+            },
+            PoolSource {
+                id: id2,
+                addr: addr("127.0.0.2:123"),
+            },
+        ];
+
+        // Unchanged resolution retires nobody
+        let vanished = pool.update_members(vec![addr("127.0.0.1:123"), addr("127.0.0.2:123")]);
+        assert!(vanished.is_empty());
+        assert!(pool.known_ips.is_empty());
+
+        // Newly resolved addresses become available, ignored ones do not
+        let vanished = pool.update_members(vec![
+            addr("127.0.0.2:123"),
+            addr("127.0.0.3:123"),
+            addr("127.0.0.9:123"),
+        ]);
+        assert_eq!(vanished, vec![id1]);
+        assert_eq!(pool.known_ips, vec![addr("127.0.0.3:123")]);
+
+        // Stale candidates are dropped once they disappear from DNS
+        let vanished = pool.update_members(vec![addr("127.0.0.4:123")]);
+        assert_eq!(vanished, vec![id1, id2]);
+        assert_eq!(pool.known_ips, vec![addr("127.0.0.4:123")]);
+
+        // An empty resolution reports every member, removal is left to the system
+        let vanished = pool.update_members(vec![]);
+        assert_eq!(vanished, vec![id1, id2]);
+        assert!(pool.known_ips.is_empty());
+    }
+
+    #[tokio::test]
+    async fn refresh_replaces_vanished_sources() {
+        let old: [SocketAddr; 2] = [
+            "127.0.0.1:123".parse().unwrap(),
+            "127.0.0.2:123".parse().unwrap(),
+        ];
+        let mut pool = pool_with_addresses(&old, 2);
+        assert_eq!(pool.refresh_interval(), Some(Duration::from_secs(3600)));
+        let (action_tx, mut action_rx) = mpsc::channel(MESSAGE_BUFFER_SIZE);
+
+        pool.try_spawn(&action_tx).await.unwrap();
+        let first = get_ntp_create_params(action_rx.try_recv().unwrap()).unwrap();
+        let second = get_ntp_create_params(action_rx.try_recv().unwrap()).unwrap();
+        assert!(pool.is_complete());
+
+        // Only the first source is still listed
+        let new: SocketAddr = "127.0.0.3:123".parse().unwrap();
+        pool.config.addr =
+            NormalizedAddress::with_hardcoded_dns("example.com", 123, vec![first.addr, new]).into();
+        pool.refresh(&action_tx).await.unwrap();
+        let res = action_rx.try_recv().unwrap();
+        assert_eq!(res.id, pool.get_id());
+        let SpawnAction::RemoveIfUnreachable(removed) = res.action else {
+            panic!("did not receive removal action!");
+        };
+        assert_eq!(removed, second.id);
+        assert_eq!(action_rx.try_recv().unwrap_err(), TryRecvError::Empty);
+
+        // Nothing is spawned until the system actually removed the source
+        assert!(pool.is_complete());
+        pool.handle_source_removed(SourceRemovedEvent {
+            id: removed,
+            reason: SourceRemovalReason::Unreachable,
+        })
+        .await
+        .unwrap();
+
+        assert!(!pool.is_complete());
+        pool.try_spawn(&action_tx).await.unwrap();
+        let params = get_ntp_create_params(action_rx.try_recv().unwrap()).unwrap();
+        assert_eq!(params.addr, new);
+        assert!(pool.is_complete());
+    }
+
+    fn pool_with_static_members(addresses: &[SocketAddr], count: usize) -> PoolSpawner {
+        PoolSpawner::new(
+            PoolSourceConfig {
+                // Resolving the pool address would never yield a usable member
+                addr: NormalizedAddress::with_hardcoded_dns("example.com", 123, vec![]).into(),
+                count,
+                ignore: vec![],
+                ntp_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
+                refresh_interval: None,
+                address_family: None,
+                addresses: addresses.to_vec(),
+            },
+            SourceConfig::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn static_members_respect_count() {
+        let addresses: Vec<SocketAddr> = ["127.0.0.1:123", "127.0.0.2:123", "127.0.0.3:123"]
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect();
+        let mut pool = pool_with_static_members(&addresses, 2);
+        let (action_tx, mut action_rx) = mpsc::channel(MESSAGE_BUFFER_SIZE);
+
+        assert!(!pool.is_complete());
+        pool.try_spawn(&action_tx).await.unwrap();
+        let first = get_ntp_create_params(action_rx.try_recv().unwrap()).unwrap();
+        let second = get_ntp_create_params(action_rx.try_recv().unwrap()).unwrap();
+        assert_eq!(first.addr, addresses[0]);
+        assert_eq!(second.addr, addresses[1]);
+        assert_eq!(action_rx.try_recv().unwrap_err(), TryRecvError::Empty);
+        assert!(pool.is_complete());
+
+        // A list shorter than count spawns every member once
+        let mut pool = pool_with_static_members(&addresses[..1], 2);
+        pool.try_spawn(&action_tx).await.unwrap();
+        let only = get_ntp_create_params(action_rx.try_recv().unwrap()).unwrap();
+        assert_eq!(only.addr, addresses[0]);
+        assert_eq!(action_rx.try_recv().unwrap_err(), TryRecvError::Empty);
+        assert!(!pool.is_complete());
+    }
+
+    #[tokio::test]
+    async fn static_members_rotate_on_replacement() {
+        let addresses: Vec<SocketAddr> = ["127.0.0.1:123", "127.0.0.2:123", "127.0.0.3:123"]
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect();
+        let mut pool = pool_with_static_members(&addresses, 2);
+        let (action_tx, mut action_rx) = mpsc::channel(MESSAGE_BUFFER_SIZE);
+
+        pool.try_spawn(&action_tx).await.unwrap();
+        let first = get_ntp_create_params(action_rx.try_recv().unwrap()).unwrap();
+        let _second = get_ntp_create_params(action_rx.try_recv().unwrap()).unwrap();
+
+        pool.handle_source_removed(SourceRemovedEvent {
+            id: first.id,
+            reason: SourceRemovalReason::Unreachable,
+        })
+        .await
+        .unwrap();
+        pool.try_spawn(&action_tx).await.unwrap();
+        let third = get_ntp_create_params(action_rx.try_recv().unwrap()).unwrap();
+        assert_eq!(third.addr, addresses[2]);
+
+        // Replacements wrap around to the start of the list
+        pool.handle_source_removed(SourceRemovedEvent {
+            id: third.id,
+            reason: SourceRemovalReason::Unreachable,
+        })
+        .await
+        .unwrap();
+        pool.try_spawn(&action_tx).await.unwrap();
+        let fourth = get_ntp_create_params(action_rx.try_recv().unwrap()).unwrap();
+        assert_eq!(fourth.addr, addresses[0]);
+        assert!(pool.is_complete());
+
+        // Refreshing never touches a static member list
+        pool.refresh(&action_tx).await.unwrap();
+        assert_eq!(action_rx.try_recv().unwrap_err(), TryRecvError::Empty);
+    }
+
+    #[tokio::test]
+    async fn static_members_respect_address_family() {
+        let addresses: Vec<SocketAddr> = ["[::1]:123", "127.0.0.1:123"]
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect();
+        let mut pool = pool_with_static_members(&addresses, 2);
+        pool.config.address_family = Some(AddressFamily::Ipv4);
+        let (action_tx, mut action_rx) = mpsc::channel(MESSAGE_BUFFER_SIZE);
+
+        pool.try_spawn(&action_tx).await.unwrap();
+        let only = get_ntp_create_params(action_rx.try_recv().unwrap()).unwrap();
+        assert_eq!(only.addr, addresses[1]);
+        assert_eq!(action_rx.try_recv().unwrap_err(), TryRecvError::Empty);
+        assert!(!pool.is_complete());
+    }
 }
@@ -100,6 +100,7 @@ mod tests {
                 path: socket_path.clone(),
                 precision,
                 accuracy,
+                group: None,
             },
             SourceConfig::default(),
         );
@@ -111,7 +112,9 @@ mod tests {
         let res = action_rx.try_recv().unwrap();
         assert_eq!(res.id, spawner_id);
 
-        let SpawnAction::Create(create_params) = res.action;
+        let SpawnAction::Create(create_params) = res.action else {
+            panic!("did not receive create action!");
+        };
         assert_eq!(create_params.get_addr(), socket_path.display().to_string());
 
         let SourceCreateParameters::Sock(params) = create_params else {
@@ -56,7 +56,11 @@ impl StandardSpawner {
         if let (false, Some(addr)) = (force_resolve, self.resolved) {
             Some(addr)
         } else {
-            let address = resolve_single_ntp_server(self.config.address.clone()).await?;
+            let address = resolve_single_ntp_server(
+                self.config.address.clone(),
+                self.config.address_family.unwrap_or_default(),
+            )
+            .await?;
             self.resolved = Some(address);
             self.resolved
         }
@@ -129,7 +133,7 @@ mod tests {
     use tokio::sync::mpsc::{self, error::TryRecvError};
 
     use crate::daemon::{
-        config::{NormalizedAddress, StandardSource},
+        config::{AddressFamily, NormalizedAddress, StandardSource},
         spawn::{
             SourceRemovalReason, SourceRemovedEvent, SpawnAction, Spawner,
             standard::StandardSpawner, tests::get_ntp_create_params,
@@ -148,6 +152,8 @@ mod tests {
                 )
                 .into(),
                 ntp_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
+                prefer: false,
+                address_family: None,
             },
             SourceConfig::default(),
         );
@@ -158,7 +164,9 @@ mod tests {
         spawner.try_spawn(&action_tx).await.unwrap();
         let res = action_rx.try_recv().unwrap();
         assert_eq!(res.id, spawner_id);
-        let SpawnAction::Create(create_params) = &res.action;
+        let SpawnAction::Create(create_params) = &res.action else {
+            panic!("did not receive create action!");
+        };
         assert_eq!(create_params.get_addr(), "127.0.0.1:123");
         let params = get_ntp_create_params(res).unwrap();
         assert_eq!(params.addr.to_string(), "127.0.0.1:123");
@@ -171,6 +179,54 @@ mod tests {
         assert!(spawner.is_complete());
     }
 
+    #[tokio::test]
+    async fn respects_address_family() {
+        let mut spawner = StandardSpawner::new(
+            StandardSource {
+                address: NormalizedAddress::with_hardcoded_dns(
+                    "example.com",
+                    123,
+                    vec![
+                        "[::1]:123".parse().unwrap(),
+                        "127.0.0.1:123".parse().unwrap(),
+                    ],
+                )
+                .into(),
+                ntp_version: ProtocolVersion::V4,
+                prefer: false,
+                address_family: Some(AddressFamily::Ipv4),
+            },
+            SourceConfig::default(),
+        );
+        let (action_tx, mut action_rx) = mpsc::channel(MESSAGE_BUFFER_SIZE);
+
+        spawner.try_spawn(&action_tx).await.unwrap();
+        let res = action_rx.try_recv().unwrap();
+        let params = get_ntp_create_params(res).unwrap();
+        assert_eq!(params.addr.to_string(), "127.0.0.1:123");
+        assert!(spawner.is_complete());
+
+        // Without addresses of the requested family, resolution fails
+        let mut spawner = StandardSpawner::new(
+            StandardSource {
+                address: NormalizedAddress::with_hardcoded_dns(
+                    "example.com",
+                    123,
+                    vec!["127.0.0.1:123".parse().unwrap()],
+                )
+                .into(),
+                ntp_version: ProtocolVersion::V4,
+                prefer: false,
+                address_family: Some(AddressFamily::Ipv6),
+            },
+            SourceConfig::default(),
+        );
+
+        spawner.try_spawn(&action_tx).await.unwrap();
+        assert!(action_rx.try_recv().is_err());
+        assert!(!spawner.is_complete());
+    }
+
     #[tokio::test]
     async fn respects_ntp_version_force_v5() {
         let mut spawner = StandardSpawner::new(
@@ -182,6 +238,8 @@ mod tests {
                 )
                 .into(),
                 ntp_version: ProtocolVersion::V5,
+                prefer: false,
+                address_family: None,
             },
             SourceConfig::default(),
         );
@@ -211,6 +269,8 @@ mod tests {
                 )
                 .into(),
                 ntp_version: ProtocolVersion::V4,
+                prefer: false,
+                address_family: None,
             },
             SourceConfig::default(),
         );
@@ -240,6 +300,8 @@ mod tests {
                 )
                 .into(),
                 ntp_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
+                prefer: false,
+                address_family: None,
             },
             SourceConfig::default(),
         );
@@ -281,6 +343,8 @@ mod tests {
                 )
                 .into(),
                 ntp_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
+                prefer: false,
+                address_family: None,
             },
             SourceConfig::default(),
         );
@@ -334,6 +398,8 @@ mod tests {
                 address: NormalizedAddress::with_hardcoded_dns("does.not.resolve", 123, vec![])
                     .into(),
                 ntp_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
+                prefer: false,
+                address_family: None,
             },
             SourceConfig::default(),
         );
@@ -3,6 +3,7 @@ use crate::daemon::config::CsptpConfig;
 #[cfg(feature = "pps")]
 use crate::daemon::pps_source::PpsSourceTask;
 use crate::daemon::{
+    broadcast_source::BroadcastSourceTask,
     sock_source::SockSourceTask,
     spawn::{SourceCreateParameters, spawner_task},
 };
@@ -10,12 +11,19 @@ use crate::daemon::{
 use super::spawn::nts_pool::NtsPoolSpawner;
 use super::{
     clock::NtpClockWrapper,
-    config::{ClockConfig, NtpSourceConfig, ServerConfig, TimestampMode},
+    config::{
+        ClockConfig, DnsConfig, NtpSourceConfig, ServerConfig, StartupSourceRetryConfig,
+        TimestampMode,
+    },
+    control::{DisabledSources, DrainSender, ForceSyncSender, SourceRequestSenders},
+    dns::{self, DnsState},
     ntp_source::{MsgForSystem, SourceChannels, SourceTask},
+    peerstats::{PeerStats, PeerStatsWriter},
     server::{ServerStats, ServerTask},
     spawn::{
         SourceRemovalReason, SpawnAction, SpawnEvent, Spawner, SpawnerId, SystemEvent,
-        nts::NtsSpawner, pool::PoolSpawner, sock::SockSpawner, standard::StandardSpawner,
+        broadcast::BroadcastSpawner, nts::NtsSpawner, pool::PoolSpawner, sock::SockSpawner,
+        standard::StandardSpawner,
     },
 };
 
@@ -25,42 +33,72 @@ use super::spawn::pps::PpsSpawner;
 #[cfg(target_os = "linux")]
 use std::net::{Ipv4Addr, Ipv6Addr};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
     net::IpAddr,
     sync::{Arc, Mutex, RwLock},
 };
 
 use ntp_proto::{
-    ClockId, KeySet, NtpClock, NtpManager, ObservableSourceState, OneWaySource, SourceConfig,
+    ClockId, ForceSyncOutcome, KeySet, NtpClock, NtpDuration, NtpLeapIndicator, NtpManager,
+    NtpTimestamp, ObservableSourceState, ObservableSourceTimedata, OneWaySource, SourceConfig,
     SourceType, SynchronizationConfig, SystemSnapshot, TimeSyncController,
 };
+use serde::{Deserialize, Serialize};
 use timestamped_socket::interface::InterfaceName;
-use tokio::{sync::mpsc, task::JoinHandle};
-use tracing::{debug, info};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::{AbortHandle, JoinHandle},
+};
+use tracing::{debug, error, info};
 
 pub const NETWORK_WAIT_PERIOD: std::time::Duration = std::time::Duration::from_secs(1);
 
 pub const MESSAGE_BUFFER_SIZE: usize = 32;
 
+/// Number of consecutive unanswered polls after which a spawner may have a
+/// source removed as unreachable.
+const REMOVAL_UNANSWERED_POLLS: u32 = 4;
+
+/// Tracing target for structured events about source state changes. These
+/// events are meant to be consumed by external automation, and can be enabled
+/// independently of the regular log output.
+pub const SOURCE_EVENTS_TARGET: &str = "ntpd_rs::events";
+
 pub struct DaemonChannels {
     pub source_snapshots: Arc<std::sync::RwLock<HashMap<ClockId, ObservableSourceState>>>,
     pub server_data_receiver: tokio::sync::watch::Receiver<Vec<ServerData>>,
     pub system_snapshot_receiver: tokio::sync::watch::Receiver<SystemSnapshot>,
+    pub clock_history: Arc<RwLock<ClockHistory>>,
+    pub source_histograms: Arc<RwLock<SourceHistograms>>,
+    pub source_requests: SourceRequestSenders,
+    pub disabled_sources: DisabledSources,
+    pub force_sync: ForceSyncSender,
+    pub config_reload: mpsc::Sender<ConfigReload>,
+    pub drain: DrainSender,
+}
+
+/// Sources and servers from a reloaded configuration, replacing the ones the
+/// daemon is currently running.
+#[derive(Debug)]
+pub struct ConfigReload {
+    pub sources: Vec<NtpSourceConfig>,
+    pub servers: Vec<ServerConfig>,
 }
 
 /// Spawn the NTP daemon
-#[cfg_attr(
-    target_os = "linux",
-    expect(
-        clippy::too_many_arguments,
-        reason = "FIXME: System needs a larger refactor to properly receive configuration"
-    )
+#[expect(
+    clippy::too_many_arguments,
+    reason = "FIXME: System needs a larger refactor to properly receive configuration"
 )]
 pub async fn spawn<Controller: TimeSyncController<Clock = NtpClockWrapper>>(
     synchronization_config: SynchronizationConfig,
     algorithm_config: Controller::AlgorithmConfig,
     source_defaults_config: SourceConfig,
+    startup_source_retry: StartupSourceRetryConfig,
+    dns_config: DnsConfig,
     clock_config: ClockConfig,
+    clock_history_length: usize,
+    source_histograms: SourceHistograms,
     source_configs: &[NtpSourceConfig],
     server_configs: &[ServerConfig],
     #[cfg(target_os = "linux")] csptp_server_configs: &[crate::daemon::config::CsptpServerConfig],
@@ -75,61 +113,20 @@ pub async fn spawn<Controller: TimeSyncController<Clock = NtpClockWrapper>>(
         clock_config.timestamp_mode,
         synchronization_config,
         algorithm_config,
+        source_defaults_config,
+        startup_source_retry,
+        dns_config,
+        clock_history_length,
+        source_histograms,
         &keyset,
         ip_list,
         !source_configs.is_empty(),
         #[cfg(target_os = "linux")]
         csptp_config,
-    );
+    )?;
 
     for source_config in source_configs {
-        match source_config {
-            NtpSourceConfig::Standard(cfg) => {
-                system.add_spawner(StandardSpawner::new(
-                    cfg.first.clone(),
-                    cfg.second.clone().with_defaults(source_defaults_config),
-                ));
-            }
-            NtpSourceConfig::Nts(cfg) => {
-                NtsSpawner::new(
-                    cfg.first.clone(),
-                    cfg.second.clone().with_defaults(source_defaults_config),
-                )
-                .map(|spawner| system.add_spawner(spawner))
-                .map_err(|e| {
-                    tracing::error!("Could not spawn source: {}", e);
-                    std::io::Error::other(e)
-                })?;
-            }
-            NtpSourceConfig::Pool(cfg) => {
-                system.add_spawner(PoolSpawner::new(
-                    cfg.first.clone(),
-                    cfg.second.clone().with_defaults(source_defaults_config),
-                ));
-            }
-            NtpSourceConfig::NtsPool(cfg) => {
-                NtsPoolSpawner::new(
-                    cfg.first.clone(),
-                    cfg.second.clone().with_defaults(source_defaults_config),
-                )
-                .map(|spawner| system.add_spawner(spawner))
-                .map_err(|e| {
-                    tracing::error!("Could not spawn source: {}", e);
-                    std::io::Error::other(e)
-                })?;
-            }
-            NtpSourceConfig::Sock(cfg) => {
-                system.add_spawner(SockSpawner::new(cfg.clone(), source_defaults_config));
-            }
-            #[cfg(feature = "pps")]
-            NtpSourceConfig::Pps(cfg) => {
-                system.add_spawner(PpsSpawner::new(cfg.clone(), source_defaults_config));
-            }
-            #[cfg(target_os = "linux")]
-            NtpSourceConfig::Csptp(cfg) => {
-                system.add_spawner(crate::daemon::spawn::csptp::CsptpSpawner::new(cfg.clone()));
-            }
-        }
+        system.add_source_config(source_config)?;
     }
 
     for server_config in server_configs {
@@ -150,6 +147,8 @@ pub async fn spawn<Controller: TimeSyncController<Clock = NtpClockWrapper>>(
 struct SystemSpawnerData {
     id: SpawnerId,
     notify_tx: mpsc::Sender<SystemEvent>,
+    config: NtpSourceConfig,
+    task: AbortHandle,
 }
 
 struct SystemTask<C: NtpClock, Controller: TimeSyncController<Clock = C>> {
@@ -167,6 +166,10 @@ struct SystemTask<C: NtpClock, Controller: TimeSyncController<Clock = C>> {
 
     system_snapshot_sender: tokio::sync::watch::Sender<SystemSnapshot>,
     source_snapshots: Arc<std::sync::RwLock<HashMap<ClockId, ObservableSourceState>>>,
+    clock_history: Arc<RwLock<ClockHistory>>,
+    source_histograms: Arc<RwLock<SourceHistograms>>,
+    source_requests: SourceRequestSenders,
+    disabled_sources: DisabledSources,
     server_data_sender: tokio::sync::watch::Sender<Vec<ServerData>>,
     keyset: tokio::sync::watch::Receiver<Arc<KeySet>>,
     ip_list: tokio::sync::watch::Receiver<Arc<[IpAddr]>>,
@@ -175,10 +178,23 @@ struct SystemTask<C: NtpClock, Controller: TimeSyncController<Clock = C>> {
     msg_for_system_tx: mpsc::Sender<MsgForSystem>,
     spawn_tx: mpsc::Sender<SpawnEvent>,
     spawn_rx: mpsc::Receiver<SpawnEvent>,
+    config_reload_rx: mpsc::Receiver<ConfigReload>,
+    force_sync_rx: mpsc::Receiver<oneshot::Sender<ForceSyncOutcome>>,
+    drain: tokio::sync::watch::Receiver<Option<std::time::Duration>>,
 
     sources: Arc<Mutex<HashMap<ClockId, SourceState>>>,
+    source_tasks: HashMap<ClockId, AbortHandle>,
+    // Disabled sources that were removed, by spawner and address, so they are
+    // disabled again when their spawner recreates them
+    removed_disabled_sources: HashSet<(SpawnerId, String)>,
+    // Tasks of the servers, in the same order as `servers`
     servers: Vec<ServerData>,
+    server_tasks: Vec<AbortHandle>,
     spawners: Vec<SystemSpawnerData>,
+    source_defaults: SourceConfig,
+    startup_source_retry: StartupSourceRetryConfig,
+    // Lookup settings and cache, shared by the spawner tasks
+    dns: Arc<DnsState>,
 
     clock: C,
 
@@ -198,40 +214,60 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
         timestamp_mode: TimestampMode,
         synchronization_config: SynchronizationConfig,
         algorithm_config: Controller::AlgorithmConfig,
+        source_defaults: SourceConfig,
+        startup_source_retry: StartupSourceRetryConfig,
+        dns_config: DnsConfig,
+        clock_history_length: usize,
+        source_histograms: SourceHistograms,
         keyset: &tokio::sync::watch::Receiver<Arc<KeySet>>,
         ip_list: tokio::sync::watch::Receiver<Arc<[IpAddr]>>,
         have_sources: bool,
         #[cfg(target_os = "linux")] csptp_config: CsptpConfig,
-    ) -> (Self, DaemonChannels) {
-        let Ok(controller) =
-            Controller::new(clock.clone(), synchronization_config, algorithm_config)
-        else {
-            tracing::error!("Could not create clock controller");
-            std::process::exit(70);
-        };
+    ) -> std::io::Result<(Self, DaemonChannels)> {
+        let controller =
+            match Controller::new(clock.clone(), synchronization_config, algorithm_config) {
+                Ok(controller) => controller,
+                Err(e) => {
+                    tracing::error!("Could not create clock controller: {}", e);
+                    return Err(std::io::Error::other(e));
+                }
+            };
         let ntp_manager = NtpManager::new(synchronization_config, ip_list.borrow().clone());
 
         if have_sources && let Err(e) = controller.take_control() {
             tracing::error!("Could not control clock: {}", e);
-            std::process::exit(70);
+            return Err(std::io::Error::other(e));
         }
 
+        apply_tai_offset(&clock, synchronization_config.tai_offset);
+
         let system_snapshot = SystemSnapshot {
             time_snapshot: controller.synchronization_state().0,
             ntp_snapshot: ntp_manager.observe(),
+            clock_estimated_error: None,
+            clock_maximum_error: None,
+            tai_offset: None,
+            generation: 0,
         };
 
         // Create communication channels
         let (system_snapshot_sender, system_snapshot_receiver) =
             tokio::sync::watch::channel(system_snapshot);
         let source_snapshots = Arc::new(RwLock::new(HashMap::new()));
+        let clock_history = Arc::new(RwLock::new(ClockHistory::new(clock_history_length)));
+        let source_histograms = Arc::new(RwLock::new(source_histograms));
+        let source_requests = SourceRequestSenders::default();
+        let disabled_sources = DisabledSources::default();
         let (server_data_sender, server_data_receiver) = tokio::sync::watch::channel(vec![]);
         let (msg_for_system_sender, msg_for_system_receiver) =
             tokio::sync::mpsc::channel(MESSAGE_BUFFER_SIZE);
         let (spawn_tx, spawn_rx) = mpsc::channel(MESSAGE_BUFFER_SIZE);
+        let (config_reload_tx, config_reload_rx) = mpsc::channel(1);
+        let (force_sync_tx, force_sync_rx) = mpsc::channel(1);
+        let (drain_tx, drain_rx) = tokio::sync::watch::channel(None);
 
         // Build System and its channels
-        (
+        Ok((
             SystemTask {
                 controller: Arc::new(controller),
                 ntp_manager: Arc::new(ntp_manager),
@@ -259,6 +295,10 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
 
                 system_snapshot_sender,
                 source_snapshots: source_snapshots.clone(),
+                clock_history: clock_history.clone(),
+                source_histograms: source_histograms.clone(),
+                source_requests: source_requests.clone(),
+                disabled_sources: disabled_sources.clone(),
                 server_data_sender,
                 keyset: keyset.clone(),
                 ip_list,
@@ -267,10 +307,19 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
                 msg_for_system_tx: msg_for_system_sender,
                 spawn_rx,
                 spawn_tx,
+                config_reload_rx,
+                force_sync_rx,
+                drain: drain_rx,
 
                 sources: Arc::default(),
+                source_tasks: HashMap::new(),
+                removed_disabled_sources: HashSet::new(),
                 servers: vec![],
+                server_tasks: vec![],
                 spawners: vec![],
+                source_defaults,
+                startup_source_retry,
+                dns: DnsState::new(dns_config),
                 clock,
                 timestamp_mode,
                 interface,
@@ -279,19 +328,127 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
                 source_snapshots,
                 server_data_receiver,
                 system_snapshot_receiver,
+                clock_history,
+                source_histograms,
+                source_requests,
+                disabled_sources,
+                force_sync: force_sync_tx,
+                config_reload: config_reload_tx,
+                drain: drain_tx,
             },
-        )
+        ))
     }
 
-    fn add_spawner(&mut self, spawner: impl Spawner + Send + Sync + 'static) -> SpawnerId {
+    fn add_source_config(&mut self, config: &NtpSourceConfig) -> std::io::Result<()> {
+        let source_defaults = self.source_defaults;
+        match config {
+            NtpSourceConfig::Standard(cfg) => {
+                self.add_spawner(
+                    StandardSpawner::new(
+                        cfg.first.clone(),
+                        SourceConfig {
+                            prefer: cfg.first.prefer,
+                            ..cfg.second.clone().with_defaults(source_defaults)
+                        },
+                    ),
+                    config.clone(),
+                );
+            }
+            NtpSourceConfig::Nts(cfg) => {
+                let spawner = NtsSpawner::new(
+                    cfg.first.clone(),
+                    SourceConfig {
+                        prefer: cfg.first.prefer,
+                        require_authenticated: cfg.first.require_authenticated,
+                        ..cfg.second.clone().with_defaults(source_defaults)
+                    },
+                )
+                .map_err(|e| {
+                    tracing::error!("Could not spawn source: {}", e);
+                    std::io::Error::other(e)
+                })?;
+                self.add_spawner(spawner, config.clone());
+            }
+            NtpSourceConfig::Pool(cfg) => {
+                self.add_spawner(
+                    PoolSpawner::new(
+                        cfg.first.clone(),
+                        cfg.second.clone().with_defaults(source_defaults),
+                    ),
+                    config.clone(),
+                );
+            }
+            NtpSourceConfig::NtsPool(cfg) => {
+                let spawner = NtsPoolSpawner::new(
+                    cfg.first.clone(),
+                    cfg.second.clone().with_defaults(source_defaults),
+                )
+                .map_err(|e| {
+                    tracing::error!("Could not spawn source: {}", e);
+                    std::io::Error::other(e)
+                })?;
+                self.add_spawner(spawner, config.clone());
+            }
+            NtpSourceConfig::Broadcast(cfg) => {
+                self.add_spawner(
+                    BroadcastSpawner::new(
+                        cfg.first.clone(),
+                        cfg.second.clone().with_defaults(source_defaults),
+                    ),
+                    config.clone(),
+                );
+            }
+            NtpSourceConfig::Sock(cfg) => {
+                self.add_spawner(
+                    SockSpawner::new(
+                        cfg.clone(),
+                        SourceConfig {
+                            group: cfg.group,
+                            ..source_defaults
+                        },
+                    ),
+                    config.clone(),
+                );
+            }
+            #[cfg(feature = "pps")]
+            NtpSourceConfig::Pps(cfg) => {
+                self.add_spawner(
+                    PpsSpawner::new(cfg.clone(), source_defaults),
+                    config.clone(),
+                );
+            }
+            #[cfg(target_os = "linux")]
+            NtpSourceConfig::Csptp(cfg) => {
+                self.add_spawner(
+                    crate::daemon::spawn::csptp::CsptpSpawner::new(cfg.clone()),
+                    config.clone(),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_spawner(
+        &mut self,
+        spawner: impl Spawner + Send + Sync + 'static,
+        config: NtpSourceConfig,
+    ) -> SpawnerId {
         let (notify_tx, notify_rx) = mpsc::channel(MESSAGE_BUFFER_SIZE);
         let id = spawner.get_id();
-        let spawner_data = SystemSpawnerData { id, notify_tx };
-        debug!(id=?spawner_data.id, ty=spawner.get_description(), addr=spawner.get_addr_description(), "Running spawner");
-        self.spawners.push(spawner_data);
+        debug!(id=?id, ty=spawner.get_description(), addr=spawner.get_addr_description(), "Running spawner");
         let spawn_tx = self.spawn_tx.clone();
         // tokio::spawn(async move { spawner.run(spawn_tx, notify_rx).await });
-        tokio::spawn(spawner_task(spawner, spawn_tx, notify_rx));
+        let task = tokio::spawn(dns::scope(
+            self.dns.clone(),
+            spawner_task(spawner, spawn_tx, notify_rx, self.startup_source_retry),
+        ));
+        self.spawners.push(SystemSpawnerData {
+            id,
+            notify_tx,
+            config,
+            task: task.abort_handle(),
+        });
         id
     }
 
@@ -303,30 +460,62 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
         let controller = self.controller.clone();
         let ntp_manager = self.ntp_manager.clone();
         let sources = self.sources.clone();
+        let clock = self.clock.clone();
+        let source_snapshots = self.source_snapshots.clone();
+        let clock_history = self.clock_history.clone();
         let timer_loop = async move {
+            let mut last_clock_update = None;
+            let mut leap_indicator = NtpLeapIndicator::Unknown;
             loop {
                 // Scope is needed to keep the future send.
                 {
                     let (time_snapshot, used_sources) = controller.synchronization_state();
-                    let sources = sources.lock().unwrap();
+                    let outlier_sources = controller.outlier_sources();
+
+                    log_leap_change(leap_indicator, time_snapshot.leap_indicator);
+                    leap_indicator = time_snapshot.leap_indicator;
+
+                    // The base time of the root variance moves with every clock update
+                    let clock_update = time_snapshot.root_variance_base_time;
+                    if last_clock_update != Some(clock_update) {
+                        last_clock_update = Some(clock_update);
+                        record_clock_sample(
+                            &clock_history,
+                            &clock,
+                            &source_snapshots,
+                            clock_update,
+                            &used_sources,
+                        );
+                    }
+
+                    let (clock_estimated_error, clock_maximum_error) =
+                        read_error_estimate(&clock).unzip();
+                    let tai_offset = read_tai_offset(&clock);
+
+                    let mut sources = sources.lock().unwrap();
                     ntp_manager.update_time_snapshot(time_snapshot);
+                    update_selection_states(&mut sources, &used_sources, &outlier_sources);
 
-                    if let Some(used_sources) = used_sources
+                    let ntp_snapshot = used_sources
                         .into_iter()
                         .map(|id| sources.get(&id).map(|state| (id, state.stype)))
                         .collect::<Option<Vec<_>>>()
-                    {
-                        let ntp_snapshot =
-                            ntp_manager.update_used_sources(used_sources.into_iter());
-                        sender
-                            .send(SystemSnapshot {
-                                time_snapshot,
-                                ntp_snapshot,
-                            })
-                            .ok();
-                    } else {
-                        sender.send_modify(|v| v.time_snapshot = time_snapshot);
-                    }
+                        .map(|used_sources| {
+                            ntp_manager.update_used_sources(used_sources.into_iter())
+                        });
+
+                    // Only notify watchers when something substantive changed,
+                    // the clock error readbacks are updated silently.
+                    sender.send_if_modified(|current| {
+                        current.update(SystemSnapshot {
+                            time_snapshot,
+                            ntp_snapshot: ntp_snapshot.unwrap_or(current.ntp_snapshot),
+                            clock_estimated_error,
+                            clock_maximum_error,
+                            tai_offset,
+                            generation: current.generation,
+                        })
+                    });
                 }
 
                 tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
@@ -362,9 +551,20 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
                             }
                         }
                     }
+                    Some(reload) = self.config_reload_rx.recv() => {
+                        self.reload(reload);
+                    }
+                    Some(reply) = self.force_sync_rx.recv() => {
+                        self.controller.force_sync(reply);
+                    }
                     _ = self.ip_list.changed(), if self.ip_list.has_changed().is_ok() => {
                         ntp_manager.update_ip_list(self.ip_list.borrow_and_update().clone());
                     }
+                    _ = self.drain.changed(), if self.drain.has_changed().is_ok() => {
+                        if self.drain.borrow_and_update().is_some() {
+                            ntp_manager.set_draining(true);
+                        }
+                    }
                 }
             }
 
@@ -397,7 +597,14 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
 
     async fn handle_source_network_issue(&mut self, index: ClockId) -> std::io::Result<()> {
         // Restart the source reusing its configuration.
-        let state = self.sources.lock().unwrap().remove(&index).unwrap();
+        // The source may already be gone when its spawner was retired
+        let Some(state) = self.sources.lock().unwrap().remove(&index) else {
+            return Ok(());
+        };
+        self.source_requests.write().unwrap().remove(&index);
+        self.remember_disabled_source(index, &state);
+        self.source_histograms.write().unwrap().remove(index);
+        self.source_tasks.remove(&index);
         let spawner_id = state.spawner_id;
         let source_id = state.source_id;
         let opt_spawner = self.spawners.iter().find(|s| s.id == spawner_id);
@@ -417,7 +624,15 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
 
     async fn handle_source_unreachable(&mut self, index: ClockId) -> std::io::Result<()> {
         // Restart the source reusing its configuration.
-        let state = self.sources.lock().unwrap().remove(&index).unwrap();
+        // The source may already be gone when its spawner was retired
+        let Some(state) = self.sources.lock().unwrap().remove(&index) else {
+            return Ok(());
+        };
+        self.source_requests.write().unwrap().remove(&index);
+        self.remember_disabled_source(index, &state);
+        self.source_histograms.write().unwrap().remove(index);
+        self.source_tasks.remove(&index);
+        emit_source_event(&state, SourceSelectionState::Unreachable);
         let spawner_id = state.spawner_id;
         let source_id = state.source_id;
         let opt_spawner = self.spawners.iter().find(|s| s.id == spawner_id);
@@ -437,7 +652,14 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
 
     async fn handle_source_demobilize(&mut self, index: ClockId) -> Result<(), C::Error> {
         // Restart the source reusing its configuration.
-        let state = self.sources.lock().unwrap().remove(&index).unwrap();
+        // The source may already be gone when its spawner was retired
+        let Some(state) = self.sources.lock().unwrap().remove(&index) else {
+            return Ok(());
+        };
+        self.source_requests.write().unwrap().remove(&index);
+        self.remember_disabled_source(index, &state);
+        self.source_histograms.write().unwrap().remove(index);
+        self.source_tasks.remove(&index);
         let spawner_id = state.spawner_id;
         let source_id = state.source_id;
         let opt_spawner = self.spawners.iter().find(|s| s.id == spawner_id);
@@ -470,8 +692,12 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
             SourceState {
                 source_id,
                 spawner_id,
+                addr: params.get_addr(),
+                selection: SourceSelectionState::Candidate,
                 stype: match &params {
-                    SourceCreateParameters::Ntp(_) => SourceType::Ntp,
+                    SourceCreateParameters::Ntp(_) | SourceCreateParameters::Broadcast(_) => {
+                        SourceType::Ntp
+                    }
                     SourceCreateParameters::Sock(_) => SourceType::Sock,
                     #[cfg(feature = "pps")]
                     SourceCreateParameters::Pps(_) => SourceType::Pps,
@@ -481,10 +707,10 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
             },
         );
 
-        match params {
+        let task = match params {
             SourceCreateParameters::Ntp(ref mut params) => {
                 let source_controller = self.controller.add_source(source_id, params.config);
-                let (source, initial_actions) = self.ntp_manager.new_source(
+                let (mut source, initial_actions) = self.ntp_manager.new_source(
                     params.addr,
                     params.config,
                     params.protocol_version,
@@ -492,6 +718,19 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
                     params.nts.take(),
                     source_id,
                 );
+                if self
+                    .removed_disabled_sources
+                    .remove(&(spawner_id, params.addr.to_string()))
+                {
+                    info!(source_id=?source_id, "keeping recreated source disabled");
+                    source.set_disabled(true);
+                    self.disabled_sources.write().unwrap().insert(source_id);
+                }
+                let (source_request_tx, source_request_rx) = mpsc::channel(1);
+                self.source_requests
+                    .write()
+                    .unwrap()
+                    .insert(source_id, source_request_tx);
 
                 SourceTask::spawn(
                     source_id,
@@ -500,13 +739,45 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
                     self.interface,
                     self.clock.clone(),
                     self.timestamp_mode,
+                    params.config.dscp,
                     SourceChannels {
                         msg_for_system_sender: self.msg_for_system_tx.clone(),
                         source_snapshots: self.source_snapshots.clone(),
+                        source_histograms: self.source_histograms.clone(),
                     },
                     source,
                     initial_actions,
+                    source_request_rx,
+                )
+            }
+            SourceCreateParameters::Broadcast(ref params) => {
+                let source_controller = self.controller.add_one_way_source(
+                    source_id,
+                    params.config,
+                    params.precision,
+                    params.accuracy,
+                    None,
                 );
+                let client = self.ntp_manager.new_broadcast_client(
+                    params.addr,
+                    params.config,
+                    source_controller,
+                    source_id,
+                );
+                BroadcastSourceTask::spawn(
+                    source_id,
+                    params.addr,
+                    params.listen,
+                    params.group,
+                    self.clock.clone(),
+                    self.timestamp_mode,
+                    SourceChannels {
+                        msg_for_system_sender: self.msg_for_system_tx.clone(),
+                        source_snapshots: self.source_snapshots.clone(),
+                        source_histograms: self.source_histograms.clone(),
+                    },
+                    client,
+                )
             }
             SourceCreateParameters::Sock(ref params) => {
                 let source_controller = self.controller.add_one_way_source(
@@ -524,9 +795,10 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
                     SourceChannels {
                         msg_for_system_sender: self.msg_for_system_tx.clone(),
                         source_snapshots: self.source_snapshots.clone(),
+                        source_histograms: self.source_histograms.clone(),
                     },
                     source,
-                );
+                )
             }
             #[cfg(feature = "pps")]
             SourceCreateParameters::Pps(ref params) => {
@@ -544,9 +816,10 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
                     SourceChannels {
                         msg_for_system_sender: self.msg_for_system_tx.clone(),
                         source_snapshots: self.source_snapshots.clone(),
+                        source_histograms: self.source_histograms.clone(),
                     },
                     source,
-                );
+                )
             }
             #[cfg(target_os = "linux")]
             SourceCreateParameters::Csptp(ref params) => match params.addr {
@@ -574,7 +847,7 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
                         controller,
                         self.csptp_manager,
                         network,
-                    );
+                    )
                 }
                 IpAddr::V6(addr) => {
                     let network = if let Some(network) = self.ptp_networking_ipv6.as_ref() {
@@ -600,10 +873,11 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
                         controller,
                         self.csptp_manager,
                         network,
-                    );
+                    )
                 }
             },
-        }
+        };
+        self.source_tasks.insert(source_id, task.abort_handle());
 
         // Try and find a related spawner and notify that spawner.
         // This makes sure that the spawner that initially sent the create event
@@ -618,15 +892,68 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
         Ok(())
     }
 
+    /// Remember a removed source that was disabled through the control
+    /// socket, so it is disabled again when its spawner recreates it at the
+    /// same address.
+    fn remember_disabled_source(&mut self, index: ClockId, state: &SourceState) {
+        if self.disabled_sources.write().unwrap().remove(&index) {
+            self.removed_disabled_sources
+                .insert((state.spawner_id, state.addr.clone()));
+        }
+    }
+
     async fn handle_spawn_event(&mut self, event: SpawnEvent) -> Result<(), C::Error> {
+        if !self.spawners.iter().any(|s| s.id == event.id) {
+            debug!(spawner=?event.id, "ignoring spawn event of retired spawner");
+            return Ok(());
+        }
+
         match event.action {
             SpawnAction::Create(params) => {
                 self.create_source(event.id, params).await?;
             }
+            SpawnAction::RemoveIfUnreachable(source_id) => {
+                if let Err(e) = self.remove_source_if_unreachable(event.id, source_id).await {
+                    error!("Could not remove source: {}", e);
+                }
+            }
         }
         Ok(())
     }
 
+    async fn remove_source_if_unreachable(
+        &mut self,
+        spawner_id: SpawnerId,
+        source_id: ClockId,
+    ) -> std::io::Result<()> {
+        // Sources without a snapshot have not been polled yet, so keep them
+        let unreachable = self
+            .source_snapshots
+            .read()
+            .unwrap()
+            .get(&source_id)
+            .is_some_and(|snapshot| snapshot.unanswered_polls >= REMOVAL_UNANSWERED_POLLS);
+        let owned = self
+            .sources
+            .lock()
+            .unwrap()
+            .get(&source_id)
+            .is_some_and(|state| state.spawner_id == spawner_id);
+        if !unreachable || !owned {
+            return Ok(());
+        }
+
+        debug!(
+            ?source_id,
+            "removing unreachable source at request of spawner"
+        );
+        if let Some(task) = self.source_tasks.get(&source_id) {
+            task.abort();
+        }
+        self.source_snapshots.write().unwrap().remove(&source_id);
+        self.handle_source_unreachable(source_id).await
+    }
+
     fn add_server(&mut self, config: ServerConfig) {
         let stats = ServerStats::default();
         self.servers.push(ServerData {
@@ -638,16 +965,93 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
             self.clock.clone(),
             self.keyset.borrow().clone(),
         );
-        ServerTask::spawn(
+        let task = ServerTask::spawn(
             server,
             config,
             stats,
             self.keyset.clone(),
             NETWORK_WAIT_PERIOD,
         );
+        self.server_tasks.push(task.abort_handle());
         let _ = self.server_data_sender.send(self.servers.clone());
     }
 
+    /// Apply the sources and servers of a reloaded configuration. Sources and
+    /// servers whose configuration is unchanged keep running, the others are
+    /// stopped or started.
+    fn reload(&mut self, reload: ConfigReload) {
+        let mut added_sources = reload.sources;
+        let (kept, retired): (Vec<_>, Vec<_>) = std::mem::take(&mut self.spawners)
+            .into_iter()
+            .partition(
+                |spawner| match added_sources.iter().position(|c| *c == spawner.config) {
+                    Some(index) => {
+                        added_sources.remove(index);
+                        true
+                    }
+                    None => false,
+                },
+            );
+        self.spawners = kept;
+
+        for spawner in retired {
+            self.retire_spawner(&spawner);
+        }
+
+        for config in &added_sources {
+            // Errors are already logged, the other sources can still be added
+            let _ = self.add_source_config(config);
+        }
+
+        let mut added_servers = reload.servers;
+        let mut index = 0;
+        while index < self.servers.len() {
+            if let Some(position) = added_servers
+                .iter()
+                .position(|c| *c == self.servers[index].config)
+            {
+                added_servers.remove(position);
+                index += 1;
+            } else {
+                let server = self.servers.remove(index);
+                info!(listen=?server.config.listen, "stopping server");
+                self.server_tasks.remove(index).abort();
+            }
+        }
+
+        for config in added_servers {
+            self.add_server(config);
+        }
+        let _ = self.server_data_sender.send(self.servers.clone());
+    }
+
+    /// Stop a spawner along with all sources it created. The spawner task is
+    /// aborted, so it does not create any new sources afterwards.
+    fn retire_spawner(&mut self, spawner: &SystemSpawnerData) {
+        info!(id=?spawner.id, "retiring spawner");
+        spawner.task.abort();
+
+        let mut sources = self.sources.lock().unwrap();
+        sources.retain(|&source_id, state| {
+            if state.spawner_id != spawner.id {
+                return true;
+            }
+
+            info!(source_id=?source_id, addr=?state.addr, "removing source");
+            // Dropping the source also removes it from the controller
+            if let Some(task) = self.source_tasks.remove(&source_id) {
+                task.abort();
+            }
+            self.source_requests.write().unwrap().remove(&source_id);
+            self.disabled_sources.write().unwrap().remove(&source_id);
+            self.source_snapshots.write().unwrap().remove(&source_id);
+            self.source_histograms.write().unwrap().remove(source_id);
+            false
+        });
+        self.removed_disabled_sources
+            .retain(|(spawner_id, _)| *spawner_id != spawner.id);
+    }
+
     #[cfg(target_os = "linux")]
     fn add_csptp_server(&mut self, config: crate::daemon::config::CsptpServerConfig) {
         let network_v4 = if let Some(network) = &self.ptp_networking_ipv4 {
@@ -681,11 +1085,393 @@ impl<C: NtpClock + Sync, Controller: TimeSyncController<Clock = C>> SystemTask<C
     }
 }
 
+impl<C: NtpClock, Controller: TimeSyncController<Clock = C>> Drop for SystemTask<C, Controller> {
+    fn drop(&mut self) {
+        // Spawners, sources and servers should not outlive the system that
+        // manages them
+        for spawner in &self.spawners {
+            spawner.task.abort();
+        }
+        for task in self.source_tasks.values() {
+            task.abort();
+        }
+        for task in &self.server_tasks {
+            task.abort();
+        }
+    }
+}
+
 #[derive(Debug)]
 struct SourceState {
     spawner_id: SpawnerId,
     source_id: ClockId,
+    addr: String,
     stype: SourceType,
+    selection: SourceSelectionState,
+}
+
+/// State of a source as reported on the [`SOURCE_EVENTS_TARGET`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SourceSelectionState {
+    /// The source is used to steer the clock
+    Selected,
+    /// The source is available, but currently not used to steer the clock
+    Candidate,
+    /// The source disagrees with the other sources and is not used
+    Outlier,
+    /// The source could not be reached and will be removed
+    Unreachable,
+}
+
+impl std::fmt::Display for SourceSelectionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SourceSelectionState::Selected => "selected",
+            SourceSelectionState::Candidate => "candidate",
+            SourceSelectionState::Outlier => "outlier",
+            SourceSelectionState::Unreachable => "unreachable",
+        })
+    }
+}
+
+fn emit_source_event(source: &SourceState, state: SourceSelectionState) {
+    tracing::info!(
+        target: SOURCE_EVENTS_TARGET,
+        source_id = ?source.source_id,
+        addr = %source.addr,
+        state = %state,
+        "source state changed"
+    );
+}
+
+/// Record which sources are currently used or rejected by the controller,
+/// emitting an event for every source whose selection state changed. This runs
+/// on every clock update, so nothing is done while the events are disabled.
+/// Once enabled, changes are reported relative to the last reported state.
+fn update_selection_states(
+    sources: &mut HashMap<ClockId, SourceState>,
+    used_sources: &[ClockId],
+    outlier_sources: &[ClockId],
+) {
+    if !tracing::enabled!(target: SOURCE_EVENTS_TARGET, tracing::Level::INFO) {
+        return;
+    }
+
+    for source in sources.values_mut() {
+        let selection = if used_sources.contains(&source.source_id) {
+            SourceSelectionState::Selected
+        } else if outlier_sources.contains(&source.source_id) {
+            SourceSelectionState::Outlier
+        } else {
+            SourceSelectionState::Candidate
+        };
+
+        if source.selection != selection {
+            source.selection = selection;
+            emit_source_event(source, selection);
+        }
+    }
+}
+
+/// Read back the estimated and maximum error of the clock, if it keeps track
+/// of them.
+fn read_error_estimate<C: NtpClock>(clock: &C) -> Option<(NtpDuration, NtpDuration)> {
+    match clock.get_error_estimate() {
+        Ok(error_estimate) => error_estimate,
+        Err(e) => {
+            debug!("Could not read the error estimate of the clock: {e}");
+            None
+        }
+    }
+}
+
+/// Set the configured offset of TAI from UTC on the clock, if any. Failing to
+/// do so only affects applications using TAI, so synchronization continues.
+fn apply_tai_offset<C: NtpClock>(clock: &C, tai_offset: Option<i32>) {
+    let Some(tai_offset) = tai_offset else {
+        return;
+    };
+
+    match clock.set_tai_offset(tai_offset) {
+        Ok(()) => info!(tai_offset, "Set the TAI offset of the clock"),
+        Err(e) => error!("Could not set the TAI offset of the clock: {e}"),
+    }
+}
+
+/// Read back the offset of TAI from UTC, if the clock keeps one.
+fn read_tai_offset<C: NtpClock>(clock: &C) -> Option<i32> {
+    match clock.get_tai_offset() {
+        Ok(tai_offset) => tai_offset,
+        Err(e) => {
+            debug!("Could not read the TAI offset of the clock: {e}");
+            None
+        }
+    }
+}
+
+/// Warn ahead of a leap second announced by the selected sources, and note
+/// when the announcement ends.
+fn log_leap_change(previous: NtpLeapIndicator, current: NtpLeapIndicator) {
+    if previous == current {
+        return;
+    }
+
+    match current {
+        NtpLeapIndicator::Leap61 => {
+            tracing::warn!("Sources announce a leap second, one second will be inserted");
+        }
+        NtpLeapIndicator::Leap59 => {
+            tracing::warn!("Sources announce a leap second, one second will be deleted");
+        }
+        _ if matches!(
+            previous,
+            NtpLeapIndicator::Leap61 | NtpLeapIndicator::Leap59
+        ) =>
+        {
+            info!("Leap second is no longer pending");
+        }
+        _ => {}
+    }
+}
+
+/// State of the local clock at a clock update, kept for drift analysis
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ClockSample {
+    /// Time of the clock update
+    pub timestamp: NtpTimestamp,
+    /// Offset of the primary source at the time of the update
+    pub offset: NtpDuration,
+    /// Frequency of the local clock, as reported by the clock
+    pub frequency: f64,
+}
+
+/// Fixed-capacity history of the most recent clock samples, dropping the
+/// oldest sample once full.
+#[derive(Debug, Clone, Default)]
+pub struct ClockHistory {
+    samples: VecDeque<ClockSample>,
+    capacity: usize,
+}
+
+impl ClockHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, sample: ClockSample) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Samples from oldest to most recent
+    pub fn to_vec(&self) -> Vec<ClockSample> {
+        self.samples.iter().copied().collect()
+    }
+}
+
+fn record_clock_sample(
+    clock_history: &RwLock<ClockHistory>,
+    clock: &impl NtpClock,
+    source_snapshots: &RwLock<HashMap<ClockId, ObservableSourceState>>,
+    timestamp: NtpTimestamp,
+    used_sources: &[ClockId],
+) {
+    // Without a primary source there is no offset to record
+    let Some(offset) = used_sources.first().and_then(|id| {
+        source_snapshots
+            .read()
+            .unwrap()
+            .get(id)
+            .map(|state| state.timedata.offset)
+    }) else {
+        return;
+    };
+
+    match clock.get_frequency() {
+        Ok(frequency) => clock_history.write().unwrap().push(ClockSample {
+            timestamp,
+            offset,
+            frequency,
+        }),
+        Err(error) => debug!(?error, "Could not read clock frequency for history"),
+    }
+}
+
+/// Default upper bounds, in seconds, of the offset and jitter histograms
+pub const DEFAULT_HISTOGRAM_BUCKETS: &[f64] = &[
+    1e-6, 1e-5, 5e-5, 1e-4, 5e-4, 1e-3, 5e-3, 1e-2, 5e-2, 1e-1, 5e-1,
+];
+
+/// Distribution of observed values over a fixed set of buckets
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    /// Upper bounds of the buckets, in increasing order
+    pub bounds: Vec<f64>,
+    /// Number of values at or below each of the bounds
+    pub counts: Vec<u64>,
+    /// Total number of values, including those above the largest bound
+    pub count: u64,
+    /// Sum of all values
+    pub sum: f64,
+}
+
+impl Histogram {
+    pub fn new(bounds: Vec<f64>) -> Self {
+        Self {
+            counts: vec![0; bounds.len()],
+            bounds,
+            count: 0,
+            sum: 0.0,
+        }
+    }
+
+    pub fn record(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(&mut self.counts) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+/// Bucket bounds for the per-source histograms, in seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramBuckets {
+    pub offset: Vec<f64>,
+    pub jitter: Vec<f64>,
+}
+
+impl Default for HistogramBuckets {
+    fn default() -> Self {
+        Self {
+            offset: DEFAULT_HISTOGRAM_BUCKETS.to_vec(),
+            jitter: DEFAULT_HISTOGRAM_BUCKETS.to_vec(),
+        }
+    }
+}
+
+/// Offset and jitter distributions of a single source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObservableSourceHistograms {
+    pub id: ClockId,
+    /// Absolute value of the filtered offset
+    pub offset: Histogram,
+    /// Absolute difference between consecutive filtered offsets
+    pub jitter: Histogram,
+}
+
+#[derive(Debug)]
+struct SourceHistogramEntry {
+    histograms: ObservableSourceHistograms,
+    // Time and offset of the last filter update that was recorded
+    last: Option<(NtpTimestamp, NtpDuration)>,
+}
+
+/// Offset and jitter histograms of all running sources
+#[derive(Debug)]
+pub struct SourceHistograms {
+    buckets: HistogramBuckets,
+    sources: HashMap<ClockId, SourceHistogramEntry>,
+    peerstats: Option<PeerStatsWriter>,
+}
+
+impl Default for SourceHistograms {
+    fn default() -> Self {
+        Self::new(HistogramBuckets::default())
+    }
+}
+
+impl SourceHistograms {
+    pub fn new(buckets: HistogramBuckets) -> Self {
+        Self {
+            buckets,
+            sources: HashMap::new(),
+            peerstats: None,
+        }
+    }
+
+    /// Also write every recorded measurement to a statistics file. The file
+    /// is written on a separate thread, outside of the lock around the
+    /// histograms.
+    pub fn with_peerstats(mut self, peerstats: Option<PeerStats>) -> Self {
+        self.peerstats = peerstats.map(PeerStats::spawn);
+        self
+    }
+
+    /// Record the filtered data of a source, and log it to the statistics
+    /// file if one is configured
+    pub fn record_source(&mut self, source: &ObservableSourceState) {
+        if self.record(source.id, &source.timedata)
+            && let Some(peerstats) = &mut self.peerstats
+        {
+            peerstats.record(
+                std::time::SystemTime::now(),
+                &source.name,
+                &source.address,
+                &source.timedata,
+            );
+        }
+    }
+
+    /// Record the filtered data of a source, if the filter was updated since
+    /// the last time this source was recorded. Returns whether anything was
+    /// recorded.
+    pub fn record(&mut self, id: ClockId, timedata: &ObservableSourceTimedata) -> bool {
+        // The filter has not seen any measurement yet
+        if timedata.last_update == NtpTimestamp::default() {
+            return false;
+        }
+
+        let entry = self
+            .sources
+            .entry(id)
+            .or_insert_with(|| SourceHistogramEntry {
+                histograms: ObservableSourceHistograms {
+                    id,
+                    offset: Histogram::new(self.buckets.offset.clone()),
+                    jitter: Histogram::new(self.buckets.jitter.clone()),
+                },
+                last: None,
+            });
+
+        if entry
+            .last
+            .is_some_and(|(last_update, _)| last_update == timedata.last_update)
+        {
+            return false;
+        }
+
+        let histograms = &mut entry.histograms;
+        histograms.offset.record(timedata.offset.abs().to_seconds());
+        if let Some((_, last_offset)) = entry.last {
+            histograms
+                .jitter
+                .record(timedata.offset.abs_diff(last_offset).to_seconds());
+        }
+        entry.last = Some((timedata.last_update, timedata.offset));
+        true
+    }
+
+    pub fn remove(&mut self, id: ClockId) {
+        self.sources.remove(&id);
+    }
+
+    pub fn to_vec(&self) -> Vec<ObservableSourceHistograms> {
+        self.sources
+            .values()
+            .map(|entry| entry.histograms.clone())
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -693,3 +1479,462 @@ pub struct ServerData {
     pub stats: ServerStats,
     pub config: ServerConfig,
 }
+
+#[cfg(test)]
+mod tests {
+    use ntp_proto::{
+        KalmanClockController, KeySetProvider, ProtocolVersion, TimeSyncControllerWrapper,
+    };
+
+    use crate::daemon::config::{FlattenedPair, NormalizedAddress, StandardSource};
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy)]
+    struct TestClock;
+
+    impl NtpClock for TestClock {
+        type Error = core::convert::Infallible;
+
+        fn now(&self) -> Result<NtpTimestamp, Self::Error> {
+            Ok(NtpTimestamp::default())
+        }
+
+        fn set_frequency(&self, _freq: f64) -> Result<NtpTimestamp, Self::Error> {
+            unimplemented!()
+        }
+
+        fn get_frequency(&self) -> Result<f64, Self::Error> {
+            Ok(0.0)
+        }
+
+        fn step_clock(&self, _offset: NtpDuration) -> Result<NtpTimestamp, Self::Error> {
+            unimplemented!()
+        }
+
+        fn disable_ntp_algorithm(&self) -> Result<(), Self::Error> {
+            unimplemented!()
+        }
+
+        fn error_estimate_update(
+            &self,
+            _est_error: NtpDuration,
+            _max_error: NtpDuration,
+        ) -> Result<(), Self::Error> {
+            unimplemented!()
+        }
+
+        fn status_update(&self, _leap_status: NtpLeapIndicator) -> Result<(), Self::Error> {
+            unimplemented!()
+        }
+    }
+
+    type TestSystem =
+        SystemTask<TestClock, TimeSyncControllerWrapper<KalmanClockController<TestClock>>>;
+
+    fn test_system() -> TestSystem {
+        let (_, keyset) = tokio::sync::watch::channel(KeySetProvider::new(1).get());
+        let (_, ip_list) = tokio::sync::watch::channel(Arc::from([]));
+        let (system, _) = TestSystem::new(
+            TestClock,
+            None,
+            TimestampMode::default(),
+            SynchronizationConfig::default(),
+            Default::default(),
+            SourceConfig::default(),
+            StartupSourceRetryConfig::default(),
+            DnsConfig::default(),
+            0,
+            SourceHistograms::default(),
+            &keyset,
+            ip_list,
+            false,
+            #[cfg(target_os = "linux")]
+            CsptpConfig::default(),
+        )
+        .unwrap();
+        system
+    }
+
+    fn standard_source(name: &str, ip: &str) -> NtpSourceConfig {
+        NtpSourceConfig::Standard(FlattenedPair {
+            first: StandardSource {
+                address: NormalizedAddress::with_hardcoded_dns(
+                    name,
+                    123,
+                    vec![format!("{ip}:123").parse().unwrap()],
+                )
+                .into(),
+                ntp_version: ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
+                prefer: false,
+                address_family: None,
+            },
+            second: Default::default(),
+        })
+    }
+
+    async fn next_spawn_event(system: &mut TestSystem) -> SpawnEvent {
+        tokio::time::timeout(std::time::Duration::from_secs(1), system.spawn_rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_reload_spawns_added_source() {
+        let mut system = test_system();
+        let existing = standard_source("example.com", "127.0.0.1");
+        system.add_source_config(&existing).unwrap();
+        let existing_spawner = system.spawners[0].id;
+
+        let event = next_spawn_event(&mut system).await;
+        assert_eq!(event.id, existing_spawner);
+
+        // Stand-in for the source created by the existing spawner
+        let existing_source = ClockId::new();
+        system.sources.lock().unwrap().insert(
+            existing_source,
+            SourceState {
+                spawner_id: existing_spawner,
+                source_id: existing_source,
+                addr: "127.0.0.1:123".into(),
+                stype: SourceType::Ntp,
+                selection: SourceSelectionState::Candidate,
+            },
+        );
+
+        system.reload(ConfigReload {
+            sources: vec![
+                existing.clone(),
+                standard_source("example.net", "127.0.0.2"),
+            ],
+            servers: vec![],
+        });
+
+        assert_eq!(system.spawners.len(), 2);
+        assert_eq!(system.spawners[0].id, existing_spawner);
+        assert!(
+            system
+                .sources
+                .lock()
+                .unwrap()
+                .contains_key(&existing_source)
+        );
+
+        let event = next_spawn_event(&mut system).await;
+        assert_eq!(event.id, system.spawners[1].id);
+        let SpawnAction::Create(params) = event.action else {
+            panic!("did not receive create action!");
+        };
+        assert_eq!(params.get_addr(), "127.0.0.2:123");
+
+        // Removing the original source retires its spawner and its sources
+        let retired_task = system.spawners[0].task.clone();
+        system.reload(ConfigReload {
+            sources: vec![standard_source("example.net", "127.0.0.2")],
+            servers: vec![],
+        });
+        assert_eq!(system.spawners.len(), 1);
+        assert_ne!(system.spawners[0].id, existing_spawner);
+        assert!(system.sources.lock().unwrap().is_empty());
+        assert_finished(&retired_task).await;
+    }
+
+    async fn assert_finished(task: &AbortHandle) {
+        for _ in 0..100 {
+            if task.is_finished() {
+                return;
+            }
+            tokio::task::yield_now().await;
+        }
+        panic!("task was not stopped");
+    }
+
+    #[tokio::test]
+    async fn test_spawners_stop_with_system() {
+        let mut system = test_system();
+        system
+            .add_source_config(&standard_source("example.com", "127.0.0.1"))
+            .unwrap();
+        let task = system.spawners[0].task.clone();
+        assert!(!task.is_finished());
+
+        drop(system);
+        assert_finished(&task).await;
+    }
+
+    #[tokio::test]
+    async fn test_disabled_source_stays_disabled_when_recreated() {
+        let mut system = test_system();
+        let spawner_id = SpawnerId::new();
+        let create = |id| {
+            let SpawnAction::Create(params) = SpawnAction::create_ntp(
+                id,
+                "127.0.0.1:123".parse().unwrap(),
+                NormalizedAddress::with_hardcoded_dns("example.com", 123, vec![]),
+                ProtocolVersion::v4_upgrading_to_v5_with_default_tries(),
+                SourceConfig::default(),
+                None,
+            ) else {
+                unreachable!()
+            };
+            params
+        };
+
+        let first = ClockId::new();
+        system
+            .create_source(spawner_id, create(first))
+            .await
+            .unwrap();
+        system.disabled_sources.write().unwrap().insert(first);
+
+        system.handle_source_network_issue(first).await.unwrap();
+        assert!(system.disabled_sources.read().unwrap().is_empty());
+
+        // The spawner recreates the source under a new id
+        let second = ClockId::new();
+        system
+            .create_source(spawner_id, create(second))
+            .await
+            .unwrap();
+        assert!(system.disabled_sources.read().unwrap().contains(&second));
+        assert!(system.removed_disabled_sources.is_empty());
+    }
+
+    fn captured_events(f: impl FnOnce()) -> String {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(buffer.clone())
+            .with_ansi(false)
+            .finish();
+        tracing::subscriber::with_default(subscriber, f);
+        String::from_utf8(buffer.lock().unwrap().clone()).unwrap()
+    }
+
+    #[test]
+    fn test_read_error_estimate() {
+        #[derive(Debug, Clone, Copy)]
+        struct ErrorEstimateClock(Option<(NtpDuration, NtpDuration)>);
+
+        impl NtpClock for ErrorEstimateClock {
+            type Error = std::io::Error;
+
+            fn now(&self) -> Result<NtpTimestamp, Self::Error> {
+                Ok(NtpTimestamp::default())
+            }
+
+            fn set_frequency(&self, _freq: f64) -> Result<NtpTimestamp, Self::Error> {
+                unimplemented!()
+            }
+
+            fn get_frequency(&self) -> Result<f64, Self::Error> {
+                Ok(0.0)
+            }
+
+            fn step_clock(&self, _offset: NtpDuration) -> Result<NtpTimestamp, Self::Error> {
+                unimplemented!()
+            }
+
+            fn disable_ntp_algorithm(&self) -> Result<(), Self::Error> {
+                unimplemented!()
+            }
+
+            fn error_estimate_update(
+                &self,
+                _est_error: NtpDuration,
+                _max_error: NtpDuration,
+            ) -> Result<(), Self::Error> {
+                unimplemented!()
+            }
+
+            fn get_error_estimate(
+                &self,
+            ) -> Result<Option<(NtpDuration, NtpDuration)>, Self::Error> {
+                self.0
+                    .map(Some)
+                    .ok_or_else(|| std::io::Error::other("clock error"))
+            }
+
+            fn status_update(&self, _leap_status: NtpLeapIndicator) -> Result<(), Self::Error> {
+                unimplemented!()
+            }
+        }
+
+        // Clocks that don't track their error report nothing
+        assert_eq!(read_error_estimate(&TestClock), None);
+
+        let error_estimate = (
+            NtpDuration::from_seconds(0.001),
+            NtpDuration::from_seconds(0.5),
+        );
+        assert_eq!(
+            read_error_estimate(&ErrorEstimateClock(Some(error_estimate))),
+            Some(error_estimate)
+        );
+
+        // A failure to read the error is reported as unknown
+        assert_eq!(read_error_estimate(&ErrorEstimateClock(None)), None);
+    }
+
+    #[test]
+    fn test_tai_offset() {
+        #[derive(Debug, Clone, Default)]
+        struct TaiClock(Arc<std::sync::Mutex<Vec<i32>>>);
+
+        impl NtpClock for TaiClock {
+            type Error = std::io::Error;
+
+            fn now(&self) -> Result<NtpTimestamp, Self::Error> {
+                Ok(NtpTimestamp::default())
+            }
+
+            fn set_frequency(&self, _freq: f64) -> Result<NtpTimestamp, Self::Error> {
+                unimplemented!()
+            }
+
+            fn get_frequency(&self) -> Result<f64, Self::Error> {
+                Ok(0.0)
+            }
+
+            fn step_clock(&self, _offset: NtpDuration) -> Result<NtpTimestamp, Self::Error> {
+                unimplemented!()
+            }
+
+            fn disable_ntp_algorithm(&self) -> Result<(), Self::Error> {
+                unimplemented!()
+            }
+
+            fn error_estimate_update(
+                &self,
+                _est_error: NtpDuration,
+                _max_error: NtpDuration,
+            ) -> Result<(), Self::Error> {
+                unimplemented!()
+            }
+
+            fn set_tai_offset(&self, tai_offset: i32) -> Result<(), Self::Error> {
+                self.0.lock().unwrap().push(tai_offset);
+                Ok(())
+            }
+
+            fn get_tai_offset(&self) -> Result<Option<i32>, Self::Error> {
+                Ok(self.0.lock().unwrap().last().copied())
+            }
+
+            fn status_update(&self, _leap_status: NtpLeapIndicator) -> Result<(), Self::Error> {
+                unimplemented!()
+            }
+        }
+
+        // Without configuration the clock is left alone
+        let clock = TaiClock::default();
+        apply_tai_offset(&clock, SynchronizationConfig::default().tai_offset);
+        assert!(clock.0.lock().unwrap().is_empty());
+        assert_eq!(read_tai_offset(&clock), None);
+
+        let config: SynchronizationConfig = toml::from_str("tai-offset = 37").unwrap();
+        apply_tai_offset(&clock, config.tai_offset);
+        assert_eq!(*clock.0.lock().unwrap(), vec![37]);
+        assert_eq!(read_tai_offset(&clock), Some(37));
+
+        // Clocks without a TAI offset report nothing
+        assert_eq!(read_tai_offset(&TestClock), None);
+    }
+
+    #[test]
+    fn test_clock_history_keeps_most_recent() {
+        let sample = |i: u32| ClockSample {
+            timestamp: NtpTimestamp::from_seconds_nanos_since_ntp_era(i, 0),
+            offset: NtpDuration::from_seconds(f64::from(i) * 1e-3),
+            frequency: f64::from(i) * 1e-6,
+        };
+
+        let mut history = ClockHistory::new(4);
+        for i in 0..10 {
+            history.push(sample(i));
+        }
+        assert_eq!(history.to_vec(), (6..10).map(sample).collect::<Vec<_>>());
+
+        let mut history = ClockHistory::new(0);
+        history.push(sample(0));
+        assert!(history.to_vec().is_empty());
+    }
+
+    #[test]
+    fn test_source_histograms_record_filter_updates() {
+        let timedata = |time: u32, offset: f64| ObservableSourceTimedata {
+            offset: NtpDuration::from_seconds(offset),
+            last_update: NtpTimestamp::from_seconds_nanos_since_ntp_era(time, 0),
+            ..Default::default()
+        };
+
+        let id = ClockId::new();
+        let mut histograms = SourceHistograms::new(HistogramBuckets {
+            offset: vec![1e-3, 1e-2],
+            jitter: vec![1e-3],
+        });
+
+        // Nothing is recorded before the filter has seen a measurement
+        histograms.record(id, &ObservableSourceTimedata::default());
+        assert!(histograms.to_vec().is_empty());
+
+        histograms.record(id, &timedata(1, -0.002));
+        // Observing the same filter state again does not count twice
+        histograms.record(id, &timedata(1, -0.002));
+        histograms.record(id, &timedata(2, 0.0005));
+
+        let recorded = histograms.to_vec();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].id, id);
+        assert_eq!(recorded[0].offset.counts, vec![1, 2]);
+        assert_eq!(recorded[0].offset.count, 2);
+        assert!((recorded[0].offset.sum - 0.0025).abs() < 1e-9);
+        assert_eq!(recorded[0].jitter.counts, vec![0]);
+        assert_eq!(recorded[0].jitter.count, 1);
+
+        histograms.remove(id);
+        assert!(histograms.to_vec().is_empty());
+    }
+
+    #[test]
+    fn test_selection_change_emits_event() {
+        let id = ClockId::new();
+        let mut sources = HashMap::from([(
+            id,
+            SourceState {
+                spawner_id: SpawnerId::new(),
+                source_id: id,
+                addr: "127.0.0.1:123".into(),
+                stype: SourceType::Ntp,
+                selection: SourceSelectionState::Candidate,
+            },
+        )]);
+
+        let output = captured_events(|| update_selection_states(&mut sources, &[id], &[]));
+        assert_eq!(sources[&id].selection, SourceSelectionState::Selected);
+        assert_eq!(output.matches(SOURCE_EVENTS_TARGET).count(), 1);
+        assert!(output.contains("addr=127.0.0.1:123"));
+        assert!(output.contains("state=selected"));
+
+        // No change in selection, so no new event
+        let output = captured_events(|| update_selection_states(&mut sources, &[id], &[]));
+        assert!(!output.contains(SOURCE_EVENTS_TARGET));
+
+        let output = captured_events(|| update_selection_states(&mut sources, &[], &[id]));
+        assert_eq!(sources[&id].selection, SourceSelectionState::Outlier);
+        assert!(output.contains("state=outlier"));
+
+        let output = captured_events(|| update_selection_states(&mut sources, &[], &[]));
+        assert_eq!(sources[&id].selection, SourceSelectionState::Candidate);
+        assert!(output.contains("state=candidate"));
+
+        // Nothing is done while the events are disabled
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::WARN)
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            update_selection_states(&mut sources, &[id], &[]);
+        });
+        assert_eq!(sources[&id].selection, SourceSelectionState::Candidate);
+    }
+}
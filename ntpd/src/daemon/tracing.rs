@@ -1,13 +1,15 @@
 use std::{
-    path::PathBuf,
+    io::Write,
+    os::unix::net::UnixDatagram,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::{Arc, Mutex},
 };
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tracing::metadata::LevelFilter;
 
-#[derive(Debug, Default, Copy, Clone, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     /// The "trace" level.
@@ -68,6 +70,128 @@ impl From<LogLevel> for LevelFilter {
     }
 }
 
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogTarget {
+    /// Write log output to stdout, or to the configured log file
+    #[default]
+    Stdout,
+    /// Send log output to the local syslog daemon
+    Syslog,
+}
+
+// Socket on which the local syslog daemon receives messages
+const SYSLOG_SOCKET: &str = "/dev/log";
+
+// All messages are logged with the LOG_DAEMON facility
+const SYSLOG_FACILITY: u8 = 3;
+
+/// Map a tracing level to the corresponding syslog severity
+fn syslog_severity(level: tracing::Level) -> u8 {
+    match level {
+        tracing::Level::ERROR => 3,                         // LOG_ERR
+        tracing::Level::WARN => 4,                          // LOG_WARNING
+        tracing::Level::INFO => 6,                          // LOG_INFO
+        tracing::Level::DEBUG | tracing::Level::TRACE => 7, // LOG_DEBUG
+    }
+}
+
+struct SyslogMakeWriter {
+    socket: UnixDatagram,
+    identity: String,
+}
+
+impl SyslogMakeWriter {
+    fn connect(path: &Path) -> Result<Self, std::io::Error> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+
+        let program = std::env::args()
+            .next()
+            .as_deref()
+            .map(Path::new)
+            .and_then(Path::file_name)
+            .map_or_else(
+                || "ntpd-rs".into(),
+                |name| name.to_string_lossy().into_owned(),
+            );
+
+        Ok(Self {
+            socket,
+            identity: format!("{program}[{}]", std::process::id()),
+        })
+    }
+}
+
+/// Collects a single formatted event, which is sent as one syslog message
+/// once the writer is dropped.
+struct SyslogWriter<'a> {
+    make_writer: &'a SyslogMakeWriter,
+    severity: u8,
+    buffer: Vec<u8>,
+}
+
+impl Write for SyslogWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SyslogWriter<'_> {
+    fn drop(&mut self) {
+        let message = String::from_utf8_lossy(&self.buffer);
+        let message = format!(
+            "<{}>{}: {}",
+            SYSLOG_FACILITY * 8 + self.severity,
+            self.make_writer.identity,
+            message.trim_end()
+        );
+        // There is nowhere left to report a failure to log to
+        let _ = self.make_writer.socket.send(message.as_bytes());
+    }
+}
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for SyslogMakeWriter {
+    type Writer = SyslogWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        SyslogWriter {
+            make_writer: self,
+            severity: syslog_severity(tracing::Level::INFO),
+            buffer: vec![],
+        }
+    }
+
+    fn make_writer_for(&'a self, meta: &tracing::Metadata<'_>) -> Self::Writer {
+        SyslogWriter {
+            make_writer: self,
+            severity: syslog_severity(*meta.level()),
+            buffer: vec![],
+        }
+    }
+}
+
+fn syslog_init(
+    level: impl Into<LevelFilter>,
+    socket_path: &Path,
+) -> Result<Box<dyn tracing::Subscriber + Send + Sync + 'static>, std::io::Error> {
+    let writer = SyslogMakeWriter::connect(socket_path)?;
+    // Syslog adds its own timestamps, and does not handle escape codes
+    Ok(Box::new(
+        tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_ansi(false)
+            .without_time()
+            .with_writer(writer)
+            .finish(),
+    ))
+}
+
 struct ReloadableMakeWriter {
     file: Arc<Mutex<std::fs::File>>,
 }
@@ -126,28 +250,119 @@ impl LogReloadTaskStarter {
 
 pub fn tracing_init(
     level: impl Into<LevelFilter>,
+    log_target: LogTarget,
     log_path: Option<PathBuf>,
     ansi_colors: bool,
-) -> (
+) -> std::io::Result<(
     Box<dyn tracing::Subscriber + Send + Sync + 'static>,
     Option<LogReloadTaskStarter>,
-) {
-    let builder = tracing_subscriber::fmt()
-        .with_max_level(level)
-        .with_ansi(ansi_colors);
-    if let Some(path) = log_path {
-        let (writer, task_starter) = match ReloadableMakeWriter::new(path.clone()) {
-            Ok(writer) => writer,
-            Err(e) => {
-                tracing::error!("Could not open logfile {}, exiting: {e}", path.display());
-                std::process::exit(70);
-            }
-        };
-        (
-            Box::new(builder.with_writer(writer).finish()),
-            Some(task_starter),
+)> {
+    let level = level.into();
+
+    if log_target == LogTarget::Syslog {
+        let subscriber = syslog_init(level, Path::new(SYSLOG_SOCKET)).map_err(|e| {
+            std::io::Error::new(
+                e.kind(),
+                format!("Could not connect to syslog at {SYSLOG_SOCKET}: {e}"),
+            )
+        })?;
+        return Ok((subscriber, None));
+    }
+
+    let Some(path) = log_path else {
+        return Ok((stdout_tracing_init(level, ansi_colors), None));
+    };
+
+    let (writer, task_starter) = ReloadableMakeWriter::new(path.clone()).map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("Could not open logfile {}: {e}", path.display()),
         )
-    } else {
-        (Box::new(builder.finish()), None)
+    })?;
+    Ok((
+        Box::new(
+            tracing_subscriber::fmt()
+                .with_max_level(level)
+                .with_ansi(ansi_colors)
+                .with_writer(writer)
+                .finish(),
+        ),
+        Some(task_starter),
+    ))
+}
+
+/// Log to stdout, which unlike the other log targets can not fail
+pub fn stdout_tracing_init(
+    level: impl Into<LevelFilter>,
+    ansi_colors: bool,
+) -> Box<dyn tracing::Subscriber + Send + Sync + 'static> {
+    Box::new(
+        tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_ansi(ansi_colors)
+            .finish(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syslog_severity() {
+        assert_eq!(syslog_severity(tracing::Level::ERROR), 3);
+        assert_eq!(syslog_severity(tracing::Level::WARN), 4);
+        assert_eq!(syslog_severity(tracing::Level::INFO), 6);
+        assert_eq!(syslog_severity(tracing::Level::DEBUG), 7);
+        assert_eq!(syslog_severity(tracing::Level::TRACE), 7);
+    }
+
+    #[test]
+    fn test_syslog_messages() {
+        // be careful with copying: tests run concurrently and should use a unique socket name!
+        let path =
+            std::env::temp_dir().join(format!("ntp-test-syslog-{}", crate::test::alloc_port()));
+        if path.exists() {
+            std::fs::remove_file(&path).unwrap();
+        }
+        let syslog = UnixDatagram::bind(&path).unwrap();
+
+        let subscriber = syslog_init(LevelFilter::INFO, &path).unwrap();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::error!("first message");
+            tracing::debug!("filtered message");
+            tracing::info!("second message");
+        });
+
+        let mut buf = [0; 1024];
+        let size = syslog.recv(&mut buf).unwrap();
+        let message = std::str::from_utf8(&buf[..size]).unwrap();
+        // LOG_DAEMON with LOG_ERR
+        assert!(message.starts_with("<27>"));
+        assert!(message.ends_with("first message"));
+
+        let size = syslog.recv(&mut buf).unwrap();
+        let message = std::str::from_utf8(&buf[..size]).unwrap();
+        // LOG_DAEMON with LOG_INFO
+        assert!(message.starts_with("<30>"));
+        assert!(message.ends_with("second message"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_deserialize_log_target() {
+        #[derive(Deserialize)]
+        struct TestConfig {
+            target: LogTarget,
+        }
+
+        let config: TestConfig = toml::from_str(r#"target = "syslog""#).unwrap();
+        assert_eq!(config.target, LogTarget::Syslog);
+
+        let config: TestConfig = toml::from_str(r#"target = "stdout""#).unwrap();
+        assert_eq!(config.target, LogTarget::Stdout);
+
+        assert!(toml::from_str::<TestConfig>(r#"target = "journal""#).is_err());
     }
 }
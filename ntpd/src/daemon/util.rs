@@ -1,3 +1,5 @@
+use std::{net::SocketAddr, os::fd::AsFd};
+
 use ntp_proto::NtpTimestamp;
 
 // Epoch offset between NTP and UNIX timescales
@@ -20,3 +22,56 @@ pub(crate) fn convert_clock_timestamp(ts: clock_steering::Timestamp) -> NtpTimes
 pub(crate) fn convert_unix_timestamp(seconds: u64, nanos: u32) -> NtpTimestamp {
     NtpTimestamp::from_seconds_nanos_since_ntp_era(EPOCH_OFFSET.wrapping_add(seconds as _), nanos)
 }
+
+/// Whether an error from receiving on a socket is transient, such as an
+/// interrupted system call (`EINTR`) or a spurious wakeup (`EAGAIN`), and
+/// the receive should simply be retried.
+pub(crate) fn is_transient_io_error(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::Interrupted | std::io::ErrorKind::WouldBlock
+    )
+}
+
+/// Mark outgoing packets on a socket connected to or bound on `addr` with the
+/// given DSCP value. Whether the marking is honored depends on the network
+/// hardware and drivers.
+pub(crate) fn set_dscp(socket: &impl AsFd, addr: SocketAddr, dscp: u8) -> std::io::Result<()> {
+    let socket = socket2::SockRef::from(socket);
+    // The DSCP occupies the upper 6 bits of the traffic class
+    let traffic_class = u32::from(dscp) << 2;
+    match addr {
+        SocketAddr::V4(_) => socket.set_tos_v4(traffic_class),
+        SocketAddr::V6(_) => socket.set_tclass_v6(traffic_class),
+    }
+}
+
+/// Request a receive buffer (`SO_RCVBUF`) of `size` bytes for a socket.
+/// Returns the size actually granted by the kernel, which may differ from the
+/// requested size: Linux doubles it to account for bookkeeping overhead and
+/// caps it at `net.core.rmem_max`.
+pub(crate) fn set_recv_buffer_size(socket: &impl AsFd, size: usize) -> std::io::Result<usize> {
+    let socket = socket2::SockRef::from(socket);
+    socket.set_recv_buffer_size(size)?;
+    socket.recv_buffer_size()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_recv_buffer_size() {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let default_size = socket2::SockRef::from(&socket).recv_buffer_size().unwrap();
+
+        // Pick a size the kernel certainly caps differently from the default
+        let requested = if default_size > 8192 { 4096 } else { 65536 };
+        let granted = set_recv_buffer_size(&socket, requested).unwrap();
+        assert_ne!(granted, default_size);
+        assert_eq!(
+            socket2::SockRef::from(&socket).recv_buffer_size().unwrap(),
+            granted
+        );
+    }
+}
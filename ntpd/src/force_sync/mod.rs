@@ -110,8 +110,9 @@ pub(crate) fn force_sync(config: Option<&Path>) -> std::io::Result<ExitCode> {
     let (config, _) = initialize_logging_parse_config(
         Some(LogLevel::Warn),
         config,
+        config::PermissionCheck::Warn,
         crate::daemon::Application::Ctl,
-    );
+    )?;
 
     // Warn/error if the config is unreasonable. We do this after finishing
     // tracing setup to ensure logging is fully configured.
@@ -134,6 +135,7 @@ pub(crate) fn force_sync(config: Option<&Path>) -> std::io::Result<ExitCode> {
                 match source {
                     config::NtpSourceConfig::Standard(_)
                     | config::NtpSourceConfig::Nts(_)
+                    | config::NtpSourceConfig::Broadcast(_)
                     | config::NtpSourceConfig::Sock(_) => total_sources += 1,
                     #[cfg(feature = "pps")]
                     config::NtpSourceConfig::Pps(_) => {} // PPS sources don't count
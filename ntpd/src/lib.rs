@@ -20,6 +20,7 @@ mod notify;
 
 pub use ctl::main as ctl_main;
 pub use daemon::main as daemon_main;
+pub use daemon::{Config, run_daemon};
 pub use metrics::exporter::main as metrics_exporter_main;
 
 #[cfg(test)]
@@ -1,17 +1,23 @@
 use libc::{ECONNABORTED, EMFILE, ENFILE, ENOBUFS, ENOMEM};
 use timestamped_socket::interface::ChangeDetector;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
 use tokio::runtime::Builder;
 use tracing::{debug, error, trace, warn};
 
 use std::{
     fmt::Write,
+    os::unix::fs::PermissionsExt,
     path::{Path, PathBuf},
     sync::Arc,
 };
 
-use crate::daemon::{ObservableState, config::CliArg, initialize_logging_parse_config};
+use crate::daemon::{
+    ObservableState,
+    config::{CliArg, MetricsExporterListen, PermissionCheck},
+    initialize_logging_parse_config,
+    sockets::create_unix_socket_with_permissions,
+};
 use crate::notify::notify_ready;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -130,16 +136,15 @@ fn run(options: &NtpMetricsExporterOptions) -> Result<(), Box<dyn std::error::Er
     let (config, task_starter) = initialize_logging_parse_config(
         None,
         options.config.as_deref(),
+        PermissionCheck::Warn,
         crate::daemon::Application::MetricsExporter,
-    );
+    )?;
 
     Builder::new_current_thread().enable_all().build()?.block_on(async {
         if let Some(task_starter) = task_starter {
             task_starter.start();
         }
 
-        let timeout = std::time::Duration::from_millis(1000);
-
         let Some(observation_socket_path) = config.observability.observation_path.map(Arc::new) else {
             eprintln!("An observation socket path must be configured using the observation-path option in the [observability] section of the configuration");
             std::process::exit(1);
@@ -152,74 +157,108 @@ fn run(options: &NtpMetricsExporterOptions) -> Result<(), Box<dyn std::error::Er
 
         let _ = notify_ready().await;
 
-        let listener = loop {
-            match TcpListener::bind(&config.observability.metrics_exporter_listen).await {
-                Err(e) if e.kind() == std::io::ErrorKind::AddrNotAvailable => {
-                    tracing::info!("Could not open listening socket, waiting for interface to come up");
-                    let _ = tokio::time::timeout(
-                        std::time::Duration::from_secs(60),
-                        ChangeDetector::new()?.wait_for_change(),
-                    )
-                    .await;
-                }
-                Err(e) => {
-                    tracing::warn!("Could not open listening socket: {}", e);
-                    let _ = tokio::time::timeout(
-                        std::time::Duration::from_secs(60),
-                        ChangeDetector::new()?.wait_for_change(),
-                    )
-                    .await;
+        let listener = match &config.observability.metrics_exporter_listen {
+            MetricsExporterListen::Tcp(addr) => Listener::Tcp(loop {
+                match TcpListener::bind(addr).await {
+                    Err(e) if e.kind() == std::io::ErrorKind::AddrNotAvailable => {
+                        tracing::info!("Could not open listening socket, waiting for interface to come up");
+                        let _ = tokio::time::timeout(
+                            std::time::Duration::from_secs(60),
+                            ChangeDetector::new()?.wait_for_change(),
+                        )
+                        .await;
+                    }
+                    Err(e) => {
+                        tracing::warn!("Could not open listening socket: {}", e);
+                        let _ = tokio::time::timeout(
+                            std::time::Duration::from_secs(60),
+                            ChangeDetector::new()?.wait_for_change(),
+                        )
+                        .await;
+                    }
+                    Ok(listener) => break listener,
                 }
-                Ok(listener) => break listener,
+            }),
+            MetricsExporterListen::Unix(path) => {
+                let permissions: std::fs::Permissions =
+                    PermissionsExt::from_mode(config.observability.metrics_exporter_permissions);
+                Listener::Unix(create_unix_socket_with_permissions(path, permissions)?)
             }
         };
 
-        // this has a lot more permits than the daemon observer has, but we expect http transfers to
-        // take longer than how much time the daemon needs to return observability data
-        let permits = Arc::new(tokio::sync::Semaphore::new(100));
+        serve(listener, observation_socket_path).await?;
 
-        loop {
-            let permit = permits
-                .clone()
-                .acquire_owned()
-                .await
-                .expect("semaphore was unexpectedly closed");
-            let (mut tcp_stream, _) = match listener.accept().await {
-                Ok(a) => a,
-                Err(e) if matches!(e.raw_os_error(), Some(ECONNABORTED)) => {
-                    debug!("Client unexpectedly closed connection: {e}");
-                    continue;
-                }
-                Err(e)
-                    if matches!(
-                        e.raw_os_error(),
-                        Some(ENFILE | EMFILE | ENOMEM | ENOBUFS)
-                    ) =>
-                {
-                    error!("Not enough resources available to accept incoming connection: {e}");
-                    tokio::time::sleep(timeout).await;
-                    continue;
-                }
-                Err(e) => {
-                    error!("Could not accept incoming connection: {e}");
-                    return Err(e.into());
-                }
-            };
-            let path = observation_socket_path.clone();
+        Ok(())
+    })
+}
 
-            // handle each connection on a separate task
-            let fut = async move { handle_connection(&mut tcp_stream, &path).await };
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
 
-            tokio::spawn(async move {
-                match tokio::time::timeout(timeout, fut).await {
-                    Err(_) => debug!("connection timed out"),
-                    Ok(Err(e)) => warn!("error handling connection: {e}"),
-                    Ok(_) => trace!("connection handled successfully"),
-                }
-                drop(permit);
-            });
+enum Stream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Listener {
+    async fn accept(&self) -> std::io::Result<Stream> {
+        match self {
+            Listener::Tcp(listener) => listener.accept().await.map(|(s, _)| Stream::Tcp(s)),
+            Listener::Unix(listener) => listener.accept().await.map(|(s, _)| Stream::Unix(s)),
         }
-    })
+    }
+}
+
+async fn serve(listener: Listener, observation_socket_path: Arc<PathBuf>) -> std::io::Result<()> {
+    let timeout = std::time::Duration::from_millis(1000);
+
+    // this has a lot more permits than the daemon observer has, but we expect http transfers to
+    // take longer than how much time the daemon needs to return observability data
+    let permits = Arc::new(tokio::sync::Semaphore::new(100));
+
+    loop {
+        let permit = permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore was unexpectedly closed");
+        let stream = match listener.accept().await {
+            Ok(a) => a,
+            Err(e) if matches!(e.raw_os_error(), Some(ECONNABORTED)) => {
+                debug!("Client unexpectedly closed connection: {e}");
+                continue;
+            }
+            Err(e) if matches!(e.raw_os_error(), Some(ENFILE | EMFILE | ENOMEM | ENOBUFS)) => {
+                error!("Not enough resources available to accept incoming connection: {e}");
+                tokio::time::sleep(timeout).await;
+                continue;
+            }
+            Err(e) => {
+                error!("Could not accept incoming connection: {e}");
+                return Err(e);
+            }
+        };
+        let path = observation_socket_path.clone();
+
+        // handle each connection on a separate task
+        let fut = async move {
+            match stream {
+                Stream::Tcp(mut stream) => handle_connection(&mut stream, &path).await,
+                Stream::Unix(mut stream) => handle_connection(&mut stream, &path).await,
+            }
+        };
+
+        tokio::spawn(async move {
+            match tokio::time::timeout(timeout, fut).await {
+                Err(_) => debug!("connection timed out"),
+                Ok(Err(e)) => warn!("error handling connection: {e}"),
+                Ok(_) => trace!("connection handled successfully"),
+            }
+            drop(permit);
+        });
+    }
 }
 
 async fn handle_connection(
@@ -312,6 +351,19 @@ fn format_response(buf: &mut String, state: &ObservableState) -> std::fmt::Resul
 mod tests {
     use std::io::Cursor;
 
+    use ntp_proto::{
+        ClockId, NtpDuration, NtpTimestamp, ObservableSourceState, ObservableSourceTimedata,
+        PollIntervalLimits, SystemSnapshot,
+    };
+
+    use crate::{
+        daemon::{
+            observer::{HistogramBuckets, ProgramData, SourceHistograms},
+            sockets::write_json,
+        },
+        test::alloc_port,
+    };
+
     use super::*;
 
     const BINARY: &str = "/usr/bin/ntp-metrics-exporter";
@@ -345,4 +397,157 @@ mod tests {
         assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
         assert_eq!(err.to_string(), "Request too long");
     }
+
+    #[tokio::test]
+    async fn scrape_over_unix_socket() {
+        // be careful with copying: tests run concurrently and should use a unique socket name!
+        let observation_path =
+            std::env::temp_dir().join(format!("ntp-test-metrics-observe-{}", alloc_port()));
+        let metrics_path =
+            std::env::temp_dir().join(format!("ntp-test-metrics-listen-{}", alloc_port()));
+
+        let permissions: std::fs::Permissions = PermissionsExt::from_mode(0o600);
+        let observation_listener =
+            create_unix_socket_with_permissions(&observation_path, permissions.clone()).unwrap();
+        let observer = tokio::spawn(async move {
+            let (mut stream, _) = observation_listener.accept().await.unwrap();
+            let state = ObservableState {
+                program: ProgramData::default(),
+                system: SystemSnapshot::default(),
+                bloom_filter_population: 0,
+                sources: vec![],
+                servers: vec![],
+                nts_ke_servers: vec![],
+                clock_history: vec![],
+                source_histograms: vec![],
+            };
+            write_json(&mut stream, &state).await.unwrap();
+        });
+
+        let listener = Listener::Unix(
+            create_unix_socket_with_permissions(&metrics_path, permissions).unwrap(),
+        );
+        let exporter = tokio::spawn(serve(listener, Arc::new(observation_path)));
+
+        let mut stream = UnixStream::connect(&metrics_path).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("ntp_system_stratum"));
+
+        observer.await.unwrap();
+        exporter.abort();
+    }
+
+    #[tokio::test]
+    async fn scrape_source_histograms() {
+        // be careful with copying: tests run concurrently and should use a unique socket name!
+        let observation_path =
+            std::env::temp_dir().join(format!("ntp-test-metrics-observe-{}", alloc_port()));
+        let metrics_path =
+            std::env::temp_dir().join(format!("ntp-test-metrics-listen-{}", alloc_port()));
+
+        let id = ClockId::new();
+        let source = ObservableSourceState {
+            timedata: ObservableSourceTimedata::default(),
+            unanswered_polls: 0,
+            poll_interval: PollIntervalLimits::default().min,
+            nts_cookies: None,
+            last_poll_sent: NtpTimestamp::default(),
+            last_response_received: NtpTimestamp::default(),
+            timescale: None,
+            captured_packets: vec![],
+            last_round_trip_delay: None,
+            last_precision: None,
+            name: "example.com".into(),
+            address: "192.0.2.1:123".into(),
+            id,
+        };
+        let mut histograms = SourceHistograms::new(HistogramBuckets {
+            offset: vec![1e-3, 1e-2],
+            jitter: vec![1e-3, 1e-2],
+        });
+        let mut measure = |time: u32, offset: f64| {
+            histograms.record(
+                id,
+                &ObservableSourceTimedata {
+                    offset: NtpDuration::from_seconds(offset),
+                    last_update: NtpTimestamp::from_seconds_nanos_since_ntp_era(time, 0),
+                    ..Default::default()
+                },
+            );
+            ObservableState {
+                program: ProgramData::default(),
+                system: SystemSnapshot::default(),
+                bloom_filter_population: 0,
+                sources: vec![source.clone()],
+                servers: vec![],
+                nts_ke_servers: vec![],
+                clock_history: vec![],
+                source_histograms: histograms.to_vec(),
+            }
+        };
+        let states = [measure(1, 0.0005), {
+            measure(2, 0.005);
+            measure(3, 0.05)
+        }];
+
+        let permissions: std::fs::Permissions = PermissionsExt::from_mode(0o600);
+        let observation_listener =
+            create_unix_socket_with_permissions(&observation_path, permissions.clone()).unwrap();
+        let observer = tokio::spawn(async move {
+            for state in states {
+                let (mut stream, _) = observation_listener.accept().await.unwrap();
+                write_json(&mut stream, &state).await.unwrap();
+            }
+        });
+
+        let listener = Listener::Unix(
+            create_unix_socket_with_permissions(&metrics_path, permissions).unwrap(),
+        );
+        let exporter = tokio::spawn(serve(listener, Arc::new(observation_path)));
+
+        let scrape = || async {
+            let mut stream = UnixStream::connect(&metrics_path).await.unwrap();
+            stream
+                .write_all(b"GET /metrics HTTP/1.1\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+        let labels = format!(r#"name="example.com",address="192.0.2.1:123",id="{id}""#);
+
+        let response = scrape().await;
+        assert!(response.contains(&format!(
+            "ntp_source_offset_histogram_seconds_bucket{{{labels},le=\"0.001\"}} 1\n"
+        )));
+        assert!(response.contains(&format!(
+            "ntp_source_offset_histogram_seconds_count{{{labels}}} 1\n"
+        )));
+        assert!(response.contains(&format!(
+            "ntp_source_jitter_histogram_seconds_count{{{labels}}} 0\n"
+        )));
+
+        let response = scrape().await;
+        for (bound, count) in [("0.001", 1), ("0.01", 2), ("+Inf", 3)] {
+            assert!(response.contains(&format!(
+                "ntp_source_offset_histogram_seconds_bucket{{{labels},le=\"{bound}\"}} {count}\n"
+            )));
+        }
+        for (bound, count) in [("0.001", 0), ("0.01", 1), ("+Inf", 2)] {
+            assert!(response.contains(&format!(
+                "ntp_source_jitter_histogram_seconds_bucket{{{labels},le=\"{bound}\"}} {count}\n"
+            )));
+        }
+
+        observer.await.unwrap();
+        exporter.abort();
+    }
 }
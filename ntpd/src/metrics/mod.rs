@@ -1,8 +1,8 @@
 pub mod exporter;
 
-use ntp_proto::{NtpDuration, PollIntervalLimits};
+use ntp_proto::{ClockState, NtpDuration, NtpLeapIndicator, PollIntervalLimits};
 
-use crate::daemon::ObservableState;
+use crate::daemon::{ObservableState, observer::Histogram};
 
 struct Measurement<T> {
     labels: Vec<(&'static str, String)>,
@@ -32,6 +32,7 @@ impl Unit {
 enum MetricType {
     Gauge,
     Counter,
+    Histogram,
 }
 
 impl MetricType {
@@ -39,6 +40,7 @@ impl MetricType {
         match self {
             MetricType::Gauge => "gauge",
             MetricType::Counter => "counter",
+            MetricType::Histogram => "histogram",
         }
     }
 }
@@ -57,6 +59,55 @@ fn format_metric<T: std::fmt::Display>(
         name.to_owned()
     };
 
+    write_header(w, &name, help, metric_type, unit)?;
+
+    // write all the measurements
+    for measurement in measurements {
+        write_sample(w, &name, &measurement.labels, &measurement.value)?;
+    }
+
+    Ok(())
+}
+
+fn format_histogram(
+    w: &mut impl std::fmt::Write,
+    name: &str,
+    help: &str,
+    unit: Unit,
+    measurements: Vec<Measurement<&Histogram>>,
+) -> std::fmt::Result {
+    let name = format!("{}_{}", name, unit.as_str());
+
+    write_header(w, &name, help, &MetricType::Histogram, Some(unit))?;
+
+    let bucket_name = format!("{name}_bucket");
+    let sum_name = format!("{name}_sum");
+    let count_name = format!("{name}_count");
+    for measurement in measurements {
+        let histogram = measurement.value;
+        let mut labels = measurement.labels;
+        for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+            labels.push(("le", bound.to_string()));
+            write_sample(w, &bucket_name, &labels, count)?;
+            labels.pop();
+        }
+        labels.push(("le", "+Inf".to_owned()));
+        write_sample(w, &bucket_name, &labels, &histogram.count)?;
+        labels.pop();
+        write_sample(w, &sum_name, &labels, &histogram.sum)?;
+        write_sample(w, &count_name, &labels, &histogram.count)?;
+    }
+
+    Ok(())
+}
+
+fn write_header(
+    w: &mut impl std::fmt::Write,
+    name: &str,
+    help: &str,
+    metric_type: &MetricType,
+    unit: Option<Unit>,
+) -> std::fmt::Result {
     // write help text
     writeln!(w, "# HELP {name} {help}.")?;
 
@@ -68,32 +119,57 @@ fn format_metric<T: std::fmt::Display>(
         writeln!(w, "# UNIT {name} {}", unit.as_str())?;
     }
 
-    // write all the measurements
-    for measurement in measurements {
-        w.write_str(&name)?;
-        if !measurement.labels.is_empty() {
-            w.write_str("{")?;
-
-            for (offset, (label, value)) in measurement.labels.iter().enumerate() {
-                let value = value
-                    .replace('\\', "\\\\")
-                    .replace('"', "\\\"")
-                    .replace('\n', "\\n");
-                write!(w, "{label}=\"{value}\"")?;
-                if offset < measurement.labels.len() - 1 {
-                    w.write_str(",")?;
-                }
+    Ok(())
+}
+
+fn write_sample(
+    w: &mut impl std::fmt::Write,
+    name: &str,
+    labels: &[(&'static str, String)],
+    value: &impl std::fmt::Display,
+) -> std::fmt::Result {
+    w.write_str(name)?;
+    if !labels.is_empty() {
+        w.write_str("{")?;
+
+        for (offset, (label, value)) in labels.iter().enumerate() {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            write!(w, "{label}=\"{value}\"")?;
+            if offset < labels.len() - 1 {
+                w.write_str(",")?;
             }
-            w.write_str("}")?;
         }
-        w.write_str(" ")?;
-        write!(w, "{}", measurement.value)?;
-        w.write_str("\n")?;
+        w.write_str("}")?;
     }
+    w.write_str(" ")?;
+    write!(w, "{value}")?;
+    w.write_str("\n")?;
 
     Ok(())
 }
 
+fn leap_pending(leap_indicator: NtpLeapIndicator) -> i64 {
+    match leap_indicator {
+        NtpLeapIndicator::Leap61 => 1,
+        NtpLeapIndicator::Leap59 => -1,
+        NtpLeapIndicator::NoWarning
+        | NtpLeapIndicator::Unknown
+        | NtpLeapIndicator::Unsynchronized => 0,
+    }
+}
+
+fn clock_state(state: ClockState) -> i64 {
+    match state {
+        ClockState::Unsynchronized => 0,
+        ClockState::Steering => 1,
+        ClockState::Synchronized => 2,
+        ClockState::PanicHold => 3,
+    }
+}
+
 macro_rules! collect_sources {
     ($from: expr, |$ident: ident| $value: expr $(,)?) => {{
         let mut data = vec![];
@@ -127,6 +203,25 @@ macro_rules! collect_some_sources {
     }};
 }
 
+macro_rules! collect_source_histograms {
+    ($from: expr, |$ident: ident| $value: expr $(,)?) => {{
+        let mut data = vec![];
+        for $ident in &$from.source_histograms {
+            // Skip histograms of sources that have since been removed
+            if let Some(source) = $from.sources.iter().find(|s| s.id == $ident.id) {
+                let labels = vec![
+                    ("name", source.name.clone()),
+                    ("address", source.address.clone()),
+                    ("id", format!("{}", source.id)),
+                ];
+                let value = $value;
+                data.push(Measurement { labels, value });
+            }
+        }
+        data
+    }};
+}
+
 macro_rules! collect_servers {
     ($from: expr, |$ident: ident| $value: expr $(,)?) => {{
         let mut data = vec![];
@@ -139,6 +234,18 @@ macro_rules! collect_servers {
     }};
 }
 
+macro_rules! collect_nts_ke_servers {
+    ($from: expr, |$ident: ident| $value: expr $(,)?) => {{
+        let mut data = vec![];
+        for $ident in &$from.nts_ke_servers {
+            let labels = vec![("listen_address", format!("{}", $ident.address))];
+            let value = $value;
+            data.push(Measurement { labels, value })
+        }
+        data
+    }};
+}
+
 // Allow this function to be oversized as it is otherwise straightforward
 // and has no reasonable way to be split.
 #[expect(clippy::too_many_lines)]
@@ -210,6 +317,24 @@ pub fn format_state(w: &mut impl std::fmt::Write, state: &ObservableState) -> st
         Measurement::simple(state.system.time_snapshot.leap_indicator as i64),
     )?;
 
+    format_metric(
+        w,
+        "ntp_system_leap_pending",
+        "Leap second announced by the selected sources (1 for an inserted second, -1 for a deleted second, 0 if none)",
+        &MetricType::Gauge,
+        None,
+        Measurement::simple(leap_pending(state.system.time_snapshot.leap_indicator)),
+    )?;
+
+    format_metric(
+        w,
+        "ntpd_clock_state",
+        "State of the clock controller (0 unsynchronized, 1 steering, 2 synchronized, 3 holding corrections after exceeding the panic threshold)",
+        &MetricType::Gauge,
+        None,
+        Measurement::simple(clock_state(state.system.time_snapshot.clock_state)),
+    )?;
+
     format_metric(
         w,
         "ntp_system_root_delay",
@@ -243,6 +368,15 @@ pub fn format_state(w: &mut impl std::fmt::Write, state: &ObservableState) -> st
         Measurement::simple(state.system.ntp_snapshot.stratum),
     )?;
 
+    format_metric(
+        w,
+        "ntp_system_bloom_filter_population",
+        "Number of bits set in the NTPv5 loop detection bloom filter",
+        &MetricType::Gauge,
+        None,
+        Measurement::simple(state.bloom_filter_population),
+    )?;
+
     format_metric(
         w,
         "ntp_source_poll_interval",
@@ -315,6 +449,22 @@ pub fn format_state(w: &mut impl std::fmt::Write, state: &ObservableState) -> st
         collect_sources!(state, |p| p.timedata.remote_uncertainty.to_seconds()),
     )?;
 
+    format_histogram(
+        w,
+        "ntp_source_offset_histogram",
+        "Distribution of the absolute offset between the upstream source and system time",
+        Unit::Seconds,
+        collect_source_histograms!(state, |h| &h.offset),
+    )?;
+
+    format_histogram(
+        w,
+        "ntp_source_jitter_histogram",
+        "Distribution of the change in offset between consecutive measurements of the source",
+        Unit::Seconds,
+        collect_source_histograms!(state, |h| &h.jitter),
+    )?;
+
     format_metric(
         w,
         "ntp_server_received_packets_total",
@@ -414,6 +564,185 @@ pub fn format_state(w: &mut impl std::fmt::Write, state: &ObservableState) -> st
         collect_servers!(state, |s| s.stats.nts_nak_packets.get()),
     )?;
 
+    let mut parse_errors = vec![];
+    for server in &state.servers {
+        for (category, counter) in server.stats.parse_errors.by_category() {
+            parse_errors.push(Measurement {
+                labels: vec![
+                    ("listen_address", format!("{}", server.address)),
+                    ("category", category.to_owned()),
+                ],
+                value: counter.get(),
+            });
+        }
+    }
+    format_metric(
+        w,
+        "ntp_server_parse_errors_total",
+        "Number of incoming packets that could not be parsed, per category of error",
+        &MetricType::Counter,
+        None,
+        parse_errors,
+    )?;
+
+    format_metric(
+        w,
+        "ntp_nts_ke_server_rejected_connections_total",
+        "Number of NTS-KE connections rejected because too many were in flight",
+        &MetricType::Counter,
+        None,
+        collect_nts_ke_servers!(state, |s| s.stats.rejected_connections.get()),
+    )?;
+
     w.write_str("# EOF\n")?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use ntp_proto::{
+        ClockId, NtpTimestamp, ObservableSourceState, ObservableSourceTimedata, ServerStatHandler,
+        SystemSnapshot,
+    };
+
+    use crate::daemon::observer::{ObservableServerState, ProgramData};
+
+    use super::*;
+
+    fn state_with_leap(leap_indicator: NtpLeapIndicator) -> ObservableState {
+        let mut system = SystemSnapshot::default();
+        system.time_snapshot.leap_indicator = leap_indicator;
+        ObservableState {
+            program: ProgramData::default(),
+            system,
+            bloom_filter_population: 0,
+            sources: vec![],
+            servers: vec![],
+            nts_ke_servers: vec![],
+            clock_history: vec![],
+            source_histograms: vec![],
+        }
+    }
+
+    fn leap_pending_metric(state: &ObservableState) -> String {
+        let mut output = String::new();
+        format_state(&mut output, state).unwrap();
+        output
+            .lines()
+            .find(|line| line.starts_with("ntp_system_leap_pending "))
+            .unwrap()
+            .to_owned()
+    }
+
+    #[test]
+    fn test_leap_pending() {
+        let state = state_with_leap(NtpLeapIndicator::Leap61);
+        assert_eq!(leap_pending_metric(&state), "ntp_system_leap_pending 1");
+
+        let state = state_with_leap(NtpLeapIndicator::Leap59);
+        assert_eq!(leap_pending_metric(&state), "ntp_system_leap_pending -1");
+
+        // Once the leap second has passed, sources stop announcing it
+        let state = state_with_leap(NtpLeapIndicator::NoWarning);
+        assert_eq!(leap_pending_metric(&state), "ntp_system_leap_pending 0");
+    }
+
+    #[test]
+    fn test_bloom_filter_population() {
+        let mut state = state_with_leap(NtpLeapIndicator::NoWarning);
+        state.bloom_filter_population = 42;
+        let mut output = String::new();
+        format_state(&mut output, &state).unwrap();
+        assert!(
+            output
+                .lines()
+                .any(|line| line == "ntp_system_bloom_filter_population 42")
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let mut state = state_with_leap(NtpLeapIndicator::NoWarning);
+        state.servers.push(ObservableServerState {
+            address: "192.0.2.1:123".parse().unwrap(),
+            stats: Default::default(),
+        });
+        // The server shares its counters with the observed state
+        let mut handler = state.servers[0].stats.clone();
+        handler.register_parse_error("too_short");
+        handler.register_parse_error("too_short");
+        handler.register_parse_error("unknown_mode");
+        let mut output = String::new();
+        format_state(&mut output, &state).unwrap();
+        let metric = |category| {
+            output
+                .lines()
+                .find(|line| {
+                    line.starts_with(&format!(
+                        "ntp_server_parse_errors_total{{listen_address=\"192.0.2.1:123\",category=\"{category}\"}}"
+                    ))
+                })
+                .unwrap()
+                .rsplit(' ')
+                .next()
+                .unwrap()
+                .to_owned()
+        };
+        assert_eq!(metric("too_short"), "2");
+        assert_eq!(metric("unknown_mode"), "1");
+        assert_eq!(metric("bad_extension_length"), "0");
+    }
+
+    #[test]
+    fn test_nts_cookies() {
+        let metric = |nts_cookies| {
+            let mut state = state_with_leap(NtpLeapIndicator::NoWarning);
+            state.sources.push(ObservableSourceState {
+                timedata: ObservableSourceTimedata::default(),
+                unanswered_polls: 0,
+                poll_interval: PollIntervalLimits::default().min,
+                nts_cookies,
+                last_poll_sent: NtpTimestamp::default(),
+                last_response_received: NtpTimestamp::default(),
+                timescale: None,
+                captured_packets: vec![],
+                last_round_trip_delay: None,
+                last_precision: None,
+                name: "example.com".into(),
+                address: "192.0.2.1:123".into(),
+                id: ClockId::new(),
+            });
+            let mut output = String::new();
+            format_state(&mut output, &state).unwrap();
+            output
+                .lines()
+                .find(|line| line.starts_with("ntp_source_nts_cookies_available{"))
+                .map(|line| line.rsplit_once(' ').unwrap().1.to_owned())
+        };
+
+        assert_eq!(metric(Some(8)).as_deref(), Some("8"));
+        assert_eq!(metric(Some(0)).as_deref(), Some("0"));
+        // Sources without NTS have no cookie count
+        assert_eq!(metric(None), None);
+    }
+
+    #[test]
+    fn test_clock_state() {
+        let metric = |clock_state| {
+            let mut state = state_with_leap(NtpLeapIndicator::NoWarning);
+            state.system.time_snapshot.clock_state = clock_state;
+            let mut output = String::new();
+            format_state(&mut output, &state).unwrap();
+            output
+                .lines()
+                .find(|line| line.starts_with("ntpd_clock_state "))
+                .unwrap()
+                .to_owned()
+        };
+
+        assert_eq!(metric(ClockState::Unsynchronized), "ntpd_clock_state 0");
+        assert_eq!(metric(ClockState::Steering), "ntpd_clock_state 1");
+        assert_eq!(metric(ClockState::Synchronized), "ntpd_clock_state 2");
+        assert_eq!(metric(ClockState::PanicHold), "ntpd_clock_state 3");
+    }
+}
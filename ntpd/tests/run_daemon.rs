@@ -0,0 +1,35 @@
+#![allow(missing_docs)]
+
+use std::time::Duration;
+
+const CARGO_TARGET_TMPDIR: &str = env!("CARGO_TARGET_TMPDIR");
+
+#[tokio::test]
+async fn test_run_daemon_until_shutdown() {
+    let observation_path = format!("{CARGO_TARGET_TMPDIR}/run_daemon_test_socket");
+    let _ = std::fs::remove_file(&observation_path);
+
+    // Without any sources the daemon does not need to steer the clock
+    let mut config = ntpd::Config::default();
+    config.observability.observation_path = Some(observation_path.clone().into());
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let daemon = ntpd::run_daemon(config, async {
+        let _ = shutdown_rx.await;
+    });
+
+    let stop = async {
+        // The observer creates its socket once the daemon is up and running
+        while !std::path::Path::new(&observation_path).exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        shutdown_tx.send(()).unwrap();
+    };
+
+    let (result, ()) = tokio::time::timeout(Duration::from_secs(10), async {
+        tokio::join!(daemon, stop)
+    })
+    .await
+    .expect("daemon did not start and stop in time");
+    assert!(result.is_ok());
+}